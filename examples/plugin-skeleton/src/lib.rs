@@ -0,0 +1,314 @@
+//! A plugin model for the pendulum: a van der Pol oscillator, a bob on a
+//! spring whose damping pumps energy in at small swings and takes it out at
+//! large ones, so any start settles onto the same bounce. Up and down change
+//! how strongly, `R` starts it again from rest.
+//!
+//! The ABI definitions are copied from the pendulum's `src/plugin.rs`, which
+//! says what a plugin may and may not do; keep them in step with it.
+
+use std::ffi::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+// ---- The ABI, as in `src/plugin.rs` ----
+
+const ABI_VERSION: u32 = 1;
+const KEY_UP: u32 = 0x102;
+const KEY_DOWN: u32 = 0x103;
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct PluginState {
+    pub time: f64,
+    pub pivot: Point,
+    pub bob: Point,
+    pub grabbed: u32,
+    pub reach: f64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+#[repr(C)]
+pub struct DrawSink {
+    pub context: *mut c_void,
+    pub line: unsafe extern "C" fn(*mut c_void, Point, Point, f64, Rgba),
+    pub circle: unsafe extern "C" fn(*mut c_void, Point, f64, Rgba),
+    pub text: unsafe extern "C" fn(*mut c_void, Point, f64, Rgba, *const c_char),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PluginVtable {
+    pub abi_version: u32,
+    pub size: u32,
+    pub name: *const c_char,
+    pub init: unsafe extern "C" fn(*mut PluginState) -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(*mut c_void),
+    pub step: unsafe extern "C" fn(*mut c_void, f64, *mut PluginState) -> i32,
+    pub draw: unsafe extern "C" fn(*const c_void, *const PluginState, *const DrawSink) -> i32,
+    pub key: unsafe extern "C" fn(*mut c_void, u32, *mut u32) -> i32,
+    pub serialize: unsafe extern "C" fn(*const c_void, *mut u8, usize, *mut usize) -> i32,
+    pub deserialize: unsafe extern "C" fn(*mut c_void, *mut PluginState, *const u8, usize) -> i32,
+}
+
+// The vtable only holds a pointer to a static string.
+unsafe impl Sync for PluginVtable {}
+
+// ---- The oscillator ----
+
+/// Length of the spring at rest, and how far one unit of displacement
+/// stretches it, in world units.
+const REST_LENGTH: f64 = 180.0;
+const SCALE: f64 = 50.0;
+/// Natural angular frequency, radians a second.
+const OMEGA: f64 = 3.0;
+const MU_STEP: f64 = 0.25;
+const MAX_MU: f64 = 5.0;
+const BOB_RADIUS: f64 = 10.0;
+
+struct Oscillator {
+    /// Stretch from the rest length, in units of `SCALE`.
+    displacement: f64,
+    velocity: f64,
+    mu: f64,
+}
+
+impl Oscillator {
+    fn update(&self, state: &mut PluginState) {
+        state.bob = Point {
+            x: state.pivot.x,
+            y: state.pivot.y + REST_LENGTH + SCALE * self.displacement,
+        };
+        // The limit cycle reaches a displacement of about 2.
+        state.reach = REST_LENGTH + SCALE * 2.5 + BOB_RADIUS;
+    }
+
+    fn acceleration(&self, displacement: f64, velocity: f64) -> f64 {
+        OMEGA * self.mu * (1.0 - displacement * displacement) * velocity
+            - OMEGA * OMEGA * displacement
+    }
+
+    /// One RK4 step of `dt` seconds.
+    fn step(&mut self, dt: f64) {
+        let (x, v) = (self.displacement, self.velocity);
+        let k1 = (v, self.acceleration(x, v));
+        let k2 = {
+            let (x, v) = (x + k1.0 * dt / 2.0, v + k1.1 * dt / 2.0);
+            (v, self.acceleration(x, v))
+        };
+        let k3 = {
+            let (x, v) = (x + k2.0 * dt / 2.0, v + k2.1 * dt / 2.0);
+            (v, self.acceleration(x, v))
+        };
+        let k4 = {
+            let (x, v) = (x + k3.0 * dt, v + k3.1 * dt);
+            (v, self.acceleration(x, v))
+        };
+        self.displacement += dt / 6.0 * (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0);
+        self.velocity += dt / 6.0 * (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1);
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        [self.displacement, self.velocity, self.mu]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Oscillator> {
+        if bytes.len() != 24 {
+            return None;
+        }
+        let value = |i: usize| f64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().unwrap());
+        Some(Oscillator {
+            displacement: value(0),
+            velocity: value(1),
+            mu: value(2),
+        })
+    }
+}
+
+// ---- The entry points ----
+
+/// Runs `body`, turning a panic into a non-zero status so it never unwinds
+/// into the host.
+fn guard(body: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(1)
+}
+
+unsafe extern "C" fn init(state: *mut PluginState) -> *mut c_void {
+    panic::catch_unwind(|| {
+        let oscillator = Oscillator {
+            displacement: 1.0,
+            velocity: 0.0,
+            mu: 1.0,
+        };
+        oscillator.update(unsafe { &mut *state });
+        Box::into_raw(Box::new(oscillator)) as *mut c_void
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+unsafe extern "C" fn destroy(instance: *mut c_void) {
+    let _ = panic::catch_unwind(|| drop(unsafe { Box::from_raw(instance as *mut Oscillator) }));
+}
+
+unsafe extern "C" fn step(instance: *mut c_void, dt: f64, state: *mut PluginState) -> i32 {
+    guard(|| {
+        let (oscillator, state) = unsafe { (&mut *(instance as *mut Oscillator), &mut *state) };
+        if state.grabbed != 0 {
+            // Follow the mouse, keeping how fast it moved for the release.
+            let displacement = (state.bob.y - state.pivot.y - REST_LENGTH) / SCALE;
+            oscillator.velocity = (displacement - oscillator.displacement) / dt;
+            oscillator.displacement = displacement;
+            state.bob.x = state.pivot.x;
+            return 0;
+        }
+        oscillator.step(dt);
+        if !oscillator.displacement.is_finite() {
+            return 2;
+        }
+        oscillator.update(state);
+        0
+    })
+}
+
+unsafe extern "C" fn draw(
+    instance: *const c_void,
+    state: *const PluginState,
+    sink: *const DrawSink,
+) -> i32 {
+    guard(|| {
+        let (oscillator, state, sink) =
+            unsafe { (&*(instance as *const Oscillator), &*state, &*sink) };
+        let grey = Rgba {
+            r: 0.4,
+            g: 0.4,
+            b: 0.4,
+            a: 1.0,
+        };
+        let blue = Rgba {
+            r: 0.2,
+            g: 0.4,
+            b: 0.8,
+            a: 1.0,
+        };
+
+        // The spring as a zigzag from the pivot to the bob.
+        let coils = 12;
+        let length = state.bob.y - state.pivot.y;
+        let mut last = state.pivot;
+        for i in 1..=coils * 2 {
+            let side = match i {
+                i if i == coils * 2 => 0.0,
+                i if i % 2 == 0 => -8.0,
+                _ => 8.0,
+            };
+            let next = Point {
+                x: state.pivot.x + side,
+                y: state.pivot.y + length * i as f64 / (coils * 2) as f64,
+            };
+            unsafe { (sink.line)(sink.context, last, next, 2.0, grey) };
+            last = next;
+        }
+        unsafe { (sink.circle)(sink.context, state.bob, BOB_RADIUS, blue) };
+
+        let label = format!("van der Pol, mu {:.2}\0", oscillator.mu);
+        let at = Point {
+            x: state.bob.x + 2.0 * BOB_RADIUS,
+            y: state.bob.y - BOB_RADIUS,
+        };
+        unsafe { (sink.text)(sink.context, at, 20.0, grey, label.as_ptr() as *const c_char) };
+        0
+    })
+}
+
+unsafe extern "C" fn key(instance: *mut c_void, key: u32, handled: *mut u32) -> i32 {
+    guard(|| {
+        let oscillator = unsafe { &mut *(instance as *mut Oscillator) };
+        let taken = match key {
+            KEY_UP => {
+                oscillator.mu = (oscillator.mu + MU_STEP).min(MAX_MU);
+                true
+            }
+            KEY_DOWN => {
+                oscillator.mu = (oscillator.mu - MU_STEP).max(0.0);
+                true
+            }
+            k if k == b'R' as u32 => {
+                oscillator.displacement = 1.0;
+                oscillator.velocity = 0.0;
+                true
+            }
+            _ => false,
+        };
+        unsafe { *handled = taken as u32 };
+        0
+    })
+}
+
+unsafe extern "C" fn serialize(
+    instance: *const c_void,
+    buffer: *mut u8,
+    capacity: usize,
+    length: *mut usize,
+) -> i32 {
+    guard(|| {
+        let bytes = unsafe { &*(instance as *const Oscillator) }.to_bytes();
+        unsafe { *length = bytes.len() };
+        if capacity >= bytes.len() {
+            unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, bytes.len()) };
+        }
+        0
+    })
+}
+
+unsafe extern "C" fn deserialize(
+    instance: *mut c_void,
+    state: *mut PluginState,
+    data: *const u8,
+    length: usize,
+) -> i32 {
+    guard(|| {
+        let bytes = unsafe { std::slice::from_raw_parts(data, length) };
+        let Some(restored) = Oscillator::from_bytes(bytes) else {
+            return 3;
+        };
+        let oscillator = unsafe { &mut *(instance as *mut Oscillator) };
+        *oscillator = restored;
+        oscillator.update(unsafe { &mut *state });
+        0
+    })
+}
+
+static VTABLE: PluginVtable = PluginVtable {
+    abi_version: ABI_VERSION,
+    size: std::mem::size_of::<PluginVtable>() as u32,
+    name: c"oscillator".as_ptr(),
+    init,
+    destroy,
+    step,
+    draw,
+    key,
+    serialize,
+    deserialize,
+};
+
+#[no_mangle]
+pub extern "C" fn pendulum_plugin_entry() -> *const PluginVtable {
+    &VTABLE
+}
@@ -0,0 +1,310 @@
+//! Accuracy dashboard: every registered integrator run for a few seconds
+//! from the selected pendulum's state, next to a reference run of RK4 at a
+//! small fraction of the step, with the worst angle error, the energy drift
+//! and the cost per step tabulated. The runs are spread over frames so the
+//! window keeps drawing.
+
+use std::time::{Duration, Instant};
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::budget::Budget;
+use crate::integrator::{Derivatives, Integrator, Integrators, Rk4, State};
+use crate::math;
+use crate::pendulum::{DampingModel, Pendulum};
+use crate::STEPS_PER_SECOND;
+
+/// Simulated seconds each scheme is run for.
+const RUN_SECONDS: f32 = 5.0;
+/// Reference substeps per step. Any more and single-precision rounding
+/// outgrows what the smaller step saves.
+const REFERENCE_SUBSTEPS: usize = 8;
+
+/// Left, top and width of the table, which is as tall as it has rows.
+const RECT: (f32, f32, f32) = (110.0, 110.0, 580.0);
+const ROW_HEIGHT: f32 = 22.0;
+const COLUMNS: [(&str, f32); 6] = [
+    ("Scheme", 8.0),
+    ("Order", 150.0),
+    ("Max angle error", 210.0),
+    ("Energy drift", 340.0),
+    ("Evals/step", 440.0),
+    ("us/step", 520.0),
+];
+
+/// The continuous equation the selected pendulum approximates: gravity over
+/// its effective length, its viscous damping, the motor and its drive. Dry
+/// friction isn't smooth enough to compare schemes on, so it's left out.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Equation {
+    g: f32,
+    gravity_gradient: f32,
+    length: f32,
+    damping: f32,
+    motor_torque: f32,
+    drive_amplitude: f32,
+    drive_frequency: f32,
+    /// Drive phase at step zero.
+    drive_offset: f32,
+}
+
+impl Equation {
+    pub fn of(p: &Pendulum) -> Equation {
+        Equation {
+            g: p.g,
+            gravity_gradient: p.gravity_gradient,
+            length: p.effective_length(),
+            damping: match p.damping_model {
+                DampingModel::Viscous => p.damping_coefficient(),
+                DampingModel::Coulomb => 0.0,
+            },
+            motor_torque: p.motor_torque,
+            drive_amplitude: p.drive_amplitude,
+            drive_frequency: p.drive_frequency,
+            drive_offset: p.drive_phase(0.0),
+        }
+    }
+
+    /// The angular acceleration at step `time`, worked in double precision
+    /// throughout for a run that outdoes the single-precision step.
+    pub fn acceleration(&self, angle: f64, angular_velocity: f64, time: f64) -> f64 {
+        let length = self.length as f64;
+        let gravity =
+            self.g as f64 * (1.0 + self.gravity_gradient as f64 * length * math::cos(angle));
+        -gravity / length * math::sin(angle) - self.damping as f64 * angular_velocity
+            + self.motor_torque as f64
+            + self.drive_amplitude as f64
+                * math::cos(self.drive_frequency as f64 * time + self.drive_offset as f64)
+    }
+
+    fn derivatives(&self, state: &State) -> Derivatives {
+        Derivatives {
+            angle: state.angular_velocity,
+            angular_velocity: -self.gravity(state.angle) / self.length * state.angle.sin()
+                - self.damping * state.angular_velocity
+                + self.motor_torque
+                + self.drive_amplitude
+                    * (self.drive_frequency * state.time + self.drive_offset).cos(),
+        }
+    }
+
+    /// Gravity at the bob, as in `Pendulum::gravity_at_bob`.
+    fn gravity(&self, angle: f32) -> f32 {
+        self.g * (1.0 + self.gravity_gradient * self.length * angle.cos())
+    }
+
+    /// Energy per unit mass, as in `energy::energy`.
+    fn energy(&self, state: &State) -> f32 {
+        let cos = state.angle.cos();
+        0.5 * (self.length * state.angular_velocity).powi(2)
+            + self.g * self.length * (1.0 - cos)
+            + 0.5 * self.g * self.gravity_gradient * self.length.powi(2) * (1.0 - cos * cos)
+    }
+}
+
+struct Row {
+    name: String,
+    order: u32,
+    max_error: f32,
+    /// Largest energy difference from the reference, as a fraction of the
+    /// starting energy.
+    energy_drift: f32,
+    evaluations: usize,
+    elapsed: Duration,
+}
+
+struct Run {
+    equation: Equation,
+    start: State,
+    steps: usize,
+    /// Reference state at the end of each step.
+    reference: Vec<State>,
+    reference_state: State,
+    /// Scheme being run, and its state and row so far.
+    scheme: usize,
+    state: State,
+    taken: usize,
+    rows: Vec<Row>,
+}
+
+impl Run {
+    fn new(p: &Pendulum) -> Run {
+        let start = State {
+            angle: p.angle,
+            angular_velocity: p.angular_velocity,
+            time: p.time,
+        };
+        let steps = (RUN_SECONDS * STEPS_PER_SECOND) as usize;
+        Run {
+            equation: Equation::of(p),
+            start,
+            steps,
+            reference: Vec::with_capacity(steps),
+            reference_state: start,
+            scheme: 0,
+            state: start,
+            taken: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    fn is_done(&self, integrators: &Integrators) -> bool {
+        self.scheme >= integrators.schemes.len()
+    }
+
+    /// Fraction of the work done, the reference counting as one scheme.
+    fn progress(&self, integrators: &Integrators) -> f32 {
+        let parts = integrators.schemes.len() + 1;
+        let done = if self.reference.len() < self.steps {
+            self.reference.len() as f32 / self.steps as f32
+        } else {
+            1.0 + self.scheme as f32 + self.taken as f32 / self.steps as f32
+        };
+        done / parts as f32
+    }
+
+    /// Carries on with the reference, then the schemes in turn, until this
+    /// frame's budget of evaluations is spent.
+    fn work(&mut self, integrators: &Integrators, budget: &Budget) {
+        let equation = self.equation;
+        let dynamics = |state: &State| {
+            budget.charge(1);
+            equation.derivatives(state)
+        };
+
+        while !budget.is_spent() && !self.is_done(integrators) {
+            if self.reference.len() < self.steps {
+                let dt = 1.0 / REFERENCE_SUBSTEPS as f32;
+                for _ in 0..REFERENCE_SUBSTEPS {
+                    self.reference_state = Rk4.step(&self.reference_state, dt, &dynamics);
+                }
+                // Pin the clock to whole steps so it doesn't drift from the
+                // schemes' with rounding.
+                self.reference_state.time = self.start.time + (self.reference.len() + 1) as f32;
+                self.reference.push(self.reference_state);
+                continue;
+            }
+
+            let scheme = &integrators.schemes[self.scheme];
+            if self.taken == 0 {
+                self.rows.push(Row {
+                    name: scheme.name().to_string(),
+                    order: scheme.order(),
+                    max_error: 0.0,
+                    energy_drift: 0.0,
+                    evaluations: 0,
+                    elapsed: Duration::ZERO,
+                });
+            }
+
+            let before = budget.spent();
+            let started = Instant::now();
+            self.state = scheme.step(&self.state, 1.0, &dynamics);
+            let elapsed = started.elapsed();
+
+            let reference = self.reference[self.taken];
+            let energy = self.equation.energy(&self.start).max(f32::MIN_POSITIVE);
+            let row = self.rows.last_mut().unwrap();
+            row.evaluations += budget.spent() - before;
+            row.elapsed += elapsed;
+            row.max_error = row
+                .max_error
+                .max((self.state.angle - reference.angle).abs());
+            row.energy_drift = row.energy_drift.max(
+                (self.equation.energy(&self.state) - self.equation.energy(&reference)).abs()
+                    / energy,
+            );
+
+            self.taken += 1;
+            if self.taken == self.steps {
+                self.scheme += 1;
+                self.taken = 0;
+                self.state = self.start;
+            }
+        }
+    }
+}
+
+pub struct AccuracyDashboard {
+    pub visible: bool,
+    run: Option<Run>,
+}
+
+impl AccuracyDashboard {
+    pub fn new() -> AccuracyDashboard {
+        AccuracyDashboard {
+            visible: false,
+            run: None,
+        }
+    }
+
+    /// Opens the dashboard on a fresh run from `p`, or closes it.
+    pub fn toggle(&mut self, p: Option<&Pendulum>) {
+        self.visible = !self.visible && p.is_some();
+        self.run = p.filter(|_| self.visible).map(Run::new);
+    }
+
+    pub fn step(&mut self, integrators: &Integrators, budget: &Budget) {
+        if let Some(run) = &mut self.run {
+            run.work(integrators, budget);
+        }
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, integrators: &Integrators) {
+        let (true, Some(run)) = (self.visible, &self.run) else {
+            return;
+        };
+
+        let (left, top, width) = RECT;
+        let rows = 2 + integrators.schemes.len();
+        let height = 8.0 + ROW_HEIGHT * rows as f32;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.9),
+        );
+        let text = |graphics: &mut Graphics2D, x: f32, row: usize, text: &str| {
+            graphics.draw_text(
+                (left + x, top + 4.0 + ROW_HEIGHT * row as f32),
+                Color::BLACK,
+                &font.layout_text(text, 18.0, TextOptions::new()),
+            );
+        };
+
+        let status = if run.is_done(integrators) {
+            format!(
+                "{:.0} s from the current state against RK4 at 1/{} step",
+                RUN_SECONDS, REFERENCE_SUBSTEPS
+            )
+        } else {
+            format!("Running: {:.0}%", 100.0 * run.progress(integrators))
+        };
+        text(graphics, COLUMNS[0].1, 0, &status);
+        for (heading, x) in COLUMNS {
+            text(graphics, x, 1, heading);
+        }
+
+        for (i, row) in run.rows.iter().enumerate() {
+            // The row being run shows its figures so far.
+            let steps = if i == run.scheme {
+                run.taken
+            } else {
+                run.steps
+            }
+            .max(1);
+            let cells = [
+                row.name.clone(),
+                format!("{}", row.order),
+                format!("{:.2e} rad", row.max_error),
+                format!("{:.2e}", row.energy_drift),
+                format!("{:.1}", row.evaluations as f32 / steps as f32),
+                format!("{:.2}", row.elapsed.as_secs_f32() * 1e6 / steps as f32),
+            ];
+            for (cell, (_, x)) in cells.iter().zip(COLUMNS) {
+                text(graphics, x, i + 2, cell);
+            }
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Red and cyan anaglyph drawing of the scene, for paper 3D glasses. The
+//! world is drawn once for each eye, each one shifted sideways by how far
+//! behind or in front of the screen a layer stands and kept to its eye's
+//! colours: the left eye's picture in the red channel, the right eye's in
+//! green and blue, with everything in grey. The HUD and panels are drawn
+//! once, flat, on top.
+//!
+//! The scene is flat, so the depth is given by layer: the backdrop stands
+//! behind the swing, on the screen unless the convergence says otherwise,
+//! and the models of a scene of several are spread through a shallow depth
+//! from the first, furthest back, to the last, nearest.
+//!
+//! The window mixes colours by opacity alone, so where the two pictures
+//! overlap the right eye's can't simply be added to the left's. It's laid
+//! over at half strength instead, which keeps a little of the left eye's
+//! picture showing through; an SVG export is the same.
+//!
+//! An `[anaglyph]` section turns it on at the start, with the separation,
+//! the most the two pictures are shifted apart in pixels, and the
+//! convergence, the depth in world units behind the swing that sits on the
+//! screen:
+//!
+//! ```text
+//! [anaglyph]
+//! enabled = true
+//! separation = 20
+//! convergence = 0
+//! ```
+
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::Rectangle;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+
+const DEFAULT_SEPARATION: f32 = 20.0;
+const MAX_SEPARATION: f32 = 100.0;
+/// How far off the viewer stands from the swing, in world units.
+const VIEWER: f32 = 1000.0;
+/// Depths nearer than this are held at it, short of the viewer.
+const NEAREST: f32 = -0.9 * VIEWER;
+/// The backdrop's depth behind the swing.
+pub const BACKDROP: f32 = 400.0;
+/// The depth a scene's models are spread through.
+const SPREAD: f32 = 200.0;
+/// How strongly the right eye's picture is laid over the left's.
+const RIGHT_STRENGTH: f32 = 0.5;
+
+#[derive(Copy, Clone)]
+pub struct Anaglyph {
+    pub enabled: bool,
+    /// In pixels: how far apart the two pictures of something infinitely
+    /// far back would be.
+    pub separation: f32,
+    /// In world units behind the swing: the depth drawn on the screen, with
+    /// the two pictures not shifted at all.
+    pub convergence: f32,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Anaglyph {
+    pub fn new() -> Anaglyph {
+        Anaglyph {
+            enabled: false,
+            separation: DEFAULT_SEPARATION,
+            convergence: 0.0,
+        }
+    }
+
+    /// Reads `enabled = true|false`, `separation = <pixels>` and
+    /// `convergence = <depth>` from an `[anaglyph]` section.
+    pub fn from_config(section: &Section) -> Result<Anaglyph, String> {
+        let mut anaglyph = Anaglyph::new();
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("enabled", value)) => {
+                    anaglyph.enabled = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `enabled`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                Some(("separation", value)) => anaglyph
+                    .set_separation(parse_f32("separation", value).map_err(error)?)
+                    .map_err(error)?,
+                Some(("convergence", value)) => anaglyph
+                    .set_convergence(parse_f32("convergence", value).map_err(error)?)
+                    .map_err(error)?,
+                _ => return Err(error(format!("unknown anaglyph setting `{}`", line))),
+            }
+        }
+        Ok(anaglyph)
+    }
+
+    pub fn set_separation(&mut self, separation: f32) -> Result<(), String> {
+        if !(0.0..=MAX_SEPARATION).contains(&separation) {
+            return Err(format!(
+                "the separation must be from 0 to {} pixels, not {}",
+                MAX_SEPARATION, separation
+            ));
+        }
+        self.separation = separation;
+        Ok(())
+    }
+
+    pub fn set_convergence(&mut self, convergence: f32) -> Result<(), String> {
+        if !(NEAREST..=VIEWER).contains(&convergence) {
+            return Err(format!(
+                "the convergence must be from {} to {}, not {}",
+                NEAREST, VIEWER, convergence
+            ));
+        }
+        self.convergence = convergence;
+        Ok(())
+    }
+
+    /// How far apart the two pictures of something at `depth` are drawn,
+    /// the right eye's to the right of the left's for something behind the
+    /// screen.
+    pub fn parallax(&self, depth: f32) -> f32 {
+        let depth = depth.max(NEAREST);
+        self.separation * (depth - self.convergence) / (VIEWER + depth)
+    }
+
+    /// Draws into `graphics` as `side`'s eye sees it, over `background`.
+    pub fn eye<'a>(
+        &self,
+        graphics: &'a mut dyn Renderer,
+        side: Side,
+        background: Color,
+    ) -> Eye<'a> {
+        let mut eye = Eye {
+            graphics,
+            anaglyph: *self,
+            side,
+            background: grey(background),
+            shift: 0.0,
+        };
+        eye.set_depth(0.0);
+        eye
+    }
+
+    /// What `background` clears to: the two eyes' grey.
+    pub fn background(background: Color) -> Color {
+        let grey = grey(background);
+        Color::from_rgb(grey, grey, grey)
+    }
+
+    /// The `anaglyph` console command: `anaglyph on|off`,
+    /// `anaglyph separation <pixels>` or `anaglyph convergence <depth>`; on
+    /// its own it shows the settings.
+    pub fn command(&mut self, words: &[&str]) -> Result<Vec<String>, String> {
+        let number = |value: &str| {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("Not a number: `{}`", value))
+        };
+        match words {
+            [] => {}
+            ["on"] => self.enabled = true,
+            ["off"] => self.enabled = false,
+            ["separation", value] => self.set_separation(number(value)?)?,
+            ["convergence", value] => self.set_convergence(number(value)?)?,
+            _ => {
+                return Err(
+                    "Usage: anaglyph [on|off|separation <pixels>|convergence <depth>]".to_string(),
+                )
+            }
+        }
+        Ok(vec![format!(
+            "Anaglyph {}: separation {} px, convergence {}",
+            if self.enabled { "on" } else { "off" },
+            self.separation,
+            self.convergence
+        )])
+    }
+}
+
+/// The depth the `index`th of `count` models stands at.
+pub fn model_depth(index: usize, count: usize) -> f32 {
+    if count < 2 {
+        return 0.0;
+    }
+    SPREAD * (0.5 - index as f32 / (count - 1) as f32)
+}
+
+/// How bright `color` looks.
+fn grey(color: Color) -> f32 {
+    0.299 * color.r() + 0.587 * color.g() + 0.114 * color.b()
+}
+
+/// One eye's picture: shapes shifted for the depth last set and kept to the
+/// eye's colours, passed on to another renderer.
+pub struct Eye<'a> {
+    graphics: &'a mut dyn Renderer,
+    anaglyph: Anaglyph,
+    side: Side,
+    /// The background's grey, which the other eye's channels are left at.
+    background: f32,
+    /// Pixels to the right everything is moved.
+    shift: f32,
+}
+
+impl Eye<'_> {
+    fn color(&self, color: Color) -> Color {
+        let (grey, background) = (grey(color), self.background);
+        match self.side {
+            Side::Left => Color::from_rgba(grey, background, background, color.a()),
+            Side::Right => Color::from_rgba(background, grey, grey, color.a() * RIGHT_STRENGTH),
+        }
+    }
+
+    fn point(&self, point: Vec2) -> Vec2 {
+        Vec2::new(point.x + self.shift, point.y)
+    }
+
+    fn tuple(&self, point: (f32, f32)) -> (f32, f32) {
+        (point.0 + self.shift, point.1)
+    }
+}
+
+impl Renderer for Eye<'_> {
+    fn draw_line(&mut self, start: (f32, f32), end: (f32, f32), thickness: f32, color: Color) {
+        let (start, end, color) = (self.tuple(start), self.tuple(end), self.color(color));
+        self.graphics.draw_line(start, end, thickness, color);
+    }
+
+    fn draw_circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        let (center, color) = (self.tuple(center), self.color(color));
+        self.graphics.draw_circle(center, radius, color);
+    }
+
+    fn draw_rectangle(&mut self, rectangle: Rectangle, color: Color) {
+        let rectangle = Rectangle::new(
+            self.point(*rectangle.top_left()),
+            self.point(*rectangle.bottom_right()),
+        );
+        let color = self.color(color);
+        self.graphics.draw_rectangle(rectangle, color);
+    }
+
+    fn draw_triangle(&mut self, points: [Vec2; 3], color: Color) {
+        let (points, color) = (points.map(|p| self.point(p)), self.color(color));
+        self.graphics.draw_triangle(points, color);
+    }
+
+    fn draw_quad(&mut self, points: [Vec2; 4], color: Color) {
+        let (points, color) = (points.map(|p| self.point(p)), self.color(color));
+        self.graphics.draw_quad(points, color);
+    }
+
+    fn draw_quad_four_color(&mut self, points: [Vec2; 4], colors: [Color; 4]) {
+        let points = points.map(|p| self.point(p));
+        let colors = colors.map(|c| self.color(c));
+        self.graphics.draw_quad_four_color(points, colors);
+    }
+
+    fn draw_polyline(&mut self, points: &[Vector], thickness: f32, colors: &[Color]) {
+        let points: Vec<Vector> = points
+            .iter()
+            .map(|p| Vector::new(p.x + self.shift, p.y))
+            .collect();
+        let colors: Vec<Color> = colors.iter().map(|&c| self.color(c)).collect();
+        self.graphics.draw_polyline(&points, thickness, &colors);
+    }
+
+    fn set_depth(&mut self, depth: f32) {
+        let half = self.anaglyph.parallax(depth) / 2.0;
+        self.shift = match self.side {
+            Side::Left => -half,
+            Side::Right => half,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::model::Model;
+    use crate::pendulum::Pendulum;
+    use crate::svg::Svg;
+    use crate::viewport::Viewport;
+
+    const BACKGROUND: Color = Color::from_rgb(0.8, 0.9, 1.0);
+
+    /// Two pendulums at a fixed pose, drawn to an SVG flat or for both eyes.
+    fn picture(anaglyph: Option<Anaglyph>) -> String {
+        let view = Viewport::new(false);
+        let pendulums: Vec<Pendulum> = [(300.0, 0.4), (500.0, -0.7)]
+            .iter()
+            .map(|&(x, angle)| {
+                let mut p = Pendulum::new(x, 100.0, 200.0);
+                p.angle = angle;
+                p.update();
+                p
+            })
+            .collect();
+        let draw = |graphics: &mut dyn Renderer| {
+            for (i, p) in pendulums.iter().enumerate() {
+                graphics.set_depth(model_depth(i, pendulums.len()));
+                p.draw(graphics, &view);
+            }
+        };
+        match anaglyph {
+            None => {
+                let mut svg = Svg::new(800.0, 600.0, BACKGROUND);
+                draw(&mut svg);
+                svg.finish()
+            }
+            Some(anaglyph) => {
+                let mut svg = Svg::new(800.0, 600.0, Anaglyph::background(BACKGROUND));
+                for side in [Side::Left, Side::Right] {
+                    draw(&mut anaglyph.eye(&mut svg, side, BACKGROUND));
+                }
+                svg.finish()
+            }
+        }
+    }
+
+    #[test]
+    fn a_fixed_pose_draws_as_it_did_flat_and_for_both_eyes() {
+        let mut anaglyph = Anaglyph::new();
+        anaglyph.enabled = true;
+        assert_eq!(picture(None), include_str!("../tests/data/mono.svg"));
+        assert_eq!(
+            picture(Some(anaglyph)),
+            include_str!("../tests/data/anaglyph.svg")
+        );
+    }
+
+    #[test]
+    fn what_stands_further_back_is_shifted_further_apart() {
+        let anaglyph = Anaglyph::new();
+        assert_eq!(anaglyph.parallax(0.0), 0.0);
+        assert!(anaglyph.parallax(BACKDROP) > 0.0);
+        assert!(anaglyph.parallax(-100.0) < 0.0);
+        assert!(anaglyph.parallax(1e9) <= anaglyph.separation);
+        assert!(anaglyph.parallax(-1e9).is_finite());
+        assert!(model_depth(0, 3) > model_depth(1, 3));
+        assert!(model_depth(1, 3) > model_depth(2, 3));
+        assert_eq!(model_depth(0, 1), 0.0);
+
+        let mut converged = anaglyph;
+        converged.set_convergence(BACKDROP).unwrap();
+        assert_eq!(converged.parallax(BACKDROP), 0.0);
+        assert!(converged.parallax(0.0) < 0.0);
+    }
+
+    #[test]
+    fn each_eye_keeps_to_its_own_channels() {
+        let mut recorded = Svg::new(10.0, 10.0, BACKGROUND);
+        let anaglyph = Anaglyph::new();
+        let eye = anaglyph.eye(&mut recorded, Side::Left, BACKGROUND);
+        let left = eye.color(Color::RED);
+        let background = grey(BACKGROUND);
+        assert_eq!(
+            (left.g(), left.b(), left.a()),
+            (background, background, 1.0)
+        );
+        assert!((left.r() - grey(Color::RED)).abs() < 1e-6);
+        let eye = anaglyph.eye(&mut recorded, Side::Right, BACKGROUND);
+        let right = eye.color(Color::RED);
+        assert_eq!((right.r(), right.a()), (background, RIGHT_STRENGTH));
+        assert_eq!(right.g(), right.b());
+    }
+
+    #[test]
+    fn the_settings_are_read_and_checked() {
+        let read = |text: &str| {
+            let config = Config::parse(text).unwrap();
+            Anaglyph::from_config(config.section("anaglyph").unwrap())
+        };
+        let anaglyph =
+            read("[anaglyph]\nenabled = true\nseparation = 30\nconvergence = 100\n").unwrap();
+        assert!(anaglyph.enabled);
+        assert_eq!((anaglyph.separation, anaglyph.convergence), (30.0, 100.0));
+        assert!(read("[anaglyph]\nseparation = -1\n").is_err());
+        assert!(read("[anaglyph]\nconvergence = NaN\n").is_err());
+        assert!(read("[anaglyph]\ndepth = 3\n").is_err());
+
+        let mut anaglyph = Anaglyph::new();
+        assert!(anaglyph.command(&["separation", "inf"]).is_err());
+        anaglyph.command(&["on"]).unwrap();
+        assert!(anaglyph.enabled);
+    }
+}
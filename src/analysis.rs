@@ -0,0 +1,122 @@
+//! Signal analysis over recorded samples, one sample per physics step.
+
+/// A local maximum of a sampled signal, refined by fitting a parabola
+/// through it and its two neighbours.
+#[derive(Copy, Clone)]
+pub struct Peak {
+    /// Fractional sample index of the top of the parabola.
+    pub index: f32,
+    pub value: f32,
+}
+
+/// Finds the positive peaks of `samples`. Plateaus count once.
+pub fn find_peaks(samples: &[f32]) -> Vec<Peak> {
+    let mut peaks = Vec::new();
+
+    for i in 1..samples.len().saturating_sub(1) {
+        let (left, middle, right) = (samples[i - 1], samples[i], samples[i + 1]);
+
+        if middle > 0.0 && middle > left && middle >= right {
+            let curvature = left - 2.0 * middle + right;
+            let offset = if curvature != 0.0 {
+                0.5 * (left - right) / curvature
+            } else {
+                0.0
+            };
+
+            peaks.push(Peak {
+                index: i as f32 + offset,
+                value: middle - 0.25 * (left - right) * offset,
+            });
+        }
+    }
+
+    peaks
+}
+
+/// Oscillation frequency in cycles per sample from the mean spacing of
+/// successive peaks.
+pub fn frequency(peaks: &[Peak]) -> Option<f32> {
+    let (first, last) = (peaks.first()?, peaks.last()?);
+    if peaks.len() < 2 || last.index <= first.index {
+        return None;
+    }
+
+    Some((peaks.len() - 1) as f32 / (last.index - first.index))
+}
+
+/// Damping ratio from the mean logarithmic decrement of successive peaks.
+pub fn damping_ratio(peaks: &[Peak]) -> Option<f32> {
+    let decrements: Vec<f32> = peaks
+        .windows(2)
+        .filter(|pair| pair[0].value > 0.0 && pair[1].value > 0.0)
+        .map(|pair| (pair[0].value / pair[1].value).ln())
+        .collect();
+
+    if decrements.is_empty() {
+        return None;
+    }
+
+    let delta = decrements.iter().sum::<f32>() / decrements.len() as f32;
+    Some(delta / (4.0 * std::f32::consts::PI.powi(2) + delta * delta).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+    use std::f32::consts::TAU;
+
+    /// A damped swing sampled 100 times a cycle, with noise of up to
+    /// `noise` either way. The peaks are found without hysteresis, so the
+    /// noise is kept small next to the change between samples at a peak.
+    fn swing(zeta: f32, noise: f32) -> Vec<f32> {
+        let mut rng = Rng::new(11);
+        let natural = TAU / 100.0;
+        let damped = natural * (1.0 - zeta * zeta).sqrt();
+        (0..1500)
+            .map(|i| {
+                let t = i as f32;
+                (-zeta * natural * t).exp() * (damped * t).cos() + rng.range(-noise, noise)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn peaks_come_once_a_cycle_at_the_envelope() {
+        let zeta = 0.03;
+        let samples = swing(zeta, 1e-5);
+        let peaks = find_peaks(&samples);
+        // The first sample is a peak with nothing to its left, so isn't
+        // counted.
+        assert_eq!(peaks.len(), 14);
+        let damped = (1.0 - zeta * zeta).sqrt() / 100.0;
+        for (n, peak) in peaks.iter().enumerate() {
+            let expected = (n + 1) as f32 / damped;
+            assert!(
+                (peak.index - expected).abs() < 1.0,
+                "peak {} at {}",
+                n,
+                peak.index
+            );
+        }
+        let frequency = frequency(&peaks).unwrap();
+        assert!((frequency - damped).abs() < 1e-3 * damped, "{}", frequency);
+    }
+
+    #[test]
+    fn the_damping_ratio_comes_from_the_decrement() {
+        // Six cycles, so the most damped is still well above the noise.
+        for zeta in [0.005, 0.03, 0.1] {
+            let estimated = damping_ratio(&find_peaks(&swing(zeta, 1e-5)[..600])).unwrap();
+            assert!(
+                (estimated - zeta).abs() < 0.02 * zeta,
+                "{} came out {}",
+                zeta,
+                estimated
+            );
+        }
+        assert!(damping_ratio(&find_peaks(&[0.0, 1.0, 0.0])).is_none());
+        assert!(frequency(&[]).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+
+use crate::config::Section;
+
+/// A note attached to a moment of the run.
+pub struct Annotation {
+    /// Simulated time in seconds, on the strip chart's clock.
+    pub time: f32,
+    pub text: String,
+}
+
+/// Notes dropped during a session, kept in time order.
+pub struct Annotations {
+    pub notes: Vec<Annotation>,
+    /// The note last clicked, shown in the status lines.
+    pub selected: Option<usize>,
+}
+
+impl Annotations {
+    pub fn new() -> Annotations {
+        Annotations {
+            notes: Vec::new(),
+            selected: None,
+        }
+    }
+
+    pub fn add(&mut self, time: f32, text: String) {
+        let index = self.notes.partition_point(|note| note.time <= time);
+        self.notes.insert(index, Annotation { time, text });
+        self.selected = None;
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Annotation> {
+        self.selected = None;
+        (index < self.notes.len()).then(|| self.notes.remove(index))
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+        self.selected = None;
+    }
+
+    /// Reads an `[annotations]` section of `<seconds> <note>` lines.
+    pub fn from_config(section: &Section) -> Result<Annotations, String> {
+        let mut annotations = Annotations::new();
+        for (line_number, line) in &section.lines {
+            let (time, text) = line.split_once(' ').unwrap_or((line, ""));
+            let time = time
+                .parse()
+                .map_err(|_| format!("line {}: invalid note time `{}`", line_number, time))?;
+            annotations.add(time, text.trim().to_string());
+        }
+        Ok(annotations)
+    }
+
+    /// The notes as an `[annotations]` section, or nothing if there are none.
+    pub fn to_section(&self) -> String {
+        if self.notes.is_empty() {
+            return String::new();
+        }
+
+        let mut text = String::from("[annotations]\n");
+        for note in &self.notes {
+            text.push_str(&format!("{} {}\n", note.time, note.text));
+        }
+        text
+    }
+
+    /// Writes the notes as CSV, quoting the text.
+    pub fn export_csv(&self, path: &Path) -> Result<(), String> {
+        let mut text = String::from("time,note\n");
+        for note in &self.notes {
+            text.push_str(&format!(
+                "{},\"{}\"\n",
+                note.time,
+                note.text.replace('"', "\"\"")
+            ));
+        }
+        fs::write(path, text).map_err(|e| format!("could not write {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,988 @@
+//! Apparatus profiles: the real pendulums on the lab bench, kept by name so
+//! their numbers needn't be typed in again each session. A profile has the
+//! rod length in real units, the mass, the bob shape, an estimate of the
+//! damping ratio, the pixels a metre is drawn with and free notes, and
+//! attaching it to a pendulum sets that pendulum up from them. The
+//! profile's name then follows the pendulum into saved scenes and shows in
+//! the legend, so several apparatus can swing side by side in one scene.
+//!
+//! The profiles live in `apparatus.ini`, next to the results store, one
+//! section each, and can be edited by hand; they're checked as they're
+//! read, and a file that doesn't read is never written over. The current
+//! pendulum can be saved as a profile, and its impulse test results
+//! written back under the profile's history, where the sessions can be
+//! compared:
+//!
+//! ```text
+//! [apparatus brass-small]
+//! length = 25 cm
+//! mass = 0.2
+//! bob = disc
+//! damping_ratio = 0.002
+//! pixels_per_metre = 800
+//! notes = Brass bob on the short rod, bench 3
+//!
+//! [history brass-small]
+//! at=1760437800 period=1.0034 damping_ratio=0.0021
+//! ```
+//!
+//! The profiles panel lists them. Clicking one shows its notes and history,
+//! and its buttons attach it to the selected pendulum, save the selected
+//! pendulum as a new one, or write back the last result.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::autosave::Autosave;
+use crate::config::{key_value, parse_f32, Config, Section};
+use crate::focus::{Handling, Registry, Target};
+use crate::model::{Model, ModelEntry};
+use crate::pendulum::{BobShape, DampingModel, Pendulum};
+use crate::store::{format_timestamp, Store};
+use crate::units::{self, Units};
+
+const FILE_NAME: &str = "apparatus.ini";
+const PROFILE_PREFIX: &str = "apparatus ";
+const HISTORY_PREFIX: &str = "history ";
+
+const PANEL: (f32, f32, f32, f32) = (40.0, 50.0, 560.0, 330.0);
+const ROW_HEIGHT: f32 = 20.0;
+/// Profiles listed at once; the rest scroll.
+const ROWS: usize = 6;
+/// Results of the picked profile shown, the latest.
+const HISTORY_ROWS: usize = 5;
+const BUTTON_WIDTH: f32 = 110.0;
+const BUTTON_HEIGHT: f32 = 28.0;
+const BUTTONS: [&str; 3] = ["Attach", "Save as...", "Write back"];
+
+/// A period measured on an apparatus, with the damping fitted alongside.
+#[derive(Clone, PartialEq)]
+pub struct Measurement {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// In seconds.
+    pub period: f32,
+    pub damping_ratio: Option<f32>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Profile {
+    pub id: String,
+    /// Rod length in metres.
+    pub length: f32,
+    pub mass: f32,
+    pub bob: BobShape,
+    pub damping_ratio: Option<f32>,
+    /// The scale to draw it at, unless the scene already has one.
+    pub pixels_per_metre: Option<f32>,
+    pub notes: String,
+    /// Oldest first.
+    pub history: Vec<Measurement>,
+}
+
+/// Whether `id` will do as a profile's name: letters, digits, `-` and `_`,
+/// so it can stand in a scene line and on the command line as it is.
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+impl Measurement {
+    pub fn new(period: f32, damping_ratio: Option<f32>) -> Measurement {
+        Measurement {
+            timestamp: now(),
+            period,
+            damping_ratio,
+        }
+    }
+
+    /// Reads `at=<timestamp> period=<s> damping_ratio=<ratio>`, the last
+    /// optional.
+    fn parse(line: &str) -> Result<Measurement, String> {
+        let (mut timestamp, mut period, mut damping_ratio) = (None, None, None);
+        for token in line.split_whitespace() {
+            match key_value(token) {
+                Some(("at", value)) => {
+                    timestamp = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid timestamp `{}`", value))?,
+                    )
+                }
+                Some(("period", value)) => {
+                    let value = parse_f32("period", value)?;
+                    if !(value.is_finite() && value > 0.0) {
+                        return Err("`period` must be positive".to_string());
+                    }
+                    period = Some(value)
+                }
+                Some(("damping_ratio", value)) => {
+                    damping_ratio = Some(parse_f32("damping_ratio", value)?)
+                }
+                _ => return Err(format!("unknown measurement field `{}`", token)),
+            }
+        }
+        Ok(Measurement {
+            timestamp: timestamp.ok_or("missing `at`")?,
+            period: period.ok_or("missing `period`")?,
+            damping_ratio,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = format!("at={} period={}", self.timestamp, self.period);
+        if let Some(ratio) = self.damping_ratio {
+            line.push_str(&format!(" damping_ratio={}", ratio));
+        }
+        line
+    }
+
+    fn describe(&self) -> String {
+        let damping = self
+            .damping_ratio
+            .map_or(String::new(), |d| format!(", zeta {:.4}", d));
+        format!(
+            "{}: period {:.4} s{}",
+            format_timestamp(self.timestamp),
+            self.period,
+            damping
+        )
+    }
+}
+
+impl Profile {
+    /// Reads a profile's settings from its section. `length` is the only
+    /// one it can't do without.
+    fn from_section(id: &str, section: &Section) -> Result<Profile, String> {
+        if !valid_id(id) {
+            return Err(format!(
+                "`{}` is not a profile name: use letters, digits, - and _",
+                id
+            ));
+        }
+        let mut profile = Profile {
+            id: id.to_string(),
+            length: 0.0,
+            mass: 1.0,
+            bob: BobShape::Point,
+            damping_ratio: None,
+            pixels_per_metre: None,
+            notes: String::new(),
+            history: Vec::new(),
+        };
+        let mut length = None;
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("length", value)) => {
+                    length = Some(units::parse_length(value).map_err(error)?)
+                }
+                Some(("mass", value)) => profile.mass = parse_f32("mass", value).map_err(error)?,
+                Some(("bob", value)) => {
+                    profile.bob = BobShape::parse(value)
+                        .ok_or(error(format!("unknown bob shape `{}`", value)))?
+                }
+                Some(("damping_ratio", value)) => {
+                    profile.damping_ratio = Some(parse_f32("damping_ratio", value).map_err(error)?)
+                }
+                Some(("pixels_per_metre", value)) => {
+                    let scale = parse_f32("pixels_per_metre", value).map_err(error)?;
+                    Units::per_metre(scale).map_err(error)?;
+                    profile.pixels_per_metre = Some(scale);
+                }
+                Some(("notes", value)) => profile.notes = value.to_string(),
+                _ => return Err(error(format!("unknown apparatus setting `{}`", line))),
+            }
+        }
+
+        profile.length = length.ok_or(format!("[{}{}] has no `length`", PROFILE_PREFIX, id))?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(self.length.is_finite() && self.length > 0.0) {
+            return Err(format!("{}: the length must be positive", self.id));
+        }
+        if !(self.mass.is_finite() && self.mass > 0.0) {
+            return Err(format!("{}: the mass must be positive", self.id));
+        }
+        if let Some(ratio) = self.damping_ratio {
+            if !(ratio.is_finite() && ratio >= 0.0) {
+                return Err(format!("{}: the damping ratio can't be negative", self.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// A profile called `id` of `p` as it is now, which needs the scene
+    /// calibrated to give the length in real units.
+    pub fn of(id: &str, p: &Pendulum, units: &Units, notes: &str) -> Result<Profile, String> {
+        if !valid_id(id) {
+            return Err(format!(
+                "`{}` is not a profile name: use letters, digits, - and _",
+                id
+            ));
+        }
+        let length = units.metres(p.r).ok_or(
+            "Calibrate the scene first, so the length can be saved in real units".to_string(),
+        )?;
+        Ok(Profile {
+            id: id.to_string(),
+            length,
+            mass: p.m,
+            bob: p.bob,
+            damping_ratio: p.damping_ratio(),
+            pixels_per_metre: units.pixels_per_metre(),
+            notes: notes.to_string(),
+            history: Vec::new(),
+        })
+    }
+
+    /// Sets `p` up as this apparatus, keeping its angle and motion. The
+    /// length is drawn at the scene's scale, or at the profile's if the
+    /// scene has none yet, which then becomes the scene's.
+    pub fn apply(&self, p: &mut Pendulum, units: &mut Units) -> Result<String, String> {
+        let scale = match (units.pixels_per_metre(), self.pixels_per_metre) {
+            (Some(scale), _) => scale,
+            (None, Some(scale)) => {
+                *units = Units::per_metre(scale)?;
+                scale
+            }
+            (None, None) => {
+                return Err(format!(
+                    "{} has no pixels_per_metre, and the scene isn't calibrated",
+                    self.id
+                ))
+            }
+        };
+        let ModelEntry::Pendulum(mut entry) = p.entry() else {
+            unreachable!("a pendulum's entry is a pendulum's");
+        };
+        entry.r = self.length * scale;
+        entry.m = self.mass;
+        entry.bob = self.bob;
+        if let Some(ratio) = self.damping_ratio {
+            entry.damping = DampingModel::Viscous;
+            entry.damping_ratio = Some(ratio);
+        }
+        entry.apparatus = self.id.clone();
+        *p = Pendulum::from_entry(&entry);
+        Ok(format!("Set up as {}: {}", self.id, self.summary(units)))
+    }
+
+    /// One line of its settings, with the length in `units`.
+    pub fn summary(&self, units: &Units) -> String {
+        let length = match units.pixels_per_metre().or(self.pixels_per_metre) {
+            Some(scale) => Units::per_metre(scale)
+                .map_or(String::new(), |units| units.length(self.length * scale)),
+            None => format!("{} m", self.length),
+        };
+        let damping = self
+            .damping_ratio
+            .map_or(String::new(), |d| format!(", zeta {:.4}", d));
+        format!(
+            "{}, {} kg, {} bob{}",
+            length,
+            self.mass,
+            self.bob.name(),
+            damping
+        )
+    }
+
+    /// The latest results against the mean of all of them, for comparing
+    /// sessions.
+    pub fn history_lines(&self) -> Vec<String> {
+        if self.history.is_empty() {
+            return vec![format!("{}: no results yet", self.id)];
+        }
+        let count = self.history.len() as f32;
+        let mean = self.history.iter().map(|m| m.period).sum::<f32>() / count;
+        let spread = (self
+            .history
+            .iter()
+            .map(|m| (m.period - mean).powi(2))
+            .sum::<f32>()
+            / count)
+            .sqrt();
+        let mut lines = vec![format!(
+            "{}: {} results, mean period {:.4} s, spread {:.4} s",
+            self.id,
+            self.history.len(),
+            mean,
+            spread
+        )];
+        lines.extend(
+            self.history
+                .iter()
+                .rev()
+                .take(HISTORY_ROWS - 1)
+                .map(|m| format!("{} ({:+.4} s)", m.describe(), m.period - mean)),
+        );
+        lines
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("[{}{}]\n", PROFILE_PREFIX, self.id);
+        text.push_str(&format!("length = {} m\n", self.length));
+        text.push_str(&format!("mass = {}\n", self.mass));
+        text.push_str(&format!("bob = {}\n", self.bob.name()));
+        if let Some(ratio) = self.damping_ratio {
+            text.push_str(&format!("damping_ratio = {}\n", ratio));
+        }
+        if let Some(scale) = self.pixels_per_metre {
+            text.push_str(&format!("pixels_per_metre = {}\n", scale));
+        }
+        if !self.notes.is_empty() {
+            text.push_str(&format!("notes = {}\n", self.notes));
+        }
+        if !self.history.is_empty() {
+            text.push_str(&format!("\n[{}{}]\n", HISTORY_PREFIX, self.id));
+            for measurement in &self.history {
+                text.push_str(&measurement.to_line());
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+/// Reads the profiles in a profiles file, each checked, with their
+/// histories.
+pub fn parse(text: &str) -> Result<Vec<Profile>, String> {
+    let config = Config::parse(text)?;
+    let mut profiles: Vec<Profile> = Vec::new();
+    for section in config.sections() {
+        if let Some(id) = section.name.strip_prefix(PROFILE_PREFIX) {
+            let id = id.trim();
+            if profiles.iter().any(|profile| profile.id == id) {
+                return Err(format!("apparatus `{}` is given twice", id));
+            }
+            profiles.push(Profile::from_section(id, section)?);
+        }
+    }
+    for section in config.sections() {
+        if let Some(id) = section.name.strip_prefix(HISTORY_PREFIX) {
+            let id = id.trim();
+            let profile = profiles
+                .iter_mut()
+                .find(|profile| profile.id == id)
+                .ok_or(format!("history for unknown apparatus `{}`", id))?;
+            for (line_number, line) in &section.lines {
+                profile.history.push(
+                    Measurement::parse(line).map_err(|e| format!("line {}: {}", line_number, e))?,
+                );
+            }
+        } else if !section.name.starts_with(PROFILE_PREFIX) {
+            return Err(format!("unknown section [{}]", section.name));
+        }
+    }
+    Ok(profiles)
+}
+
+/// Every profile, and the file they're kept in.
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+    path: PathBuf,
+    /// Why the file couldn't be read, if it couldn't, which keeps it from
+    /// being written over with what little was.
+    broken: Option<String>,
+}
+
+impl Profiles {
+    /// Next to the results store.
+    pub fn default_path() -> PathBuf {
+        Store::default_path().with_file_name(FILE_NAME)
+    }
+
+    /// Reads the profiles in `path`. A missing file is no profiles yet.
+    pub fn open(path: &Path) -> Profiles {
+        let mut profiles = Profiles {
+            profiles: Vec::new(),
+            path: path.to_path_buf(),
+            broken: None,
+        };
+        if let Err(e) = profiles.reload() {
+            log!("{}", e);
+        }
+        profiles
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the file again, after it's been edited by hand.
+    pub fn reload(&mut self) -> Result<usize, String> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                let e = format!("could not read {}: {}", self.path.display(), e);
+                self.broken = Some(e.clone());
+                return Err(e);
+            }
+        };
+        match parse(&text) {
+            Ok(profiles) => {
+                self.profiles = profiles;
+                self.broken = None;
+                Ok(self.profiles.len())
+            }
+            Err(e) => {
+                let e = format!("{}: {}", self.path.display(), e);
+                self.broken = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    fn to_text(&self) -> String {
+        let sections: Vec<String> = self.profiles.iter().map(Profile::to_text).collect();
+        format!(
+            "# Apparatus profiles, one [apparatus <name>] section each.\n\n{}",
+            sections.join("\n")
+        )
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(e) = &self.broken {
+            return Err(format!(
+                "Not writing over the profiles, which didn't read: {}",
+                e
+            ));
+        }
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)
+                .map_err(|e| format!("could not create {}: {}", directory.display(), e))?;
+        }
+        Autosave::write(&self.path, &self.to_text())
+    }
+
+    /// Adds `profile`, or replaces the one of that name, keeping its
+    /// history, and writes the file.
+    pub fn put(&mut self, mut profile: Profile) -> Result<String, String> {
+        profile.validate()?;
+        let id = profile.id.clone();
+        let reply = match self.profiles.iter_mut().find(|old| old.id == id) {
+            Some(old) => {
+                profile.history = std::mem::take(&mut old.history);
+                *old = profile;
+                format!("Updated apparatus {}", id)
+            }
+            None => {
+                self.profiles.push(profile);
+                format!("Saved apparatus {}", id)
+            }
+        };
+        self.save()?;
+        Ok(reply)
+    }
+
+    /// Adds `measurement` to the history of the profile `id` and writes the
+    /// file.
+    pub fn record(&mut self, id: &str, measurement: Measurement) -> Result<String, String> {
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.id == id)
+            .ok_or(format!("No apparatus called {}", id))?;
+        let reply = format!("Wrote to {}: {}", id, measurement.describe());
+        profile.history.push(measurement);
+        if let Err(e) = self.save() {
+            if let Some(profile) = self.profiles.iter_mut().find(|profile| profile.id == id) {
+                profile.history.pop();
+            }
+            return Err(e);
+        }
+        Ok(reply)
+    }
+}
+
+/// What a click on the panel asks for.
+pub enum Click {
+    /// Nothing beyond picking a profile, or a click on the background.
+    Taken,
+    Attach(usize),
+    SaveAs,
+    WriteBack,
+}
+
+fn inside((left, top, width, height): (f32, f32, f32, f32), x: f32, y: f32) -> bool {
+    x >= left && x <= left + width && y >= top && y <= top + height
+}
+
+fn button_rect(i: usize) -> (f32, f32, f32, f32) {
+    let (left, top, _, height) = PANEL;
+    (
+        left + 8.0 + (BUTTON_WIDTH + 8.0) * i as f32,
+        top + height - BUTTON_HEIGHT - 8.0,
+        BUTTON_WIDTH,
+        BUTTON_HEIGHT,
+    )
+}
+
+/// The profiles panel: the profiles to pick from, the picked one's notes
+/// and history, and the buttons.
+pub struct ProfilesPanel {
+    pub visible: bool,
+    /// The picked profile.
+    pub picked: Option<usize>,
+    /// The last impulse test result on an apparatus, waiting to be written
+    /// back to it.
+    pub pending: Option<(String, Measurement)>,
+    /// First profile listed.
+    scroll: usize,
+}
+
+impl ProfilesPanel {
+    pub fn new() -> ProfilesPanel {
+        ProfilesPanel {
+            visible: false,
+            picked: None,
+            pending: None,
+            scroll: 0,
+        }
+    }
+
+    fn row_top(row: usize) -> f32 {
+        PANEL.1 + ROW_HEIGHT * (row + 1) as f32
+    }
+
+    /// Returns what the click asks for, if the panel took it.
+    pub fn mouse_down(&mut self, x: f32, y: f32, profiles: &Profiles) -> Option<Click> {
+        if !self.visible || !inside(PANEL, x, y) {
+            return None;
+        }
+        if let Some(i) = (0..BUTTONS.len()).find(|&i| inside(button_rect(i), x, y)) {
+            return Some(match i {
+                0 => match self.picked {
+                    Some(picked) => Click::Attach(picked),
+                    None => Click::Taken,
+                },
+                1 => Click::SaveAs,
+                _ => Click::WriteBack,
+            });
+        }
+        let row = (0..ROWS).find(|&row| {
+            let top = ProfilesPanel::row_top(row);
+            y >= top && y < top + ROW_HEIGHT
+        });
+        if let Some(i) = row
+            .map(|row| row + self.scroll)
+            .filter(|&i| i < profiles.profiles.len())
+        {
+            self.picked = Some(i);
+        }
+        Some(Click::Taken)
+    }
+
+    /// Scrolls the list by `lines` wheel notches if (`x`, `y`) is over the
+    /// panel.
+    pub fn scroll(&mut self, lines: f32, x: f32, y: f32, profiles: &Profiles) -> bool {
+        if !self.visible || !inside(PANEL, x, y) {
+            return false;
+        }
+        let last = profiles.profiles.len().saturating_sub(ROWS);
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(-lines.round() as isize)
+            .min(last);
+        true
+    }
+
+    /// Registers the listed profiles and the buttons, when shown.
+    pub fn register(&self, registry: &mut Registry, profiles: &Profiles) {
+        if !self.visible {
+            return;
+        }
+        let shown = profiles
+            .profiles
+            .len()
+            .saturating_sub(self.scroll)
+            .min(ROWS);
+        for row in 0..shown {
+            registry.register(
+                Target::Profile(row + self.scroll),
+                (
+                    PANEL.0 + 60.0,
+                    ProfilesPanel::row_top(row) + ROW_HEIGHT / 2.0,
+                ),
+                ROW_HEIGHT / 2.0,
+                Handling::Click,
+            );
+        }
+        for i in 0..BUTTONS.len() {
+            let (left, top, width, height) = button_rect(i);
+            registry.register(
+                Target::ProfileButton(i),
+                (left + width / 2.0, top + height / 2.0),
+                height / 2.0,
+                Handling::Click,
+            );
+        }
+    }
+
+    pub fn draw(
+        &self,
+        graphics: &mut Graphics2D,
+        font: &Font,
+        profiles: &Profiles,
+        units: &Units,
+        selected: Option<&Pendulum>,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = PANEL;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.9),
+        );
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + ROW_HEIGHT)),
+            Color::LIGHT_GRAY,
+        );
+        let text = |graphics: &mut Graphics2D, x: f32, y: f32, text: &str, color: Color| {
+            graphics.draw_text(
+                (left + x, y),
+                color,
+                &font.layout_text(text, 16.0, TextOptions::new()),
+            );
+        };
+
+        let attached = selected
+            .map(|p| p.apparatus.as_str())
+            .filter(|id| !id.is_empty());
+        let heading = match attached {
+            Some(id) => format!("Apparatus - selected pendulum is {}", id),
+            None => format!("Apparatus - {}", profiles.path().display()),
+        };
+        text(graphics, 8.0, top + 2.0, &heading, Color::BLACK);
+
+        if profiles.profiles.is_empty() {
+            text(
+                graphics,
+                8.0,
+                ProfilesPanel::row_top(0) + 2.0,
+                "No profiles yet: calibrate, then save the pendulum as one",
+                Color::BLACK,
+            );
+        }
+        for (row, (i, profile)) in profiles
+            .profiles
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(ROWS)
+            .enumerate()
+        {
+            let y = ProfilesPanel::row_top(row);
+            if self.picked == Some(i) {
+                graphics.draw_rectangle(
+                    Rectangle::from_tuples((left, y), (left + width, y + ROW_HEIGHT)),
+                    Color::from_rgba(0.6, 0.8, 1.0, 0.6),
+                );
+            }
+            let color = if attached == Some(profile.id.as_str()) {
+                Color::BLUE
+            } else {
+                Color::BLACK
+            };
+            text(graphics, 8.0, y + 2.0, &profile.id, color);
+            text(graphics, 150.0, y + 2.0, &profile.summary(units), color);
+        }
+
+        let mut y = ProfilesPanel::row_top(ROWS) + 4.0;
+        if let Some(profile) = self.picked.and_then(|i| profiles.profiles.get(i)) {
+            if !profile.notes.is_empty() {
+                text(graphics, 8.0, y, &profile.notes, Color::DARK_GRAY);
+                y += ROW_HEIGHT;
+            }
+            for line in profile.history_lines() {
+                text(graphics, 8.0, y, &line, Color::BLACK);
+                y += ROW_HEIGHT;
+            }
+        }
+
+        for (i, label) in BUTTONS.iter().enumerate() {
+            let (bx, by, bw, bh) = button_rect(i);
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((bx, by), (bx + bw, by + bh)),
+                Color::LIGHT_GRAY,
+            );
+            text(graphics, bx - left + 10.0, by + 5.0, label, Color::BLACK);
+        }
+        if let Some((id, measurement)) = &self.pending {
+            let (bx, by, bw, _) = button_rect(BUTTONS.len() - 1);
+            text(
+                graphics,
+                bx + bw - left + 8.0,
+                by + 5.0,
+                &format!("{:.4} s to {}", measurement.period, id),
+                Color::BLACK,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BENCH: &str = "\
+[apparatus brass-small]
+length = 25 cm
+mass = 0.2
+bob = disc
+damping_ratio = 0.002
+pixels_per_metre = 800
+notes = Brass bob on the short rod, bench 3
+
+[apparatus long]
+length = 1.5 m
+
+[history brass-small]
+at=1760437800 period=1.0034 damping_ratio=0.0021
+at=1760524200 period=1.0029
+";
+
+    fn scratch(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "pendulum-apparatus-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        directory.join(FILE_NAME)
+    }
+
+    #[test]
+    fn reads_the_profiles_and_their_histories() {
+        let profiles = parse(BENCH).unwrap();
+        assert_eq!(profiles.len(), 2);
+        let brass = &profiles[0];
+        assert_eq!(brass.id, "brass-small");
+        assert!((brass.length - 0.25).abs() < 1e-6 && brass.mass == 0.2);
+        assert!(brass.bob == BobShape::parse("disc").unwrap());
+        assert!(brass.damping_ratio == Some(0.002) && brass.pixels_per_metre == Some(800.0));
+        assert_eq!(brass.notes, "Brass bob on the short rod, bench 3");
+        assert_eq!(brass.history.len(), 2);
+        assert!(brass.history[0].timestamp == 1760437800 && brass.history[0].period == 1.0034);
+        assert!(brass.history[0].damping_ratio == Some(0.0021));
+        assert!(brass.history[1].damping_ratio.is_none());
+
+        // What isn't given is a plain point bob of mass 1.
+        let long = &profiles[1];
+        assert!(long.length == 1.5 && long.mass == 1.0 && long.bob == BobShape::Point);
+        assert!(long.damping_ratio.is_none() && long.notes.is_empty() && long.history.is_empty());
+    }
+
+    #[test]
+    fn profiles_round_trip_through_their_text() {
+        let profiles = parse(BENCH).unwrap();
+        for profile in &profiles {
+            let again = parse(&profile.to_text()).unwrap();
+            assert!(
+                again.len() == 1 && again[0] == *profile,
+                "{}",
+                profile.to_text()
+            );
+        }
+        let file = Profiles {
+            profiles: profiles.clone(),
+            path: PathBuf::new(),
+            broken: None,
+        };
+        assert!(parse(&file.to_text()).unwrap() == profiles);
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn each_profile_is_checked_as_it_is_read() {
+        for (text, error) in [
+            ("[apparatus a]\nmass = 2\n", "[apparatus a] has no `length`"),
+            (
+                "[apparatus a]\nlength = 1 m\nmass = -2\n",
+                "a: the mass must be positive",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\nmass = 0\n",
+                "a: the mass must be positive",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\ndamping_ratio = -0.1\n",
+                "a: the damping ratio can't be negative",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\nbob = cube\n",
+                "line 3: unknown bob shape `cube`",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\ncolour = red\n",
+                "line 3: unknown apparatus setting `colour = red`",
+            ),
+            (
+                "[apparatus a b]\nlength = 1 m\n",
+                "`a b` is not a profile name: use letters, digits, - and _",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\n[apparatus a]\nlength = 2 m\n",
+                "apparatus `a` is given twice",
+            ),
+            (
+                "[history a]\nat=1 period=1\n",
+                "history for unknown apparatus `a`",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\n[bench]\n",
+                "unknown section [bench]",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\n[history a]\nat=1 period=0\n",
+                "line 4: `period` must be positive",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\n[history a]\nperiod=1\n",
+                "line 4: missing `at`",
+            ),
+            (
+                "[apparatus a]\nlength = 1 m\n[history a]\nat=1 period=1 by=me\n",
+                "line 4: unknown measurement field `by=me`",
+            ),
+        ] {
+            assert_eq!(parse(text).err().unwrap(), error, "for {:?}", text);
+        }
+        for text in [
+            "[apparatus a]\nlength = -1 m\n",
+            "[apparatus a]\nlength = long\n",
+            "[apparatus a]\nlength = 1 m\npixels_per_metre = 0\n",
+            "[apparatus a]\nlength = 1 m\nmass = nan\n",
+        ] {
+            assert!(parse(text).is_err(), "read {:?}", text);
+        }
+        assert!(valid_id("bench_3-b") && !valid_id("") && !valid_id("a/b"));
+    }
+
+    #[test]
+    fn saving_a_pendulum_and_attaching_it_again() {
+        let units = Units::per_metre(400.0).unwrap();
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.zeta = Some(0.01);
+        let profile = Profile::of("half-metre", &p, &units, "on the stand").unwrap();
+        assert!((profile.length - 0.5).abs() < 1e-6);
+        assert!(profile.damping_ratio == Some(0.01) && profile.pixels_per_metre == Some(400.0));
+        assert!(parse(&profile.to_text()).unwrap()[0] == profile);
+        assert!(Profile::of("half-metre", &p, &Units::default(), "").is_err());
+        assert!(Profile::of("half metre", &p, &units, "").is_err());
+
+        // On a scene drawn twice as big, it's twice as long on screen.
+        let mut other = Pendulum::new(400.0, 0.0, 120.0);
+        other.angle = 0.4;
+        let mut scene = Units::per_metre(800.0).unwrap();
+        profile.apply(&mut other, &mut scene).unwrap();
+        assert!((other.r - 400.0).abs() < 1e-3 && other.angle == 0.4);
+        assert!(other
+            .damping_ratio()
+            .is_some_and(|zeta| (zeta - 0.01).abs() < 1e-6));
+        assert_eq!(other.apparatus, "half-metre");
+        // Uncalibrated, the scene takes the profile's scale.
+        let mut uncalibrated = Units::default();
+        profile.apply(&mut other, &mut uncalibrated).unwrap();
+        assert!(uncalibrated.pixels_per_metre() == Some(400.0) && (other.r - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_file_is_written_and_read_back() {
+        let path = scratch("file");
+        let mut profiles = Profiles::open(&path);
+        assert!(profiles.profiles.is_empty());
+        let brass = parse(BENCH).unwrap().remove(0);
+        let history = brass.history.clone();
+        assert_eq!(
+            profiles.put(brass.clone()).unwrap(),
+            "Saved apparatus brass-small"
+        );
+        profiles
+            .record("brass-small", Measurement::new(1.0031, None))
+            .unwrap();
+        assert!(profiles
+            .record("steel", Measurement::new(1.0, None))
+            .is_err());
+
+        let mut reread = Profiles::open(&path);
+        assert_eq!(reread.profiles.len(), 1);
+        assert!(reread.profiles[0].history.len() == history.len() + 1);
+        // Replacing keeps the history.
+        let heavier = Profile {
+            mass: 0.5,
+            history: Vec::new(),
+            ..brass
+        };
+        assert_eq!(
+            reread.put(heavier).unwrap(),
+            "Updated apparatus brass-small"
+        );
+        assert_eq!(reread.reload().unwrap(), 1);
+        let profile = reread.get("brass-small").unwrap();
+        assert!(profile.mass == 0.5 && profile.history.len() == history.len() + 1);
+        assert!(reread
+            .put(Profile {
+                mass: -1.0,
+                ..profile.clone()
+            })
+            .is_err());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_file_that_doesnt_read_is_never_written_over() {
+        let path = scratch("broken");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let text = "[apparatus a]\nmass = 2\n";
+        fs::write(&path, text).unwrap();
+        let mut profiles = Profiles::open(&path);
+        assert!(profiles.reload().is_err());
+        let long = parse(BENCH).unwrap().remove(1);
+        assert!(profiles
+            .put(long)
+            .err()
+            .unwrap()
+            .starts_with("Not writing over the profiles, which didn't read: "));
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+
+        // Once it's mended, it's written again.
+        fs::write(&path, BENCH).unwrap();
+        assert_eq!(profiles.reload().unwrap(), 2);
+        profiles
+            .record("long", Measurement::new(2.46, None))
+            .unwrap();
+        assert_eq!(
+            parse(&fs::read_to_string(&path).unwrap()).unwrap()[1]
+                .history
+                .len(),
+            1
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
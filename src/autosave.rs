@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::config::{key_value, parse_f32, Section};
+
+/// Seconds between autosaves unless `[autosave]` says otherwise.
+const DEFAULT_INTERVAL: f32 = 30.0;
+
+/// What to do with a recovery file found at startup.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Restore {
+    /// Ask, and let any other key skip it.
+    Ask,
+    Always,
+    Never,
+}
+
+/// Periodically writes the whole session to a recovery file, so a crash
+/// loses at most one interval of a long experiment.
+pub struct Autosave {
+    /// `None` when autosaving is off.
+    interval: Option<Duration>,
+    pub restore: Restore,
+    last: Instant,
+}
+
+impl Autosave {
+    pub fn new() -> Autosave {
+        Autosave {
+            interval: Some(Duration::from_secs_f32(DEFAULT_INTERVAL)),
+            restore: Restore::Ask,
+            last: Instant::now(),
+        }
+    }
+
+    /// Reads `interval = <seconds>` (0 turns autosaving off) and
+    /// `restore = ask|always|never` from an `[autosave]` section.
+    pub fn from_config(section: &Section) -> Result<Autosave, String> {
+        let mut autosave = Autosave::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("interval", value)) => {
+                    let seconds = parse_f32("interval", value).map_err(error)?;
+                    autosave.interval = (seconds > 0.0).then(|| Duration::from_secs_f32(seconds));
+                }
+                Some(("restore", value)) => {
+                    autosave.restore = match value {
+                        "ask" => Restore::Ask,
+                        "always" => Restore::Always,
+                        "never" => Restore::Never,
+                        _ => return Err(error(format!("unknown restore mode `{}`", value))),
+                    }
+                }
+                _ => return Err(error(format!("unknown autosave setting `{}`", line))),
+            }
+        }
+
+        Ok(autosave)
+    }
+
+    pub fn disable(&mut self) {
+        self.interval = None;
+        self.restore = Restore::Never;
+    }
+
+    /// True once per interval.
+    pub fn due(&mut self) -> bool {
+        match self.interval {
+            Some(interval) if self.last.elapsed() >= interval => {
+                self.last = Instant::now();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Writes `text` next to `path` first and then moves it over, so a crash
+    /// mid-write can't leave a half-written recovery file behind.
+    pub fn write(path: &Path, text: &str) -> Result<(), String> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, text)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| format!("could not write {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,897 @@
+//! A batched path for drawing the scene, for scenes of many pendulums. In
+//! place of a call to the window per shape, the frame's shapes are gathered
+//! into one list of coloured triangles, in the order they were drawn:
+//! circles as fans of as many triangles as their size on screen needs, and
+//! lines as quads, with the stretches of a line through many points kept
+//! whole. At the end of the frame they're filled in on the processor into
+//! one picture, which goes to the window in two calls. Without it, each
+//! shape goes to the window as it's drawn. It's off unless a `[render]`
+//! section turns it on:
+//!
+//! ```text
+//! [render]
+//! batched = true
+//! ```
+//!
+//! Only the scene is batched; the HUD and panels, and anything written to a
+//! file, are drawn as before.
+
+use std::f32::consts::TAU;
+
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::image::{ImageDataType, ImageHandle, ImageSmoothingMode};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::config::{key_value, Section};
+use crate::polyline::Strip;
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+
+/// Furthest a fan's edge may fall inside the circle, in pixels.
+const TOLERANCE: f32 = 0.25;
+/// Fewest and most triangles in a circle's fan.
+const MIN_SEGMENTS: usize = 6;
+const MAX_SEGMENTS: usize = 96;
+
+/// Reads `batched = true|false` from a `[render]` config section.
+pub fn batched_from_config(section: &Section) -> Result<bool, String> {
+    let mut batched = false;
+    for (line_number, line) in &section.lines {
+        match key_value(line) {
+            Some(("batched", value)) => {
+                batched = value.parse::<bool>().map_err(|_| {
+                    format!(
+                        "line {}: expected true or false for `batched`, found `{}`",
+                        line_number, value
+                    )
+                })?
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: unknown render setting `{}`",
+                    line_number, line
+                ))
+            }
+        }
+    }
+    Ok(batched)
+}
+
+/// Triangles in the fan of a circle `radius` pixels across on screen: the
+/// fewest whose edges, the chords, stay within `TOLERANCE` of the circle.
+/// A chord spanning an angle θ falls `r (1 - cos θ/2)` inside it.
+pub fn segments(radius: f32) -> usize {
+    if radius <= TOLERANCE {
+        return MIN_SEGMENTS;
+    }
+    let angle = 2.0 * (1.0 - TOLERANCE / radius).acos();
+    ((TAU / angle).ceil() as usize).clamp(MIN_SEGMENTS, MAX_SEGMENTS)
+}
+
+/// The frame's shapes as triangles, oldest first, and the stretches of its
+/// lines through many points, each kept with where it falls among them.
+pub struct Batch {
+    /// Three corners for each triangle, clockwise on screen.
+    positions: Vec<Vec2>,
+    /// The colour at each corner.
+    colors: Vec<Color>,
+    /// The stretches, each after as many triangles as it was drawn after.
+    bands: Vec<(usize, Band)>,
+    /// The shapes filled in, premultiplied, a pixel at a time.
+    layer: Vec<[f32; 4]>,
+    /// The layer as the window takes it, four bytes a pixel.
+    pixels: Vec<u8>,
+    /// The last picture handed over. The window may still have it in hand
+    /// after the frame, and it can't let go of the last hold on one then,
+    /// so the batch keeps it until the next replaces it.
+    image: Option<ImageHandle>,
+}
+
+/// A stretch of a line through many points between two of them, filled in
+/// one piece rather than as `Strip::draw_segment`'s six thin triangles: the
+/// quad out to its fringe, shaded by how far along the stretch and how far
+/// out from the line each pixel is.
+struct Band {
+    corners: [Vec2; 4],
+    start: Vec2,
+    /// The stretch's direction over its length, so a point's distance
+    /// along it over it is its fraction of the way.
+    along: Vec2,
+    /// The stretch's left-hand normal.
+    across: Vec2,
+    /// The half thicknesses of the line's solid core and its fringe.
+    core: f32,
+    outer: f32,
+    colors: [Color; 2],
+}
+
+impl Batch {
+    pub fn new() -> Batch {
+        Batch {
+            positions: Vec::new(),
+            colors: Vec::new(),
+            bands: Vec::new(),
+            layer: Vec::new(),
+            pixels: Vec::new(),
+            image: None,
+        }
+    }
+
+    pub fn triangles(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    /// The `i`th triangle's corners and their colours.
+    pub fn triangle(&self, i: usize) -> ([Vec2; 3], [Color; 3]) {
+        let at = 3 * i;
+        (
+            [
+                self.positions[at],
+                self.positions[at + 1],
+                self.positions[at + 2],
+            ],
+            [self.colors[at], self.colors[at + 1], self.colors[at + 2]],
+        )
+    }
+
+    fn push(&mut self, points: [Vec2; 3], colors: [Color; 3]) {
+        self.positions.extend(points);
+        self.colors.extend(colors);
+    }
+
+    /// Fills everything gathered into one picture the size of the window,
+    /// `size` pixels, and hands it to `graphics` in two calls: one to take
+    /// the picture in and one to draw it over what's there. The batch is
+    /// emptied for the next frame, keeping its memory.
+    pub fn flush(&mut self, graphics: &mut Graphics2D, size: (f32, f32)) {
+        let (width, height) = (size.0.max(0.0) as u32, size.1.max(0.0) as u32);
+        let drawn = self.triangles() > 0 || !self.bands.is_empty();
+        if drawn && width > 0 && height > 0 {
+            self.fill(width as usize, height as usize);
+            match graphics.create_image_from_raw_pixels(
+                ImageDataType::RGBA,
+                ImageSmoothingMode::NearestNeighbor,
+                (width, height),
+                &self.pixels,
+            ) {
+                Ok(image) => {
+                    graphics.draw_rectangle_image(
+                        Rectangle::from_tuples((0.0, 0.0), (width as f32, height as f32)),
+                        &image,
+                    );
+                    self.image = Some(image);
+                }
+                Err(e) => log!("Couldn't draw the batched scene: {:?}", e),
+            }
+        }
+        self.positions.clear();
+        self.colors.clear();
+        self.bands.clear();
+    }
+
+    /// Fills the shapes, in order, into `pixels`, a `width` by `height`
+    /// picture, each laid over those before as the window would lay it.
+    fn fill(&mut self, width: usize, height: usize) {
+        self.layer.clear();
+        self.layer.resize(width * height, [0.0; 4]);
+        let mut next = 0;
+        for (before, band) in &self.bands {
+            for i in next..*before {
+                let triangle = self.triangle(i);
+                fill_triangle(&mut self.layer, width, height, triangle);
+            }
+            fill_band(&mut self.layer, width, height, band);
+            next = *before;
+        }
+        for i in next..self.triangles() {
+            let triangle = self.triangle(i);
+            fill_triangle(&mut self.layer, width, height, triangle);
+        }
+
+        self.pixels.clear();
+        self.pixels
+            .extend(self.layer.iter().flat_map(|&[r, g, b, a]| {
+                // The window lays pictures on by their alpha, so the colour
+                // goes back to not being multiplied by it.
+                let scale = if a > 0.0 { 255.0 / a } else { 0.0 };
+                [r * scale, g * scale, b * scale, a * 255.0].map(|channel| (channel + 0.5) as u8)
+            }));
+    }
+}
+
+/// The least whole number at or above `at`, or 0 if that's less. Worked
+/// by casting, as rounding is slow without the newer instructions.
+fn ceil(at: f32) -> usize {
+    let below = at.max(0.0) as i32;
+    (below + ((below as f32) < at) as i32) as usize
+}
+
+/// The pixels a convex shape of up to four corners covers, a row at a
+/// time, top down: those whose centres fall from where the row goes into
+/// the shape up to where it comes out, so a pixel centred on an edge two
+/// shapes share goes to just one of them.
+struct Span {
+    /// The rows and columns of pixels whose centres could be inside.
+    top: usize,
+    bottom: usize,
+    left: usize,
+    right: usize,
+    /// For each edge the rows go in across, where the next row does, and
+    /// how far that moves from one row to the next; likewise for the edges
+    /// they come out across.
+    ins: [(f32, f32); 4],
+    outs: [(f32, f32); 4],
+}
+
+impl Span {
+    /// The shape with `corners`, a triangle's last given twice, as far as
+    /// it's over a picture `width` by `height`.
+    fn new(corners: [Vec2; 4], width: usize, height: usize) -> Option<Span> {
+        // Twice the area, its sign telling which way round the corners go so
+        // the inside can be put on the same side of every edge.
+        let area: f32 = (0..4)
+            .map(|k| {
+                let (from, to) = (corners[k], corners[(k + 1) % 4]);
+                from.x * to.y - to.x * from.y
+            })
+            .sum();
+        if !area.is_normal() {
+            return None;
+        }
+        let turn = area.signum();
+        let xs = corners.map(|corner| corner.x);
+        let ys = corners.map(|corner| corner.y);
+        let (left, right) = (
+            xs.into_iter().fold(f32::MAX, f32::min),
+            xs.into_iter().fold(f32::MIN, f32::max),
+        );
+        let (top, bottom) = (
+            ys.into_iter().fold(f32::MAX, f32::min),
+            ys.into_iter().fold(f32::MIN, f32::max),
+        );
+        let (left, right) = (ceil(left - 0.5), ceil(right - 0.5).min(width));
+        let (top, bottom) = (ceil(top - 0.5), ceil(bottom - 0.5).min(height));
+        if left >= right || top >= bottom {
+            return None;
+        }
+
+        // Along a row of pixels, each edge's side of the shape is where x
+        // is past some point, for an edge the row goes in across, or short
+        // of it, for one it comes out across, and that point moves by a
+        // fixed step from one row down to the next. A flat edge is the top
+        // or bottom of the rows already.
+        let first_row = top as f32 + 0.5;
+        let mut ins = [(f32::MIN, 0.0); 4];
+        let mut outs = [(f32::MAX, 0.0); 4];
+        for k in 0..4 {
+            let (from, to) = (corners[k], corners[(k + 1) % 4]);
+            let (rise, run) = (turn * (to.y - from.y), turn * (to.x - from.x));
+            if rise == 0.0 {
+                continue;
+            }
+            let at = from.x + (first_row - from.y) * run / rise;
+            let crossing = (at, run / rise);
+            if rise < 0.0 {
+                ins[k] = crossing;
+            } else {
+                outs[k] = crossing;
+            }
+        }
+        Some(Span {
+            top,
+            bottom,
+            left,
+            right,
+            ins,
+            outs,
+        })
+    }
+
+    /// The run of pixels the next row covers, from the first to one past
+    /// the last, if any.
+    fn next_row(&mut self) -> Option<(usize, usize)> {
+        let into = self.ins.iter().fold(f32::MIN, |max, &(at, _)| max.max(at));
+        let out_of = self.outs.iter().fold(f32::MAX, |min, &(at, _)| min.min(at));
+        for (at, step) in &mut self.ins {
+            *at += *step;
+        }
+        for (at, step) in &mut self.outs {
+            *at += *step;
+        }
+        let (from, to) = (
+            ceil(into - 0.5).max(self.left),
+            ceil(out_of - 0.5).min(self.right),
+        );
+        (from < to).then_some((from, to))
+    }
+}
+
+/// Lays `color`, premultiplied, over `pixel`.
+fn lay(pixel: &mut [f32; 4], color: [f32; 4]) {
+    let keep = 1.0 - color[3];
+    for channel in 0..4 {
+        pixel[channel] = color[channel] + pixel[channel] * keep;
+    }
+}
+
+/// A value known at `origin` and changing by `x` and `y` for a pixel along
+/// and down, sampled at the centre of the pixel at `(column, row)`.
+fn at_pixel(
+    origin: Vec2,
+    value: [f32; 4],
+    x: [f32; 4],
+    y: [f32; 4],
+    column: usize,
+    row: usize,
+) -> [f32; 4] {
+    let (dx, dy) = (column as f32 + 0.5 - origin.x, row as f32 + 0.5 - origin.y);
+    std::array::from_fn(|k| value[k] + dx * x[k] + dy * y[k])
+}
+
+/// Lays the triangle at `corners`, its colour shaded between theirs, over
+/// the pixels of `layer`, `width` by `height`, whose centres it covers.
+fn fill_triangle(
+    layer: &mut [[f32; 4]],
+    width: usize,
+    height: usize,
+    (corners, colors): ([Vec2; 3], [Color; 3]),
+) {
+    let [a, b, c] = corners;
+    let Some(mut span) = Span::new([a, b, c, c], width, height) else {
+        return;
+    };
+    // The colour's change along and down, from the plane through its value
+    // at each corner.
+    let channels = colors.map(|color| [color.r(), color.g(), color.b(), color.a()]);
+    let (ab, ac) = (b - a, c - a);
+    let area = ab.x * ac.y - ac.x * ab.y;
+    let x: [f32; 4] = std::array::from_fn(|k| {
+        ((channels[1][k] - channels[0][k]) * ac.y - (channels[2][k] - channels[0][k]) * ab.y) / area
+    });
+    let y: [f32; 4] = std::array::from_fn(|k| {
+        ((channels[2][k] - channels[0][k]) * ab.x - (channels[1][k] - channels[0][k]) * ac.x) / area
+    });
+    for row in span.top..span.bottom {
+        let Some((from, to)) = span.next_row() else {
+            continue;
+        };
+        let mut color = at_pixel(a, channels[0], x, y, from, row);
+        for pixel in &mut layer[row * width + from..row * width + to] {
+            let alpha = color[3];
+            lay(
+                pixel,
+                [color[0] * alpha, color[1] * alpha, color[2] * alpha, alpha],
+            );
+            for k in 0..4 {
+                color[k] += x[k];
+            }
+        }
+    }
+}
+
+/// Lays `band` over the pixels of `layer`, `width` by `height`, whose
+/// centres it covers: its colour shaded from one end's to the other's, and
+/// faded out over the fringe as `Strip::draw_segment` fades it.
+fn fill_band(layer: &mut [[f32; 4]], width: usize, height: usize, band: &Band) {
+    let Some(mut span) = Span::new(band.corners, width, height) else {
+        return;
+    };
+    // How far along, as a fraction, and how far out from the line the
+    // centre of the first pixel is, and the change in each from one pixel
+    // to the next along and down.
+    let (along, across) = (band.along, band.across);
+    let fringe = 1.0 / (band.outer - band.core);
+    let [first, last] = band
+        .colors
+        .map(|color| [color.r(), color.g(), color.b(), color.a()]);
+    for row in span.top..span.bottom {
+        let Some((from, to)) = span.next_row() else {
+            continue;
+        };
+        let [mut fraction, mut out, ..] = at_pixel(
+            band.start,
+            [0.0; 4],
+            [along.x, across.x, 0.0, 0.0],
+            [along.y, across.y, 0.0, 0.0],
+            from,
+            row,
+        );
+        for pixel in &mut layer[row * width + from..row * width + to] {
+            let t = fraction.clamp(0.0, 1.0);
+            let cover = ((band.outer - out.abs()) * fringe).clamp(0.0, 1.0);
+            let alpha = (first[3] + t * (last[3] - first[3])) * cover;
+            lay(
+                pixel,
+                [
+                    (first[0] + t * (last[0] - first[0])) * alpha,
+                    (first[1] + t * (last[1] - first[1])) * alpha,
+                    (first[2] + t * (last[2] - first[2])) * alpha,
+                    alpha,
+                ],
+            );
+            fraction += along.x;
+            out += across.x;
+        }
+    }
+}
+
+impl Renderer for Batch {
+    fn draw_line(&mut self, start: (f32, f32), end: (f32, f32), thickness: f32, color: Color) {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            return;
+        }
+        let (nx, ny) = (
+            -dy / length * thickness / 2.0,
+            dx / length * thickness / 2.0,
+        );
+        self.draw_quad(
+            [
+                Vec2::new(start.0 + nx, start.1 + ny),
+                Vec2::new(end.0 + nx, end.1 + ny),
+                Vec2::new(end.0 - nx, end.1 - ny),
+                Vec2::new(start.0 - nx, start.1 - ny),
+            ],
+            color,
+        );
+    }
+
+    fn draw_circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        let count = segments(radius);
+        let center = Vec2::new(center.0, center.1);
+        let rim = |i: usize| {
+            let (sin, cos) = (TAU * i as f32 / count as f32).sin_cos();
+            Vec2::new(center.x + radius * cos, center.y + radius * sin)
+        };
+        for i in 0..count {
+            self.push([center, rim(i), rim(i + 1)], [color; 3]);
+        }
+    }
+
+    fn draw_rectangle(&mut self, rectangle: Rectangle, color: Color) {
+        let (top_left, bottom_right) = (*rectangle.top_left(), *rectangle.bottom_right());
+        self.draw_quad(
+            [
+                top_left,
+                Vec2::new(bottom_right.x, top_left.y),
+                bottom_right,
+                Vec2::new(top_left.x, bottom_right.y),
+            ],
+            color,
+        );
+    }
+
+    fn draw_triangle(&mut self, points: [Vec2; 3], color: Color) {
+        self.push(points, [color; 3]);
+    }
+
+    fn draw_quad(&mut self, points: [Vec2; 4], color: Color) {
+        self.draw_quad_four_color(points, [color; 4]);
+    }
+
+    fn draw_quad_four_color(&mut self, points: [Vec2; 4], colors: [Color; 4]) {
+        self.push(
+            [points[0], points[1], points[2]],
+            [colors[0], colors[1], colors[2]],
+        );
+        self.push(
+            [points[2], points[3], points[0]],
+            [colors[2], colors[3], colors[0]],
+        );
+    }
+
+    /// Each stretch as one `Band`, but for one so sharply bent its quad
+    /// isn't convex, which is drawn as the strip would draw it.
+    fn draw_polyline(&mut self, points: &[Vector], thickness: f32, colors: &[Color]) {
+        let Some(strip) = Strip::new(points, thickness, colors) else {
+            return;
+        };
+        for i in 0..strip.segments() {
+            let (outer, core) = (strip.outer, strip.core);
+            let corners = [
+                strip.at(i, outer),
+                strip.at(i + 1, outer),
+                strip.at(i + 1, -outer),
+                strip.at(i, -outer),
+            ];
+            if !convex(corners) {
+                strip.draw_segment(self, i);
+                continue;
+            }
+            let (start, end) = (strip.points[i], strip.points[i + 1]);
+            let (dx, dy) = (end.x - start.x, end.y - start.y);
+            let length_squared = dx * dx + dy * dy;
+            let length = length_squared.sqrt();
+            let band = Band {
+                corners,
+                start: Vec2::new(start.x, start.y),
+                along: Vec2::new(dx / length_squared, dy / length_squared),
+                across: Vec2::new(dy / length, -dx / length),
+                core,
+                outer,
+                colors: [strip.colors[i], strip.colors[i + 1]],
+            };
+            self.bands.push((self.triangles(), band));
+        }
+    }
+}
+
+/// Whether the quad with `corners` turns the same way at each of them.
+fn convex(corners: [Vec2; 4]) -> bool {
+    let turns = (0..4).map(|k| {
+        let (a, b, c) = (corners[k], corners[(k + 1) % 4], corners[(k + 2) % 4]);
+        (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x)
+    });
+    let (mut left, mut right) = (false, false);
+    for turn in turns {
+        left |= turn > 0.0;
+        right |= turn < 0.0;
+    }
+    !(left && right)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_char, c_void, CString};
+    use std::ptr::null;
+    use std::time::{Duration, Instant};
+
+    use libloading::Library;
+    use speedy2d::GLRenderer;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::model::Model;
+    use crate::pendulum::Pendulum;
+    use crate::polyline;
+    use crate::viewport::{Viewport, WORLD_SIZE};
+
+    #[test]
+    fn a_circle_gets_more_triangles_the_bigger_it_is_on_screen() {
+        assert_eq!(segments(0.0), MIN_SEGMENTS);
+        assert_eq!(segments(1.0), MIN_SEGMENTS);
+        assert_eq!(segments(10_000.0), MAX_SEGMENTS);
+        let mut last = 0;
+        for radius in [2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0] {
+            let count = segments(radius);
+            assert!(count >= last, "{} triangles at radius {}", count, radius);
+            last = count;
+            if count < MAX_SEGMENTS {
+                let sagitta = radius * (1.0 - (TAU / count as f32 / 2.0).cos());
+                assert!(
+                    sagitta <= TOLERANCE + 1e-4,
+                    "{} px out at radius {}",
+                    sagitta,
+                    radius
+                );
+            }
+        }
+
+        let mut batch = Batch::new();
+        batch.draw_circle((0.0, 0.0), 40.0, Color::RED);
+        assert_eq!(batch.triangles(), segments(40.0));
+        for i in 0..batch.triangles() {
+            let (points, _) = batch.triangle(i);
+            for point in &points[1..] {
+                assert!((point.x.hypot(point.y) - 40.0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn each_shape_keeps_its_colour_and_its_place_in_the_order() {
+        let mut batch = Batch::new();
+        batch.draw_line((0.0, 0.0), (10.0, 0.0), 2.0, Color::RED);
+        batch.draw_circle((5.0, 5.0), 3.0, Color::GREEN);
+        batch.draw_rectangle(Rectangle::from_tuples((0.0, 0.0), (4.0, 4.0)), Color::BLUE);
+        let corners = [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE];
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].map(Vec2::from);
+        batch.draw_quad_four_color(square, corners);
+        batch.draw_line((3.0, 3.0), (3.0, 3.0), 2.0, Color::BLACK);
+
+        let circle = segments(3.0);
+        assert_eq!(batch.triangles(), 2 + circle + 2 + 2);
+        let color_of = |i: usize| batch.triangle(i).1;
+        assert_eq!(color_of(0), [Color::RED; 3]);
+        assert_eq!(color_of(1), [Color::RED; 3]);
+        for i in 2..2 + circle {
+            assert_eq!(color_of(i), [Color::GREEN; 3]);
+        }
+        assert_eq!(color_of(2 + circle), [Color::BLUE; 3]);
+        assert_eq!(
+            color_of(4 + circle),
+            [Color::RED, Color::GREEN, Color::BLUE]
+        );
+        assert_eq!(
+            color_of(5 + circle),
+            [Color::BLUE, Color::WHITE, Color::RED]
+        );
+    }
+
+    #[test]
+    fn a_line_is_a_quad_as_wide_as_it_is_thick() {
+        let mut batch = Batch::new();
+        batch.draw_line((0.0, 0.0), (0.0, 10.0), 4.0, Color::RED);
+        let ([a, b, c], _) = batch.triangle(0);
+        let ([_, d, _], _) = batch.triangle(1);
+        let xs = [a.x, b.x, c.x, d.x];
+        assert_eq!(xs.iter().cloned().fold(f32::MAX, f32::min), -2.0);
+        assert_eq!(xs.iter().cloned().fold(f32::MIN, f32::max), 2.0);
+        assert_eq!((a.y, b.y, c.y, d.y), (0.0, 10.0, 10.0, 0.0));
+    }
+
+    /// The batch filled into a picture `width` by `height`, as RGBA, a
+    /// pixel at a time.
+    fn fill(
+        batch: &mut Batch,
+        width: usize,
+        height: usize,
+    ) -> impl Fn(usize, usize) -> [u8; 4] + '_ {
+        batch.fill(width, height);
+        move |x, y| {
+            let at = 4 * (y * width + x);
+            [0, 1, 2, 3].map(|k| batch.pixels[at + k])
+        }
+    }
+
+    #[test]
+    fn each_pixel_a_shape_covers_is_laid_over_once() {
+        // A translucent rectangle is two triangles sharing a diagonal; a
+        // pixel on it laid over twice would be the darker.
+        let mut batch = Batch::new();
+        let half = Color::from_rgba(1.0, 0.0, 0.0, 0.5);
+        batch.draw_rectangle(Rectangle::from_tuples((1.0, 1.0), (5.0, 4.0)), half);
+        batch.draw_quad(
+            [(6.0, 0.0), (9.0, 2.0), (7.5, 6.0), (5.5, 3.0)].map(Vec2::from),
+            half,
+        );
+        let pixel = fill(&mut batch, 10, 6);
+        let mut covered = 0;
+        for y in 0..6 {
+            for x in 0..10 {
+                match pixel(x, y) {
+                    [0, 0, 0, 0] => {}
+                    color => {
+                        assert_eq!(color, [255, 0, 0, 128], "at ({}, {})", x, y);
+                        covered += 1;
+                    }
+                }
+            }
+        }
+        for (x, y) in [(1, 1), (4, 3), (2, 2)] {
+            assert_ne!(pixel(x, y), [0; 4]);
+        }
+        assert_eq!(pixel(5, 1), [0; 4]);
+        assert!(covered > 12);
+    }
+
+    #[test]
+    fn a_line_through_many_points_fades_out_over_its_fringe() {
+        let mut batch = Batch::new();
+        let line = [Vector::new(2.0, 10.0), Vector::new(20.0, 10.0)];
+        batch.draw_polyline(&line, 3.0, &[Color::BLUE; 2]);
+        assert_eq!((batch.triangles(), batch.bands.len()), (0, 1));
+        let pixel = fill(&mut batch, 24, 16);
+        // Half a pixel from the middle is in the core, a pixel and a half
+        // halfway across the fringe, and two and a half past it.
+        for y in [9, 10] {
+            assert_eq!(pixel(10, y), [0, 0, 255, 255]);
+        }
+        for y in [8, 11] {
+            assert_eq!(pixel(10, y), [0, 0, 255, 128]);
+        }
+        for y in [7, 12] {
+            assert_eq!(pixel(10, y), [0; 4]);
+        }
+        assert_eq!(pixel(1, 10), [0; 4]);
+        assert_eq!(pixel(21, 10), [0; 4]);
+    }
+
+    #[test]
+    fn lines_through_many_points_keep_their_place_among_the_shapes() {
+        let mut batch = Batch::new();
+        batch.draw_rectangle(Rectangle::from_tuples((0.0, 0.0), (12.0, 12.0)), Color::RED);
+        let line = [Vector::new(0.0, 6.0), Vector::new(12.0, 6.0)];
+        batch.draw_polyline(&line, 4.0, &[Color::BLUE, Color::GREEN]);
+        batch.draw_rectangle(
+            Rectangle::from_tuples((8.0, 0.0), (12.0, 12.0)),
+            Color::WHITE,
+        );
+        assert_eq!(batch.bands[0].0, 2);
+        let pixel = fill(&mut batch, 12, 12);
+        assert_eq!(pixel(2, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(10, 6), [255, 255, 255, 255]);
+        // Shaded from one end's colour to the other's along the way.
+        let [r, g, b, a] = pixel(4, 6);
+        assert_eq!((r, a), (0, 255));
+        assert!(b > g && g > 0, "{:?}", [r, g, b, a]);
+    }
+
+    #[test]
+    fn batching_is_off_unless_turned_on() {
+        let batched = |text: &str| {
+            let config = Config::parse(text).unwrap();
+            batched_from_config(config.section("render").unwrap())
+        };
+        assert!(!batched("[render]\n").unwrap());
+        assert!(batched("[render]\nbatched = true\n").unwrap());
+        assert!(batched("[render]\nbatched = yes\n").is_err());
+        assert!(batched("[render]\nfast = true\n").is_err());
+    }
+
+    /// A window's drawing without the window: a GL context from Mesa's
+    /// EGL on an offscreen surface, `size` pixels.
+    struct Headless {
+        renderer: GLRenderer,
+        _egl: Library,
+    }
+
+    impl Headless {
+        fn new(size: (u32, u32)) -> Result<Headless, String> {
+            type Pointer = *const c_void;
+            const PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+            const OPENGL_API: u32 = 0x30A2;
+            const NONE: i32 = 0x3038;
+            const CONFIG: [i32; 13] = [
+                0x3033, 0x0001, // a pbuffer surface
+                0x3040, 0x0008, // desktop GL
+                0x3024, 8, 0x3023, 8, 0x3022, 8, 0x3021, 8, // 8 bits a channel
+                NONE,
+            ];
+            let surface_size = [0x3057, size.0 as i32, 0x3056, size.1 as i32, NONE];
+
+            // Safety: each function is looked up by its name in the EGL
+            // specification and given the arguments it declares there.
+            unsafe {
+                let egl = Library::new("libEGL.so.1").map_err(|e| e.to_string())?;
+                macro_rules! function {
+                    ($name:literal: $($argument:ty),* => $result:ty) => {
+                        *egl.get::<unsafe extern "C" fn($($argument),*) -> $result>($name)
+                            .map_err(|e| e.to_string())?
+                    };
+                }
+                let get_proc_address = function!(b"eglGetProcAddress": *const c_char => Pointer);
+                let get_display =
+                    function!(b"eglGetPlatformDisplay": u32, Pointer, *const isize => Pointer);
+                let initialize = function!(b"eglInitialize": Pointer, *mut i32, *mut i32 => u32);
+                let bind_api = function!(b"eglBindAPI": u32 => u32);
+                let choose_config = function!(
+                    b"eglChooseConfig": Pointer, *const i32, *mut Pointer, i32, *mut i32 => u32
+                );
+                let create_surface =
+                    function!(b"eglCreatePbufferSurface": Pointer, Pointer, *const i32 => Pointer);
+                let create_context = function!(b"eglCreateContext": Pointer, Pointer, Pointer, *const i32 => Pointer);
+                let make_current =
+                    function!(b"eglMakeCurrent": Pointer, Pointer, Pointer, Pointer => u32);
+
+                let display = get_display(PLATFORM_SURFACELESS_MESA, null(), null());
+                let (mut major, mut minor) = (0, 0);
+                if display.is_null() || initialize(display, &mut major, &mut minor) == 0 {
+                    return Err("no EGL display".into());
+                }
+                let (mut config, mut configs) = (null(), 0);
+                if bind_api(OPENGL_API) == 0
+                    || choose_config(display, CONFIG.as_ptr(), &mut config, 1, &mut configs) == 0
+                    || configs == 0
+                {
+                    return Err("no EGL config for desktop GL".into());
+                }
+                let surface = create_surface(display, config, surface_size.as_ptr());
+                let context = create_context(display, config, null(), [NONE].as_ptr());
+                if surface.is_null()
+                    || context.is_null()
+                    || make_current(display, surface, surface, context) == 0
+                {
+                    return Err("no EGL context".into());
+                }
+                let renderer = GLRenderer::new_for_gl_context(size, |name| {
+                    let name = CString::new(name).unwrap();
+                    get_proc_address(name.as_ptr())
+                })
+                .map_err(|e| format!("{:?}", e))?;
+                Ok(Headless {
+                    renderer,
+                    _egl: egl,
+                })
+            }
+        }
+
+        /// The time to draw a frame with `draw` and read it back, which
+        /// waits for the GL to finish it.
+        fn time(&mut self, mut draw: impl FnMut(&mut Graphics2D)) -> Duration {
+            let started = Instant::now();
+            self.renderer.draw_frame(|graphics| {
+                graphics.clear_screen(Color::BLACK);
+                draw(graphics);
+                graphics.capture(ImageDataType::RGB);
+            });
+            started.elapsed()
+        }
+    }
+
+    /// The frame of 5,000 pendulums with trails drawn shape by shape and
+    /// batched, in a real GL context, each the fastest of a few. Run by
+    /// hand, with `--release -- --ignored`, as it needs Mesa's EGL and
+    /// takes a while without a graphics card.
+    #[test]
+    #[ignore]
+    fn benchmark_5000_pendulums_with_trails() {
+        let size = (WORLD_SIZE.0 as u32, WORLD_SIZE.1 as u32);
+        let mut gl = Headless::new(size).expect("a GL context from Mesa's EGL");
+        let view = Viewport::new(false);
+        let mut pendulums: Vec<Pendulum> = (0..5000)
+            .map(|i| {
+                let mut p = Pendulum::new(400.0, 100.0, 80.0 + (i % 200) as f32);
+                p.angle = 0.3 + (i % 97) as f32 * 0.02;
+                p
+            })
+            .collect();
+        let mut trails: Vec<Vec<Vector>> = pendulums.iter().map(|_| Vec::new()).collect();
+        for _ in 0..120 {
+            for (p, trail) in pendulums.iter_mut().zip(&mut trails) {
+                p.update();
+                let (x, y) = view.world_to_screen(p.position);
+                trail.push(Vector::new(x, y));
+            }
+        }
+        let draw = |graphics: &mut dyn Renderer| {
+            for (p, trail) in pendulums.iter().zip(&trails) {
+                polyline::draw_polyline(graphics, trail, 2.0, Color::GRAY);
+                p.draw(graphics, &view);
+            }
+        };
+
+        const FRAMES: usize = 3;
+        let unbatched = (0..FRAMES)
+            .map(|_| gl.time(|graphics| draw(graphics)))
+            .min()
+            .unwrap();
+        let mut batch = Batch::new();
+        let batched = (0..FRAMES)
+            .map(|_| {
+                gl.time(|graphics| {
+                    draw(&mut batch);
+                    batch.flush(graphics, WORLD_SIZE);
+                })
+            })
+            .min()
+            .unwrap();
+        println!("unbatched: {:?} a frame; batched: {:?}", unbatched, batched);
+
+        // And it's the same picture, to within rounding and the edges of
+        // the bobs, which the window smooths and the batch doesn't.
+        let mut frames = Vec::new();
+        for batched in [false, true] {
+            gl.renderer.draw_frame(|graphics| {
+                graphics.clear_screen(Color::BLACK);
+                if batched {
+                    draw(&mut batch);
+                    batch.flush(graphics, WORLD_SIZE);
+                } else {
+                    draw(graphics);
+                }
+                frames.push(graphics.capture(ImageDataType::RGB).data().clone());
+            });
+        }
+        let difference: u64 = frames[0]
+            .iter()
+            .zip(&frames[1])
+            .map(|(a, b)| a.abs_diff(*b) as u64)
+            .sum();
+        let mean = difference as f64 / frames[0].len() as f64;
+        assert!(
+            mean < 2.0,
+            "the frames are {} levels apart on average",
+            mean
+        );
+        assert!(
+            unbatched >= 3 * batched,
+            "batched {:?} against {:?} unbatched",
+            batched,
+            unbatched
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Beat drive, built with the `beat` feature: the selected pendulum is
+//! kicked once per beat of a fixed tempo, so it swings in time with music
+//! played at that tempo. Each kick is the console's `impulse`, given in the
+//! direction the bob is already moving so the beats pump the swing up like
+//! a child on a swing. The beats are counted on the simulation clock, so
+//! pausing holds them too. The tempo is set by hand; there's no audio
+//! input, so nothing beyond the standard library is needed.
+
+use speedy2d::color::Color;
+use speedy2d::Graphics2D;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::pendulum::Pendulum;
+use crate::STEPS_PER_SECOND;
+
+const DEFAULT_BPM: f32 = 60.0;
+const MIN_BPM: f32 = 20.0;
+const MAX_BPM: f32 = 240.0;
+/// Angular velocity each beat adds, in rad/s, unless `[beat]` says
+/// otherwise.
+const DEFAULT_KICK: f32 = 0.3;
+/// Frames the beat light stays lit after a kick.
+const FLASH_FRAMES: usize = 6;
+/// Where the beat light is drawn, and its radius.
+const LIGHT: (f32, f32, f32) = (780.0, 20.0, 10.0);
+
+pub struct BeatDrive {
+    pub enabled: bool,
+    bpm: f32,
+    /// How far into each beat the kick comes, as a fraction of the beat.
+    offset: f32,
+    kick: f32,
+    /// The last beat counted, and frames since its kick.
+    last_beat: Option<i64>,
+    since_kick: usize,
+}
+
+impl BeatDrive {
+    pub fn new() -> BeatDrive {
+        BeatDrive {
+            enabled: false,
+            bpm: DEFAULT_BPM,
+            offset: 0.0,
+            kick: DEFAULT_KICK,
+            last_beat: None,
+            since_kick: FLASH_FRAMES,
+        }
+    }
+
+    /// Reads `enabled = true|false`, `bpm = <beats per minute>`,
+    /// `offset = <fraction of a beat>` and `kick = <rad/s>` from a `[beat]`
+    /// section.
+    pub fn from_config(section: &Section) -> Result<BeatDrive, String> {
+        let mut beat = BeatDrive::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("enabled", value)) => {
+                    beat.enabled = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `enabled`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                Some(("bpm", value)) => beat
+                    .set_bpm(parse_f32("bpm", value).map_err(error)?)
+                    .map_err(error)?,
+                Some(("offset", value)) => {
+                    beat.set_offset(parse_f32("offset", value).map_err(error)?)
+                }
+                Some(("kick", value)) => {
+                    beat.kick = parse_f32("kick", value).map_err(error)?;
+                    if beat.kick <= 0.0 {
+                        return Err(error("`kick` must be positive".to_string()));
+                    }
+                }
+                _ => return Err(error(format!("unknown beat setting `{}`", line))),
+            }
+        }
+
+        Ok(beat)
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) -> Result<(), String> {
+        if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+            return Err(format!(
+                "the tempo must be between {} and {} BPM",
+                MIN_BPM, MAX_BPM
+            ));
+        }
+        self.bpm = bpm;
+        Ok(())
+    }
+
+    /// Any offset is taken round into one beat.
+    pub fn set_offset(&mut self, offset: f32) {
+        self.offset = offset.rem_euclid(1.0);
+    }
+
+    /// Kicks `p` if a beat has come since the last step. Called once a step;
+    /// returns whether it kicked.
+    pub fn step(&mut self, p: &mut Pendulum) -> bool {
+        self.since_kick = self.since_kick.saturating_add(1);
+        if !self.enabled {
+            self.last_beat = None;
+            return false;
+        }
+
+        let beats = p.time / STEPS_PER_SECOND * self.bpm / 60.0 - self.offset;
+        let beat = beats.floor() as i64;
+        let due = self.last_beat.is_some_and(|last| beat > last);
+        self.last_beat = Some(beat);
+        if !due {
+            return false;
+        }
+
+        let direction = if p.angular_velocity < 0.0 { -1.0 } else { 1.0 };
+        p.angular_velocity += direction * self.kick / STEPS_PER_SECOND;
+        self.since_kick = 0;
+        true
+    }
+
+    /// The `beat` console command: `beat on|off`, `beat bpm <n>` or
+    /// `beat offset <fraction>`, or on its own to show the settings.
+    pub fn command(&mut self, words: &[&str]) -> Result<Vec<String>, String> {
+        match words {
+            [] => {}
+            ["on"] => self.enabled = true,
+            ["off"] => self.enabled = false,
+            ["bpm", bpm] => {
+                let bpm = bpm.parse().map_err(|_| format!("Not a tempo: `{}`", bpm))?;
+                self.set_bpm(bpm)?;
+            }
+            ["offset", offset] => {
+                let offset = offset
+                    .parse()
+                    .map_err(|_| format!("Not a fraction of a beat: `{}`", offset))?;
+                self.set_offset(offset);
+            }
+            _ => return Err("Usage: beat [on|off|bpm <n>|offset <fraction>]".to_string()),
+        }
+        Ok(vec![self.hud_line().unwrap_or_else(|| {
+            format!("Beat drive off ({:.0} BPM)", self.bpm)
+        })])
+    }
+
+    pub fn hud_line(&self) -> Option<String> {
+        self.enabled.then(|| {
+            format!(
+                "Beat drive: {:.0} BPM, offset {:.2} beat",
+                self.bpm, self.offset
+            )
+        })
+    }
+
+    /// A light in the top right corner that flashes on each kick.
+    pub fn draw(&self, graphics: &mut Graphics2D) {
+        if !self.enabled {
+            return;
+        }
+        let (x, y, radius) = LIGHT;
+        let lit = self.since_kick < FLASH_FRAMES;
+        let color = if lit {
+            Color::from_rgb(0.9, 0.2, 0.2)
+        } else {
+            Color::from_rgb(0.5, 0.5, 0.5)
+        };
+        graphics.draw_circle((x, y), radius, color);
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+
+use speedy2d::window::VirtualKeyCode;
+
+use crate::config::{key_value, Section};
+
+/// Everything a key can do.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    IncreaseGravity,
+    DecreaseGravity,
+    IncreaseMass,
+    DecreaseMass,
+    Reset,
+    ToggleFriction,
+    CycleDamping,
+    SoftStart,
+    LowerEnergy,
+    RaiseEnergy,
+    CycleBob,
+    CycleIntegrator,
+    CycleRate,
+    ToggleString,
+    MotorLeft,
+    MotorRight,
+    FlagGame,
+    PracticeMode,
+    ToggleScrub,
+    TogglePause,
+    SelectNext,
+    Focus,
+    SaveScene,
+    LoadScene,
+    StrobeSource,
+    StrobeCopies,
+    ToggleChart,
+    Impulse,
+    PoincareExport,
+    ExportReport,
+    ExportSvg,
+    ExportTrajectory,
+    InstantReplay,
+    HysteresisSweep,
+    ToggleGrid,
+    SetPeriod,
+    TogglePhase,
+    ToggleSensor,
+    ToggleMetronome,
+    SlowerTempo,
+    FasterTempo,
+    DampingExplorer,
+    ResultsPanel,
+    ApparatusPanel,
+    AccuracyDashboard,
+    Annotate,
+    JumpToNote,
+    Console,
+    ToggleEnergy,
+    DrawObstacle,
+    ToggleWalls,
+    Calibrate,
+    Frame,
+    ToggleEnvelope,
+    GravityField,
+    TurningPoints,
+    EnergyPartition,
+    RhythmColors,
+    Sensitivity,
+    EnergyBudget,
+    ReplayMacro,
+    Help,
+    Menu,
+    Preset(usize),
+}
+
+/// Groups the cheat sheet lists the keys under.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Category {
+    /// What changes the swing, and the scenes and games it's in.
+    Physics,
+    /// Overlays, plots and panels.
+    View,
+    /// Saving, measuring and playing back.
+    Recording,
+}
+
+pub const CATEGORIES: [Category; 3] = [Category::Physics, Category::View, Category::Recording];
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Physics => "Physics",
+            Category::View => "View",
+            Category::Recording => "Recording",
+        }
+    }
+
+    /// Spelled out action by action, so a new one can't be left out.
+    pub fn of(action: Action) -> Category {
+        use Action::*;
+        match action {
+            IncreaseGravity | DecreaseGravity | IncreaseMass | DecreaseMass | Reset
+            | ToggleFriction | CycleDamping | SoftStart | LowerEnergy | RaiseEnergy | CycleBob
+            | CycleIntegrator | CycleRate | ToggleString | MotorLeft | MotorRight | FlagGame
+            | PracticeMode | TogglePause | SelectNext | Impulse | SetPeriod | SlowerTempo
+            | FasterTempo | DrawObstacle | ToggleWalls | Preset(_) => Category::Physics,
+            StrobeSource | StrobeCopies | ToggleChart | ToggleGrid | TogglePhase | ToggleSensor
+            | ToggleMetronome | DampingExplorer | AccuracyDashboard | Console | ToggleEnergy
+            | ToggleEnvelope | GravityField | TurningPoints | EnergyPartition | RhythmColors
+            | Sensitivity | EnergyBudget | Calibrate | Frame | Focus | Help | Menu => {
+                Category::View
+            }
+            ToggleScrub | SaveScene | LoadScene | PoincareExport | ExportReport | ExportSvg
+            | ExportTrajectory | InstantReplay | HysteresisSweep | ResultsPanel
+            | ApparatusPanel | Annotate | JumpToNote | ReplayMacro => Category::Recording,
+        }
+    }
+}
+
+/// Config name and default key of every action.
+const ACTIONS: &[(&str, Action, VirtualKeyCode)] = &[
+    (
+        "increase_gravity",
+        Action::IncreaseGravity,
+        VirtualKeyCode::Up,
+    ),
+    (
+        "decrease_gravity",
+        Action::DecreaseGravity,
+        VirtualKeyCode::Down,
+    ),
+    ("increase_mass", Action::IncreaseMass, VirtualKeyCode::Right),
+    ("decrease_mass", Action::DecreaseMass, VirtualKeyCode::Left),
+    ("reset", Action::Reset, VirtualKeyCode::R),
+    ("toggle_friction", Action::ToggleFriction, VirtualKeyCode::F),
+    ("cycle_damping", Action::CycleDamping, VirtualKeyCode::X),
+    ("soft_start", Action::SoftStart, VirtualKeyCode::Z),
+    ("lower_energy", Action::LowerEnergy, VirtualKeyCode::Minus),
+    ("raise_energy", Action::RaiseEnergy, VirtualKeyCode::Equals),
+    ("cycle_bob", Action::CycleBob, VirtualKeyCode::K),
+    (
+        "cycle_integrator",
+        Action::CycleIntegrator,
+        VirtualKeyCode::Semicolon,
+    ),
+    ("cycle_rate", Action::CycleRate, VirtualKeyCode::Comma),
+    ("toggle_string", Action::ToggleString, VirtualKeyCode::Y),
+    ("motor_left", Action::MotorLeft, VirtualKeyCode::A),
+    ("motor_right", Action::MotorRight, VirtualKeyCode::D),
+    ("flag_game", Action::FlagGame, VirtualKeyCode::G),
+    ("practice_mode", Action::PracticeMode, VirtualKeyCode::P),
+    ("toggle_scrub", Action::ToggleScrub, VirtualKeyCode::T),
+    ("toggle_pause", Action::TogglePause, VirtualKeyCode::Space),
+    ("select_next", Action::SelectNext, VirtualKeyCode::PageDown),
+    ("focus", Action::Focus, VirtualKeyCode::Tab),
+    ("save_scene", Action::SaveScene, VirtualKeyCode::S),
+    ("load_scene", Action::LoadScene, VirtualKeyCode::L),
+    ("strobe_source", Action::StrobeSource, VirtualKeyCode::B),
+    ("strobe_copies", Action::StrobeCopies, VirtualKeyCode::N),
+    ("toggle_chart", Action::ToggleChart, VirtualKeyCode::C),
+    ("impulse", Action::Impulse, VirtualKeyCode::I),
+    ("poincare_export", Action::PoincareExport, VirtualKeyCode::W),
+    (
+        "export_report",
+        Action::ExportReport,
+        VirtualKeyCode::Apostrophe,
+    ),
+    ("export_svg", Action::ExportSvg, VirtualKeyCode::End),
+    (
+        "export_trajectory",
+        Action::ExportTrajectory,
+        VirtualKeyCode::PageUp,
+    ),
+    (
+        "instant_replay",
+        Action::InstantReplay,
+        VirtualKeyCode::Delete,
+    ),
+    (
+        "hysteresis_sweep",
+        Action::HysteresisSweep,
+        VirtualKeyCode::F4,
+    ),
+    ("toggle_grid", Action::ToggleGrid, VirtualKeyCode::M),
+    ("set_period", Action::SetPeriod, VirtualKeyCode::E),
+    ("toggle_phase", Action::TogglePhase, VirtualKeyCode::V),
+    ("toggle_sensor", Action::ToggleSensor, VirtualKeyCode::U),
+    (
+        "toggle_metronome",
+        Action::ToggleMetronome,
+        VirtualKeyCode::Q,
+    ),
+    (
+        "slower_tempo",
+        Action::SlowerTempo,
+        VirtualKeyCode::LBracket,
+    ),
+    (
+        "faster_tempo",
+        Action::FasterTempo,
+        VirtualKeyCode::RBracket,
+    ),
+    (
+        "damping_explorer",
+        Action::DampingExplorer,
+        VirtualKeyCode::F8,
+    ),
+    ("results_panel", Action::ResultsPanel, VirtualKeyCode::F11),
+    (
+        "apparatus_panel",
+        Action::ApparatusPanel,
+        VirtualKeyCode::Insert,
+    ),
+    (
+        "accuracy_dashboard",
+        Action::AccuracyDashboard,
+        VirtualKeyCode::F3,
+    ),
+    ("annotate", Action::Annotate, VirtualKeyCode::Return),
+    ("jump_to_note", Action::JumpToNote, VirtualKeyCode::J),
+    ("console", Action::Console, VirtualKeyCode::Grave),
+    (
+        "toggle_energy",
+        Action::ToggleEnergy,
+        VirtualKeyCode::Period,
+    ),
+    ("draw_obstacle", Action::DrawObstacle, VirtualKeyCode::O),
+    (
+        "toggle_walls",
+        Action::ToggleWalls,
+        VirtualKeyCode::Backslash,
+    ),
+    ("calibrate", Action::Calibrate, VirtualKeyCode::Slash),
+    ("frame", Action::Frame, VirtualKeyCode::Home),
+    ("toggle_envelope", Action::ToggleEnvelope, VirtualKeyCode::H),
+    ("gravity_field", Action::GravityField, VirtualKeyCode::F2),
+    ("turning_points", Action::TurningPoints, VirtualKeyCode::F5),
+    (
+        "energy_partition",
+        Action::EnergyPartition,
+        VirtualKeyCode::F6,
+    ),
+    ("rhythm_colors", Action::RhythmColors, VirtualKeyCode::F7),
+    ("sensitivity", Action::Sensitivity, VirtualKeyCode::F10),
+    ("energy_budget", Action::EnergyBudget, VirtualKeyCode::F12),
+    ("replay_macro", Action::ReplayMacro, VirtualKeyCode::F9),
+    ("help", Action::Help, VirtualKeyCode::F1),
+    ("menu", Action::Menu, VirtualKeyCode::Escape),
+    ("preset_1", Action::Preset(0), VirtualKeyCode::Key1),
+    ("preset_2", Action::Preset(1), VirtualKeyCode::Key2),
+    ("preset_3", Action::Preset(2), VirtualKeyCode::Key3),
+    ("preset_4", Action::Preset(3), VirtualKeyCode::Key4),
+    ("preset_5", Action::Preset(4), VirtualKeyCode::Key5),
+    ("preset_6", Action::Preset(5), VirtualKeyCode::Key6),
+    ("preset_7", Action::Preset(6), VirtualKeyCode::Key7),
+    ("preset_8", Action::Preset(7), VirtualKeyCode::Key8),
+    ("preset_9", Action::Preset(8), VirtualKeyCode::Key9),
+];
+
+/// Keys that can be named in `[keybindings]`, spelled as in `VirtualKeyCode`.
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Key0,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Escape,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        Insert,
+        Home,
+        Delete,
+        End,
+        PageDown,
+        PageUp,
+        Left,
+        Up,
+        Right,
+        Down,
+        Backspace,
+        Return,
+        Space,
+        Tab,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadSubtract,
+        NumpadMultiply,
+        NumpadDivide,
+        NumpadEnter,
+        Minus,
+        Equals,
+        Comma,
+        Period,
+        Slash,
+        Backslash,
+        Semicolon,
+        Apostrophe,
+        Grave,
+        LBracket,
+        RBracket,
+    ]
+};
+
+pub fn parse_action(name: &str) -> Option<Action> {
+    ACTIONS
+        .iter()
+        .find(|(action, _, _)| *action == name)
+        .map(|(_, action, _)| *action)
+}
+
+pub fn action_name(action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|(_, a, _)| *a == action)
+        .map_or("", |(name, _, _)| *name)
+}
+
+pub fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    KEYS.iter()
+        .copied()
+        .find(|key| format!("{:?}", key) == name)
+}
+
+pub struct Bindings {
+    keys: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Bindings {
+    pub fn defaults() -> Bindings {
+        Bindings {
+            keys: ACTIONS
+                .iter()
+                .map(|(_, action, key)| (*key, *action))
+                .collect(),
+        }
+    }
+
+    /// Applies a `[keybindings]` section of `action = Key` lines on top of
+    /// the defaults. Actions it doesn't mention keep their default key.
+    pub fn from_config(section: &Section) -> Result<Bindings, String> {
+        let mut bound: HashMap<Action, VirtualKeyCode> = HashMap::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+
+            let (name, key_name) = key_value(line)
+                .ok_or(error(format!("expected `action = Key`, found `{}`", line)))?;
+            let action = parse_action(name).ok_or(error(format!("unknown action `{}`", name)))?;
+            let key =
+                parse_key(key_name).ok_or(error(format!("unknown key name `{}`", key_name)))?;
+
+            if let Some((other, _)) = bound.iter().find(|(_, k)| **k == key) {
+                return Err(error(format!(
+                    "`{}` is already bound to `{}`",
+                    key_name,
+                    action_name(*other)
+                )));
+            }
+            bound.insert(action, key);
+        }
+
+        let mut keys: HashMap<VirtualKeyCode, Action> =
+            bound.iter().map(|(action, key)| (*key, *action)).collect();
+        for (_, action, default) in ACTIONS {
+            // A remapped action may have taken an unbound action's default,
+            // which then stays unbound.
+            if !bound.contains_key(action) && !keys.contains_key(default) {
+                keys.insert(*default, *action);
+            }
+        }
+
+        Ok(Bindings { keys })
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.keys.get(&key).copied()
+    }
+
+    /// Every action with a key, in the order they're declared, with the
+    /// key's name, grouped by category.
+    pub fn cheat_sheet(&self) -> Vec<(Category, Vec<(&'static str, String)>)> {
+        CATEGORIES
+            .iter()
+            .map(|&category| {
+                let keys = ACTIONS
+                    .iter()
+                    .filter(|(_, action, _)| Category::of(*action) == category)
+                    .filter_map(|(name, action, _)| {
+                        let (key, _) = self.keys.iter().find(|(_, a)| *a == action)?;
+                        Some((*name, format!("{:?}", key)))
+                    })
+                    .collect();
+                (category, keys)
+            })
+            .collect()
+    }
+
+    /// The cheat sheet as text, a heading line for each category and a line
+    /// per key with the action's name as `[keybindings]` spells it.
+    pub fn cheat_sheet_text(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (category, keys) in self.cheat_sheet() {
+            lines.push(format!("{}:", category.name()));
+            for (name, key) in keys {
+                lines.push(format!("  {} = {}", name, key));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn remapped(text: &str) -> Result<Bindings, String> {
+        Bindings::from_config(Config::parse(text).unwrap().section("keybindings").unwrap())
+    }
+
+    /// The category heading each `  name = Key` line of the text comes
+    /// under, by action name.
+    fn sheet(bindings: &Bindings) -> HashMap<String, (String, String)> {
+        let mut heading = String::new();
+        let mut lines = HashMap::new();
+        for line in bindings.cheat_sheet_text() {
+            match line
+                .strip_prefix("  ")
+                .and_then(|line| line.split_once(" = "))
+            {
+                Some((name, key)) => {
+                    let previous =
+                        lines.insert(name.to_string(), (heading.clone(), key.to_string()));
+                    assert!(previous.is_none(), "{} is listed twice", name);
+                }
+                None => heading = line.trim_end_matches(':').to_string(),
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn every_action_is_on_the_cheat_sheet_with_its_default_key() {
+        let lines = sheet(&Bindings::defaults());
+        assert_eq!(lines.len(), ACTIONS.len());
+        for (name, action, key) in ACTIONS {
+            let (heading, listed) = &lines[*name];
+            assert_eq!(heading, Category::of(*action).name(), "{}", name);
+            assert_eq!(listed, &format!("{:?}", key), "{}", name);
+        }
+    }
+
+    #[test]
+    fn a_remapped_action_is_listed_with_its_new_key() {
+        // `reset` takes the energy budget's key, which leaves that unbound,
+        // and `help` a free one.
+        let bindings = remapped("[keybindings]\nreset = F12\nhelp = Numpad0\n").unwrap();
+        let lines = sheet(&bindings);
+        assert_eq!(lines["reset"].1, "F12");
+        assert_eq!(lines["help"].1, "Numpad0");
+        assert!(!lines.contains_key("energy_budget"));
+        assert_eq!(lines.len(), ACTIONS.len() - 1);
+        assert!(bindings.action(VirtualKeyCode::F12) == Some(Action::Reset));
+        assert!(bindings.action(VirtualKeyCode::R).is_none());
+        assert!(bindings.action(VirtualKeyCode::F1).is_none());
+    }
+
+    #[test]
+    fn a_key_can_only_be_bound_once() {
+        let error = remapped("[keybindings]\nreset = F3\nhelp = F3\n")
+            .err()
+            .unwrap();
+        assert_eq!(error, "line 3: `F3` is already bound to `reset`");
+        assert!(remapped("[keybindings]\njuggle = F3\n")
+            .err()
+            .unwrap()
+            .contains("unknown action"));
+        assert!(remapped("[keybindings]\nreset = Hyper\n")
+            .err()
+            .unwrap()
+            .contains("unknown key"));
+    }
+}
@@ -0,0 +1,35 @@
+//! The allowance of simulation work spent per frame on runs ahead of the
+//! pendulum on screen, shared by everything that runs them so that
+//! together they never hold up drawing. Whatever is left over when the
+//! budget runs out carries on next frame.
+
+use std::cell::Cell;
+
+/// Evaluations of the equation of motion allowed per frame.
+const FRAME_BUDGET: usize = 4000;
+
+pub struct Budget {
+    spent: Cell<usize>,
+}
+
+impl Budget {
+    /// A fresh allowance for this frame.
+    pub fn frame() -> Budget {
+        Budget {
+            spent: Cell::new(0),
+        }
+    }
+
+    /// Counts `evaluations` more as spent.
+    pub fn charge(&self, evaluations: usize) {
+        self.spent.set(self.spent.get() + evaluations);
+    }
+
+    pub fn spent(&self) -> usize {
+        self.spent.get()
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.spent.get() >= FRAME_BUDGET
+    }
+}
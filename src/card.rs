@@ -0,0 +1,345 @@
+//! Pendulum cards: a whole session packed into one line of text that can be
+//! pasted into a chat message or an issue and turned back into the same
+//! scene. A card is `pendulum-card-<version>:` followed by URL-safe base64
+//! of a CRC-32 of the session text, its length, and the text compressed
+//! with a small LZ77 scheme.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const CARD_VERSION: u32 = 1;
+const PREFIX: &str = "pendulum-card-";
+
+/// How far back a match may reach, and the longest one, as fit the 12 and
+/// 4 bits of a match token.
+const WINDOW: usize = 4096;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = MIN_MATCH + 15;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Packs session text into a card.
+pub fn encode(text: &str) -> String {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&crc32(text.as_bytes()).to_be_bytes());
+    payload.extend_from_slice(&(text.len() as u32).to_be_bytes());
+    payload.extend(compress(text.as_bytes()));
+    format!("{}{}:{}", PREFIX, CARD_VERSION, base64_encode(&payload))
+}
+
+/// Unpacks a card back into session text. Surrounding whitespace and line
+/// breaks added by a chat client are ignored.
+pub fn decode(card: &str) -> Result<String, String> {
+    let card: String = card.split_whitespace().collect();
+    let (version, data) = card
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or("not a pendulum card")?;
+    let version: u32 = version
+        .parse()
+        .map_err(|_| format!("invalid card version `{}`", version))?;
+
+    let payload = base64_decode(data)?;
+    if payload.len() < 8 {
+        return Err("the card is truncated".to_string());
+    }
+    let checksum = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    let length = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]) as usize;
+
+    let bytes = decompress(&payload[8..], length)?;
+    if bytes.len() < length {
+        return Err(format!(
+            "the card is truncated: {} of {} bytes",
+            bytes.len(),
+            length
+        ));
+    }
+    if crc32(&bytes) != checksum {
+        return Err("the card fails its checksum: it was cut short or changed".to_string());
+    }
+    let text = String::from_utf8(bytes).map_err(|_| "the card isn't text".to_string())?;
+
+    migrate(text, version)
+}
+
+/// Brings a decoded card up to the current version.
+fn migrate(text: String, version: u32) -> Result<String, String> {
+    match version {
+        CARD_VERSION => Ok(text),
+        v if v > CARD_VERSION => Err(format!(
+            "made by a newer version (card {}, this one reads up to {})",
+            v, CARD_VERSION
+        )),
+        v => Err(format!("unknown card version {}", v)),
+    }
+}
+
+/// CRC-32 as used by zip and PNG, bit by bit.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// LZ77 with a flag byte ahead of every eight tokens, a set bit marking a
+/// two-byte match (12 bits of distance less one, 4 bits of length less
+/// `MIN_MATCH`) and a clear bit a literal byte. Session text is a few
+/// kilobytes, so the longest match is simply searched for.
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut flags_at = 0;
+    let mut i = 0;
+    let mut token = 0;
+
+    while i < input.len() {
+        if token % 8 == 0 {
+            flags_at = output.len();
+            output.push(0);
+        }
+
+        let (mut best_length, mut best_distance) = (0, 0);
+        for start in i.saturating_sub(WINDOW)..i {
+            let length = (0..MAX_MATCH.min(input.len() - i))
+                .take_while(|&k| input[start + k] == input[i + k])
+                .count();
+            if length > best_length {
+                (best_length, best_distance) = (length, i - start);
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            output[flags_at] |= 1 << (token % 8);
+            let code = ((best_distance - 1) << 4) | (best_length - MIN_MATCH);
+            output.extend_from_slice(&(code as u16).to_be_bytes());
+            i += best_length;
+        } else {
+            output.push(input[i]);
+            i += 1;
+        }
+        token += 1;
+    }
+
+    output
+}
+
+/// Undoes `compress`, stopping at `length` bytes. A match reaching back
+/// before the start means the card was damaged.
+fn decompress(input: &[u8], length: usize) -> Result<Vec<u8>, String> {
+    let damaged = || "the card is damaged".to_string();
+    // The length comes off the card, so room is only made for what the
+    // input could unpack to, a longest match from every two bytes.
+    let mut output = Vec::with_capacity(length.min(input.len() / 2 * MAX_MATCH + 1));
+    let mut bytes = input.iter().copied();
+
+    'tokens: while output.len() < length {
+        let Some(flags) = bytes.next() else {
+            break;
+        };
+        for bit in 0..8 {
+            if output.len() >= length {
+                break 'tokens;
+            }
+            if flags & (1 << bit) == 0 {
+                let Some(byte) = bytes.next() else {
+                    break 'tokens;
+                };
+                output.push(byte);
+                continue;
+            }
+
+            let (Some(high), Some(low)) = (bytes.next(), bytes.next()) else {
+                break 'tokens;
+            };
+            let code = u16::from_be_bytes([high, low]) as usize;
+            let distance = (code >> 4) + 1;
+            let count = (code & 0xF) + MIN_MATCH;
+            if distance > output.len() {
+                return Err(damaged());
+            }
+            for _ in 0..count {
+                output.push(output[output.len() - distance]);
+            }
+        }
+    }
+
+    output.truncate(length);
+    Ok(output)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            text.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+    }
+    text
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for (i, c) in text.chars().enumerate() {
+        let value = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(format!(
+                "invalid character `{}` at {} in the card",
+                c,
+                i + 1
+            ))?;
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Clipboard tools tried in turn, for Wayland, X11, macOS and Windows.
+const COPY_COMMANDS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+    &["clip.exe"],
+];
+const PASTE_COMMANDS: &[&[&str]] = &[
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+    &["pbpaste"],
+    &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+/// Puts `text` on the system clipboard with whichever clipboard tool is
+/// installed. Returns false if none is.
+pub fn copy_to_clipboard(text: &str) -> bool {
+    COPY_COMMANDS.iter().any(|command| {
+        let Ok(mut child) = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return false;
+        };
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    })
+}
+
+/// The text on the system clipboard, if a clipboard tool is installed.
+pub fn read_clipboard() -> Option<String> {
+    PASTE_COMMANDS.iter().find_map(|command| {
+        let output = Command::new(command[0])
+            .args(&command[1..])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &str = "[models]\n\
+        pendulum x=400 y=0 r=200 angle=1 g=0.5\n\
+        pendulum x=400 y=0 r=200 angle=1 g=0.5\n\
+        pendulum x=300 y=0 r=150 angle=0.5 g=0.5\n\
+        [camera]\nzoom = 1.5\n# ünïcödé survives too\n";
+
+    #[test]
+    fn cards_round_trip() {
+        let long = SESSION.repeat(40);
+        for text in [
+            "",
+            "a",
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            SESSION,
+            &long,
+        ] {
+            let card = encode(text);
+            assert!(card.starts_with("pendulum-card-1:"), "{}", card);
+            assert_eq!(decode(&card).as_deref(), Ok(text));
+        }
+        // Repeated lines come out as matches, well under the text's size.
+        assert!(encode(&long).len() < long.len() / 4);
+    }
+
+    #[test]
+    fn line_breaks_a_chat_client_adds_are_ignored() {
+        let card = encode(SESSION);
+        let (head, tail) = card.split_at(card.len() / 2);
+        assert_eq!(
+            decode(&format!("  {}\n   {}\n", head, tail)).as_deref(),
+            Ok(SESSION)
+        );
+    }
+
+    #[test]
+    fn a_card_from_a_newer_version_is_refused() {
+        let card = encode(SESSION).replace("pendulum-card-1:", "pendulum-card-2:");
+        let error = decode(&card).unwrap_err();
+        assert!(error.contains("newer version (card 2"), "{}", error);
+
+        let card = encode(SESSION).replace("pendulum-card-1:", "pendulum-card-0:");
+        assert_eq!(decode(&card), Err("unknown card version 0".to_string()));
+    }
+
+    #[test]
+    fn damaged_cards_are_refused() {
+        let card = encode(SESSION);
+        assert!(decode("hello").is_err());
+        assert!(decode(&card[..card.len() / 2])
+            .unwrap_err()
+            .contains("truncated"));
+        assert!(decode(&format!("{}!", card))
+            .unwrap_err()
+            .contains("invalid character"));
+
+        // A changed byte of text trips the checksum.
+        let mut payload = base64_decode(card.split_once(':').unwrap().1).unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        let changed = format!("{}{}:{}", PREFIX, CARD_VERSION, base64_encode(&payload));
+        assert!(decode(&changed).unwrap_err().contains("checksum"));
+    }
+
+    #[test]
+    fn a_huge_claimed_length_only_gets_room_for_what_is_there() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&crc32(b"abc").to_be_bytes());
+        payload.extend_from_slice(&u32::MAX.to_be_bytes());
+        payload.extend(compress(b"abc"));
+        let card = format!("{}{}:{}", PREFIX, CARD_VERSION, base64_encode(&payload));
+        assert!(decode(&card).unwrap_err().contains("3 of 4294967295 bytes"));
+
+        let output = decompress(&compress(b"abc"), usize::MAX).unwrap();
+        assert_eq!(output, b"abc");
+        assert!(output.capacity() <= 2 * MAX_MATCH + 1);
+    }
+}
@@ -0,0 +1,399 @@
+use speedy2d::color::Color;
+use speedy2d::shape::Rectangle;
+
+use crate::framing::Bounds;
+use crate::math;
+use crate::model::{CartEntry, Model, ModelEntry};
+use crate::renderer::Renderer;
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+const CART_WIDTH: f32 = 80.0;
+const CART_HEIGHT: f32 = 30.0;
+const WHEEL_RADIUS: f32 = 8.0;
+const BOB_RADIUS: f32 = 20.0;
+
+/// Cart position, pendulum angle and their conjugate momenta:
+/// `[x, θ, p_x, p_θ]`.
+type State = [f32; 4];
+
+/// A pendulum hung from a cart that rolls freely on a level track between
+/// two elastic walls. Nothing drives it: the swing pushes the cart back and
+/// forth and the cart's recoil feeds back into the swing.
+///
+/// The state is integrated in momentum form. The cart position doesn't
+/// appear in the equations, so without track friction its momentum `p_x`,
+/// the total horizontal momentum, has a zero derivative and RK4 keeps it
+/// exactly.
+pub struct CartPendulum {
+    /// Height of the pivot, i.e. of the middle of the cart.
+    pub y: f32,
+    /// Track ends, where the walls are.
+    pub track: (f32, f32),
+    pub cart_mass: f32,
+    pub m: f32,
+    pub r: f32,
+    pub g: f32,
+    /// Fraction of the cart's velocity lost to rolling friction each step,
+    /// applied as a force so it acts on the whole system.
+    pub friction: f32,
+
+    state: State,
+    grabbed: bool,
+}
+
+impl CartPendulum {
+    pub fn from_entry(entry: &CartEntry) -> CartPendulum {
+        let mut cart = CartPendulum {
+            y: entry.y,
+            track: (entry.left, entry.right),
+            cart_mass: entry.cart_mass,
+            m: entry.m,
+            r: entry.r,
+            g: entry.g,
+            friction: entry.friction,
+            state: [entry.x, entry.angle, 0.0, 0.0],
+            grabbed: false,
+        };
+        cart.set_velocities(entry.velocity, entry.angular_velocity);
+        cart
+    }
+
+    /// Cart velocity and angular velocity for the current momenta.
+    pub fn velocities(&self) -> (f32, f32) {
+        let [_, theta, px, ptheta] = self.state;
+        self.velocities_at(theta, px, ptheta)
+    }
+
+    fn velocities_at(&self, theta: f32, px: f32, ptheta: f32) -> (f32, f32) {
+        let (m, l, total) = (self.m, self.r, self.cart_mass + self.m);
+        let cos = math::cos(theta);
+        let determinant = m * l * l * (self.cart_mass + m * math::sin(theta).powi(2));
+
+        (
+            (m * l * l * px - m * l * cos * ptheta) / determinant,
+            (total * ptheta - m * l * cos * px) / determinant,
+        )
+    }
+
+    fn set_velocities(&mut self, velocity: f32, angular_velocity: f32) {
+        let (m, l, total) = (self.m, self.r, self.cart_mass + self.m);
+        let cos = math::cos(self.state[1]);
+        self.state[2] = total * velocity + m * l * cos * angular_velocity;
+        self.state[3] = m * l * cos * velocity + m * l * l * angular_velocity;
+    }
+
+    fn derivative(&self, state: &State) -> State {
+        let [_, theta, px, ptheta] = *state;
+        let (velocity, angular_velocity) = self.velocities_at(theta, px, ptheta);
+        let (sin, l) = (math::sin(theta), self.r);
+
+        [
+            velocity,
+            angular_velocity,
+            -self.friction * (self.cart_mass + self.m) * velocity,
+            -self.m * l * sin * (velocity * angular_velocity + self.g),
+        ]
+    }
+
+    /// Total horizontal momentum.
+    pub fn momentum(&self) -> f32 {
+        self.state[2]
+    }
+
+    /// Kinetic energy of the cart and of the bob, and the bob's potential
+    /// energy measured from hanging straight down.
+    pub fn energies(&self) -> (f32, f32, f32) {
+        let theta = self.state[1];
+        let (velocity, angular_velocity) = self.velocities();
+        let (m, l) = (self.m, self.r);
+
+        let cart = 0.5 * self.cart_mass * velocity * velocity;
+        let bob_x = velocity + l * angular_velocity * theta.cos();
+        let bob_y = -l * angular_velocity * theta.sin();
+        let bob = 0.5 * m * (bob_x * bob_x + bob_y * bob_y);
+        let potential = m * self.g * l * (1.0 - theta.cos());
+
+        (cart, bob, potential)
+    }
+
+    /// Bounces the cart off a wall it has run into. The wall's impulse acts
+    /// on the cart alone and is sized so no energy is lost.
+    fn bounce(&mut self) {
+        let (left, right) = (
+            self.track.0 + CART_WIDTH / 2.0,
+            self.track.1 - CART_WIDTH / 2.0,
+        );
+        let (velocity, _) = self.velocities();
+        let x = self.state[0];
+
+        if (x < left && velocity < 0.0) || (x > right && velocity > 0.0) {
+            // How much the cart velocity changes per unit of impulse.
+            let (per_impulse, _) = self.velocities_at(self.state[1], 1.0, 0.0);
+            self.state[2] -= 2.0 * velocity / per_impulse;
+        }
+        self.state[0] = x.clamp(left, right);
+    }
+
+    fn bob(&self) -> Vector {
+        let [x, theta, _, _] = self.state;
+        Vector::new(x + self.r * theta.sin(), self.y + self.r * theta.cos())
+    }
+}
+
+impl Model for CartPendulum {
+    fn step(&mut self) {
+        if self.grabbed {
+            return;
+        }
+
+        // Classic RK4 over one step.
+        let add = |state: &State, k: &State, scale: f32| {
+            let mut next = *state;
+            for (n, d) in next.iter_mut().zip(k) {
+                *n += scale * d;
+            }
+            next
+        };
+        let k1 = self.derivative(&self.state);
+        let k2 = self.derivative(&add(&self.state, &k1, 0.5));
+        let k3 = self.derivative(&add(&self.state, &k2, 0.5));
+        let k4 = self.derivative(&add(&self.state, &k3, 1.0));
+        for i in 0..4 {
+            self.state[i] += (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]) / 6.0;
+        }
+
+        self.bounce();
+    }
+
+    fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let x = self.state[0];
+        let track_y = self.y + CART_HEIGHT / 2.0 + 2.0 * WHEEL_RADIUS;
+
+        graphics.draw_line(
+            view.world_to_screen(Vector::new(self.track.0, track_y)),
+            view.world_to_screen(Vector::new(self.track.1, track_y)),
+            view.length(2.0),
+            view.tint(Color::DARK_GRAY),
+        );
+        for wall in [self.track.0, self.track.1] {
+            graphics.draw_line(
+                view.world_to_screen(Vector::new(wall, track_y)),
+                view.world_to_screen(Vector::new(wall, track_y - 2.0 * CART_HEIGHT)),
+                view.length(4.0),
+                view.tint(Color::DARK_GRAY),
+            );
+        }
+
+        let top_left = view.world_to_screen(Vector::new(
+            x - CART_WIDTH / 2.0,
+            self.y - CART_HEIGHT / 2.0,
+        ));
+        let bottom_right = view.world_to_screen(Vector::new(
+            x + CART_WIDTH / 2.0,
+            self.y + CART_HEIGHT / 2.0,
+        ));
+        graphics.draw_rectangle(
+            Rectangle::from_tuples(top_left, bottom_right),
+            view.tint(Color::from_rgb(0.45, 0.5, 0.6)),
+        );
+        for side in [-1.0, 1.0] {
+            let wheel = Vector::new(
+                x + side * CART_WIDTH / 3.0,
+                self.y + CART_HEIGHT / 2.0 + WHEEL_RADIUS,
+            );
+            graphics.draw_circle(
+                view.world_to_screen(wheel),
+                view.length(WHEEL_RADIUS),
+                view.tint(Color::BLACK),
+            );
+        }
+
+        let pivot = view.world_to_screen(self.pivot());
+        let bob = view.world_to_screen(self.bob());
+        graphics.draw_line(pivot, bob, view.length(3.0), view.tint(Color::GRAY));
+        graphics.draw_circle(
+            bob,
+            view.length(BOB_RADIUS + 3.0),
+            view.tint(Color::DARK_GRAY),
+        );
+        graphics.draw_circle(bob, view.length(BOB_RADIUS), view.tint(Color::LIGHT_GRAY));
+    }
+
+    fn grab(&mut self, point: Vector) -> bool {
+        let bob = self.bob();
+        self.grabbed =
+            (bob.x - point.x).powi(2) + (bob.y - point.y).powi(2) < (BOB_RADIUS + 3.0).powi(2);
+        self.grabbed
+    }
+
+    /// Swings the rod towards the mouse, keeping the cart rolling. How fast
+    /// the rod moved is kept, so letting go mid-swing flings the bob and the
+    /// cart recoils from it.
+    fn drag_to(&mut self, point: Vector) {
+        if !self.grabbed {
+            return;
+        }
+
+        let (velocity, _) = self.velocities();
+        let theta = self.state[1];
+        let target = (point.x - self.state[0]).atan2(point.y - self.y);
+        // Unwrap so a drag across the top doesn't read as a full turn.
+        let change = (target - theta + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+
+        self.state[0] += velocity;
+        self.state[1] = theta + change;
+        self.set_velocities(velocity, change);
+        self.bounce();
+    }
+
+    fn release(&mut self) {
+        self.grabbed = false;
+    }
+
+    fn pivot(&self) -> Vector {
+        Vector::new(self.state[0], self.y)
+    }
+
+    /// The track goes with the cart.
+    fn move_pivot(&mut self, by: Vector) {
+        self.state[0] += by.x;
+        self.track = (self.track.0 + by.x, self.track.1 + by.x);
+        self.y += by.y;
+    }
+
+    fn handles(&self) -> Vec<Vector> {
+        vec![self.bob()]
+    }
+
+    /// The track and its walls, and the bob's swing from the cart at
+    /// either end of it, no higher than all the energy would lift the bob.
+    fn reach(&self) -> Bounds {
+        let track_y = self.y + CART_HEIGHT / 2.0 + 2.0 * WHEEL_RADIUS;
+        let (cart, bob, potential) = self.energies();
+        let top = if self.g > 0.0 {
+            self.y + self.r - (cart + bob + potential) / (self.m * self.g)
+        } else {
+            f32::NEG_INFINITY
+        };
+        let swing = |x: f32| Bounds::arc(Vector::new(x, self.y), self.r, top);
+        Bounds {
+            min: Vector::new(self.track.0, track_y - 2.0 * CART_HEIGHT),
+            max: Vector::new(self.track.1, track_y),
+        }
+        .union(
+            swing(self.track.0 + CART_WIDTH / 2.0)
+                .union(swing(self.track.1 - CART_WIDTH / 2.0))
+                .padded(BOB_RADIUS + 3.0),
+        )
+    }
+
+    fn label(&self, _units: &Units) -> String {
+        format!("Cart pendulum, {:.0} on {:.0}", self.m, self.cart_mass)
+    }
+
+    fn hud_lines(&self, units: &Units) -> Vec<String> {
+        let (velocity, angular_velocity) = self.velocities();
+        let (cart, bob, potential) = self.energies();
+        vec![
+            format!("Gravity: {:.2}", self.g),
+            format!("Angle: {:.2}", self.state[1]),
+            format!("Velocity: {:.3}", angular_velocity),
+            format!(
+                "Cart: {} at {:.2}/step",
+                units.length(self.state[0]),
+                velocity
+            ),
+            format!("Masses: bob {:.1}, cart {:.1}", self.m, self.cart_mass),
+            format!("Track friction: {:.4}", self.friction),
+            format!("Energy: {:.2} (cart {:.2})", cart + bob + potential, cart),
+            format!("Momentum: {:.3}", self.momentum()),
+        ]
+    }
+
+    fn entry(&self) -> ModelEntry {
+        let (velocity, angular_velocity) = self.velocities();
+        ModelEntry::Cart(CartEntry {
+            x: self.state[0],
+            y: self.y,
+            left: self.track.0,
+            right: self.track.1,
+            cart_mass: self.cart_mass,
+            m: self.m,
+            r: self.r,
+            g: self.g,
+            friction: self.friction,
+            angle: self.state[1],
+            angular_velocity,
+            velocity,
+        })
+    }
+
+    fn state(&self) -> Vec<f64> {
+        self.state.iter().map(|&x| x as f64).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cart on a track too long to reach the walls, pushed off with
+    /// `velocity` and the bob let go a radian out.
+    fn rolling(velocity: f32, friction: f32) -> CartPendulum {
+        CartPendulum::from_entry(&CartEntry {
+            left: -1e6,
+            right: 1e6,
+            velocity,
+            friction,
+            ..CartEntry::default()
+        })
+    }
+
+    /// Horizontal momentum worked out from the velocities rather than read
+    /// off the state.
+    fn momentum_of(cart: &CartPendulum) -> f32 {
+        let (velocity, angular_velocity) = cart.velocities();
+        let theta = cart.state[1];
+        (cart.cart_mass + cart.m) * velocity + cart.m * cart.r * theta.cos() * angular_velocity
+    }
+
+    #[test]
+    fn without_friction_horizontal_momentum_is_conserved() {
+        for velocity in [0.0, 0.7] {
+            let mut cart = rolling(velocity, 0.0);
+            let start = cart.momentum();
+            let (centre, total) = (
+                |cart: &CartPendulum| {
+                    let bob = cart.state[0] + cart.r * cart.state[1].sin();
+                    (cart.cart_mass * cart.state[0] + cart.m * bob) / (cart.cart_mass + cart.m)
+                },
+                cart.cart_mass + cart.m,
+            );
+            let from = centre(&cart);
+            for step in 1..=3000 {
+                cart.step();
+                assert_eq!(cart.momentum(), start, "changed at step {}", step);
+                assert!((momentum_of(&cart) - start).abs() < 1e-3);
+                // So the centre of mass drifts at a steady p / (M + m).
+                let drifted = from + start / total * step as f32;
+                assert!((centre(&cart) - drifted).abs() < 0.05);
+            }
+            // The swing still pushes the cart to and fro.
+            assert!(cart.velocities().0 != start / total);
+        }
+    }
+
+    #[test]
+    fn track_friction_takes_the_momentum_out() {
+        let mut cart = rolling(0.7, 0.01);
+        let start = cart.momentum();
+        for _ in 0..600 {
+            cart.step();
+        }
+        // What's left goes back and forth with the swing's recoil.
+        assert!(cart.momentum().abs() < 0.25 * start);
+    }
+}
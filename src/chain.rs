@@ -0,0 +1,281 @@
+use speedy2d::color::Color;
+
+use crate::framing::Bounds;
+use crate::math;
+use crate::model::{ChainEntry, Model, ModelEntry};
+use crate::renderer::Renderer;
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// Velocity kept by each joint per step.
+const CHAIN_DAMPING: f32 = 0.999;
+/// Constraint relaxation passes per step; more keeps the links stiffer.
+const CONSTRAINT_ITERATIONS: usize = 20;
+const JOINT_RADIUS: f32 = 14.0;
+
+/// A multi-link pendulum of equal rigid links, integrated with Verlet steps
+/// and distance constraints. Two links make the classic double pendulum.
+pub struct Chain {
+    pub origin: Vector,
+    pub link_length: f32,
+    pub g: f32,
+
+    /// Joint positions, from the first joint below the pivot to the end bob.
+    joints: Vec<Vector>,
+    /// Joint positions one step earlier; the difference is the velocity.
+    previous: Vec<Vector>,
+
+    grabbed: Option<usize>,
+}
+
+impl Chain {
+    pub fn from_entry(entry: &ChainEntry) -> Chain {
+        let mut chain = Chain {
+            origin: Vector::new(entry.x, entry.y),
+            link_length: entry.link_length,
+            g: entry.g,
+            joints: Vec::new(),
+            previous: Vec::new(),
+            grabbed: None,
+        };
+
+        chain.joints = chain.joints_from_angles(&entry.angles);
+
+        let previous_angles: Vec<f32> = entry
+            .angles
+            .iter()
+            .zip(
+                entry
+                    .angular_velocities
+                    .iter()
+                    .chain(std::iter::repeat(&0.0)),
+            )
+            .map(|(angle, velocity)| angle - velocity)
+            .collect();
+        chain.previous = chain.joints_from_angles(&previous_angles);
+
+        chain
+    }
+
+    fn joints_from_angles(&self, angles: &[f32]) -> Vec<Vector> {
+        let mut joint = self.origin;
+        angles
+            .iter()
+            .map(|angle| {
+                joint.add(&Vector::new(
+                    self.link_length * math::sin(*angle),
+                    self.link_length * math::cos(*angle),
+                ));
+                joint
+            })
+            .collect()
+    }
+
+    /// Angle of every link from the vertical, in the same convention as
+    /// `Pendulum::angle`.
+    fn link_angles(&self, joints: &[Vector]) -> Vec<f32> {
+        let mut above = self.origin;
+        joints
+            .iter()
+            .map(|joint| {
+                let angle = (joint.x - above.x).atan2(joint.y - above.y);
+                above = *joint;
+                angle
+            })
+            .collect()
+    }
+
+    fn satisfy_constraints(&mut self) {
+        for _ in 0..CONSTRAINT_ITERATIONS {
+            for i in 0..self.joints.len() {
+                let above = if i == 0 {
+                    self.origin
+                } else {
+                    self.joints[i - 1]
+                };
+                let joint = self.joints[i];
+
+                let dx = joint.x - above.x;
+                let dy = joint.y - above.y;
+                let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+                let error = (length - self.link_length) / length;
+
+                // The pivot and a grabbed joint are held in place, so the
+                // other end of the link takes the whole correction.
+                let above_fixed = i == 0 || self.grabbed == Some(i - 1);
+                let joint_fixed = self.grabbed == Some(i);
+                let (move_above, move_joint) = match (above_fixed, joint_fixed) {
+                    (true, true) => (0.0, 0.0),
+                    (true, false) => (0.0, 1.0),
+                    (false, true) => (1.0, 0.0),
+                    (false, false) => (0.5, 0.5),
+                };
+
+                self.joints[i].x -= dx * error * move_joint;
+                self.joints[i].y -= dy * error * move_joint;
+                if i > 0 {
+                    self.joints[i - 1].x += dx * error * move_above;
+                    self.joints[i - 1].y += dy * error * move_above;
+                }
+            }
+        }
+    }
+}
+
+impl Model for Chain {
+    fn step(&mut self) {
+        for (joint, previous) in self.joints.iter_mut().zip(self.previous.iter_mut()) {
+            let velocity_x = (joint.x - previous.x) * CHAIN_DAMPING;
+            let velocity_y = (joint.y - previous.y) * CHAIN_DAMPING;
+
+            *previous = *joint;
+            joint.add(&Vector::new(velocity_x, velocity_y + self.g));
+        }
+
+        self.satisfy_constraints();
+    }
+
+    fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let mut above = view.world_to_screen(self.origin);
+        for joint in &self.joints {
+            let joint = view.world_to_screen(*joint);
+            graphics.draw_line(above, joint, view.length(3.0), view.tint(Color::GRAY));
+            above = joint;
+        }
+
+        for joint in &self.joints {
+            let joint = view.world_to_screen(*joint);
+            graphics.draw_circle(
+                joint,
+                view.length(JOINT_RADIUS),
+                view.tint(Color::DARK_GRAY),
+            );
+            graphics.draw_circle(
+                joint,
+                view.length(JOINT_RADIUS - 3.0),
+                view.tint(Color::LIGHT_GRAY),
+            );
+        }
+    }
+
+    fn grab(&mut self, point: Vector) -> bool {
+        self.grabbed = self.joints.iter().rposition(|joint| {
+            (joint.x - point.x).powi(2) + (joint.y - point.y).powi(2) < JOINT_RADIUS.powi(2)
+        });
+        self.grabbed.is_some()
+    }
+
+    fn drag_to(&mut self, point: Vector) {
+        if let Some(i) = self.grabbed {
+            self.joints[i] = point;
+            self.previous[i] = point;
+            self.satisfy_constraints();
+        }
+    }
+
+    fn release(&mut self) {
+        if let Some(i) = self.grabbed.take() {
+            self.previous[i] = self.joints[i];
+        }
+    }
+
+    fn pivot(&self) -> Vector {
+        self.origin
+    }
+
+    fn move_pivot(&mut self, by: Vector) {
+        self.origin.add(&by);
+        for point in self.joints.iter_mut().chain(&mut self.previous) {
+            point.add(&by);
+        }
+    }
+
+    fn handles(&self) -> Vec<Vector> {
+        self.joints.clone()
+    }
+
+    /// Like the double pendulum's: each joint anywhere within reach, and
+    /// lifted at most the energy over the weight of it and every joint
+    /// below it, which it lifts at least as far.
+    fn reach(&self) -> Bounds {
+        let link = self.link_length;
+        let energy: f32 = self
+            .joints
+            .iter()
+            .zip(&self.previous)
+            .enumerate()
+            .map(|(i, (joint, previous))| {
+                let (vx, vy) = (joint.x - previous.x, joint.y - previous.y);
+                let lowest = (i + 1) as f32 * link;
+                0.5 * (vx * vx + vy * vy) + self.g * (lowest - (joint.y - self.origin.y))
+            })
+            .sum();
+
+        let count = self.joints.len();
+        (0..count)
+            .fold(Bounds::point(self.origin), |bounds, i| {
+                let radius = (i + 1) as f32 * link;
+                let rise = if self.g > 0.0 {
+                    energy / ((count - i) as f32 * self.g)
+                } else {
+                    f32::INFINITY
+                };
+                bounds.union(Bounds::arc(
+                    self.origin,
+                    radius,
+                    self.origin.y + radius - rise,
+                ))
+            })
+            .padded(JOINT_RADIUS)
+    }
+
+    fn label(&self, _units: &Units) -> String {
+        format!("Chain, {} links", self.joints.len())
+    }
+
+    fn hud_lines(&self, units: &Units) -> Vec<String> {
+        let angles = self.link_angles(&self.joints);
+        let mut lines = vec![
+            format!("Gravity: {:.2}", self.g),
+            format!(
+                "Links: {} x {}",
+                self.joints.len(),
+                units.length(self.link_length)
+            ),
+        ];
+        lines.extend(
+            angles
+                .iter()
+                .enumerate()
+                .map(|(i, angle)| format!("Angle {}: {:.2}", i + 1, angle)),
+        );
+        lines
+    }
+
+    fn entry(&self) -> ModelEntry {
+        let angles = self.link_angles(&self.joints);
+        let previous_angles = self.link_angles(&self.previous);
+
+        ModelEntry::Chain(ChainEntry {
+            x: self.origin.x,
+            y: self.origin.y,
+            link_length: self.link_length,
+            g: self.g,
+            angular_velocities: angles
+                .iter()
+                .zip(previous_angles.iter())
+                .map(|(angle, previous)| angle - previous)
+                .collect(),
+            angles,
+        })
+    }
+
+    fn state(&self) -> Vec<f64> {
+        self.joints
+            .iter()
+            .chain(&self.previous)
+            .flat_map(|joint| [joint.x as f64, joint.y as f64])
+            .collect()
+    }
+}
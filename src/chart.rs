@@ -0,0 +1,537 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::annotations::Annotations;
+use crate::config::{key_value, parse_f32, Section};
+use crate::focus::{Handling, Registry, Target};
+use crate::polyline;
+use crate::retention::{self, Resolution, Sample, Tiered};
+use crate::vector::Vector;
+
+const MIN_SPAN: f32 = 0.5;
+const DEFAULT_SPAN: f32 = 10.0;
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// Height of the strip along the bottom edge that holds note markers.
+const MARKER_STRIP: f32 = 14.0;
+/// Rows of note labels stacked above the markers before labels are dropped.
+const LABEL_ROWS: usize = 3;
+/// Longest note label drawn on the chart, in characters.
+const LABEL_LENGTH: usize = 16;
+
+/// A sample, or a run of them reduced to its time, value range and mean.
+#[derive(Copy, Clone)]
+struct Bin {
+    time: f32,
+    min: f32,
+    max: f32,
+    mean: f32,
+}
+
+impl Sample for Bin {
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// The least of the minima and the most of the maxima, so the amplitude
+    /// envelope of old data is as wide as it was. Each bin of a tier stands
+    /// for as many samples, so the mean is the mean of the means.
+    fn merge(run: &[Bin]) -> Bin {
+        Bin {
+            time: run[0].time,
+            min: run.iter().map(|bin| bin.min).fold(f32::INFINITY, f32::min),
+            max: run
+                .iter()
+                .map(|bin| bin.max)
+                .fold(f32::NEG_INFINITY, f32::max),
+            mean: run.iter().map(|bin| bin.mean).sum::<f32>() / run.len() as f32,
+        }
+    }
+}
+
+/// The angle trace, kept in tiers: recent samples at full rate, older ones
+/// merged into min/max bins so hours of history fit in the chart's share
+/// of the memory without losing the amplitude envelope.
+pub struct SampleBuffer {
+    bins: Tiered<Bin>,
+    /// Seconds kept at most, if `[chart]` says; otherwise as long as the
+    /// memory allows.
+    retention: Option<f32>,
+}
+
+impl SampleBuffer {
+    pub fn new(budget: usize, retention: Option<f32>) -> SampleBuffer {
+        SampleBuffer {
+            bins: Tiered::new(budget),
+            retention,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bins.clear();
+    }
+
+    pub fn push(&mut self, time: f32, value: f32) {
+        self.bins.push(Bin {
+            time,
+            min: value,
+            max: value,
+            mean: value,
+        });
+        if let Some(retention) = self.retention {
+            self.bins.forget_before(time - retention);
+        }
+    }
+
+    /// Time span covered, oldest first.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        Some((self.bins.first()?.time, self.bins.last()?.time))
+    }
+
+    /// The samples and bins from `start` to `end` seconds, oldest first.
+    fn bins(&self, start: f32, end: f32) -> impl Iterator<Item = Bin> + '_ {
+        self.bins.between(start, end)
+    }
+
+    /// The value recorded closest to `time`, the mean of the bin if merged.
+    pub fn value_at(&self, time: f32) -> Option<f32> {
+        let after = self.bins.partition(time);
+        let before = after.checked_sub(1).and_then(|i| self.bins.get(i));
+        [before, self.bins.get(after)]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| (a.time - time).abs().total_cmp(&(b.time - time).abs()))
+            .map(|bin| bin.mean)
+    }
+
+    pub fn memory(&self) -> usize {
+        self.bins.memory()
+    }
+
+    pub fn resolutions(&self) -> Vec<Resolution> {
+        self.bins.resolutions()
+    }
+
+    /// Writes the trace to `file` as CSV, a row a sample or bin, with a `#`
+    /// line for each stretch kept at a different resolution.
+    pub fn export(&self, file: &Path) -> Result<String, String> {
+        let mut text = String::from(
+            "# Angle of the selected pendulum, in radians, from the strip chart.\n\
+             # Older rows are bins of samples: the time of the first, and the\n\
+             # least, mean and most angle in it.\n",
+        );
+        for resolution in self.resolutions() {
+            let _ = writeln!(
+                text,
+                "# {:.2} s to {:.2} s: {}",
+                resolution.start,
+                resolution.end,
+                retention::describe(&resolution, "step")
+            );
+        }
+        text.push_str("time,min,mean,max\n");
+        for bin in self.bins.iter() {
+            let _ = writeln!(text, "{},{},{},{}", bin.time, bin.min, bin.mean, bin.max);
+        }
+        fs::write(file, text).map_err(|e| format!("could not write {}: {}", file.display(), e))?;
+        Ok(format!(
+            "Chart written to {}: {} rows",
+            file.display(),
+            self.bins.len()
+        ))
+    }
+}
+
+/// Linear map between time/value and the chart's pixels, with inverses for
+/// reading values back at the mouse.
+#[derive(Copy, Clone)]
+pub struct Axes {
+    pub start: f32,
+    pub span: f32,
+    pub scale: f32,
+    pub rect: (f32, f32, f32, f32),
+}
+
+impl Axes {
+    pub fn time_to_x(&self, time: f32) -> f32 {
+        self.rect.0 + (time - self.start) / self.span * self.rect.2
+    }
+
+    pub fn x_to_time(&self, x: f32) -> f32 {
+        self.start + (x - self.rect.0) / self.rect.2 * self.span
+    }
+
+    pub fn value_to_y(&self, value: f32) -> f32 {
+        self.rect.1 + self.rect.3 * (0.5 - 0.5 * value / self.scale)
+    }
+}
+
+enum View {
+    /// Shows the last `span` seconds and scrolls with new samples.
+    Live { span: f32 },
+    /// Anchored at a fixed start after zooming or panning.
+    Fixed { start: f32, span: f32 },
+}
+
+/// Angle-against-time chart of the selected pendulum. While hovered, the
+/// wheel zooms around the cursor, dragging pans through the retained history,
+/// a cursor line reads out the value under the mouse, and a double click
+/// returns to following the live data.
+pub struct StripChart {
+    pub visible: bool,
+    pub buffer: SampleBuffer,
+    rect: (f32, f32, f32, f32),
+    view: View,
+    hover: Option<(f32, f32)>,
+    /// Mouse x and view start when a drag began.
+    drag: Option<(f32, f32)>,
+    last_click: Option<Instant>,
+}
+
+impl StripChart {
+    /// A chart keeping its trace in `budget` bytes, and for at most
+    /// `retention` seconds if given.
+    pub fn new(budget: usize, retention: Option<f32>) -> StripChart {
+        StripChart {
+            visible: false,
+            buffer: SampleBuffer::new(budget, retention),
+            rect: (220.0, 340.0, 360.0, 130.0),
+            view: View::Live { span: DEFAULT_SPAN },
+            hover: None,
+            drag: None,
+            last_click: None,
+        }
+    }
+
+    /// Reads `retention = <seconds>` from a `[chart]` config section.
+    pub fn retention_from_config(section: &Section) -> Result<f32, String> {
+        let mut retention = None;
+        for (line_number, line) in &section.lines {
+            match key_value(line) {
+                Some(("retention", value)) => {
+                    retention = Some(
+                        parse_f32("retention", value)
+                            .map_err(|e| format!("line {}: {}", line_number, e))?,
+                    )
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}: unknown chart setting `{}`",
+                        line_number, line
+                    ))
+                }
+            }
+        }
+        retention.ok_or("`retention` isn't set".to_string())
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let (left, top, width, height) = self.rect;
+        self.visible && x >= left && x <= left + width && y >= top && y <= top + height
+    }
+
+    fn axes(&self) -> Option<Axes> {
+        let (_, last) = self.buffer.range()?;
+        let (start, span) = match self.view {
+            View::Live { span } => (last - span, span),
+            View::Fixed { start, span } => (start, span),
+        };
+
+        let scale = self
+            .buffer
+            .bins(start, start + span)
+            .map(|bin| bin.min.abs().max(bin.max.abs()))
+            .fold(0.1, f32::max);
+
+        Some(Axes {
+            start,
+            span,
+            scale,
+            rect: self.rect,
+        })
+    }
+
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.hover = self.contains(x, y).then_some((x, y));
+
+        if let (Some((drag_x, drag_start)), Some(axes)) = (self.drag, self.axes()) {
+            let start = drag_start - (x - drag_x) / self.rect.2 * axes.span;
+            self.view = View::Fixed {
+                start,
+                span: axes.span,
+            };
+        }
+    }
+
+    /// Returns true if the chart took the click.
+    pub fn mouse_down(&mut self, x: f32, y: f32) -> bool {
+        if !self.contains(x, y) {
+            return false;
+        }
+
+        let now = Instant::now();
+        if self
+            .last_click
+            .is_some_and(|last| now.duration_since(last) < DOUBLE_CLICK)
+        {
+            self.view = View::Live { span: DEFAULT_SPAN };
+            self.last_click = None;
+            return true;
+        }
+        self.last_click = Some(now);
+
+        if let Some(axes) = self.axes() {
+            self.drag = Some((x, axes.start));
+        }
+        true
+    }
+
+    /// The note whose marker is under the mouse, if any.
+    pub fn marker_at(&self, x: f32, y: f32, annotations: &Annotations) -> Option<usize> {
+        let (_, top, _, height) = self.rect;
+        if !self.contains(x, y) || y < top + height - MARKER_STRIP {
+            return None;
+        }
+
+        let axes = self.axes()?;
+        annotations
+            .notes
+            .iter()
+            .enumerate()
+            .map(|(i, note)| (i, (axes.time_to_x(note.time) - x).abs()))
+            .filter(|(_, distance)| *distance < 6.0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Registers the chart, when shown, to pan from its middle.
+    pub fn register(&self, registry: &mut Registry) {
+        if !self.visible {
+            return;
+        }
+        let (left, top, width, height) = self.rect;
+        registry.register(
+            Target::Chart,
+            (left + width / 2.0, top + height / 2.0),
+            12.0,
+            Handling::Drag,
+        );
+    }
+
+    /// Returns true if a drag of the chart ended.
+    pub fn mouse_up(&mut self) -> bool {
+        self.drag.take().is_some()
+    }
+
+    /// Zooms the time axis by `lines` wheel notches around the cursor.
+    pub fn scroll(&mut self, lines: f32) -> bool {
+        let (Some((x, _)), Some(axes), Some((first, last))) =
+            (self.hover, self.axes(), self.buffer.range())
+        else {
+            return false;
+        };
+
+        let factor = 1.2_f32.powf(-lines);
+        let span = (axes.span * factor).clamp(MIN_SPAN, (last - first).max(MIN_SPAN));
+        let anchor = axes.x_to_time(x);
+        let start = anchor - (anchor - axes.start) * span / axes.span;
+
+        self.view = View::Fixed { start, span };
+        true
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, annotations: &Annotations) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = self.rect;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.6),
+        );
+
+        let Some(axes) = self.axes() else {
+            return;
+        };
+
+        let zero = axes.value_to_y(0.0);
+        graphics.draw_line((left, zero), (left + width, zero), 1.0, Color::LIGHT_GRAY);
+
+        // Reduce to one min/max pair per pixel column so the cost depends on
+        // the chart width, not on how much history is in view.
+        let columns = width as usize;
+        let mut ranges = vec![None::<(f32, f32)>; columns];
+        for bin in self.buffer.bins(axes.start, axes.start + axes.span) {
+            let column = axes.time_to_x(bin.time) - left;
+            if column < 0.0 || column >= width {
+                continue;
+            }
+            let range = &mut ranges[column as usize];
+            *range = Some(match *range {
+                Some((min, max)) => (min.min(bin.min), max.max(bin.max)),
+                None => (bin.min, bin.max),
+            });
+        }
+
+        let mut trace = Vec::with_capacity(columns);
+        for (column, range) in ranges.iter().enumerate() {
+            let Some((min, max)) = range else {
+                continue;
+            };
+            let x = left + column as f32;
+            let (y_min, y_max) = (axes.value_to_y(*min), axes.value_to_y(*max));
+
+            if y_min - y_max > 1.0 {
+                graphics.draw_line((x, y_min), (x, y_max), 1.0, Color::BLUE);
+            }
+            trace.push(Vector::new(x, 0.5 * (y_min + y_max)));
+        }
+        polyline::draw_polyline(graphics, &trace, 1.5, Color::BLUE);
+
+        self.draw_markers(graphics, font, &axes, annotations);
+
+        if let Some((x, _)) = self.hover {
+            graphics.draw_line((x, top), (x, top + height), 1.0, Color::DARK_GRAY);
+
+            let time = axes.x_to_time(x);
+            if let Some(value) = self.buffer.value_at(time) {
+                graphics.draw_text(
+                    (left + 4.0, top + 2.0),
+                    Color::BLACK,
+                    &font.layout_text(
+                        &format!("t = {:.2} s, angle = {:.3}", time, value),
+                        20.0,
+                        TextOptions::new(),
+                    ),
+                );
+            }
+        }
+
+        if matches!(self.view, View::Fixed { .. }) {
+            graphics.draw_text(
+                (left + width - 110.0, top + height - 22.0),
+                Color::DARK_GRAY,
+                &font.layout_text("double-click: live", 20.0, TextOptions::new()),
+            );
+        }
+    }
+
+    /// Marks each note along the bottom edge, with labels stacked into rows
+    /// so neighbouring ones don't overlap.
+    fn draw_markers(
+        &self,
+        graphics: &mut Graphics2D,
+        font: &Font,
+        axes: &Axes,
+        annotations: &Annotations,
+    ) {
+        let (left, top, width, height) = self.rect;
+        let bottom = top + height;
+        let mut row_ends = [f32::NEG_INFINITY; LABEL_ROWS];
+
+        for (i, note) in annotations.notes.iter().enumerate() {
+            let x = axes.time_to_x(note.time);
+            if x < left || x > left + width {
+                continue;
+            }
+
+            let color = if annotations.selected == Some(i) {
+                Color::RED
+            } else {
+                Color::from_rgb(0.9, 0.5, 0.1)
+            };
+            graphics.draw_line(
+                (x, top),
+                (x, bottom),
+                1.0,
+                Color::from_rgba(0.9, 0.5, 0.1, 0.4),
+            );
+            graphics.draw_triangle(
+                [
+                    (x, bottom - MARKER_STRIP).into(),
+                    (x + 5.0, bottom).into(),
+                    (x - 5.0, bottom).into(),
+                ],
+                color,
+            );
+
+            let label: String = note.text.chars().take(LABEL_LENGTH).collect();
+            let text = font.layout_text(&label, 16.0, TextOptions::new());
+            let Some(row) = row_ends.iter().position(|end| *end + 4.0 < x) else {
+                continue;
+            };
+            if x + text.width() > left + width {
+                continue;
+            }
+            row_ends[row] = x + text.width();
+            graphics.draw_text(
+                (x + 3.0, bottom - MARKER_STRIP - 16.0 * (row + 1) as f32),
+                Color::from_rgb(0.5, 0.25, 0.0),
+                &text,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AXES: Axes = Axes {
+        start: 20.0,
+        span: 10.0,
+        scale: 2.0,
+        rect: (100.0, 50.0, 400.0, 200.0),
+    };
+
+    #[test]
+    fn the_time_axis_maps_the_span_across_the_chart_and_back() {
+        assert_eq!(AXES.time_to_x(20.0), 100.0);
+        assert_eq!(AXES.time_to_x(30.0), 500.0);
+        assert_eq!(AXES.time_to_x(25.0), 300.0);
+        for time in [18.0, 20.0, 23.7, 30.0, 41.0] {
+            let back = AXES.x_to_time(AXES.time_to_x(time));
+            assert!((back - time).abs() < 1e-4, "{} came back as {}", time, back);
+        }
+        for x in [100.0, 212.5, 500.0] {
+            assert!((AXES.time_to_x(AXES.x_to_time(x)) - x).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn the_value_axis_puts_the_scale_at_the_edges_and_zero_in_the_middle() {
+        assert_eq!(AXES.value_to_y(2.0), 50.0);
+        assert_eq!(AXES.value_to_y(-2.0), 250.0);
+        assert_eq!(AXES.value_to_y(0.0), 150.0);
+        assert!(AXES.value_to_y(1.0) < AXES.value_to_y(0.5));
+    }
+
+    #[test]
+    fn the_live_view_follows_the_data_and_zooming_keeps_the_time_under_the_cursor() {
+        let mut chart = StripChart::new(1 << 20, None);
+        chart.visible = true;
+        for i in 0..=600 {
+            let time = i as f32 / 20.0;
+            chart.buffer.push(time, 1.5 * time.sin());
+        }
+        let axes = chart.axes().unwrap();
+        assert_eq!((axes.start, axes.span), (20.0, DEFAULT_SPAN));
+        assert!(axes.scale > 1.4 && axes.scale <= 1.5);
+
+        let (left, top, width, height) = chart.rect;
+        let x = left + 0.25 * width;
+        chart.mouse_move(x, top + 0.5 * height);
+        let under = axes.x_to_time(x);
+        assert!(chart.scroll(2.0));
+        let zoomed = chart.axes().unwrap();
+        assert!(zoomed.span < axes.span);
+        assert!((zoomed.x_to_time(x) - under).abs() < 1e-3);
+    }
+}
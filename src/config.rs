@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::Path;
+
+/// A config or preset file: `[section]` headers followed by lines belonging
+/// to that section. Blank lines and lines starting with `#` are ignored.
+/// Sections interpret their own lines; this only splits the file up.
+pub struct Config {
+    sections: Vec<Section>,
+}
+
+pub struct Section {
+    pub name: String,
+    /// Line contents paired with their 1-based line numbers for errors.
+    pub lines: Vec<(usize, String)>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut sections: Vec<Section> = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or(format!("line {}: unterminated section header", line_number))?;
+                sections.push(Section {
+                    name: name.trim().to_string(),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            match sections.last_mut() {
+                Some(section) => section.lines.push((line_number, line.to_string())),
+                None => {
+                    return Err(format!(
+                        "line {}: entry outside of any section",
+                        line_number
+                    ))
+                }
+            }
+        }
+
+        Ok(Config { sections })
+    }
+
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+}
+
+/// `parse_f32` in double precision.
+pub fn parse_f64(key: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number: &f64| number.is_finite())
+        .ok_or_else(|| format!("invalid number for `{}`: `{}`", key, value))
+}
+
+/// Splits `key=value` tokens, as used by the entries of several sections.
+pub fn key_value(token: &str) -> Option<(&str, &str)> {
+    let (key, value) = token.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+/// Reads a finite number: `NaN` and `inf` parse as `f32`s but would only
+/// poison whatever they were set on.
+pub fn parse_f32(key: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number: &f32| number.is_finite())
+        .ok_or_else(|| format!("invalid number for `{}`: `{}`", key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_have_to_be_finite() {
+        assert_eq!(parse_f32("g", "9.81"), Ok(9.81));
+        assert_eq!(parse_f32("g", "-1e-3"), Ok(-1e-3));
+        for value in ["NaN", "nan", "inf", "-inf", "infinity", "1e39", "", "ten"] {
+            assert!(parse_f32("g", value).is_err(), "`{}` was taken", value);
+        }
+    }
+
+    #[test]
+    fn sections_keep_their_line_numbers() {
+        let config = Config::parse("# comment\n[a]\nx = 1\n\n[b]\ny=2\n").unwrap();
+        assert_eq!(
+            config.section("a").unwrap().lines,
+            vec![(3, "x = 1".to_string())]
+        );
+        assert_eq!(key_value("y=2"), Some(("y", "2")));
+        assert!(Config::parse("stray\n").is_err());
+        assert!(Config::parse("[open\n").is_err());
+    }
+}
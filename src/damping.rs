@@ -0,0 +1,451 @@
+//! Damping explorer: a slider over the damping ratio of the selected
+//! pendulum with the critical value marked, and a race of three ghost
+//! pendulums released together at under, critical and over damping.
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::focus::{Handling, Registry, Target};
+use crate::model::{Model, ModelEntry, PendulumEntry};
+use crate::pendulum::{DampingModel, DampingRegime, Pendulum};
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+use crate::STEPS_PER_SECOND;
+
+/// Damping ratio at the right end of the slider.
+const MAX_RATIO: f32 = 5.0;
+/// Slider positions this close to the middle snap to critical damping.
+const SNAP: f32 = 0.01;
+/// Angle the racing ghosts are released from.
+const RACE_ANGLE: f32 = 0.5;
+/// A ghost has settled once it stays within this fraction of the release
+/// angle.
+const SETTLE_BAND: f32 = 0.02;
+/// Steps after which a race is called off, settled or not.
+const RACE_STEPS: f32 = 90.0 * STEPS_PER_SECOND;
+
+const PANEL: (f32, f32, f32, f32) = (90.0, 262.0, 620.0, 74.0);
+const TRACK: (f32, f32, f32) = (110.0, 610.0, 308.0);
+const BUTTON: (f32, f32, f32, f32) = (630.0, 293.0, 64.0, 28.0);
+
+/// Damping ratio at slider position `u` in [0, 1]. The scale is
+/// exponential, `(b^u - 1) / (√b - 1)` with `√b = MAX_RATIO - 1`, which
+/// starts at zero, puts critical damping in the middle and reaches
+/// `MAX_RATIO` at the end, spreading out the ratios near critical.
+pub fn ratio_at(u: f32) -> f32 {
+    let root = MAX_RATIO - 1.0;
+    ((root * root).powf(u.clamp(0.0, 1.0)) - 1.0) / (root - 1.0)
+}
+
+/// Slider position of a damping ratio, inverting `ratio_at`.
+pub fn position_of(ratio: f32) -> f32 {
+    let root = MAX_RATIO - 1.0;
+    ((ratio.max(0.0) * (root - 1.0) + 1.0).ln() / (root * root).ln()).clamp(0.0, 1.0)
+}
+
+/// One ghost in a race.
+struct Ghost {
+    regime: DampingRegime,
+    pendulum: Pendulum,
+    /// Step at which it last came inside the settling band, while it stays
+    /// there.
+    inside_since: Option<f32>,
+}
+
+/// Three copies of the selected pendulum released at once, one per regime,
+/// timed until each settles for good.
+struct Race {
+    ghosts: Vec<Ghost>,
+    steps: f32,
+}
+
+impl Race {
+    fn new(entry: &PendulumEntry) -> Race {
+        let ghosts = [
+            DampingRegime::Underdamped,
+            DampingRegime::Critical,
+            DampingRegime::Overdamped,
+        ]
+        .into_iter()
+        .map(|regime| Ghost {
+            regime,
+            pendulum: Pendulum::from_entry(&PendulumEntry {
+                angle: RACE_ANGLE,
+                angular_velocity: 0.0,
+                damping: DampingModel::Viscous,
+                damping_ratio: Some(regime.ratio()),
+                drive_amplitude: 0.0,
+                time: 0.0,
+                ..entry.fresh()
+            }),
+            inside_since: None,
+        })
+        .collect();
+
+        Race { ghosts, steps: 0.0 }
+    }
+
+    /// Over when every ghost has been inside the band for a second, or time
+    /// ran out.
+    fn is_over(&self) -> bool {
+        self.steps >= RACE_STEPS
+            || self.ghosts.iter().all(|ghost| {
+                ghost
+                    .inside_since
+                    .is_some_and(|since| self.steps - since >= STEPS_PER_SECOND)
+            })
+    }
+
+    fn step(&mut self) {
+        if self.is_over() {
+            return;
+        }
+
+        self.steps += 1.0;
+        for ghost in &mut self.ghosts {
+            ghost.pendulum.update();
+            let inside = ghost.pendulum.angle.abs() < SETTLE_BAND * RACE_ANGLE;
+            ghost.inside_since = match ghost.inside_since {
+                Some(since) if inside => Some(since),
+                _ if inside => Some(self.steps),
+                _ => None,
+            };
+        }
+    }
+
+    fn summary(&self) -> String {
+        let times: Vec<String> = self
+            .ghosts
+            .iter()
+            .map(|ghost| {
+                let time = match ghost.inside_since {
+                    Some(since) if self.is_over() => format!("{:.1} s", since / STEPS_PER_SECOND),
+                    _ if self.is_over() => "never".to_string(),
+                    _ => "...".to_string(),
+                };
+                format!("{} {}", ghost.regime.name(), time)
+            })
+            .collect();
+        format!("Settled: {}", times.join(", "))
+    }
+}
+
+fn ghost_color(regime: DampingRegime, alpha: f32) -> Color {
+    match regime {
+        DampingRegime::Underdamped => Color::from_rgba(0.9, 0.2, 0.2, alpha),
+        DampingRegime::Critical => Color::from_rgba(0.1, 0.6, 0.2, alpha),
+        DampingRegime::Overdamped => Color::from_rgba(0.2, 0.3, 0.9, alpha),
+    }
+}
+
+fn inside((left, top, width, height): (f32, f32, f32, f32), x: f32, y: f32) -> bool {
+    x >= left && x <= left + width && y >= top && y <= top + height
+}
+
+pub struct DampingExplorer {
+    pub visible: bool,
+    dragging: bool,
+    race: Option<Race>,
+}
+
+impl DampingExplorer {
+    pub fn new() -> DampingExplorer {
+        DampingExplorer {
+            visible: false,
+            dragging: false,
+            race: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.dragging = false;
+        self.race = None;
+    }
+
+    /// Returns true if the explorer took the click: a drag of the slider,
+    /// or the race button, which races copies of `p`.
+    pub fn mouse_down(&mut self, x: f32, y: f32, p: &mut Pendulum) -> bool {
+        if !self.visible || !inside(PANEL, x, y) {
+            return false;
+        }
+
+        if inside(BUTTON, x, y) {
+            if let ModelEntry::Pendulum(entry) = p.entry() {
+                self.race = Some(Race::new(&entry));
+            }
+        } else if (y - TRACK.2).abs() < 12.0 && x >= TRACK.0 - 8.0 && x <= TRACK.1 + 8.0 {
+            self.dragging = true;
+            self.mouse_move(x, p);
+        }
+        true
+    }
+
+    /// Sets the damping of `p` from the mouse while the slider is dragged.
+    pub fn mouse_move(&mut self, x: f32, p: &mut Pendulum) {
+        if !self.dragging {
+            return;
+        }
+
+        let u = (x - TRACK.0) / (TRACK.1 - TRACK.0);
+        let critical = DampingRegime::Critical.ratio();
+        let ratio = if (u - position_of(critical)).abs() < SNAP {
+            critical
+        } else {
+            ratio_at(u)
+        };
+
+        p.damping_model = DampingModel::Viscous;
+        p.zeta = Some(ratio);
+    }
+
+    /// Registers the slider's knob and the race button, when shown.
+    pub fn register(&self, registry: &mut Registry, p: &Pendulum) {
+        if !self.visible {
+            return;
+        }
+        let (from, to, y) = TRACK;
+        let zeta = p.damping_ratio().unwrap_or(0.0);
+        registry.register(
+            Target::DampingSlider,
+            (from + (to - from) * position_of(zeta), y),
+            7.0,
+            Handling::Drag,
+        );
+        let (bx, by, bw, bh) = BUTTON;
+        registry.register(
+            Target::RaceButton,
+            (bx + bw / 2.0, by + bh / 2.0),
+            bw / 2.0,
+            Handling::Click,
+        );
+    }
+
+    /// Returns true if a drag of the slider ended.
+    pub fn mouse_up(&mut self) -> bool {
+        std::mem::replace(&mut self.dragging, false)
+    }
+
+    pub fn step(&mut self) {
+        if let Some(race) = &mut self.race {
+            race.step();
+        }
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, view: &Viewport, p: &Pendulum) {
+        if !self.visible {
+            return;
+        }
+
+        if let Some(race) = &self.race {
+            for ghost in &race.ghosts {
+                let g = &ghost.pendulum;
+                let bob = view.world_to_screen(Vector::new(
+                    g.origin.x + g.r * g.angle.sin(),
+                    g.origin.y + g.r * g.angle.cos(),
+                ));
+                graphics.draw_line(
+                    view.world_to_screen(g.origin),
+                    bob,
+                    view.length(2.0),
+                    ghost_color(ghost.regime, 0.5),
+                );
+                graphics.draw_circle(bob, view.length(20.0), ghost_color(ghost.regime, 0.4));
+            }
+        }
+
+        let (left, top, width, height) = PANEL;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.7),
+        );
+        let text = |graphics: &mut Graphics2D, position: (f32, f32), text: &str, color: Color| {
+            graphics.draw_text(
+                position,
+                color,
+                &font.layout_text(text, 18.0, TextOptions::new()),
+            );
+        };
+
+        let heading = match (p.damping_ratio(), p.critical_coefficient()) {
+            (Some(zeta), Some(critical)) => format!(
+                "Damping ratio {:.3}: {:.4} per step, critical {:.4} at m = {}, g = {:.2}, r = {:.0}",
+                zeta,
+                p.damping_coefficient(),
+                critical,
+                p.m,
+                p.g,
+                p.r
+            ),
+            _ => "Dry friction has no critical damping: drag to switch to viscous".to_string(),
+        };
+        text(graphics, (left + 8.0, top + 4.0), &heading, Color::BLACK);
+
+        let (from, to, y) = TRACK;
+        let x_at = |ratio: f32| from + (to - from) * position_of(ratio);
+        graphics.draw_line((from, y), (to, y), 3.0, Color::DARK_GRAY);
+        for regime in [DampingRegime::Underdamped, DampingRegime::Overdamped] {
+            let x = x_at(regime.ratio());
+            graphics.draw_line((x, y - 5.0), (x, y + 5.0), 1.0, ghost_color(regime, 1.0));
+        }
+        let critical = x_at(DampingRegime::Critical.ratio());
+        graphics.draw_line(
+            (critical, y - 10.0),
+            (critical, y + 10.0),
+            2.0,
+            ghost_color(DampingRegime::Critical, 1.0),
+        );
+        text(graphics, (from - 4.0, y + 8.0), "0", Color::DARK_GRAY);
+        text(
+            graphics,
+            (critical - 20.0, y + 8.0),
+            "critical",
+            Color::DARK_GRAY,
+        );
+        text(
+            graphics,
+            (to - 12.0, y + 8.0),
+            &format!("{}", MAX_RATIO),
+            Color::DARK_GRAY,
+        );
+        if let Some(zeta) = p.damping_ratio() {
+            graphics.draw_circle((x_at(zeta), y), 7.0, Color::BLUE);
+        }
+
+        let (bx, by, bw, bh) = BUTTON;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((bx, by), (bx + bw, by + bh)),
+            Color::LIGHT_GRAY,
+        );
+        text(graphics, (bx + 14.0, by + 5.0), "Race", Color::BLACK);
+
+        if let Some(race) = &self.race {
+            text(
+                graphics,
+                (left + 8.0, top + height + 4.0),
+                &race.summary(),
+                Color::BLACK,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pendulum(r: f32, g: f32, m: f32) -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, r);
+        p.g = g;
+        p.m = m;
+        p
+    }
+
+    #[test]
+    fn the_slider_puts_critical_in_the_middle_and_spreads_it_out() {
+        assert_eq!(ratio_at(0.0), 0.0);
+        assert!((ratio_at(0.5) - 1.0).abs() < 1e-6);
+        assert!((ratio_at(1.0) - MAX_RATIO).abs() < 1e-5);
+        assert_eq!(ratio_at(-1.0), 0.0);
+        assert_eq!(ratio_at(2.0), ratio_at(1.0));
+
+        for i in 0..=40 {
+            let u = i as f32 / 40.0;
+            assert!((position_of(ratio_at(u)) - u).abs() < 1e-5, "at {}", u);
+        }
+        assert_eq!(position_of(-1.0), 0.0);
+        assert_eq!(position_of(2.0 * MAX_RATIO), 1.0);
+
+        // A stretch of slider covers less of the ratio near critical than
+        // towards the end.
+        let near = ratio_at(0.55) - ratio_at(0.45);
+        let far = ratio_at(1.0) - ratio_at(0.9);
+        assert!(near < 0.5 * far, "{} against {}", near, far);
+    }
+
+    #[test]
+    fn the_critical_value_follows_the_mass_gravity_and_length() {
+        let mut previous = None;
+        for (r, g, m) in [(200.0, 0.5, 1.0), (200.0, 2.0, 1.0), (80.0, 0.5, 5.0)] {
+            let mut p = pendulum(r, g, m);
+            let critical = p.critical_coefficient().unwrap();
+            assert_ne!(Some(critical), previous);
+            previous = Some(critical);
+
+            p.zeta = Some(1.0);
+            assert!((p.damping_coefficient() - critical).abs() < 1e-6 * critical);
+            p.zeta = Some(0.5);
+            assert!(p.damping_coefficient() < critical);
+        }
+
+        let mut p = pendulum(200.0, 0.5, 1.0);
+        p.damping_model = DampingModel::Coulomb;
+        assert!(p.critical_coefficient().is_none());
+    }
+
+    #[test]
+    fn dragging_the_slider_sets_a_viscous_ratio_and_snaps_to_critical() {
+        let mut explorer = DampingExplorer::new();
+        let mut p = pendulum(200.0, 0.5, 1.0);
+        p.damping_model = DampingModel::Coulomb;
+        let (from, to, y) = TRACK;
+        let x_at = |u: f32| from + (to - from) * u;
+
+        // Hidden, it takes nothing.
+        assert!(!explorer.mouse_down(x_at(0.25), y, &mut p));
+        explorer.toggle();
+
+        assert!(explorer.mouse_down(x_at(0.25), y, &mut p));
+        assert!(matches!(p.damping_model, DampingModel::Viscous));
+        assert!((p.zeta.unwrap() - ratio_at(0.25)).abs() < 1e-5);
+
+        explorer.mouse_move(x_at(0.5 + 0.5 * SNAP), &mut p);
+        assert_eq!(p.zeta, Some(DampingRegime::Critical.ratio()));
+        explorer.mouse_move(x_at(0.8), &mut p);
+        assert!((p.zeta.unwrap() - ratio_at(0.8)).abs() < 1e-4);
+
+        assert!(explorer.mouse_up());
+        assert!(!explorer.mouse_up());
+        explorer.mouse_move(x_at(0.1), &mut p);
+        assert!((p.zeta.unwrap() - ratio_at(0.8)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn critical_damping_wins_the_race() {
+        let mut explorer = DampingExplorer::new();
+        explorer.toggle();
+        let mut p = pendulum(200.0, 0.5, 1.0);
+        let (bx, by, bw, bh) = BUTTON;
+        assert!(explorer.mouse_down(bx + bw / 2.0, by + bh / 2.0, &mut p));
+
+        let mut overshot = [false; 3];
+        let race = explorer.race.as_mut().unwrap();
+        while !race.is_over() {
+            race.step();
+            for (ghost, overshot) in race.ghosts.iter().zip(&mut overshot) {
+                *overshot |= ghost.pendulum.angle < 0.0;
+            }
+        }
+
+        assert!(race.steps < RACE_STEPS, "a ghost never settled");
+        let settled: Vec<f32> = race
+            .ghosts
+            .iter()
+            .map(|ghost| ghost.inside_since.unwrap())
+            .collect();
+        assert!(
+            settled[1] < settled[0] && settled[1] < settled[2],
+            "{:?}",
+            settled
+        );
+        // Only the underdamped one swings through the bottom.
+        assert_eq!(overshot, [true, false, false]);
+        assert!(race.summary().starts_with("Settled: underdamped "));
+        assert!(!race.summary().contains("never"));
+
+        // The ghosts are copies: the pendulum raced is left alone.
+        assert_eq!(p.angle, 1.0);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::config::{key_value, parse_f32, Section};
+use crate::presets::{Preset, PRESETS};
+use crate::STEPS_PER_SECOND;
+
+/// Presets the reel shows, in order.
+const REEL: &[&str] = &["Single pendulum", "Double pendulum", "Pendulum wave"];
+/// Seconds each preset is shown for, unless `[demo]` says otherwise.
+const DEFAULT_DURATION: f32 = 10.0;
+
+/// Attract mode: cycles through a few presets on its own until someone
+/// presses a key or clicks.
+pub struct DemoReel {
+    presets: Vec<&'static Preset>,
+    current: usize,
+    /// Frames each preset is shown for.
+    duration: usize,
+    frames: usize,
+}
+
+impl DemoReel {
+    pub fn new(duration: f32) -> DemoReel {
+        DemoReel {
+            presets: REEL
+                .iter()
+                .filter_map(|name| PRESETS.iter().find(|preset| preset.name == *name))
+                .collect(),
+            current: 0,
+            duration: (duration * STEPS_PER_SECOND).max(1.0) as usize,
+            frames: 0,
+        }
+    }
+
+    /// Reads the `[demo]` section: `enabled = true|false` and
+    /// `duration = <seconds per preset>`. Returns `None` if the reel is off.
+    pub fn from_config(section: &Section) -> Result<Option<DemoReel>, String> {
+        let mut enabled = true;
+        let mut duration = DEFAULT_DURATION;
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("enabled", value)) => {
+                    enabled = value
+                        .parse()
+                        .map_err(|_| error(format!("expected true or false, found `{}`", value)))?
+                }
+                Some(("duration", value)) => {
+                    duration = parse_f32("duration", value).map_err(error)?
+                }
+                _ => return Err(error(format!("unknown demo setting `{}`", line))),
+            }
+        }
+
+        Ok(enabled.then(|| DemoReel::new(duration)))
+    }
+
+    pub fn default_reel() -> DemoReel {
+        DemoReel::new(DEFAULT_DURATION)
+    }
+
+    pub fn current(&self) -> Option<&'static Preset> {
+        self.presets.get(self.current).copied()
+    }
+
+    /// Counts a frame and returns the next preset when it's time to switch.
+    pub fn tick(&mut self) -> Option<&'static Preset> {
+        self.frames += 1;
+        if self.frames < self.duration || self.presets.is_empty() {
+            return None;
+        }
+
+        self.frames = 0;
+        self.current = (self.current + 1) % self.presets.len();
+        self.current()
+    }
+}
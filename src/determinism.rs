@@ -0,0 +1,259 @@
+//! The determinism audit. `--determinism-dump <file>` steps a fixed scene,
+//! one of each model and every way a pendulum can step, and writes the
+//! exact bits of the state every so often; `--determinism-check <file>`
+//! steps it again and compares with a dump made on another machine,
+//! reporting where the two first parted and by how much. Built with the
+//! `deterministic` feature the two should never part; see `math`.
+//!
+//! A dump is a header then a line per sample, the step and then each
+//! model's state as the hex of its `f64`s, the models separated by `|`:
+//!
+//! ```text
+//! pendulum determinism dump
+//! functions = portable
+//! every = 60
+//! steps = 18000
+//! 0 3ff0000000000000 0000000000000000 ... | 4000000000000000 ...
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use crate::math;
+use crate::model::ModelEntry;
+use crate::scene::Scene;
+use crate::units::Units;
+use crate::STEPS_PER_SECOND;
+
+const HEADER: &str = "pendulum determinism dump";
+/// Steps between samples.
+const EVERY: usize = STEPS_PER_SECOND as usize;
+/// Five minutes, long enough for the chaotic models to have made anything
+/// that differs at the start differ a lot.
+const STEPS: usize = 300 * STEPS_PER_SECOND as usize;
+
+/// A large driven swing, a string thrown hard enough to go slack, friction
+/// at the pivot, a gravity gradient, one stepping with RK4 at five times the
+/// rate, and one of each of the other models.
+const SCENARIO: [&str; 8] = [
+    "pendulum angle=2.5 damping_ratio=0.02 drive_amplitude=0.002 drive_frequency=0.07",
+    "pendulum r=150 angle=0 angular_velocity=0.12 string=true",
+    "pendulum angle=1.2 damping=coulomb",
+    "pendulum angle=2 gravity_gradient=0.002",
+    "pendulum angle=1.5 integrator=rk4 rate=5",
+    "double angle1=2 angle2=2.5",
+    "chain links=5 angle=1.3",
+    "cart angle=2.8 velocity=1",
+];
+
+fn functions() -> &'static str {
+    if math::DETERMINISTIC {
+        "portable"
+    } else {
+        "platform"
+    }
+}
+
+fn scenario() -> Scene {
+    Scene::new(
+        SCENARIO
+            .iter()
+            .map(|line| ModelEntry::parse(line).expect("the determinism scenario parses"))
+            .collect(),
+    )
+}
+
+/// The scenario's state every `every` steps up to `steps`, starting with
+/// where it starts.
+fn run(every: usize, steps: usize) -> Vec<(usize, Vec<Vec<f64>>)> {
+    let mut scene = scenario();
+    let sample = |scene: &Scene| scene.models.iter().map(|model| model.state()).collect();
+    let mut samples = vec![(0, sample(&scene))];
+    for step in 1..=steps {
+        scene.step();
+        if step % every == 0 {
+            samples.push((step, sample(&scene)));
+        }
+    }
+    samples
+}
+
+fn format_sample(step: usize, models: &[Vec<f64>]) -> String {
+    let models: Vec<String> = models
+        .iter()
+        .map(|state| {
+            state
+                .iter()
+                .map(|x| format!("{:016x}", x.to_bits()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    format!("{} {}", step, models.join(" | "))
+}
+
+fn parse_sample(line: &str) -> Result<(usize, Vec<Vec<f64>>), String> {
+    let (step, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let step = step
+        .parse()
+        .map_err(|_| format!("invalid step `{}`", step))?;
+    let models = rest
+        .split('|')
+        .map(|state| {
+            state
+                .split_whitespace()
+                .map(|word| {
+                    u64::from_str_radix(word, 16)
+                        .map(f64::from_bits)
+                        .map_err(|_| format!("invalid bits `{}`", word))
+                })
+                .collect()
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((step, models))
+}
+
+/// Writes the dump to `path`, and says what it wrote.
+pub fn dump(path: &Path) -> Result<String, String> {
+    let samples = run(EVERY, STEPS);
+    let mut text = format!(
+        "{}\nfunctions = {}\nevery = {}\nsteps = {}\n",
+        HEADER,
+        functions(),
+        EVERY,
+        STEPS
+    );
+    for (step, models) in &samples {
+        text.push_str(&format_sample(*step, models));
+        text.push('\n');
+    }
+    fs::write(path, text).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    Ok(format!(
+        "Wrote {} samples of {} models to {} with the {} functions",
+        samples.len(),
+        SCENARIO.len(),
+        path.display(),
+        functions()
+    ))
+}
+
+/// Steps the scenario as the dump at `path` did and compares. Returns the
+/// report and whether every bit matched.
+pub fn check(path: &Path) -> Result<(Vec<String>, bool), String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err(format!("{} isn't a determinism dump", path.display()));
+    }
+
+    let mut setting = |key: &str| {
+        let (line_number, line) = lines
+            .next()
+            .ok_or_else(|| format!("the dump ends before `{}`", key))?;
+        let error = |message: String| format!("line {}: {}", line_number, message);
+        match line.split_once('=') {
+            Some((found, value)) if found.trim() == key => Ok(value.trim().to_string()),
+            _ => Err(error(format!("expected `{} = ...`, found `{}`", key, line))),
+        }
+    };
+    let theirs = setting("functions")?;
+    let count = |value: String| {
+        value
+            .parse()
+            .ok()
+            .filter(|&n: &usize| n > 0)
+            .ok_or_else(|| format!("invalid count `{}`", value))
+    };
+    let every = count(setting("every")?)?;
+    let steps = count(setting("steps")?)?;
+
+    let mut report = Vec::new();
+    if theirs != functions() {
+        report.push(format!(
+            "The dump was made with the {} functions and this with the {}, so they may well differ",
+            theirs,
+            functions()
+        ));
+    }
+
+    let ours = run(every, steps);
+    let mut last_match = None;
+    for (ours, (line_number, line)) in ours.iter().zip(lines) {
+        let (step, models) =
+            parse_sample(line).map_err(|e| format!("line {}: {}", line_number, e))?;
+        if step != ours.0 {
+            return Err(format!(
+                "line {}: expected step {}, found {}",
+                line_number, ours.0, step
+            ));
+        }
+        if let Some(difference) = first_difference(&ours.1, &models) {
+            let since = match last_match {
+                Some(matched) => format!(", after matching at step {}", matched),
+                None => ", from the start".to_string(),
+            };
+            report.push(format!("First differs at step {}{}", step, since));
+            report.push(difference);
+            return Ok((report, false));
+        }
+        last_match = Some(step);
+    }
+
+    match last_match {
+        Some(step) if step == ours.last().map_or(0, |(step, _)| *step) => {
+            report.push(format!(
+                "Every bit matches, over {} samples to step {}",
+                ours.len(),
+                step
+            ));
+            Ok((report, true))
+        }
+        _ => Err(format!(
+            "the dump ends early, at step {}",
+            last_match.map_or(0, |step| step)
+        )),
+    }
+}
+
+/// Where `theirs` first differs from `ours`, and by how much.
+fn first_difference(ours: &[Vec<f64>], theirs: &[Vec<f64>]) -> Option<String> {
+    if ours.len() != theirs.len() {
+        return Some(format!(
+            "The dump has {} models, this scenario {}",
+            theirs.len(),
+            ours.len()
+        ));
+    }
+    for (i, (ours, theirs)) in ours.iter().zip(theirs).enumerate() {
+        let model = ModelEntry::parse(SCENARIO[i])
+            .map(|entry| entry.build().label(&Units::default()))
+            .unwrap_or_default();
+        if ours.len() != theirs.len() {
+            return Some(format!(
+                "Model {} ({}) has {} numbers in the dump and {} here",
+                i + 1,
+                model,
+                theirs.len(),
+                ours.len()
+            ));
+        }
+        for (j, (a, b)) in ours.iter().zip(theirs).enumerate() {
+            if a.to_bits() == b.to_bits() {
+                continue;
+            }
+            let difference = (a - b).abs();
+            return Some(format!(
+                "Model {} ({}), number {}: {} here, {} in the dump, {:.3e} apart ({:.3e} relative)",
+                i + 1,
+                model,
+                j + 1,
+                a,
+                b,
+                difference,
+                difference / a.abs().max(b.abs()).max(f64::MIN_POSITIVE)
+            ));
+        }
+    }
+    None
+}
@@ -0,0 +1,187 @@
+//! Debug log and the panic hook that bundles it up with everything else
+//! useful for working out what went wrong.
+
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Lines of the debug log kept for the bundle.
+const LOG_LINES: usize = 200;
+/// Where bundles are written, one directory per crash.
+const DIAGNOSTICS_DIR: &str = "diagnostics";
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+/// Bytes the chart and the history took at the last frame.
+static RETENTION: AtomicUsize = AtomicUsize::new(0);
+static SESSION: OnceLock<Session> = OnceLock::new();
+
+/// What the hook copies into the bundle besides the log.
+struct Session {
+    started: Instant,
+    config: Option<String>,
+    recovery: PathBuf,
+    /// Where the bundles go.
+    directory: PathBuf,
+}
+
+/// Prints a line to stderr and keeps it for the bundle. Used through `log!`.
+pub fn record(line: String) {
+    eprintln!("{}", line);
+
+    if let Ok(mut log) = LOG.lock() {
+        if log.capacity() == 0 {
+            log.reserve_exact(LOG_LINES);
+        }
+        if log.len() == LOG_LINES {
+            log.pop_front();
+        }
+        log.push_back(line);
+    }
+}
+
+pub fn count_frame() {
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn set_retention(bytes: usize) {
+    RETENTION.store(bytes, Ordering::Relaxed);
+}
+
+/// Installs the panic hook. `config` is the text of the config file the
+/// session started with, `recovery` the autosave file.
+pub fn install(config: Option<String>, recovery: &Path) {
+    install_in(config, recovery, Path::new(DIAGNOSTICS_DIR));
+}
+
+/// `install`, writing the bundles under `directory`.
+fn install_in(config: Option<String>, recovery: &Path, directory: &Path) {
+    let _ = SESSION.set(Session {
+        started: Instant::now(),
+        config,
+        recovery: recovery.to_path_buf(),
+        directory: directory.to_path_buf(),
+    });
+
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default(info);
+
+        match write_bundle(info) {
+            Ok(path) => eprintln!(
+                "\n============================================================\n  \
+                 The pendulum crashed. Diagnostics were written to\n  {}\n\
+                 ============================================================",
+                path.display()
+            ),
+            Err(e) => eprintln!("could not write the diagnostic bundle: {}", e),
+        }
+    }));
+}
+
+/// Writes each part of the bundle on a best-effort basis: one that fails
+/// doesn't stop the others. Never locks the log if the panic happened while
+/// holding it.
+fn write_bundle(info: &PanicHookInfo) -> io::Result<PathBuf> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let directory = match SESSION.get() {
+        Some(session) => session.directory.join(format!("crash-{}", seconds)),
+        None => Path::new(DIAGNOSTICS_DIR).join(format!("crash-{}", seconds)),
+    };
+    fs::create_dir_all(&directory)?;
+
+    let _ = fs::write(
+        directory.join("panic.txt"),
+        format!("{}\n\n{}", info, Backtrace::force_capture()),
+    );
+
+    if let Ok(log) = LOG.try_lock() {
+        let mut text = String::new();
+        for line in log.iter() {
+            text.push_str(line);
+            text.push('\n');
+        }
+        let _ = fs::write(directory.join("log.txt"), text);
+    }
+
+    if let Some(session) = SESSION.get() {
+        if let Some(config) = &session.config {
+            let _ = fs::write(directory.join("config.cfg"), config);
+        }
+        let _ = fs::copy(&session.recovery, directory.join("recovery.cfg"));
+        let _ = fs::write(
+            directory.join("stats.txt"),
+            format!(
+                "uptime = {:.1} s\nframes = {}\nretention = {} bytes\n",
+                session.started.elapsed().as_secs_f32(),
+                FRAMES.load(Ordering::Relaxed),
+                RETENTION.load(Ordering::Relaxed)
+            ),
+        );
+    }
+
+    Ok(directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    static EARLIER_HOOK_RAN: AtomicBool = AtomicBool::new(false);
+
+    #[test]
+    fn a_panic_writes_the_bundle_and_still_reaches_the_earlier_hook() {
+        let root =
+            std::env::temp_dir().join(format!("pendulum-diagnostics-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let recovery = root.join("recovery.cfg");
+        fs::write(&recovery, "[scene]\npendulum r=1\n").unwrap();
+        let directory = root.join("diagnostics");
+
+        panic::set_hook(Box::new(|_| EARLIER_HOOK_RAN.store(true, Ordering::SeqCst)));
+        install_in(
+            Some("[retention]\nmemory = 20\n".to_string()),
+            &recovery,
+            &directory,
+        );
+        record("before the crash".to_string());
+        count_frame();
+        assert!(!directory.exists(), "nothing is written before a panic");
+
+        let crashed = thread::spawn(|| panic!("a controlled panic")).join();
+        drop(panic::take_hook());
+        assert!(crashed.is_err());
+        assert!(EARLIER_HOOK_RAN.load(Ordering::SeqCst));
+
+        let bundles: Vec<PathBuf> = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(bundles.len(), 1);
+        let bundle = &bundles[0];
+        assert!(bundle
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("crash-"));
+        let read = |name: &str| fs::read_to_string(bundle.join(name)).unwrap();
+        assert!(read("panic.txt").contains("a controlled panic"));
+        assert!(read("log.txt").contains("before the crash"));
+        assert_eq!(read("config.cfg"), "[retention]\nmemory = 20\n");
+        assert_eq!(read("recovery.cfg"), "[scene]\npendulum r=1\n");
+        assert!(read("stats.txt").contains("frames = "));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,494 @@
+//! What's different between two saved sessions, for when two people's
+//! pendulums don't swing alike. Both files are read with the same parsers
+//! the program loads them with and written back out, so an older spelling
+//! of an entry, such as a chain's `links=3 angle=1`, compares equal to its
+//! expanded form. Models are matched by name, and the rest by order.
+//!
+//! Each difference is one of three kinds: configuration, which changes how
+//! things swing (a model's parameters and type, its integrator and damping,
+//! the obstacles, walls and LFOs, a model added or removed); state, where the
+//! same setup is just somewhere else in its swing; and cosmetic, which
+//! doesn't change the motion at all (names, notes, panels shown, macros).
+//! Cosmetic differences are left out unless all are asked for.
+//!
+//! `--diff <a> <b> [--all]` prints the differences and exits with `0` if
+//! there are none, `1` if only the state differs and `3` if the
+//! configuration does. The `diff <file> [all]` console command compares the
+//! session as it is now with a file.
+
+use std::collections::BTreeSet;
+
+use crate::config::{key_value, Config, Section};
+use crate::model::{self, ModelEntry};
+use crate::obstacles::Obstacles;
+use crate::scene::Scene;
+use crate::walls::Walls;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Cosmetic,
+    State,
+    Configuration,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Cosmetic => "cosmetic",
+            Kind::State => "state",
+            Kind::Configuration => "configuration",
+        }
+    }
+}
+
+pub struct Difference {
+    pub kind: Kind,
+    /// What differs, such as `pendulum "left" g` or `walls restitution`.
+    pub place: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// Sections whose differences change the motion. Anything else in a
+/// session or config file only changes what's shown.
+const CONFIGURATION_SECTIONS: &[&str] = &["scene", "obstacles", "walls", "lfo"];
+
+/// The fields of each model type that are where it is in its swing, rather
+/// than how it's set up.
+fn state_fields(tag: &str) -> &'static [&'static str] {
+    match tag {
+        "pendulum" => &[
+            "angle",
+            "angular_velocity",
+            "angular_acceleration",
+            "time",
+            "drive_offset",
+            "ramp_from",
+            "slack",
+            "substep",
+        ],
+        "chain" => &["angles", "angular_velocities"],
+        "double" => &["angle1", "angle2", "angular_velocity1", "angular_velocity2"],
+        "cart" => &["x", "angle", "angular_velocity", "velocity"],
+        _ => &[],
+    }
+}
+
+/// The energy ledger only keeps count of the motion.
+const COSMETIC_FIELDS: &[&str] = &["name", "ledger"];
+
+/// A model entry taken apart: its type and its fields in the order
+/// `ModelEntry::to_line` writes them.
+struct Fields {
+    line: String,
+    tag: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Fields {
+    fn of(entry: &ModelEntry) -> Fields {
+        let line = entry.to_line();
+        // Every line `to_line` writes splits, quotes and all.
+        let tokens = model::tokens(&line).unwrap_or_default();
+        let mut tokens = tokens.iter();
+        let tag = tokens.next().cloned().unwrap_or_default();
+        let fields = tokens
+            .filter_map(|token| key_value(token))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Fields { line, tag, fields }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.get("name")
+    }
+}
+
+/// Pairs up the models of two scenes: by name where both sides have the
+/// same one, then the rest in order. A model with nothing to pair with is
+/// paired with `None`.
+fn match_models(left: &[Fields], right: &[Fields]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut pairs = Vec::new();
+    let mut taken = vec![false; right.len()];
+    let mut unmatched = Vec::new();
+    for (i, model) in left.iter().enumerate() {
+        let named = model.name().and_then(|name| {
+            (0..right.len()).find(|&j| !taken[j] && right[j].name() == Some(name))
+        });
+        match named {
+            Some(j) => {
+                taken[j] = true;
+                pairs.push((Some(i), Some(j)));
+            }
+            None => unmatched.push(i),
+        }
+    }
+    let mut rest = (0..right.len())
+        .filter(|&j| !taken[j])
+        .collect::<Vec<_>>()
+        .into_iter();
+    for i in unmatched {
+        pairs.push((Some(i), rest.next()));
+    }
+    pairs.extend(rest.map(|j| (None, Some(j))));
+    pairs
+}
+
+fn describe(fields: &Fields, index: usize) -> String {
+    match fields.name() {
+        Some(name) => format!("{} \"{}\"", fields.tag, name),
+        None => format!("{} {}", fields.tag, index + 1),
+    }
+}
+
+fn compare_models(left: &[ModelEntry], right: &[ModelEntry], out: &mut Vec<Difference>) {
+    let left: Vec<Fields> = left.iter().map(Fields::of).collect();
+    let right: Vec<Fields> = right.iter().map(Fields::of).collect();
+
+    for pair in match_models(&left, &right) {
+        let (a, b) = match pair {
+            (Some(i), Some(j)) => (i, j),
+            (Some(i), None) => {
+                out.push(Difference {
+                    kind: Kind::Configuration,
+                    place: describe(&left[i], i),
+                    left: Some(left[i].line.clone()),
+                    right: None,
+                });
+                continue;
+            }
+            (None, Some(j)) => {
+                out.push(Difference {
+                    kind: Kind::Configuration,
+                    place: describe(&right[j], j),
+                    left: None,
+                    right: Some(right[j].line.clone()),
+                });
+                continue;
+            }
+            (None, None) => continue,
+        };
+        let (a, b, place) = (&left[a], &right[b], describe(&left[a], a));
+        if a.tag != b.tag {
+            out.push(Difference {
+                kind: Kind::Configuration,
+                place: format!("{} model type", place),
+                left: Some(a.tag.clone()),
+                right: Some(b.tag.clone()),
+            });
+            continue;
+        }
+
+        let keys: Vec<&str> = a
+            .fields
+            .iter()
+            .chain(&b.fields)
+            .map(|(key, _)| key.as_str())
+            .collect();
+        let mut seen = BTreeSet::new();
+        for key in keys.into_iter().filter(|key| seen.insert(*key)) {
+            let (x, y) = (a.get(key), b.get(key));
+            if x == y {
+                continue;
+            }
+            let kind = if COSMETIC_FIELDS.contains(&key) {
+                Kind::Cosmetic
+            } else if state_fields(&a.tag).contains(&key) {
+                Kind::State
+            } else {
+                Kind::Configuration
+            };
+            out.push(Difference {
+                kind,
+                place: format!("{} {}", place, key),
+                left: x.map(str::to_string),
+                right: y.map(str::to_string),
+            });
+        }
+    }
+}
+
+/// Compares two sections line by line: `key = value` lines by key, and the
+/// rest, such as obstacles, by their order.
+fn compare_lines(
+    name: &str,
+    kind: Kind,
+    left: &[String],
+    right: &[String],
+    out: &mut Vec<Difference>,
+) {
+    let split = |lines: &[String]| {
+        let mut keyed = Vec::new();
+        let mut plain = Vec::new();
+        for line in lines {
+            match key_value(line) {
+                Some((key, value)) => keyed.push((key.to_string(), value.to_string())),
+                None => plain.push(line.clone()),
+            }
+        }
+        (keyed, plain)
+    };
+    let ((left_keyed, left_plain), (right_keyed, right_plain)) = (split(left), split(right));
+
+    let get = |keyed: &[(String, String)], key: &str| {
+        keyed
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+    };
+    let mut seen = BTreeSet::new();
+    for (key, _) in left_keyed.iter().chain(&right_keyed) {
+        if !seen.insert(key.as_str()) {
+            continue;
+        }
+        let (x, y) = (get(&left_keyed, key), get(&right_keyed, key));
+        if x != y {
+            out.push(Difference {
+                kind,
+                place: format!("{} {}", name, key),
+                left: x,
+                right: y,
+            });
+        }
+    }
+    for i in 0..left_plain.len().max(right_plain.len()) {
+        let (x, y) = (left_plain.get(i), right_plain.get(i));
+        if x != y {
+            out.push(Difference {
+                kind,
+                place: format!("{} entry {}", name, i + 1),
+                left: x.cloned(),
+                right: y.cloned(),
+            });
+        }
+    }
+}
+
+/// A section's lines as the program would write them back out, for the
+/// sections that have their own format, and as they are for the rest.
+fn normalized(config: &Config, name: &str) -> Result<Vec<String>, String> {
+    let Some(section) = config.section(name) else {
+        return Ok(Vec::new());
+    };
+    let written = match name {
+        "obstacles" => Obstacles::from_config(section)
+            .map_err(|e| format!("[obstacles] {}", e))?
+            .to_section(),
+        "walls" => Walls::from_config(section)
+            .map_err(|e| format!("[walls] {}", e))?
+            .to_section(),
+        _ => return Ok(lines(section)),
+    };
+    Ok(Config::parse(&written)?
+        .section(name)
+        .map_or(Vec::new(), lines))
+}
+
+fn lines(section: &Section) -> Vec<String> {
+    section.lines.iter().map(|(_, line)| line.clone()).collect()
+}
+
+/// Every difference between two sessions, configuration first, with the
+/// cosmetic ones left out unless `all` is set.
+pub fn diff(left: &Config, right: &Config, all: bool) -> Result<Vec<Difference>, String> {
+    let entries =
+        |config: &Config| Scene::from_config(config).map(|scene| scene.initial_entries().to_vec());
+    let mut differences = Vec::new();
+    compare_models(&entries(left)?, &entries(right)?, &mut differences);
+
+    let mut names: Vec<&str> = Vec::new();
+    for section in left.sections().iter().chain(right.sections()) {
+        if section.name != "scene" && !names.contains(&section.name.as_str()) {
+            names.push(&section.name);
+        }
+    }
+    for name in names {
+        let kind = if CONFIGURATION_SECTIONS.contains(&name) {
+            Kind::Configuration
+        } else {
+            Kind::Cosmetic
+        };
+        let (a, b) = (normalized(left, name)?, normalized(right, name)?);
+        compare_lines(name, kind, &a, &b, &mut differences);
+    }
+
+    differences.retain(|difference| all || difference.kind != Kind::Cosmetic);
+    differences.sort_by_key(|difference| std::cmp::Reverse(difference.kind));
+    Ok(differences)
+}
+
+/// The most important kind of difference, if there are any.
+pub fn worst(differences: &[Difference]) -> Option<Kind> {
+    differences.iter().map(|difference| difference.kind).max()
+}
+
+/// `--diff`'s exit status for what was found.
+pub fn exit_code(differences: &[Difference]) -> i32 {
+    match worst(differences) {
+        None | Some(Kind::Cosmetic) => 0,
+        Some(Kind::State) => 1,
+        Some(Kind::Configuration) => 3,
+    }
+}
+
+/// The differences side by side, under a line naming the two sides. A
+/// side without the model or setting shows `-`, which for a model's field
+/// means the default it's left out at.
+pub fn report(left_name: &str, right_name: &str, differences: &[Difference]) -> Vec<String> {
+    let Some(worst) = worst(differences) else {
+        return vec![format!("{} and {} are the same", left_name, right_name)];
+    };
+    let width = differences
+        .iter()
+        .map(|difference| difference.place.len())
+        .max()
+        .unwrap_or(0);
+    let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+
+    let mut lines = vec![format!(
+        "{} | {}: {} difference{}, the most important {}",
+        left_name,
+        right_name,
+        differences.len(),
+        if differences.len() == 1 { "" } else { "s" },
+        worst.name()
+    )];
+    for difference in differences {
+        lines.push(format!(
+            "  {:<13} {:<width$}  {} | {}",
+            difference.kind.name(),
+            difference.place,
+            value(&difference.left),
+            value(&difference.right),
+            width = width
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn differences(left: &str, right: &str, all: bool) -> Vec<Difference> {
+        diff(
+            &Config::parse(left).unwrap(),
+            &Config::parse(right).unwrap(),
+            all,
+        )
+        .unwrap()
+    }
+
+    /// Each difference as `kind place: left | right`.
+    fn summary(differences: &[Difference]) -> Vec<String> {
+        let value = |value: &Option<String>| value.clone().unwrap_or("-".to_string());
+        differences
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} {}: {} | {}",
+                    d.kind.name(),
+                    d.place,
+                    value(&d.left),
+                    value(&d.right)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn each_field_is_of_its_kind() {
+        let left = "[scene]\npendulum name=a x=100 r=150 angle=0.5\n[panels]\nchart = true\n";
+        let right = "[scene]\npendulum name=a x=120 r=150 angle=0.7 damping=coulomb\n[panels]\nchart = false\n";
+        let found = differences(left, right, true);
+        assert_eq!(
+            summary(&found),
+            [
+                "configuration pendulum \"a\" x: 100 | 120",
+                "configuration pendulum \"a\" damping: viscous | coulomb",
+                "state pendulum \"a\" angle: 0.5 | 0.7",
+                "cosmetic panels chart: true | false",
+            ]
+        );
+        assert_eq!(worst(&found).map(Kind::name), Some("configuration"));
+        assert_eq!(exit_code(&found), 3);
+        assert_eq!(exit_code(&found[2..]), 1);
+        assert_eq!(exit_code(&found[3..]), 0);
+        assert_eq!(exit_code(&[]), 0);
+        // Without `all` the cosmetic one is left out.
+        assert_eq!(differences(left, right, false).len(), 3);
+    }
+
+    #[test]
+    fn models_are_matched_by_name_then_in_order() {
+        let left = "[scene]\npendulum name=a x=100\npendulum name=b x=200\nchain links=2\n";
+        let right = "[scene]\nchain links=2\npendulum name=b x=200\npendulum name=a x=100\n";
+        assert!(differences(left, right, true).is_empty());
+
+        // A renamed one pairs up with what's left, and its name is cosmetic.
+        let renamed = "[scene]\npendulum name=c x=100\npendulum name=b x=200\nchain links=2\n";
+        assert_eq!(
+            summary(&differences(left, renamed, true)),
+            ["cosmetic pendulum \"a\" name: a | c"]
+        );
+
+        // A different type of model in the same place.
+        let typed = "[scene]\npendulum name=a x=100\npendulum name=b x=200\ndouble\n";
+        assert_eq!(
+            summary(&differences(left, typed, false)),
+            ["configuration chain 3 model type: chain | double"]
+        );
+
+        // One taken away.
+        let named = "[scene]\npendulum name=a x=100\npendulum name=b x=200\n";
+        let fewer = "[scene]\npendulum name=b x=200\n";
+        let found = differences(named, fewer, false);
+        assert!(found.len() == 1 && found[0].place == "pendulum \"a\"" && found[0].right.is_none());
+    }
+
+    #[test]
+    fn older_spellings_compare_equal() {
+        let legacy = "[scene]\nchain links=3 angle=1.2\n[walls]\nleft = 100\nright = 700\n";
+        let expanded =
+            "[scene]\nchain angles=1.2,1.2,1.2\n[walls]\nright = 700\nrestitution = 0.9\nleft = 100\n";
+        assert!(differences(legacy, expanded, true).is_empty());
+    }
+
+    #[test]
+    fn reports_side_by_side() {
+        let found = differences(
+            "[scene]\npendulum name=a angle=0.5\n",
+            "[scene]\npendulum name=a angle=0.7 g=0.8\n",
+            false,
+        );
+        assert_eq!(
+            report("x.cfg", "y.cfg", &found),
+            [
+                "x.cfg | y.cfg: 2 differences, the most important configuration",
+                "  configuration pendulum \"a\" g      0.5 | 0.8",
+                "  state         pendulum \"a\" angle  0.5 | 0.7",
+            ]
+        );
+        assert_eq!(report("x", "y", &[]), ["x and y are the same"]);
+    }
+
+    #[test]
+    fn a_session_that_doesnt_read_is_an_error() {
+        let good = Config::parse("[scene]\npendulum\n").unwrap();
+        for bad in [
+            "[notes]\n",
+            "[scene]\nrocket\n",
+            "[scene]\npendulum\n[walls]\nleft = 5\nright = 10\n",
+        ] {
+            let bad = Config::parse(bad).unwrap();
+            assert!(diff(&good, &bad, false).is_err());
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use speedy2d::color::Color;
+
+use crate::framing::Bounds;
+use crate::math;
+use crate::model::{DoubleEntry, Model, ModelEntry};
+use crate::renderer::Renderer;
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+const BOB_RADIUS: f32 = 18.0;
+/// Longest substep, one frame: slow phases take a single step per frame.
+const MAX_DT: f64 = 1.0;
+/// Shortest substep, so a tolerance that can't be met still makes progress.
+const MIN_DT: f64 = 1e-4;
+/// Step size controller limits on how fast the step may shrink or grow.
+const SHRINK_LIMIT: f64 = 0.2;
+const GROW_LIMIT: f64 = 5.0;
+const SAFETY: f64 = 0.9;
+
+/// Angles and angular velocities of both links: `[θ1, ω1, θ2, ω2]`.
+type State = [f64; 4];
+
+/// Dormand–Prince 5(4) tableau. The equations don't depend on time, so the
+/// stage times aren't needed.
+pub const A: [[f64; 6]; 7] = [
+    [0.0; 6],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+/// Fifth-order weights, the same as the last row of `A`.
+pub const B5: [f64; 7] = [
+    35.0 / 384.0,
+    0.0,
+    500.0 / 1113.0,
+    125.0 / 192.0,
+    -2187.0 / 6784.0,
+    11.0 / 84.0,
+    0.0,
+];
+/// Embedded fourth-order weights for the error estimate.
+pub const B4: [f64; 7] = [
+    5179.0 / 57600.0,
+    0.0,
+    7571.0 / 16695.0,
+    393.0 / 640.0,
+    -92097.0 / 339200.0,
+    187.0 / 2100.0,
+    1.0 / 40.0,
+];
+
+/// The ideal double pendulum, two point masses on massless rods, solved from
+/// its equations of motion rather than with constraints. Its motion is
+/// chaotic, so it's integrated with an adaptive Runge–Kutta method that
+/// shrinks the substep in fast swings to keep the estimated error of each
+/// substep under the tolerance, and grows it again in slow ones. Each frame
+/// still advances exactly one step of simulated time.
+pub struct DoublePendulum {
+    pub origin: Vector,
+    pub lengths: [f32; 2],
+    pub masses: [f32; 2],
+    pub g: f32,
+    /// Largest error estimate accepted for a substep, in radians and
+    /// radians per step.
+    pub tolerance: f64,
+
+    state: State,
+    /// Substep the controller will try next.
+    dt: f64,
+    /// Substeps taken and rejected during the last frame.
+    substeps: usize,
+    rejected: usize,
+    /// Largest error estimate among the substeps accepted in the last frame.
+    error: f64,
+
+    grabbed: Option<usize>,
+}
+
+impl DoublePendulum {
+    pub fn from_entry(entry: &DoubleEntry) -> DoublePendulum {
+        DoublePendulum {
+            origin: Vector::new(entry.x, entry.y),
+            lengths: entry.lengths,
+            masses: entry.masses,
+            g: entry.g,
+            tolerance: entry.tolerance as f64,
+            state: [
+                entry.angles[0] as f64,
+                entry.angular_velocities[0] as f64,
+                entry.angles[1] as f64,
+                entry.angular_velocities[1] as f64,
+            ],
+            dt: MAX_DT,
+            substeps: 0,
+            rejected: 0,
+            error: 0.0,
+            grabbed: None,
+        }
+    }
+
+    /// Time derivative of the state.
+    fn derivative(&self, state: &State) -> State {
+        let [theta1, omega1, theta2, omega2] = *state;
+        let (l1, l2) = (self.lengths[0] as f64, self.lengths[1] as f64);
+        let (m1, m2) = (self.masses[0] as f64, self.masses[1] as f64);
+        let g = self.g as f64;
+
+        let delta = theta1 - theta2;
+        let denominator = 2.0 * m1 + m2 - m2 * math::cos(2.0 * delta);
+
+        let alpha1 = (-g * (2.0 * m1 + m2) * math::sin(theta1)
+            - m2 * g * math::sin(theta1 - 2.0 * theta2)
+            - 2.0
+                * math::sin(delta)
+                * m2
+                * (omega2 * omega2 * l2 + omega1 * omega1 * l1 * math::cos(delta)))
+            / (l1 * denominator);
+        let alpha2 = 2.0
+            * math::sin(delta)
+            * (omega1 * omega1 * l1 * (m1 + m2)
+                + g * (m1 + m2) * math::cos(theta1)
+                + omega2 * omega2 * l2 * m2 * math::cos(delta))
+            / (l2 * denominator);
+
+        [omega1, alpha1, omega2, alpha2]
+    }
+
+    /// One Dormand–Prince substep of size `dt`: the fifth-order result and
+    /// the estimated error, the largest difference from the embedded
+    /// fourth-order one.
+    fn try_step(&self, dt: f64) -> (State, f64) {
+        let mut k = [[0.0; 4]; 7];
+        for stage in 0..7 {
+            let mut state = self.state;
+            for (j, kj) in k.iter().enumerate().take(stage) {
+                for (s, d) in state.iter_mut().zip(kj) {
+                    *s += dt * A[stage][j] * d;
+                }
+            }
+            k[stage] = self.derivative(&state);
+        }
+
+        let mut next = self.state;
+        let mut error: f64 = 0.0;
+        for i in 0..4 {
+            let (mut high, mut low) = (0.0, 0.0);
+            for stage in 0..7 {
+                high += B5[stage] * k[stage][i];
+                low += B4[stage] * k[stage][i];
+            }
+            next[i] += dt * high;
+            error = error.max((dt * (high - low)).abs());
+        }
+        // `max` passes over a difference that isn't a number, so a state
+        // that has blown up is made to say so.
+        if next.iter().any(|s| !s.is_finite()) {
+            error = f64::NAN;
+        }
+
+        (next, error)
+    }
+
+    /// Advances one step of simulated time in as many substeps as the
+    /// tolerance needs.
+    fn advance(&mut self) {
+        self.substeps = 0;
+        self.rejected = 0;
+        self.error = 0.0;
+
+        let mut remaining = 1.0;
+        while remaining > 0.0 {
+            let dt = self.dt.min(remaining);
+            let (next, error) = self.try_step(dt);
+
+            // Resize for the next attempt from how far under or over the
+            // tolerance this one came. An error that isn't a number counts
+            // as far over, as in `integrator::Rk45`: it shrinks to the
+            // floor and is taken there, so the frame still ends.
+            let factor = if !error.is_finite() {
+                SHRINK_LIMIT
+            } else if error > 0.0 {
+                SAFETY * math::powf(self.tolerance / error, 0.2)
+            } else {
+                GROW_LIMIT
+            };
+            let resized = (dt * factor.clamp(SHRINK_LIMIT, GROW_LIMIT)).clamp(MIN_DT, MAX_DT);
+
+            if error <= self.tolerance || dt <= MIN_DT {
+                self.state = next;
+                self.substeps += 1;
+                self.error = self.error.max(error);
+                remaining -= dt;
+                // A substep cut short by the end of the frame says nothing
+                // about how big the next one can be.
+                if dt == self.dt {
+                    self.dt = resized;
+                }
+            } else {
+                self.rejected += 1;
+                self.dt = resized;
+            }
+        }
+    }
+
+    fn joints(&self) -> [Vector; 2] {
+        let [theta1, _, theta2, _] = self.state;
+        let first = Vector::new(
+            self.origin.x + self.lengths[0] * (theta1 as f32).sin(),
+            self.origin.y + self.lengths[0] * (theta1 as f32).cos(),
+        );
+        let second = Vector::new(
+            first.x + self.lengths[1] * (theta2 as f32).sin(),
+            first.y + self.lengths[1] * (theta2 as f32).cos(),
+        );
+        [first, second]
+    }
+}
+
+impl Model for DoublePendulum {
+    fn step(&mut self) {
+        if self.grabbed.is_none() {
+            self.advance();
+        }
+    }
+
+    fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let mut above = view.world_to_screen(self.origin);
+        for joint in self.joints() {
+            let joint = view.world_to_screen(joint);
+            graphics.draw_line(above, joint, view.length(3.0), view.tint(Color::GRAY));
+            above = joint;
+        }
+
+        for joint in self.joints() {
+            let joint = view.world_to_screen(joint);
+            graphics.draw_circle(
+                joint,
+                view.length(BOB_RADIUS + 3.0),
+                view.tint(Color::DARK_GRAY),
+            );
+            graphics.draw_circle(joint, view.length(BOB_RADIUS), view.tint(Color::LIGHT_GRAY));
+        }
+    }
+
+    fn grab(&mut self, point: Vector) -> bool {
+        self.grabbed = self.joints().iter().rposition(|joint| {
+            (joint.x - point.x).powi(2) + (joint.y - point.y).powi(2) < (BOB_RADIUS + 3.0).powi(2)
+        });
+        self.grabbed.is_some()
+    }
+
+    fn drag_to(&mut self, point: Vector) {
+        let Some(i) = self.grabbed else {
+            return;
+        };
+
+        let above = if i == 0 {
+            self.origin
+        } else {
+            self.joints()[0]
+        };
+        self.state[2 * i] = (point.x - above.x).atan2(point.y - above.y) as f64;
+        self.state[1] = 0.0;
+        self.state[3] = 0.0;
+    }
+
+    fn release(&mut self) {
+        if self.grabbed.take().is_some() {
+            self.state[1] = 0.0;
+            self.state[3] = 0.0;
+        }
+    }
+
+    fn pivot(&self) -> Vector {
+        self.origin
+    }
+
+    fn move_pivot(&mut self, by: Vector) {
+        self.origin.add(&by);
+    }
+
+    fn handles(&self) -> Vec<Vector> {
+        self.joints().to_vec()
+    }
+
+    /// Chaotic, so anywhere within reach, but no higher than the energy
+    /// lifts it. Lifting a bob lifts whatever hangs below it at least as
+    /// far, so the upper bob rises at most the energy over the weight of
+    /// both and the lower one over its own.
+    fn reach(&self) -> Bounds {
+        let [theta1, omega1, theta2, omega2] = self.state;
+        let (l1, l2) = (self.lengths[0] as f64, self.lengths[1] as f64);
+        let (m1, m2) = (self.masses[0] as f64, self.masses[1] as f64);
+        let g = self.g as f64;
+        let kinetic = 0.5 * (m1 + m2) * l1 * l1 * omega1 * omega1
+            + 0.5 * m2 * l2 * l2 * omega2 * omega2
+            + m2 * l1 * l2 * omega1 * omega2 * (theta1 - theta2).cos();
+        let potential =
+            g * ((m1 + m2) * l1 * (1.0 - theta1.cos()) + m2 * l2 * (1.0 - theta2.cos()));
+        let rise = |weight: f64| {
+            if g > 0.0 {
+                ((kinetic + potential) / (weight * g)) as f32
+            } else {
+                f32::INFINITY
+            }
+        };
+
+        let (l1, l2) = (self.lengths[0], self.lengths[1]);
+        Bounds::arc(self.origin, l1, self.origin.y + l1 - rise(m1 + m2))
+            .union(Bounds::arc(
+                self.origin,
+                l1 + l2,
+                self.origin.y + l1 + l2 - rise(m2),
+            ))
+            .union(Bounds::point(self.origin))
+            .padded(BOB_RADIUS + 3.0)
+    }
+
+    fn label(&self, units: &Units) -> String {
+        format!(
+            "Double pendulum, {} + {}",
+            units.length(self.lengths[0]),
+            units.length(self.lengths[1])
+        )
+    }
+
+    fn hud_lines(&self, _units: &Units) -> Vec<String> {
+        vec![
+            format!("Gravity: {:.2}", self.g),
+            format!("Angle 1: {:.2}", self.state[0]),
+            format!("Angle 2: {:.2}", self.state[2]),
+            format!(
+                "Adaptive dt: {:.3} ({} substeps, {} rejected)",
+                self.dt, self.substeps, self.rejected
+            ),
+            format!(
+                "Error: {:.1e} (tolerance {:.0e})",
+                self.error, self.tolerance
+            ),
+        ]
+    }
+
+    fn entry(&self) -> ModelEntry {
+        let [theta1, omega1, theta2, omega2] = self.state;
+        ModelEntry::Double(DoubleEntry {
+            x: self.origin.x,
+            y: self.origin.y,
+            lengths: self.lengths,
+            masses: self.masses,
+            g: self.g,
+            angles: [theta1 as f32, theta2 as f32],
+            angular_velocities: [omega1 as f32, omega2 as f32],
+            tolerance: self.tolerance as f32,
+        })
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = self.state.to_vec();
+        state.push(self.dt);
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_substep_taken_is_within_the_tolerance() {
+        let mut double = DoublePendulum::from_entry(&DoubleEntry::default());
+        let mut rejected = 0;
+        for _ in 0..1200 {
+            double.advance();
+            assert!(double.substeps > 0);
+            assert!(
+                double.error <= double.tolerance,
+                "an error of {} was taken",
+                double.error
+            );
+            assert!(double.state.iter().all(|s| s.is_finite()));
+            rejected += double.rejected;
+        }
+        // Started high, the swing is fast enough in places to need retries.
+        assert!(rejected > 0);
+        assert!(double.dt >= MIN_DT && double.dt <= MAX_DT);
+    }
+
+    #[test]
+    fn a_state_that_has_blown_up_still_finishes_the_frame() {
+        let mut double = DoublePendulum::from_entry(&DoubleEntry::default());
+        double.state[1] = f64::NAN;
+        double.advance();
+        assert!(double.substeps > 0 && double.rejected > 0);
+        assert_eq!(double.dt, MIN_DT);
+
+        double.state = [0.1, f64::INFINITY, 0.1, 0.0];
+        double.advance();
+        assert!(double.substeps > 0);
+    }
+}
@@ -0,0 +1,501 @@
+//! Energy bookkeeping for the single pendulum, in the simulation's pixel and
+//! step units. Energies are those of the point-mass pendulum with the same
+//! effective length, per unit mass, so every bob shape shares one formula.
+
+use crate::math;
+use crate::pendulum::{DampingModel, Pendulum};
+
+/// How far the energy may rise above its shell, as a fraction of the shell,
+/// before the step counts as a violation. The integrator's energy wobbles a
+/// few percent within a swing even when it's working.
+const TOLERANCE: f32 = 0.1;
+/// Absolute slack, as a fraction of `g r`, so a pendulum hanging nearly
+/// still doesn't trip over rounding.
+const FLOOR: f32 = 1e-3;
+/// Frames the contour stays red after a violation.
+const FLASH_FRAMES: u32 = 30;
+
+pub fn energy(p: &Pendulum) -> f32 {
+    precise_energy(p) as f32
+}
+
+/// Potential energy at `depth` below the pivot, from the bottom of the
+/// swing. Under a gravity gradient `k` it's the integral of `g (1 + k y)`
+/// over the depth `y` the bob rises through, which on the circle is
+/// `g L (1 - cos θ) + g k L² (1 - cos² θ) / 2`.
+/// The top of the swing is still `2 g L` up, since the extra pull on the
+/// way down from level with the pivot is what's missing on the way up.
+pub fn potential(p: &Pendulum, depth: f64) -> f64 {
+    let (g, gradient) = (p.g as f64, p.gravity_gradient as f64);
+    let length = p.effective_length() as f64;
+    g * (length - depth) + 0.5 * g * gradient * (length * length - depth * depth)
+}
+
+/// Largest angle from the vertical reachable with energy `e`, or `None` if
+/// it's enough to swing over the top.
+pub fn turning_angle(p: &Pendulum, e: f32) -> Option<f32> {
+    // The potential is `g L ((1 - c) + a (1 - c²))` in `c = cos θ`, with
+    // `a = k L / 2`; the root is written so it doesn't cancel as `a` goes
+    // to nothing, where it's `1 - u`.
+    let u = e / (p.g * p.effective_length());
+    let a = 0.5 * p.gravity_gradient * p.effective_length();
+    let b = 1.0 + a - u;
+    let cos = 2.0 * b / (1.0 + (1.0 + 4.0 * a * b).max(0.0).sqrt());
+    (cos > -1.0).then(|| math::acos(cos.clamp(-1.0, 1.0)))
+}
+
+/// Angular velocity at `angle` on the energy shell `e`, if reachable.
+pub fn shell_velocity(p: &Pendulum, e: f32, angle: f32) -> Option<f32> {
+    let length = p.effective_length();
+    let kinetic = e - potential(p, (length * math::cos(angle)) as f64) as f32;
+    (kinetic >= 0.0).then(|| (2.0 * kinetic).sqrt() / length)
+}
+
+/// Where a pendulum's energy has gone since it was made, kept up step by
+/// step by `Pendulum::update` in double precision so it can run for hours.
+/// Each mechanism is credited with the change it makes to the energy within
+/// the step, so the buckets balance: kinetic, potential and the losses
+/// always add up to the initial energy plus the inputs.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct EnergyLedger {
+    /// Energy before the first step.
+    pub initial: f64,
+    /// Taken out by viscous damping and by dry friction at the pivot.
+    pub viscous: f64,
+    pub friction: f64,
+    /// Taken out by a slack string snapping taut.
+    pub snap: f64,
+    /// Put in by the periodic drive and the motor, negative when they have
+    /// taken out more than they put in.
+    pub drive: f64,
+    pub motor: f64,
+    /// Changed between steps, by impulses, the mouse or edited parameters,
+    /// and by the soft start holding gravity back.
+    pub external: f64,
+    /// Made or lost by the step itself, which only conserves energy on
+    /// average.
+    pub integration: f64,
+    /// Energy at the end of the last step.
+    last: Option<f64>,
+}
+
+/// Work done on the swing during one step, split by where it came from.
+pub struct StepWork {
+    pub gravity: f64,
+    pub ramp: f64,
+    pub motor: f64,
+    pub drive: f64,
+    /// Kinetic energy taken out by damping or friction.
+    pub dissipated: f64,
+    /// Change in potential energy.
+    pub potential: f64,
+    /// Lost as a slack string snapped taut.
+    pub snap: f64,
+}
+
+impl EnergyLedger {
+    /// Counts whatever changed the energy since the last step as external,
+    /// or opens the ledger on the first.
+    pub fn open(&mut self, e: f64) {
+        match self.last {
+            Some(last) => self.external += e - last,
+            None => self.initial = e,
+        }
+    }
+
+    pub fn close(&mut self, p: &Pendulum, work: StepWork) {
+        let dissipated = match p.damping_model {
+            DampingModel::Viscous => &mut self.viscous,
+            DampingModel::Coulomb => &mut self.friction,
+        };
+        *dissipated += work.dissipated;
+        self.snap += work.snap;
+        self.motor += work.motor;
+        self.drive += work.drive;
+        self.external += work.ramp;
+        // Gravity is conservative, so whatever its work and the change in
+        // potential don't cancel is the step's own error.
+        self.integration += work.gravity + work.potential;
+
+        let e = precise_energy(p);
+        self.last = Some(e);
+        debug_assert!(
+            (e - self.balance()).abs() <= 1e-4 * (self.initial.abs() + self.total_in()) + 1e-6,
+            "energy ledger out of balance: {} against {}",
+            e,
+            self.balance()
+        );
+    }
+
+    /// Every bucket in order, and the energy at the end of the last step if
+    /// there's been one, for saving the ledger with the pendulum.
+    pub fn to_values(self) -> Vec<f64> {
+        let mut values = vec![
+            self.initial,
+            self.viscous,
+            self.friction,
+            self.snap,
+            self.drive,
+            self.motor,
+            self.external,
+            self.integration,
+        ];
+        values.extend(self.last);
+        values
+    }
+
+    /// The ledger `to_values` gave, if that's what `values` are.
+    pub fn from_values(values: &[f64]) -> Option<EnergyLedger> {
+        let (&[initial, viscous, friction, snap, drive, motor, external, integration], last) =
+            (values.get(..8)?, &values[8..])
+        else {
+            return None;
+        };
+        let last = match last {
+            [] => None,
+            &[last] => Some(last),
+            _ => return None,
+        };
+        Some(EnergyLedger {
+            initial,
+            viscous,
+            friction,
+            snap,
+            drive,
+            motor,
+            external,
+            integration,
+            last,
+        })
+    }
+
+    pub fn dissipated(&self) -> f64 {
+        self.viscous + self.friction + self.snap
+    }
+
+    /// Everything that has added to, or taken from, the initial energy
+    /// other than the losses.
+    pub fn inputs(&self) -> f64 {
+        self.drive + self.motor + self.external + self.integration
+    }
+
+    /// The energy the buckets say the pendulum should have now.
+    pub fn balance(&self) -> f64 {
+        self.initial + self.inputs() - self.dissipated()
+    }
+
+    /// The budget for the HUD: the energy now and what damping and friction
+    /// have taken, which add up to what it started with plus whatever was
+    /// put in since. What's left over is the step's own error.
+    pub fn readout(&self, current: f64) -> [String; 2] {
+        let dissipated = self.dissipated();
+        let put_in = self.drive + self.motor + self.external;
+        let expected = self.initial + put_in;
+        // Rounding as the state is copied about leaves a trace in the
+        // external bucket that isn't worth showing.
+        let put_in = if put_in.abs() <= 1e-6 * self.initial.abs() {
+            String::new()
+        } else {
+            format!(", {:+.1} put in", put_in)
+        };
+        [
+            format!(
+                "Energy: {:.1} + {:.1} dissipated = {:.1}",
+                current,
+                dissipated,
+                current + dissipated
+            ),
+            format!(
+                "Started at {:.1}{}, off by {:+.2}%",
+                self.initial,
+                put_in,
+                100.0 * (current + dissipated - expected) / expected.abs().max(1e-9)
+            ),
+        ]
+    }
+
+    /// Sum of every bucket's size, a scale for rounding.
+    fn total_in(&self) -> f64 {
+        [
+            self.viscous,
+            self.friction,
+            self.snap,
+            self.drive,
+            self.motor,
+            self.external,
+            self.integration,
+        ]
+        .iter()
+        .map(|bucket| bucket.abs())
+        .sum()
+    }
+}
+
+/// Kinetic and potential energy in double precision, which the ledger's
+/// sums need.
+pub fn precise_parts(p: &Pendulum) -> (f64, f64) {
+    if let Some(flight) = p.slack {
+        let (vx, vy) = (flight.vx as f64, flight.vy as f64);
+        return (0.5 * (vx * vx + vy * vy), potential(p, flight.y as f64));
+    }
+    let length = p.effective_length() as f64;
+    (
+        0.5 * (length * p.angular_velocity as f64).powi(2),
+        potential(p, length * math::cos(p.angle as f64)),
+    )
+}
+
+pub fn precise_energy(p: &Pendulum) -> f64 {
+    let (kinetic, potential) = precise_parts(p);
+    kinetic + potential
+}
+
+/// Watches for steps that gain energy nobody put in. The shell is the energy
+/// the pendulum should stay within: it follows losses to damping down, is
+/// reset by anything that legitimately adds energy, and a step that climbs
+/// out of it is reported once until the energy falls back inside.
+pub struct EnergyGuard {
+    pub shell: Option<f32>,
+    /// Gravity, its gradient and the length the shell was computed for.
+    parameters: (f32, f32, f32),
+    violating: bool,
+    flash: u32,
+}
+
+impl EnergyGuard {
+    pub fn new() -> EnergyGuard {
+        EnergyGuard {
+            shell: None,
+            parameters: (0.0, 0.0, 0.0),
+            violating: false,
+            flash: 0,
+        }
+    }
+
+    /// Forgets the shell, for when energy was added from outside.
+    pub fn reset(&mut self) {
+        self.shell = None;
+        self.violating = false;
+    }
+
+    pub fn is_flashing(&self) -> bool {
+        self.flash > 0
+    }
+
+    /// Checks the step the pendulum just took. `driven` is set when the
+    /// motor, the drive or the mouse may have added energy.
+    pub fn observe(&mut self, p: &Pendulum, driven: bool) {
+        self.flash = self.flash.saturating_sub(1);
+
+        let e = energy(p);
+        let parameters = (p.g, p.gravity_gradient, p.effective_length());
+        let Some(shell) = self
+            .shell
+            .filter(|_| !driven && parameters == self.parameters)
+        else {
+            self.shell = Some(e);
+            self.parameters = parameters;
+            self.violating = false;
+            return;
+        };
+
+        let slack = FLOOR * p.g * p.effective_length();
+        if e > shell * (1.0 + TOLERANCE) + slack {
+            if !self.violating {
+                log!(
+                    "energy shell exceeded at t = {:.0}: {:.4} over {:.4} ({:+.1}%)",
+                    p.time,
+                    e - shell,
+                    shell,
+                    100.0 * (e - shell) / shell.max(slack)
+                );
+                self.violating = true;
+            }
+            self.flash = FLASH_FRAMES;
+        } else if e < shell * (1.0 + 0.5 * TOLERANCE) + 0.5 * slack {
+            self.violating = false;
+        }
+
+        if e < shell {
+            self.shell = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::Scheme;
+    use std::f32::consts::PI;
+
+    fn pendulum(gradient: f32) -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.gravity_gradient = gradient;
+        p.zeta = Some(0.0);
+        p
+    }
+
+    /// Sets `p` at the bottom moving with energy `e`.
+    fn launch(p: &mut Pendulum, e: f32) {
+        p.angle = 0.0;
+        p.angular_velocity = (2.0 * e).sqrt() / p.effective_length();
+    }
+
+    #[test]
+    fn a_bounded_swing_turns_where_its_energy_is_all_potential() {
+        for gradient in [0.0, 0.002] {
+            for amplitude in [0.05, 0.3, 1.0, 2.5, 3.1] {
+                let mut p = pendulum(gradient);
+                p.angle = amplitude;
+                p.angular_velocity = 0.0;
+                let turning = p.turning_angle();
+                assert!(
+                    (turning - amplitude).abs() < 2e-3,
+                    "{} came out {} with gradient {}",
+                    amplitude,
+                    turning,
+                    gradient
+                );
+
+                // The same energy given as speed at the bottom.
+                let e = energy(&p);
+                launch(&mut p, e);
+                assert!((p.turning_angle() - turning).abs() < 2e-3);
+                let top = p.effective_length() * amplitude.cos();
+                assert!(shell_velocity(&p, e, 0.5 * amplitude).is_some());
+                assert!(shell_velocity(&p, e, (1.05 * amplitude).min(PI)).is_none());
+                assert!((potential(&p, top as f64) as f32 - e).abs() < 1e-3 * e);
+            }
+        }
+    }
+
+    #[test]
+    fn enough_energy_goes_over_the_top() {
+        for gradient in [0.0, 0.002] {
+            let mut p = pendulum(gradient);
+            // The top is 2 g L up with or without the gradient.
+            let top = 2.0 * p.g * p.effective_length();
+            assert!((potential(&p, -p.effective_length() as f64) as f32 - top).abs() < 1e-3);
+
+            launch(&mut p, 0.999 * top);
+            let turning = p.turning_angle();
+            assert!(turning.is_finite() && turning > 3.0, "{}", turning);
+
+            launch(&mut p, 1.001 * top);
+            assert!(turning_angle(&p, energy(&p)).is_none());
+            assert!(p.turning_angle().is_infinite());
+            assert!(shell_velocity(&p, energy(&p), PI).is_some());
+        }
+    }
+
+    #[test]
+    fn the_turning_angle_shrinks_with_the_swing_as_it_is_damped() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let mut predicted = p.turning_angle();
+        let mut turns = 0;
+        for _ in 0..3000 {
+            let before = p.angular_velocity;
+            p.update();
+            let turning = p.turning_angle();
+            assert!(
+                turning <= predicted + 1e-3,
+                "{} after {}",
+                turning,
+                predicted
+            );
+            predicted = turning;
+            if (before < 0.0) != (p.angular_velocity < 0.0) {
+                turns += 1;
+                assert!(
+                    (p.angle.abs() - turning).abs() < 1e-2,
+                    "{} against {}",
+                    p.angle,
+                    turning
+                );
+            }
+        }
+        assert!(turns > 10);
+        assert!(predicted < 0.5);
+    }
+
+    /// Runs `p` for 1500 steps, nudging it from outside every 400, and
+    /// checks after every step that the buckets account for its energy.
+    fn balances(mut p: Pendulum) -> EnergyLedger {
+        for step in 1..=1500 {
+            if step % 400 == 0 {
+                p.angular_velocity *= 1.05;
+            }
+            p.update();
+            let e = precise_energy(&p);
+            let scale = p.ledger.initial.abs() + p.ledger.total_in();
+            assert!(
+                (e - p.ledger.balance()).abs() <= 1e-6 * scale,
+                "step {}: {} against {}",
+                step,
+                e,
+                p.ledger.balance()
+            );
+        }
+        p.ledger
+    }
+
+    #[test]
+    fn the_buckets_add_up_to_the_initial_energy_plus_the_inputs() {
+        let start = || Pendulum::new(400.0, 0.0, 200.0);
+
+        let ledger = balances(start());
+        assert!(ledger.viscous > 0.0 && ledger.external > 0.0);
+        assert_eq!(
+            (ledger.friction, ledger.snap, ledger.drive),
+            (0.0, 0.0, 0.0)
+        );
+
+        let mut p = start();
+        p.damping_model = DampingModel::Coulomb;
+        assert!(balances(p).friction > 0.0);
+
+        let mut p = start();
+        p.drive_amplitude = 0.001;
+        p.drive_frequency = 0.05;
+        p.motor_torque = 0.0002;
+        p.gravity_gradient = 0.002;
+        let ledger = balances(p);
+        assert!(ledger.drive != 0.0 && ledger.motor != 0.0);
+
+        let mut p = start();
+        p.ease_in(5.0);
+        balances(p);
+
+        for scheme in [
+            Scheme::Euler,
+            Scheme::VelocityVerlet,
+            Scheme::Rk4,
+            Scheme::Rk45,
+        ] {
+            let mut p = start();
+            p.integrator = scheme;
+            p.drive_amplitude = 0.001;
+            p.drive_frequency = 0.05;
+            let ledger = balances(p);
+            assert!(ledger.integration != 0.0 && ledger.viscous > 0.0);
+        }
+
+        // Falling from above the pivot on a string, then jerked taut.
+        let mut p = start();
+        p.string = true;
+        p.angle = 2.5;
+        assert!(balances(p).snap > 0.0);
+    }
+
+    #[test]
+    fn the_ledger_saves_and_loads() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        assert!(EnergyLedger::from_values(&p.ledger.to_values()) == Some(p.ledger));
+        p.update();
+        let values = p.ledger.to_values();
+        assert_eq!(values.len(), 9);
+        assert!(EnergyLedger::from_values(&values) == Some(p.ledger));
+        assert!(EnergyLedger::from_values(&values[..7]).is_none());
+        assert!(EnergyLedger::from_values(&[values.clone(), vec![1.0]].concat()).is_none());
+    }
+}
@@ -0,0 +1,277 @@
+//! Setting the selected pendulum's energy outright, as a fraction of the
+//! separatrix: the energy that just carries the bob up to the top, where
+//! swinging back and forth gives way to going round. The energy keys step
+//! the fraction and `energy <fraction>` sets it. Below 1 the pendulum is
+//! let go from rest at the angle that has that energy, or sent through the
+//! bottom with it; from 1 up it's always sent through the bottom, since
+//! nothing at rest below the top has that much. The HUD shows the fraction
+//! from then on, as damping and the drive move it.
+//!
+//! A `[energy_dial]` section sets the fraction a key press moves it by, and
+//! how energies below the separatrix are started:
+//!
+//! ```text
+//! [energy_dial]
+//! step = 0.05
+//! release = rest
+//! ```
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::energy;
+use crate::pendulum::Pendulum;
+
+const DEFAULT_STEP: f32 = 0.05;
+/// Fractions beyond this would have the bob going round too fast to see.
+const MAX_FRACTION: f32 = 4.0;
+const NO_TOP: &str = "Without gravity there's no top to reach";
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Release {
+    /// At rest, at the angle with the energy.
+    Rest,
+    /// Through the bottom, with the energy all kinetic.
+    Bottom,
+}
+
+pub struct EnergyDial {
+    /// Whether the HUD shows the fraction.
+    pub shown: bool,
+    step: f32,
+    release: Release,
+}
+
+/// The energy at the top of the swing, from the bottom.
+pub fn separatrix(p: &Pendulum) -> f32 {
+    energy::potential(p, -p.effective_length() as f64) as f32
+}
+
+/// The energy of `p` over the separatrix's, unless there's no top to reach
+/// without gravity.
+pub fn fraction(p: &Pendulum) -> Option<f32> {
+    let separatrix = separatrix(p);
+    (separatrix > 0.0).then(|| energy::energy(p) / separatrix)
+}
+
+impl EnergyDial {
+    pub fn new() -> EnergyDial {
+        EnergyDial {
+            shown: false,
+            step: DEFAULT_STEP,
+            release: Release::Rest,
+        }
+    }
+
+    /// Reads `step = <fraction>` and `release = rest|bottom` from an
+    /// `[energy_dial]` section.
+    pub fn from_config(section: &Section) -> Result<EnergyDial, String> {
+        let mut dial = EnergyDial::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("step", value)) => {
+                    dial.step = parse_f32("step", value).map_err(error)?;
+                    if !(dial.step > 0.0 && dial.step <= 1.0) {
+                        return Err(error(
+                            "`step` must be more than 0 and at most 1".to_string(),
+                        ));
+                    }
+                }
+                Some(("release", "rest")) => dial.release = Release::Rest,
+                Some(("release", "bottom")) => dial.release = Release::Bottom,
+                Some(("release", value)) => {
+                    return Err(error(format!(
+                        "unknown release `{}`: use rest or bottom",
+                        value
+                    )))
+                }
+                _ => return Err(error(format!("unknown energy dial setting `{}`", line))),
+            }
+        }
+
+        Ok(dial)
+    }
+
+    /// Moves the energy of `p` by `steps` key presses, from the nearest
+    /// step to where it is now.
+    pub fn nudge(&mut self, p: &mut Pendulum, steps: i32) -> Result<String, String> {
+        let now = fraction(p).ok_or(NO_TOP)?;
+        let fraction = ((now / self.step).round() + steps as f32) * self.step;
+        self.set(p, fraction.max(0.0))
+    }
+
+    /// Gives `p` exactly `fraction` of the separatrix energy, keeping to the
+    /// side it's on and the way it's going.
+    pub fn set(&mut self, p: &mut Pendulum, fraction: f32) -> Result<String, String> {
+        if !(0.0..=MAX_FRACTION).contains(&fraction) {
+            return Err(format!(
+                "The energy must be from 0 to {} of the separatrix",
+                MAX_FRACTION
+            ));
+        }
+        let separatrix = separatrix(p);
+        if separatrix <= 0.0 {
+            return Err(NO_TOP.to_string());
+        }
+        let e = fraction * separatrix;
+        let side = if p.angle < 0.0 { -1.0 } else { 1.0 };
+        let way = if p.angular_velocity < 0.0 { -1.0 } else { 1.0 };
+
+        let mut state = p.snapshot();
+        // A flight on a slack string is left for the swing it would set.
+        state.slack = None;
+        match energy::turning_angle(p, e).filter(|_| self.release == Release::Rest) {
+            Some(angle) => {
+                state.angle = side * angle;
+                state.angular_velocity = 0.0;
+            }
+            None => {
+                state.angle = 0.0;
+                state.angular_velocity = way * energy::shell_velocity(p, e, 0.0).unwrap_or(0.0);
+            }
+        }
+        p.restore(&state);
+        self.shown = true;
+        Ok(if fraction == 1.0 {
+            "Energy on the separatrix: creeps up to the top".to_string()
+        } else if fraction < 1.0 {
+            format!("Energy {:.3} of the separatrix: swings", fraction)
+        } else {
+            format!("Energy {:.3} of the separatrix: goes round", fraction)
+        })
+    }
+
+    pub fn hud_line(&self, p: &Pendulum) -> Option<String> {
+        let fraction = fraction(p).filter(|_| self.shown)?;
+        let motion = if fraction < 1.0 {
+            "swinging"
+        } else {
+            "rotating"
+        };
+        Some(format!("E / E sep: {:.3}, {}", fraction, motion))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::f32::consts::PI;
+
+    fn undamped() -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.zeta = Some(0.0);
+        p
+    }
+
+    /// The furthest the swing gets either side over `steps`.
+    fn widest(p: &mut Pendulum, steps: usize) -> (f32, f32) {
+        let (mut low, mut high) = (p.angle, p.angle);
+        for _ in 0..steps {
+            p.update();
+            low = low.min(p.angle);
+            high = high.max(p.angle);
+        }
+        (low, high)
+    }
+
+    #[test]
+    fn just_below_the_separatrix_swings_and_just_above_goes_round() {
+        for release in [Release::Rest, Release::Bottom] {
+            let mut dial = EnergyDial {
+                release,
+                ..EnergyDial::new()
+            };
+            let mut p = undamped();
+            dial.set(&mut p, 0.99).unwrap();
+            assert!((fraction(&p).unwrap() - 0.99).abs() < 1e-3);
+            let (low, high) = widest(&mut p, 5000);
+            // Back and forth, turning short of the top both ways.
+            assert!(low > -PI && high < PI, "{} to {}", low, high);
+            assert!(low < -2.5 && high > 2.5, "{} to {}", low, high);
+        }
+
+        let mut dial = EnergyDial::new();
+        let mut p = undamped();
+        p.angular_velocity = -0.01;
+        assert_eq!(
+            dial.set(&mut p, 1.01).unwrap(),
+            "Energy 1.010 of the separatrix: goes round"
+        );
+        // From 1 up it's sent through the bottom, the way it was going.
+        assert!(p.angle == 0.0 && p.angular_velocity < 0.0);
+        assert!((fraction(&p).unwrap() - 1.01).abs() < 1e-3);
+        let mut previous = p.angle;
+        for _ in 0..5000 {
+            p.update();
+            assert!(p.angle < previous, "it turned back at {}", p.angle);
+            previous = p.angle;
+        }
+        assert!(p.angle < -4.0 * PI, "only got to {}", p.angle);
+    }
+
+    #[test]
+    fn below_it_a_rest_release_starts_at_the_angle_with_that_energy() {
+        let mut dial = EnergyDial::new();
+        let mut p = undamped();
+        p.angle = -0.3;
+        assert_eq!(
+            dial.set(&mut p, 0.5).unwrap(),
+            "Energy 0.500 of the separatrix: swings"
+        );
+        // Half way to the top's energy is level with the pivot, on its side.
+        assert!((p.angle + PI / 2.0).abs() < 1e-3 && p.angular_velocity == 0.0);
+        assert!(dial.shown);
+        assert_eq!(dial.hud_line(&p).unwrap(), "E / E sep: 0.500, swinging");
+        assert_eq!(
+            dial.set(&mut p, 1.0).unwrap(),
+            "Energy on the separatrix: creeps up to the top"
+        );
+        assert!(dial.set(&mut p, 4.5).is_err() && dial.set(&mut p, -0.1).is_err());
+    }
+
+    #[test]
+    fn nudging_moves_from_the_nearest_step() {
+        let mut dial = EnergyDial::new();
+        let mut p = undamped();
+        dial.set(&mut p, 0.52).unwrap();
+        dial.nudge(&mut p, 1).unwrap();
+        assert!((fraction(&p).unwrap() - 0.55).abs() < 1e-3);
+        dial.nudge(&mut p, -20).unwrap();
+        assert!(fraction(&p).unwrap().abs() < 1e-4 && p.angle == 0.0);
+
+        p.g = 0.0;
+        assert_eq!(dial.nudge(&mut p, 1).err().unwrap(), NO_TOP);
+        assert!(fraction(&p).is_none());
+    }
+
+    #[test]
+    fn reads_the_section() {
+        let section = |text: &str| Config::parse(text).unwrap();
+        let config = section("[energy_dial]\nstep = 0.1\nrelease = bottom\n");
+        let dial = EnergyDial::from_config(config.section("energy_dial").unwrap()).unwrap();
+        assert!(dial.step == 0.1 && dial.release == Release::Bottom);
+        for (text, error) in [
+            (
+                "step = 0",
+                "line 2: `step` must be more than 0 and at most 1",
+            ),
+            (
+                "release = top",
+                "line 2: unknown release `top`: use rest or bottom",
+            ),
+            (
+                "speed = 2",
+                "line 2: unknown energy dial setting `speed = 2`",
+            ),
+        ] {
+            let config = section(&format!("[energy_dial]\n{}\n", text));
+            assert_eq!(
+                EnergyDial::from_config(config.section("energy_dial").unwrap())
+                    .err()
+                    .unwrap(),
+                error
+            );
+        }
+    }
+}
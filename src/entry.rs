@@ -0,0 +1,65 @@
+/// What a text entry is being typed for.
+#[derive(Copy, Clone, PartialEq)]
+pub enum EntryKind {
+    /// A period in seconds for the selected pendulum.
+    Period,
+    /// A note attached to the current time.
+    Note,
+    /// A console command.
+    Command,
+    /// The real length of the calibration bar.
+    Calibration,
+    /// A target tempo for the metronome, in beats per minute.
+    Tempo,
+    /// The name to save the selected pendulum as an apparatus profile under.
+    Apparatus,
+}
+
+/// A one-line text field typed into from the keyboard, shown in the status
+/// lines while open.
+pub struct TextEntry {
+    pub kind: EntryKind,
+    pub text: String,
+}
+
+impl TextEntry {
+    pub fn new(kind: EntryKind) -> TextEntry {
+        TextEntry {
+            kind,
+            text: String::new(),
+        }
+    }
+
+    /// Adds a typed character if it belongs in this kind of entry. The
+    /// character of the key that opened the entry arrives here too, so
+    /// periods take only number characters and commands skip the console
+    /// key.
+    pub fn push(&mut self, c: char) {
+        let accepted = match self.kind {
+            EntryKind::Period | EntryKind::Tempo => c.is_ascii_digit() || c == '.',
+            EntryKind::Note => !c.is_control(),
+            EntryKind::Command => !c.is_control() && c != '`',
+            EntryKind::Calibration => c.is_ascii_alphanumeric() || c == '.' || c == ' ',
+            EntryKind::Apparatus => c.is_ascii_alphanumeric() || c == '-' || c == '_',
+        };
+        if accepted {
+            self.text.push(c);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.text.pop();
+    }
+
+    pub fn status_line(&self) -> String {
+        let prompt = match self.kind {
+            EntryKind::Period => "Period (s)",
+            EntryKind::Note => "Note",
+            EntryKind::Command => "Console",
+            EntryKind::Calibration => "Real length of the bar (e.g. 30 cm)",
+            EntryKind::Tempo => "Tempo (BPM)",
+            EntryKind::Apparatus => "Save as apparatus",
+        };
+        format!("{}: {}_", prompt, self.text)
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use speedy2d::color::Color;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// Steps of swept area kept per pendulum.
+const ENVELOPE_LENGTH: usize = 1200;
+const DEFAULT_OPACITY: f32 = 0.04;
+
+/// Paints the area each rod has swept over the last `ENVELOPE_LENGTH`
+/// steps, as a fan of thin triangles from the pivot to successive bob
+/// positions. The fill is translucent, so the parts swept most often come
+/// out darkest. Purely visual, like the strobe.
+pub struct Envelope {
+    pub visible: bool,
+    /// Opacity of a single triangle.
+    opacity: f32,
+    /// Pivot and bob after each step, per model.
+    positions: Vec<VecDeque<(Vector, Vector)>>,
+}
+
+impl Envelope {
+    pub fn new(opacity: f32) -> Envelope {
+        Envelope {
+            visible: false,
+            opacity,
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn default_opacity() -> f32 {
+        DEFAULT_OPACITY
+    }
+
+    /// Reads `opacity = <0..1>` from an `[envelope]` section.
+    pub fn opacity_from_config(section: &Section) -> Result<f32, String> {
+        let mut opacity = DEFAULT_OPACITY;
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("opacity", value)) => {
+                    opacity = parse_f32("opacity", value).map_err(error)?;
+                    if !(0.0..=1.0).contains(&opacity) {
+                        return Err(error(format!(
+                            "`opacity` must be between 0 and 1, found {}",
+                            value
+                        )));
+                    }
+                }
+                _ => return Err(error(format!("unknown envelope setting `{}`", line))),
+            }
+        }
+        Ok(opacity)
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+
+    pub fn record(&mut self, scene: &Scene) {
+        if !self.visible {
+            return;
+        }
+
+        self.positions
+            .resize_with(scene.models.len(), VecDeque::new);
+        for (i, model) in scene.models.iter().enumerate() {
+            let Some(p) = model.as_pendulum() else {
+                continue;
+            };
+            let bob = Vector::new(
+                p.origin.x + p.r * p.angle.sin(),
+                p.origin.y + p.r * p.angle.cos(),
+            );
+
+            let positions = &mut self.positions[i];
+            if positions.len() == ENVELOPE_LENGTH {
+                positions.pop_front();
+            }
+            positions.push_back((p.origin, bob));
+        }
+    }
+
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        if !self.visible {
+            return;
+        }
+
+        let color = Color::from_rgba(0.2, 0.4, 0.8, self.opacity);
+        for positions in &self.positions {
+            for (&(_, from), &(pivot, to)) in positions.iter().zip(positions.iter().skip(1)) {
+                graphics.draw_triangle(
+                    [
+                        view.world_to_screen(pivot).into(),
+                        view.world_to_screen(from).into(),
+                        view.world_to_screen(to).into(),
+                    ],
+                    color,
+                );
+            }
+        }
+    }
+}
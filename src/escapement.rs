@@ -0,0 +1,481 @@
+//! A pendulum clock: an escapement that gives the selected pendulum a small
+//! push each time it swings through the bottom, just enough to put back the
+//! energy damping took since the last one, so the swing holds its amplitude
+//! however long it runs. Pushing at the bottom, where the bob is fastest,
+//! disturbs the timing least, as a clockmaker's escapement is made to. Each
+//! push is a tick, and the ticks drive the clock's hands: one second per
+//! swing, as a seconds pendulum beats, or per however many swings are set.
+//!
+//! The amplitude held is the energy the bob passes the bottom with, so with
+//! damping still at work on the way up the swing turns a little short of it.
+//! The escapement can only push, and no harder than its most: a swing above
+//! the amplitude is left to settle, and damping too strong to make up for
+//! settles where the most it can give does. The HUD shows the push, the
+//! damping, the amplitude and the clock, with how far the clock runs ahead
+//! of the simulation's own time.
+//!
+//! An `[escapement]` section sets it going at the start, with the amplitude
+//! to hold (by default the one it finds), the largest push in rad/s and the
+//! swings a clock second takes:
+//!
+//! ```text
+//! [escapement]
+//! enabled = true
+//! amplitude = 0.3
+//! impulse = 0.5
+//! swings = 1
+//! ```
+
+use std::f32::consts::PI;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::energy;
+use crate::math;
+use crate::pendulum::{DampingModel, Pendulum, PendulumState};
+use crate::STEPS_PER_SECOND;
+
+/// The largest push, in rad/s, unless `[escapement]` says otherwise.
+const DEFAULT_IMPULSE: f32 = 0.5;
+/// Amplitudes past this would have the bob swinging over the top, where
+/// there's no swing to regulate.
+const MAX_AMPLITUDE: f32 = 3.0;
+const SECONDS_PER_DAY: f32 = 86400.0;
+
+pub struct Escapement {
+    pub enabled: bool,
+    /// The amplitude held, in radians: the one set, or else the one found
+    /// at the first tick.
+    amplitude: Option<f32>,
+    /// Whether the amplitude was set, and so isn't found afresh.
+    fixed: bool,
+    /// The largest push, in rad/s.
+    impulse: f32,
+    /// Swings to a clock second.
+    swings: u32,
+    previous: Option<PendulumState>,
+    ticks: u64,
+    /// Simulation time, in steps, of the first tick, from which the clock's
+    /// rate is measured.
+    first: Option<f32>,
+    /// Of the last tick, and the push it gave, in rad/s.
+    last: Option<(f32, f32)>,
+}
+
+/// Hours, minutes and seconds.
+fn clock_face(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl Escapement {
+    pub fn new() -> Escapement {
+        Escapement {
+            enabled: false,
+            amplitude: None,
+            fixed: false,
+            impulse: DEFAULT_IMPULSE,
+            swings: 1,
+            previous: None,
+            ticks: 0,
+            first: None,
+            last: None,
+        }
+    }
+
+    /// Reads `enabled = true|false`, `amplitude = <rad>`,
+    /// `impulse = <rad/s>` and `swings = <n>` from an `[escapement]`
+    /// section.
+    pub fn from_config(section: &Section) -> Result<Escapement, String> {
+        let mut escapement = Escapement::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("enabled", value)) => {
+                    escapement.enabled = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `enabled`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                Some(("amplitude", value)) => escapement
+                    .set_amplitude(parse_f32("amplitude", value).map_err(error)?)
+                    .map_err(error)?,
+                Some(("impulse", value)) => escapement
+                    .set_impulse(parse_f32("impulse", value).map_err(error)?)
+                    .map_err(error)?,
+                Some(("swings", value)) => {
+                    escapement.swings =
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|&swings| swings > 0)
+                            .ok_or(error(format!(
+                                "expected a whole number of swings, found `{}`",
+                                value
+                            )))?
+                }
+                _ => return Err(error(format!("unknown escapement setting `{}`", line))),
+            }
+        }
+
+        Ok(escapement)
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) -> Result<(), String> {
+        if !(amplitude > 0.0 && amplitude <= MAX_AMPLITUDE) {
+            return Err(format!(
+                "the amplitude must be more than 0 and at most {} rad",
+                MAX_AMPLITUDE
+            ));
+        }
+        self.amplitude = Some(amplitude);
+        self.fixed = true;
+        Ok(())
+    }
+
+    pub fn set_impulse(&mut self, impulse: f32) -> Result<(), String> {
+        if impulse <= 0.0 {
+            return Err("the impulse must be positive".to_string());
+        }
+        self.impulse = impulse;
+        Ok(())
+    }
+
+    /// Stops the clock and forgets the swing it was following, keeping the
+    /// settings. An amplitude that was found is found again.
+    pub fn clear(&mut self) {
+        self.previous = None;
+        self.ticks = 0;
+        self.first = None;
+        self.last = None;
+        if !self.fixed {
+            self.amplitude = None;
+        }
+    }
+
+    /// Looks for a swing through the bottom in the step just taken, and if
+    /// there was one, ticks and tops the energy of `p` back up to the
+    /// amplitude's. Called once a frame; returns whether it pushed.
+    pub fn step(&mut self, p: &mut Pendulum) -> bool {
+        if !self.enabled {
+            self.previous = None;
+            return false;
+        }
+        let after = p.snapshot();
+        let Some(before) = self.previous.replace(after) else {
+            return false;
+        };
+        // Going over the top flips the sign of the angle too, but that's a
+        // jump of nearly a turn and isn't a swing.
+        let (a, b) = (before.angle, after.angle);
+        if a == b || (a < 0.0) == (b < 0.0) || (a - b).abs() >= PI {
+            return false;
+        }
+
+        let amplitude = *self.amplitude.get_or_insert_with(|| {
+            energy::turning_angle(p, energy::energy(p)).unwrap_or(MAX_AMPLITUDE)
+        });
+        let time = before.time + (after.time - before.time) * a / (a - b);
+        self.first.get_or_insert(time);
+        self.ticks += 1;
+
+        let held =
+            energy::potential(p, (p.effective_length() * math::cos(amplitude)) as f64) as f32;
+        let speed = p.angular_velocity.abs();
+        let push = energy::shell_velocity(p, held, p.angle)
+            .map_or(0.0, |needed| needed - speed)
+            .clamp(0.0, self.impulse / STEPS_PER_SECOND);
+        p.angular_velocity += push * p.angular_velocity.signum();
+        self.last = Some((time, push * STEPS_PER_SECOND));
+        push > 0.0
+    }
+
+    /// Seconds shown on the clock.
+    pub fn clock_seconds(&self) -> f32 {
+        self.ticks as f32 / self.swings as f32
+    }
+
+    /// Seconds the clock gains a day against the simulation's time, from
+    /// the ticks since the first.
+    fn gain_per_day(&self) -> Option<f32> {
+        let (first, (last, _)) = (self.first?, self.last?);
+        let elapsed = (last - first) / STEPS_PER_SECOND;
+        (self.ticks > 1 && elapsed > 0.0).then(|| {
+            let shown = (self.ticks - 1) as f32 / self.swings as f32;
+            (shown / elapsed - 1.0) * SECONDS_PER_DAY
+        })
+    }
+
+    /// The `escapement` console command: `escapement on|off`,
+    /// `escapement amplitude <rad>|impulse <rad/s>|swings <n>`,
+    /// `escapement damping <ratio>` to set the damping of `p` it makes up
+    /// for, or `escapement reset` to start the clock again; on its own it
+    /// shows the settings.
+    pub fn command(&mut self, words: &[&str], p: &mut Pendulum) -> Result<Vec<String>, String> {
+        match words {
+            [] => {}
+            ["on"] => self.enabled = true,
+            ["off"] => {
+                self.enabled = false;
+                self.clear();
+            }
+            ["reset"] => self.clear(),
+            ["amplitude", amplitude] => {
+                let amplitude = amplitude
+                    .parse()
+                    .map_err(|_| format!("Not an amplitude: `{}`", amplitude))?;
+                self.set_amplitude(amplitude)?;
+            }
+            ["impulse", impulse] => {
+                let impulse = impulse
+                    .parse()
+                    .map_err(|_| format!("Not an angular velocity: `{}`", impulse))?;
+                self.set_impulse(impulse)?;
+            }
+            ["swings", swings] => {
+                self.swings = swings
+                    .parse()
+                    .ok()
+                    .filter(|&swings| swings > 0)
+                    .ok_or(format!("Not a whole number of swings: `{}`", swings))?;
+                self.clear();
+            }
+            ["damping", ratio] => {
+                let ratio: f32 = ratio
+                    .parse()
+                    .map_err(|_| format!("Not a damping ratio: `{}`", ratio))?;
+                if !(0.0..1.0).contains(&ratio) {
+                    return Err("The damping ratio must be from 0 to below 1".to_string());
+                }
+                p.zeta = Some(ratio);
+                p.damping_model = DampingModel::Viscous;
+            }
+            _ => {
+                return Err("Usage: escapement [on|off|reset|amplitude <rad>|impulse <rad/s>|swings <n>|damping <ratio>]".to_string())
+            }
+        }
+        let lines = self.hud_lines(p);
+        if lines.is_empty() {
+            return Ok(vec![format!(
+                "Escapement off (pushes at most {} rad/s, {} swings a second)",
+                self.impulse, self.swings
+            )]);
+        }
+        Ok(lines)
+    }
+
+    pub fn hud_lines(&self, p: &Pendulum) -> Vec<String> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let damping = p
+            .damping_ratio()
+            .map_or("dry friction".to_string(), |zeta| {
+                format!("zeta {:.4}", zeta)
+            });
+        let amplitude = energy::turning_angle(p, energy::energy(p))
+            .map_or("over the top".to_string(), |a| format!("{:.3} rad", a));
+        let held = self
+            .amplitude
+            .map_or("the next swing's".to_string(), |a| format!("{:.3} rad", a));
+        let push = self.last.map_or("none yet".to_string(), |(_, push)| {
+            format!("{:.4} rad/s", push)
+        });
+        let gain = self.gain_per_day().map_or(String::new(), |gain| {
+            let way = if gain < 0.0 { "losing" } else { "gaining" };
+            format!(", {} {:.1} s a day", way, gain.abs())
+        });
+        vec![
+            format!(
+                "Escapement: holds {}, swings {}, last push {}, {}",
+                held, amplitude, push, damping
+            ),
+            format!(
+                "Clock {} ({} ticks, {} a second){}",
+                clock_face(self.clock_seconds()),
+                self.ticks,
+                self.swings,
+                gain
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damped(angle: f32, zeta: f32) -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.angle = angle;
+        p.zeta = Some(zeta);
+        p
+    }
+
+    fn holding(amplitude: f32) -> Escapement {
+        let mut escapement = Escapement::new();
+        escapement.enabled = true;
+        escapement.set_amplitude(amplitude).unwrap();
+        escapement
+    }
+
+    /// Runs `steps` frames with `escapement` at work, and returns the widest
+    /// the swing went over the last `tail` of them.
+    fn run(p: &mut Pendulum, escapement: &mut Escapement, steps: usize, tail: usize) -> f32 {
+        let mut widest: f32 = 0.0;
+        for step in 0..steps {
+            p.update();
+            escapement.step(p);
+            if step >= steps - tail {
+                widest = widest.max(p.angle.abs());
+            }
+        }
+        widest
+    }
+
+    #[test]
+    fn the_amplitude_settles_where_it_is_held() {
+        // Left alone, the swing dies away.
+        let mut p = damped(0.3, 0.02);
+        let mut off = Escapement::new();
+        assert!(run(&mut p, &mut off, 6000, 500) < 0.01);
+        assert_eq!(off.ticks, 0);
+
+        for start in [0.3, 0.1, 0.6] {
+            let mut p = damped(start, 0.02);
+            let mut escapement = holding(0.3);
+            run(&mut p, &mut escapement, 6000, 0);
+            let late = run(&mut p, &mut escapement, 2000, 1000);
+            let later = run(&mut p, &mut escapement, 2000, 1000);
+            // Settled, and a little short of the amplitude with damping on
+            // the way up.
+            assert!(
+                (late - later).abs() < 1e-3,
+                "{} then {} from {}",
+                late,
+                later,
+                start
+            );
+            assert!(
+                later < 0.3 && later > 0.28,
+                "settled at {} from {}",
+                later,
+                start
+            );
+        }
+    }
+
+    #[test]
+    fn the_clock_ticks_once_a_swing() {
+        let mut p = damped(0.3, 0.02);
+        let mut escapement = holding(0.3);
+        let steps = 12000;
+        run(&mut p, &mut escapement, steps, 0);
+        // Two swings through the bottom a period.
+        let period = p.period_at(0.3) * STEPS_PER_SECOND;
+        let expected = 2.0 * steps as f32 / period;
+        assert!(
+            (escapement.ticks as f32 - expected).abs() <= 2.0,
+            "{} ticks for {}",
+            escapement.ticks,
+            expected
+        );
+        assert_eq!(escapement.clock_seconds(), escapement.ticks as f32);
+        // The clock's second is half the period, so it gains what those
+        // fall short of one, near enough for a swing a little short of the
+        // amplitude and slowed a little by the damping.
+        let gain = escapement.gain_per_day().unwrap();
+        let expected = (2.0 / p.period_at(0.3) - 1.0) * SECONDS_PER_DAY;
+        assert!(
+            (gain - expected).abs() < 0.02 * expected.abs(),
+            "{} for {}",
+            gain,
+            expected
+        );
+
+        let mut p = damped(0.3, 0.02);
+        let mut every_other = holding(0.3);
+        every_other.swings = 2;
+        run(&mut p, &mut every_other, steps, 0);
+        assert_eq!(every_other.ticks, escapement.ticks);
+        assert_eq!(every_other.clock_seconds(), escapement.ticks as f32 / 2.0);
+    }
+
+    #[test]
+    fn no_harder_than_its_most() {
+        // Damping too strong for the pushes settles lower down.
+        let mut p = damped(0.3, 0.2);
+        let mut escapement = holding(0.3);
+        escapement.set_impulse(0.02).unwrap();
+        let settled = run(&mut p, &mut escapement, 6000, 1000);
+        assert!(settled > 0.0 && settled < 0.2, "settled at {}", settled);
+        let (_, push) = escapement.last.unwrap();
+        assert!((push - 0.02).abs() < 1e-6);
+    }
+
+    #[test]
+    fn finds_the_amplitude_unless_one_is_set() {
+        let mut p = damped(0.4, 0.0);
+        let mut escapement = Escapement::new();
+        escapement.enabled = true;
+        run(&mut p, &mut escapement, 200, 0);
+        assert!((escapement.amplitude.unwrap() - 0.4).abs() < 1e-3);
+        escapement.clear();
+        assert!(escapement.amplitude.is_none() && escapement.ticks == 0);
+        let mut set = holding(0.2);
+        set.clear();
+        assert_eq!(set.amplitude, Some(0.2));
+    }
+
+    #[test]
+    fn shows_the_clock() {
+        assert_eq!(clock_face(3725.9), "1:02:05");
+        assert_eq!(clock_face(-3.0), "0:00:00");
+        let mut p = damped(0.3, 0.02);
+        let mut escapement = Escapement::new();
+        assert_eq!(
+            escapement.command(&[], &mut p).unwrap(),
+            ["Escapement off (pushes at most 0.5 rad/s, 1 swings a second)"]
+        );
+        let lines = escapement.command(&["on"], &mut p).unwrap();
+        assert_eq!(lines[1], "Clock 0:00:00 (0 ticks, 1 a second)");
+        assert!(escapement.command(&["damping", "1"], &mut p).is_err());
+        assert!(escapement.command(&["swings", "0"], &mut p).is_err());
+    }
+
+    #[test]
+    fn reads_the_section() {
+        let parse = |text: &str| {
+            let config = crate::config::Config::parse(&format!("[escapement]\n{}", text)).unwrap();
+            Escapement::from_config(config.section("escapement").unwrap())
+        };
+        let escapement =
+            parse("enabled = true\namplitude = 0.3\nimpulse = 0.2\nswings = 2\n").unwrap();
+        assert!(escapement.enabled && escapement.fixed && escapement.amplitude == Some(0.3));
+        assert!(escapement.impulse == 0.2 && escapement.swings == 2);
+        for (text, error) in [
+            (
+                "amplitude = 4",
+                "line 2: the amplitude must be more than 0 and at most 3 rad",
+            ),
+            ("impulse = 0", "line 2: the impulse must be positive"),
+            (
+                "swings = 0",
+                "line 2: expected a whole number of swings, found `0`",
+            ),
+            (
+                "enabled = yes",
+                "line 2: expected true or false for `enabled`, found `yes`",
+            ),
+        ] {
+            assert_eq!(parse(text).err().unwrap(), error);
+        }
+    }
+}
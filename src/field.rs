@@ -0,0 +1,84 @@
+use speedy2d::color::Color;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::draw_arrow;
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// World units between arrows unless `[gravity_field]` says otherwise.
+const DEFAULT_SPACING: f32 = 60.0;
+/// Gravity at which an arrow is drawn half as long as the spacing: the
+/// default gravity, so the usual scene shows well-spaced arrows.
+const REFERENCE_G: f32 = 0.5;
+
+/// A faint grid of arrows behind the scene pointing the way gravity pulls,
+/// each as long as the gravity is strong, so turning it up visibly
+/// stretches them. Purely visual.
+pub struct GravityField {
+    pub visible: bool,
+    spacing: f32,
+}
+
+impl GravityField {
+    pub fn new() -> GravityField {
+        GravityField {
+            visible: false,
+            spacing: DEFAULT_SPACING,
+        }
+    }
+
+    /// Reads `spacing = <world units>` from a `[gravity_field]` section.
+    pub fn from_config(section: &Section) -> Result<GravityField, String> {
+        let mut field = GravityField::new();
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("spacing", value)) => {
+                    field.spacing = parse_f32("spacing", value).map_err(error)?;
+                    if field.spacing < 10.0 {
+                        return Err(error(format!(
+                            "`spacing` must be at least 10, found {}",
+                            value
+                        )));
+                    }
+                }
+                _ => return Err(error(format!("unknown gravity field setting `{}`", line))),
+            }
+        }
+        Ok(field)
+    }
+
+    /// Covers everything the window shows, not just the world, so the grid
+    /// still fills the window after a resize. Arrows stay on world grid
+    /// points, spaced and sized in world units like everything else.
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport, g: f32) {
+        if !self.visible {
+            return;
+        }
+
+        let (top_left, bottom_right) = view.visible_world();
+        let spacing = self.spacing;
+        // Negative gravity points the arrows up. Past the spacing they'd
+        // run into each other.
+        let length = (0.5 * spacing * g / REFERENCE_G).clamp(-spacing, spacing);
+        let color = Color::from_rgba(0.2, 0.3, 0.5, 0.25);
+
+        let mut x = (top_left.x / spacing).floor() * spacing + 0.5 * spacing;
+        while x < bottom_right.x + spacing {
+            let mut y = (top_left.y / spacing).floor() * spacing + 0.5 * spacing;
+            while y < bottom_right.y + spacing {
+                draw_arrow(
+                    graphics,
+                    view,
+                    Vector::new(x, y - 0.5 * length),
+                    Vector::new(x, y + 0.5 * length),
+                    1.5,
+                    color,
+                );
+                y += spacing;
+            }
+            x += spacing;
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! Working the whole window from the keyboard. The focus key, and Shift
+//! with it, steps the focus through everything that can be clicked or
+//! dragged, in the order it's registered each frame: the models' handles
+//! and pivots, the obstacles, the controls of whichever panels are open,
+//! and last a free pointer for the rest of the scene. The focused element
+//! wears a ring.
+//!
+//! Focusing puts the pointer on the element, and the keys then do with the
+//! pointer exactly what the mouse would, through the same handlers, so
+//! nothing the mouse can do is out of reach: `Enter` presses or lets go,
+//! the arrows move the pointer, pressing first on anything that drags,
+//! `Delete` is a right click and `Shift` with up and down the wheel. The
+//! pivots, which the mouse can't move, move with the arrows instead. `Esc`
+//! lets go and clears the focus, and so does moving the mouse.
+
+use speedy2d::color::Color;
+use speedy2d::Graphics2D;
+
+/// Pixels the pointer moves for an arrow key.
+pub const STEP: f32 = 8.0;
+const RING: Color = Color::from_rgb(0.95, 0.55, 0.0);
+const RING_SEGMENTS: usize = 32;
+
+/// Something that can have the focus, known by what it is so the focus
+/// stays on it as the registry is made afresh.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Target {
+    /// A point of model `.0` that can be grabbed, counting from its pivot.
+    Handle(usize, usize),
+    Pivot(usize),
+    Obstacle(usize),
+    DampingSlider,
+    RaceButton,
+    Tempo,
+    TuneButton,
+    /// A column heading of the results table.
+    Heading(usize),
+    Chart,
+    Tile(usize),
+    /// An apparatus profile in the profiles panel.
+    Profile(usize),
+    ProfileButton(usize),
+    /// The pointer on its own, for the rest of the scene: drawing
+    /// obstacles, laying the calibration bar or picking a note's marker.
+    Pointer,
+}
+
+impl Target {
+    /// What has the focus, to say so. Models count from 1, as the HUD does.
+    pub fn describe(self) -> String {
+        match self {
+            Target::Handle(model, 0) => format!("Model {}, its bob", model + 1),
+            Target::Handle(model, handle) => format!("Model {}, bob {}", model + 1, handle + 1),
+            Target::Pivot(model) => format!("Model {}, its pivot", model + 1),
+            Target::Obstacle(i) => format!("Obstacle {}", i + 1),
+            Target::DampingSlider => "Damping slider".to_string(),
+            Target::RaceButton => "Race button".to_string(),
+            Target::Tempo => "Target tempo".to_string(),
+            Target::TuneButton => "Tune button".to_string(),
+            Target::Heading(i) => format!("Results column {}", i + 1),
+            Target::Chart => "Chart".to_string(),
+            Target::Tile(i) => format!("Tile {}", i + 1),
+            Target::Profile(i) => format!("Apparatus {}", i + 1),
+            Target::ProfileButton(i) => format!("Apparatus button {}", i + 1),
+            Target::Pointer => "Pointer".to_string(),
+        }
+    }
+}
+
+/// What the keys do with a focused element.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Handling {
+    /// `Enter` grabs and lets go; the arrows grab and drag.
+    Drag,
+    /// `Enter` clicks.
+    Click,
+    /// The arrows move the pivot.
+    Pivot,
+    /// `Enter` presses and lets go; the arrows move the pointer, pressed or
+    /// not.
+    Pointer,
+}
+
+const HANDLINGS: [Handling; 4] = [
+    Handling::Drag,
+    Handling::Click,
+    Handling::Pivot,
+    Handling::Pointer,
+];
+
+impl Handling {
+    fn name(self) -> &'static str {
+        match self {
+            Handling::Drag => "Bobs, obstacles, sliders, chart",
+            Handling::Click => "Buttons, headings, tiles",
+            Handling::Pivot => "Pivots",
+            Handling::Pointer => "Pointer",
+        }
+    }
+
+    /// The keys, and what they do, for the help overlay.
+    fn keys(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Handling::Drag => &[("Enter", "grab / let go"), ("Arrows", "grab and drag")],
+            Handling::Click => &[("Enter", "click"), ("Arrows", "move the pointer")],
+            Handling::Pivot => &[("Arrows", "move the pivot")],
+            Handling::Pointer => &[("Enter", "press / let go"), ("Arrows", "move the pointer")],
+        }
+    }
+}
+
+/// Everything the mouse does, and the keys that do it on the focused
+/// element. All of it can be done with the free pointer too; this is the
+/// quicker way where there is one.
+pub const MOUSE_PATHS: [(&str, &str); 11] = [
+    ("drag a bob", "Arrows"),
+    ("move an obstacle", "Arrows"),
+    ("remove an obstacle", "Delete"),
+    ("draw an obstacle", "its key+Enter"),
+    ("lay the calibration bar", "its key+Enter"),
+    ("drag the damping slider", "Arrows"),
+    ("click a button or heading", "Enter"),
+    ("pick a grid tile", "Enter"),
+    ("pan the chart", "Arrows"),
+    ("zoom the chart, scroll", "Shift+Up/Dn"),
+    ("pick a note's marker", "Enter"),
+];
+
+pub struct Focusable {
+    pub target: Target,
+    /// Centre on screen, where the pointer goes.
+    pub at: (f32, f32),
+    /// Of the ring, on screen.
+    pub radius: f32,
+    pub handling: Handling,
+}
+
+/// Everything there is to focus this frame, in focus order.
+pub struct Registry {
+    items: Vec<Focusable>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry { items: Vec::new() }
+    }
+
+    pub fn register(&mut self, target: Target, at: (f32, f32), radius: f32, handling: Handling) {
+        self.items.push(Focusable {
+            target,
+            at,
+            radius,
+            handling,
+        });
+    }
+
+    pub fn get(&self, target: Target) -> Option<&Focusable> {
+        self.items.iter().find(|item| item.target == target)
+    }
+
+    /// The element after `from`, or before it going `backwards`, round to
+    /// the start; the first or last if nothing has the focus.
+    pub fn next(&self, from: Option<Target>, backwards: bool) -> Option<&Focusable> {
+        let count = self.items.len();
+        if count == 0 {
+            return None;
+        }
+        let index = match from.and_then(|from| self.items.iter().position(|i| i.target == from)) {
+            Some(i) if backwards => (i + count - 1) % count,
+            Some(i) => (i + 1) % count,
+            None if backwards => count - 1,
+            None => 0,
+        };
+        self.items.get(index)
+    }
+}
+
+/// The focused element, if any, and whether the keys are holding the
+/// pointer's button down.
+pub struct Focus {
+    pub target: Option<Target>,
+    pub pressed: bool,
+}
+
+impl Focus {
+    pub fn new() -> Focus {
+        Focus {
+            target: None,
+            pressed: false,
+        }
+    }
+
+    /// Rings the focused element, or the pointer while it's pressed.
+    pub fn draw(&self, graphics: &mut Graphics2D, registry: &Registry, pointer: (f32, f32)) {
+        let Some(item) = self.target.and_then(|target| registry.get(target)) else {
+            return;
+        };
+        let (x, y) = if self.pressed || item.handling == Handling::Pointer {
+            pointer
+        } else {
+            item.at
+        };
+        let radius = item.radius.max(6.0) + 4.0;
+        let point = |i: usize| {
+            let (sin, cos) = (std::f32::consts::TAU * i as f32 / RING_SEGMENTS as f32).sin_cos();
+            (x + radius * cos, y + radius * sin)
+        };
+        for i in 0..RING_SEGMENTS {
+            graphics.draw_line(point(i), point(i + 1), 2.5, RING);
+        }
+    }
+}
+
+/// The help overlay's keyboard section, under headings: the keys for
+/// each kind of element there is to focus now, then the keys for each
+/// thing the mouse does.
+pub fn help_rows(registry: &Registry) -> Vec<(&'static str, Vec<(String, String)>)> {
+    let pairs = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|&(key, does)| (key.to_string(), does.to_string()))
+            .collect()
+    };
+    let mut rows = vec![(
+        "Keyboard only",
+        pairs(&[
+            ("focus key", "focus the next"),
+            ("Shift+it", "the one before"),
+            ("Esc", "clear the focus"),
+            ("Delete", "right click"),
+            ("Shift+Up/Dn", "mouse wheel"),
+        ]),
+    )];
+    rows.extend(
+        HANDLINGS
+            .iter()
+            .filter(|&&handling| registry.items.iter().any(|i| i.handling == handling))
+            .map(|handling| (handling.name(), pairs(handling.keys()))),
+    );
+    rows.push((
+        "Mouse, by keys",
+        MOUSE_PATHS
+            .iter()
+            .map(|&(mouse, keys)| (keys.to_string(), mouse.to_string()))
+            .collect(),
+    ));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apparatus::{Click, Profile, Profiles, ProfilesPanel};
+    use crate::chart::StripChart;
+    use crate::damping::DampingExplorer;
+    use crate::grid::GridScan;
+    use crate::metronome::Metronome;
+    use crate::model::{CartEntry, ChainEntry, DoubleEntry, ModelEntry, PendulumEntry};
+    use crate::obstacles::{Obstacle, Obstacles};
+    use crate::pendulum::Pendulum;
+    use crate::results::ResultsPanel;
+    use crate::scene::Scene;
+    use crate::units::Units;
+    use crate::vector::Vector;
+    use crate::viewport::Viewport;
+
+    fn registered(registry: &Registry) -> Vec<(Target, (f32, f32), Handling)> {
+        registry
+            .items
+            .iter()
+            .map(|item| (item.target, item.at, item.handling))
+            .collect()
+    }
+
+    #[test]
+    fn the_focus_key_steps_through_everything_and_round() {
+        let mut registry = Registry::new();
+        assert!(registry.next(None, false).is_none());
+        let targets = [
+            Target::Pivot(0),
+            Target::Handle(0, 0),
+            Target::Obstacle(0),
+            Target::Pointer,
+        ];
+        for (i, &target) in targets.iter().enumerate() {
+            registry.register(target, (i as f32, 0.0), 4.0, Handling::Click);
+        }
+
+        let mut focused = None;
+        for &target in targets.iter().chain(&targets) {
+            focused = registry.next(focused, false).map(|item| item.target);
+            assert_eq!(focused, Some(target));
+        }
+        let mut focused = None;
+        for &target in targets.iter().rev().chain(targets.iter().rev()) {
+            focused = registry.next(focused, true).map(|item| item.target);
+            assert_eq!(focused, Some(target));
+        }
+        // Something gone from the registry starts it again from the first.
+        let gone = registry.next(Some(Target::Tile(3)), false).unwrap();
+        assert_eq!(gone.target, Target::Pivot(0));
+        assert_eq!(registry.get(Target::Obstacle(0)).unwrap().at, (2.0, 0.0));
+        assert!(registry.get(Target::Chart).is_none());
+    }
+
+    #[test]
+    fn the_keys_put_the_pointer_where_the_mouse_does_the_same() {
+        // Every model's pivot moves with the arrows, and every handle is
+        // where grabbing it would.
+        let view = Viewport::new(true);
+        let mut scene = Scene::new(vec![
+            ModelEntry::Pendulum(PendulumEntry::default()),
+            ModelEntry::Double(DoubleEntry::default()),
+            ModelEntry::Chain(ChainEntry::default()),
+            ModelEntry::Cart(CartEntry::default()),
+        ]);
+        for (i, model) in scene.models.iter_mut().enumerate() {
+            let mut registry = Registry::new();
+            registry.register(
+                Target::Pivot(i),
+                view.world_to_screen(model.pivot()),
+                4.0,
+                Handling::Pivot,
+            );
+            let handles = model.handles();
+            assert!(!handles.is_empty());
+            for (j, handle) in handles.into_iter().enumerate() {
+                registry.register(
+                    Target::Handle(i, j),
+                    view.world_to_screen(handle),
+                    25.0,
+                    Handling::Drag,
+                );
+            }
+            for (target, (x, y), handling) in registered(&registry) {
+                if let Target::Handle(..) = target {
+                    assert_eq!(handling, Handling::Drag);
+                    assert!(
+                        model.grab(view.screen_to_world(x, y)),
+                        "{}",
+                        target.describe()
+                    );
+                    model.release();
+                }
+            }
+        }
+
+        let mut obstacles = Obstacles::new();
+        for x in [200.0, 500.0] {
+            obstacles.obstacles.push(Obstacle {
+                center: Vector::new(x, 300.0),
+                radius: 30.0,
+            });
+        }
+        let mut registry = Registry::new();
+        obstacles.register(&mut registry, &view);
+        let items = registered(&registry);
+        assert_eq!(items.len(), 2);
+        for (_, (x, y), handling) in items.iter().copied() {
+            assert_eq!(handling, Handling::Drag);
+            assert!(obstacles.grab(view.screen_to_world(x, y)));
+            assert!(obstacles.release());
+        }
+        // `Delete` is the right click that takes one away.
+        let (x, y) = items[0].1;
+        assert!(obstacles.remove_at(view.screen_to_world(x, y)));
+        assert_eq!(obstacles.obstacles.len(), 1);
+
+        // The damping explorer's knob drags, and its button races.
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let mut damping = DampingExplorer::new();
+        let mut registry = Registry::new();
+        damping.register(&mut registry, &p);
+        assert!(registry.items.is_empty(), "registered while hidden");
+        damping.toggle();
+        damping.register(&mut registry, &p);
+        let knob = registry.get(Target::DampingSlider).unwrap();
+        assert_eq!(knob.handling, Handling::Drag);
+        let (x, y) = knob.at;
+        assert!(damping.mouse_down(x, y, &mut p));
+        damping.mouse_move(x + 5.0 * STEP, &mut p);
+        assert!(damping.mouse_up(), "the knob wasn't grabbed");
+        let button = registry.get(Target::RaceButton).unwrap();
+        assert_eq!(button.handling, Handling::Click);
+        assert!(damping.mouse_down(button.at.0, button.at.1, &mut p));
+        assert!(!damping.mouse_up(), "the button grabbed the knob");
+
+        // The metronome's tempo is typed from, and its button tunes.
+        let mut metronome = Metronome::new();
+        metronome.visible = true;
+        let mut registry = Registry::new();
+        metronome.register(&mut registry);
+        let tempo = registry.get(Target::Tempo).unwrap();
+        assert_eq!(tempo.handling, Handling::Click);
+        assert!(metronome.tempo_at(tempo.at.0, tempo.at.1));
+        let tune = registry.get(Target::TuneButton).unwrap();
+        assert!(!metronome.tempo_at(tune.at.0, tune.at.1));
+        let before = p.r;
+        assert!(metronome.mouse_down(tune.at.0, tune.at.1, &mut p));
+        assert!(p.r != before, "the tune button didn't tune");
+
+        // Each heading of the results sorts by its column.
+        let mut results = ResultsPanel::new();
+        results.visible = true;
+        let mut registry = Registry::new();
+        results.register(&mut registry);
+        let headings = registered(&registry);
+        assert!(headings.len() > 1);
+        for (target, (x, y), handling) in headings {
+            assert!(matches!(target, Target::Heading(_)) && handling == Handling::Click);
+            assert!(results.mouse_down(x, y));
+        }
+
+        // The chart pans from its middle.
+        let mut chart = StripChart::new(1 << 16, None);
+        chart.visible = true;
+        let mut registry = Registry::new();
+        chart.register(&mut registry);
+        let middle = registry.get(Target::Chart).unwrap();
+        assert_eq!(middle.handling, Handling::Drag);
+        assert!(chart.mouse_down(middle.at.0, middle.at.1));
+
+        // Every tile of the grid is picked where it's focused.
+        let grid = GridScan::new(PendulumEntry::default(), (800.0, 480.0));
+        let mut registry = Registry::new();
+        grid.register(&mut registry);
+        let tiles = registered(&registry);
+        assert!(!tiles.is_empty());
+        for (_, (x, y), handling) in tiles {
+            assert_eq!(handling, Handling::Click);
+            assert!(grid.click(x, y).is_some());
+        }
+
+        // Each profile is picked, and each button clicked.
+        let path = std::env::temp_dir().join(format!("pendulum-focus-{}.txt", std::process::id()));
+        let mut profiles = Profiles::open(&path);
+        let units = Units::per_metre(400.0).unwrap();
+        for id in ["bench", "tall"] {
+            profiles
+                .profiles
+                .push(Profile::of(id, &p, &units, "").unwrap());
+        }
+        let mut panel = ProfilesPanel::new();
+        panel.visible = true;
+        let mut registry = Registry::new();
+        panel.register(&mut registry, &profiles);
+        let items = registered(&registry);
+        for (i, (target, (x, y), handling)) in items.into_iter().enumerate() {
+            assert_eq!(handling, Handling::Click);
+            let click = panel.mouse_down(x, y, &profiles);
+            match target {
+                Target::Profile(i) => assert_eq!(panel.picked, Some(i)),
+                Target::ProfileButton(0) => assert!(matches!(click, Some(Click::Attach(1)))),
+                Target::ProfileButton(1) => assert!(matches!(click, Some(Click::SaveAs))),
+                Target::ProfileButton(_) => assert!(matches!(click, Some(Click::WriteBack))),
+                _ => panic!("registered {} at {}", target.describe(), i),
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn the_help_gives_keys_for_every_mouse_action() {
+        let mut registry = Registry::new();
+        for (target, handling) in [
+            (Target::Handle(0, 0), Handling::Drag),
+            (Target::RaceButton, Handling::Click),
+            (Target::Pivot(0), Handling::Pivot),
+            (Target::Pointer, Handling::Pointer),
+        ] {
+            registry.register(target, (0.0, 0.0), 4.0, handling);
+        }
+        let rows = help_rows(&registry);
+        let headings: Vec<&str> = rows.iter().map(|(heading, _)| *heading).collect();
+        for handling in HANDLINGS {
+            assert!(
+                headings.contains(&handling.name()),
+                "no {}",
+                handling.name()
+            );
+        }
+
+        // Every key the mouse's actions are done with is one the help
+        // teaches, for the focused element or for the keyboard alone.
+        let taught: Vec<&str> = rows
+            .iter()
+            .filter(|(heading, _)| *heading != "Mouse, by keys")
+            .flat_map(|(_, keys)| keys.iter().map(|(key, _)| key.as_str()))
+            .collect();
+        let (_, by_keys) = rows.last().unwrap();
+        assert_eq!(by_keys.len(), MOUSE_PATHS.len());
+        for ((keys, mouse), (action, path)) in by_keys.iter().zip(MOUSE_PATHS) {
+            assert!(keys == path && mouse == action);
+            let key = path.strip_prefix("its key+").unwrap_or(path);
+            assert!(
+                taught.contains(&key),
+                "`{}` for {} isn't taught",
+                key,
+                action
+            );
+        }
+
+        // Only the kinds there are now get their own heading.
+        let mut registry = Registry::new();
+        registry.register(Target::Tile(0), (0.0, 0.0), 4.0, Handling::Click);
+        let rows = help_rows(&registry);
+        let headings: Vec<&str> = rows.iter().map(|(heading, _)| *heading).collect();
+        assert_eq!(
+            headings,
+            ["Keyboard only", Handling::Click.name(), "Mouse, by keys"]
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Fitting the camera to the scene. `Shift+Home` frames everything that
+//! moves or can be hit: each model's whole reach on its current energy
+//! rather than where it happens to be, the obstacles and the walls, with a
+//! margin. `frame auto` keeps doing it as things move, but only once
+//! something leaves the frame or the frame has grown well too big for what's
+//! in it, so the camera doesn't breathe with every swing. `Home` goes back
+//! to the world as it's laid out.
+
+use crate::obstacles::Obstacles;
+use crate::scene::Scene;
+use crate::tween::{Easing, Tween};
+use crate::vector::Vector;
+use crate::viewport::{self, Viewport, WORLD_SIZE};
+use crate::walls::Walls;
+
+/// Room left round the content, in world units.
+const MARGIN: f32 = 30.0;
+/// Frames the camera takes to move to a new framing.
+const FRAMES: usize = 30;
+/// Zooms the camera keeps to, so a lone bob isn't blown up to fill the
+/// window and a runaway one doesn't shrink the rest to nothing.
+const ZOOM: (f32, f32) = (0.2, 2.0);
+/// Following, the camera reframes once the content would fit at this many
+/// times the zoom, and then frames it this much looser than it needs, so it
+/// can grow a little before the next reframe.
+const SHRINK: f32 = 1.5;
+const SLACK: f32 = 1.1;
+
+/// A box in world coordinates, `min` its top left.
+#[derive(Copy, Clone)]
+pub struct Bounds {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Bounds {
+    pub fn point(point: Vector) -> Bounds {
+        Bounds {
+            min: point,
+            max: point,
+        }
+    }
+
+    pub fn around(centre: Vector, radius: f32) -> Bounds {
+        Bounds::point(centre).padded(radius)
+    }
+
+    /// Everything within `radius` of `centre` that isn't above `top`:
+    /// where something on a rod of that length from `centre` can get
+    /// without rising higher, with screen `y` going down.
+    pub fn arc(centre: Vector, radius: f32, top: f32) -> Bounds {
+        let bottom = centre.y + radius;
+        let top = top.clamp(centre.y - radius, bottom);
+        let half_width = if top <= centre.y {
+            radius
+        } else {
+            (radius * radius - (top - centre.y).powi(2)).max(0.0).sqrt()
+        };
+        Bounds {
+            min: Vector::new(centre.x - half_width, top),
+            max: Vector::new(centre.x + half_width, bottom),
+        }
+    }
+
+    pub fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            min: Vector::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vector::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    pub fn padded(self, by: f32) -> Bounds {
+        Bounds {
+            min: Vector::new(self.min.x - by, self.min.y - by),
+            max: Vector::new(self.max.x + by, self.max.y + by),
+        }
+    }
+
+    pub fn centre(&self) -> Vector {
+        Vector::new(
+            0.5 * (self.min.x + self.max.x),
+            0.5 * (self.min.y + self.max.y),
+        )
+    }
+
+    pub fn contains(&self, other: &Bounds) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+    }
+
+    /// Zoom that just fits the box where the world would be.
+    fn zoom(&self) -> f32 {
+        let width = (self.max.x - self.min.x).max(1.0);
+        let height = (self.max.y - self.min.y).max(1.0);
+        (WORLD_SIZE.0 / width)
+            .min(WORLD_SIZE.1 / height)
+            .clamp(ZOOM.0, ZOOM.1)
+    }
+}
+
+/// The union of all of `bounds`, or nothing if there are none.
+pub fn union(bounds: impl IntoIterator<Item = Bounds>) -> Option<Bounds> {
+    bounds.into_iter().reduce(Bounds::union)
+}
+
+/// Everything in the scene to frame: every model's reach, the obstacles,
+/// and the walls over the height of the rest.
+pub fn content(scene: &Scene, obstacles: &Obstacles, walls: &Walls) -> Option<Bounds> {
+    let bounds = union(
+        scene
+            .models
+            .iter()
+            .map(|model| model.reach())
+            .chain(obstacles.bounds()),
+    )?;
+    Some(match walls.span() {
+        Some((left, right)) => bounds.union(Bounds {
+            min: Vector::new(left, bounds.min.y),
+            max: Vector::new(right, bounds.max.y),
+        }),
+        None => bounds,
+    })
+}
+
+/// Moves the camera to new framings, and keeps it on the content while
+/// following.
+pub struct Framing {
+    pub follow: bool,
+    /// Centre and zoom on the way to the last framing.
+    moving: Option<(Tween<Vector>, Tween<f32>)>,
+}
+
+impl Framing {
+    pub fn new() -> Framing {
+        Framing {
+            follow: false,
+            moving: None,
+        }
+    }
+
+    /// Starts moving the camera to frame `content`.
+    pub fn fit(&mut self, view: &Viewport, content: Bounds) {
+        let content = content.padded(MARGIN);
+        self.move_to(view, content.centre(), content.zoom());
+    }
+
+    /// Starts moving the camera back to the world as it's laid out, and
+    /// stops following.
+    pub fn home(&mut self, view: &Viewport) {
+        self.follow = false;
+        self.move_to(view, viewport::home(), 1.0);
+    }
+
+    /// Puts the camera straight back home, for a new scene.
+    pub fn reset(&mut self, view: &mut Viewport) {
+        self.moving = None;
+        view.set_camera(viewport::home(), 1.0);
+    }
+
+    fn move_to(&mut self, view: &Viewport, centre: Vector, zoom: f32) {
+        self.moving = Some((
+            Tween::new(view.centre, centre, FRAMES, Easing::Smoothstep),
+            Tween::new(view.zoom, zoom, FRAMES, Easing::Smoothstep),
+        ));
+    }
+
+    /// Whether, following, the camera should move to frame `content`: if
+    /// any of it is out of frame, or all of it would fit `SHRINK` times
+    /// closer.
+    fn needs_reframe(view: &Viewport, content: &Bounds) -> bool {
+        let content = content.padded(MARGIN);
+        let (min, max) = view.framed_world();
+        !Bounds { min, max }.contains(&content) || content.zoom() >= SHRINK * view.zoom
+    }
+
+    /// Moves the camera on a frame. `content` is what's in the scene now,
+    /// which is only needed while following.
+    pub fn update(&mut self, view: &mut Viewport, content: impl FnOnce() -> Option<Bounds>) {
+        if self.moving.is_none() && self.follow {
+            if let Some(content) = content().filter(|c| Framing::needs_reframe(view, c)) {
+                let loose = content.padded(MARGIN);
+                self.move_to(view, loose.centre(), (loose.zoom() / SLACK).max(ZOOM.0));
+            }
+        }
+        let Some((centre, zoom)) = &mut self.moving else {
+            return;
+        };
+        view.set_camera(centre.step(), zoom.step());
+        if centre.is_done() {
+            self.moving = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::model::{Model, ModelEntry, PendulumEntry};
+    use crate::obstacles::Obstacle;
+    use crate::pendulum::{Pendulum, BOB_RADIUS};
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn same(a: Bounds, b: Bounds) -> bool {
+        close(a.min.x, b.min.x)
+            && close(a.min.y, b.min.y)
+            && close(a.max.x, b.max.x)
+            && close(a.max.y, b.max.y)
+    }
+
+    fn boxed(left: f32, top: f32, right: f32, bottom: f32) -> Bounds {
+        Bounds {
+            min: Vector::new(left, top),
+            max: Vector::new(right, bottom),
+        }
+    }
+
+    #[test]
+    fn an_arc_is_cut_off_at_its_top() {
+        let centre = Vector::new(400.0, 100.0);
+        // Below the pivot it's as wide as the circle where it's cut.
+        let low = Bounds::arc(centre, 200.0, 220.0);
+        assert!(same(low, boxed(240.0, 220.0, 560.0, 300.0)));
+        // Level with the pivot or above, it's the circle's full width.
+        assert!(same(
+            Bounds::arc(centre, 200.0, 100.0),
+            boxed(200.0, 100.0, 600.0, 300.0)
+        ));
+        assert!(same(
+            Bounds::arc(centre, 200.0, 0.0),
+            boxed(200.0, 0.0, 600.0, 300.0)
+        ));
+        // No higher than the top of the circle, and no lower than its bottom.
+        let whole = Bounds::arc(centre, 200.0, f32::NEG_INFINITY);
+        assert!(same(whole, Bounds::around(centre, 200.0)));
+        let hanging = Bounds::arc(centre, 200.0, 1000.0);
+        assert!(same(hanging, boxed(400.0, 300.0, 400.0, 300.0)));
+    }
+
+    #[test]
+    fn a_swing_stays_in_its_reach() {
+        for angle in [0.3, 1.0, 2.0, 3.0] {
+            let mut p = Pendulum::new(400.0, 100.0, 150.0);
+            p.zeta = Some(0.0);
+            p.angle = angle;
+            let reach = p.reach();
+            let (mut low, mut high) = (f32::INFINITY, f32::NEG_INFINITY);
+            let (mut left, mut right) = (f32::INFINITY, f32::NEG_INFINITY);
+            for _ in 0..1000 {
+                p.update();
+                let bob = Bounds::around(p.position, BOB_RADIUS);
+                assert!(
+                    reach.contains(&bob),
+                    "the bob left its reach from {}",
+                    angle
+                );
+                low = low.min(p.position.y);
+                high = high.max(p.position.y);
+                left = left.min(p.position.x);
+                right = right.max(p.position.x);
+            }
+            // And the reach is no bigger than the swing needs.
+            let pad = BOB_RADIUS + 3.0;
+            assert!(
+                (reach.min.y + pad - low.min(p.origin.y)).abs() < 2.0,
+                "top from {}",
+                angle
+            );
+            assert!(
+                (reach.max.y - pad - high).abs() < 1.0,
+                "bottom from {}",
+                angle
+            );
+            assert!(
+                (reach.min.x + pad - left).abs() < 2.0,
+                "left from {}",
+                angle
+            );
+            assert!(
+                (reach.max.x - pad - right).abs() < 2.0,
+                "right from {}",
+                angle
+            );
+        }
+
+        // Going over the top, it's the whole circle.
+        let mut p = Pendulum::new(400.0, 100.0, 150.0);
+        p.angle = 0.0;
+        p.angular_velocity = 0.2;
+        assert!(same(
+            p.reach(),
+            Bounds::around(p.origin, 150.0 + BOB_RADIUS + 3.0)
+        ));
+    }
+
+    #[test]
+    fn the_content_takes_in_every_model_the_obstacles_and_the_walls() {
+        let entry = |x: f32, r: f32, angle: f32| {
+            ModelEntry::Pendulum(PendulumEntry {
+                x,
+                r,
+                angle,
+                ..PendulumEntry::default()
+            })
+        };
+        let scene = Scene::new(vec![entry(200.0, 100.0, 0.5), entry(600.0, 250.0, 1.5)]);
+        let reaches: Vec<Bounds> = scene.models.iter().map(|model| model.reach()).collect();
+        let mut obstacles = Obstacles::new();
+        let mut walls = Walls::new();
+
+        let models = content(&scene, &obstacles, &walls).unwrap();
+        let expected = reaches[0].union(reaches[1]);
+        assert!(same(models, expected));
+        assert!(reaches.iter().all(|reach| models.contains(reach)));
+        assert!(models.min.x == reaches[0].min.x && models.max.x == reaches[1].max.x);
+
+        obstacles.obstacles.push(Obstacle {
+            center: Vector::new(400.0, 900.0),
+            radius: 40.0,
+        });
+        let with_obstacle = content(&scene, &obstacles, &walls).unwrap();
+        assert!(same(
+            with_obstacle,
+            expected.union(boxed(360.0, 860.0, 440.0, 940.0))
+        ));
+
+        // The walls widen it, over the height of the rest.
+        let config = Config::parse("[walls]\nleft = -100\nright = 1000\n").unwrap();
+        walls = Walls::from_config(config.section("walls").unwrap()).unwrap();
+        let with_walls = content(&scene, &obstacles, &walls).unwrap();
+        assert!(same(
+            with_walls,
+            boxed(-100.0, with_obstacle.min.y, 1000.0, with_obstacle.max.y)
+        ));
+
+        assert!(union(std::iter::empty()).is_none());
+        let mut empty = Scene::single();
+        empty.models.clear();
+        assert!(content(&empty, &Obstacles::new(), &walls).is_none());
+    }
+
+    /// Runs `framing` until the camera stops, and says whether it moved.
+    fn settle(framing: &mut Framing, view: &mut Viewport, content: Bounds) -> bool {
+        let (centre, zoom) = (view.centre, view.zoom);
+        for _ in 0..2 * FRAMES {
+            framing.update(view, || Some(content));
+        }
+        assert!(framing.moving.is_none(), "still moving");
+        !(close(view.centre.x, centre.x)
+            && close(view.centre.y, centre.y)
+            && close(view.zoom, zoom))
+    }
+
+    #[test]
+    fn following_reframes_only_once_the_content_leaves_or_shrinks_well_inside() {
+        let mut view = Viewport::new(true);
+        let mut framing = Framing::new();
+        let filling = boxed(100.0, 50.0, 700.0, 430.0);
+        let wider = boxed(-20.0, 50.0, 700.0, 430.0);
+
+        // Not following, nothing moves the camera.
+        assert!(!settle(&mut framing, &mut view, wider));
+
+        framing.follow = true;
+        // What fits the world as laid out is left there.
+        assert!(!settle(&mut framing, &mut view, filling));
+        // Leaving the frame reframes, with room to spare.
+        assert!(settle(&mut framing, &mut view, wider));
+        let (min, max) = view.framed_world();
+        let framed = Bounds { min, max };
+        assert!(framed.contains(&wider.padded(MARGIN)));
+        assert!(close(view.zoom, wider.padded(MARGIN).zoom() / SLACK));
+        // Then it stays put, both for the same and for a bit less.
+        assert!(!settle(&mut framing, &mut view, wider));
+        assert!(!settle(&mut framing, &mut view, filling));
+        // Until all of it would fit well closer.
+        let small = boxed(350.0, 200.0, 450.0, 280.0);
+        assert!(small.padded(MARGIN).zoom() >= SHRINK * view.zoom);
+        assert!(settle(&mut framing, &mut view, small));
+        assert!(close(view.zoom, ZOOM.1 / SLACK));
+
+        // Home stops following and goes back.
+        framing.home(&view);
+        assert!(!framing.follow);
+        settle(&mut framing, &mut view, wider);
+        assert!(close(view.zoom, 1.0) && close(view.centre.x, viewport::home().x));
+    }
+
+    #[test]
+    fn fitting_frames_the_content_with_a_margin() {
+        let mut view = Viewport::new(true);
+        let mut framing = Framing::new();
+        let content = boxed(300.0, 100.0, 500.0, 500.0);
+        framing.fit(&view, content);
+        settle(&mut framing, &mut view, content);
+        let (min, max) = view.framed_world();
+        assert!(Bounds { min, max }.contains(&content.padded(MARGIN)));
+        // It fills the height, the tighter way.
+        assert!(close(max.y - min.y, 400.0 + 2.0 * MARGIN));
+    }
+}
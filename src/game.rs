@@ -0,0 +1,231 @@
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::Graphics2D;
+
+use crate::draw_arc;
+use crate::pendulum::Pendulum;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+use crate::STEPS_PER_SECOND;
+
+/// How hard a round is: where the flags and gravity start and how fast
+/// they close in and ramp up.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Difficulty {
+    pub start_half_width: f32,
+    pub min_half_width: f32,
+    /// Radians a second the flags close in by.
+    pub narrowing: f32,
+    /// How far either way the middle of the flags drifts, and the seconds
+    /// it takes to drift there and back.
+    pub drift: f32,
+    pub drift_period: f32,
+    pub start_gravity: f32,
+    pub max_gravity: f32,
+    /// Gravity added each second.
+    pub gravity_ramp: f32,
+}
+
+impl Difficulty {
+    /// The everyday game's schedule.
+    pub fn standard() -> Difficulty {
+        Difficulty {
+            start_half_width: 0.6,
+            min_half_width: 0.12,
+            narrowing: 0.01,
+            drift: 0.35,
+            drift_period: 12.0,
+            start_gravity: 0.5,
+            max_gravity: 1.5,
+            gravity_ramp: 0.01,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RoundState {
+    Idle,
+    Running,
+    Over,
+}
+
+/// "Keep it between the flags": two flag angles drift and close in while
+/// gravity ramps up, and the player steers the bob with the motor alone.
+pub struct FlagGame {
+    pub state: RoundState,
+    /// Freezes the flags and gravity at their starting values.
+    pub practice: bool,
+    pub difficulty: Difficulty,
+    steps: u32,
+    last_score: f32,
+    best_score: f32,
+    saved_gravity: f32,
+}
+
+impl FlagGame {
+    pub fn new() -> FlagGame {
+        FlagGame {
+            state: RoundState::Idle,
+            practice: false,
+            difficulty: Difficulty::standard(),
+            steps: 0,
+            last_score: 0.0,
+            best_score: 0.0,
+            saved_gravity: Difficulty::standard().start_gravity,
+        }
+    }
+
+    pub fn start(&mut self, p: &mut Pendulum) {
+        if self.state != RoundState::Running {
+            self.saved_gravity = p.g;
+        }
+
+        self.state = RoundState::Running;
+        self.steps = 0;
+
+        p.angle = 0.0;
+        p.angular_velocity = 0.0;
+        p.angular_acceleration = 0.0;
+        p.g = self.difficulty.start_gravity;
+    }
+
+    pub fn stop(&mut self, p: &mut Pendulum) {
+        if self.state == RoundState::Running {
+            p.g = self.saved_gravity;
+        }
+        self.state = RoundState::Idle;
+    }
+
+    /// Game time is counted in steps so every run sees exactly the same
+    /// difficulty curve.
+    fn seconds(&self) -> f32 {
+        self.steps as f32 / STEPS_PER_SECOND
+    }
+
+    /// Returns the (left, right) flag angles for the current moment.
+    pub fn flags(&self) -> (f32, f32) {
+        let d = &self.difficulty;
+        let (center, half_width) = if self.practice {
+            (0.0, d.start_half_width)
+        } else {
+            let t = self.seconds();
+            (
+                d.drift * (t * std::f32::consts::TAU / d.drift_period).sin(),
+                (d.start_half_width - d.narrowing * t).max(d.min_half_width),
+            )
+        };
+
+        (center - half_width, center + half_width)
+    }
+
+    fn gravity(&self) -> f32 {
+        let d = &self.difficulty;
+        if self.practice {
+            d.start_gravity
+        } else {
+            (d.start_gravity + d.gravity_ramp * self.seconds()).min(d.max_gravity)
+        }
+    }
+
+    /// Applies the difficulty schedule before the physics step.
+    pub fn before_update(&mut self, p: &mut Pendulum) {
+        if self.state == RoundState::Running {
+            p.g = self.gravity();
+        }
+    }
+
+    /// Advances the clock and ends the round once the bob leaves the flags.
+    /// Returns true on the step the round ends.
+    pub fn after_update(&mut self, p: &mut Pendulum) -> bool {
+        if self.state != RoundState::Running {
+            return false;
+        }
+
+        self.steps += 1;
+
+        let (left, right) = self.flags();
+        if p.angle < left || p.angle > right {
+            self.last_score = self.seconds();
+            if !self.practice && self.difficulty == Difficulty::standard() {
+                self.best_score = self.best_score.max(self.last_score);
+            }
+            p.g = self.saved_gravity;
+            self.state = RoundState::Over;
+            return true;
+        }
+        false
+    }
+
+    /// Score of the last finished round, in seconds.
+    pub fn last_score(&self) -> f32 {
+        self.last_score
+    }
+
+    /// Carries over the best score from earlier sessions.
+    pub fn remember_best(&mut self, score: f32) {
+        self.best_score = self.best_score.max(score);
+    }
+
+    pub fn draw(&self, p: &Pendulum, graphics: &mut Graphics2D, font: &Font, view: &Viewport) {
+        if self.state == RoundState::Idle {
+            return;
+        }
+
+        let (left, right) = self.flags();
+        draw_arc(
+            graphics,
+            view,
+            p.origin,
+            p.r,
+            left,
+            right,
+            6.0,
+            Color::from_rgba(0.2, 0.7, 0.3, 0.5),
+        );
+
+        for angle in [left, right] {
+            let (sin, cos) = angle.sin_cos();
+            let base =
+                view.world_to_screen(Vector::new(p.origin.x + p.r * sin, p.origin.y + p.r * cos));
+            let tip = view.world_to_screen(Vector::new(
+                p.origin.x + (p.r + 40.0) * sin,
+                p.origin.y + (p.r + 40.0) * cos,
+            ));
+            let (flag_width, flag_height) = (view.length(16.0), view.length(12.0));
+            graphics.draw_line(base, tip, view.length(2.0), Color::BLACK);
+            graphics.draw_triangle(
+                [
+                    tip.into(),
+                    (tip.0 + flag_width, tip.1 + 0.5 * flag_height).into(),
+                    (tip.0, tip.1 + flag_height).into(),
+                ],
+                Color::RED,
+            );
+        }
+
+        let status = match self.state {
+            RoundState::Running => format!("Survival: {:.1} s", self.seconds()),
+            _ => format!("Round over: {:.1} s - G to retry", self.last_score),
+        };
+        let mode = if self.practice { " (practice)" } else { "" };
+
+        graphics.draw_text(
+            (520.0, 0.0),
+            Color::BLACK,
+            &font.layout_text(
+                format!("{}{}", status, mode).as_str(),
+                30.0,
+                TextOptions::new(),
+            ),
+        );
+        graphics.draw_text(
+            (520.0, 30.0),
+            Color::BLACK,
+            &font.layout_text(
+                format!("Best: {:.1} s", self.best_score).as_str(),
+                30.0,
+                TextOptions::new(),
+            ),
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::focus::{Handling, Registry, Target};
+use crate::model::PendulumEntry;
+use crate::pendulum::Pendulum;
+
+const COLUMNS: usize = 8;
+const ROWS: usize = 5;
+/// Initial angles across the columns, left to right.
+const ANGLES: (f32, f32) = (0.2, 3.0);
+/// Drive amplitudes down the rows, top to bottom.
+const DRIVE_AMPLITUDES: (f32, f32) = (0.0, 0.002);
+
+/// Initial conditions of one tile.
+#[derive(Copy, Clone)]
+pub struct TileParameters {
+    pub angle: f32,
+    pub drive_amplitude: f32,
+    pub drive_frequency: f32,
+}
+
+/// Small multiples: the same pendulum started from a grid of initial angles
+/// (columns) and drive amplitudes (rows), all stepped together and drawn as
+/// tiny rods, so how the motion varies over the range is visible at a glance.
+pub struct GridScan {
+    template: PendulumEntry,
+    parameters: Vec<TileParameters>,
+    tiles: Vec<Pendulum>,
+    size: (f32, f32),
+    hover: Option<usize>,
+}
+
+impl GridScan {
+    /// Lays out a grid over a window of `size` pixels, varying `template`.
+    /// An undriven template is driven at its small-angle natural frequency.
+    pub fn new(template: PendulumEntry, size: (f32, f32)) -> GridScan {
+        let template = template.fresh();
+        let drive_frequency = if template.drive_frequency > 0.0 {
+            template.drive_frequency
+        } else {
+            (template.g / template.r).sqrt()
+        };
+        let lerp = |(from, to): (f32, f32), i: usize, count: usize| {
+            from + (to - from) * i as f32 / (count - 1) as f32
+        };
+
+        let mut parameters = Vec::with_capacity(COLUMNS * ROWS);
+        for row in 0..ROWS {
+            for column in 0..COLUMNS {
+                parameters.push(TileParameters {
+                    angle: lerp(ANGLES, column, COLUMNS),
+                    drive_amplitude: lerp(DRIVE_AMPLITUDES, row, ROWS),
+                    drive_frequency,
+                });
+            }
+        }
+
+        let tiles = parameters
+            .iter()
+            .map(|tile| {
+                Pendulum::from_entry(&PendulumEntry {
+                    angle: tile.angle,
+                    angular_velocity: 0.0,
+                    drive_amplitude: tile.drive_amplitude,
+                    drive_frequency: tile.drive_frequency,
+                    ..template.clone()
+                })
+            })
+            .collect();
+
+        GridScan {
+            template,
+            parameters,
+            tiles,
+            size,
+            hover: None,
+        }
+    }
+
+    pub fn resize(&mut self, size: (f32, f32)) {
+        self.size = size;
+        self.hover = None;
+    }
+
+    pub fn step(&mut self) {
+        self.tiles.iter_mut().for_each(Pendulum::update);
+    }
+
+    fn tile_size(&self) -> (f32, f32) {
+        (self.size.0 / COLUMNS as f32, self.size.1 / ROWS as f32)
+    }
+
+    fn tile_at(&self, x: f32, y: f32) -> Option<usize> {
+        let (width, height) = self.tile_size();
+        if x < 0.0 || y < 0.0 || x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+        let (column, row) = ((x / width) as usize, (y / height) as usize);
+        Some(row.min(ROWS - 1) * COLUMNS + column.min(COLUMNS - 1))
+    }
+
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.hover = self.tile_at(x, y);
+    }
+
+    /// Registers each tile, to promote.
+    pub fn register(&self, registry: &mut Registry) {
+        let (width, height) = self.tile_size();
+        for i in 0..self.tiles.len() {
+            let (column, row) = ((i % COLUMNS) as f32, (i / COLUMNS) as f32);
+            registry.register(
+                Target::Tile(i),
+                ((column + 0.5) * width, (row + 0.5) * height),
+                0.4 * width.min(height),
+                Handling::Click,
+            );
+        }
+    }
+
+    /// The template with the clicked tile's initial conditions, to replace
+    /// the pendulum the grid was made from.
+    pub fn click(&self, x: f32, y: f32) -> Option<PendulumEntry> {
+        let tile = self.parameters[self.tile_at(x, y)?];
+        Some(PendulumEntry {
+            angle: tile.angle,
+            angular_velocity: 0.0,
+            drive_amplitude: tile.drive_amplitude,
+            drive_frequency: tile.drive_frequency,
+            ..self.template.clone()
+        })
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font) {
+        let (width, height) = self.tile_size();
+        // The rod fills most of the tile whatever its real length, with room
+        // for the bob to swing over the top.
+        let scale = 0.4 * width.min(height) / self.template.r;
+
+        for (i, p) in self.tiles.iter().enumerate() {
+            let left = (i % COLUMNS) as f32 * width;
+            let top = (i / COLUMNS) as f32 * height;
+
+            graphics.set_clip(Some(Rectangle::from_tuples(
+                (left as i32, top as i32),
+                ((left + width) as i32, (top + height) as i32),
+            )));
+
+            let shade = if self.hover == Some(i) { 1.0 } else { 0.9 };
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((left + 1.0, top + 1.0), (left + width, top + height)),
+                Color::from_rgb(shade, shade, 1.0),
+            );
+
+            let pivot = (left + 0.5 * width, top + 0.5 * height);
+            let bob = (
+                pivot.0 + scale * p.r * p.angle.sin(),
+                pivot.1 + scale * p.r * p.angle.cos(),
+            );
+            graphics.draw_line(pivot, bob, 1.5, Color::GRAY);
+            graphics.draw_circle(bob, 4.0, Color::DARK_GRAY);
+        }
+        graphics.set_clip(None);
+
+        if let Some(i) = self.hover {
+            let tile = self.parameters[i];
+            let text = font.layout_text(
+                &format!(
+                    "angle {:.2}, drive {:.4} @ {:.3}",
+                    tile.angle, tile.drive_amplitude, tile.drive_frequency
+                ),
+                20.0,
+                TextOptions::new(),
+            );
+
+            // Beside the tile, kept inside the window.
+            let left = ((i % COLUMNS) as f32 * width).min(self.size.0 - text.width() - 8.0);
+            let top = ((i / COLUMNS + 1) as f32 * height).min(self.size.1 - 26.0);
+            graphics.draw_rectangle(
+                Rectangle::from_tuples(
+                    (left, top),
+                    (left + text.width() + 8.0, top + text.height() + 4.0),
+                ),
+                Color::from_rgba(1.0, 1.0, 1.0, 0.9),
+            );
+            graphics.draw_text((left + 4.0, top + 2.0), Color::BLACK, &text);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! The keys, as they're bound, and the recorded macros, over the scene. The
+//! cheat sheet is made from the bindings each time it's drawn, grouped by
+//! category and flowed down the columns, so a remapped key shows as it is
+//! now. Keys that don't fit are carried over to further pages, which the
+//! help key steps through before closing the overlay. After the keys
+//! comes how to work everything by keyboard alone, from what there is to
+//! focus at the time; see `focus`.
+//!
+//! A `[help]` section sets the number of columns and the text size:
+//!
+//! ```text
+//! [help]
+//! columns = 3
+//! size = 13
+//! ```
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::bindings::Bindings;
+use crate::config::{key_value, parse_f32, Section};
+use crate::focus::{self, Registry};
+use crate::macros::Macros;
+
+const RECT: (f32, f32, f32, f32) = (20.0, 20.0, 760.0, 440.0);
+/// Macros listed before the rest are left to `macro list`.
+const MACRO_ROWS: usize = 4;
+const MAX_COLUMNS: usize = 6;
+const TEXT_SIZES: (f32, f32) = (8.0, 24.0);
+
+/// A line of the cheat sheet.
+enum Row {
+    Heading(&'static str),
+    Key(String, String),
+}
+
+pub struct HelpOverlay {
+    pub visible: bool,
+    page: usize,
+    columns: usize,
+    size: f32,
+}
+
+impl HelpOverlay {
+    pub fn new() -> HelpOverlay {
+        HelpOverlay {
+            visible: false,
+            page: 0,
+            columns: 3,
+            size: 13.0,
+        }
+    }
+
+    /// Reads `columns = <count>` and `size = <pixels>` from a `[help]`
+    /// section.
+    pub fn from_config(section: &Section) -> Result<HelpOverlay, String> {
+        let mut help = HelpOverlay::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("columns", value)) => {
+                    help.columns = value
+                        .parse()
+                        .ok()
+                        .filter(|columns| (1..=MAX_COLUMNS).contains(columns))
+                        .ok_or_else(|| {
+                            error(format!(
+                                "expected from 1 to {} columns, found `{}`",
+                                MAX_COLUMNS, value
+                            ))
+                        })?
+                }
+                Some(("size", value)) => {
+                    help.size = parse_f32("size", value).map_err(error)?;
+                    if !(TEXT_SIZES.0..=TEXT_SIZES.1).contains(&help.size) {
+                        return Err(error(format!(
+                            "`size` must be from {} to {}",
+                            TEXT_SIZES.0, TEXT_SIZES.1
+                        )));
+                    }
+                }
+                _ => return Err(error(format!("unknown help setting `{}`", line))),
+            }
+        }
+
+        Ok(help)
+    }
+
+    /// Opens the overlay, or moves on to its next page, or closes it after
+    /// the last.
+    pub fn advance(&mut self, bindings: &Bindings, registry: &Registry) {
+        if !self.visible {
+            self.visible = true;
+            self.page = 0;
+        } else if self.page + 1 < self.pages(bindings, registry).len() {
+            self.page += 1;
+        } else {
+            self.visible = false;
+        }
+    }
+
+    fn row_height(&self) -> f32 {
+        self.size + 2.0
+    }
+
+    /// The cheat sheet cut into pages of columns of rows, leaving room at
+    /// the bottom for the macros and the page number.
+    fn pages(&self, bindings: &Bindings, registry: &Registry) -> Vec<Vec<Vec<Row>>> {
+        let mut rows = Vec::new();
+        for (category, keys) in bindings.cheat_sheet() {
+            if keys.is_empty() {
+                continue;
+            }
+            rows.push(Row::Heading(category.name()));
+            rows.extend(
+                keys.into_iter()
+                    .map(|(name, key)| Row::Key(key, name.replace('_', " "))),
+            );
+        }
+        for (heading, keys) in focus::help_rows(registry) {
+            rows.push(Row::Heading(heading));
+            rows.extend(keys.into_iter().map(|(key, does)| Row::Key(key, does)));
+        }
+
+        let (_, _, _, height) = RECT;
+        let footer = (MACRO_ROWS + 3) as f32 * self.row_height();
+        let per_column = (((height - footer) / self.row_height()) as usize).max(1);
+        let mut columns: Vec<Vec<Row>> = vec![Vec::new()];
+        for row in rows {
+            let column = columns.last_mut().unwrap();
+            // A heading goes over to the next column with its first key
+            // rather than sit at the foot of this one.
+            let needs = if matches!(row, Row::Heading(_)) { 2 } else { 1 };
+            if !column.is_empty() && column.len() + needs > per_column {
+                columns.push(Vec::new());
+            }
+            columns.last_mut().unwrap().push(row);
+        }
+
+        let mut pages: Vec<Vec<Vec<Row>>> = Vec::new();
+        for column in columns {
+            match pages.last_mut() {
+                Some(page) if page.len() < self.columns => page.push(column),
+                _ => pages.push(vec![column]),
+            }
+        }
+        if pages.is_empty() {
+            pages.push(Vec::new());
+        }
+        pages
+    }
+
+    pub fn draw(
+        &self,
+        graphics: &mut Graphics2D,
+        font: &Font,
+        bindings: &Bindings,
+        registry: &Registry,
+        macros: &Macros,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = RECT;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.92),
+        );
+        let size = self.size;
+        let text = |graphics: &mut Graphics2D, x: f32, y: f32, text: &str, color: Color| {
+            graphics.draw_text(
+                (x, y),
+                color,
+                &font.layout_text(text, size, TextOptions::new()),
+            );
+        };
+
+        let pages = self.pages(bindings, registry);
+        let page = &pages[self.page.min(pages.len() - 1)];
+        let column_width = width / self.columns as f32;
+        let key_width = 6.0 * self.size;
+        let mut bottom: f32 = top;
+        for (i, column) in page.iter().enumerate() {
+            let x = left + 8.0 + column_width * i as f32;
+            for (j, row) in column.iter().enumerate() {
+                let y = top + 6.0 + self.row_height() * j as f32;
+                match row {
+                    Row::Heading(name) => text(graphics, x, y, name, Color::DARK_GRAY),
+                    Row::Key(key, name) => {
+                        text(graphics, x, y, key, Color::BLACK);
+                        text(graphics, x + key_width, y, name, Color::BLACK);
+                    }
+                }
+                bottom = bottom.max(y + self.row_height());
+            }
+        }
+
+        let mut y = bottom + 8.0;
+        text(
+            graphics,
+            left + 8.0,
+            y,
+            "Macros - replay key plays the latest, Ctrl with it records, `macro list` for all",
+            Color::BLACK,
+        );
+        for line in macros.list().iter().take(MACRO_ROWS) {
+            y += self.row_height();
+            text(graphics, left + 8.0, y, line, Color::BLACK);
+        }
+
+        if pages.len() > 1 {
+            text(
+                graphics,
+                left + 8.0,
+                top + height - self.row_height() - 4.0,
+                &format!(
+                    "Page {} of {} - the help key again for the next",
+                    self.page + 1,
+                    pages.len()
+                ),
+                Color::DARK_GRAY,
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::pendulum::PendulumState;
+use crate::retention::{Resolution, Sample, Tiered};
+
+impl Sample for PendulumState {
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// The first state of the run, so scrubbing into old history still lands
+    /// on a state the run really passed through, exact to carry on from.
+    fn merge(run: &[PendulumState]) -> PendulumState {
+        run[0]
+    }
+}
+
+/// Record of every physics step, used to scrub back and forth through a run
+/// exactly instead of re-integrating it. The recent steps are all kept and
+/// older ones ever more thinly, within the history's share of the memory.
+pub struct History {
+    states: Tiered<PendulumState>,
+    /// Index of the state being shown while scrubbing.
+    cursor: Option<usize>,
+}
+
+impl History {
+    /// A history keeping its states in `budget` bytes.
+    pub fn new(budget: usize) -> History {
+        History {
+            states: Tiered::new(budget),
+            cursor: None,
+        }
+    }
+
+    pub fn record(&mut self, state: PendulumState) {
+        self.states.push(state);
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+        self.cursor = None;
+    }
+
+    pub fn is_scrubbing(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// Enters scrub mode at the most recent state.
+    pub fn start_scrub(&mut self) -> Option<PendulumState> {
+        let last = self.states.len().checked_sub(1)?;
+        self.cursor = Some(last);
+        self.states.get(last)
+    }
+
+    /// Leaves scrub mode, dropping everything recorded after the cursor so the
+    /// run continues from the state that was on screen.
+    pub fn stop_scrub(&mut self) {
+        if let Some(cursor) = self.cursor.take() {
+            self.states.truncate(cursor + 1);
+        }
+    }
+
+    /// Moves the cursor by `steps` recorded states (negative is backwards in
+    /// time; in older history each is more than one physics step) and
+    /// returns the state under it.
+    pub fn scrub(&mut self, steps: isize) -> Option<PendulumState> {
+        let cursor = self.cursor?;
+        let last = self.states.len().checked_sub(1)?;
+        let cursor = cursor.saturating_add_signed(steps).min(last);
+        self.cursor = Some(cursor);
+        self.states.get(cursor)
+    }
+
+    /// Scrubs to the recorded state closest to `time` (in steps), entering
+    /// scrub mode if needed. Returns `None` if the time has already fallen
+    /// out of the history.
+    pub fn scrub_to(&mut self, time: f32) -> Option<PendulumState> {
+        let (first, last) = self.time_range()?;
+        if time < first - 0.5 || time > last + 0.5 {
+            return None;
+        }
+
+        let cursor = self.states.partition(time).min(self.states.len() - 1);
+        self.cursor = Some(cursor);
+        self.states.get(cursor)
+    }
+
+    /// Time in steps of the oldest and newest recorded states.
+    pub fn time_range(&self) -> Option<(f32, f32)> {
+        Some((self.states.first()?.time, self.states.last()?.time))
+    }
+
+    /// Every recorded state, oldest first.
+    pub fn states(&self) -> Vec<PendulumState> {
+        self.states.iter().collect()
+    }
+
+    /// Returns the cursor position, the number of recorded states and the
+    /// time in steps of the state under the cursor.
+    pub fn position(&self) -> Option<(usize, usize, f32)> {
+        let cursor = self.cursor?;
+        Some((cursor, self.states.len(), self.states.get(cursor)?.time))
+    }
+
+    pub fn memory(&self) -> usize {
+        self.states.memory()
+    }
+
+    pub fn resolutions(&self) -> Vec<Resolution> {
+        self.states.resolutions()
+    }
+}
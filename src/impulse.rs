@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::Path;
+
+use crate::analysis::{damping_ratio, find_peaks, frequency};
+use crate::pendulum::Pendulum;
+use crate::STEPS_PER_SECOND;
+
+/// Angular velocity given to the bob by the impulse.
+const IMPULSE: f32 = 0.04;
+/// The longest response recorded, in steps.
+const MAX_STEPS: usize = 30 * STEPS_PER_SECOND as usize;
+/// Recording stops early once the swing has died down below this angle.
+const SETTLED_ANGLE: f32 = 0.002;
+
+pub enum ImpulseState {
+    Idle,
+    /// The last attempt was refused because the pendulum was moving.
+    NotAtRest,
+    Recording,
+    Done {
+        /// Natural frequency in Hz.
+        frequency: f32,
+        damping_ratio: f32,
+    },
+    /// Recording finished but the response had too few peaks to fit.
+    Failed,
+}
+
+/// Systems-identification experiment: kick a resting pendulum once, record
+/// how it rings down, and read its natural frequency and damping ratio off
+/// the response.
+pub struct ImpulseTest {
+    pub state: ImpulseState,
+    /// The angle after each step of the scene.
+    samples: Vec<f32>,
+    /// The pendulum's steps per sample, its rate.
+    rate: u32,
+}
+
+impl ImpulseTest {
+    pub fn new() -> ImpulseTest {
+        ImpulseTest {
+            state: ImpulseState::Idle,
+            samples: Vec::new(),
+            rate: 1,
+        }
+    }
+
+    /// Fires the impulse if the pendulum is hanging still.
+    pub fn fire(&mut self, p: &mut Pendulum) {
+        if p.angular_velocity.abs() > 1e-4 || p.angle.abs() > 1e-3 {
+            self.state = ImpulseState::NotAtRest;
+            return;
+        }
+
+        p.angular_velocity += IMPULSE;
+        self.samples.clear();
+        self.rate = p.rate;
+        self.state = ImpulseState::Recording;
+    }
+
+    pub fn cancel(&mut self) {
+        self.state = ImpulseState::Idle;
+        self.samples.clear();
+    }
+
+    /// Records the angle after each step and analyses the response once it
+    /// has settled. Returns the fitted frequency in Hz and damping ratio on
+    /// the step the analysis succeeds.
+    pub fn record(&mut self, p: &Pendulum, csv: &Path) -> Option<(f32, f32)> {
+        if !matches!(self.state, ImpulseState::Recording) {
+            return None;
+        }
+
+        self.samples.push(p.angle);
+
+        let settled = self.samples.len() > STEPS_PER_SECOND as usize
+            && self
+                .samples
+                .iter()
+                .rev()
+                .take(STEPS_PER_SECOND as usize)
+                .all(|angle| angle.abs() < SETTLED_ANGLE);
+
+        if settled || self.samples.len() >= MAX_STEPS {
+            self.finish(csv);
+        }
+        match self.state {
+            ImpulseState::Done {
+                frequency,
+                damping_ratio,
+            } => Some((frequency, damping_ratio)),
+            _ => None,
+        }
+    }
+
+    fn finish(&mut self, csv: &Path) {
+        let peaks = find_peaks(&self.samples);
+
+        self.state = match (frequency(&peaks), damping_ratio(&peaks)) {
+            (Some(frequency), Some(damping_ratio)) => ImpulseState::Done {
+                frequency: frequency * STEPS_PER_SECOND / self.rate as f32,
+                damping_ratio,
+            },
+            _ => ImpulseState::Failed,
+        };
+
+        // The scene's time and the pendulum's own, which differ for a
+        // pendulum running fast.
+        let mut text = String::from("time,pendulum_time,angle\n");
+        for (i, angle) in self.samples.iter().enumerate() {
+            text.push_str(&format!(
+                "{},{},{}\n",
+                i as f32 / STEPS_PER_SECOND,
+                (i * self.rate as usize) as f32 / STEPS_PER_SECOND,
+                angle
+            ));
+        }
+        if let Err(e) = fs::write(csv, text) {
+            log!("could not write {}: {}", csv.display(), e);
+        }
+    }
+
+    pub fn hud_line(&self) -> Option<String> {
+        match self.state {
+            ImpulseState::Idle => None,
+            ImpulseState::NotAtRest => Some("Impulse: wait until it hangs still".to_string()),
+            ImpulseState::Recording => Some(format!(
+                "Impulse: recording {:.1} s",
+                self.samples.len() as f32 / STEPS_PER_SECOND
+            )),
+            ImpulseState::Done {
+                frequency,
+                damping_ratio,
+            } => Some(format!(
+                "Impulse: f = {:.3} Hz, zeta = {:.4}",
+                frequency, damping_ratio
+            )),
+            ImpulseState::Failed => Some("Impulse: response too short to fit".to_string()),
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! Instant replay: the selected pendulum's last complete swing played back
+//! again at a quarter speed in an inset, while the simulation carries on in
+//! the main view. The swing is taken from the scrubbing history, from one
+//! time the bob passed the bottom to the latest time it passed it going the
+//! same way, and is played between its recorded steps so the slowed motion
+//! stays smooth. It can loop until dismissed; it holds still while the
+//! simulation is paused, and asking for another replaces it.
+
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::model::{Model, ModelEntry, PendulumEntry};
+use crate::pendulum::{Pendulum, PendulumState, BOB_RADIUS};
+use crate::polyline;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// Steps of the swing played each frame.
+pub const SPEED: f32 = 0.25;
+const RECT: (f32, f32, f32, f32) = (590.0, 190.0, 200.0, 180.0);
+const BAR_HEIGHT: f32 = 6.0;
+
+/// Whether the angle passes through zero between `a` and `b`, and if so
+/// whether going up. Going over the top flips the sign of the angle too,
+/// but that's a jump of nearly a turn and isn't a pass through the bottom.
+fn crossing(a: f32, b: f32) -> Option<bool> {
+    let through = a != b && (a < 0.0) != (b < 0.0) && (a - b).abs() < PI;
+    through.then_some(b > a)
+}
+
+/// The last complete swing in `states`, as the range from the first state
+/// past one crossing of the bottom to the first state past the latest
+/// crossing the same way. `None` until there are two such crossings, so a
+/// history that starts part way through a swing only counts from its first
+/// crossing.
+pub fn last_swing(states: &[PendulumState]) -> Option<Range<usize>> {
+    let mut crossings = states
+        .windows(2)
+        .enumerate()
+        .rev()
+        .filter_map(|(i, pair)| Some((i + 1, crossing(pair[0].angle, pair[1].angle)?)));
+    let (end, upward) = crossings.next()?;
+    let (start, _) = crossings.find(|&(_, way)| way == upward)?;
+    Some(start..end + 1)
+}
+
+/// `a` to `b` by `t`, the short way round for an angle.
+fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let delta = (b - a + PI).rem_euclid(2.0 * PI) - PI;
+    a + delta * t
+}
+
+pub struct InstantReplay {
+    /// The pendulum the swing is drawn as, set to each state in turn.
+    pendulum: Option<PendulumEntry>,
+    states: Vec<PendulumState>,
+    /// Position in `states`, between steps while slowed down.
+    cursor: f32,
+    pub looping: bool,
+}
+
+impl InstantReplay {
+    pub fn new() -> InstantReplay {
+        InstantReplay {
+            pendulum: None,
+            states: Vec::new(),
+            cursor: 0.0,
+            looping: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.pendulum.is_some()
+    }
+
+    /// Replays the last complete swing of `p` in `history`, in place of any
+    /// replay already showing.
+    pub fn start(&mut self, p: &Pendulum, history: &[PendulumState]) -> Result<String, String> {
+        let ModelEntry::Pendulum(entry) = p.entry() else {
+            return Err("Only a pendulum's swing can be replayed".to_string());
+        };
+        let swing = last_swing(history).ok_or("No complete swing to replay yet")?;
+        self.states = history[swing].to_vec();
+        self.pendulum = Some(entry);
+        self.cursor = 0.0;
+        Ok(format!(
+            "Replaying the last swing ({:.2} s) at {}x{}",
+            self.duration(),
+            SPEED,
+            if self.looping { ", looping" } else { "" }
+        ))
+    }
+
+    pub fn dismiss(&mut self) {
+        self.pendulum = None;
+        self.states.clear();
+    }
+
+    /// Seconds the swing took.
+    fn duration(&self) -> f32 {
+        match (self.states.first(), self.states.last()) {
+            (Some(first), Some(last)) => (last.time - first.time) / crate::STEPS_PER_SECOND,
+            _ => 0.0,
+        }
+    }
+
+    fn end(&self) -> f32 {
+        self.states.len().saturating_sub(1) as f32
+    }
+
+    /// Moves the replay on a frame, unless the simulation is `paused`.
+    pub fn step(&mut self, paused: bool) {
+        if paused || !self.is_active() {
+            return;
+        }
+        self.cursor += SPEED;
+        if self.cursor > self.end() {
+            self.cursor = if self.looping && self.end() > 0.0 {
+                self.cursor - self.end()
+            } else {
+                self.end()
+            };
+        }
+    }
+
+    /// The state under the cursor, between the two recorded steps it's
+    /// part way through.
+    fn current(&self) -> Option<PendulumState> {
+        let i = (self.cursor.floor() as usize).min(self.states.len().checked_sub(1)?);
+        let (a, b) = (
+            self.states[i],
+            *self.states.get(i + 1).unwrap_or(&self.states[i]),
+        );
+        let t = self.cursor - i as f32;
+        Some(PendulumState {
+            angle: lerp_angle(a.angle, b.angle, t),
+            angular_velocity: a.angular_velocity + (b.angular_velocity - a.angular_velocity) * t,
+            time: a.time + (b.time - a.time) * t,
+            ..a
+        })
+    }
+
+    /// The `instant_replay` console command: `loop` or `once` for what
+    /// happens at the end of the swing, or `off` to dismiss it.
+    pub fn command(&mut self, words: &[&str]) -> Result<Vec<String>, String> {
+        match words {
+            ["loop"] => self.looping = true,
+            ["once"] => self.looping = false,
+            ["off"] => self.dismiss(),
+            [] => {}
+            _ => return Err("Usage: instant_replay [loop|once|off]".to_string()),
+        }
+        Ok(vec![format!(
+            "Instant replay {}, {}",
+            if self.is_active() {
+                "showing"
+            } else {
+                "not showing"
+            },
+            if self.looping {
+                "looping"
+            } else {
+                "playing once"
+            }
+        )])
+    }
+
+    /// Draws the swing in the inset, framed on the pivot, with the path the
+    /// bob takes and a bar for how far through it is.
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, view: &Viewport) {
+        let (Some(entry), Some(state)) = (&self.pendulum, self.current()) else {
+            return;
+        };
+        let mut pendulum = Pendulum::from_entry(entry);
+        pendulum.restore(&state);
+        let (left, top, width, height) = RECT;
+        let plot_height = height - BAR_HEIGHT - 20.0;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.8),
+        );
+        graphics.draw_text(
+            (left + 4.0, top + 2.0),
+            Color::BLACK,
+            &font.layout_text(
+                &format!(
+                    "Last swing at {}x{}",
+                    SPEED,
+                    if self.looping { ", looping" } else { "" }
+                ),
+                14.0,
+                TextOptions::new(),
+            ),
+        );
+
+        let reach = self.states.iter().map(|state| state.r).fold(0.0, f32::max) + BOB_RADIUS + 4.0;
+        let inset = view.inset(
+            pendulum.origin,
+            reach,
+            (left, top + 18.0, width, plot_height),
+        );
+        graphics.set_clip(Some(Rectangle::from_tuples(
+            (left as i32, (top + 18.0) as i32),
+            ((left + width) as i32, (top + 18.0 + plot_height) as i32),
+        )));
+        let path: Vec<Vector> = self
+            .states
+            .iter()
+            .map(|state| {
+                let (x, y) = inset.world_to_screen(Vector::new(
+                    pendulum.origin.x + state.r * state.angle.sin(),
+                    pendulum.origin.y + state.r * state.angle.cos(),
+                ));
+                Vector::new(x, y)
+            })
+            .collect();
+        polyline::draw_polyline(graphics, &path, 1.0, Color::from_rgba(0.2, 0.3, 0.6, 0.4));
+        pendulum.draw(graphics, &inset);
+        graphics.set_clip(None);
+
+        let bar_top = top + height - BAR_HEIGHT - 2.0;
+        let done = if self.end() > 0.0 {
+            self.cursor / self.end()
+        } else {
+            1.0
+        };
+        graphics.draw_rectangle(
+            Rectangle::from_tuples(
+                (left + 4.0, bar_top),
+                (left + width - 4.0, bar_top + BAR_HEIGHT),
+            ),
+            Color::from_rgba(0.0, 0.0, 0.0, 0.15),
+        );
+        graphics.draw_rectangle(
+            Rectangle::from_tuples(
+                (left + 4.0, bar_top),
+                (left + 4.0 + (width - 8.0) * done, bar_top + BAR_HEIGHT),
+            ),
+            Color::from_rgb(0.2, 0.4, 0.8),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small undamped swing released from rest, so its history starts
+    /// part way through a swing, a quarter of one short of the bottom.
+    fn swinging(steps: usize) -> (Pendulum, Vec<PendulumState>) {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.zeta = Some(0.0);
+        p.angle = 0.2;
+        let mut history = vec![p.snapshot()];
+        for _ in 0..steps {
+            p.update();
+            history.push(p.snapshot());
+        }
+        (p, history)
+    }
+
+    fn crossings(states: &[PendulumState]) -> Vec<(usize, bool)> {
+        states
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| Some((i + 1, crossing(pair[0].angle, pair[1].angle)?)))
+            .collect()
+    }
+
+    #[test]
+    fn the_swing_runs_between_the_last_two_crossings_the_same_way() {
+        // About 126 steps a swing: down through the bottom at about 31,
+        // back up at 94, down again at 157 and up at 220.
+        let (_, history) = swinging(240);
+        let all = crossings(&history);
+        assert_eq!(all.len(), 4);
+        let swing = last_swing(&history).unwrap();
+        // The latest crossing is the upward one, and the swing starts at
+        // the upward one before it, not the downward one in between.
+        let (start, end) = (swing.start, swing.end - 1);
+        assert_eq!((start, true), all[1]);
+        assert_eq!((end, true), all[3]);
+        assert!(history[start - 1].angle < 0.0 && history[start].angle >= 0.0);
+        assert!(history[end - 1].angle < 0.0 && history[end].angle >= 0.0);
+        assert!(
+            ((end - start) as i32 - 126).abs() <= 1,
+            "{} steps",
+            end - start
+        );
+    }
+
+    #[test]
+    fn an_incomplete_first_swing_isnt_counted() {
+        // The release and the fall to the first crossing aren't a swing,
+        // and nor are two crossings opposite ways.
+        let (_, history) = swinging(25);
+        assert!(crossings(&history).is_empty());
+        assert!(last_swing(&history).is_none());
+        let (_, history) = swinging(100);
+        assert_eq!(crossings(&history).len(), 2);
+        assert!(last_swing(&history).is_none());
+        // Once it comes down through the bottom again, the swing is from
+        // the first crossing on, not from the release.
+        let (_, history) = swinging(160);
+        let swing = last_swing(&history).unwrap();
+        assert_eq!(swing.start, crossings(&history)[0].0);
+        assert!(swing.start > 25);
+        assert!(last_swing(&[]).is_none());
+    }
+
+    #[test]
+    fn going_over_the_top_isnt_passing_the_bottom() {
+        let base = Pendulum::new(400.0, 0.0, 200.0).snapshot();
+        let at = |angles: &[f32]| -> Vec<PendulumState> {
+            angles
+                .iter()
+                .map(|&angle| PendulumState { angle, ..base })
+                .collect()
+        };
+        assert_eq!(crossing(3.0, -3.0), None);
+        assert_eq!(crossing(0.1, -0.1), Some(false));
+        assert_eq!(crossing(-0.1, 0.1), Some(true));
+        assert_eq!(crossing(0.0, 0.0), None);
+        // Round and round over the top, only ever through the bottom one
+        // way at a time.
+        let spinning = at(&[2.0, 3.0, -3.0, -2.0, -0.5, 0.5, 2.0, 3.0, -3.0, -0.5, 0.5]);
+        assert_eq!(last_swing(&spinning), Some(5..11));
+        assert!(last_swing(&at(&[2.0, 3.0, -3.0, -2.0, -0.5, 0.5])).is_none());
+        // Between recorded steps, the replay goes the short way round.
+        assert!((lerp_angle(3.0, -3.0, 0.5).abs() - PI).abs() < 1e-5);
+        assert!((lerp_angle(0.2, -0.2, 0.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn the_replay_plays_slowly_and_loops_or_holds() {
+        let mut replay = InstantReplay::new();
+        let (p, history) = swinging(90);
+        assert_eq!(
+            replay.start(&p, &history).unwrap_err(),
+            "No complete swing to replay yet"
+        );
+        assert!(!replay.is_active());
+
+        let (p, history) = swinging(240);
+        let message = replay.start(&p, &history).unwrap();
+        assert!(
+            message.starts_with("Replaying the last swing (2.1"),
+            "{}",
+            message
+        );
+        assert!(message.ends_with("at 0.25x"), "{}", message);
+        assert!(replay.is_active());
+        let swing = last_swing(&history).unwrap();
+        assert_eq!(replay.states.len(), swing.len());
+        assert!((replay.duration() - 126.0 / 60.0).abs() < 0.05);
+
+        // Four frames a recorded step, in between on the way.
+        let first = replay.states[0];
+        let second = replay.states[1];
+        replay.step(false);
+        replay.step(false);
+        let between = replay.current().unwrap();
+        assert!((between.angle - 0.5 * (first.angle + second.angle)).abs() < 1e-6);
+        assert!((between.time - first.time - 0.5).abs() < 1e-4);
+        // Paused, it holds still.
+        replay.step(true);
+        assert_eq!(replay.cursor, 0.5);
+        replay.step(false);
+        replay.step(false);
+        assert_eq!(replay.current().unwrap().angle, second.angle);
+
+        // Playing once, it holds at the end.
+        for _ in 0..1000 {
+            replay.step(false);
+        }
+        assert_eq!(replay.cursor, replay.end());
+        let last = *replay.states.last().unwrap();
+        assert!(replay.current().unwrap().angle == last.angle);
+
+        // Looping, it goes round again.
+        assert_eq!(
+            replay.command(&["loop"]).unwrap(),
+            vec!["Instant replay showing, looping".to_string()]
+        );
+        replay.step(false);
+        assert!(replay.cursor < 1.0);
+        let message = replay.start(&p, &history).unwrap();
+        assert!(message.ends_with(", looping"), "{}", message);
+        for _ in 0..4 * swing.len() + 2 {
+            replay.step(false);
+            assert!(replay.cursor <= replay.end());
+        }
+
+        assert!(replay.command(&["sideways"]).is_err());
+        assert_eq!(
+            replay.command(&["off"]).unwrap(),
+            vec!["Instant replay not showing, looping".to_string()]
+        );
+        assert!(!replay.is_active() && replay.current().is_none());
+        replay.step(false);
+    }
+}
@@ -0,0 +1,458 @@
+//! Integration schemes for a single swinging degree of freedom, behind one
+//! trait so they can be swapped, compared and added to. These are what the
+//! accuracy dashboard races against each other, and a pendulum can be set
+//! to step with any of the built-in ones in place of its own; see `Scheme`.
+
+use std::cell::Cell;
+
+use crate::double::{A, B4, B5};
+use crate::math;
+
+/// Angle and angular velocity at a moment of simulated time, in steps.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct State {
+    pub angle: f32,
+    pub angular_velocity: f32,
+    pub time: f32,
+}
+
+/// Rates of change of a `State`, per step.
+#[derive(Copy, Clone)]
+pub struct Derivatives {
+    pub angle: f32,
+    pub angular_velocity: f32,
+}
+
+/// A scheme that advances a state by `dt` given its equation of motion.
+pub trait Integrator {
+    fn name(&self) -> &str;
+
+    /// Order of the global error, what halving `dt` should show.
+    fn order(&self) -> u32;
+
+    fn step(&self, state: &State, dt: f32, dynamics: &dyn Fn(&State) -> Derivatives) -> State;
+}
+
+/// `state + dt * rate`, moving time along with it.
+fn advance(state: &State, dt: f32, rate: &Derivatives) -> State {
+    State {
+        angle: state.angle + dt * rate.angle,
+        angular_velocity: state.angular_velocity + dt * rate.angular_velocity,
+        time: state.time + dt,
+    }
+}
+
+/// Forward Euler: the angle and the velocity both from the rates at the
+/// start of the step. The swing gains energy every step, so it's here to
+/// show what the others avoid.
+pub struct ExplicitEuler;
+
+impl Integrator for ExplicitEuler {
+    fn name(&self) -> &str {
+        "Euler"
+    }
+
+    fn order(&self) -> u32 {
+        1
+    }
+
+    fn step(&self, state: &State, dt: f32, dynamics: &dyn Fn(&State) -> Derivatives) -> State {
+        advance(state, dt, &dynamics(state))
+    }
+}
+
+/// Semi-implicit Euler, the scheme the interactive pendulum steps with:
+/// the velocity first, then the angle from the new velocity.
+pub struct SymplecticEuler;
+
+impl Integrator for SymplecticEuler {
+    fn name(&self) -> &str {
+        "Symplectic Euler"
+    }
+
+    fn order(&self) -> u32 {
+        1
+    }
+
+    fn step(&self, state: &State, dt: f32, dynamics: &dyn Fn(&State) -> Derivatives) -> State {
+        let angular_velocity = state.angular_velocity + dt * dynamics(state).angular_velocity;
+        State {
+            angle: state.angle + dt * angular_velocity,
+            angular_velocity,
+            time: state.time + dt,
+        }
+    }
+}
+
+/// Velocity Verlet. Damping makes the acceleration depend on the velocity,
+/// so the end-of-step acceleration uses an Euler guess at the new velocity.
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn name(&self) -> &str {
+        "Velocity Verlet"
+    }
+
+    fn order(&self) -> u32 {
+        2
+    }
+
+    fn step(&self, state: &State, dt: f32, dynamics: &dyn Fn(&State) -> Derivatives) -> State {
+        let start = dynamics(state).angular_velocity;
+        let angle = state.angle + dt * state.angular_velocity + 0.5 * dt * dt * start;
+        let guess = State {
+            angle,
+            angular_velocity: state.angular_velocity + dt * start,
+            time: state.time + dt,
+        };
+        let end = dynamics(&guess).angular_velocity;
+        State {
+            angular_velocity: state.angular_velocity + 0.5 * dt * (start + end),
+            ..guess
+        }
+    }
+}
+
+/// The classic fourth-order Runge–Kutta method.
+pub struct Rk4;
+
+impl Integrator for Rk4 {
+    fn name(&self) -> &str {
+        "RK4"
+    }
+
+    fn order(&self) -> u32 {
+        4
+    }
+
+    fn step(&self, state: &State, dt: f32, dynamics: &dyn Fn(&State) -> Derivatives) -> State {
+        let k1 = dynamics(state);
+        let k2 = dynamics(&advance(state, 0.5 * dt, &k1));
+        let k3 = dynamics(&advance(state, 0.5 * dt, &k2));
+        let k4 = dynamics(&advance(state, dt, &k3));
+        let weighted =
+            |f: fn(&Derivatives) -> f32| (f(&k1) + 2.0 * f(&k2) + 2.0 * f(&k3) + f(&k4)) / 6.0;
+        advance(
+            state,
+            dt,
+            &Derivatives {
+                angle: weighted(|k| k.angle),
+                angular_velocity: weighted(|k| k.angular_velocity),
+            },
+        )
+    }
+}
+
+/// Stage times of the Dormand–Prince tableau, which the driven pendulum
+/// needs as its equation depends on time.
+const C: [f32; 7] = [0.0, 0.2, 0.3, 0.8, 8.0 / 9.0, 1.0, 1.0];
+
+/// Adaptive Dormand–Prince 5(4), the double pendulum's method written
+/// against the trait as an example of a scheme with its own state: it
+/// covers each `dt` in as many substeps as keep the estimated error under
+/// `tolerance`, and remembers the substep size between calls.
+#[derive(Clone)]
+pub struct Rk45 {
+    pub tolerance: f32,
+    /// Substep to try next.
+    substep: Cell<f32>,
+}
+
+impl Rk45 {
+    pub fn new(tolerance: f32) -> Rk45 {
+        Rk45 {
+            tolerance,
+            substep: Cell::new(f32::INFINITY),
+        }
+    }
+
+    /// The substep it will try next, infinite before the first.
+    pub fn substep(&self) -> f32 {
+        self.substep.get()
+    }
+
+    pub fn set_substep(&self, substep: f32) {
+        self.substep.set(substep);
+    }
+
+    /// One substep of size `dt`: the fifth-order result and the largest
+    /// difference from the embedded fourth-order one.
+    fn try_step(
+        &self,
+        state: &State,
+        dt: f32,
+        dynamics: &dyn Fn(&State) -> Derivatives,
+    ) -> (State, f32) {
+        let mut k: [Derivatives; 7] = [Derivatives {
+            angle: 0.0,
+            angular_velocity: 0.0,
+        }; 7];
+        for stage in 0..7 {
+            let (mut angle, mut angular_velocity) = (state.angle, state.angular_velocity);
+            for (j, kj) in k.iter().enumerate().take(stage) {
+                angle += dt * A[stage][j] as f32 * kj.angle;
+                angular_velocity += dt * A[stage][j] as f32 * kj.angular_velocity;
+            }
+            k[stage] = dynamics(&State {
+                angle,
+                angular_velocity,
+                time: state.time + C[stage] * dt,
+            });
+        }
+
+        let combine = |weights: &[f64; 7], f: fn(&Derivatives) -> f32| {
+            k.iter()
+                .zip(weights)
+                .map(|(k, &w)| w as f32 * f(k))
+                .sum::<f32>()
+        };
+        let (angle5, velocity5) = (
+            combine(&B5, |k| k.angle),
+            combine(&B5, |k| k.angular_velocity),
+        );
+        let (angle4, velocity4) = (
+            combine(&B4, |k| k.angle),
+            combine(&B4, |k| k.angular_velocity),
+        );
+        let error = (dt * (angle5 - angle4))
+            .abs()
+            .max((dt * (velocity5 - velocity4)).abs());
+
+        (
+            advance(
+                state,
+                dt,
+                &Derivatives {
+                    angle: angle5,
+                    angular_velocity: velocity5,
+                },
+            ),
+            error,
+        )
+    }
+}
+
+impl Integrator for Rk45 {
+    fn name(&self) -> &str {
+        "RK45 adaptive"
+    }
+
+    fn order(&self) -> u32 {
+        5
+    }
+
+    fn step(&self, state: &State, dt: f32, dynamics: &dyn Fn(&State) -> Derivatives) -> State {
+        let mut state = *state;
+        let mut remaining = dt;
+        while remaining > 0.0 {
+            let planned = self.substep.get().min(dt);
+            let substep = planned.min(remaining);
+            let (next, error) = self.try_step(&state, substep, dynamics);
+
+            // Resize from how far under or over the tolerance it came, within
+            // limits, and never below a thousandth of the step so a
+            // tolerance that can't be met still makes progress. An error
+            // that isn't a number, from a state that has blown up, counts
+            // as far over: it shrinks to the floor and is taken there.
+            let factor = if !error.is_finite() {
+                0.2
+            } else if error > 0.0 {
+                (0.9 * math::powf(self.tolerance / error, 0.2)).clamp(0.2, 5.0)
+            } else {
+                5.0
+            };
+            let resized = (substep * factor).max(1e-3 * dt);
+
+            if error <= self.tolerance || substep <= 1e-3 * dt {
+                state = next;
+                remaining -= substep;
+                // A substep cut short by the end of the step says nothing
+                // about how big the next one can be.
+                if substep == planned {
+                    self.substep.set(resized);
+                }
+            } else {
+                self.substep.set(resized);
+            }
+        }
+        state
+    }
+}
+
+/// The schemes on offer, built-in ones first. More can be registered.
+pub struct Integrators {
+    pub schemes: Vec<Box<dyn Integrator>>,
+}
+
+impl Integrators {
+    pub fn builtin() -> Integrators {
+        let mut integrators = Integrators {
+            schemes: Vec::new(),
+        };
+        integrators.register(Box::new(ExplicitEuler));
+        integrators.register(Box::new(SymplecticEuler));
+        integrators.register(Box::new(VelocityVerlet));
+        integrators.register(Box::new(Rk4));
+        integrators.register(Box::new(Rk45::new(1e-6)));
+        integrators
+    }
+
+    pub fn register(&mut self, scheme: Box<dyn Integrator>) {
+        self.schemes.push(scheme);
+    }
+}
+
+/// Which of the built-in schemes a pendulum steps with. Symplectic Euler is
+/// the pendulum's own step, which also handles dry friction, a slack string
+/// and the energy ledger's exact split; the others step the smooth swing
+/// through `Integrator`, and hand over to the pendulum's own step for dry
+/// friction and slack strings, which they can't integrate.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Scheme {
+    Euler,
+    SymplecticEuler,
+    VelocityVerlet,
+    Rk4,
+    Rk45,
+}
+
+impl Scheme {
+    const ALL: [Scheme; 5] = [
+        Scheme::Euler,
+        Scheme::SymplecticEuler,
+        Scheme::VelocityVerlet,
+        Scheme::Rk4,
+        Scheme::Rk45,
+    ];
+
+    /// How the scheme is written in a scene or the console.
+    pub fn key(self) -> &'static str {
+        match self {
+            Scheme::Euler => "euler",
+            Scheme::SymplecticEuler => "symplectic_euler",
+            Scheme::VelocityVerlet => "verlet",
+            Scheme::Rk4 => "rk4",
+            Scheme::Rk45 => "rk45",
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Scheme> {
+        Scheme::ALL.into_iter().find(|scheme| scheme.key() == key)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheme::Euler => "Euler",
+            Scheme::SymplecticEuler => "Symplectic Euler",
+            Scheme::VelocityVerlet => "Velocity Verlet",
+            Scheme::Rk4 => "RK4",
+            Scheme::Rk45 => "RK45 adaptive",
+        }
+    }
+
+    pub fn next(self) -> Scheme {
+        let i = Scheme::ALL
+            .iter()
+            .position(|&scheme| scheme == self)
+            .unwrap_or(0);
+        Scheme::ALL[(i + 1) % Scheme::ALL.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The harmonic oscillator, whose swing from rest at 1 is `cos t`.
+    fn oscillator(state: &State) -> Derivatives {
+        Derivatives {
+            angle: state.angular_velocity,
+            angular_velocity: -state.angle,
+        }
+    }
+
+    /// Largest angle error over `duration`, stepping by `dt`.
+    fn error(scheme: &dyn Integrator, dt: f32, duration: f32) -> f64 {
+        let mut state = State {
+            angle: 1.0,
+            angular_velocity: 0.0,
+            time: 0.0,
+        };
+        let mut worst: f64 = 0.0;
+        for _ in 0..(duration / dt).round() as usize {
+            state = scheme.step(&state, dt, &oscillator);
+            worst = worst.max((state.angle as f64 - (state.time as f64).cos()).abs());
+        }
+        worst
+    }
+
+    fn observed_order(scheme: &dyn Integrator, dt: f32, duration: f32) -> f64 {
+        (error(scheme, dt, duration) / error(scheme, dt / 2.0, duration)).log2()
+    }
+
+    #[test]
+    fn each_fixed_step_scheme_converges_at_its_order() {
+        let cases: [(&dyn Integrator, f32, f32); 4] = [
+            (&ExplicitEuler, 0.02, 2.0),
+            (&SymplecticEuler, 0.02, 2.0),
+            (&VelocityVerlet, 0.1, 4.0),
+            (&Rk4, 0.4, 8.0),
+        ];
+        for (scheme, dt, duration) in cases {
+            let order = observed_order(scheme, dt, duration);
+            assert!(
+                (order - scheme.order() as f64).abs() < 0.3,
+                "{} converged at order {:.2}, not {}",
+                scheme.name(),
+                order,
+                scheme.order()
+            );
+        }
+    }
+
+    #[test]
+    fn the_adaptive_scheme_keeps_to_its_tolerance() {
+        let loose = error(&Rk45::new(1e-3), 1.0, 20.0);
+        let tight = error(&Rk45::new(1e-6), 1.0, 20.0);
+        assert!(loose < 2e-2, "loose error {}", loose);
+        assert!(tight < 2e-5, "tight error {}", tight);
+        assert!(tight < loose / 10.0);
+    }
+
+    #[test]
+    fn the_adaptive_substep_shrinks_for_a_tighter_tolerance() {
+        let state = State {
+            angle: 1.0,
+            angular_velocity: 0.0,
+            time: 0.0,
+        };
+        let (loose, tight) = (Rk45::new(1e-3), Rk45::new(1e-7));
+        loose.step(&state, 1.0, &oscillator);
+        tight.step(&state, 1.0, &oscillator);
+        assert!(tight.substep() < loose.substep());
+        assert!(tight.substep() >= 1e-3);
+    }
+
+    #[test]
+    fn a_state_that_blows_up_still_finishes_the_step() {
+        let scheme = Rk45::new(1e-6);
+        let state = State {
+            angle: f32::NAN,
+            angular_velocity: 0.0,
+            time: 0.0,
+        };
+        let next = scheme.step(&state, 1.0, &oscillator);
+        assert_eq!(next.time, 1.0);
+        assert!(scheme.substep().is_finite());
+
+        let infinite = |_: &State| Derivatives {
+            angle: f32::INFINITY,
+            angular_velocity: f32::NEG_INFINITY,
+        };
+        let start = State {
+            angle: 0.0,
+            ..state
+        };
+        assert!(Rk45::new(1e-6).step(&start, 1.0, &infinite).time >= 1.0);
+    }
+}
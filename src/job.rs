@@ -0,0 +1,135 @@
+//! Long work run on a thread of its own, so drawing carries on: the job
+//! reports how far it's got, can be called off, and stops itself once it's
+//! done as much as it was allowed. The window polls it each frame.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+/// What a job can see of how it's getting on, and is stopped through.
+pub struct Progress {
+    done: AtomicUsize,
+    total: usize,
+    /// Units of work the job may spend before it's stopped.
+    budget: usize,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    /// Counts `units` more done. An error once the job's been called off or
+    /// has run past its budget, to be passed straight back.
+    pub fn advance(&self, units: usize) -> Result<(), String> {
+        let done = self.done.fetch_add(units, Ordering::Relaxed) + units;
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err("cancelled".to_string());
+        }
+        if done > self.budget {
+            return Err(format!("ran over its budget of {} steps", self.budget));
+        }
+        Ok(())
+    }
+}
+
+pub struct Job<T> {
+    progress: Arc<Progress>,
+    result: Receiver<Result<T, String>>,
+}
+
+impl<T: Send + 'static> Job<T> {
+    /// Starts `work` on its own thread, expected to take `total` units and
+    /// allowed up to `budget`.
+    pub fn spawn(
+        total: usize,
+        budget: usize,
+        work: impl FnOnce(&Progress) -> Result<T, String> + Send + 'static,
+    ) -> Job<T> {
+        let progress = Arc::new(Progress {
+            done: AtomicUsize::new(0),
+            total,
+            budget,
+            cancelled: AtomicBool::new(false),
+        });
+        let (sender, result) = mpsc::channel();
+        let shared = Arc::clone(&progress);
+        thread::spawn(move || {
+            // The window may have gone by the time it's done.
+            let _ = sender.send(work(&shared));
+        });
+        Job { progress, result }
+    }
+
+    /// How far through, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        let done = self.progress.done.load(Ordering::Relaxed);
+        (done as f32 / self.progress.total.max(1) as f32).min(1.0)
+    }
+
+    /// Asks the job to stop at its next `advance`.
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// The result once the job has finished, and an error if it stopped
+    /// without one.
+    pub fn poll(&self) -> Option<Result<T, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("the job stopped".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn wait<T: Send + 'static>(job: &Job<T>) -> Result<T, String> {
+        loop {
+            if let Some(result) = job.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn a_job_reports_its_progress_and_result() {
+        let job = Job::spawn(10, 100, |progress| {
+            for _ in 0..10 {
+                progress.advance(1)?;
+            }
+            Ok(42)
+        });
+        assert_eq!(wait(&job), Ok(42));
+        assert_eq!(job.fraction(), 1.0);
+        // Once it's been taken, the job has nothing more to give.
+        assert_eq!(job.poll(), Some(Err("the job stopped".to_string())));
+        assert_eq!(Job::spawn(0, 0, |_| Ok(())).fraction(), 0.0);
+    }
+
+    #[test]
+    fn a_job_stops_at_its_budget_or_when_called_off() {
+        let job: Job<()> = Job::spawn(10, 25, |progress| loop {
+            progress.advance(10)?;
+        });
+        assert_eq!(
+            wait(&job),
+            Err("ran over its budget of 25 steps".to_string())
+        );
+        assert_eq!(job.fraction(), 1.0);
+
+        let job: Job<()> = Job::spawn(usize::MAX, usize::MAX, |progress| loop {
+            progress.advance(1)?;
+            thread::sleep(Duration::from_millis(1));
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(job.poll().is_none());
+        let fraction = job.fraction();
+        assert!(fraction > 0.0 && fraction < 1.0);
+        job.cancel();
+        assert_eq!(wait(&job), Err("cancelled".to_string()));
+    }
+}
@@ -0,0 +1,295 @@
+//! Just enough JSON for the files the program writes and reads back: the
+//! results store's records and the input logs of a replay. Numbers are
+//! `f64`s, which is all either of them needs.
+
+use std::str::CharIndices;
+
+/// `text` as a JSON string, quotes and all.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A parsed value: what the program writes, plus whatever else a hand edit
+/// might reasonably leave in a file.
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+pub fn field<'a>(fields: &'a [(String, Json)], key: &str) -> Option<&'a Json> {
+    fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value)
+}
+
+/// Four hex digits after a `\\u`.
+fn hex_escape(chars: &mut CharIndices) -> Result<u32, String> {
+    let digits: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+    match u32::from_str_radix(&digits, 16) {
+        Ok(code) if digits.len() == 4 => Ok(code),
+        _ => Err(format!("invalid escape `\\u{}`", digits)),
+    }
+}
+
+/// The character a `\\u` escape stands for: a character of the basic
+/// plane, or one past it as a UTF-16 surrogate pair, `\\ud83d\\ude00`.
+fn unicode_escape(chars: &mut CharIndices) -> Result<char, String> {
+    let code = match hex_escape(chars)? {
+        high @ 0xd800..=0xdbff => {
+            let low = match (chars.next(), chars.next()) {
+                (Some((_, '\\')), Some((_, 'u'))) => hex_escape(chars)?,
+                _ => return Err(format!("unpaired surrogate `\\u{:04x}`", high)),
+            };
+            if !(0xdc00..=0xdfff).contains(&low) {
+                return Err(format!("unpaired surrogate `\\u{:04x}`", high));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        }
+        code => code,
+    };
+    char::from_u32(code).ok_or(format!("unpaired surrogate `\\u{:04x}`", code))
+}
+
+pub struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(text: &'a str) -> Parser<'a> {
+        Parser { text, position: 0 }
+    }
+
+    /// Parses the whole text as one value.
+    pub fn parse(mut self) -> Result<Json, String> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.position != self.text.len() {
+            return Err(format!("unexpected text at column {}", self.position + 1));
+        }
+        Ok(value)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, token: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len_utf8();
+            Ok(())
+        } else {
+            Err(format!(
+                "expected `{}` at column {}",
+                token,
+                self.position + 1
+            ))
+        }
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: char) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len_utf8();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if rest.starts_with('{') {
+            self.object()
+        } else if rest.starts_with('[') {
+            self.array()
+        } else if rest.starts_with('"') {
+            self.string().map(Json::String)
+        } else if let Some(word) = ["null", "true", "false"]
+            .into_iter()
+            .find(|w| rest.starts_with(*w))
+        {
+            self.position += word.len();
+            Ok(match word {
+                "null" => Json::Null,
+                word => Json::Bool(word == "true"),
+            })
+        } else {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                .unwrap_or(rest.len());
+            let number = rest[..length]
+                .parse()
+                .map_err(|_| format!("expected a value at column {}", self.position + 1))?;
+            self.position += length;
+            Ok(Json::Number(number))
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        if self.eat('}') {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            if self.eat('}') {
+                return Ok(Json::Object(fields));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        if self.eat(']') {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat(']') {
+                return Ok(Json::Array(items));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if !self.rest().starts_with('"') {
+            return Err(format!("expected a string at column {}", self.position + 1));
+        }
+        self.position += 1;
+
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(text);
+                }
+                '\\' => match chars.next() {
+                    Some((_, '"')) => text.push('"'),
+                    Some((_, '\\')) => text.push('\\'),
+                    Some((_, '/')) => text.push('/'),
+                    Some((_, 'b')) => text.push('\u{8}'),
+                    Some((_, 'f')) => text.push('\u{c}'),
+                    Some((_, 'n')) => text.push('\n'),
+                    Some((_, 'r')) => text.push('\r'),
+                    Some((_, 't')) => text.push('\t'),
+                    Some((_, 'u')) => text.push(unicode_escape(&mut chars)?),
+                    Some((j, c)) => {
+                        return Err(format!(
+                            "invalid escape `\\{}` at column {}",
+                            c,
+                            self.position + j
+                        ))
+                    }
+                    None => break,
+                },
+                c => text.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(json: &str) -> Result<String, String> {
+        match Parser::new(json).parse()? {
+            Json::String(text) => Ok(text),
+            _ => Err("not a string".to_string()),
+        }
+    }
+
+    #[test]
+    fn every_escape_decodes_to_its_character() {
+        assert_eq!(
+            string(r#""\" \\ \/ \b \f \n \r \t""#).unwrap(),
+            "\" \\ / \u{8} \u{c} \n \r \t"
+        );
+        assert_eq!(string(r#""éé""#).unwrap(), "éé");
+        assert_eq!(string(r#""😀 😀""#).unwrap(), "😀 😀");
+    }
+
+    #[test]
+    fn broken_escapes_are_refused() {
+        for json in [
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\ud83d""#,
+            r#""\ud83d x""#,
+            r#""\ud83dA""#,
+            r#""\ude00""#,
+            r#""\"#,
+        ] {
+            assert!(string(json).is_err(), "{} was read", json);
+        }
+    }
+
+    #[test]
+    fn quoted_text_reads_back_as_it_was() {
+        let text = "tabs\t, returns\r\n, \"quotes\", back\\slashes, \u{1} and \u{7f}, é and 😀";
+        assert_eq!(string(&quote(text)).unwrap(), text);
+    }
+
+    #[test]
+    fn values_nest() {
+        let Json::Object(fields) =
+            Parser::new(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "d"}} "#)
+                .parse()
+                .unwrap()
+        else {
+            panic!("not an object");
+        };
+        let Some(Json::Array(items)) = field(&fields, "a") else {
+            panic!("no array");
+        };
+        assert!(matches!(
+            items[..],
+            [
+                Json::Number(1.0),
+                Json::Number(-25.0),
+                Json::Bool(true),
+                Json::Null
+            ]
+        ));
+        let Some(Json::Object(inner)) = field(&fields, "b") else {
+            panic!("no object");
+        };
+        assert!(matches!(field(inner, "c"), Some(Json::String(d)) if d == "d"));
+        assert!(Parser::new("[1,]").parse().is_err());
+        assert!(Parser::new("{} x").parse().is_err());
+    }
+}
@@ -0,0 +1,574 @@
+//! The start menu: a grid of tiles, one for each thing to start, each with a
+//! miniature of its scene swinging in it. The presets come first, then the
+//! scenes saved lately and the apparatus profiles, and, when the menu was
+//! opened from a running scene, a tile to go back to it, first of all.
+//!
+//! The menu opens on starting without a scene, in place of the demo reel,
+//! unless `--no-menu` is given or `[menu]` says `enabled = false`. `Esc`
+//! opens it from anywhere; the scene that was running waits, paused, behind
+//! it. The arrows or the mouse pick a tile, and `Enter` or a click starts
+//! it. `Esc` in the menu goes back to the scene it was opened from.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::window::VirtualKeyCode;
+use speedy2d::Graphics2D;
+
+use crate::apparatus::Profiles;
+use crate::config::{key_value, Config, Section};
+use crate::framing;
+use crate::presets::PRESETS;
+use crate::scene::Scene;
+use crate::units::Units;
+use crate::viewport::Viewport;
+
+const COLUMNS: usize = 4;
+const MARGIN: f32 = 16.0;
+/// Room above the tiles for the heading.
+const HEADER: f32 = 56.0;
+/// Room at the foot of each tile for its title.
+const TITLE_HEIGHT: f32 = 24.0;
+/// Most profiles given a tile, so the grid keeps to a few rows.
+const MAX_PROFILES: usize = 4;
+/// How long a miniature may take to step each frame. One that takes longer
+/// sits out the frames it overran by, so a heavy scene slows its own tile
+/// rather than the menu.
+const TILE_BUDGET: Duration = Duration::from_micros(250);
+/// Most frames a slow miniature sits out at a time.
+const MAX_SKIP: u32 = 30;
+
+/// What a tile starts.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Choice {
+    /// Back to the scene the menu was opened from.
+    Resume,
+    Preset(usize),
+    /// A saved session.
+    Saved(PathBuf),
+    /// A single pendulum set up as the apparatus profile of that id.
+    Apparatus(String),
+}
+
+struct Tile {
+    title: String,
+    choice: Choice,
+    /// The miniature, built from the same entries the tile starts. The
+    /// resume tile has none: it shows the waiting scene, standing still.
+    preview: Option<Scene>,
+    /// Frames left to sit out after overrunning the budget.
+    skip: u32,
+}
+
+pub struct Launcher {
+    tiles: Vec<Tile>,
+    selected: usize,
+    size: (f32, f32),
+}
+
+/// Reads `enabled = true|false` from a `[menu]` config section.
+pub fn enabled_from_config(section: &Section) -> Result<bool, String> {
+    let mut enabled = true;
+    for (line_number, line) in &section.lines {
+        match key_value(line) {
+            Some(("enabled", value)) => {
+                enabled = value.parse::<bool>().map_err(|_| {
+                    format!(
+                        "line {}: expected true or false for `enabled`, found `{}`",
+                        line_number, value
+                    )
+                })?
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: unknown menu setting `{}`",
+                    line_number, line
+                ))
+            }
+        }
+    }
+    Ok(enabled)
+}
+
+impl Launcher {
+    /// A menu over a window of `size` pixels, with a resume tile if
+    /// `resume`, the sessions saved in those of `saves` that read, newest
+    /// first, and the first few profiles, set up at `units`' scale.
+    pub fn new(
+        size: (f32, f32),
+        resume: bool,
+        saves: &[&Path],
+        profiles: &Profiles,
+        units: &Units,
+    ) -> Launcher {
+        let mut tiles = Vec::new();
+        if resume {
+            tiles.push(Tile {
+                title: "Back to the scene".to_string(),
+                choice: Choice::Resume,
+                preview: None,
+                skip: 0,
+            });
+        }
+        for (i, preset) in PRESETS.iter().enumerate() {
+            tiles.push(Tile {
+                title: preset.name.to_string(),
+                choice: Choice::Preset(i),
+                preview: Some(preset.scene()),
+                skip: 0,
+            });
+        }
+
+        let mut saved: Vec<_> = saves
+            .iter()
+            .filter_map(|path| {
+                let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+                let scene = Config::load(path)
+                    .and_then(|config| Scene::from_config(&config))
+                    .ok()?;
+                Some((modified, path.to_path_buf(), scene))
+            })
+            .collect();
+        saved.sort_by_key(|&(modified, _, _)| std::cmp::Reverse(modified));
+        for (_, path, scene) in saved {
+            tiles.push(Tile {
+                title: format!("Saved: {}", path.display()),
+                choice: Choice::Saved(path),
+                preview: Some(scene),
+                skip: 0,
+            });
+        }
+
+        for profile in profiles.profiles.iter().take(MAX_PROFILES) {
+            let mut scene = Scene::single();
+            if let Some(p) = scene.selected_pendulum_mut() {
+                // The menu's copy of the units, which the profile may set a
+                // scale on. Without a scale to draw it at, it's shown as it
+                // is.
+                let mut units = *units;
+                let _ = profile.apply(p, &mut units);
+            }
+            tiles.push(Tile {
+                title: format!("Apparatus: {}", profile.id),
+                choice: Choice::Apparatus(profile.id.clone()),
+                preview: Some(scene),
+                skip: 0,
+            });
+        }
+
+        Launcher {
+            tiles,
+            selected: 0,
+            size,
+        }
+    }
+
+    pub fn resize(&mut self, size: (f32, f32)) {
+        self.size = size;
+    }
+
+    fn rows(&self) -> usize {
+        self.tiles.len().div_ceil(COLUMNS)
+    }
+
+    /// Where tile `i` is, as `(left, top, width, height)`.
+    fn rect(&self, i: usize) -> (f32, f32, f32, f32) {
+        let columns = COLUMNS as f32;
+        let rows = self.rows().max(1) as f32;
+        let width = (self.size.0 - MARGIN * (columns + 1.0)) / columns;
+        let height = (self.size.1 - HEADER - MARGIN * (rows + 1.0)) / rows;
+        let (column, row) = ((i % COLUMNS) as f32, (i / COLUMNS) as f32);
+        (
+            MARGIN + column * (width + MARGIN),
+            HEADER + MARGIN + row * (height + MARGIN),
+            width,
+            height,
+        )
+    }
+
+    fn tile_at(&self, x: f32, y: f32) -> Option<usize> {
+        (0..self.tiles.len()).find(|&i| {
+            let (left, top, width, height) = self.rect(i);
+            (left..left + width).contains(&x) && (top..top + height).contains(&y)
+        })
+    }
+
+    /// Moves the selection for an arrow, across a row or down a column and
+    /// stopping at the edges, or picks the selected tile for `Enter`. `Esc`
+    /// goes back if there's a scene to go back to.
+    pub fn key(&mut self, key: VirtualKeyCode) -> Option<Choice> {
+        let last = self.tiles.len().checked_sub(1)?;
+        let column = self.selected % COLUMNS;
+        match key {
+            VirtualKeyCode::Left if column > 0 => self.selected -= 1,
+            VirtualKeyCode::Right if column + 1 < COLUMNS && self.selected < last => {
+                self.selected += 1
+            }
+            VirtualKeyCode::Up if self.selected >= COLUMNS => self.selected -= COLUMNS,
+            // Down from over the short last row lands on its last tile.
+            VirtualKeyCode::Down if self.selected / COLUMNS + 1 < self.rows() => {
+                self.selected = (self.selected + COLUMNS).min(last)
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                return Some(self.tiles[self.selected].choice.clone())
+            }
+            VirtualKeyCode::Escape => {
+                return self
+                    .tiles
+                    .iter()
+                    .any(|tile| tile.choice == Choice::Resume)
+                    .then_some(Choice::Resume)
+            }
+            _ => (),
+        }
+        None
+    }
+
+    /// Selects the tile under the mouse.
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        if let Some(i) = self.tile_at(x, y) {
+            self.selected = i;
+        }
+    }
+
+    pub fn click(&self, x: f32, y: f32) -> Option<Choice> {
+        Some(self.tiles[self.tile_at(x, y)?].choice.clone())
+    }
+
+    /// Steps every miniature that isn't sitting out.
+    pub fn step(&mut self) {
+        for tile in &mut self.tiles {
+            let Some(scene) = &mut tile.preview else {
+                continue;
+            };
+            if tile.skip > 0 {
+                tile.skip -= 1;
+                continue;
+            }
+            let started = Instant::now();
+            scene.step();
+            let spent = started.elapsed();
+            if spent > TILE_BUDGET {
+                tile.skip =
+                    ((spent.as_secs_f32() / TILE_BUDGET.as_secs_f32()) as u32).min(MAX_SKIP);
+            }
+        }
+    }
+
+    /// Draws the menu over the window, `waiting` being the scene the resume
+    /// tile shows.
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, view: &Viewport, waiting: &Scene) {
+        graphics.draw_text(
+            (MARGIN, 12.0),
+            Color::BLACK,
+            &font.layout_text("Pendulum", 36.0, TextOptions::new()),
+        );
+        let hint = font.layout_text(
+            "Arrows or the mouse to pick, Enter or click to start, Esc from anywhere to come back",
+            18.0,
+            TextOptions::new(),
+        );
+        graphics.draw_text(
+            (self.size.0 - MARGIN - hint.width(), 24.0),
+            Color::DARK_GRAY,
+            &hint,
+        );
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let (left, top, width, height) = self.rect(i);
+            let shade = if i == self.selected { 1.0 } else { 0.92 };
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((left, top), (left + width, top + height)),
+                Color::from_rgb(shade, shade, 1.0),
+            );
+            if i == self.selected {
+                let corners = [
+                    (left, top),
+                    (left + width, top),
+                    (left + width, top + height),
+                    (left, top + height),
+                ];
+                for side in 0..4 {
+                    graphics.draw_line(
+                        corners[side],
+                        corners[(side + 1) % 4],
+                        3.0,
+                        Color::from_rgb(0.95, 0.55, 0.0),
+                    );
+                }
+            }
+
+            let scene = tile.preview.as_ref().unwrap_or(waiting);
+            let picture = (left, top, width, height - TITLE_HEIGHT);
+            graphics.set_clip(Some(Rectangle::from_tuples(
+                (left as i32, top as i32),
+                ((left + width) as i32, (top + picture.3) as i32),
+            )));
+            let miniature = miniature_view(view, scene, picture);
+            for model in &scene.models {
+                model.draw(graphics, &miniature);
+            }
+            graphics.set_clip(None);
+
+            let title = font.layout_text(&tile.title, 18.0, TextOptions::new());
+            graphics.draw_text(
+                (
+                    left + (width - title.width()).max(0.0) / 2.0,
+                    top + height - TITLE_HEIGHT + 2.0,
+                ),
+                Color::BLACK,
+                &title,
+            );
+        }
+    }
+}
+
+/// A view fitting the whole of `scene`'s reach into `rect`.
+fn miniature_view(view: &Viewport, scene: &Scene, rect: (f32, f32, f32, f32)) -> Viewport {
+    let bounds = framing::union(scene.models.iter().map(|model| model.reach()));
+    let (centre, half) = match bounds {
+        Some(bounds) if bounds.centre().x.is_finite() && bounds.centre().y.is_finite() => {
+            let size = (bounds.max.x - bounds.min.x).max(bounds.max.y - bounds.min.y);
+            (bounds.centre(), 0.55 * size)
+        }
+        _ if scene.models.is_empty() => return *view,
+        _ => (scene.selected().pivot(), 0.0),
+    };
+    let half = if half.is_finite() {
+        half.max(50.0)
+    } else {
+        250.0
+    };
+    view.inset(centre, half, rect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::SystemTime;
+
+    use VirtualKeyCode::{Down, Escape, Left, NumpadEnter, Return, Right, Up};
+
+    fn no_profiles() -> Profiles {
+        Profiles::open(&std::env::temp_dir().join("pendulum-launcher-no-such-profiles"))
+    }
+
+    fn menu(resume: bool) -> Launcher {
+        Launcher::new(
+            (800.0, 600.0),
+            resume,
+            &[],
+            &no_profiles(),
+            &Units::default(),
+        )
+    }
+
+    /// Presses `keys` in turn, and where the selection is after each.
+    fn walk(launcher: &mut Launcher, keys: &[VirtualKeyCode]) -> Vec<usize> {
+        keys.iter()
+            .map(|&key| {
+                assert_eq!(launcher.key(key), None);
+                launcher.selected
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_arrows_move_across_and_down_and_stop_at_the_edges() {
+        // Seven presets: a row of four and a short row of three under it.
+        let mut launcher = menu(false);
+        assert_eq!(launcher.tiles.len(), 7);
+        assert_eq!(launcher.rows(), 2);
+        assert_eq!(
+            walk(
+                &mut launcher,
+                &[Left, Up, Right, Right, Right, Right, Down, Down]
+            ),
+            [0, 0, 1, 2, 3, 3, 6, 6]
+        );
+        // The last tile has nothing to its right.
+        assert_eq!(
+            walk(&mut launcher, &[Right, Left, Left, Left, Left, Up]),
+            [6, 5, 4, 4, 4, 0]
+        );
+        assert_eq!(walk(&mut launcher, &[Down, Right, Up]), [4, 5, 1]);
+
+        // With the resume tile both rows are full, and down goes straight
+        // down.
+        let mut launcher = menu(true);
+        assert_eq!(launcher.tiles.len(), 8);
+        assert_eq!(
+            walk(&mut launcher, &[Right, Right, Right, Down, Down, Right]),
+            [1, 2, 3, 7, 7, 7]
+        );
+    }
+
+    #[test]
+    fn enter_starts_the_selected_tile_and_escape_goes_back() {
+        let mut launcher = menu(false);
+        assert_eq!(launcher.key(Return), Some(Choice::Preset(0)));
+        launcher.key(Right);
+        launcher.key(Down);
+        assert_eq!(launcher.key(NumpadEnter), Some(Choice::Preset(5)));
+        // With nothing to go back to, escape stays in the menu.
+        assert_eq!(launcher.key(Escape), None);
+        assert_eq!(launcher.key(VirtualKeyCode::Space), None);
+        assert_eq!(launcher.selected, 5);
+
+        let mut launcher = menu(true);
+        assert_eq!(launcher.key(Return), Some(Choice::Resume));
+        launcher.key(Right);
+        assert_eq!(launcher.key(Return), Some(Choice::Preset(0)));
+        assert_eq!(launcher.key(Escape), Some(Choice::Resume));
+
+        launcher.tiles.clear();
+        assert_eq!(launcher.key(Return), None);
+        assert_eq!(launcher.key(Right), None);
+    }
+
+    #[test]
+    fn the_mouse_selects_and_clicks_the_tile_under_it() {
+        let mut launcher = menu(true);
+        for i in 0..launcher.tiles.len() {
+            let (left, top, width, height) = launcher.rect(i);
+            let (x, y) = (left + width / 2.0, top + height / 2.0);
+            launcher.mouse_move(x, y);
+            assert_eq!(launcher.selected, i);
+            assert_eq!(launcher.click(x, y), Some(launcher.tiles[i].choice.clone()));
+            // The arrows carry on from where the mouse left it.
+            assert_eq!(launcher.key(Return), launcher.click(x, y));
+        }
+        // Between the tiles and over the heading there's nothing to pick.
+        let (left, top, _, _) = launcher.rect(1);
+        for (x, y) in [
+            (left - MARGIN / 2.0, top + 10.0),
+            (left + 10.0, HEADER / 2.0),
+        ] {
+            launcher.mouse_move(x, y);
+            assert_eq!(launcher.click(x, y), None);
+        }
+        assert_eq!(launcher.selected, launcher.tiles.len() - 1);
+
+        // The grid fills the window it's given.
+        launcher.resize((1600.0, 1200.0));
+        let (left, top, width, height) = launcher.rect(7);
+        assert!((left + width + MARGIN - 1600.0).abs() < 1e-3);
+        assert!((top + height + MARGIN - 1200.0).abs() < 1e-3);
+        assert_eq!(
+            launcher.click(left + 1.0, top + 1.0),
+            Some(Choice::Preset(6))
+        );
+    }
+
+    #[test]
+    fn saved_scenes_come_newest_first_and_then_the_profiles() {
+        let directory =
+            std::env::temp_dir().join(format!("pendulum-launcher-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let (older, newer, broken, missing) = (
+            directory.join("older.cfg"),
+            directory.join("newer.cfg"),
+            directory.join("broken.cfg"),
+            directory.join("missing.cfg"),
+        );
+        fs::write(&older, "[scene]\npendulum r=150\n").unwrap();
+        fs::write(&newer, "[scene]\npendulum r=180\npendulum r=120 x=500\n").unwrap();
+        fs::write(&broken, "[scene]\nrocket\n").unwrap();
+        let day_ago = SystemTime::now() - Duration::from_secs(86_400);
+        fs::File::options()
+            .write(true)
+            .open(&older)
+            .unwrap()
+            .set_modified(day_ago)
+            .unwrap();
+
+        let mut profiles = no_profiles();
+        profiles.profiles = crate::apparatus::parse(
+            "[apparatus a]\nlength = 1 m\n[apparatus b]\nlength = 2 m\n\
+             [apparatus c]\nlength = 0.5 m\n[apparatus d]\nlength = 1 m\n\
+             [apparatus e]\nlength = 1 m\n",
+        )
+        .unwrap();
+        let saves: Vec<&Path> = vec![&older, &broken, &missing, &newer];
+        let units = Units::per_metre(100.0).unwrap();
+        let launcher = Launcher::new((800.0, 600.0), true, &saves, &profiles, &units);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let choices: Vec<Choice> = launcher
+            .tiles
+            .iter()
+            .map(|tile| tile.choice.clone())
+            .collect();
+        let mut expected = vec![Choice::Resume];
+        expected.extend((0..PRESETS.len()).map(Choice::Preset));
+        expected.push(Choice::Saved(newer.clone()));
+        expected.push(Choice::Saved(older.clone()));
+        // Only the first few profiles, to keep to a few rows.
+        expected.extend(["a", "b", "c", "d"].map(|id| Choice::Apparatus(id.to_string())));
+        assert_eq!(choices, expected);
+
+        let titles: Vec<&str> = launcher
+            .tiles
+            .iter()
+            .map(|tile| tile.title.as_str())
+            .collect();
+        assert_eq!(titles[0], "Back to the scene");
+        assert_eq!(titles[1], PRESETS[0].name);
+        assert_eq!(titles[8], format!("Saved: {}", newer.display()));
+        assert_eq!(titles[10], "Apparatus: a");
+        // Each tile shows what it starts, a profile at the menu's scale,
+        // but the resume tile shows the scene waiting behind the menu.
+        assert!(launcher.tiles[0].preview.is_none());
+        assert_eq!(launcher.tiles[8].preview.as_ref().unwrap().models.len(), 2);
+        let long = launcher.tiles[11].preview.as_ref().unwrap();
+        let short = launcher.tiles[12].preview.as_ref().unwrap();
+        let (long, short) = (
+            long.selected_pendulum().unwrap().r,
+            short.selected_pendulum().unwrap().r,
+        );
+        assert!((long - 4.0 * short).abs() < 1e-3, "{} and {}", long, short);
+    }
+
+    #[test]
+    fn a_miniature_sitting_out_holds_still() {
+        let mut launcher = menu(false);
+        let time = |launcher: &Launcher, i: usize| {
+            let scene = launcher.tiles[i].preview.as_ref().unwrap();
+            scene.selected_pendulum().unwrap().time
+        };
+        launcher.tiles[0].skip = 2;
+        let before = (time(&launcher, 0), time(&launcher, 2));
+        launcher.step();
+        launcher.step();
+        assert_eq!(time(&launcher, 0), before.0);
+        assert_eq!(launcher.tiles[0].skip, 0);
+        assert!(time(&launcher, 2) > before.1);
+        launcher.tiles[0].skip = 0;
+        launcher.step();
+        assert!(time(&launcher, 0) > before.0);
+        assert!(launcher.tiles[0].skip <= MAX_SKIP);
+    }
+
+    #[test]
+    fn reads_the_section() {
+        let enabled = |text: &str| {
+            let config = Config::parse(text).unwrap();
+            enabled_from_config(config.section("menu").unwrap())
+        };
+        assert_eq!(enabled("[menu]\nenabled = false\n"), Ok(false));
+        assert_eq!(enabled("[menu]\nenabled = true\n"), Ok(true));
+        assert_eq!(
+            enabled("[menu]\nenabled = sometimes\n"),
+            Err("line 2: expected true or false for `enabled`, found `sometimes`".to_string())
+        );
+        assert_eq!(
+            enabled("[menu]\ncolumns = 3\n"),
+            Err("line 2: unknown menu setting `columns = 3`".to_string())
+        );
+    }
+}
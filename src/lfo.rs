@@ -0,0 +1,719 @@
+//! Low-frequency oscillators: slow waves added to a pendulum parameter, so
+//! gravity can breathe in and out or the length wander on its own. Each
+//! oscillator moves one parameter of every pendulum in the scene around
+//! its own base value, in the units the `set` command takes, by up to its
+//! depth either way:
+//!
+//! ```text
+//! [lfo]
+//! g sine 0.1hz depth 0.2
+//! r sample_and_hold 0.5hz depth 20 seed 7
+//! ```
+//!
+//! or `lfo add g sine 0.1hz depth 0.2` at the console. The waves run on a
+//! pendulum's own clock, so they hold still while paused, speed up with its
+//! rate and go back with the history. What they set is stepped with, so
+//! the sine is worked with the physics' own functions.
+//!
+//! The base is whatever the parameter would be without the wave. A value
+//! set some other way, by a key, `set` or scrubbing back, becomes the new
+//! value the wave is laid on top of rather than being written over, and a
+//! saved scene holds the modulated values at its time, which the base is
+//! worked back out from when it's loaded.
+
+use std::f32::consts::TAU;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::Graphics2D;
+
+use crate::config::Section;
+use crate::math;
+use crate::pendulum::Pendulum;
+use crate::presets;
+use crate::random::Rng;
+use crate::scene::Scene;
+use crate::STEPS_PER_SECOND;
+
+const DEFAULT_SEED: u64 = 1;
+const INDICATOR_RADIUS: f32 = 7.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Square,
+    /// A new random level each cycle, held until the next.
+    SampleAndHold,
+}
+
+impl Waveform {
+    pub fn parse(name: &str) -> Option<Waveform> {
+        match name {
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            "square" => Some(Waveform::Square),
+            "sample_and_hold" => Some(Waveform::SampleAndHold),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+            Waveform::Square => "square",
+            Waveform::SampleAndHold => "sample_and_hold",
+        }
+    }
+
+    /// The wave, between -1 and 1, `cycles` cycles in: all but the square
+    /// start at zero, and the sine and triangle rise from there. The
+    /// sample-and-hold level of each cycle is drawn from `seed` and the
+    /// cycle's number, so it's the same every time through.
+    pub fn value(self, cycles: f64, seed: u64) -> f32 {
+        let phase = cycles.rem_euclid(1.0) as f32;
+        match self {
+            Waveform::Sine => math::sin(TAU * phase),
+            Waveform::Triangle => {
+                if phase < 0.25 {
+                    4.0 * phase
+                } else if phase < 0.75 {
+                    2.0 - 4.0 * phase
+                } else {
+                    4.0 * phase - 4.0
+                }
+            }
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::SampleAndHold => {
+                let cycle = cycles.floor() as i64 as u64;
+                Rng::new(seed.rotate_left(32) ^ cycle).range(-1.0, 1.0)
+            }
+        }
+    }
+}
+
+/// A pendulum parameter an oscillator can move, read and written in the
+/// units of the `set` command.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Parameter {
+    Gravity,
+    Length,
+    Mass,
+    DampingRatio,
+    GravityGradient,
+    DriveAmplitude,
+    DriveFrequency,
+}
+
+impl Parameter {
+    pub fn parse(name: &str) -> Option<Parameter> {
+        match name {
+            "g" => Some(Parameter::Gravity),
+            "r" => Some(Parameter::Length),
+            "m" => Some(Parameter::Mass),
+            "zeta" => Some(Parameter::DampingRatio),
+            "gravity_gradient" => Some(Parameter::GravityGradient),
+            "drive_amplitude" => Some(Parameter::DriveAmplitude),
+            "drive_frequency" => Some(Parameter::DriveFrequency),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Parameter::Gravity => "g",
+            Parameter::Length => "r",
+            Parameter::Mass => "m",
+            Parameter::DampingRatio => "zeta",
+            Parameter::GravityGradient => "gravity_gradient",
+            Parameter::DriveAmplitude => "drive_amplitude",
+            Parameter::DriveFrequency => "drive_frequency",
+        }
+    }
+
+    /// How the pendulum's HUD row for this parameter begins.
+    fn hud_row(self) -> &'static str {
+        match self {
+            Parameter::Gravity | Parameter::GravityGradient => "Gravity:",
+            Parameter::Length => "Length:",
+            Parameter::Mass => "Mass:",
+            Parameter::DampingRatio => "Friction:",
+            Parameter::DriveAmplitude | Parameter::DriveFrequency => "Drive:",
+        }
+    }
+
+    pub fn get(self, p: &Pendulum) -> f32 {
+        match self {
+            Parameter::Gravity => presets::real_gravity(p.g),
+            Parameter::Length => p.r,
+            Parameter::Mass => p.m,
+            Parameter::DampingRatio => p.zeta.unwrap_or(0.0),
+            Parameter::GravityGradient => p.gravity_gradient,
+            Parameter::DriveAmplitude => p.drive_amplitude * STEPS_PER_SECOND.powi(2),
+            Parameter::DriveFrequency => p.drive_frequency * STEPS_PER_SECOND,
+        }
+    }
+
+    /// Sets the parameter, kept to what it can be: a length and mass above
+    /// zero and a damping ratio and drive frequency no lower than zero.
+    pub fn set(self, p: &mut Pendulum, value: f32) {
+        let mut state = p.snapshot();
+        match self {
+            Parameter::Gravity => state.g = presets::scene_gravity(value),
+            Parameter::Length => state.r = value.max(1.0),
+            Parameter::Mass => state.m = value.max(0.01),
+            Parameter::DampingRatio => p.zeta = Some(value.max(0.0)),
+            Parameter::GravityGradient => p.gravity_gradient = value,
+            Parameter::DriveAmplitude => p.drive_amplitude = value / STEPS_PER_SECOND.powi(2),
+            Parameter::DriveFrequency => p.retune_drive(value.max(0.0) / STEPS_PER_SECOND),
+        }
+        p.restore(&state);
+    }
+}
+
+/// The base under a pendulum's parameter and the modulated value last given
+/// it, which it still has unless something else has changed it.
+#[derive(Copy, Clone)]
+struct Tracked {
+    base: f32,
+    written: f32,
+}
+
+pub struct Lfo {
+    pub parameter: Parameter,
+    pub waveform: Waveform,
+    /// Cycles a second of simulation time.
+    pub frequency: f32,
+    /// How far either way of the base the parameter is taken.
+    pub depth: f32,
+    pub seed: u64,
+    /// Per model in the scene, by index.
+    tracked: Vec<Option<Tracked>>,
+    /// Whether the pendulums' values already have the wave in them, as
+    /// they do in a scene saved with it, for the first step.
+    modulated: bool,
+}
+
+impl Lfo {
+    /// Parses `<parameter> <waveform> <frequency>[hz] depth <depth>`, with
+    /// `seed <n>` on the end for the sample-and-hold's levels.
+    pub fn parse(words: &[&str]) -> Result<Lfo, String> {
+        let [parameter, waveform, frequency, "depth", depth, rest @ ..] = words else {
+            return Err(
+                "expected <parameter> <waveform> <frequency>hz depth <depth> [seed <n>]"
+                    .to_string(),
+            );
+        };
+        let parameter = Parameter::parse(parameter).ok_or_else(|| {
+            format!(
+                "unknown parameter `{}`: try g, r, m, zeta, gravity_gradient, drive_amplitude or drive_frequency",
+                parameter
+            )
+        })?;
+        let waveform = Waveform::parse(waveform).ok_or_else(|| {
+            format!(
+                "unknown waveform `{}`: try sine, triangle, square or sample_and_hold",
+                waveform
+            )
+        })?;
+        let frequency = frequency
+            .strip_suffix("hz")
+            .unwrap_or(frequency)
+            .parse::<f32>()
+            .ok()
+            .filter(|&f| f > 0.0 && f.is_finite())
+            .ok_or_else(|| format!("expected a frequency above zero, found `{}`", frequency))?;
+        let depth = depth
+            .parse::<f32>()
+            .ok()
+            .filter(|d| d.is_finite())
+            .ok_or_else(|| format!("expected a number for the depth, found `{}`", depth))?;
+        let seed = match rest {
+            [] => DEFAULT_SEED,
+            ["seed", seed] => seed
+                .parse()
+                .map_err(|_| format!("expected a whole number for the seed, found `{}`", seed))?,
+            _ => return Err(format!("unexpected `{}`", rest.join(" "))),
+        };
+
+        Ok(Lfo {
+            parameter,
+            waveform,
+            frequency,
+            depth,
+            seed,
+            tracked: Vec::new(),
+            modulated: false,
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {} {}hz depth {}",
+            self.parameter.name(),
+            self.waveform.name(),
+            self.frequency,
+            self.depth
+        );
+        if self.seed != DEFAULT_SEED {
+            line.push_str(&format!(" seed {}", self.seed));
+        }
+        line
+    }
+
+    fn cycles(&self, p: &Pendulum) -> f64 {
+        self.frequency as f64 * (p.time / STEPS_PER_SECOND) as f64
+    }
+
+    /// How far from the base the parameter is at `p`'s time.
+    pub fn offset(&self, p: &Pendulum) -> f32 {
+        self.depth * self.waveform.value(self.cycles(p), self.seed)
+    }
+
+    /// How far through its cycle the wave is at `p`'s time, from 0 to 1.
+    pub fn phase(&self, p: &Pendulum) -> f32 {
+        self.cycles(p).rem_euclid(1.0) as f32
+    }
+
+    /// The base a pendulum's parameter is at `value`, seen for the first
+    /// time or changed since it was last written: the value itself, or for
+    /// a value with the wave already in it, what it was without.
+    fn base(&self, p: &Pendulum, value: f32) -> f32 {
+        if self.modulated {
+            value - self.offset(p)
+        } else {
+            value
+        }
+    }
+
+    /// Sets model `index`'s parameter, if it's a pendulum, to the wave on
+    /// top of its base.
+    fn apply(&mut self, index: usize, p: &mut Pendulum) {
+        if self.tracked.len() <= index {
+            self.tracked.resize(index + 1, None);
+        }
+        let value = self.parameter.get(p);
+        let base = match self.tracked[index] {
+            Some(tracked) if tracked.written == value => tracked.base,
+            _ => self.base(p, value),
+        };
+        self.parameter.set(p, base + self.offset(p));
+        self.tracked[index] = Some(Tracked {
+            base,
+            written: self.parameter.get(p),
+        });
+    }
+
+    /// Model `index`'s base, if it's been seen.
+    pub fn base_of(&self, index: usize) -> Option<f32> {
+        self.tracked.get(index).copied().flatten().map(|t| t.base)
+    }
+}
+
+/// The oscillators in use, at most one on each parameter.
+pub struct Lfos {
+    pub lfos: Vec<Lfo>,
+}
+
+impl Lfos {
+    pub fn new() -> Lfos {
+        Lfos { lfos: Vec::new() }
+    }
+
+    /// Reads one oscillator a line from an `[lfo]` section. The scene
+    /// loaded with them already has their waves in its values.
+    pub fn from_config(section: &Section) -> Result<Lfos, String> {
+        let mut lfos = Lfos::new();
+        for (line_number, line) in &section.lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            let mut lfo = Lfo::parse(&words).map_err(error)?;
+            if lfos
+                .lfos
+                .iter()
+                .any(|other| other.parameter == lfo.parameter)
+            {
+                return Err(error(format!("a second LFO on `{}`", lfo.parameter.name())));
+            }
+            lfo.modulated = true;
+            lfos.lfos.push(lfo);
+        }
+        Ok(lfos)
+    }
+
+    pub fn to_section(&self) -> String {
+        if self.lfos.is_empty() {
+            return String::new();
+        }
+        let mut text = String::from("[lfo]\n");
+        for lfo in &self.lfos {
+            text.push_str(&lfo.to_line());
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.lfos.is_empty()
+    }
+
+    /// Adds `lfo`, replacing any already on its parameter, whose wave is
+    /// taken off first.
+    pub fn add(&mut self, lfo: Lfo, scene: &mut Scene) {
+        self.remove(lfo.parameter, scene);
+        self.lfos.push(lfo);
+    }
+
+    /// Takes the oscillator off `parameter`, leaving each pendulum's value
+    /// at its base, and says whether there was one.
+    pub fn remove(&mut self, parameter: Parameter, scene: &mut Scene) -> bool {
+        let Some(i) = self.lfos.iter().position(|lfo| lfo.parameter == parameter) else {
+            return false;
+        };
+        let lfo = self.lfos.remove(i);
+        for (index, model) in scene.models.iter_mut().enumerate() {
+            if let (Some(p), Some(base)) = (model.as_pendulum_mut(), lfo.base_of(index)) {
+                parameter.set(p, base);
+            }
+        }
+        true
+    }
+
+    pub fn clear(&mut self, scene: &mut Scene) {
+        let parameters: Vec<Parameter> = self.lfos.iter().map(|lfo| lfo.parameter).collect();
+        for parameter in parameters {
+            self.remove(parameter, scene);
+        }
+    }
+
+    /// Forgets the bases, for a new scene, whose values are its own.
+    pub fn reset(&mut self) {
+        for lfo in &mut self.lfos {
+            lfo.tracked.clear();
+            lfo.modulated = false;
+        }
+    }
+
+    /// The selected pendulum was set back to a state from the history,
+    /// which has the waves in it as they were then.
+    pub fn rewound(&mut self) {
+        for lfo in &mut self.lfos {
+            lfo.modulated = true;
+        }
+    }
+
+    /// Lays each wave over its parameter, before a step.
+    pub fn step(&mut self, scene: &mut Scene) {
+        for lfo in &mut self.lfos {
+            for (index, model) in scene.models.iter_mut().enumerate() {
+                if let Some(p) = model.as_pendulum_mut() {
+                    lfo.apply(index, p);
+                }
+            }
+            lfo.modulated = false;
+        }
+    }
+
+    /// The `lfo` console command: `add <parameter> <waveform>
+    /// <frequency>hz depth <depth> [seed <n>]`, `remove <parameter>` or
+    /// `clear`, and the oscillators in use.
+    pub fn command(&mut self, words: &[&str], scene: &mut Scene) -> Result<Vec<String>, String> {
+        match words {
+            [] => {}
+            ["add", rest @ ..] => self.add(Lfo::parse(rest)?, scene),
+            ["remove", parameter] => {
+                let parameter = Parameter::parse(parameter)
+                    .ok_or_else(|| format!("Unknown parameter `{}`", parameter))?;
+                if !self.remove(parameter, scene) {
+                    return Err(format!("No LFO on {}", parameter.name()));
+                }
+            }
+            ["clear"] => self.clear(scene),
+            _ => {
+                return Err(
+                    "Usage: lfo [add <parameter> <waveform> <frequency>hz depth <depth> [seed <n>] | remove <parameter> | clear]"
+                        .to_string(),
+                )
+            }
+        }
+        if self.lfos.is_empty() {
+            return Ok(vec!["No LFOs".to_string()]);
+        }
+        Ok(self
+            .lfos
+            .iter()
+            .map(|lfo| format!("LFO: {}", lfo.to_line()))
+            .collect())
+    }
+
+    /// Draws a dial after each HUD row an oscillator moves, with a dot
+    /// going round it through the wave's cycle. `rows` are the HUD's lines,
+    /// `p` the pendulum they're of.
+    pub fn draw_indicators(
+        &self,
+        graphics: &mut Graphics2D,
+        font: &Font,
+        rows: &[String],
+        row_height: f32,
+        p: &Pendulum,
+    ) {
+        let mut on_row = vec![0; rows.len()];
+        for lfo in &self.lfos {
+            let Some(row) = rows
+                .iter()
+                .position(|row| row.starts_with(lfo.parameter.hud_row()))
+            else {
+                continue;
+            };
+            let width = font
+                .layout_text(&rows[row], row_height, TextOptions::new())
+                .width();
+            let center = (
+                width + 12.0 + 2.5 * INDICATOR_RADIUS * on_row[row] as f32,
+                row_height * (row as f32 + 0.5),
+            );
+            on_row[row] += 1;
+            let (sin, cos) = (TAU * lfo.phase(p)).sin_cos();
+            graphics.draw_circle(
+                center,
+                INDICATOR_RADIUS,
+                Color::from_rgba(0.0, 0.0, 0.0, 0.2),
+            );
+            graphics.draw_circle(
+                (
+                    center.0 + 0.6 * INDICATOR_RADIUS * sin,
+                    center.1 - 0.6 * INDICATOR_RADIUS * cos,
+                ),
+                2.0,
+                Color::from_rgb(0.2, 0.3, 0.8),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::model::{ModelEntry, PendulumEntry};
+
+    fn scene() -> Scene {
+        Scene::new(vec![
+            ModelEntry::Pendulum(PendulumEntry::default()),
+            ModelEntry::Pendulum(PendulumEntry {
+                r: 120.0,
+                ..PendulumEntry::default()
+            }),
+        ])
+    }
+
+    fn pendulum(scene: &mut Scene, index: usize) -> &mut Pendulum {
+        scene.models[index].as_pendulum_mut().unwrap()
+    }
+
+    /// Moves every pendulum's clock to `seconds` and lays the waves on.
+    fn at(lfos: &mut Lfos, scene: &mut Scene, seconds: f32) {
+        for model in &mut scene.models {
+            model.as_pendulum_mut().unwrap().time = seconds * STEPS_PER_SECOND;
+        }
+        lfos.step(scene);
+    }
+
+    #[test]
+    fn the_waves_have_their_shapes() {
+        let close = |waveform: Waveform, cycles: f64, expected: f32| {
+            let value = waveform.value(cycles, DEFAULT_SEED);
+            assert!(
+                (value - expected).abs() < 1e-5,
+                "{} at {}: {}",
+                waveform.name(),
+                cycles,
+                value
+            );
+        };
+        for (cycles, sine, triangle, square) in [
+            (0.0, 0.0, 0.0, 1.0),
+            (0.125, std::f32::consts::FRAC_1_SQRT_2, 0.5, 1.0),
+            (0.25, 1.0, 1.0, 1.0),
+            (0.5, 0.0, 0.0, -1.0),
+            (0.75, -1.0, -1.0, -1.0),
+            (0.875, -std::f32::consts::FRAC_1_SQRT_2, -0.5, -1.0),
+        ] {
+            // The same a whole number of cycles on, or back.
+            for whole in [0.0, 3.0, -2.0] {
+                close(Waveform::Sine, cycles + whole, sine);
+                close(Waveform::Triangle, cycles + whole, triangle);
+                close(Waveform::Square, cycles + whole, square);
+            }
+        }
+        for waveform in [
+            Waveform::Sine,
+            Waveform::Triangle,
+            Waveform::Square,
+            Waveform::SampleAndHold,
+        ] {
+            assert_eq!(Waveform::parse(waveform.name()), Some(waveform));
+            for n in 0..200 {
+                assert!(waveform.value(n as f64 * 0.037, 9).abs() <= 1.0);
+            }
+        }
+        assert_eq!(Waveform::parse("sawtooth"), None);
+    }
+
+    #[test]
+    fn sample_and_hold_holds_a_seeded_level_each_cycle() {
+        let levels = |seed: u64| -> Vec<f32> {
+            (0..20)
+                .map(|cycle| Waveform::SampleAndHold.value(cycle as f64 + 0.1, seed))
+                .collect()
+        };
+        let wave = Waveform::SampleAndHold;
+        for cycle in 0..20 {
+            let level = wave.value(cycle as f64, 7);
+            for part in [0.25, 0.5, 0.999] {
+                assert_eq!(wave.value(cycle as f64 + part, 7), level);
+            }
+        }
+        // The same every time through for a seed, and another for another.
+        assert_eq!(levels(7), levels(7));
+        assert_ne!(levels(7), levels(8));
+        let seven = levels(7);
+        assert!(seven.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(seven.iter().any(|&level| level > 0.3));
+        assert!(seven.iter().any(|&level| level < -0.3));
+    }
+
+    #[test]
+    fn the_wave_moves_each_pendulum_around_its_own_base() {
+        let mut scene = scene();
+        let mut lfos = Lfos::new();
+        lfos.command(
+            &["add", "r", "triangle", "0.5hz", "depth", "20"],
+            &mut scene,
+        )
+        .unwrap();
+        for (seconds, offset) in [
+            (0.0, 0.0),
+            (0.5, 20.0),
+            (1.0, 0.0),
+            (1.5, -20.0),
+            (2.25, 10.0),
+        ] {
+            at(&mut lfos, &mut scene, seconds);
+            for (index, base) in [(0, 200.0), (1, 120.0)] {
+                let r = pendulum(&mut scene, index).r;
+                assert!((r - (base + offset)).abs() < 1e-3, "{} s: {}", seconds, r);
+                assert_eq!(lfos.lfos[0].base_of(index), Some(base));
+            }
+        }
+
+        // Set some other way, the value is the new base.
+        Parameter::Length.set(pendulum(&mut scene, 0), 250.0);
+        at(&mut lfos, &mut scene, 2.5);
+        assert_eq!(lfos.lfos[0].base_of(0), Some(250.0));
+        assert!((pendulum(&mut scene, 0).r - 270.0).abs() < 1e-3);
+        assert!((pendulum(&mut scene, 1).r - 140.0).abs() < 1e-3);
+
+        // Taken off, each is left at its base.
+        assert!(lfos.remove(Parameter::Length, &mut scene));
+        assert!(!lfos.remove(Parameter::Length, &mut scene));
+        assert_eq!(pendulum(&mut scene, 0).r, 250.0);
+        assert_eq!(pendulum(&mut scene, 1).r, 120.0);
+    }
+
+    #[test]
+    fn gravity_breathes_in_the_set_commands_units() {
+        let mut scene = scene();
+        let base = Parameter::Gravity.get(pendulum(&mut scene, 0));
+        let mut lfos = Lfos::new();
+        lfos.add(
+            Lfo::parse(&["g", "sine", "0.1hz", "depth", "0.2"]).unwrap(),
+            &mut scene,
+        );
+        let (mut low, mut high, mut sum) = (f32::MAX, f32::MIN, 0.0);
+        for n in 0..100 {
+            at(&mut lfos, &mut scene, n as f32 / 10.0);
+            let g = Parameter::Gravity.get(pendulum(&mut scene, 0));
+            let expected = base + 0.2 * (TAU * n as f32 / 100.0).sin();
+            assert!((g - expected).abs() < 1e-4, "{} for {}", g, expected);
+            (low, high, sum) = (low.min(g), high.max(g), sum + g);
+        }
+        assert!((high - base - 0.2).abs() < 1e-3 && (base - low - 0.2).abs() < 1e-3);
+        assert!((sum / 100.0 - base).abs() < 1e-4);
+        lfos.clear(&mut scene);
+        assert!((Parameter::Gravity.get(pendulum(&mut scene, 0)) - base).abs() < 1e-5);
+        assert!(!lfos.is_active());
+    }
+
+    #[test]
+    fn a_loaded_scene_has_its_base_worked_back_out() {
+        let config = Config::parse("[lfo]\nr square 1hz depth 10\n").unwrap();
+        let mut lfos = Lfos::from_config(config.section("lfo").unwrap()).unwrap();
+        // Saved a quarter of a cycle in, with the wave's 10 already in it.
+        let mut scene = scene();
+        pendulum(&mut scene, 0).r = 210.0;
+        at(&mut lfos, &mut scene, 0.25);
+        assert_eq!(lfos.lfos[0].base_of(0), Some(200.0));
+        assert_eq!(pendulum(&mut scene, 0).r, 210.0);
+        at(&mut lfos, &mut scene, 0.75);
+        assert_eq!(pendulum(&mut scene, 0).r, 190.0);
+        // A new scene's values are its own.
+        lfos.reset();
+        let mut scene = Scene::single();
+        let r = pendulum(&mut scene, 0).r;
+        at(&mut lfos, &mut scene, 0.25);
+        assert_eq!(pendulum(&mut scene, 0).r, r + 10.0);
+    }
+
+    #[test]
+    fn oscillators_are_written_as_read() {
+        let text = "[lfo]\ng sine 0.1hz depth 0.2\nr sample_and_hold 0.5hz depth 20 seed 7\n";
+        let config = Config::parse(text).unwrap();
+        let lfos = Lfos::from_config(config.section("lfo").unwrap()).unwrap();
+        assert_eq!(lfos.to_section(), text);
+        assert_eq!(Lfos::new().to_section(), "");
+
+        let error = |text: &str| {
+            let config = Config::parse(text).unwrap();
+            Lfos::from_config(config.section("lfo").unwrap())
+                .err()
+                .unwrap()
+        };
+        assert_eq!(
+            error("[lfo]\ng sine 1hz depth 1\ng square 2hz depth 1\n"),
+            "line 3: a second LFO on `g`"
+        );
+        assert_eq!(
+            error("[lfo]\ng sine 0hz depth 1\n"),
+            "line 2: expected a frequency above zero, found `0hz`"
+        );
+        assert!(error("[lfo]\ng sine 1hz depth NaN\n").contains("for the depth"));
+        assert!(error("[lfo]\nh sine 1hz depth 1\n").contains("unknown parameter `h`"));
+        assert!(error("[lfo]\ng saw 1hz depth 1\n").contains("unknown waveform `saw`"));
+        assert!(error("[lfo]\ng sine 1hz depth 1 seed x\n").contains("whole number"));
+        assert!(error("[lfo]\ng sine 1hz depth 1 loud\n").contains("unexpected `loud`"));
+        assert!(error("[lfo]\ng sine 1hz\n").starts_with("line 2: expected <parameter>"));
+
+        let mut scene = scene();
+        let mut lfos = Lfos::new();
+        assert_eq!(lfos.command(&[], &mut scene).unwrap(), ["No LFOs"]);
+        assert_eq!(
+            lfos.command(&["add", "m", "square", "2hz", "depth", "0.5"], &mut scene)
+                .unwrap(),
+            ["LFO: m square 2hz depth 0.5"]
+        );
+        assert_eq!(
+            lfos.command(&["remove", "g"], &mut scene).unwrap_err(),
+            "No LFO on g"
+        );
+        assert!(lfos.command(&["remove", "colour"], &mut scene).is_err());
+        assert_eq!(lfos.command(&["clear"], &mut scene).unwrap(), ["No LFOs"]);
+    }
+}
@@ -0,0 +1,497 @@
+//! Input macros: key actions and console commands recorded with their
+//! timing and played back later. The key that replays a macro records one
+//! with Ctrl held. Grabbing with the mouse, the motor keys and typing into
+//! the boxes aren't recorded, only what the keys and commands did.
+//!
+//! Times are counted in frames, like the simulation clock: on replay a
+//! frame only counts while the simulation is running, so pausing holds the
+//! replay where it is. A pause the macro made itself doesn't, or a
+//! recorded pause would never end, so while recording the paused frames
+//! count too.
+//!
+//! Macros are saved with the session settings as a `[macros]` section, a
+//! `macro` line starting each one and a line per event after it, the time
+//! in seconds from the start then the action's name or `command` and the
+//! command:
+//!
+//! ```text
+//! [macros]
+//! macro
+//! 0.000 increase_gravity
+//! 1.250 command set zeta 0.05
+//! 2.500 toggle_pause
+//! ```
+
+use crate::bindings::{action_name, parse_action, Action};
+use crate::config::Section;
+use crate::STEPS_PER_SECOND;
+
+/// Actions a macro leaves out: what replays and lists macros, and keys
+/// that only start typing, move the focus or are held down for as long as
+/// they matter.
+const UNRECORDED: [Action; 11] = [
+    Action::ReplayMacro,
+    Action::Help,
+    Action::Console,
+    Action::SetPeriod,
+    Action::Annotate,
+    Action::MotorLeft,
+    Action::MotorRight,
+    Action::ToggleScrub,
+    Action::DrawObstacle,
+    Action::Calibrate,
+    Action::Focus,
+];
+
+#[derive(Clone, PartialEq)]
+pub enum Event {
+    Action(Action),
+    Command(String),
+}
+
+impl Event {
+    fn describe(&self) -> String {
+        match self {
+            Event::Action(action) => action_name(*action).to_string(),
+            Event::Command(command) => format!("command {}", command),
+        }
+    }
+}
+
+/// Events with the frame each came on, counted from the start.
+#[derive(Clone, PartialEq)]
+pub struct Macro {
+    pub events: Vec<(usize, Event)>,
+}
+
+impl Macro {
+    fn seconds(&self) -> f32 {
+        self.events.last().map_or(0.0, |(frame, _)| *frame as f32) / STEPS_PER_SECOND
+    }
+}
+
+struct Recording {
+    frame: usize,
+    events: Vec<(usize, Event)>,
+}
+
+struct Replay {
+    /// Which macro, counting from 1, for the status line.
+    number: usize,
+    events: Vec<(usize, Event)>,
+    frame: usize,
+    next: usize,
+    /// Whether the macro itself left the simulation paused.
+    paused: bool,
+}
+
+pub struct Macros {
+    pub macros: Vec<Macro>,
+    recording: Option<Recording>,
+    replay: Option<Replay>,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros {
+            macros: Vec::new(),
+            recording: None,
+            replay: None,
+        }
+    }
+
+    pub fn from_config(section: &Section) -> Result<Macros, String> {
+        let mut macros = Macros::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            if line.trim() == "macro" {
+                macros.macros.push(Macro { events: Vec::new() });
+                continue;
+            }
+            let last = macros
+                .macros
+                .last_mut()
+                .ok_or_else(|| error("expected `macro` before its events".to_string()))?;
+
+            let (time, event) = line
+                .trim()
+                .split_once(' ')
+                .ok_or_else(|| error(format!("expected `<seconds> <action>`, found `{}`", line)))?;
+            let seconds: f32 = time
+                .parse()
+                .ok()
+                .filter(|seconds: &f32| *seconds >= 0.0)
+                .ok_or_else(|| error(format!("invalid time `{}`", time)))?;
+            let event = match event.trim().split_once(' ') {
+                Some(("command", command)) => Event::Command(command.trim().to_string()),
+                _ => Event::Action(
+                    parse_action(event.trim())
+                        .ok_or_else(|| error(format!("unknown action `{}`", event.trim())))?,
+                ),
+            };
+            let frame = (seconds * STEPS_PER_SECOND).round() as usize;
+            if last
+                .events
+                .last()
+                .is_some_and(|(previous, _)| frame < *previous)
+            {
+                return Err(error("events must be in order of time".to_string()));
+            }
+            last.events.push((frame, event));
+        }
+
+        Ok(macros)
+    }
+
+    pub fn to_section(&self) -> String {
+        if self.macros.is_empty() {
+            return String::new();
+        }
+        let mut text = String::from("[macros]\n");
+        for recorded in &self.macros {
+            text.push_str("macro\n");
+            for (frame, event) in &recorded.events {
+                text.push_str(&format!(
+                    "{:.3} {}\n",
+                    *frame as f32 / STEPS_PER_SECOND,
+                    event.describe()
+                ));
+            }
+        }
+        text
+    }
+
+    /// Starts recording, or stops and keeps what was recorded. Refused
+    /// while a macro is playing, which would record itself.
+    pub fn toggle_recording(&mut self) -> Result<String, String> {
+        if self.replay.is_some() {
+            return Err("Can't record while a macro is playing".to_string());
+        }
+        let Some(recording) = self.recording.take() else {
+            self.recording = Some(Recording {
+                frame: 0,
+                events: Vec::new(),
+            });
+            return Ok("Recording a macro".to_string());
+        };
+        if recording.events.is_empty() {
+            return Ok("Nothing recorded".to_string());
+        }
+        let recorded = Macro {
+            events: recording.events,
+        };
+        let reply = format!(
+            "Recorded macro {}: {} events over {:.1} s",
+            self.macros.len() + 1,
+            recorded.events.len(),
+            recorded.seconds()
+        );
+        self.macros.push(recorded);
+        Ok(reply)
+    }
+
+    /// Adds `event` to the recording, if there is one and it's recorded.
+    pub fn observe(&mut self, event: Event) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        match &event {
+            Event::Action(action) if UNRECORDED.contains(action) => (),
+            Event::Command(command) if command.split_whitespace().next() == Some("macro") => (),
+            _ => recording.events.push((recording.frame, event)),
+        }
+    }
+
+    /// Plays macro `number`, counting from 1, or the latest without one.
+    pub fn play(&mut self, number: Option<usize>) -> Result<String, String> {
+        if self.recording.is_some() {
+            return Err("Can't play a macro while recording one".to_string());
+        }
+        let number = number.unwrap_or(self.macros.len());
+        let recorded = number
+            .checked_sub(1)
+            .and_then(|i| self.macros.get(i))
+            .ok_or_else(|| {
+                if self.macros.is_empty() {
+                    "No macros recorded - Ctrl and the replay key records one".to_string()
+                } else {
+                    format!("No macro {}", number)
+                }
+            })?;
+        self.replay = Some(Replay {
+            number,
+            events: recorded.events.clone(),
+            frame: 0,
+            next: 0,
+            paused: false,
+        });
+        Ok(format!("Playing macro {}", number))
+    }
+
+    /// Moves the macro clock on a frame and returns the events come due on
+    /// it. Frames the simulation is `held` on, by a transition or the
+    /// history, don't count, recording or playing. A replay also holds
+    /// while paused, unless it was the macro that paused.
+    pub fn tick(&mut self, paused: bool, held: bool) -> Vec<Event> {
+        if let Some(recording) = self.recording.as_mut().filter(|_| !held) {
+            recording.frame += 1;
+        }
+        let Some(replay) = &mut self.replay else {
+            return Vec::new();
+        };
+        if held || (paused && !replay.paused) {
+            return Vec::new();
+        }
+
+        let due: Vec<Event> = replay.events[replay.next..]
+            .iter()
+            .take_while(|(frame, _)| *frame <= replay.frame)
+            .map(|(_, event)| event.clone())
+            .collect();
+        replay.next += due.len();
+        replay.frame += 1;
+        if replay.next == replay.events.len() {
+            self.replay = None;
+        }
+        due
+    }
+
+    /// Notes whether the events just played left the simulation paused.
+    pub fn played(&mut self, paused: bool) {
+        if let Some(replay) = &mut self.replay {
+            replay.paused = paused;
+        }
+    }
+
+    pub fn cancel_replay(&mut self) {
+        self.replay = None;
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        if self.macros.is_empty() {
+            return vec!["No macros recorded".to_string()];
+        }
+        self.macros
+            .iter()
+            .enumerate()
+            .map(|(i, recorded)| {
+                let events: Vec<String> = recorded
+                    .events
+                    .iter()
+                    .map(|(_, event)| event.describe())
+                    .collect();
+                format!(
+                    "{}. {:.1} s: {}",
+                    i + 1,
+                    recorded.seconds(),
+                    events.join(", ")
+                )
+            })
+            .collect()
+    }
+
+    pub fn delete(&mut self, number: usize) -> Result<String, String> {
+        if self
+            .replay
+            .as_ref()
+            .is_some_and(|replay| replay.number == number)
+        {
+            return Err(format!("Macro {} is playing", number));
+        }
+        if number == 0 || number > self.macros.len() {
+            return Err(format!("No macro {}", number));
+        }
+        self.macros.remove(number - 1);
+        if let Some(replay) = self.replay.as_mut().filter(|replay| replay.number > number) {
+            replay.number -= 1;
+        }
+        Ok(format!("Deleted macro {}", number))
+    }
+
+    pub fn status_line(&self) -> Option<String> {
+        if let Some(recording) = &self.recording {
+            return Some(format!(
+                "Recording macro: {} events, {:.1} s - Ctrl+replay key to stop",
+                recording.events.len(),
+                recording.frame as f32 / STEPS_PER_SECOND
+            ));
+        }
+        self.replay.as_ref().map(|replay| {
+            format!(
+                "Playing macro {}: {} of {} events",
+                replay.number,
+                replay.next,
+                replay.events.len()
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// Records `events`, each on its frame, ticking `frames` frames in all.
+    fn record(macros: &mut Macros, events: &[(usize, Event)], frames: usize) {
+        macros.toggle_recording().unwrap();
+        for frame in 0..frames {
+            for (_, event) in events.iter().filter(|(at, _)| *at == frame) {
+                macros.observe(event.clone());
+            }
+            macros.tick(false, false);
+        }
+        macros.toggle_recording().unwrap();
+    }
+
+    /// Plays macro `number` for `frames` frames and returns what came due
+    /// on each frame that had something.
+    fn replay(macros: &mut Macros, number: usize, frames: usize) -> Vec<(usize, Event)> {
+        macros.play(Some(number)).unwrap();
+        (0..frames)
+            .flat_map(|frame| {
+                let due = macros.tick(false, false);
+                due.into_iter().map(move |event| (frame, event))
+            })
+            .collect()
+    }
+
+    fn sample() -> Vec<(usize, Event)> {
+        vec![
+            (0, Event::Action(Action::IncreaseGravity)),
+            (75, Event::Command("set zeta 0.05".to_string())),
+            (75, Event::Action(Action::CycleBob)),
+            (150, Event::Action(Action::Reset)),
+        ]
+    }
+
+    #[test]
+    fn a_replay_comes_at_the_recorded_frames() {
+        let mut macros = Macros::new();
+        let mut events = sample();
+        // What's left out of a recording.
+        events.push((20, Event::Action(Action::Help)));
+        events.push((30, Event::Command("macro list".to_string())));
+        record(&mut macros, &events, 200);
+
+        assert_eq!(macros.macros.len(), 1);
+        assert!(macros.macros[0].events == sample());
+        assert!(replay(&mut macros, 1, 300) == sample());
+        assert!(macros.status_line().is_none());
+    }
+
+    #[test]
+    fn pausing_holds_a_replay_unless_the_macro_paused() {
+        let mut macros = Macros::new();
+        macros.macros.push(Macro {
+            events: vec![
+                (0, Event::Action(Action::TogglePause)),
+                (10, Event::Action(Action::TogglePause)),
+            ],
+        });
+
+        // Paused from outside, or held, nothing moves on.
+        macros.play(None).unwrap();
+        for _ in 0..50 {
+            assert!(macros.tick(true, false).is_empty());
+            assert!(macros.tick(false, true).is_empty());
+        }
+        assert_eq!(macros.tick(false, false).len(), 1);
+        macros.played(true);
+        // The macro's own pause lets it carry on to unpause.
+        let due: Vec<usize> = (1..=20)
+            .filter(|_| !macros.tick(true, false).is_empty())
+            .collect();
+        assert_eq!(due, [10]);
+    }
+
+    #[test]
+    fn recording_counts_paused_frames_but_not_held_ones() {
+        let mut macros = Macros::new();
+        macros.toggle_recording().unwrap();
+        for _ in 0..30 {
+            macros.tick(true, false);
+            macros.tick(false, true);
+        }
+        macros.observe(Event::Action(Action::Reset));
+        macros.toggle_recording().unwrap();
+        assert!(macros.macros[0].events == [(30, Event::Action(Action::Reset))]);
+    }
+
+    #[test]
+    fn recording_and_playing_at_once_is_refused() {
+        let mut macros = Macros::new();
+        assert_eq!(macros.toggle_recording().unwrap(), "Recording a macro");
+        assert!(macros.play(None).is_err());
+        assert_eq!(macros.toggle_recording().unwrap(), "Nothing recorded");
+        assert!(macros
+            .play(None)
+            .unwrap_err()
+            .starts_with("No macros recorded"));
+
+        record(&mut macros, &sample(), 200);
+        macros.play(Some(1)).unwrap();
+        assert!(macros.toggle_recording().is_err());
+        assert!(macros.delete(1).is_err());
+        macros.cancel_replay();
+        assert!(macros.toggle_recording().is_ok());
+    }
+
+    #[test]
+    fn macros_save_and_load_with_their_timing() {
+        let mut macros = Macros::new();
+        record(&mut macros, &sample(), 200);
+        record(&mut macros, &[(7, Event::Action(Action::TogglePause))], 10);
+
+        let text = macros.to_section();
+        assert!(text
+            .starts_with("[macros]\nmacro\n0.000 increase_gravity\n1.250 command set zeta 0.05\n"));
+        let config = Config::parse(&text).unwrap();
+        let loaded = Macros::from_config(config.section("macros").unwrap()).unwrap();
+        assert!(loaded.macros == macros.macros);
+        // Saved again, it comes out the same.
+        assert_eq!(loaded.to_section(), text);
+        assert_eq!(Macros::new().to_section(), "");
+    }
+
+    #[test]
+    fn a_bad_macros_section_is_refused() {
+        let load = |text: &str| {
+            let config = Config::parse(text).unwrap();
+            Macros::from_config(config.section("macros").unwrap()).err()
+        };
+        assert_eq!(
+            load("[macros]\n0.0 reset\n").as_deref(),
+            Some("line 2: expected `macro` before its events")
+        );
+        assert_eq!(
+            load("[macros]\nmacro\n1.0 reset\n0.5 reset\n").as_deref(),
+            Some("line 4: events must be in order of time")
+        );
+        assert!(load("[macros]\nmacro\n0.0 juggle\n")
+            .unwrap()
+            .contains("unknown action"));
+        assert!(load("[macros]\nmacro\n-1 reset\n")
+            .unwrap()
+            .contains("invalid time"));
+    }
+
+    #[test]
+    fn macros_list_and_delete_by_number() {
+        let mut macros = Macros::new();
+        assert_eq!(macros.list(), ["No macros recorded"]);
+        record(&mut macros, &sample(), 200);
+        record(&mut macros, &[(0, Event::Action(Action::Reset))], 1);
+        assert_eq!(
+            macros.list(),
+            [
+                "1. 2.5 s: increase_gravity, command set zeta 0.05, cycle_bob, reset",
+                "2. 0.0 s: reset",
+            ]
+        );
+        assert!(macros.delete(3).is_err());
+        assert_eq!(macros.delete(1).unwrap(), "Deleted macro 1");
+        assert_eq!(macros.list(), ["1. 0.0 s: reset"]);
+    }
+}
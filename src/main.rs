@@ -1,216 +1,3562 @@
-use std::f32::consts::PI;
+use std::env;
+use std::f32::consts::TAU;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use speedy2d::color::Color;
 use speedy2d::font::{Font, TextLayout, TextOptions};
-use speedy2d::window::{MouseButton, WindowHandler, WindowHelper};
+use speedy2d::window::{
+    ModifiersState, MouseButton, MouseScrollDistance, VirtualKeyCode, WindowCreationOptions,
+    WindowHandler, WindowHelper, WindowPosition, WindowSize,
+};
 use speedy2d::{Graphics2D, Window};
 use vector::Vector;
 
-fn main() {
-    let window = Window::new_centered("Pendulum", (800, 480)).unwrap();
+use accuracy::AccuracyDashboard;
+use anaglyph::{Anaglyph, Side};
+use annotations::Annotations;
+use apparatus::{Click, Measurement, Profile, Profiles, ProfilesPanel};
+use autosave::{Autosave, Restore};
+use batch::Batch;
+#[cfg(feature = "beat")]
+use beat::BeatDrive;
+use bindings::{Action, Bindings};
+use budget::Budget;
+use chart::StripChart;
+use config::{key_value, parse_f32, Config, Section};
+use damping::DampingExplorer;
+use demo::DemoReel;
+use energy::EnergyGuard;
+use energy_dial::EnergyDial;
+use entry::{EntryKind, TextEntry};
+use envelope::Envelope;
+use escapement::Escapement;
+use field::GravityField;
+use focus::{Focus, Handling, Registry, Target};
+use framing::Framing;
+use game::{Difficulty, FlagGame, RoundState};
+use grid::GridScan;
+use help::HelpOverlay;
+use history::History;
+use impulse::ImpulseTest;
+use instant_replay::InstantReplay;
+use integrator::{Integrators, Scheme};
+use launcher::{Choice, Launcher};
+use lfo::Lfos;
+use macros::{Event, Macros};
+use metronome::Metronome;
+use model::ModelEntry;
+use obstacles::Obstacles;
+use partition::EnergyPartition;
+use pendulum::{DampingModel, Pendulum, BOB_RADIUS, MAX_RATE};
+use phase::PhasePlot;
+use playlist::Playlist;
+use poincare::PoincareExport;
+use portrait::PhasePortrait;
+use remote::{Incoming, RemoteControl};
+use renderer::Renderer;
+use replay::{Input, InputLog, Modifiers, ReplaySettings, Tape};
+use report::LabReport;
+use results::ResultsPanel;
+use retention::Retention;
+use scene::Scene;
+use sensitivity::SensitivityOverlay;
+use sensor::SensorPanel;
+use soft_start::SoftStart;
+use store::{format_timestamp, Kind, Record, Store};
+use strobe::Strobe;
+use svg::{Svg, VectorExport};
+use sweep::{HysteresisSweep, SweepState};
+use sync::SyncPattern;
+use tournament::Tournament;
+use trajectory::{Trajectory, TrajectoryExport};
+use transition::{Pose, Transition, Transitions};
+use units::{Calibration, Units};
+use viewport::{Viewport, WORLD_SIZE};
+use walls::Walls;
+use warm_start::{Track, Trail, WarmStart};
+use windows::WindowSlot;
+
+/// Prints to stderr and keeps the line for the crash diagnostics.
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::diagnostics::record(format!($($arg)*))
+    };
+}
+
+mod accuracy;
+mod anaglyph;
+mod analysis;
+mod annotations;
+mod apparatus;
+mod autosave;
+mod batch;
+#[cfg(feature = "beat")]
+mod beat;
+mod bindings;
+mod budget;
+mod card;
+mod cart;
+mod chain;
+mod chart;
+mod config;
+mod damping;
+mod demo;
+mod determinism;
+mod diagnostics;
+mod diff;
+mod double;
+mod energy;
+mod energy_dial;
+mod entry;
+mod envelope;
+mod escapement;
+mod field;
+mod focus;
+mod framing;
+mod game;
+mod grid;
+mod help;
+mod history;
+mod impulse;
+mod instant_replay;
+mod integrator;
+mod job;
+mod json;
+mod launcher;
+mod lfo;
+mod macros;
+mod math;
+mod metronome;
+mod model;
+mod obstacles;
+mod partition;
+mod pendulum;
+mod phase;
+mod playlist;
+#[cfg(feature = "plugin")]
+mod plugin;
+mod png;
+mod poincare;
+mod polyline;
+mod portrait;
+mod presets;
+mod random;
+mod remote;
+mod renderer;
+mod replay;
+mod report;
+mod results;
+mod retention;
+mod scene;
+mod sensitivity;
+mod sensor;
+mod slack;
+mod soft_start;
+mod store;
+mod strobe;
+mod svg;
+mod sweep;
+mod sync;
+mod tournament;
+mod trajectory;
+mod transition;
+mod tween;
+mod units;
+mod viewport;
+mod walls;
+mod warm_start;
+mod windows;
+
+/// Physics steps per second of simulated time. The integration runs one
+/// step per frame, so this is the clock everything timed is measured on.
+const STEPS_PER_SECOND: f32 = 60.0;
+
+/// Number of steps of the selected pendulum's swing kept for the trajectory
+/// export (one minute).
+const HISTORY_LENGTH: usize = 60 * STEPS_PER_SECOND as usize;
+
+/// Behind everything drawn, in the window and in a vector export.
+const BACKGROUND: Color = Color::from_rgb(0.8, 0.9, 1.0);
 
+/// Angular acceleration the motor applies while `A` or `D` is held. Kept low
+/// so steering against gravity needs some anticipation.
+const MOTOR_TORQUE: f32 = 0.0012;
+
+/// Scene loaded at startup when no file is given on the command line.
+const DEFAULT_CONFIG: &str = "pendulum.cfg";
+/// Where `S` saves and `L` loads the current scene.
+const SAVE_FILE: &str = "scene.cfg";
+/// Where the impulse test writes the recorded response.
+const IMPULSE_FILE: &str = "impulse_response.csv";
+/// Where the Poincaré section is exported.
+const POINCARE_FILE: &str = "poincare.csv";
+/// Where the `portrait` command writes the phase portrait.
+const PORTRAIT_FILE: &str = "phase_portrait.png";
+/// Where the `keys` command writes the cheat sheet.
+const KEYS_FILE: &str = "keys.txt";
+/// Where the hysteresis sweep is exported.
+const SWEEP_FILE: &str = "sweep.csv";
+/// Where `chart export` writes the strip chart's trace.
+const CHART_FILE: &str = "chart.csv";
+/// Where saving also exports the session's notes.
+const ANNOTATIONS_FILE: &str = "annotations.csv";
+/// Where `Ctrl+Shift+C` also writes the scene card, and where
+/// `Ctrl+Shift+V` falls back to when the clipboard has none.
+const CARD_FILE: &str = "scene.card";
+/// Where the session is autosaved, and restored from after a crash.
+const RECOVERY_FILE: &str = "recovery.cfg";
+
+/// Reply to console commands that need a pendulum selected.
+const NO_PENDULUM: &str = "Select a pendulum first";
+
+fn main() {
     let font = Font::new(include_bytes!("./assets/bebas.ttf")).unwrap();
 
-    let win = MyWindowHandler {
-        p: Pendulum::new(400.0, 0.0, 200.0),
+    // A config file, `--card <card or file>` to start from a card instead
+    // of the config's scene, `--windows N` to compare in N windows, which
+    // are each started with `--window <i>/<N>`, `--stdin-control` to take
+    // console commands from stdin, and `--determinism-dump <file>` or
+    // `--determinism-check <file>` to run the determinism audit instead,
+    // `--report <seconds>` to run the scene that long and write the report,
+    // `--record`, or `--replay <file>` in place of a config, to record the
+    // session's inputs or replay them, `--warm-start <track.csv>` to
+    // carry on from a tracked real pendulum, `--apparatus <name>` to set
+    // the selected pendulum up as an apparatus profile, and `--diff <a> <b>`,
+    // with `--all` for the cosmetic differences too, to compare two saved
+    // sessions instead, and `--tournament <rounds>`, with `--seed <n>` to
+    // replay someone else's, to play a tournament of the flag game, and
+    // `--no-menu` to skip the start menu. Built
+    // with the `plugin` feature, `--plugin <library>`, as many times as
+    // there are plugins, loads plugin models.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut config_arg = None;
+    let mut card = None;
+    let mut windows = None;
+    let mut slot = None;
+    let mut stdin_control = false;
+    let mut headless = false;
+    let mut determinism_dump = None;
+    let mut determinism_check = None;
+    let mut report_after = None;
+    let mut record = false;
+    let mut replay_from = None;
+    let mut warm_start_from = None;
+    let mut apparatus = None;
+    let mut diff_files = None;
+    let mut diff_all = false;
+    let mut tournament_rounds = None;
+    let mut tournament_seed = None;
+    let mut no_menu = false;
+    #[cfg(feature = "plugin")]
+    let mut plugins = Vec::new();
+    let mut rest = args.iter().cloned();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--card" => card = rest.next(),
+            "--windows" => windows = rest.next(),
+            "--window" => slot = rest.next(),
+            "--stdin-control" => stdin_control = true,
+            "--headless" => headless = true,
+            "--determinism-dump" => determinism_dump = rest.next(),
+            "--determinism-check" => determinism_check = rest.next(),
+            "--report" => report_after = rest.next(),
+            "--record" => record = true,
+            "--replay" => replay_from = rest.next(),
+            "--warm-start" => warm_start_from = rest.next(),
+            "--apparatus" => apparatus = rest.next(),
+            "--diff" => diff_files = Some((rest.next(), rest.next())),
+            "--all" => diff_all = true,
+            "--tournament" => tournament_rounds = rest.next(),
+            "--seed" => tournament_seed = rest.next(),
+            "--no-menu" => no_menu = true,
+            #[cfg(feature = "plugin")]
+            "--plugin" => plugins.extend(rest.next()),
+            _ => config_arg = Some(arg),
+        }
+    }
+
+    if let Some(path) = determinism_dump {
+        match determinism::dump(Path::new(&path)) {
+            Ok(reply) => println!("{}", reply),
+            Err(e) => {
+                log!("--determinism-dump: {}", e);
+                process::exit(2);
+            }
+        }
+        return;
+    }
+    if let Some(path) = determinism_check {
+        match determinism::check(Path::new(&path)) {
+            Ok((report, matched)) => {
+                report.iter().for_each(|line| println!("{}", line));
+                process::exit(if matched { 0 } else { 1 });
+            }
+            Err(e) => {
+                log!("--determinism-check: {}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    if let Some(files) = diff_files {
+        let (Some(left), Some(right)) = files else {
+            log!("--diff: expected two files to compare");
+            process::exit(2);
+        };
+        let differences = Config::load(Path::new(&left))
+            .and_then(|a| Ok((a, Config::load(Path::new(&right))?)))
+            .and_then(|(a, b)| diff::diff(&a, &b, diff_all));
+        match differences {
+            Ok(differences) => {
+                diff::report(&left, &right, &differences)
+                    .iter()
+                    .for_each(|line| println!("{}", line));
+                process::exit(diff::exit_code(&differences));
+            }
+            Err(e) => {
+                log!("--diff: {}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    if headless && !stdin_control {
+        log!("--headless: only runs with --stdin-control, with nothing else to drive it");
+        process::exit(2);
+    }
+
+    let tournament = tournament_rounds.map(|rounds| {
+        let rounds = tournament::parse_rounds(&rounds).unwrap_or_else(|e| {
+            log!("--tournament: {}", e);
+            process::exit(2);
+        });
+        let seed = tournament_seed.map_or_else(tournament::fresh_seed, |seed| {
+            tournament::parse_seed(&seed).unwrap_or_else(|e| {
+                log!("--seed: {}", e);
+                process::exit(2);
+            })
+        });
+        Tournament::new(seed, rounds)
+    });
+
+    if let Some(count) = windows {
+        let Some(count) = count.parse().ok().filter(|&count: &usize| count >= 1) else {
+            log!("--windows: expected a number of windows, found `{}`", count);
+            process::exit(2);
+        };
+        let position = args.iter().position(|arg| arg == "--windows").unwrap();
+        let passed_on: Vec<String> = args
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != position && i != position + 1)
+            .map(|(_, arg)| arg.clone())
+            .collect();
+        process::exit(windows::supervise(count, &passed_on));
+    }
+    let slot = slot.and_then(|text| {
+        WindowSlot::parse(&text)
+            .map_err(|e| log!("--window: {}", e))
+            .ok()
+    });
+    let config_path = config_arg
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG));
+
+    // A replay starts from the config its recording did, whatever's on disk.
+    let replaying = replay_from.map(|path| {
+        InputLog::load(Path::new(&path)).unwrap_or_else(|e| {
+            log!("--replay: {}", e);
+            process::exit(2);
+        })
+    });
+    let config_text = match &replaying {
+        Some(log) => log.config.clone(),
+        None => fs::read_to_string(&config_path).ok(),
+    };
+    diagnostics::install(config_text.clone(), Path::new(RECOVERY_FILE));
+
+    let config = config_text.as_deref().and_then(|text| {
+        Config::parse(text)
+            .map_err(|e| log!("{}: {}", config_path.display(), e))
+            .ok()
+    });
+
+    // Plugins are loaded before the scene, which may have their models.
+    #[cfg(feature = "plugin")]
+    for path in &plugins {
+        if let Err(e) = plugin::load(Path::new(path)) {
+            log!("--plugin {}: {}", path, e);
+            process::exit(2);
+        }
+    }
+    #[cfg(feature = "plugin")]
+    let default_scene = || {
+        let entries = plugin::default_entries();
+        if entries.is_empty() {
+            Scene::single()
+        } else {
+            Scene::new(entries)
+        }
+    };
+    #[cfg(not(feature = "plugin"))]
+    let default_scene = Scene::single;
+
+    let has_scene = config
+        .as_ref()
+        .is_some_and(|c| c.section("scene").is_some());
+    let scene = match &config {
+        Some(config) if has_scene => Scene::from_config(config).unwrap_or_else(|e| {
+            log!("{}: {}", config_path.display(), e);
+            default_scene()
+        }),
+        _ => default_scene(),
+    };
+    let scene = match slot {
+        Some(slot) => slot.scene(&scene, has_scene),
+        None => scene,
+    };
+
+    let report = load_section(
+        config.as_ref(),
+        &config_path,
+        "report",
+        LabReport::from_config,
+        LabReport::new,
+    );
+    let vector_export = load_section(
+        config.as_ref(),
+        &config_path,
+        "vector_export",
+        VectorExport::from_config,
+        VectorExport::new,
+    );
+    let trajectory_export = load_section(
+        config.as_ref(),
+        &config_path,
+        "trajectory",
+        TrajectoryExport::from_config,
+        TrajectoryExport::new,
+    );
+    if let Some(seconds) = report_after {
+        let Some(seconds) = seconds.parse().ok().filter(|&s: &f32| s > 0.0) else {
+            log!(
+                "--report: expected a number of seconds, found `{}`",
+                seconds
+            );
+            process::exit(2);
+        };
+        match report::run_headless(&report, scene, seconds, &Units::default()) {
+            Ok(reply) => println!("{}", reply),
+            Err(e) => {
+                log!("--report: {}", e);
+                process::exit(2);
+            }
+        }
+        return;
+    }
+
+    // A comparison window shows its share of the scene, not the playlist.
+    let playlist = load_section(
+        config.as_ref().filter(|_| slot.is_none()),
+        &config_path,
+        "playlist",
+        |section| Playlist::from_config(section).map(Some),
+        || None,
+    );
+
+    // Without a scene to show, open the start menu, or run the demo reel
+    // until someone takes over if the menu's turned off or the reel is set
+    // up.
+    let menu_enabled = load_section(
+        config.as_ref(),
+        &config_path,
+        "menu",
+        launcher::enabled_from_config,
+        || true,
+    );
+    let start_menu = !has_scene
+        && playlist.is_none()
+        && slot.is_none()
+        && !no_menu
+        && !headless
+        && menu_enabled
+        && config.as_ref().is_none_or(|c| c.section("demo").is_none());
+    let demo = if has_scene || playlist.is_some() || slot.is_some() || start_menu || headless {
+        None
+    } else {
+        load_section(
+            config.as_ref(),
+            &config_path,
+            "demo",
+            DemoReel::from_config,
+            || Some(DemoReel::default_reel()),
+        )
+    };
+    let (mut scene, title) = match (&playlist, demo.as_ref().and_then(DemoReel::current)) {
+        (Some(playlist), _) => {
+            let preset = playlist.current();
+            (preset.scene(), format!("Pendulum - {}", preset.name))
+        }
+        (None, Some(preset)) => (preset.scene(), format!("Pendulum - Demo: {}", preset.name)),
+        (None, None) => match slot {
+            Some(slot) => (
+                scene,
+                format!("Pendulum - window {} of {}", slot.index + 1, slot.count),
+            ),
+            None => (scene, "Pendulum".to_string()),
+        },
+    };
+
+    // The chart keeps as much as its share of the memory holds, unless
+    // `[chart]` says to keep less.
+    let chart_retention = load_section(
+        config.as_ref(),
+        &config_path,
+        "chart",
+        |section| StripChart::retention_from_config(section).map(Some),
+        || None,
+    );
+    let retention = load_section(
+        config.as_ref(),
+        &config_path,
+        "retention",
+        Retention::from_config,
+        Retention::new,
+    );
+
+    let bindings = load_section(
+        config.as_ref(),
+        &config_path,
+        "keybindings",
+        Bindings::from_config,
+        Bindings::defaults,
+    );
+
+    let letterbox = load_section(
+        config.as_ref(),
+        &config_path,
+        "window",
+        Viewport::letterbox_from_config,
+        || false,
+    );
+
+    let batched = load_section(
+        config.as_ref(),
+        &config_path,
+        "render",
+        batch::batched_from_config,
+        || false,
+    );
+
+    let envelope_opacity = load_section(
+        config.as_ref(),
+        &config_path,
+        "envelope",
+        Envelope::opacity_from_config,
+        Envelope::default_opacity,
+    );
+
+    let poincare = load_section(
+        config.as_ref(),
+        &config_path,
+        "poincare",
+        PoincareExport::from_config,
+        PoincareExport::new,
+    );
+
+    let portrait = load_section(
+        config.as_ref(),
+        &config_path,
+        "portrait",
+        PhasePortrait::from_config,
+        PhasePortrait::new,
+    );
+
+    let sweep = load_section(
+        config.as_ref(),
+        &config_path,
+        "sweep",
+        HysteresisSweep::from_config,
+        HysteresisSweep::new,
+    );
+
+    let gravity_field = load_section(
+        config.as_ref(),
+        &config_path,
+        "gravity_field",
+        GravityField::from_config,
+        GravityField::new,
+    );
+
+    let soft_start = load_section(
+        config.as_ref(),
+        &config_path,
+        "soft_start",
+        SoftStart::from_config,
+        SoftStart::new,
+    );
+    soft_start.apply(&mut scene);
+
+    let energy_dial = load_section(
+        config.as_ref(),
+        &config_path,
+        "energy_dial",
+        EnergyDial::from_config,
+        EnergyDial::new,
+    );
+
+    let obstacles = load_section(
+        config.as_ref(),
+        &config_path,
+        "obstacles",
+        Obstacles::from_config,
+        Obstacles::new,
+    );
+    let walls = load_section(
+        config.as_ref(),
+        &config_path,
+        "walls",
+        Walls::from_config,
+        Walls::new,
+    );
+
+    let help = load_section(
+        config.as_ref(),
+        &config_path,
+        "help",
+        HelpOverlay::from_config,
+        HelpOverlay::new,
+    );
+
+    let macros = load_section(
+        config.as_ref(),
+        &config_path,
+        "macros",
+        Macros::from_config,
+        Macros::new,
+    );
+
+    #[cfg(feature = "beat")]
+    let beat = load_section(
+        config.as_ref(),
+        &config_path,
+        "beat",
+        BeatDrive::from_config,
+        BeatDrive::new,
+    );
+
+    let escapement = load_section(
+        config.as_ref(),
+        &config_path,
+        "escapement",
+        Escapement::from_config,
+        Escapement::new,
+    );
+
+    let anaglyph = load_section(
+        config.as_ref(),
+        &config_path,
+        "anaglyph",
+        Anaglyph::from_config,
+        Anaglyph::new,
+    );
+
+    let transitions = load_section(
+        config.as_ref(),
+        &config_path,
+        "transitions",
+        Transitions::from_config,
+        Transitions::new,
+    );
+
+    let warm_start = load_section(
+        config.as_ref(),
+        &config_path,
+        "warm_start",
+        WarmStart::from_config,
+        WarmStart::new,
+    );
+
+    let replay = load_section(
+        config.as_ref(),
+        &config_path,
+        "replay",
+        ReplaySettings::from_config,
+        ReplaySettings::new,
+    );
+
+    let mut autosave = load_section(
+        config.as_ref(),
+        &config_path,
+        "autosave",
+        Autosave::from_config,
+        Autosave::new,
+    );
+    // Comparison windows would all write the one recovery file, and a
+    // replay or a headless run would write over it with a session not had
+    // in a window.
+    if slot.is_some() || replaying.is_some() || headless {
+        autosave.disable();
+    }
+    let recovery = Path::new(RECOVERY_FILE).exists() && autosave.restore != Restore::Never;
+
+    let store = Store::open(&Store::default_path());
+    let mut game = FlagGame::new();
+    if let Some(best) = store.best("flag") {
+        game.remember_best(best.value);
+    }
+
+    let size = (WORLD_SIZE.0 as u32, WORLD_SIZE.1 as u32);
+    let window = (!headless).then(|| {
+        match slot {
+            Some(slot) => {
+                let (x, y) = slot.position(size);
+                Window::new_with_options(
+                    &title,
+                    WindowCreationOptions::new_windowed(
+                        WindowSize::PhysicalPixels(size.into()),
+                        Some(WindowPosition::PrimaryMonitorPixelsFromTopLeft(
+                            (x, y).into(),
+                        )),
+                    ),
+                )
+            }
+            None => Window::new_centered(title, size),
+        }
+        .unwrap()
+    });
+    if let (Some(window), Some(_)) = (&window, slot) {
+        windows::watch_supervisor(window.create_user_event_sender());
+    }
+
+    let mut win = MyWindowHandler {
+        scene,
         font,
         grabbed: false,
         mouse_x: 0.0,
         mouse_y: 0.0,
+        motor_left: false,
+        motor_right: false,
+        drawing_obstacle: false,
+        units: Units::default(),
+        calibration: Calibration::new(),
+        calibrating: false,
+        obstacles,
+        walls,
+        game,
+        tournament: None,
+        history: History::new(retention.history()),
+        retention,
+        strobe: Strobe::new(),
+        envelope: Envelope::new(envelope_opacity),
+        gravity_field,
+        impulse: ImpulseTest::new(),
+        poincare,
+        portrait,
+        sweep,
+        chart: StripChart::new(retention.chart(), chart_retention),
+        bindings,
+        paused: false,
+        grid: None,
+        menu: None,
+        size: WORLD_SIZE,
+        view: Viewport::new(letterbox),
+        batch: batched.then(Batch::new),
+        entry: None,
+        annotations: Annotations::new(),
+        console_reply: Vec::new(),
+        demo,
+        playlist,
+        outgoing: None,
+        phase: PhasePlot::new(),
+        sensor: SensorPanel::new(),
+        report,
+        vector_export,
+        trajectory: Trajectory::new(trajectory_export, HISTORY_LENGTH),
+        framing: Framing::new(),
+        focus: Focus::new(),
+        energy_dial,
+        warm_start,
+        trail: None,
+        metronome: Metronome::new(),
+        sync: SyncPattern::new(),
+        partition: EnergyPartition::new(),
+        sensitivity: SensitivityOverlay::new(),
+        damping: DampingExplorer::new(),
+        store,
+        results: ResultsPanel::new(),
+        profiles: Profiles::open(&Profiles::default_path()),
+        profiles_panel: ProfilesPanel::new(),
+        integrators: Integrators::builtin(),
+        accuracy: AccuracyDashboard::new(),
+        soft_start,
+        transitions,
+        transition: None,
+        macros,
+        help,
+        #[cfg(feature = "beat")]
+        beat,
+        escapement,
+        anaglyph,
+        instant_replay: InstantReplay::new(),
+        lfos: Lfos::new(),
+        energy_guard: EnergyGuard::new(),
+        energy_overlay: false,
+        energy_budget: false,
+        turning_points: false,
+        recovery_prompt: recovery && autosave.restore == Restore::Ask,
+        autosave,
+        modifiers: Modifiers::default(),
+        frame: 0,
+        tape: None,
+        replay,
+        remote: stdin_control.then(RemoteControl::start),
+        title: None,
     };
 
-    window.run_loop(win)
+    if let Some(log) = replaying {
+        win.start_tape(Tape::Playing { log, next: 0 });
+        run(window, win)
+    }
+
+    // Anything asked for on the command line, or the recovery prompt, goes
+    // ahead of the menu.
+    if start_menu
+        && !win.recovery_prompt
+        && card.is_none()
+        && !(recovery && win.autosave.restore == Restore::Always)
+        && warm_start_from.is_none()
+        && apparatus.is_none()
+        && tournament.is_none()
+        && !record
+    {
+        win.open_menu(false);
+    }
+
+    if let Some(card) = card {
+        let text = fs::read_to_string(&card).unwrap_or(card);
+        win.demo = None;
+        win.recovery_prompt = false;
+        if let Err(e) = win.import_card(&text) {
+            log!("--card: {}", e);
+        }
+    }
+
+    if recovery && win.autosave.restore == Restore::Always {
+        win.demo = None;
+        win.load_scene(Path::new(RECOVERY_FILE));
+    }
+
+    if let Some(path) = warm_start_from {
+        match Track::load(Path::new(&path)).and_then(|track| win.warm_start(&track)) {
+            Ok(report) => {
+                report.iter().for_each(|line| println!("{}", line));
+                win.console_reply = report;
+            }
+            Err(e) => log!("--warm-start: {}", e),
+        }
+    }
+
+    if let Some(id) = apparatus {
+        win.demo = None;
+        match win.attach_apparatus(&id) {
+            Ok(reply) => win.console_reply = vec![reply],
+            Err(e) => log!("--apparatus: {}", e),
+        }
+    }
+
+    if let Some(tournament) = tournament {
+        win.demo = None;
+        win.console_reply = vec![win.start_tournament(tournament)];
+    }
+
+    if record {
+        win.recovery_prompt = false;
+        let log = InputLog::new(config_text, win.session_text());
+        let file = win.replay.file.clone();
+        win.start_tape(Tape::Recording {
+            log,
+            file,
+            stopping: false,
+        });
+    }
+
+    run(window, win)
+}
+
+/// Hands over to the window's event loop, or runs headless without one.
+fn run(window: Option<Window>, win: MyWindowHandler) -> ! {
+    match window {
+        Some(window) => window.run_loop(win),
+        None => win.run_headless(),
+    }
+}
+
+/// The `[name]` section of the config read with `read`, or `default()` if
+/// there's no such section or it doesn't read, which is logged.
+fn load_section<T>(
+    config: Option<&Config>,
+    config_path: &Path,
+    name: &str,
+    read: impl FnOnce(&Section) -> Result<T, String>,
+    default: impl FnOnce() -> T,
+) -> T {
+    match config.and_then(|c| c.section(name)) {
+        Some(section) => read(section).unwrap_or_else(|e| {
+            log!("{}: [{}] {}", config_path.display(), name, e);
+            default()
+        }),
+        None => default(),
+    }
 }
 
 struct MyWindowHandler {
-    p: Pendulum,
+    scene: Scene,
     font: Font,
     grabbed: bool,
     mouse_x: f32,
     mouse_y: f32,
+    motor_left: bool,
+    motor_right: bool,
+    /// Held down, dragging draws an obstacle.
+    drawing_obstacle: bool,
+    /// What lengths are shown in.
+    units: Units,
+    calibration: Calibration,
+    /// Whether the calibrate key is held, so a drag lays the bar.
+    calibrating: bool,
+    obstacles: Obstacles,
+    walls: Walls,
+    game: FlagGame,
+    tournament: Option<Tournament>,
+    /// Step history of the selected pendulum.
+    history: History,
+    /// The memory the chart and the history share.
+    retention: Retention,
+    strobe: Strobe,
+    envelope: Envelope,
+    gravity_field: GravityField,
+    impulse: ImpulseTest,
+    poincare: PoincareExport,
+    portrait: PhasePortrait,
+    sweep: HysteresisSweep,
+    chart: StripChart,
+    bindings: Bindings,
+    paused: bool,
+    /// Small-multiples scan of the selected pendulum, shown instead of the
+    /// scene while open.
+    grid: Option<GridScan>,
+    /// The start menu, shown instead of everything else while open, with
+    /// the scene waiting behind it.
+    menu: Option<Launcher>,
+    /// Window size in pixels.
+    size: (f32, f32),
+    /// Where the world is drawn in the window.
+    view: Viewport,
+    /// Text being typed in, taking over the keyboard until submitted.
+    entry: Option<TextEntry>,
+    annotations: Annotations,
+    /// Output of the last console command, shown in the status lines.
+    console_reply: Vec<String>,
+    /// Running until the first key press or click.
+    demo: Option<DemoReel>,
+    playlist: Option<Playlist>,
+    /// The scene the playlist is fading out of.
+    outgoing: Option<Scene>,
+    autosave: Autosave,
+    /// A recovery file was found at startup and the next key decides whether
+    /// to restore it. Autosaving waits until then.
+    recovery_prompt: bool,
+    phase: PhasePlot,
+    sensor: SensorPanel,
+    report: LabReport,
+    vector_export: VectorExport,
+    /// The selected pendulum's steps since it was selected, for exporting.
+    trajectory: Trajectory,
+    framing: Framing,
+    focus: Focus,
+    energy_dial: EnergyDial,
+    warm_start: WarmStart,
+    /// The track a warm start took over from.
+    trail: Option<Trail>,
+    /// Where the scene is gathered to be drawn in one go, when it's drawn
+    /// batched.
+    batch: Option<Batch>,
+    metronome: Metronome,
+    sync: SyncPattern,
+    partition: EnergyPartition,
+    sensitivity: SensitivityOverlay,
+    damping: DampingExplorer,
+    /// Game scores and measurements from this and earlier sessions.
+    store: Store,
+    results: ResultsPanel,
+    profiles: Profiles,
+    profiles_panel: ProfilesPanel,
+    /// Schemes the accuracy dashboard compares.
+    integrators: Integrators,
+    accuracy: AccuracyDashboard,
+    soft_start: SoftStart,
+    transitions: Transitions,
+    /// Pendulums gliding into place after a reset or a preset load, holding
+    /// the physics until they're there.
+    transition: Option<Transition>,
+    macros: Macros,
+    help: HelpOverlay,
+    #[cfg(feature = "beat")]
+    beat: BeatDrive,
+    escapement: Escapement,
+    anaglyph: Anaglyph,
+    instant_replay: InstantReplay,
+    lfos: Lfos,
+    /// Always checking the selected pendulum; `energy_overlay` only decides
+    /// whether its shell is drawn.
+    energy_guard: EnergyGuard,
+    energy_overlay: bool,
+    /// Adds the selected pendulum's energy budget to the HUD.
+    energy_budget: bool,
+    /// Marks where the selected pendulum's swing will turn back.
+    turning_points: bool,
+    modifiers: Modifiers,
+    /// Frames begun, which is what inputs are recorded and replayed against.
+    frame: u64,
+    /// The inputs being recorded or replayed.
+    tape: Option<Tape>,
+    replay: ReplaySettings,
+    /// Commands read from stdin, with `--stdin-control`.
+    remote: Option<RemoteControl>,
+    /// Window title to set on the next frame, for changes made where the
+    /// window isn't at hand.
+    title: Option<String>,
 }
 
-impl WindowHandler for MyWindowHandler {
-    fn on_draw(&mut self, helper: &mut WindowHelper<()>, graphics: &mut Graphics2D) {
-        graphics.clear_screen(Color::from_rgb(0.8, 0.9, 1.0));
-        self.p.update();
-        if self.grabbed {
-            let diff = Vector::new(
-                self.p.origin.x - self.mouse_x,
-                self.p.origin.y - self.mouse_y,
-            );
+impl MyWindowHandler {
+    /// Ends everything that follows the selected pendulum: the game, the
+    /// history and the measurements. Called before the selection changes or
+    /// the pendulum is replaced.
+    fn forget_selected(&mut self) {
+        if let Some(p) = self.scene.selected_pendulum_mut() {
+            self.game.stop(p);
+        }
+        self.history.clear();
+        self.trajectory.clear();
+        self.instant_replay.dismiss();
+        self.impulse.cancel();
+        self.poincare.cancel();
+        self.sweep.cancel();
+        self.chart.buffer.clear();
+        self.phase.clear();
+        self.sensor.clear();
+        self.metronome.clear();
+        self.escapement.clear();
+        self.sync.clear();
+        self.partition.clear();
+        self.sensitivity.clear();
+        self.energy_guard.reset();
+    }
 
-            self.p.position.set(self.mouse_x, self.mouse_y);
-            self.p.r = ((self.p.position.x - self.p.origin.x).powi(2)
-                + (self.p.position.y - self.p.origin.y).powi(2))
-            .sqrt();
-            self.p.angular_acceleration = 0.0;
-            self.p.angular_velocity = 0.0;
-            self.p.angle = (-diff.y).atan2(diff.x) - PI / 2.0;
+    /// Puts `tournament` in place of any running, ending the round being
+    /// played.
+    fn start_tournament(&mut self, tournament: Tournament) -> String {
+        if let Some(p) = self.scene.selected_pendulum_mut() {
+            self.game.stop(p);
         }
-        self.p.draw(graphics, &self.font);
+        self.game.practice = false;
+        let reply = tournament.describe();
+        self.tournament = Some(tournament);
+        reply
+    }
 
-        helper.request_redraw();
+    /// Leaves the tournament, and the game goes back to its usual schedule.
+    fn end_tournament(&mut self) {
+        if let Some(p) = self.scene.selected_pendulum_mut() {
+            self.game.stop(p);
+        }
+        self.game.difficulty = Difficulty::standard();
+        self.tournament = None;
     }
 
-    fn on_key_down(
-        &mut self,
-        helper: &mut WindowHelper<()>,
-        virtual_key_code: Option<speedy2d::window::VirtualKeyCode>,
-        scancode: speedy2d::window::KeyScancode,
-    ) {
-        match scancode {
-            57416 => self.p.g += 0.1, // UP Arrow - Increase Gravity
-            57424 => self.p.g -= 0.1, // DOWN Arrow - Decrease Gravity
-            57419 => self.p.m -= 1.0, // LEFT Arrow - Decrease Mass
-            57421 => self.p.m += 1.0, // RIGHT Arrow - Increase Mass
-            19 => {
-                // R - Reset pendulum position
-                self.p.r = 200.0;
-                self.p.angle = 1.0;
+    fn select_next(&mut self) {
+        self.forget_selected();
+        self.scene.select_next();
+    }
+
+    /// Replaces the scene, clearing everything gathered from the old one,
+    /// which is handed back.
+    fn set_scene(&mut self, scene: Scene) -> Scene {
+        self.game.state = RoundState::Idle;
+        self.lfos.reset();
+        self.history.clear();
+        self.trajectory.clear();
+        self.instant_replay.dismiss();
+        self.impulse.cancel();
+        self.poincare.cancel();
+        self.sweep.cancel();
+        self.chart.buffer.clear();
+        self.strobe.clear();
+        self.envelope.clear();
+        self.phase.clear();
+        self.sensor.clear();
+        self.metronome.clear();
+        self.escapement.clear();
+        self.sync.clear();
+        self.partition.clear();
+        self.sensitivity.clear();
+        self.energy_guard.reset();
+        self.grabbed = false;
+        self.annotations.clear();
+        self.transition = None;
+        self.framing.reset(&mut self.view);
+        let old = std::mem::replace(&mut self.scene, scene);
+        self.soft_start.apply(&mut self.scene);
+        old
+    }
+
+    /// Replaces the scene with a preset's, gliding its pendulums in from
+    /// where the old ones were.
+    fn load_preset(&mut self, scene: Scene) {
+        self.trail = None;
+        let old = self.set_scene(scene);
+        self.transition = self.transitions.between(&old, &mut self.scene);
+    }
+
+    /// Loads a scene and the notes and settings saved with it.
+    fn load_scene(&mut self, path: &Path) {
+        self.trail = None;
+        if let Err(e) = Config::load(path).and_then(|config| self.load_config(&config)) {
+            log!("{}: {}", path.display(), e);
+        }
+    }
+
+    /// Replaces the session with a saved one: its scene, notes and settings.
+    fn load_config(&mut self, config: &Config) -> Result<(), String> {
+        let scene = Scene::from_config(config)?;
+        let annotations = match config.section("annotations") {
+            Some(section) => Annotations::from_config(section)?,
+            None => Annotations::new(),
+        };
+
+        let obstacles = match config.section("obstacles") {
+            Some(section) => {
+                Obstacles::from_config(section).map_err(|e| format!("[obstacles] {}", e))?
             }
-            _ => return,
+            None => Obstacles::new(),
+        };
+        let walls = match config.section("walls") {
+            Some(section) => Walls::from_config(section).map_err(|e| format!("[walls] {}", e))?,
+            None => Walls::new(),
+        };
+
+        let lfos = match config.section("lfo") {
+            Some(section) => Lfos::from_config(section).map_err(|e| format!("[lfo] {}", e))?,
+            None => Lfos::new(),
+        };
+        let macros = config.section("macros").map(Macros::from_config);
+        if let Some(macros) = macros {
+            self.macros = macros.map_err(|e| format!("[macros] {}", e))?;
+        }
+
+        self.set_scene(scene);
+        self.lfos = lfos;
+        self.annotations = annotations;
+        self.obstacles = obstacles;
+        self.walls = walls;
+        match config.section("settings") {
+            Some(section) => self
+                .apply_settings(section)
+                .map_err(|e| format!("[settings] {}", e)),
+            None => Ok(()),
         }
     }
 
-    fn on_mouse_move(&mut self, helper: &mut WindowHelper<()>, position: speedy2d::dimen::Vec2) {
-        self.mouse_x = position.x;
-        self.mouse_y = position.y;
+    /// Everything needed to pick the session up again: the scene, the notes,
+    /// the view settings and the macros.
+    fn session_text(&self) -> String {
+        self.scene.to_section()
+            + &self.annotations.to_section()
+            + &self.obstacles.to_section()
+            + &self.walls.to_section()
+            + &self.settings_section()
+            + &self.macros.to_section()
+            + &self.lfos.to_section()
     }
 
-    fn on_mouse_button_up(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
-        if button == MouseButton::Left {
-            if self.p.distance(&Vector {
-                x: self.mouse_x,
-                y: self.mouse_y,
-            }) < 28.0
-            {
-                self.grabbed = false;
-                self.p.angular_velocity = 0.0;
-            }
+    fn settings_section(&self) -> String {
+        let mut text = String::from("[settings]\n");
+        for (key, value) in [
+            ("paused", self.paused),
+            ("chart", self.chart.visible),
+            ("phase", self.phase.visible),
+            ("sensor", self.sensor.visible),
+            ("metronome", self.metronome.visible),
+            ("metronome_tick", self.metronome.tick),
+            ("energy_partition", self.partition.visible),
+            ("sensitivity", self.sensitivity.visible),
+            ("energy_overlay", self.energy_overlay),
+            ("energy_budget", self.energy_budget),
+            ("envelope", self.envelope.visible),
+            ("gravity_field", self.gravity_field.visible),
+            ("turning_points", self.turning_points),
+            ("soft_start", self.soft_start.enabled),
+            ("escapement", self.escapement.enabled),
+            ("letterbox", self.view.letterbox),
+            ("rhythm_colors", self.view.rhythm),
+            ("anaglyph", self.anaglyph.enabled),
+        ] {
+            text.push_str(&format!("{} = {}\n", key, value));
         }
+        text.push_str(&format!("selected = {}\n", self.scene.selected));
+        text.push_str(&format!(
+            "anaglyph_separation = {}\nanaglyph_convergence = {}\n",
+            self.anaglyph.separation, self.anaglyph.convergence
+        ));
+        if let Some(scale) = self.units.pixels_per_metre() {
+            text.push_str(&format!("pixels_per_metre = {}\n", scale));
+        }
+        text
     }
 
-    fn on_mouse_button_down(&mut self, helper: &mut WindowHelper, button: MouseButton) {
-        if button == MouseButton::Left {
-            if self.p.distance(&Vector {
-                x: self.mouse_x,
-                y: self.mouse_y,
-            }) < 28.0
-            {
-                self.grabbed = true;
+    fn apply_settings(&mut self, section: &Section) -> Result<(), String> {
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            let (key, value) = key_value(line)
+                .ok_or(error(format!("expected `key = value`, found `{}`", line)))?;
+
+            if key == "selected" {
+                let selected: usize = value
+                    .parse()
+                    .map_err(|_| error(format!("invalid model number `{}`", value)))?;
+                if selected < self.scene.models.len() {
+                    self.scene.selected = selected;
+                }
+                continue;
+            }
+            if key == "anaglyph_separation" {
+                let separation = parse_f32(key, value).map_err(error)?;
+                self.anaglyph.set_separation(separation).map_err(error)?;
+                continue;
+            }
+            if key == "anaglyph_convergence" {
+                let convergence = parse_f32(key, value).map_err(error)?;
+                self.anaglyph.set_convergence(convergence).map_err(error)?;
+                continue;
+            }
+            if key == "pixels_per_metre" {
+                self.units = parse_f32(key, value)
+                    .and_then(Units::per_metre)
+                    .map_err(error)?;
+                continue;
+            }
+
+            let value: bool = value
+                .parse()
+                .map_err(|_| error(format!("expected true or false, found `{}`", value)))?;
+            match key {
+                "paused" => self.paused = value,
+                "chart" => self.chart.visible = value,
+                "phase" => self.phase.visible = value,
+                "sensor" => self.sensor.visible = value,
+                "metronome" => self.metronome.visible = value,
+                "metronome_tick" => self.metronome.tick = value,
+                "energy_partition" => self.partition.visible = value,
+                "sensitivity" => self.sensitivity.visible = value,
+                "energy_overlay" => self.energy_overlay = value,
+                "energy_budget" => self.energy_budget = value,
+                "envelope" => self.envelope.visible = value,
+                "gravity_field" => self.gravity_field.visible = value,
+                "turning_points" => self.turning_points = value,
+                "soft_start" => {
+                    self.soft_start.enabled = value;
+                    self.soft_start.apply(&mut self.scene);
+                }
+                "escapement" => self.escapement.enabled = value,
+                "letterbox" => {
+                    self.view.letterbox = value;
+                    self.view.resize(self.size);
+                }
+                "rhythm_colors" => self.view.rhythm = value,
+                "anaglyph" => self.anaglyph.enabled = value,
+                _ => return Err(error(format!("unknown setting `{}`", key))),
             }
         }
+        Ok(())
     }
-}
 
-struct Pendulum {
-    origin: Vector,
+    /// Packs the session into a card, puts it on the clipboard and writes
+    /// it to `CARD_FILE`. With `initial`, the models are as they were loaded
+    /// rather than where they are now.
+    fn export_card(&self, initial: bool) -> Vec<String> {
+        let text = if initial {
+            self.scene.initial_section()
+                + &self.annotations.to_section()
+                + &self.obstacles.to_section()
+                + &self.walls.to_section()
+                + &self.settings_section()
+                + &self.macros.to_section()
+        } else {
+            self.session_text()
+        };
+        let card = card::encode(&text);
 
-    position: Vector,
+        let written =
+            fs::write(CARD_FILE, &card).map_err(|e| log!("could not write {}: {}", CARD_FILE, e));
+        let reply = match (card::copy_to_clipboard(&card), written.is_ok()) {
+            (true, true) => format!("Card copied and written to {}", CARD_FILE),
+            (true, false) => "Card copied".to_string(),
+            (false, true) => format!("No clipboard tool found: card written to {}", CARD_FILE),
+            (false, false) => "Could not copy or write the card".to_string(),
+        };
+        vec![format!("{} ({} characters)", reply, card.len())]
+    }
 
-    angle: f32,
+    fn import_card(&mut self, card: &str) -> Result<(), String> {
+        let text = card::decode(card)?;
+        self.trail = None;
+        self.load_config(&Config::parse(&text)?)
+    }
 
-    angular_velocity: f32,
-    angular_acceleration: f32,
+    /// Replaces the scene with a pendulum fitted to `track`, carrying on
+    /// from where it ends, and frames the two. Returns the fit's report.
+    fn warm_start(&mut self, track: &Track) -> Result<Vec<String>, String> {
+        let fit = self.warm_start.fit(track)?;
+        self.demo = None;
+        self.recovery_prompt = false;
+        self.set_scene(Scene::new(vec![ModelEntry::Pendulum(fit.entry.clone())]));
+        self.trail = Some(Trail::new(track));
+        self.title = Some("Pendulum - warm start".to_string());
+        self.frame_content();
+        Ok(fit.report(&self.units))
+    }
 
-    r: f32,
-    m: f32,
-    g: f32,
-}
+    /// Saves the scene with the notes, and exports the notes as CSV.
+    fn save_scene(&self, path: &Path) -> Result<(), String> {
+        let text = self.scene.to_section()
+            + &self.annotations.to_section()
+            + &self.obstacles.to_section()
+            + &self.walls.to_section();
+        fs::write(path, text).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        self.annotations.export_csv(Path::new(ANNOTATIONS_FILE))
+    }
 
-impl Pendulum {
-    fn new(x: f32, y: f32, r: f32) -> Pendulum {
-        Pendulum {
-            origin: Vector::new(x, y),
-            position: Vector::new(0.0, 0.0),
-            angle: 1.0,
-            angular_velocity: 0.0,
-            angular_acceleration: 0.0,
-            r,
-            m: 1.0,
-            g: 0.5,
-        }
+    /// Simulated time of the selected pendulum in seconds, the clock notes
+    /// and the strip chart run on.
+    fn current_time(&self) -> Option<f32> {
+        self.scene
+            .selected_pendulum()
+            .map(|p| p.time / STEPS_PER_SECOND)
     }
 
-    fn update(&mut self) {
-        let dumping = 0.995 - 0.0003 * self.m / 3.0;
+    /// Writes the lab report on the selected pendulum from what the sensor
+    /// has recorded of it.
+    fn write_report(&self) -> Result<String, String> {
+        let p = self.scene.selected_pendulum().ok_or(NO_PENDULUM)?;
+        let loaded = &self.scene.initial_entries()[self.scene.selected];
+        self.report
+            .write(p, loaded, &self.sensor.samples(), &self.units)
+    }
 
-        self.angular_acceleration = -self.g * self.angle.sin() / self.r;
+    /// Starts the camera moving to frame everything in the scene.
+    fn frame_content(&mut self) -> String {
+        match framing::content(&self.scene, &self.obstacles, &self.walls) {
+            Some(content) => {
+                self.framing.fit(&self.view, content);
+                "Framing the scene - Home to go back".to_string()
+            }
+            None => "Nothing to frame".to_string(),
+        }
+    }
 
-        self.angular_velocity += self.angular_acceleration;
+    /// Sets the selected pendulum up as the apparatus profile `id`.
+    fn attach_apparatus(&mut self, id: &str) -> Result<String, String> {
+        let profile = self
+            .profiles
+            .get(id)
+            .ok_or(format!("No apparatus called {}", id))?;
+        let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+        let reply = profile.apply(p, &mut self.units)?;
+        self.forget_selected();
+        // A real rod can be longer than the window at its scale.
+        self.frame_content();
+        Ok(reply)
+    }
 
-        self.angular_velocity *= dumping;
+    /// Saves the selected pendulum as the apparatus profile `id`.
+    fn save_apparatus(&mut self, id: &str, notes: &str) -> Result<String, String> {
+        let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+        let profile = Profile::of(id, p, &self.units, notes)?;
+        let reply = self.profiles.put(profile)?;
+        p.apparatus = id.to_string();
+        Ok(reply)
+    }
 
-        self.angle += self.angular_velocity;
+    /// Writes the last impulse test result on an apparatus back to its
+    /// profile.
+    fn write_back(&mut self) -> Result<String, String> {
+        let (id, measurement) = self
+            .profiles_panel
+            .pending
+            .clone()
+            .ok_or("No result to write back: run an impulse test on an apparatus")?;
+        let reply = self.profiles.record(&id, measurement)?;
+        self.profiles_panel.pending = None;
+        Ok(reply)
+    }
 
-        self.position
-            .set(self.r * self.angle.sin(), self.r * self.angle.cos());
+    fn profiles_click(&mut self, click: Click) {
+        let reply = match click {
+            Click::Taken => return,
+            Click::Attach(i) => {
+                let id = self.profiles.profiles[i].id.clone();
+                self.attach_apparatus(&id)
+            }
+            Click::SaveAs => {
+                self.entry = Some(TextEntry::new(EntryKind::Apparatus));
+                return;
+            }
+            Click::WriteBack => self.write_back(),
+        };
+        self.console_reply = vec![reply.unwrap_or_else(|e| e)];
+    }
 
-        self.position.add(&self.origin);
+    fn calibration_reply(&self) -> String {
+        match self.units.pixels_per_metre() {
+            Some(scale) => format!("Calibrated: 1 m is {:.1} pixels", scale),
+            None => "Not calibrated - hold the calibrate key and drag a bar".to_string(),
+        }
     }
 
-    fn draw(&mut self, graphics: &mut Graphics2D, font: &Font) {
-        graphics.draw_line(
-            (self.origin.x, self.origin.y),
-            (self.position.x, self.position.y),
-            3.0,
-            Color::GRAY,
-        );
+    /// Whether the history still reaches back to `time` seconds.
+    fn can_scrub_to(&self, time: f32) -> bool {
+        self.history.time_range().is_some_and(|(first, last)| {
+            (first..=last).contains(&(time * STEPS_PER_SECOND).round())
+        })
+    }
 
-        graphics.draw_text(
-            (0.0, 0.0),
-            Color::BLACK,
-            &font.layout_text(
-                format!("Gravity: {:.2}", self.g).as_str(),
-                30.0,
-                TextOptions::new(),
-            ),
-        );
+    fn submit_entry(&mut self, entry: TextEntry) {
+        match entry.kind {
+            EntryKind::Period => match entry.text.parse::<f32>() {
+                Ok(period) if period > 0.0 => {
+                    if let Some(p) = self.scene.selected_pendulum_mut() {
+                        p.set_period(period);
+                    }
+                }
+                _ => log!("not a period: `{}`", entry.text),
+            },
+            EntryKind::Note => {
+                if let (Some(time), false) = (self.current_time(), entry.text.is_empty()) {
+                    self.annotations.add(time, entry.text);
+                }
+            }
+            EntryKind::Command => {
+                self.macros.observe(Event::Command(entry.text.clone()));
+                self.console_reply = self.run_command(&entry.text).unwrap_or_else(|e| vec![e])
+            }
+            EntryKind::Tempo => match entry.text.parse::<f32>() {
+                Ok(bpm) if bpm > 0.0 => self.metronome.set_bpm(bpm),
+                _ => log!("not a tempo: `{}`", entry.text),
+            },
+            EntryKind::Calibration => {
+                let pixels = self.calibration.length().unwrap_or(0.0);
+                self.console_reply = vec![match units::parse_length(&entry.text)
+                    .and_then(|metres| Units::calibrated(pixels, metres))
+                {
+                    Ok(units) => {
+                        self.units = units;
+                        self.calibration_reply()
+                    }
+                    Err(e) => e,
+                }];
+                self.calibration.clear();
+            }
+            EntryKind::Apparatus => {
+                self.console_reply =
+                    vec![self.save_apparatus(&entry.text, "").unwrap_or_else(|e| e)];
+            }
+        }
+    }
 
-        graphics.draw_text(
-            (0.0, 30.0),
-            Color::BLACK,
-            &font.layout_text(
-                format!("Angle: {:.2}", self.angle).as_str(),
-                30.0,
-                TextOptions::new(),
-            ),
-        );
-        graphics.draw_text(
-            (0.0, 60.0),
-            Color::BLACK,
-            &font.layout_text(
-                format!("Acceleration: {:.2}", self.angular_acceleration * 10.0).as_str(),
-                30.0,
-                TextOptions::new(),
-            ),
-        );
-        graphics.draw_text(
-            (0.0, 90.0),
-            Color::BLACK,
-            &font.layout_text(
-                format!("Velocity: {:.2}", self.angular_velocity).as_str(),
-                30.0,
-                TextOptions::new(),
-            ),
-        );
-        graphics.draw_text(
-            (0.0, 120.0),
-            Color::BLACK,
-            &font.layout_text(
-                format!("Mass: {:.2}", self.m).as_str(),
-                30.0,
-                TextOptions::new(),
+    /// Runs a console command and returns its output.
+    fn run_command(&mut self, command: &str) -> Result<Vec<String>, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Ok(Vec::new()),
+            ["notes"] if self.annotations.notes.is_empty() => Ok(vec!["No notes".to_string()]),
+            ["notes"] => Ok(self
+                .annotations
+                .notes
+                .iter()
+                .enumerate()
+                .map(|(i, note)| format!("{}. {:.2} s: {}", i + 1, note.time, note.text))
+                .collect()),
+            ["delete", number] => match number.parse::<usize>() {
+                Ok(number) if number > 0 => match self.annotations.remove(number - 1) {
+                    Some(note) => Ok(vec![format!("Deleted note: {}", note.text)]),
+                    None => Err(format!("No note {}", number)),
+                },
+                _ => Err(format!("Not a note number: `{}`", number)),
+            },
+            ["poincare", rest @ ..] if rest.len() <= 1 => {
+                if let Some(points) = rest.first() {
+                    match points.parse::<usize>() {
+                        Ok(points) if points > 0 => self.poincare.target = points,
+                        _ => return Err(format!("Not a point count: `{}`", points)),
+                    }
+                }
+                let p = self.scene.selected_pendulum().ok_or(NO_PENDULUM)?;
+                self.poincare.start(p);
+                Ok(self.poincare.hud_line().into_iter().collect())
+            }
+            ["portrait"] => {
+                let ModelEntry::Pendulum(entry) = self.scene.selected().entry() else {
+                    return Err(NO_PENDULUM.to_string());
+                };
+                let bytes = self.portrait.export(&entry, Path::new(PORTRAIT_FILE))?;
+                Ok(vec![format!(
+                    "Phase portrait of {} trajectories written to {} ({} bytes)",
+                    self.portrait.trajectories(),
+                    PORTRAIT_FILE,
+                    bytes
+                )])
+            }
+            ["keys"] => {
+                let lines = self.bindings.cheat_sheet_text();
+                fs::write(KEYS_FILE, lines.join("\n") + "\n")
+                    .map_err(|e| format!("could not write {}: {}", KEYS_FILE, e))?;
+                Ok(vec![format!("Key bindings written to {}", KEYS_FILE)])
+            }
+            ["sensitivity"] => Ok(self.sensitivity.table()),
+            ["energy", "off"] => {
+                self.energy_dial.shown = false;
+                Ok(vec!["Energy fraction hidden".to_string()])
+            }
+            ["energy", fraction] => {
+                let fraction: f32 = fraction
+                    .parse()
+                    .map_err(|_| format!("Not a fraction: `{}`", fraction))?;
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                let reply = self.energy_dial.set(p, fraction)?;
+                self.energy_guard.reset();
+                Ok(vec![reply])
+            }
+            ["inputs"] => Ok(vec![match &self.tape {
+                Some(tape) => tape.status(self.frame),
+                None => "Not recording; start with --record".to_string(),
+            }]),
+            ["inputs", "stop"] => match &mut self.tape {
+                Some(Tape::Recording { stopping, .. }) => {
+                    *stopping = true;
+                    Ok(vec!["Stopping the recording at the next frame".to_string()])
+                }
+                // The recording's own stop, on its way through.
+                Some(Tape::Playing { .. }) => Ok(Vec::new()),
+                None => Err("Not recording".to_string()),
+            },
+            ["frame"] => Ok(vec![self.frame_content()]),
+            ["frame", "auto"] => {
+                self.framing.follow = true;
+                Ok(vec!["The camera follows the scene".to_string()])
+            }
+            ["frame", "off"] => {
+                self.framing.home(&self.view);
+                Ok(vec!["The camera is back on the whole world".to_string()])
+            }
+            ["calibration"] => Ok(vec![self.calibration_reply()]),
+            ["calibration", "off"] => {
+                self.units = Units::default();
+                Ok(vec!["Lengths back in pixels".to_string()])
+            }
+            ["macro", "list"] => Ok(self.macros.list()),
+            ["macro", "delete", number] => {
+                let number = number
+                    .parse()
+                    .map_err(|_| format!("Not a macro number: `{}`", number))?;
+                Ok(vec![self.macros.delete(number)?])
+            }
+            ["macro", "play", rest @ ..] if rest.len() <= 1 => {
+                let number = match rest.first() {
+                    Some(number) => Some(
+                        number
+                            .parse()
+                            .map_err(|_| format!("Not a macro number: `{}`", number))?,
+                    ),
+                    None => None,
+                };
+                Ok(vec![self.macros.play(number)?])
+            }
+            ["macro", "stop"] => {
+                self.macros.cancel_replay();
+                Ok(vec!["Stopped the macro".to_string()])
+            }
+            #[cfg(feature = "beat")]
+            ["beat", rest @ ..] => self.beat.command(rest),
+            ["apparatus"] if self.profiles.profiles.is_empty() => Ok(vec![format!(
+                "No apparatus profiles in {}",
+                self.profiles.path().display()
+            )]),
+            ["apparatus"] => Ok(self
+                .profiles
+                .profiles
+                .iter()
+                .map(|profile| format!("{}: {}", profile.id, profile.summary(&self.units)))
+                .collect()),
+            ["apparatus", "attach", id] => Ok(vec![self.attach_apparatus(id)?]),
+            ["apparatus", "save", id, notes @ ..] => {
+                Ok(vec![self.save_apparatus(id, &notes.join(" "))?])
+            }
+            ["apparatus", "write"] => Ok(vec![self.write_back()?]),
+            ["apparatus", "history", id] => Ok(self
+                .profiles
+                .get(id)
+                .ok_or(format!("No apparatus called {}", id))?
+                .history_lines()),
+            ["apparatus", "reload"] => {
+                let count = self.profiles.reload()?;
+                Ok(vec![format!("Read {} apparatus profiles", count)])
+            }
+            ["apparatus", ..] => Err(
+                "Usage: apparatus [attach <name>|save <name> [notes]|write|history <name>|reload]"
+                    .to_string(),
             ),
-        );
+            ["instant_replay", rest @ ..] => self.instant_replay.command(rest),
+            ["anaglyph", rest @ ..] => self.anaglyph.command(rest),
+            ["escapement", rest @ ..] => {
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                self.escapement.command(rest, p)
+            }
+            ["tournament", "off"] => {
+                self.end_tournament();
+                Ok(vec!["Tournament ended".to_string()])
+            }
+            ["tournament"] => Ok(vec![self.tournament.as_ref().map_or(
+                "No tournament: tournament <rounds> [seed]".to_string(),
+                |t| t.describe(),
+            )]),
+            ["tournament", rounds, seed @ ..] if seed.len() <= 1 => {
+                let rounds = tournament::parse_rounds(rounds)?;
+                let seed = match seed.first() {
+                    Some(seed) => tournament::parse_seed(seed)?,
+                    None => tournament::fresh_seed(),
+                };
+                Ok(vec![self.start_tournament(Tournament::new(seed, rounds))])
+            }
+            ["trajectory", rest @ ..] => self.trajectory.command(rest),
+            ["chart", "export", file @ ..] if file.len() <= 1 => {
+                let file = file.first().copied().unwrap_or(CHART_FILE);
+                Ok(vec![self.chart.buffer.export(Path::new(file))?])
+            }
+            ["retention"] => Ok(self.retention_lines()),
+            ["lfo", rest @ ..] => self.lfos.command(rest, &mut self.scene),
+            ["best", mode] => match self.store.best(mode) {
+                Some(best) => Ok(vec![format!(
+                    "Best {}: {:.1} s on {}",
+                    mode,
+                    best.value,
+                    format_timestamp(best.timestamp)
+                )]),
+                None => Ok(vec![format!("No {} rounds recorded", mode)]),
+            },
+            ["history", name] => {
+                let records = self.store.query(Kind::Measurement, name);
+                if records.is_empty() {
+                    return Ok(vec![format!("No {} measurements recorded", name)]);
+                }
+                // The console shows a few lines, so the latest ones.
+                Ok(records
+                    .iter()
+                    .rev()
+                    .take(5)
+                    .map(|record| {
+                        let damping = record
+                            .damping
+                            .map_or(String::new(), |d| format!(", zeta {:.4}", d));
+                        format!(
+                            "{}: {} {:.3}{} (r = {})",
+                            format_timestamp(record.timestamp),
+                            name,
+                            record.value,
+                            damping,
+                            self.units.length(record.parameter("r").unwrap_or(0.0))
+                        )
+                    })
+                    .collect())
+            }
+            ["predict", seconds] => {
+                let steps = match seconds.parse::<f32>() {
+                    Ok(seconds) if seconds > 0.0 => (seconds * STEPS_PER_SECOND).round().max(1.0),
+                    _ => return Err(format!("Not a duration: `{}`", seconds)),
+                };
+                let ModelEntry::Pendulum(entry) = self.scene.selected().entry() else {
+                    return Err(NO_PENDULUM.to_string());
+                };
+                // A copy runs ahead, leaving the one on screen alone.
+                let mut ahead = Pendulum::from_entry(&entry);
+                let (widest, last) = ahead
+                    .steps()
+                    .take(steps as usize)
+                    .fold((0.0f32, None), |(widest, _), state| {
+                        (widest.max(state.angle.abs()), Some(state))
+                    });
+                let Some(last) = last else {
+                    return Ok(Vec::new());
+                };
+                Ok(vec![format!(
+                    "In {:.2} s: angle {:.4}, angular velocity {:.5}, widest swing {:.4}",
+                    steps / STEPS_PER_SECOND,
+                    last.angle,
+                    last.angular_velocity,
+                    widest
+                )])
+            }
+            ["report"] => Ok(vec![self.write_report()?]),
+            ["svg"] => Ok(vec![self.export_svg()?]),
+            ["diff", file] | ["diff", file, "all"] => {
+                let all = words.len() == 3;
+                let now = Config::parse(&self.session_text())?;
+                let saved = Config::load(Path::new(file))?;
+                let differences = diff::diff(&now, &saved, all)?;
+                Ok(diff::report("now", file, &differences))
+            }
+            ["card"] => Ok(self.export_card(false)),
+            ["card", "initial"] => Ok(self.export_card(true)),
+            ["bpm"] => {
+                let mut lines = vec![format!(
+                    "Target {:.1} BPM, a swing period of {:.3} s",
+                    self.metronome.bpm(),
+                    self.metronome.target_period()
+                )];
+                if let Some(p) = self.scene.selected_pendulum() {
+                    lines.push(
+                        match metronome::tuned_length(p, self.metronome.target_period()) {
+                            Ok(r) => format!("Needs r = {}", self.units.length(r)),
+                            Err(e) => format!("Cannot tune: {}", e),
+                        },
+                    );
+                }
+                Ok(lines)
+            }
+            ["sync"] if self.sync.enabled => Ok(vec![self.sync.report()]),
+            ["sync"] | ["sync", "on"] => {
+                let g = self.scene.selected_pendulum().map_or(0.5, |p| p.g);
+                self.load_preset(SyncPattern::scene(g));
+                self.sync.enabled = true;
+                self.paused = false;
+                Ok(vec![
+                    "Sync pattern: the box flashes and the bell rings at each turning point"
+                        .to_string(),
+                ])
+            }
+            ["sync", "off"] => {
+                self.sync.enabled = false;
+                Ok(vec!["Sync pattern off".to_string()])
+            }
+            ["bpm", "tick", "on"] => {
+                self.metronome.tick = true;
+                Ok(vec!["The bell rings on each beat".to_string()])
+            }
+            ["bpm", "tick", "off"] => {
+                self.metronome.tick = false;
+                Ok(vec!["No bell on the beat".to_string()])
+            }
+            ["bpm", "tune"] => {
+                let period = self.metronome.target_period();
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                match metronome::tune(p, period) {
+                    Ok(()) => {
+                        let r = p.r;
+                        self.metronome.clear();
+                        self.energy_guard.reset();
+                        Ok(vec![format!(
+                            "Tuned to {:.1} BPM: r = {}",
+                            self.metronome.bpm(),
+                            self.units.length(r)
+                        )])
+                    }
+                    Err(e) => Err(format!("Cannot tune: {}", e)),
+                }
+            }
+            ["bpm", bpm] => match bpm.parse::<f32>() {
+                Ok(bpm) if bpm > 0.0 => {
+                    self.metronome.set_bpm(bpm);
+                    self.metronome.visible = true;
+                    Ok(vec![format!("Target {:.1} BPM", self.metronome.bpm())])
+                }
+                _ => Err(format!("Not a tempo: `{}`", bpm)),
+            },
+            ["rate"] => {
+                let p = self.scene.selected_pendulum().ok_or(NO_PENDULUM)?;
+                Ok(vec![format!("Rate: x{}", p.rate)])
+            }
+            ["rate", rate] => {
+                let rate = rate
+                    .trim_start_matches('x')
+                    .parse()
+                    .ok()
+                    .filter(|rate| (1..=MAX_RATE).contains(rate))
+                    .ok_or_else(|| format!("Not a rate from 1 to {}: `{}`", MAX_RATE, rate))?;
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                p.rate = rate;
+                Ok(vec![format!("Rate: x{}", rate)])
+            }
+            ["integrator"] => {
+                let p = self.scene.selected_pendulum().ok_or(NO_PENDULUM)?;
+                Ok(vec![format!("Integrator: {}", p.integrator.name())])
+            }
+            ["integrator", key] => {
+                let scheme = Scheme::parse(key).ok_or_else(|| {
+                    format!(
+                        "Unknown integrator `{}`: try euler, symplectic_euler, verlet, rk4 or rk45",
+                        key
+                    )
+                })?;
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                p.integrator = scheme;
+                Ok(vec![format!("Integrator: {}", scheme.name())])
+            }
+            ["set", name, value] => {
+                // As in `parse_f32`: NaN and infinity would poison the swing.
+                let value: f32 = value
+                    .parse()
+                    .ok()
+                    .filter(|value: &f32| value.is_finite())
+                    .ok_or_else(|| format!("Not a number: `{}`", value))?;
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                let mut state = p.snapshot();
+                match *name {
+                    "g" => state.g = presets::scene_gravity(value),
+                    "r" if value > 0.0 => state.r = value,
+                    "m" if value > 0.0 => state.m = value,
+                    "angle" => state.angle = value,
+                    "velocity" => state.angular_velocity = value / STEPS_PER_SECOND,
+                    "zeta" if value >= 0.0 => p.zeta = Some(value),
+                    "gravity_gradient" => p.gravity_gradient = value,
+                    "drive_amplitude" => p.drive_amplitude = value / STEPS_PER_SECOND.powi(2),
+                    "drive_frequency" if value >= 0.0 => p.retune_drive(value / STEPS_PER_SECOND),
+                    "coulomb_friction" if value >= 0.0 => {
+                        p.coulomb_friction = value / STEPS_PER_SECOND.powi(2)
+                    }
+                    "static_threshold" if value >= 0.0 => {
+                        p.static_threshold = value / STEPS_PER_SECOND.powi(2)
+                    }
+                    "r" | "m" | "zeta" | "drive_frequency" | "coulomb_friction"
+                    | "static_threshold" => return Err(format!("`{}` can't be {}", name, value)),
+                    _ => return Err(format!("Unknown setting `{}`", name)),
+                }
+                // A flight on a slack string would carry on from where it
+                // was, whatever was set.
+                state.slack = None;
+                p.restore(&state);
+                self.energy_guard.reset();
+                Ok(vec![format!("{} = {}", name, value)])
+            }
+            ["preset", number] => {
+                let preset = number
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| presets::PRESETS.get(number.wrapping_sub(1)))
+                    .ok_or(format!("No preset `{}`", number))?;
+                self.title = Some(format!("Pendulum - {}", preset.name));
+                self.load_preset(preset.scene());
+                Ok(vec![format!("Loaded {}", preset.name)])
+            }
+            ["impulse", velocity] => {
+                let velocity: f32 = velocity
+                    .parse()
+                    .ok()
+                    .filter(|velocity: &f32| velocity.is_finite())
+                    .ok_or_else(|| format!("Not an angular velocity: `{}`", velocity))?;
+                let p = self.scene.selected_pendulum_mut().ok_or(NO_PENDULUM)?;
+                p.angular_velocity += velocity / STEPS_PER_SECOND;
+                self.energy_guard.reset();
+                Ok(vec![format!("Kicked by {} rad/s", velocity)])
+            }
+            ["pause"] => {
+                self.paused = true;
+                Ok(vec!["Paused".to_string()])
+            }
+            ["resume"] => {
+                self.paused = false;
+                Ok(vec!["Running".to_string()])
+            }
+            ["query", name] => {
+                let value = self.query(name)?;
+                Ok(vec![format!("{} = {}", name, value)])
+            }
+            _ => Err(format!("Unknown command `{}`", command)),
+        }
+    }
+
+    /// A value of the selected pendulum in real units: seconds, radians and
+    /// m/s² for gravity.
+    fn query(&self, name: &str) -> Result<f32, String> {
+        if name == "paused" {
+            return Ok(if self.paused { 1.0 } else { 0.0 });
+        }
+        let p = self.scene.selected_pendulum().ok_or(NO_PENDULUM)?;
+        Ok(match name {
+            "angle" => p.angle,
+            "velocity" => p.angular_velocity * STEPS_PER_SECOND,
+            "energy" => energy::energy(p),
+            "time" => p.time / STEPS_PER_SECOND,
+            "g" => presets::real_gravity(p.g),
+            "r" => p.r,
+            "m" => p.m,
+            "period" => {
+                let amplitude = p.turning_angle();
+                if amplitude.is_infinite() {
+                    return Err("It's swinging over the top: there's no period".to_string());
+                }
+                p.period_at(amplitude)
+            }
+            _ => return Err(format!("Unknown quantity `{}`", name)),
+        })
+    }
+
+    /// Opens the start menu, with a tile back to the scene if `resume`.
+    fn open_menu(&mut self, resume: bool) {
+        self.grabbed = false;
+        self.clear_focus();
+        self.menu = Some(Launcher::new(
+            self.size,
+            resume,
+            &[Path::new(SAVE_FILE), Path::new(RECOVERY_FILE)],
+            &self.profiles,
+            &self.units,
+        ));
+    }
+
+    /// Starts what was picked in the menu and closes it.
+    fn choose(&mut self, helper: &mut WindowHelper<()>, choice: Choice) {
+        self.menu = None;
+        match choice {
+            Choice::Resume => (),
+            Choice::Preset(index) => {
+                let preset = &presets::PRESETS[index];
+                helper.set_title(format!("Pendulum - {}", preset.name));
+                self.load_preset(preset.scene());
+            }
+            Choice::Saved(path) => {
+                helper.set_title(format!("Pendulum - {}", path.display()));
+                self.load_scene(&path);
+            }
+            Choice::Apparatus(id) => {
+                helper.set_title(format!("Pendulum - {}", id));
+                self.set_scene(Scene::single());
+                self.console_reply = vec![self.attach_apparatus(&id).unwrap_or_else(|e| e)];
+            }
+        }
+    }
 
-        graphics.draw_circle((self.position.x, self.position.y), 28.0, Color::DARK_GRAY);
-        graphics.draw_circle((self.position.x, self.position.y), 25.0, Color::LIGHT_GRAY);
+    fn toggle_grid(&mut self) {
+        if self.grid.take().is_some() {
+            return;
+        }
+        if let ModelEntry::Pendulum(entry) = self.scene.selected().entry() {
+            self.grid = Some(GridScan::new(entry, self.size));
+        }
     }
 
-    fn distance(&mut self, other: &Vector) -> f32 {
-        ((self.position.x - other.x).powi(2) + (self.position.y - other.y).powi(2)).sqrt()
+    fn perform(&mut self, helper: &mut WindowHelper<()>, action: Action) {
+        // The grid covers the scene, so only the keys that affect it apply.
+        if self.grid.is_some()
+            && !matches!(
+                action,
+                Action::ToggleGrid | Action::TogglePause | Action::Focus
+            )
+        {
+            return;
+        }
+
+        self.macros.observe(Event::Action(action));
+        match action {
+            Action::SelectNext => self.select_next(),
+            Action::Reset => {
+                self.forget_selected();
+                self.strobe.clear();
+                self.envelope.clear();
+                self.grabbed = false;
+                let old = self.scene.selected_pendulum().map(Pose::of);
+                self.scene.reset_selected();
+                self.soft_start.apply_selected(&mut self.scene);
+                if let Some(old) = old {
+                    self.transition = self.transitions.reset(old, &mut self.scene);
+                }
+            }
+            Action::TogglePause => self.paused = !self.paused,
+            Action::ToggleGrid => self.toggle_grid(),
+            Action::Menu => self.open_menu(true),
+            Action::SetPeriod => {
+                if self.scene.selected_pendulum().is_some() {
+                    self.entry = Some(TextEntry::new(EntryKind::Period));
+                }
+            }
+            Action::Annotate => {
+                if self.current_time().is_some() {
+                    self.entry = Some(TextEntry::new(EntryKind::Note));
+                }
+            }
+            Action::Console => {
+                self.console_reply.clear();
+                self.entry = Some(TextEntry::new(EntryKind::Command));
+            }
+            Action::JumpToNote => {
+                let Some(note) = self
+                    .annotations
+                    .selected
+                    .map(|i| &self.annotations.notes[i])
+                else {
+                    return;
+                };
+                if let (Some(state), Some(p)) = (
+                    self.history.scrub_to(note.time * STEPS_PER_SECOND),
+                    self.scene.selected_pendulum_mut(),
+                ) {
+                    p.restore(&state);
+                    self.energy_guard.reset();
+                    self.lfos.rewound();
+                }
+            }
+            Action::ToggleChart => self.chart.visible = !self.chart.visible,
+            Action::TogglePhase => self.phase.visible = !self.phase.visible,
+            Action::ToggleSensor => self.sensor.visible = !self.sensor.visible,
+            Action::ToggleMetronome => self.metronome.visible = !self.metronome.visible,
+            Action::SlowerTempo => self.metronome.set_bpm(self.metronome.bpm() - 1.0),
+            Action::FasterTempo => self.metronome.set_bpm(self.metronome.bpm() + 1.0),
+            Action::DampingExplorer => self.damping.toggle(),
+            Action::ResultsPanel => self.results.visible = !self.results.visible,
+            Action::ApparatusPanel => self.profiles_panel.visible = !self.profiles_panel.visible,
+            Action::AccuracyDashboard => self.accuracy.toggle(self.scene.selected_pendulum()),
+            Action::SoftStart => {
+                self.soft_start.enabled = !self.soft_start.enabled;
+                self.soft_start.apply(&mut self.scene);
+            }
+            Action::ToggleEnergy => self.energy_overlay = !self.energy_overlay,
+            Action::ToggleWalls => self.walls.enabled = !self.walls.enabled,
+            Action::Calibrate => self.calibrating = true,
+            Action::Frame => self.framing.home(&self.view),
+            Action::EnergyBudget => self.energy_budget = !self.energy_budget,
+            Action::ToggleEnvelope => self.envelope.toggle(),
+            Action::GravityField => self.gravity_field.visible = !self.gravity_field.visible,
+            Action::TurningPoints => self.turning_points = !self.turning_points,
+            Action::RhythmColors => self.view.rhythm = !self.view.rhythm,
+            Action::EnergyPartition => self.partition.visible = !self.partition.visible,
+            Action::Sensitivity => self.sensitivity.visible = !self.sensitivity.visible,
+            Action::ExportReport => {
+                self.console_reply = vec![self.write_report().unwrap_or_else(|e| e)];
+            }
+            Action::ExportSvg => {
+                self.console_reply = vec![self.export_svg().unwrap_or_else(|e| e)];
+            }
+            Action::ExportTrajectory if self.modifiers.shift() => {
+                if self.trajectory.cancel() {
+                    self.console_reply = vec!["Cancelling the refinement".to_string()];
+                }
+            }
+            Action::ExportTrajectory => {
+                let p = self
+                    .scene
+                    .selected_pendulum()
+                    .ok_or(NO_PENDULUM.to_string());
+                self.console_reply = vec![p
+                    .and_then(|p| {
+                        let label = self.scene.selected().label(&self.units);
+                        self.trajectory.export(p, &label)
+                    })
+                    .unwrap_or_else(|e| e)];
+            }
+            Action::ReplayMacro => {
+                self.console_reply = vec![self.macros.play(None).unwrap_or_else(|e| e)];
+            }
+            Action::Help => self.help.advance(&self.bindings, &self.focusables()),
+            Action::Focus => self.move_focus(self.modifiers.shift()),
+            Action::StrobeSource => self.strobe.cycle_source(),
+            Action::StrobeCopies => self.strobe.cycle_copies(),
+            Action::SaveScene => {
+                if let Err(e) = self.save_scene(Path::new(SAVE_FILE)) {
+                    log!("{}", e);
+                }
+            }
+            Action::LoadScene => self.load_scene(Path::new(SAVE_FILE)),
+            Action::Preset(index) => {
+                if let Some(preset) = presets::PRESETS.get(index) {
+                    helper.set_title(format!("Pendulum - {}", preset.name));
+                    self.load_preset(preset.scene());
+                }
+            }
+            Action::MotorLeft => self.motor_left = true,
+            Action::DrawObstacle => self.drawing_obstacle = true,
+            Action::MotorRight => self.motor_right = true,
+            Action::PracticeMode => {
+                if self.game.state != RoundState::Running && self.tournament.is_none() {
+                    self.game.practice = !self.game.practice;
+                }
+            }
+            Action::InstantReplay if self.modifiers.shift() => self.instant_replay.dismiss(),
+            Action::InstantReplay => {
+                let p = self
+                    .scene
+                    .selected_pendulum()
+                    .ok_or(NO_PENDULUM.to_string());
+                self.console_reply = vec![p
+                    .and_then(|p| self.instant_replay.start(p, &self.history.states()))
+                    .unwrap_or_else(|e| e)];
+            }
+            Action::ToggleScrub => {
+                if self.history.is_scrubbing() {
+                    self.history.stop_scrub();
+                } else if let (Some(state), Some(p)) = (
+                    self.history.start_scrub(),
+                    self.scene.selected_pendulum_mut(),
+                ) {
+                    p.restore(&state);
+                    self.energy_guard.reset();
+                    self.lfos.rewound();
+                }
+            }
+            _ => {
+                let Some(p) = self.scene.selected_pendulum_mut() else {
+                    return;
+                };
+
+                match action {
+                    Action::IncreaseGravity => p.g += 0.1,
+                    Action::DecreaseGravity => p.g -= 0.1,
+                    Action::DecreaseMass => p.m -= 1.0,
+                    Action::IncreaseMass => p.m += 1.0,
+                    Action::ToggleFriction => {
+                        p.damping_model = match p.damping_model {
+                            DampingModel::Viscous => DampingModel::Coulomb,
+                            DampingModel::Coulomb => DampingModel::Viscous,
+                        }
+                    }
+                    Action::CycleBob => p.bob = p.bob.next(),
+                    Action::CycleIntegrator => p.integrator = p.integrator.next(),
+                    Action::CycleRate => p.cycle_rate(),
+                    Action::ToggleString => p.set_string(!p.string),
+                    Action::CycleDamping => p.cycle_regime(),
+                    Action::Impulse => {
+                        self.impulse.fire(p);
+                        self.energy_guard.reset();
+                    }
+                    Action::PoincareExport => self.poincare.start(p),
+                    Action::LowerEnergy | Action::RaiseEnergy => {
+                        let steps = if action == Action::RaiseEnergy { 1 } else { -1 };
+                        self.console_reply =
+                            vec![self.energy_dial.nudge(p, steps).unwrap_or_else(|e| e)];
+                        self.energy_guard.reset();
+                    }
+                    Action::HysteresisSweep => match self.sweep.state {
+                        SweepState::Running => self.sweep.cancel(),
+                        _ => self.sweep.start(p),
+                    },
+                    Action::FlagGame => match &self.tournament {
+                        Some(_) if self.game.state == RoundState::Running => {
+                            self.console_reply =
+                                vec!["A tournament round runs until the bob leaves the flags"
+                                    .to_string()];
+                        }
+                        Some(tournament) => match tournament.next_round() {
+                            Some((_, difficulty)) => {
+                                self.game.difficulty = difficulty;
+                                self.game.start(p);
+                            }
+                            None => {
+                                self.game.stop(p);
+                                self.game.difficulty = Difficulty::standard();
+                                self.tournament = None;
+                            }
+                        },
+                        None if self.game.state == RoundState::Running => self.game.stop(p),
+                        None => self.game.start(p),
+                    },
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Ends the demo reel, leaving its current scene running. Returns true
+    /// if it was playing.
+    fn key_down(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        virtual_key_code: Option<VirtualKeyCode>,
+    ) {
+        if self.recovery_prompt {
+            self.recovery_prompt = false;
+            if virtual_key_code == Some(VirtualKeyCode::Y) {
+                self.demo = None;
+                helper.set_title("Pendulum");
+                self.load_scene(Path::new(RECOVERY_FILE));
+            }
+            return;
+        }
+
+        if self.stop_demo(helper) {
+            return;
+        }
+        if let Some(playlist) = &mut self.playlist {
+            playlist.interact();
+        }
+
+        if let Some(entry) = &mut self.entry {
+            match virtual_key_code {
+                Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) => {
+                    if let Some(entry) = self.entry.take() {
+                        self.submit_entry(entry);
+                    }
+                }
+                Some(VirtualKeyCode::Escape) => {
+                    self.entry = None;
+                    self.calibration.clear();
+                }
+                Some(VirtualKeyCode::Backspace) => entry.backspace(),
+                _ => (),
+            }
+            return;
+        }
+
+        if let Some(menu) = &mut self.menu {
+            if let Some(choice) = virtual_key_code.and_then(|key| menu.key(key)) {
+                self.choose(helper, choice);
+            }
+            return;
+        }
+
+        if self.focus_key(helper, virtual_key_code) {
+            return;
+        }
+
+        if self.modifiers.ctrl() && self.modifiers.shift() {
+            match virtual_key_code {
+                Some(VirtualKeyCode::C) => {
+                    self.console_reply = self.export_card(false);
+                    return;
+                }
+                Some(VirtualKeyCode::V) => {
+                    let card = card::read_clipboard()
+                        .filter(|text| text.trim_start().starts_with("pendulum-card-"))
+                        .or_else(|| fs::read_to_string(CARD_FILE).ok());
+                    self.console_reply = match card.map(|card| self.import_card(&card)) {
+                        Some(Ok(())) => {
+                            helper.set_title("Pendulum");
+                            vec!["Card loaded".to_string()]
+                        }
+                        Some(Err(e)) => vec![format!("Cannot load the card: {}", e)],
+                        None => vec!["No card on the clipboard or in the card file".to_string()],
+                    };
+                    return;
+                }
+                _ => (),
+            }
+        }
+
+        if !self.modifiers.ctrl()
+            && virtual_key_code.is_some_and(|key| self.scene.selected_mut().key(key))
+        {
+            return;
+        }
+
+        let Some(action) = virtual_key_code.and_then(|key| self.bindings.action(key)) else {
+            return;
+        };
+
+        if action == Action::Frame && self.modifiers.shift() {
+            self.console_reply = vec![self.frame_content()];
+            return;
+        }
+
+        if action == Action::ReplayMacro && self.modifiers.ctrl() {
+            self.console_reply = vec![self.macros.toggle_recording().unwrap_or_else(|e| e)];
+            return;
+        }
+
+        // While scrubbing, the gravity and mass keys move through the history
+        // instead: left/right by a step, up/down by a second.
+        if self.history.is_scrubbing() {
+            let steps = match action {
+                Action::DecreaseMass => -1,
+                Action::IncreaseMass => 1,
+                Action::DecreaseGravity => -(STEPS_PER_SECOND as isize),
+                Action::IncreaseGravity => STEPS_PER_SECOND as isize,
+                _ => 0,
+            };
+            if steps != 0 {
+                if let (Some(state), Some(p)) = (
+                    self.history.scrub(steps),
+                    self.scene.selected_pendulum_mut(),
+                ) {
+                    p.restore(&state);
+                    self.energy_guard.reset();
+                    self.lfos.rewound();
+                }
+                return;
+            }
+        }
+
+        self.perform(helper, action);
+    }
+
+    fn key_up(&mut self, virtual_key_code: Option<VirtualKeyCode>) {
+        match virtual_key_code.and_then(|key| self.bindings.action(key)) {
+            Some(Action::MotorLeft) => self.motor_left = false,
+            Some(Action::MotorRight) => self.motor_right = false,
+            Some(Action::DrawObstacle) => self.drawing_obstacle = false,
+            Some(Action::Calibrate) => self.calibrating = false,
+            _ => (),
+        }
+    }
+
+    fn typed(&mut self, unicode_codepoint: char) {
+        if let Some(entry) = &mut self.entry {
+            entry.push(unicode_codepoint);
+        }
+    }
+
+    /// Takes an input from the window, logging it while recording. While a
+    /// replay plays, the window is ignored and the log's inputs come in
+    /// through `apply` instead.
+    fn input(&mut self, helper: &mut WindowHelper<()>, input: Input) {
+        if let Some(tape) = &mut self.tape {
+            if tape.is_playing() {
+                return;
+            }
+            tape.record(self.frame, &input);
+        }
+        self.apply(helper, input);
+    }
+
+    fn apply(&mut self, helper: &mut WindowHelper<()>, input: Input) {
+        match input {
+            Input::KeyDown(key) => self.key_down(helper, key),
+            Input::KeyUp(key) => self.key_up(key),
+            Input::Char(c) => self.typed(c),
+            Input::Modifiers(modifiers) => self.modifiers = modifiers,
+            Input::Move(x, y) => {
+                self.clear_focus();
+                self.pointer_moved(x, y);
+            }
+            Input::Down(button) => {
+                self.clear_focus();
+                self.press(helper, button);
+            }
+            Input::Up(button) => self.let_go(button),
+            Input::Wheel(lines) => self.wheel(lines),
+            Input::Resize(width, height) => self.resized((width, height)),
+            Input::Command(command) => {
+                let _ = self.run_command(&command);
+            }
+        }
+    }
+
+    fn resized(&mut self, size: (f32, f32)) {
+        self.size = size;
+        self.view.resize(self.size);
+        if let Some(grid) = &mut self.grid {
+            grid.resize(self.size);
+        }
+        if let Some(menu) = &mut self.menu {
+            menu.resize(self.size);
+        }
+    }
+
+    /// Starts recording or replaying from the session `tape`'s log begins
+    /// with, loaded afresh, so that a recording and its replays start from
+    /// the same state.
+    fn start_tape(&mut self, tape: Tape) {
+        let (Tape::Recording { log, .. } | Tape::Playing { log, .. }) = &tape;
+        if let Err(e) = Config::parse(&log.seed).and_then(|config| self.load_config(&config)) {
+            log!("The recording's session: {}", e);
+        }
+        self.demo = None;
+        self.menu = None;
+        self.title = Some("Pendulum".to_string());
+        self.tape = Some(tape);
+    }
+
+    /// Feeds in the replay's inputs that came before `frame`, then ends a
+    /// recording asked to stop or a replay that's all fed in, there where
+    /// the two line up.
+    /// Runs the commands the remote control has read since the last frame.
+    /// They land between steps, like keys do, and are recorded as they come;
+    /// a replay brings its own.
+    fn take_remote_commands(&mut self, frame: u64) {
+        for incoming in self
+            .remote
+            .as_ref()
+            .map_or(Vec::new(), RemoteControl::pending)
+        {
+            let (line, command) = match incoming {
+                Incoming::Command(line, command) => (line, command),
+                Incoming::Refused(line, command, e) => {
+                    remote::respond(line, &command, Err(e), None);
+                    continue;
+                }
+            };
+            if self.tape.as_ref().is_some_and(Tape::is_playing) {
+                remote::respond(line, &command, Err("Replaying".to_string()), None);
+                continue;
+            }
+            if let Some(tape) = &mut self.tape {
+                tape.record(frame, &Input::Command(command.clone()));
+            }
+            let value = match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["query", name] => self.query(name).ok(),
+                _ => None,
+            };
+            remote::respond(line, &command, self.run_command(&command), value);
+        }
+    }
+
+    /// One step of the scene and everything that watches it, unless paused.
+    fn step_simulation(&mut self) {
+        let motor_torque = match (self.motor_left, self.motor_right) {
+            (true, false) => -MOTOR_TORQUE,
+            (false, true) => MOTOR_TORQUE,
+            _ => 0.0,
+        };
+        if let Some(p) = self.scene.selected_pendulum_mut() {
+            p.motor_torque = motor_torque;
+        }
+
+        if let Some(transition) = &mut self.transition {
+            if transition.step(&mut self.scene) {
+                self.transition = None;
+                self.energy_guard.reset();
+            }
+        }
+        let running = !self.paused && !self.history.is_scrubbing() && self.transition.is_none();
+        // Whether the selected pendulum hit an obstacle or wall this step.
+        let mut hit = false;
+        if running {
+            if let Some(p) = self.scene.selected_pendulum_mut() {
+                self.game.before_update(p);
+                #[cfg(feature = "beat")]
+                if self.beat.step(p) {
+                    self.energy_guard.reset();
+                }
+            }
+            self.lfos.step(&mut self.scene);
+            let before: Vec<_> = self
+                .scene
+                .models
+                .iter()
+                .map(|model| model.as_pendulum().map(|p| p.position))
+                .collect();
+            self.scene.step();
+            for (i, (model, before)) in self.scene.models.iter_mut().zip(before).enumerate() {
+                if let (Some(p), Some(before)) = (model.as_pendulum_mut(), before) {
+                    let collided =
+                        self.obstacles.collide(p, before) || self.walls.collide(p, before);
+                    hit |= collided && i == self.scene.selected;
+                }
+            }
+            if let Some(outgoing) = &mut self.outgoing {
+                outgoing.step();
+            }
+            if let Some(p) = self.scene.selected_pendulum_mut() {
+                if self.escapement.step(p) {
+                    self.energy_guard.reset();
+                }
+            }
+            if let Some(p) = self.scene.selected_pendulum_mut() {
+                if self.game.after_update(p) {
+                    if let Some(tournament) = &mut self.tournament {
+                        self.console_reply = vec![tournament.finish_round(self.game.last_score())];
+                        if tournament.is_over() {
+                            self.store.add(tournament.record());
+                        }
+                    } else {
+                        let mode = if self.game.practice {
+                            "practice"
+                        } else {
+                            "flag"
+                        };
+                        self.store.add(Record::new(
+                            Kind::Game,
+                            mode,
+                            self.game.last_score(),
+                            &[("r", p.r), ("m", p.m)],
+                        ));
+                    }
+                }
+            }
+        }
+        if self.grabbed {
+            let mouse = self.view.screen_to_world(self.mouse_x, self.mouse_y);
+            self.scene.models[self.scene.selected].drag_to(mouse);
+        }
+        if running {
+            if let Some(p) = self.scene.selected_pendulum() {
+                self.history.record(p.snapshot());
+                self.trajectory.record(p, hit || self.grabbed);
+            }
+            self.strobe.observe(&self.scene);
+            self.envelope.record(&self.scene);
+            self.damping.step();
+            if let Some(p) = self.scene.selected_pendulum_mut() {
+                self.sweep.record(p, Path::new(SWEEP_FILE));
+            }
+            if let Some(p) = self.scene.selected_pendulum() {
+                if let Some((frequency, damping)) = self.impulse.record(p, Path::new(IMPULSE_FILE))
+                {
+                    if !p.apparatus.is_empty() {
+                        self.profiles_panel.pending = Some((
+                            p.apparatus.clone(),
+                            Measurement::new(1.0 / frequency, Some(damping)),
+                        ));
+                    }
+                    self.store.add(Record {
+                        damping: Some(damping),
+                        ..Record::new(
+                            Kind::Measurement,
+                            "period",
+                            1.0 / frequency,
+                            &[("r", p.r), ("m", p.m), ("g", p.g)],
+                        )
+                    });
+                }
+                self.poincare.record(p, Path::new(POINCARE_FILE));
+                self.chart.buffer.push(p.time / STEPS_PER_SECOND, p.angle);
+                self.phase.record(p);
+                self.sensor.record(p);
+                self.metronome.record(p);
+                self.sync.record(p);
+                self.partition.record(p);
+                self.sensitivity.record(p);
+
+                let driven = self.grabbed
+                    || p.motor_torque != 0.0
+                    || p.drive_amplitude != 0.0
+                    || self.lfos.is_active();
+                self.energy_guard.observe(p, driven);
+            }
+        }
+    }
+
+    /// Runs without a window, for `--headless`: takes remote commands and
+    /// steps at the window's pace until stdin has ended and the last
+    /// command is answered.
+    fn run_headless(mut self) -> ! {
+        let tick = Duration::from_secs_f32(1.0 / STEPS_PER_SECOND);
+        loop {
+            let started = Instant::now();
+            let frame = self.frame;
+            self.frame += 1;
+            let closed = self.remote.as_ref().is_none_or(RemoteControl::is_closed);
+            self.take_remote_commands(frame);
+            if closed {
+                // Drops the store, which writes out what's queued.
+                drop(self);
+                process::exit(0);
+            }
+            self.step_simulation();
+            thread::sleep(tick.saturating_sub(started.elapsed()));
+        }
+    }
+
+    fn run_tape(&mut self, helper: &mut WindowHelper<()>, frame: u64) {
+        let due = self
+            .tape
+            .as_mut()
+            .map_or(Vec::new(), |tape| tape.due(frame));
+        for input in due {
+            if let Input::Resize(width, height) = input {
+                helper.set_size_pixels((width as u32, height as u32));
+            }
+            self.apply(helper, input);
+        }
+
+        let models: Vec<Vec<f64>> = self.scene.models.iter().map(|m| m.state()).collect();
+        match self.tape.take() {
+            Some(tape) if tape.is_stopping() => {
+                self.console_reply = vec![tape.stop(frame, models).unwrap_or_else(|e| e)];
+            }
+            Some(tape) if tape.is_finished(frame) => {
+                let labels: Vec<String> = self
+                    .scene
+                    .models
+                    .iter()
+                    .map(|model| model.label(&self.units))
+                    .collect();
+                let (report, matched) = tape.check(&models, &labels);
+                report.iter().for_each(|line| println!("{}", line));
+                if self.replay.exit {
+                    process::exit(if matched { 0 } else { 1 });
+                }
+                self.console_reply = report;
+            }
+            tape => self.tape = tape,
+        }
+    }
+
+    fn stop_demo(&mut self, helper: &mut WindowHelper<()>) -> bool {
+        let Some(preset) = self.demo.take().and_then(|demo| demo.current()) else {
+            return false;
+        };
+        helper.set_title(format!("Pendulum - {}", preset.name));
+        true
+    }
+
+    /// What the mouse does, whichever moved it: the mouse itself, or the
+    /// keys with something focused.
+    fn pointer_moved(&mut self, x: f32, y: f32) {
+        self.mouse_x = x;
+        self.mouse_y = y;
+        if let Some(playlist) = &mut self.playlist {
+            playlist.interact();
+        }
+        self.chart.mouse_move(x, y);
+        self.results.mouse_move(x, y);
+        if let Some(p) = self.scene.selected_pendulum_mut() {
+            self.damping.mouse_move(x, p);
+        }
+        if let Some(grid) = &mut self.grid {
+            grid.mouse_move(x, y);
+        }
+        if let Some(menu) = &mut self.menu {
+            menu.mouse_move(x, y);
+        }
+        let mouse = self.view.screen_to_world(x, y);
+        self.obstacles.drag_to(mouse);
+        self.calibration.drag_to(mouse);
+    }
+
+    fn press(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        if let Some(playlist) = &mut self.playlist {
+            playlist.interact();
+        }
+        if self.stop_demo(helper) {
+            return;
+        }
+        if let Some(menu) = &self.menu {
+            if let Some(choice) = menu
+                .click(self.mouse_x, self.mouse_y)
+                .filter(|_| button == MouseButton::Left)
+            {
+                self.choose(helper, choice);
+            }
+            return;
+        }
+        let mouse = self.view.screen_to_world(self.mouse_x, self.mouse_y);
+        if button == MouseButton::Right {
+            self.obstacles.remove_at(mouse);
+            return;
+        }
+        if button != MouseButton::Left {
+            return;
+        }
+        if self.calibrating {
+            self.calibration.start(mouse);
+            return;
+        }
+        if self.drawing_obstacle {
+            if let Err(e) = self.obstacles.start_drawing(mouse) {
+                self.console_reply = vec![e];
+            }
+            return;
+        }
+
+        // Clicking a tile promotes its initial conditions to the pendulum
+        // the grid was made from.
+        if let Some(grid) = &self.grid {
+            if let Some(entry) = grid.click(self.mouse_x, self.mouse_y) {
+                self.forget_selected();
+                self.strobe.clear();
+                self.envelope.clear();
+                self.scene.models[self.scene.selected] = Box::new(Pendulum::from_entry(&entry));
+                self.grid = None;
+            }
+            return;
+        }
+
+        if self.results.mouse_down(self.mouse_x, self.mouse_y) {
+            return;
+        }
+        if let Some(click) =
+            self.profiles_panel
+                .mouse_down(self.mouse_x, self.mouse_y, &self.profiles)
+        {
+            self.profiles_click(click);
+            return;
+        }
+        if let Some(i) = self
+            .chart
+            .marker_at(self.mouse_x, self.mouse_y, &self.annotations)
+        {
+            self.annotations.selected = Some(i);
+            return;
+        }
+        if self.chart.mouse_down(self.mouse_x, self.mouse_y) {
+            return;
+        }
+        if let Some(p) = self.scene.selected_pendulum_mut() {
+            if self.damping.mouse_down(self.mouse_x, self.mouse_y, p) {
+                return;
+            }
+            if self.metronome.tempo_at(self.mouse_x, self.mouse_y) {
+                self.entry = Some(TextEntry::new(EntryKind::Tempo));
+                return;
+            }
+            if self.metronome.mouse_down(self.mouse_x, self.mouse_y, p) {
+                self.energy_guard.reset();
+                return;
+            }
+        }
+
+        // Topmost (last drawn) model first, then the obstacles under them.
+        let Some(index) = self
+            .scene
+            .models
+            .iter_mut()
+            .rposition(|model| model.grab(mouse))
+        else {
+            self.obstacles.grab(mouse);
+            return;
+        };
+
+        if let Some(transition) = self.transition.take() {
+            transition.cancel(&mut self.scene);
+        }
+        if index != self.scene.selected {
+            self.forget_selected();
+            self.scene.selected = index;
+        }
+        self.grabbed = true;
+    }
+
+    fn let_go(&mut self, button: MouseButton) {
+        if button == MouseButton::Left && (self.chart.mouse_up() || self.damping.mouse_up()) {
+            return;
+        }
+        let mouse = self.view.screen_to_world(self.mouse_x, self.mouse_y);
+        if button == MouseButton::Left && self.calibration.finish() {
+            self.entry = Some(TextEntry::new(EntryKind::Calibration));
+            return;
+        }
+        if button == MouseButton::Left
+            && (self.obstacles.finish_drawing(mouse) || self.obstacles.release())
+        {
+            return;
+        }
+        if button == MouseButton::Left && self.grabbed {
+            self.grabbed = false;
+            self.scene.models[self.scene.selected].release();
+        }
+    }
+
+    fn wheel(&mut self, lines: f32) {
+        if !self.results.scroll(lines, &self.store)
+            && !self
+                .profiles_panel
+                .scroll(lines, self.mouse_x, self.mouse_y, &self.profiles)
+        {
+            self.chart.scroll(lines);
+        }
+    }
+
+    /// Everything there is to focus now, in the order the focus key steps
+    /// through it: each model's pivot and handles, the obstacles, the open
+    /// panels' controls, and the free pointer. Only the tiles while the grid
+    /// covers the scene.
+    fn focusables(&self) -> Registry {
+        let mut registry = Registry::new();
+        if let Some(grid) = &self.grid {
+            grid.register(&mut registry);
+            return registry;
+        }
+        for (i, model) in self.scene.models.iter().enumerate() {
+            let at = self.view.world_to_screen(model.pivot());
+            registry.register(Target::Pivot(i), at, 4.0, Handling::Pivot);
+            for (j, handle) in model.handles().into_iter().enumerate() {
+                registry.register(
+                    Target::Handle(i, j),
+                    self.view.world_to_screen(handle),
+                    self.view.length(BOB_RADIUS),
+                    Handling::Drag,
+                );
+            }
+        }
+        self.obstacles.register(&mut registry, &self.view);
+        if let Some(p) = self.scene.selected_pendulum() {
+            self.damping.register(&mut registry, p);
+            self.metronome.register(&mut registry);
+        }
+        self.results.register(&mut registry);
+        self.profiles_panel.register(&mut registry, &self.profiles);
+        self.chart.register(&mut registry);
+        registry.register(
+            Target::Pointer,
+            (self.mouse_x, self.mouse_y),
+            4.0,
+            Handling::Pointer,
+        );
+        registry
+    }
+
+    /// Moves the focus on, or back, and the pointer onto what has it. A
+    /// model's pivot or handle selects the model, as grabbing it would.
+    fn move_focus(&mut self, backwards: bool) {
+        if std::mem::take(&mut self.focus.pressed) {
+            self.let_go(MouseButton::Left);
+        }
+        let registry = self.focusables();
+        let Some(item) = registry.next(self.focus.target, backwards) else {
+            return;
+        };
+        let (target, at, handling) = (item.target, item.at, item.handling);
+        self.focus.target = Some(target);
+        if let Target::Pivot(i) | Target::Handle(i, _) = target {
+            if i != self.scene.selected {
+                self.forget_selected();
+                self.scene.selected = i;
+            }
+        }
+        if handling != Handling::Pointer {
+            self.pointer_moved(at.0, at.1);
+        }
+        self.console_reply = vec![format!("Focus: {}", target.describe())];
+    }
+
+    /// Lets go of anything the keys are holding, and clears the focus.
+    fn clear_focus(&mut self) {
+        if std::mem::take(&mut self.focus.pressed) {
+            self.let_go(MouseButton::Left);
+        }
+        self.focus.target = None;
+    }
+
+    /// Works the focused element; see `focus`. Returns whether `key` was
+    /// one of the keys for it.
+    fn focus_key(&mut self, helper: &mut WindowHelper<()>, key: Option<VirtualKeyCode>) -> bool {
+        let Some(target) = self.focus.target else {
+            return false;
+        };
+        let Some((at, handling)) = self
+            .focusables()
+            .get(target)
+            .map(|item| (item.at, item.handling))
+        else {
+            // Gone, like a panel that was closed.
+            self.clear_focus();
+            return false;
+        };
+        let arrow = match key {
+            Some(VirtualKeyCode::Up) => Some((0.0, -1.0)),
+            Some(VirtualKeyCode::Down) => Some((0.0, 1.0)),
+            Some(VirtualKeyCode::Left) => Some((-1.0, 0.0)),
+            Some(VirtualKeyCode::Right) => Some((1.0, 0.0)),
+            _ => None,
+        };
+        let pressed = self.focus.pressed;
+        match (key, arrow) {
+            (Some(VirtualKeyCode::Escape), _) => {
+                self.clear_focus();
+                self.console_reply.clear();
+            }
+            (Some(VirtualKeyCode::Delete), _) => {
+                if !pressed && handling != Handling::Pointer {
+                    self.pointer_moved(at.0, at.1);
+                }
+                self.press(helper, MouseButton::Right);
+            }
+            (Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter), _) => match handling
+            {
+                Handling::Pivot => (),
+                _ if pressed => {
+                    self.focus.pressed = false;
+                    self.let_go(MouseButton::Left);
+                }
+                Handling::Click => {
+                    self.pointer_moved(at.0, at.1);
+                    self.press(helper, MouseButton::Left);
+                    self.let_go(MouseButton::Left);
+                }
+                Handling::Drag | Handling::Pointer => {
+                    if handling == Handling::Drag {
+                        self.pointer_moved(at.0, at.1);
+                    }
+                    self.press(helper, MouseButton::Left);
+                    self.focus.pressed = true;
+                }
+            },
+            (_, Some((0.0, dy))) if self.modifiers.shift() => self.wheel(-dy),
+            (_, Some((dx, dy))) => match (handling, target) {
+                (Handling::Pivot, Target::Pivot(i)) => {
+                    let step = focus::STEP / self.view.scale;
+                    self.scene.models[i].move_pivot(Vector::new(dx * step, dy * step));
+                }
+                _ => {
+                    if handling == Handling::Drag && !pressed {
+                        self.pointer_moved(at.0, at.1);
+                        self.press(helper, MouseButton::Left);
+                        self.focus.pressed = true;
+                    }
+                    let (x, y) = (self.mouse_x, self.mouse_y);
+                    self.pointer_moved(x + dx * focus::STEP, y + dy * focus::STEP);
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// What the `retention` command says: the memory the chart and the
+    /// history take against the cap, and how finely each stretch of them is
+    /// kept.
+    fn retention_lines(&self) -> Vec<String> {
+        let (chart, history) = (self.chart.buffer.memory(), self.history.memory());
+        let mut lines = vec![format!(
+            "Retention: {} of {}",
+            retention::megabytes(chart + history),
+            retention::megabytes(self.retention.memory)
+        )];
+        // The chart's times are in seconds, the history's in steps.
+        for (name, memory, resolutions, scale) in [
+            ("Chart", chart, self.chart.buffer.resolutions(), 1.0),
+            (
+                "History",
+                history,
+                self.history.resolutions(),
+                STEPS_PER_SECOND,
+            ),
+        ] {
+            lines.push(format!("{}: {}", name, retention::megabytes(memory)));
+            for resolution in resolutions {
+                lines.push(format!(
+                    "  {:.1} s to {:.1} s: {}",
+                    resolution.start / scale,
+                    resolution.end / scale,
+                    retention::describe(&resolution, "step")
+                ));
+            }
+        }
+        lines
+    }
+
+    fn status_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if self.recovery_prompt {
+            lines.push("Recovery file found - Y to restore, any other key to skip".to_string());
+        } else if self.demo.is_some() {
+            lines.push("Demo - press any key to take over".to_string());
+        } else if let Some(seconds) = self.playlist.as_ref().and_then(Playlist::resumes_in) {
+            lines.push(format!("Playlist held - carries on in {:.0} s", seconds));
+        }
+
+        if self.paused {
+            lines.push("Paused".to_string());
+        }
+        lines.extend(self.macros.status_line());
+        #[cfg(feature = "beat")]
+        lines.extend(self.beat.hud_line());
+        lines.extend(self.trajectory.status_line());
+        lines.extend(self.console_reply.iter().cloned());
+        if let Some(entry) = &self.entry {
+            lines.push(entry.status_line());
+        }
+        if let Some(i) = self.annotations.selected {
+            let note = &self.annotations.notes[i];
+            let hint = if self.can_scrub_to(note.time) {
+                " - J to scrub there"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "Note {} at {:.2} s: {}{}",
+                i + 1,
+                note.time,
+                note.text,
+                hint
+            ));
+        }
+
+        if let Some(source) = self.strobe.source {
+            lines.push(format!(
+                "Strobe: {} x{}",
+                source.name(),
+                self.strobe.copies()
+            ));
+        }
+        lines.extend(self.impulse.hud_line());
+        lines.extend(self.poincare.hud_line());
+        lines.extend(self.sweep.hud_line());
+        if let (Some((cursor, len, time)), Some((_, last))) =
+            (self.history.position(), self.history.time_range())
+        {
+            lines.push(format!(
+                "Scrub: {} / {} ({:.2} s)",
+                cursor + 1,
+                len,
+                (time - last) / STEPS_PER_SECOND
+            ));
+        }
+
+        lines
+    }
+
+    /// While scrubbing, a bar along the bottom spanning the history, with the
+    /// cursor and the notes that fall inside it.
+    fn draw_timeline(&self, graphics: &mut Graphics2D) {
+        let (Some((_, _, time)), Some((first, last))) =
+            (self.history.position(), self.history.time_range())
+        else {
+            return;
+        };
+        let (left, width, y) = (220.0, 360.0, 476.0);
+        let span = (last - first).max(1.0);
+        let x = |time: f32| left + width * (time - first) / span;
+
+        graphics.draw_line((left, y), (left + width, y), 4.0, Color::GRAY);
+        for note in &self.annotations.notes {
+            if self.can_scrub_to(note.time) {
+                let x = x(note.time * STEPS_PER_SECOND);
+                graphics.draw_line(
+                    (x, y - 6.0),
+                    (x, y + 4.0),
+                    2.0,
+                    Color::from_rgb(0.9, 0.5, 0.1),
+                );
+            }
+        }
+        let x = x(time);
+        graphics.draw_circle((x, y), 5.0, Color::BLACK);
+    }
+
+    /// What's drawn under the scene: the gravity field and the turning
+    /// points.
+    fn draw_backdrop(&self, graphics: &mut dyn Renderer) {
+        graphics.set_depth(anaglyph::BACKDROP);
+        if let Some(p) = self.scene.selected_pendulum() {
+            self.gravity_field.draw(graphics, &self.view, p.g);
+            if self.turning_points {
+                draw_turning_points(graphics, &self.view, p);
+            }
+        }
+    }
+
+    /// The world: the models, with their trails and everything they swing
+    /// among. `fade` is how far a playlist's crossfade has got.
+    fn draw_scene(&self, graphics: &mut dyn Renderer, fade: Option<f32>) {
+        graphics.set_depth(0.0);
+        if let Some(trail) = &self.trail {
+            trail.draw(graphics, &self.view);
+        }
+        self.envelope.draw(graphics, &self.view);
+        self.strobe.draw(graphics, &self.view);
+        // Mid-crossfade, both scenes are drawn, each at part strength.
+        if let (Some(outgoing), Some(fade)) = (&self.outgoing, fade) {
+            for model in &outgoing.models {
+                model.draw(graphics, &self.view.faded(1.0 - fade));
+            }
+        }
+        let mouse = self.view.screen_to_world(self.mouse_x, self.mouse_y);
+        self.obstacles.draw(graphics, &self.view, mouse);
+        self.walls.draw(graphics, &self.view);
+        let view = self.view.faded(fade.unwrap_or(1.0));
+        let count = self.scene.models.len();
+        for (i, model) in self.scene.models.iter().enumerate() {
+            graphics.set_depth(anaglyph::model_depth(i, count));
+            model.draw(graphics, &view);
+        }
+        // What's laid over the window rather than standing in the world.
+        graphics.set_depth(self.anaglyph.convergence);
+        self.calibration.draw(graphics, &self.view);
+        self.view.draw_letterbox(graphics);
+    }
+
+    /// The backdrop and the world as each eye sees them, for the glasses.
+    fn draw_anaglyph(&self, graphics: &mut dyn Renderer, fade: Option<f32>) {
+        for side in [Side::Left, Side::Right] {
+            let mut eye = self.anaglyph.eye(graphics, side, BACKGROUND);
+            self.draw_backdrop(&mut eye);
+            self.draw_scene(&mut eye, fade);
+        }
+    }
+
+    /// Draws with `draw` through the batch, if the scene is drawn batched,
+    /// and straight to the window if not.
+    fn draw_batched(&mut self, graphics: &mut Graphics2D, draw: impl Fn(&Self, &mut dyn Renderer)) {
+        match self.batch.take() {
+            Some(mut batch) => {
+                draw(self, &mut batch);
+                batch.flush(graphics, self.size);
+                self.batch = Some(batch);
+            }
+            None => draw(self, graphics),
+        }
+    }
+
+    /// The energy shell's arc and the phase plot of the selected pendulum.
+    fn draw_energy_overlays(&self, graphics: &mut dyn Renderer) {
+        let Some(p) = self.scene.selected_pendulum() else {
+            return;
+        };
+        let shell = self.energy_guard.shell.filter(|_| self.energy_overlay);
+        let alarm = self.energy_guard.is_flashing();
+        let color = if alarm { Color::RED } else { Color::DARK_GRAY };
+
+        // The range of angles the shell allows, unless it goes over the top.
+        if let Some(angle) = shell.and_then(|e| energy::turning_angle(p, e)) {
+            draw_arc(
+                graphics, &self.view, p.origin, p.r, -angle, angle, 2.0, color,
+            );
+        }
+        self.phase.draw(graphics, p, shell, alarm);
+    }
+
+    fn hud_text(&self) -> Vec<String> {
+        let mut lines = self.scene.selected().hud_lines(&self.units);
+        if let Some(p) = self.scene.selected_pendulum() {
+            lines.extend(self.energy_dial.hud_line(p));
+            lines.extend(self.escapement.hud_lines(p));
+        }
+        if let Some(p) = self
+            .scene
+            .selected_pendulum()
+            .filter(|_| self.energy_budget)
+        {
+            lines.extend(p.ledger.readout(energy::precise_energy(p)));
+        }
+        lines
+    }
+
+    /// Writes what's on screen to the vector export's file: the drawing,
+    /// and unless it's turned off, the HUD's text.
+    fn export_svg(&self) -> Result<String, String> {
+        let fade = self.playlist.as_ref().and_then(Playlist::fade);
+        let mut svg;
+        if self.anaglyph.enabled {
+            svg = Svg::new(self.size.0, self.size.1, Anaglyph::background(BACKGROUND));
+            self.draw_anaglyph(&mut svg, fade);
+        } else {
+            svg = Svg::new(self.size.0, self.size.1, BACKGROUND);
+            self.draw_backdrop(&mut svg);
+            self.draw_scene(&mut svg, fade);
+        }
+        self.draw_energy_overlays(&mut svg);
+        if self.vector_export.text {
+            for (i, line) in self.hud_text().iter().enumerate() {
+                svg.text((0.0, 30.0 * i as f32), 30.0, Color::BLACK, line);
+            }
+            for (i, line) in self.status_lines().iter().rev().enumerate() {
+                svg.text((0.0, 450.0 - 30.0 * i as f32), 30.0, Color::BLACK, line);
+            }
+        }
+        self.vector_export.write(svg)
+    }
+
+    fn draw_hud(&self, graphics: &mut Graphics2D) {
+        let rows = self.hud_text();
+        for (i, line) in rows.iter().enumerate() {
+            draw_text(graphics, &self.font, (0.0, 30.0 * i as f32), line);
+        }
+        if let Some(p) = self.scene.selected_pendulum() {
+            self.lfos
+                .draw_indicators(graphics, &self.font, &rows, 30.0, p);
+        }
+
+        for model in &self.scene.models {
+            for label in model.labels() {
+                let (x, y) = self.view.world_to_screen(label.at);
+                let text = self
+                    .font
+                    .layout_text(&label.text, label.size, TextOptions::new());
+                graphics.draw_text((x, y), self.view.tint(label.color), &text);
+            }
+            let (x, y) = self.view.world_to_screen(model.pivot());
+            if let Some(name) = model.name() {
+                let text = self.font.layout_text(name, 30.0, TextOptions::new());
+                graphics.draw_text((x - text.width() / 2.0, y - 34.0), Color::BLACK, &text);
+            }
+            if let Some(rate) = model
+                .as_pendulum()
+                .map(|p| p.rate)
+                .filter(|&rate| rate != 1)
+            {
+                let text = self
+                    .font
+                    .layout_text(&format!("x{}", rate), 18.0, TextOptions::new());
+                graphics.draw_text((x + 8.0, y + 4.0), Color::DARK_GRAY, &text);
+            }
+        }
+
+        self.draw_timeline(graphics);
+
+        // Status of the running tools, stacked up from the bottom left.
+        for (i, line) in self.status_lines().iter().rev().enumerate() {
+            draw_text(graphics, &self.font, (0.0, 450.0 - 30.0 * i as f32), line);
+        }
+
+        if self.scene.models.len() < 2 {
+            return;
+        }
+
+        // Legend in the bottom right, with matching numbers next to each
+        // pivot so the entries can be told apart on screen.
+        let count = self.scene.models.len();
+        for (i, model) in self.scene.models.iter().enumerate() {
+            let (x, y) = self.view.world_to_screen(model.pivot());
+            let number = format!("{}", i + 1);
+            draw_text(graphics, &self.font, (x + 8.0, y), &number);
+
+            let marker = if i == self.scene.selected { "> " } else { "" };
+            draw_text(
+                graphics,
+                &self.font,
+                (560.0, 480.0 - 24.0 * (count - i) as f32),
+                &format!("{}{}. {}", marker, number, model.label(&self.units)),
+            );
+        }
+    }
+}
+
+impl WindowHandler for MyWindowHandler {
+    fn on_draw(&mut self, helper: &mut WindowHelper<()>, graphics: &mut Graphics2D) {
+        diagnostics::count_frame();
+        diagnostics::set_retention(self.chart.buffer.memory() + self.history.memory());
+        graphics.clear_screen(if self.anaglyph.enabled {
+            Anaglyph::background(BACKGROUND)
+        } else {
+            BACKGROUND
+        });
+        let frame = self.frame;
+        self.frame += 1;
+        self.run_tape(helper, frame);
+
+        self.take_remote_commands(frame);
+
+        // Macros run on their own clock, which a pause of theirs doesn't stop.
+        let held = self.history.is_scrubbing() || self.transition.is_some();
+        let events = self.macros.tick(self.paused, held);
+        if !events.is_empty() {
+            for event in events {
+                match event {
+                    Event::Action(action) => self.perform(helper, action),
+                    Event::Command(command) => {
+                        self.console_reply = self.run_command(&command).unwrap_or_else(|e| vec![e])
+                    }
+                }
+            }
+            self.macros.played(self.paused);
+        }
+
+        if let Some(title) = self.title.take() {
+            helper.set_title(title);
+        }
+
+        if let Some(grid) = &mut self.grid {
+            if !self.paused {
+                grid.step();
+            }
+            grid.draw(graphics, &self.font);
+            self.focus
+                .draw(graphics, &self.focusables(), (self.mouse_x, self.mouse_y));
+            helper.request_redraw();
+            return;
+        }
+
+        if let Some(menu) = &mut self.menu {
+            menu.step();
+            menu.draw(graphics, &self.font, &self.view, &self.scene);
+            helper.request_redraw();
+            return;
+        }
+
+        if self.demo.is_none() && !self.recovery_prompt && self.autosave.due() {
+            if let Err(e) = Autosave::write(Path::new(RECOVERY_FILE), &self.session_text()) {
+                log!("{}", e);
+            }
+        }
+
+        if let Some(preset) = self.demo.as_mut().and_then(DemoReel::tick) {
+            helper.set_title(format!("Pendulum - Demo: {}", preset.name));
+            self.set_scene(preset.scene());
+        }
+        if let Some(preset) = self.playlist.as_mut().and_then(Playlist::tick) {
+            helper.set_title(format!("Pendulum - {}", preset.name));
+            self.outgoing = Some(self.set_scene(preset.scene()));
+        }
+        let fade = self.playlist.as_ref().and_then(Playlist::fade);
+        if fade.is_none() {
+            self.outgoing = None;
+        }
+
+        self.step_simulation();
+
+        self.framing.update(&mut self.view, || {
+            framing::content(&self.scene, &self.obstacles, &self.walls)
+        });
+
+        if self.anaglyph.enabled {
+            self.draw_batched(graphics, |app, graphics| app.draw_anaglyph(graphics, fade));
+            if let Some(p) = self.scene.selected_pendulum() {
+                self.game.draw(p, graphics, &self.font, &self.view);
+            }
+        } else {
+            self.draw_backdrop(graphics);
+            if let Some(p) = self.scene.selected_pendulum() {
+                self.game.draw(p, graphics, &self.font, &self.view);
+            }
+            self.draw_batched(graphics, |app, graphics| app.draw_scene(graphics, fade));
+        }
+        if let Some(playlist) = &self.playlist {
+            playlist.draw_title(graphics, &self.font, self.size);
+        }
+        self.draw_hud(graphics);
+        self.units.draw_scale_bar(graphics, &self.font, &self.view);
+        self.sync.draw(graphics, &self.font);
+        self.chart.draw(graphics, &self.font, &self.annotations);
+        self.sweep.draw(graphics, &self.font);
+
+        if let Some(reply) = self.trajectory.poll() {
+            self.console_reply = vec![reply];
+        }
+
+        self.draw_energy_overlays(graphics);
+        self.instant_replay.step(self.paused);
+        self.instant_replay.draw(graphics, &self.font, &self.view);
+        if let Some(p) = self.scene.selected_pendulum() {
+            self.sensor.draw(graphics, &self.font, p, &self.units);
+            self.metronome.draw(graphics, &self.font, p, &self.units);
+            self.partition.draw(graphics, &self.font, p);
+            self.sensitivity.draw(graphics, &self.font);
+            self.damping.draw(graphics, &self.font, &self.view, p);
+        }
+        #[cfg(feature = "beat")]
+        self.beat.draw(graphics);
+        self.results.draw(graphics, &self.font, &self.store);
+        if let Some(tournament) = &self.tournament {
+            tournament.draw(graphics, &self.font);
+        }
+        self.profiles_panel.draw(
+            graphics,
+            &self.font,
+            &self.profiles,
+            &self.units,
+            self.scene.selected_pendulum(),
+        );
+        let budget = Budget::frame();
+        self.accuracy.step(&self.integrators, &budget);
+        self.sensitivity.step(&budget);
+        self.accuracy.draw(graphics, &self.font, &self.integrators);
+        let registry = self.focusables();
+        self.focus
+            .draw(graphics, &registry, (self.mouse_x, self.mouse_y));
+        self.help.draw(
+            graphics,
+            &self.font,
+            &self.bindings,
+            &registry,
+            &self.macros,
+        );
+
+        helper.request_redraw();
+    }
+
+    fn on_key_down(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        virtual_key_code: Option<VirtualKeyCode>,
+        _scancode: speedy2d::window::KeyScancode,
+    ) {
+        self.input(helper, Input::KeyDown(virtual_key_code));
+    }
+
+    fn on_key_up(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        virtual_key_code: Option<VirtualKeyCode>,
+        _scancode: speedy2d::window::KeyScancode,
+    ) {
+        self.input(helper, Input::KeyUp(virtual_key_code));
+    }
+
+    fn on_keyboard_modifiers_changed(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        state: ModifiersState,
+    ) {
+        self.input(helper, Input::Modifiers(Modifiers::of(&state)));
+    }
+
+    fn on_keyboard_char(&mut self, helper: &mut WindowHelper<()>, unicode_codepoint: char) {
+        self.input(helper, Input::Char(unicode_codepoint));
+    }
+
+    fn on_mouse_move(&mut self, helper: &mut WindowHelper<()>, position: speedy2d::dimen::Vec2) {
+        self.input(helper, Input::Move(position.x, position.y));
+    }
+
+    /// The only user event is a comparison window's supervisor asking it
+    /// to close; see `windows`.
+    fn on_user_event(&mut self, helper: &mut WindowHelper<()>, _: ()) {
+        helper.terminate_loop();
+    }
+
+    fn on_resize(&mut self, helper: &mut WindowHelper<()>, size_pixels: speedy2d::dimen::UVec2) {
+        let size = (size_pixels.x as f32, size_pixels.y as f32);
+        self.input(helper, Input::Resize(size.0, size.1));
+    }
+
+    fn on_mouse_wheel_scroll(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        distance: MouseScrollDistance,
+    ) {
+        let lines = match distance {
+            MouseScrollDistance::Lines { y, .. } => y,
+            MouseScrollDistance::Pixels { y, .. } => y / 40.0,
+            MouseScrollDistance::Pages { y, .. } => y * 10.0,
+        };
+        self.input(helper, Input::Wheel(lines as f32));
+    }
+
+    fn on_mouse_button_up(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        self.input(helper, Input::Up(button));
+    }
+
+    fn on_mouse_button_down(&mut self, helper: &mut WindowHelper, button: MouseButton) {
+        self.input(helper, Input::Down(button));
+    }
+}
+
+fn draw_text(graphics: &mut Graphics2D, font: &Font, position: (f32, f32), text: &str) {
+    graphics.draw_text(
+        position,
+        Color::BLACK,
+        &font.layout_text(text, 30.0, TextOptions::new()),
+    );
+}
+
+/// Draws an arrow from `from` to `to` with a head at `to`, all in world
+/// units like `draw_arc`.
+fn draw_arrow(
+    graphics: &mut dyn Renderer,
+    view: &Viewport,
+    from: Vector,
+    to: Vector,
+    thickness: f32,
+    color: Color,
+) {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return;
+    }
+
+    // The head takes up to a third of the arrow, and the shaft stops where
+    // it starts so the two don't overlap when translucent.
+    let head = (4.0 * thickness).min(length / 3.0);
+    let (ux, uy) = (dx / length, dy / length);
+    let base = Vector::new(to.x - head * ux, to.y - head * uy);
+    let half = 0.6 * head;
+
+    graphics.draw_line(
+        view.world_to_screen(from),
+        view.world_to_screen(base),
+        view.length(thickness),
+        color,
+    );
+    graphics.draw_triangle(
+        [
+            view.world_to_screen(to).into(),
+            view.world_to_screen(Vector::new(base.x - half * uy, base.y + half * ux))
+                .into(),
+            view.world_to_screen(Vector::new(base.x + half * uy, base.y - half * ux))
+                .into(),
+        ],
+        color,
+    );
+}
+
+/// Faint ticks across the bob's path at the angles where it will turn back,
+/// or the whole circle dashed if it has the energy to keep going round.
+fn draw_turning_points(graphics: &mut dyn Renderer, view: &Viewport, p: &Pendulum) {
+    let color = Color::from_rgba(0.2, 0.2, 0.3, 0.35);
+    let angle = p.turning_angle();
+    if angle.is_infinite() {
+        for i in 0..24 {
+            let from = TAU * i as f32 / 24.0;
+            draw_arc(
+                graphics,
+                view,
+                p.origin,
+                p.r,
+                from,
+                from + 0.5 * TAU / 24.0,
+                2.0,
+                color,
+            );
+        }
+        return;
+    }
+
+    for side in [-angle, angle] {
+        let (sin, cos) = side.sin_cos();
+        let point = |radius: f32| {
+            view.world_to_screen(Vector::new(
+                p.origin.x + radius * sin,
+                p.origin.y + radius * cos,
+            ))
+        };
+        graphics.draw_line(
+            point(p.r - 16.0),
+            point(p.r + 16.0),
+            view.length(2.0),
+            color,
+        );
+    }
+}
+
+/// Colour from hue (in turns, so 0 and 1 are both red), saturation and
+/// value, each from 0 to 1.
+fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    Color::from_rgb(r + m, g + m, b + m)
+}
+
+/// Draws the arc of radius `radius` around `center` between two pendulum
+/// angles, measured like `Pendulum::angle` (0 points straight down). Centre,
+/// radius and thickness are in world units.
+#[allow(clippy::too_many_arguments)]
+fn draw_arc(
+    graphics: &mut dyn Renderer,
+    view: &Viewport,
+    center: Vector,
+    radius: f32,
+    from: f32,
+    to: f32,
+    thickness: f32,
+    color: Color,
+) {
+    let segments = ((to - from).abs() * radius / 8.0).ceil().max(1.0) as u32;
+    let point = |angle: f32| {
+        view.world_to_screen(Vector::new(
+            center.x + radius * angle.sin(),
+            center.y + radius * angle.cos(),
+        ))
+    };
+
+    let mut previous = point(from);
+    for i in 1..=segments {
+        let next = point(from + (to - from) * i as f32 / segments as f32);
+        graphics.draw_line(previous, next, view.length(thickness), color);
+        previous = next;
     }
 }
 
@@ -0,0 +1,466 @@
+//! The transcendental functions the physics steps with. By default they're
+//! the platform's, which are fast but only promise to be close: macOS on
+//! ARM and glibc on x86 round some results differently, and a chaotic
+//! swing soon makes a last-place difference into a different trajectory.
+//! With the `deterministic` feature they're worked here instead, from
+//! fdlibm's polynomials in plain `f64` arithmetic, whose every operation
+//! IEEE 754 rounds the same way on every machine, and an `f32` is rounded
+//! once from the `f64` result.
+//!
+//! Rust never fuses a multiply and add on its own, so the rest of the step
+//! is the same everywhere already, as long as nothing in it asks for
+//! `mul_add`; `--determinism-dump` and `--determinism-check` are how to
+//! tell.
+//!
+//! Only the step calls through here. What's drawn can be a pixel out
+//! without anything going wrong, so drawing keeps the platform's functions.
+
+/// A float the physics steps in.
+pub trait Real: Copy {
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn atan2(self, x: Self) -> Self;
+    fn acos(self) -> Self;
+    /// `self` raised to `n`, for a positive `self`.
+    fn powf(self, n: Self) -> Self;
+    fn ln(self) -> Self;
+    fn hypot(self, y: Self) -> Self;
+}
+
+macro_rules! real {
+    ($t:ty) => {
+        impl Real for $t {
+            #[cfg(not(feature = "deterministic"))]
+            fn sin(self) -> $t {
+                <$t>::sin(self)
+            }
+
+            #[cfg(not(feature = "deterministic"))]
+            fn cos(self) -> $t {
+                <$t>::cos(self)
+            }
+
+            #[cfg(not(feature = "deterministic"))]
+            fn atan2(self, x: $t) -> $t {
+                <$t>::atan2(self, x)
+            }
+
+            #[cfg(not(feature = "deterministic"))]
+            fn acos(self) -> $t {
+                <$t>::acos(self)
+            }
+
+            #[cfg(not(feature = "deterministic"))]
+            fn powf(self, n: $t) -> $t {
+                <$t>::powf(self, n)
+            }
+
+            #[cfg(not(feature = "deterministic"))]
+            fn ln(self) -> $t {
+                <$t>::ln(self)
+            }
+
+            #[cfg(not(feature = "deterministic"))]
+            fn hypot(self, y: $t) -> $t {
+                <$t>::hypot(self, y)
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn sin(self) -> $t {
+                portable::sin(self as f64) as $t
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn cos(self) -> $t {
+                portable::cos(self as f64) as $t
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn atan2(self, x: $t) -> $t {
+                portable::atan2(self as f64, x as f64) as $t
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn acos(self) -> $t {
+                portable::acos(self as f64) as $t
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn powf(self, n: $t) -> $t {
+                portable::powf(self as f64, n as f64) as $t
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn ln(self) -> $t {
+                portable::ln(self as f64) as $t
+            }
+
+            #[cfg(feature = "deterministic")]
+            fn hypot(self, y: $t) -> $t {
+                portable::hypot(self as f64, y as f64) as $t
+            }
+        }
+    };
+}
+
+real!(f32);
+real!(f64);
+
+pub fn sin<T: Real>(x: T) -> T {
+    x.sin()
+}
+
+pub fn cos<T: Real>(x: T) -> T {
+    x.cos()
+}
+
+pub fn sin_cos<T: Real>(x: T) -> (T, T) {
+    (x.sin(), x.cos())
+}
+
+pub fn atan2<T: Real>(y: T, x: T) -> T {
+    y.atan2(x)
+}
+
+pub fn acos<T: Real>(x: T) -> T {
+    x.acos()
+}
+
+pub fn powf<T: Real>(x: T, n: T) -> T {
+    x.powf(n)
+}
+
+pub fn ln<T: Real>(x: T) -> T {
+    x.ln()
+}
+
+pub fn hypot<T: Real>(x: T, y: T) -> T {
+    x.hypot(y)
+}
+
+/// Whether the physics was built with the functions here rather than the
+/// platform's, for dumps to say which they came from.
+pub const DETERMINISTIC: bool = cfg!(feature = "deterministic");
+
+// The constants are fdlibm's, digit for digit, so they can be checked
+// against it.
+#[cfg(feature = "deterministic")]
+#[allow(clippy::excessive_precision)]
+mod portable {
+    use std::f64::consts::{FRAC_2_PI, FRAC_PI_2, FRAC_PI_4, PI};
+
+    // π/2 in three parts, the first two short enough that a multiple of
+    // them by the quadrant count is exact, for the Cody–Waite reduction.
+    const PIO2_1: f64 = 1.570_796_326_734_125_614_17e0;
+    const PIO2_2: f64 = 6.077_100_506_303_965_976_6e-11;
+    const PIO2_3: f64 = 2.022_266_248_711_166_455_8e-21;
+
+    const S: [f64; 6] = [
+        -1.666_666_666_666_663_243_48e-1,
+        8.333_333_333_322_489_461_24e-3,
+        -1.984_126_982_985_794_931_34e-4,
+        2.755_731_370_707_006_767_89e-6,
+        -2.505_076_025_340_686_341_95e-8,
+        1.589_690_995_211_550_102_21e-10,
+    ];
+    const C: [f64; 6] = [
+        4.166_666_666_666_660_190_37e-2,
+        -1.388_888_888_887_410_957_49e-3,
+        2.480_158_728_947_672_941_78e-5,
+        -2.755_731_435_139_066_330_35e-7,
+        2.087_572_321_298_174_827_9e-9,
+        -1.135_964_755_778_819_482_65e-11,
+    ];
+
+    /// `x` less the nearest multiple of π/2, and which multiple it was
+    /// modulo 4.
+    fn reduce(x: f64) -> (f64, i64) {
+        let n = (x * FRAC_2_PI).round();
+        let r = ((x - n * PIO2_1) - n * PIO2_2) - n * PIO2_3;
+        (r, (n as i64).rem_euclid(4))
+    }
+
+    fn kernel_sin(x: f64) -> f64 {
+        let z = x * x;
+        let p = S[0] + z * (S[1] + z * (S[2] + z * (S[3] + z * (S[4] + z * S[5]))));
+        x + x * z * p
+    }
+
+    fn kernel_cos(x: f64) -> f64 {
+        let z = x * x;
+        let p = C[0] + z * (C[1] + z * (C[2] + z * (C[3] + z * (C[4] + z * C[5]))));
+        1.0 - (0.5 * z - z * z * p)
+    }
+
+    pub fn sin(x: f64) -> f64 {
+        if !x.is_finite() {
+            return f64::NAN;
+        }
+        let (r, quadrant) = reduce(x);
+        match quadrant {
+            0 => kernel_sin(r),
+            1 => kernel_cos(r),
+            2 => -kernel_sin(r),
+            _ => -kernel_cos(r),
+        }
+    }
+
+    pub fn cos(x: f64) -> f64 {
+        if !x.is_finite() {
+            return f64::NAN;
+        }
+        let (r, quadrant) = reduce(x);
+        match quadrant {
+            0 => kernel_cos(r),
+            1 => -kernel_sin(r),
+            2 => -kernel_cos(r),
+            _ => kernel_sin(r),
+        }
+    }
+
+    const ATAN_HI: [f64; 4] = [
+        4.636_476_090_008_060_935_15e-1,
+        FRAC_PI_4,
+        9.827_937_232_473_290_540_82e-1,
+        FRAC_PI_2,
+    ];
+    const ATAN_LO: [f64; 4] = [
+        2.269_877_745_296_168_709_24e-17,
+        3.061_616_997_868_383_017_93e-17,
+        1.390_331_103_123_099_845_16e-17,
+        6.123_233_995_736_766_035_87e-17,
+    ];
+    const AT: [f64; 11] = [
+        3.333_333_333_333_293_180_27e-1,
+        -1.999_999_999_987_648_324_76e-1,
+        1.428_571_427_250_346_637_11e-1,
+        -1.111_111_040_546_235_578_8e-1,
+        9.090_887_133_436_506_561_96e-2,
+        -7.691_876_205_044_829_994_95e-2,
+        6.661_073_137_387_531_206_69e-2,
+        -5.833_570_133_790_573_486_45e-2,
+        4.976_877_994_615_932_360_17e-2,
+        -3.653_157_274_421_691_552_7e-2,
+        1.628_582_011_536_578_236_23e-2,
+    ];
+
+    fn atan(x: f64) -> f64 {
+        let ax = x.abs();
+        if ax.is_nan() {
+            return x;
+        }
+        if ax >= 7.378_697_629_483_820_646_4e19 {
+            return (ATAN_HI[3] + ATAN_LO[3]).copysign(x);
+        }
+        let (id, t) = if ax < 0.4375 {
+            if ax < 7.450_580_596_923_828e-9 {
+                return x;
+            }
+            (None, ax)
+        } else if ax < 0.6875 {
+            (Some(0), (2.0 * ax - 1.0) / (2.0 + ax))
+        } else if ax < 1.1875 {
+            (Some(1), (ax - 1.0) / (ax + 1.0))
+        } else if ax < 2.4375 {
+            (Some(2), (ax - 1.5) / (1.0 + 1.5 * ax))
+        } else {
+            (Some(3), -1.0 / ax)
+        };
+
+        let z = t * t;
+        let w = z * z;
+        let s1 = z * (AT[0] + w * (AT[2] + w * (AT[4] + w * (AT[6] + w * (AT[8] + w * AT[10])))));
+        let s2 = w * (AT[1] + w * (AT[3] + w * (AT[5] + w * (AT[7] + w * AT[9]))));
+        let result = match id {
+            None => t - t * (s1 + s2),
+            Some(i) => ATAN_HI[i] - ((t * (s1 + s2) - ATAN_LO[i]) - t),
+        };
+        result.copysign(x)
+    }
+
+    pub fn atan2(y: f64, x: f64) -> f64 {
+        if x.is_nan() || y.is_nan() {
+            return f64::NAN;
+        }
+        if x == 0.0 {
+            return if y == 0.0 {
+                if x.is_sign_negative() {
+                    PI.copysign(y)
+                } else {
+                    y
+                }
+            } else {
+                FRAC_PI_2.copysign(y)
+            };
+        }
+        let angle = atan(y / x);
+        if x > 0.0 {
+            angle
+        } else {
+            // Half a turn round, kept on the side `y` is.
+            angle + PI.copysign(y)
+        }
+    }
+
+    /// From `atan2`, with the sine worked as `√((1 - x)(1 + x))` so it
+    /// doesn't cancel near ±1.
+    pub fn acos(x: f64) -> f64 {
+        if x.is_nan() || x.abs() > 1.0 {
+            return f64::NAN;
+        }
+        atan2(((1.0 - x) * (1.0 + x)).sqrt(), x)
+    }
+
+    /// The larger side scaled by `√(1 + r²)`, so the squares can't
+    /// overflow. A square root is one of the operations IEEE 754 rounds
+    /// exactly.
+    pub fn hypot(x: f64, y: f64) -> f64 {
+        let (x, y) = (x.abs(), y.abs());
+        if x.is_infinite() || y.is_infinite() {
+            return f64::INFINITY;
+        }
+        if x.is_nan() || y.is_nan() {
+            return f64::NAN;
+        }
+        let (big, small) = if x >= y { (x, y) } else { (y, x) };
+        if big == 0.0 {
+            return 0.0;
+        }
+        let ratio = small / big;
+        big * (1.0 + ratio * ratio).sqrt()
+    }
+
+    const LN2_HI: f64 = 6.931_471_803_691_238_164_9e-1;
+    const LN2_LO: f64 = 1.908_214_929_270_587_700_02e-10;
+    const LG: [f64; 7] = [
+        6.666_666_666_666_735_13e-1,
+        3.999_999_999_940_941_908e-1,
+        2.857_142_874_366_239_149e-1,
+        2.222_219_843_214_978_396e-1,
+        1.818_357_216_161_805_012e-1,
+        1.531_383_769_920_937_332e-1,
+        1.479_819_860_511_658_591e-1,
+    ];
+    const P: [f64; 5] = [
+        1.666_666_666_666_660_190_37e-1,
+        -2.777_777_777_701_559_338_42e-3,
+        6.613_756_321_437_934_361_17e-5,
+        -1.653_390_220_546_525_153_9e-6,
+        4.138_136_797_057_238_460_39e-8,
+    ];
+
+    pub fn ln(x: f64) -> f64 {
+        if x.is_nan() || x < 0.0 {
+            return f64::NAN;
+        }
+        if x == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        if x.is_infinite() {
+            return x;
+        }
+        // x = 2^k m, with m from √½ to √2, a subnormal scaled up first.
+        let (x, shift) = if x.is_normal() {
+            (x, 0)
+        } else {
+            (x * 18_014_398_509_481_984.0, 54)
+        };
+        let bits = x.to_bits();
+        let mut k = ((bits >> 52) & 0x7ff) as i64 - 1023 - shift;
+        let mut m = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | 0x3ff0_0000_0000_0000);
+        if m > std::f64::consts::SQRT_2 {
+            m *= 0.5;
+            k += 1;
+        }
+        let f = m - 1.0;
+        let s = f / (2.0 + f);
+        let z = s * s;
+        let r = z
+            * (LG[0]
+                + z * (LG[1] + z * (LG[2] + z * (LG[3] + z * (LG[4] + z * (LG[5] + z * LG[6]))))));
+        let half_square = 0.5 * f * f;
+        let k = k as f64;
+        k * LN2_HI + (f - (half_square - (s * (half_square + r) + k * LN2_LO)))
+    }
+
+    fn exp(x: f64) -> f64 {
+        if x > 709.78 {
+            return f64::INFINITY;
+        }
+        if x < -745.2 {
+            return 0.0;
+        }
+        let k = (x / (LN2_HI + LN2_LO)).round();
+        let r = (x - k * LN2_HI) - k * LN2_LO;
+        let z = r * r;
+        let c = r - z * (P[0] + z * (P[1] + z * (P[2] + z * (P[3] + z * P[4]))));
+        let y = 1.0 + (r + r * c / (2.0 - c));
+
+        // Scaled by 2^k in two halves, so neither power leaves the normal
+        // range.
+        let k = k as i64;
+        let half = k / 2;
+        let power = |e: i64| f64::from_bits(((e + 1023) as u64) << 52);
+        y * power(half) * power(k - half)
+    }
+
+    pub fn powf(x: f64, n: f64) -> f64 {
+        if n == 0.0 || x == 1.0 {
+            return 1.0;
+        }
+        if x.is_nan() || n.is_nan() || x < 0.0 {
+            return f64::NAN;
+        }
+        if x == 0.0 || x.is_infinite() || n.is_infinite() {
+            // Whichever of nothing or everything the limit is.
+            return if (x > 1.0) == (n > 0.0) {
+                f64::INFINITY
+            } else {
+                0.0
+            };
+        }
+        exp(n * ln(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whichever functions were built in, they're the functions they say.
+    #[test]
+    fn each_function_is_close_to_the_exact_value() {
+        let close = |a: f64, b: f64| (a - b).abs() <= 4.0 * f64::EPSILON * b.abs().max(1.0);
+        for i in -40..=40 {
+            let x = i as f64 * 0.173;
+            assert!(close(sin(x), x.sin()), "sin {}", x);
+            assert!(close(cos(x), x.cos()), "cos {}", x);
+            assert!(close(atan2(x, 0.7), x.atan2(0.7)), "atan2 {}", x);
+            assert!(close(hypot(x, 3.0 - x), x.hypot(3.0 - x)), "hypot {}", x);
+            let c = x / 7.0;
+            assert!(close(acos(c), c.acos()), "acos {}", c);
+            let positive = x.abs() + 0.01;
+            assert!(close(ln(positive), positive.ln()), "ln {}", positive);
+            assert!(
+                close(powf(positive, 1.7), positive.powf(1.7)),
+                "powf {}",
+                positive
+            );
+        }
+    }
+
+    #[test]
+    fn the_edges_are_the_ones_ieee_754_gives() {
+        assert_eq!(ln(1.0f64), 0.0);
+        assert_eq!(ln(0.0f64), f64::NEG_INFINITY);
+        assert_eq!(ln(f64::INFINITY), f64::INFINITY);
+        assert!(ln(-1.0f64).is_nan());
+        assert_eq!(hypot(0.0f64, 0.0), 0.0);
+        assert_eq!(hypot(3.0f32, -4.0), 5.0);
+        assert_eq!(hypot(f64::INFINITY, f64::NAN), f64::INFINITY);
+        assert_eq!(hypot(1e300f64, 1e300), 1e300 * 2f64.sqrt());
+        assert_eq!(acos(1.0f64), 0.0);
+        assert!(acos(1.5f64).is_nan());
+        assert_eq!(powf(2.0f64, 0.0), 1.0);
+    }
+}
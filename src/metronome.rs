@@ -0,0 +1,491 @@
+//! Metronome practice: the turning points of the selected pendulum, one
+//! beat per swing, compared against a beat grid at a target tempo, and a
+//! tuner that sets the rod length so the swing keeps that tempo at its
+//! current amplitude. The crossings of the bottom beat the same tempo a
+//! quarter of a period on, and as the bob is fastest there, that's where a
+//! beat is timed most sharply to the eye: a light flashes on each, and with
+//! `tick` on the terminal bell rings too; there's no audio output, so
+//! that's the only sound to be had. Clicking the tempo types an exact one.
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::io::Write;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::energy;
+use crate::focus::{Handling, Registry, Target};
+use crate::model::{Model, ModelEntry};
+use crate::pendulum::{Pendulum, PendulumState};
+use crate::units::Units;
+use crate::STEPS_PER_SECOND;
+
+const DEFAULT_BPM: f32 = 60.0;
+const MIN_BPM: f32 = 20.0;
+const MAX_BPM: f32 = 240.0;
+/// Beats the actual tempo and the drift are fitted over.
+const FIT_BEATS: usize = 16;
+/// Within this many milliseconds of the grid counts as on the beat, and
+/// within three times it as close. Drift per minute uses the same bands.
+const ON_BEAT_MS: f32 = 20.0;
+/// Rounds of the length solve before giving up.
+const TUNE_ROUNDS: usize = 50;
+
+/// Steps the beat light stays lit after a beat.
+const FLASH: f32 = 0.1 * STEPS_PER_SECOND;
+
+const PANEL: (f32, f32, f32, f32) = (590.0, 274.0, 200.0, 162.0);
+const BUTTON: (f32, f32, f32, f32) = (598.0, 400.0, 64.0, 28.0);
+/// The target tempo's line, clicked to type one.
+const TEMPO: (f32, f32, f32, f32) = (590.0, 274.0, 170.0, 24.0);
+
+/// Sets the rod length of `p` so it swings with a period of `period`
+/// seconds at its current amplitude, using the exact large-amplitude period
+/// and allowing for viscous damping.
+///
+/// With the angle and angular velocity kept, a new length also moves the
+/// turning point, so length and amplitude are solved for together until
+/// the length settles.
+pub fn tune(p: &mut Pendulum, period: f32) -> Result<(), String> {
+    for _ in 0..TUNE_ROUNDS {
+        let Some(amplitude) = energy::turning_angle(p, energy::energy(p)) else {
+            return Err("swinging over the top, there's no period to tune".to_string());
+        };
+        let zeta = p.damping_ratio().unwrap_or(0.0);
+        if zeta >= 1.0 {
+            return Err("overdamped, it doesn't swing".to_string());
+        }
+
+        // Damping shrinks the swing as it goes, so the amplitude that counts
+        // is the one half a period on, in the middle of the next swing.
+        let damped = (1.0 - zeta * zeta).sqrt();
+        let amplitude = amplitude * (-PI * zeta / damped).exp();
+
+        // The small-angle, undamped period that gives `period` once both
+        // are allowed for.
+        let stretch = p.period_at(amplitude) / p.natural_period();
+        let natural = period * damped / stretch;
+        let previous = p.r;
+        p.set_period(natural);
+        if (p.r - previous).abs() <= 1e-5 * p.r {
+            return Ok(());
+        }
+    }
+    Err("the length didn't settle".to_string())
+}
+
+/// The rod length `tune` would give `p` for `period`, leaving `p` alone.
+pub fn tuned_length(p: &Pendulum, period: f32) -> Result<f32, String> {
+    let ModelEntry::Pendulum(entry) = p.entry() else {
+        unreachable!("a pendulum's entry is a pendulum's");
+    };
+    let mut copy = Pendulum::from_entry(&entry);
+    tune(&mut copy, period)?;
+    Ok(copy.r)
+}
+
+fn band_color(ms: f32) -> Color {
+    if ms.abs() <= ON_BEAT_MS {
+        Color::from_rgb(0.1, 0.6, 0.2)
+    } else if ms.abs() <= 3.0 * ON_BEAT_MS {
+        Color::from_rgb(0.9, 0.5, 0.1)
+    } else {
+        Color::RED
+    }
+}
+
+fn inside((left, top, width, height): (f32, f32, f32, f32), x: f32, y: f32) -> bool {
+    x >= left && x <= left + width && y >= top && y <= top + height
+}
+
+fn steps_to_ms(steps: f32) -> f32 {
+    1000.0 * steps / STEPS_PER_SECOND
+}
+
+pub struct Metronome {
+    pub visible: bool,
+    /// Whether each crossing of the bottom rings the terminal bell.
+    pub tick: bool,
+    bpm: f32,
+    previous: Option<PendulumState>,
+    /// Simulation time, in steps, of the first turning point since the grid
+    /// was last set: beat zero. The grid runs on simulation time, so pausing
+    /// or a slow frame doesn't move it.
+    anchor: Option<f32>,
+    /// Recent turning points as beat number and time in steps.
+    beats: VecDeque<(f32, f32)>,
+    count: usize,
+    /// Simulation time, in steps, of the last crossing of the bottom.
+    crossed: Option<f32>,
+}
+
+impl Metronome {
+    pub fn new() -> Metronome {
+        Metronome {
+            visible: false,
+            tick: false,
+            bpm: DEFAULT_BPM,
+            previous: None,
+            anchor: None,
+            beats: VecDeque::with_capacity(FIT_BEATS),
+            count: 0,
+            crossed: None,
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Sets the target tempo and starts a new grid from the next turning
+    /// point.
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+        self.clear();
+    }
+
+    /// Swing period that beats at the target tempo, a beat at each end.
+    pub fn target_period(&self) -> f32 {
+        120.0 / self.bpm
+    }
+
+    pub fn clear(&mut self) {
+        self.previous = None;
+        self.anchor = None;
+        self.beats.clear();
+        self.count = 0;
+        self.crossed = None;
+    }
+
+    /// Steps between beats of the grid.
+    fn interval(&self) -> f32 {
+        60.0 * STEPS_PER_SECOND / self.bpm
+    }
+
+    /// Looks for a turning point in the step just taken, timed to within the
+    /// step by where the angular velocity crossed zero, and for a crossing
+    /// of the bottom, timed by where the angle did. Going over the top flips
+    /// the sign of the angle too, but that's a jump of nearly a turn and
+    /// isn't counted.
+    pub fn record(&mut self, p: &Pendulum) {
+        let after = p.snapshot();
+        let Some(before) = self.previous.replace(after) else {
+            return;
+        };
+        let within = |a: f32, b: f32| before.time + (after.time - before.time) * a / (a - b);
+
+        let (a, b) = (before.angular_velocity, after.angular_velocity);
+        if a != b && (a < 0.0) != (b < 0.0) {
+            let time = within(a, b);
+            self.anchor.get_or_insert(time);
+            if self.beats.len() == FIT_BEATS {
+                self.beats.pop_front();
+            }
+            self.beats.push_back((self.count as f32, time));
+            self.count += 1;
+        }
+
+        let (a, b) = (before.angle, after.angle);
+        if a != b && (a < 0.0) != (b < 0.0) && (a - b).abs() < PI {
+            self.crossed = Some(within(a, b));
+            if self.tick {
+                let mut stderr = std::io::stderr();
+                let _ = stderr.write_all(b"\x07").and_then(|()| stderr.flush());
+            }
+        }
+    }
+
+    /// Whether (`x`, `y`) is on the target tempo, to type a new one.
+    pub fn tempo_at(&self, x: f32, y: f32) -> bool {
+        self.visible && inside(TEMPO, x, y)
+    }
+
+    /// Registers the target tempo and the tune button, when shown.
+    pub fn register(&self, registry: &mut Registry) {
+        if !self.visible {
+            return;
+        }
+        for (target, (left, top, width, height)) in
+            [(Target::Tempo, TEMPO), (Target::TuneButton, BUTTON)]
+        {
+            registry.register(
+                target,
+                (left + width / 2.0, top + height / 2.0),
+                height / 2.0,
+                Handling::Click,
+            );
+        }
+    }
+
+    /// Steps the beat came after its place on the grid.
+    fn offset(&self, (beat, time): (f32, f32)) -> f32 {
+        time - self.anchor.unwrap_or(time) - beat * self.interval()
+    }
+
+    /// How late the last beat was, in milliseconds, against the nearest
+    /// beat of the grid. Negative is early.
+    pub fn last_offset_ms(&self) -> Option<f32> {
+        let interval = self.interval();
+        self.beats.back().map(|&beat| {
+            let offset = self.offset(beat);
+            steps_to_ms(offset - interval * (offset / interval).round())
+        })
+    }
+
+    /// Tempo of the recent swings.
+    pub fn actual_bpm(&self) -> Option<f32> {
+        let (&(first, from), &(last, to)) = (self.beats.front()?, self.beats.back()?);
+        (last > first).then(|| 60.0 * STEPS_PER_SECOND * (last - first) / (to - from))
+    }
+
+    /// How fast the beats are falling behind the grid, in milliseconds per
+    /// minute, from a least-squares fit of the recent offsets.
+    pub fn drift_ms_per_minute(&self) -> Option<f32> {
+        if self.beats.len() < 3 {
+            return None;
+        }
+
+        let n = self.beats.len() as f32;
+        let mean_time = self.beats.iter().map(|&(_, time)| time).sum::<f32>() / n;
+        let mean_offset = self
+            .beats
+            .iter()
+            .map(|&beat| self.offset(beat))
+            .sum::<f32>()
+            / n;
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for &beat in &self.beats {
+            let dt = beat.1 - mean_time;
+            covariance += dt * (self.offset(beat) - mean_offset);
+            variance += dt * dt;
+        }
+        // Steps of offset per step of time, scaled to a minute.
+        Some(steps_to_ms(covariance / variance * 60.0 * STEPS_PER_SECOND))
+    }
+
+    /// Returns true if the panel took the click. The tune button retunes
+    /// `p` to the target tempo.
+    pub fn mouse_down(&mut self, x: f32, y: f32, p: &mut Pendulum) -> bool {
+        if !self.visible || !inside(PANEL, x, y) {
+            return false;
+        }
+
+        if inside(BUTTON, x, y) {
+            match tune(p, self.target_period()) {
+                Ok(()) => self.clear(),
+                Err(e) => log!("cannot tune to {} BPM: {}", self.bpm, e),
+            }
+        }
+        true
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, p: &Pendulum, units: &Units) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = PANEL;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.7),
+        );
+        let text = |graphics: &mut Graphics2D, position: (f32, f32), text: &str, color: Color| {
+            graphics.draw_text(
+                position,
+                color,
+                &font.layout_text(text, 18.0, TextOptions::new()),
+            );
+        };
+
+        let actual = self
+            .actual_bpm()
+            .map_or("...".to_string(), |bpm| format!("{:.1}", bpm));
+        text(
+            graphics,
+            (left + 8.0, top + 4.0),
+            &format!("Target {:.1} BPM, actual {}", self.bpm, actual),
+            Color::BLACK,
+        );
+        let lit = self.crossed.is_some_and(|time| p.time - time < FLASH);
+        graphics.draw_circle(
+            (left + width - 14.0, top + 14.0),
+            8.0,
+            if lit {
+                Color::from_rgb(0.9, 0.2, 0.2)
+            } else {
+                Color::from_rgb(0.5, 0.5, 0.5)
+            },
+        );
+
+        let offset = self.last_offset_ms();
+        if let Some(ms) = offset {
+            let when = if ms < 0.0 { "early" } else { "late" };
+            text(
+                graphics,
+                (left + 8.0, top + 26.0),
+                &format!("Last beat {:.0} ms {}", ms.abs(), when),
+                band_color(ms),
+            );
+        }
+        if let Some(drift) = self.drift_ms_per_minute() {
+            text(
+                graphics,
+                (left + 8.0, top + 48.0),
+                &format!("Drift {:+.0} ms per minute", drift),
+                band_color(drift),
+            );
+        }
+        let needed = match tuned_length(p, self.target_period()) {
+            Ok(r) => format!("Needs r = {}", units.length(r)),
+            Err(_) => "Can't keep this tempo".to_string(),
+        };
+        text(graphics, (left + 8.0, top + 70.0), &needed, Color::BLACK);
+
+        // Half a beat either side of the grid beat in the middle, with the
+        // recent beats where they fell and a cursor sweeping through the
+        // grid, pointing the way the bob is swinging.
+        let (from, to, y) = (left + 12.0, left + width - 12.0, top + 106.0);
+        let middle = 0.5 * (from + to);
+        let interval = self.interval();
+        let x_at = |steps: f32| middle + (to - from) * (steps / interval).clamp(-0.5, 0.5);
+        graphics.draw_line((from, y), (to, y), 2.0, Color::DARK_GRAY);
+        graphics.draw_line((middle, y - 10.0), (middle, y + 10.0), 2.0, Color::BLACK);
+        for &beat in &self.beats {
+            let offset = self.offset(beat);
+            let offset = offset - interval * (offset / interval).round();
+            graphics.draw_circle((x_at(offset), y), 4.0, band_color(steps_to_ms(offset)));
+        }
+        if let Some(anchor) = self.anchor {
+            let phase = (p.time - anchor).rem_euclid(interval);
+            let x = x_at(if phase > 0.5 * interval {
+                phase - interval
+            } else {
+                phase
+            });
+            let direction = if p.angular_velocity < 0.0 { -1.0 } else { 1.0 };
+            graphics.draw_triangle(
+                [
+                    (x + 8.0 * direction, y).into(),
+                    (x, y - 6.0).into(),
+                    (x, y + 6.0).into(),
+                ],
+                Color::BLUE,
+            );
+        }
+
+        let (bx, by, bw, bh) = BUTTON;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((bx, by), (bx + bw, by + bh)),
+            Color::LIGHT_GRAY,
+        );
+        text(graphics, (bx + 14.0, by + 5.0), "Tune", Color::BLACK);
+        text(
+            graphics,
+            (bx + bw + 8.0, by + 5.0),
+            &format!("r = {}", units.length(p.r)),
+            Color::BLACK,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Swings, with a damping ratio and the tempo to beat, to tune for. The
+    /// mass-based damping a pendulum starts with is heavy enough to change
+    /// the tempo as the swing dies away, so these choose a light one.
+    const SWINGS: [(f32, f32, f32); 5] = [
+        (0.3, 0.0, 60.0),
+        (0.3, 0.01, 60.0),
+        (1.0, 0.0, 100.0),
+        (1.5, 0.0, 90.0),
+        (2.0, 0.0, 45.0),
+    ];
+
+    /// A pendulum let go `amplitude` out and tuned to `bpm`, with a
+    /// metronome at that tempo watching it.
+    fn tuned(amplitude: f32, zeta: f32, bpm: f32) -> (Pendulum, Metronome) {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.angle = amplitude;
+        p.zeta = Some(zeta);
+        let mut metronome = Metronome::new();
+        metronome.set_bpm(bpm);
+        tune(&mut p, metronome.target_period()).unwrap();
+        metronome.record(&p);
+        (p, metronome)
+    }
+
+    fn assert_close(what: &str, actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3 * expected,
+            "{}: {}, wanted {}",
+            what,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn the_tuned_length_crosses_the_bottom_at_the_target_rate() {
+        for (amplitude, zeta, bpm) in SWINGS {
+            let (mut p, mut metronome) = tuned(amplitude, zeta, bpm);
+            let mut crossings = Vec::new();
+            while crossings.len() < 5 {
+                p.update();
+                metronome.record(&p);
+                if metronome.crossed != crossings.last().copied() {
+                    crossings.extend(metronome.crossed);
+                }
+            }
+            assert_close(
+                &format!("{} out at {} BPM, steps between crossings", amplitude, bpm),
+                (crossings[4] - crossings[0]) / 4.0,
+                metronome.interval(),
+            );
+        }
+    }
+
+    /// Steps `p` until `metronome` has seen `beats` turning points.
+    fn run_to(p: &mut Pendulum, metronome: &mut Metronome, beats: usize) {
+        while metronome.count < beats {
+            p.update();
+            metronome.record(p);
+        }
+    }
+
+    #[test]
+    fn the_tuned_length_swings_with_the_requested_period() {
+        for (amplitude, zeta, bpm) in SWINGS {
+            let (mut p, mut metronome) = tuned(amplitude, zeta, bpm);
+            run_to(&mut p, &mut metronome, 3);
+            // The release is the first turning point, and two more make the
+            // first whole swing, the one the tuner aimed at.
+            assert_eq!(metronome.beats[0], (0.0, 0.0));
+            assert_close(
+                &format!("{} out at {} BPM, period", amplitude, bpm),
+                (metronome.beats[2].1 - metronome.beats[0].1) / STEPS_PER_SECOND,
+                metronome.target_period(),
+            );
+        }
+    }
+
+    #[test]
+    fn a_tuned_swing_keeps_to_the_grid_and_a_long_one_falls_behind() {
+        let (mut p, mut metronome) = tuned(1.0, 0.0, 60.0);
+        run_to(&mut p, &mut metronome, 2 * FIT_BEATS);
+        assert_close("actual tempo", metronome.actual_bpm().unwrap(), 60.0);
+        assert!(metronome.last_offset_ms().unwrap().abs() < ON_BEAT_MS);
+        assert!(metronome.drift_ms_per_minute().unwrap().abs() < ON_BEAT_MS);
+
+        let (mut p, mut metronome) = tuned(1.0, 0.0, 60.0);
+        p.r *= 1.02;
+        run_to(&mut p, &mut metronome, 2 * FIT_BEATS);
+        assert!(metronome.actual_bpm().unwrap() < 59.5);
+        assert!(metronome.last_offset_ms().unwrap() > ON_BEAT_MS);
+        assert!(metronome.drift_ms_per_minute().unwrap() > 3.0 * ON_BEAT_MS);
+    }
+}
@@ -0,0 +1,786 @@
+use crate::cart::CartPendulum;
+use speedy2d::color::Color;
+use speedy2d::window::VirtualKeyCode;
+
+use crate::chain::Chain;
+use crate::config::{key_value, parse_f32, parse_f64};
+use crate::double::DoublePendulum;
+use crate::energy::EnergyLedger;
+use crate::framing::Bounds;
+use crate::integrator::Scheme;
+use crate::pendulum::{
+    BobShape, DampingModel, Pendulum, COULOMB_FRICTION, MAX_RATE, STATIC_THRESHOLD,
+};
+#[cfg(feature = "plugin")]
+use crate::plugin::{PluginEntry, PluginModel};
+use crate::renderer::Renderer;
+use crate::slack::Flight;
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// Anything that can live in a scene: it steps itself, draws itself, can be
+/// grabbed with the mouse and can describe itself as a `ModelEntry`.
+pub trait Model {
+    fn step(&mut self);
+
+    fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport);
+
+    /// Starts dragging the model if `point` is on one of its handles.
+    fn grab(&mut self, point: Vector) -> bool;
+
+    /// Moves the grabbed handle to `point`. Called after every step while
+    /// the mouse button is held.
+    fn drag_to(&mut self, point: Vector);
+
+    fn release(&mut self);
+
+    fn pivot(&self) -> Vector;
+
+    /// Moves the pivot and everything hanging from it by `by`, without
+    /// changing how it swings.
+    fn move_pivot(&mut self, by: Vector);
+
+    /// Where the points that can be grabbed are now, from the pivot down.
+    fn handles(&self) -> Vec<Vector>;
+
+    /// Everything the model can reach on its current energy, for framing
+    /// the camera: the whole of its swing rather than where it is now.
+    fn reach(&self) -> Bounds;
+
+    /// Name shown above the pivot, if the scene gave the model one.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Short description used by the legend, with lengths in `units`.
+    fn label(&self, units: &Units) -> String;
+
+    /// Lines shown in the HUD while this model is selected, with lengths
+    /// in `units`.
+    fn hud_lines(&self, units: &Units) -> Vec<String>;
+
+    /// Parameters and current state, enough to rebuild the model exactly.
+    fn entry(&self) -> ModelEntry;
+
+    /// Everything the next step starts from, exactly, for comparing runs
+    /// bit for bit. An `f32` is widened, which keeps every bit of it.
+    fn state(&self) -> Vec<f64>;
+
+    /// Offers the model a key while it's selected, before the bindings get
+    /// it. True if the model took it.
+    fn key(&mut self, _key: VirtualKeyCode) -> bool {
+        false
+    }
+
+    /// Text to draw with the HUD's font, where the model last drew.
+    fn labels(&self) -> Vec<Label> {
+        Vec::new()
+    }
+
+    fn as_pendulum(&self) -> Option<&Pendulum> {
+        None
+    }
+
+    fn as_pendulum_mut(&mut self) -> Option<&mut Pendulum> {
+        None
+    }
+}
+
+/// A line of text a model draws, at a point in the world and `size` pixels
+/// high.
+#[derive(Clone)]
+pub struct Label {
+    pub at: Vector,
+    pub size: f32,
+    pub color: Color,
+    pub text: String,
+}
+
+/// Serialized form of a model, one variant per model type. Each entry is a
+/// single line in a `[scene]` section: the type tag followed by `key=value`
+/// pairs, e.g. `pendulum x=400 y=0 r=200 angle=1`.
+#[derive(Clone, PartialEq)]
+pub enum ModelEntry {
+    Pendulum(PendulumEntry),
+    Chain(ChainEntry),
+    Double(DoubleEntry),
+    Cart(CartEntry),
+    /// A model from a plugin loaded with `--plugin`.
+    #[cfg(feature = "plugin")]
+    Plugin(PluginEntry),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct PendulumEntry {
+    pub name: String,
+    /// Id of the apparatus profile it was set up from.
+    pub apparatus: String,
+    pub x: f32,
+    pub y: f32,
+    pub r: f32,
+    pub angle: f32,
+    pub angular_velocity: f32,
+    /// As the last step left it, which the HUD and the history show.
+    pub angular_acceleration: f32,
+    pub m: f32,
+    pub g: f32,
+    /// Fraction of `g` gained per pixel the bob hangs below the pivot; see
+    /// `Pendulum::gravity_gradient`.
+    pub gravity_gradient: f32,
+    /// Hung from a string that can go slack rather than a rod.
+    pub string: bool,
+    pub damping: DampingModel,
+    /// Kinetic friction and the static dead band of the dry friction model;
+    /// see `Pendulum::coulomb_friction`.
+    pub coulomb_friction: f32,
+    pub static_threshold: f32,
+    /// Damping ratio chosen in place of the mass-based viscous damping.
+    pub damping_ratio: Option<f32>,
+    pub bob: BobShape,
+    pub drive_amplitude: f32,
+    pub drive_frequency: f32,
+    /// Added to the drive's phase once it's been retuned; see
+    /// `Pendulum::retune_drive`.
+    pub drive_offset: f32,
+    /// Steps simulated so far, the clock the drive is phased against.
+    pub time: f32,
+    /// Seconds of soft start and the step it began at; see
+    /// `Pendulum::ease_in`.
+    pub soft_start: f32,
+    pub ramp_from: f32,
+    /// The bob's flight, if the string is slack.
+    pub slack: Option<Flight>,
+    /// Where the energy has gone so far.
+    pub ledger: EnergyLedger,
+    pub integrator: Scheme,
+    /// The substep RK45 tries next, once it has stepped.
+    pub substep: Option<f32>,
+    /// Steps taken for each step of the scene.
+    pub rate: u32,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ChainEntry {
+    pub x: f32,
+    pub y: f32,
+    pub link_length: f32,
+    pub g: f32,
+    pub angles: Vec<f32>,
+    pub angular_velocities: Vec<f32>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct DoubleEntry {
+    pub x: f32,
+    pub y: f32,
+    pub lengths: [f32; 2],
+    pub masses: [f32; 2],
+    pub g: f32,
+    pub angles: [f32; 2],
+    pub angular_velocities: [f32; 2],
+    pub tolerance: f32,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct CartEntry {
+    pub x: f32,
+    pub y: f32,
+    pub left: f32,
+    pub right: f32,
+    pub cart_mass: f32,
+    pub m: f32,
+    pub r: f32,
+    pub g: f32,
+    pub friction: f32,
+    pub angle: f32,
+    pub angular_velocity: f32,
+    pub velocity: f32,
+}
+
+impl Default for PendulumEntry {
+    fn default() -> PendulumEntry {
+        PendulumEntry {
+            name: String::new(),
+            apparatus: String::new(),
+            x: 400.0,
+            y: 0.0,
+            r: 200.0,
+            angle: 1.0,
+            angular_velocity: 0.0,
+            angular_acceleration: 0.0,
+            m: 1.0,
+            g: 0.5,
+            gravity_gradient: 0.0,
+            string: false,
+            damping: DampingModel::Viscous,
+            coulomb_friction: COULOMB_FRICTION,
+            static_threshold: STATIC_THRESHOLD,
+            damping_ratio: None,
+            bob: BobShape::Point,
+            drive_amplitude: 0.0,
+            drive_frequency: 0.0,
+            drive_offset: 0.0,
+            time: 0.0,
+            soft_start: 0.0,
+            ramp_from: 0.0,
+            slack: None,
+            ledger: EnergyLedger::default(),
+            integrator: Scheme::SymplecticEuler,
+            substep: None,
+            rate: 1,
+        }
+    }
+}
+
+impl Default for ChainEntry {
+    fn default() -> ChainEntry {
+        ChainEntry {
+            x: 400.0,
+            y: 0.0,
+            link_length: 70.0,
+            g: 0.5,
+            angles: vec![1.0; 3],
+            angular_velocities: Vec::new(),
+        }
+    }
+}
+
+impl Default for DoubleEntry {
+    fn default() -> DoubleEntry {
+        DoubleEntry {
+            x: 400.0,
+            y: 200.0,
+            lengths: [100.0, 100.0],
+            masses: [1.0, 1.0],
+            g: 0.5,
+            angles: [2.0, 2.0],
+            angular_velocities: [0.0, 0.0],
+            tolerance: 1e-6,
+        }
+    }
+}
+
+impl Default for CartEntry {
+    fn default() -> CartEntry {
+        CartEntry {
+            x: 400.0,
+            y: 200.0,
+            left: 60.0,
+            right: 740.0,
+            cart_mass: 2.0,
+            m: 1.0,
+            r: 150.0,
+            g: 0.5,
+            friction: 0.0,
+            angle: 1.0,
+            angular_velocity: 0.0,
+            velocity: 0.0,
+        }
+    }
+}
+
+impl PendulumEntry {
+    /// The same pendulum with nothing carried over from the swing it was
+    /// in: no flight, no energy counted yet and no substep learned, for
+    /// starting a new one from.
+    pub fn fresh(&self) -> PendulumEntry {
+        PendulumEntry {
+            angular_acceleration: 0.0,
+            slack: None,
+            ledger: EnergyLedger::default(),
+            substep: None,
+            ..self.clone()
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "name" => self.name = value.to_string(),
+            "apparatus" => self.apparatus = value.to_string(),
+            "x" => self.x = parse_f32(key, value)?,
+            "y" => self.y = parse_f32(key, value)?,
+            "r" => self.r = parse_positive(key, value)?,
+            "angle" => self.angle = parse_f32(key, value)?,
+            "angular_velocity" => self.angular_velocity = parse_f32(key, value)?,
+            "angular_acceleration" => self.angular_acceleration = parse_f32(key, value)?,
+            "m" => self.m = parse_positive(key, value)?,
+            "g" => self.g = parse_f32(key, value)?,
+            "gravity_gradient" => self.gravity_gradient = parse_f32(key, value)?,
+            "string" => {
+                self.string = value.parse().map_err(|_| {
+                    format!("expected true or false for `string`, found `{}`", value)
+                })?
+            }
+            "damping" => {
+                self.damping = match value {
+                    "viscous" => DampingModel::Viscous,
+                    "coulomb" => DampingModel::Coulomb,
+                    _ => return Err(format!("unknown damping model `{}`", value)),
+                }
+            }
+            "coulomb_friction" => self.coulomb_friction = parse_friction(key, value)?,
+            "static_threshold" => self.static_threshold = parse_friction(key, value)?,
+            "damping_ratio" => self.damping_ratio = Some(parse_f32(key, value)?),
+            "bob" => {
+                self.bob = BobShape::parse(value).ok_or(format!("unknown bob shape `{}`", value))?
+            }
+            "drive_amplitude" => self.drive_amplitude = parse_f32(key, value)?,
+            "drive_frequency" => self.drive_frequency = parse_f32(key, value)?,
+            "drive_offset" => self.drive_offset = parse_f32(key, value)?,
+            "time" => self.time = parse_f32(key, value)?,
+            "soft_start" => self.soft_start = parse_friction(key, value)?,
+            "ramp_from" => self.ramp_from = parse_f32(key, value)?,
+            "slack" => {
+                let [x, y, vx, vy] = parse_list(key, value)?[..] else {
+                    return Err(format!("expected x,y,vx,vy for `slack`, found `{}`", value));
+                };
+                self.slack = Some(Flight { x, y, vx, vy });
+            }
+            "ledger" => {
+                let values = value
+                    .split(',')
+                    .map(|v| parse_f64(key, v))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.ledger = EnergyLedger::from_values(&values).ok_or(format!(
+                    "expected 8 or 9 numbers for `ledger`, found `{}`",
+                    value
+                ))?;
+            }
+            "substep" => self.substep = Some(parse_positive(key, value)?),
+            "integrator" => {
+                self.integrator =
+                    Scheme::parse(value).ok_or(format!("unknown integrator `{}`", value))?
+            }
+            "rate" => {
+                self.rate = value
+                    .parse()
+                    .ok()
+                    .filter(|rate| (1..=MAX_RATE).contains(rate))
+                    .ok_or_else(|| {
+                        format!("expected a rate from 1 to {}, found `{}`", MAX_RATE, value)
+                    })?
+            }
+            _ => return Err(format!("unknown pendulum parameter `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = String::from("pendulum");
+        if !self.name.is_empty() {
+            line.push_str(&format!(" name={}", quote(&self.name)));
+        }
+        if !self.apparatus.is_empty() {
+            line.push_str(&format!(" apparatus={}", quote(&self.apparatus)));
+        }
+        line.push_str(&format!(
+            " x={} y={} r={} angle={} angular_velocity={} m={} g={} damping={} bob={} drive_amplitude={} drive_frequency={}",
+            self.x,
+            self.y,
+            self.r,
+            self.angle,
+            self.angular_velocity,
+            self.m,
+            self.g,
+            match self.damping {
+                DampingModel::Viscous => "viscous",
+                DampingModel::Coulomb => "coulomb",
+            },
+            self.bob.name(),
+            self.drive_amplitude,
+            self.drive_frequency
+        ));
+        if let Some(ratio) = self.damping_ratio {
+            line.push_str(&format!(" damping_ratio={}", ratio));
+        }
+        if self.coulomb_friction != COULOMB_FRICTION {
+            line.push_str(&format!(" coulomb_friction={}", self.coulomb_friction));
+        }
+        if self.static_threshold != STATIC_THRESHOLD {
+            line.push_str(&format!(" static_threshold={}", self.static_threshold));
+        }
+        if self.gravity_gradient != 0.0 {
+            line.push_str(&format!(" gravity_gradient={}", self.gravity_gradient));
+        }
+        if self.string {
+            line.push_str(" string=true");
+        }
+        if self.angular_acceleration != 0.0 {
+            line.push_str(&format!(
+                " angular_acceleration={}",
+                self.angular_acceleration
+            ));
+        }
+        if self.drive_offset != 0.0 {
+            line.push_str(&format!(" drive_offset={}", self.drive_offset));
+        }
+        if self.time != 0.0 {
+            line.push_str(&format!(" time={}", self.time));
+        }
+        if self.soft_start != 0.0 {
+            line.push_str(&format!(
+                " soft_start={} ramp_from={}",
+                self.soft_start, self.ramp_from
+            ));
+        }
+        if let Some(flight) = self.slack {
+            line.push_str(&format!(
+                " slack={}",
+                format_list(&[flight.x, flight.y, flight.vx, flight.vy])
+            ));
+        }
+        if self.ledger != EnergyLedger::default() {
+            let values: Vec<String> = self.ledger.to_values().iter().map(f64::to_string).collect();
+            line.push_str(&format!(" ledger={}", values.join(",")));
+        }
+        if self.integrator != Scheme::SymplecticEuler {
+            line.push_str(&format!(" integrator={}", self.integrator.key()));
+        }
+        if let Some(substep) = self.substep {
+            line.push_str(&format!(" substep={}", substep));
+        }
+        if self.rate != 1 {
+            line.push_str(&format!(" rate={}", self.rate));
+        }
+        line
+    }
+}
+
+impl ChainEntry {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "x" => self.x = parse_f32(key, value)?,
+            "y" => self.y = parse_f32(key, value)?,
+            "link_length" => self.link_length = parse_positive(key, value)?,
+            "g" => self.g = parse_f32(key, value)?,
+            "links" => {
+                let links: usize = value
+                    .parse()
+                    .map_err(|_| format!("invalid link count `{}`", value))?;
+                let angle = self.angles.first().copied().unwrap_or(1.0);
+                self.angles = vec![angle; links];
+            }
+            "angle" => {
+                let angle = parse_f32(key, value)?;
+                self.angles.iter_mut().for_each(|a| *a = angle);
+            }
+            "angles" => self.angles = parse_list(key, value)?,
+            "angular_velocities" => self.angular_velocities = parse_list(key, value)?,
+            _ => return Err(format!("unknown chain parameter `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "chain x={} y={} link_length={} g={} angles={} angular_velocities={}",
+            self.x,
+            self.y,
+            self.link_length,
+            self.g,
+            format_list(&self.angles),
+            format_list(&self.angular_velocities)
+        )
+    }
+}
+
+impl DoubleEntry {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "x" => self.x = parse_f32(key, value)?,
+            "y" => self.y = parse_f32(key, value)?,
+            "l1" => self.lengths[0] = parse_positive(key, value)?,
+            "l2" => self.lengths[1] = parse_positive(key, value)?,
+            "m1" => self.masses[0] = parse_positive(key, value)?,
+            "m2" => self.masses[1] = parse_positive(key, value)?,
+            "g" => self.g = parse_f32(key, value)?,
+            "angle1" => self.angles[0] = parse_f32(key, value)?,
+            "angle2" => self.angles[1] = parse_f32(key, value)?,
+            "angular_velocity1" => self.angular_velocities[0] = parse_f32(key, value)?,
+            "angular_velocity2" => self.angular_velocities[1] = parse_f32(key, value)?,
+            "tolerance" => self.tolerance = parse_f32(key, value)?,
+            _ => return Err(format!("unknown double pendulum parameter `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "double x={} y={} l1={} l2={} m1={} m2={} g={} angle1={} angle2={} angular_velocity1={} angular_velocity2={} tolerance={}",
+            self.x,
+            self.y,
+            self.lengths[0],
+            self.lengths[1],
+            self.masses[0],
+            self.masses[1],
+            self.g,
+            self.angles[0],
+            self.angles[1],
+            self.angular_velocities[0],
+            self.angular_velocities[1],
+            self.tolerance
+        )
+    }
+}
+
+impl CartEntry {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "x" => self.x = parse_f32(key, value)?,
+            "y" => self.y = parse_f32(key, value)?,
+            "left" => self.left = parse_f32(key, value)?,
+            "right" => self.right = parse_f32(key, value)?,
+            "cart_mass" => self.cart_mass = parse_positive(key, value)?,
+            "m" => self.m = parse_positive(key, value)?,
+            "r" => self.r = parse_positive(key, value)?,
+            "g" => self.g = parse_f32(key, value)?,
+            "friction" => self.friction = parse_f32(key, value)?,
+            "angle" => self.angle = parse_f32(key, value)?,
+            "angular_velocity" => self.angular_velocity = parse_f32(key, value)?,
+            "velocity" => self.velocity = parse_f32(key, value)?,
+            _ => return Err(format!("unknown cart parameter `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "cart x={} y={} left={} right={} cart_mass={} m={} r={} g={} friction={} angle={} angular_velocity={} velocity={}",
+            self.x,
+            self.y,
+            self.left,
+            self.right,
+            self.cart_mass,
+            self.m,
+            self.r,
+            self.g,
+            self.friction,
+            self.angle,
+            self.angular_velocity,
+            self.velocity
+        )
+    }
+}
+
+impl ModelEntry {
+    pub fn parse(line: &str) -> Result<ModelEntry, String> {
+        let tokens = tokens(line)?;
+        let mut tokens = tokens.iter();
+        let tag = tokens.next().ok_or("empty model entry")?;
+
+        let mut entry = match tag.as_str() {
+            "pendulum" => ModelEntry::Pendulum(PendulumEntry::default()),
+            "chain" => ModelEntry::Chain(ChainEntry::default()),
+            "double" => ModelEntry::Double(DoubleEntry::default()),
+            "cart" => ModelEntry::Cart(CartEntry::default()),
+            #[cfg(feature = "plugin")]
+            "plugin" => ModelEntry::Plugin(PluginEntry::default()),
+            _ => return Err(format!("unknown model type `{}`", tag)),
+        };
+
+        for token in tokens {
+            let (key, value) =
+                key_value(token).ok_or(format!("expected `key=value`, found `{}`", token))?;
+            match &mut entry {
+                ModelEntry::Pendulum(pendulum) => pendulum.set(key, value)?,
+                ModelEntry::Chain(chain) => chain.set(key, value)?,
+                ModelEntry::Double(double) => double.set(key, value)?,
+                ModelEntry::Cart(cart) => cart.set(key, value)?,
+                #[cfg(feature = "plugin")]
+                ModelEntry::Plugin(plugin) => plugin.set(key, value)?,
+            }
+        }
+
+        if let ModelEntry::Chain(chain) = &mut entry {
+            if chain.angles.is_empty() {
+                return Err("a chain needs at least one link".to_string());
+            }
+            chain.angular_velocities.resize(chain.angles.len(), 0.0);
+        }
+        #[cfg(feature = "plugin")]
+        if let ModelEntry::Plugin(plugin) = &entry {
+            plugin.check()?;
+        }
+
+        Ok(entry)
+    }
+
+    pub fn build(&self) -> Box<dyn Model> {
+        match self {
+            ModelEntry::Pendulum(pendulum) => Box::new(Pendulum::from_entry(pendulum)),
+            ModelEntry::Chain(chain) => Box::new(Chain::from_entry(chain)),
+            ModelEntry::Double(double) => Box::new(DoublePendulum::from_entry(double)),
+            ModelEntry::Cart(cart) => Box::new(CartPendulum::from_entry(cart)),
+            #[cfg(feature = "plugin")]
+            ModelEntry::Plugin(plugin) => Box::new(PluginModel::from_entry(plugin)),
+        }
+    }
+
+    /// Formats the entry as a `[scene]` line that `parse` reads back.
+    pub fn to_line(&self) -> String {
+        match self {
+            ModelEntry::Pendulum(pendulum) => pendulum.to_line(),
+            ModelEntry::Chain(chain) => chain.to_line(),
+            ModelEntry::Double(double) => double.to_line(),
+            ModelEntry::Cart(cart) => cart.to_line(),
+            #[cfg(feature = "plugin")]
+            ModelEntry::Plugin(plugin) => plugin.to_line(),
+        }
+    }
+}
+
+/// A length or a mass, which has to be more than nothing.
+fn parse_positive(key: &str, value: &str) -> Result<f32, String> {
+    match parse_f32(key, value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("`{}` has to be positive, found `{}`", key, value)),
+    }
+}
+
+/// Splits an entry into its tokens at whitespace, except inside double
+/// quotes, which are taken off. Within quotes a backslash escapes the next
+/// character, so a name can hold spaces, quotes and backslashes.
+pub fn tokens(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut token = None::<String>;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let token = token.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => token.push(chars.next().ok_or("unterminated quote")?),
+                        Some(c) => token.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            c if c.is_whitespace() => tokens.extend(token.take()),
+            c => token.get_or_insert_with(String::new).push(c),
+        }
+    }
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+/// `text` as `tokens` reads it back: as it is when it's a plain word, in
+/// quotes otherwise.
+fn quote(text: &str) -> String {
+    if !text.is_empty() && !text.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return text.to_string();
+    }
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A friction, which can't pull the bob along.
+fn parse_friction(key: &str, value: &str) -> Result<f32, String> {
+    match parse_f32(key, value)? {
+        friction if friction >= 0.0 => Ok(friction),
+        _ => Err(format!("`{}` can't be negative, found `{}`", key, value)),
+    }
+}
+
+fn parse_list(key: &str, value: &str) -> Result<Vec<f32>, String> {
+    value.split(',').map(|v| parse_f32(key, v)).collect()
+}
+
+fn format_list(values: &[f32]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(entry: &ModelEntry) -> ModelEntry {
+        let line = entry.to_line();
+        ModelEntry::parse(&line).unwrap_or_else(|e| panic!("`{}` didn't parse: {}", line, e))
+    }
+
+    #[test]
+    fn every_kind_of_entry_reads_back_as_written() {
+        let entries = [
+            ModelEntry::Pendulum(PendulumEntry {
+                name: "Mars".to_string(),
+                angle: -0.25,
+                damping: DampingModel::Coulomb,
+                coulomb_friction: 0.0001,
+                damping_ratio: Some(0.3),
+                bob: BobShape::Disc,
+                string: true,
+                time: 120.0,
+                integrator: Scheme::Rk4,
+                rate: 5,
+                ..PendulumEntry::default()
+            }),
+            ModelEntry::Chain(ChainEntry {
+                angles: vec![0.5, -0.25, 1.0],
+                angular_velocities: vec![0.0, 0.01, 0.0],
+                ..ChainEntry::default()
+            }),
+            ModelEntry::Double(DoubleEntry::default()),
+            ModelEntry::Cart(CartEntry {
+                friction: 0.1,
+                ..CartEntry::default()
+            }),
+        ];
+        for entry in &entries {
+            assert!(round_trip(entry) == *entry, "{}", entry.to_line());
+        }
+    }
+
+    #[test]
+    fn names_with_spaces_and_quotes_read_back() {
+        for name in ["Big Ben", "say \"hi\"", "back\\slash", " padded"] {
+            let entry = ModelEntry::Pendulum(PendulumEntry {
+                name: name.to_string(),
+                apparatus: "lab bench 2".to_string(),
+                ..PendulumEntry::default()
+            });
+            let ModelEntry::Pendulum(read) = round_trip(&entry) else {
+                panic!("a pendulum should read back as a pendulum");
+            };
+            assert_eq!(read.name, name.trim());
+            assert_eq!(read.apparatus, "lab bench 2");
+        }
+        assert!(ModelEntry::parse("pendulum name=\"open").is_err());
+    }
+
+    #[test]
+    fn non_finite_and_non_positive_values_are_refused() {
+        for line in [
+            "pendulum angle=NaN",
+            "pendulum g=inf",
+            "pendulum r=0",
+            "pendulum m=-1",
+            "pendulum coulomb_friction=-0.1",
+            "chain link_length=0",
+            "double l2=-5",
+            "double m1=0",
+            "cart cart_mass=0",
+            "cart r=-infinity",
+        ] {
+            assert!(ModelEntry::parse(line).is_err(), "`{}` was taken", line);
+        }
+        assert!(ModelEntry::parse("pendulum r=150 m=2").is_ok());
+    }
+}
@@ -0,0 +1,384 @@
+//! Fixed round obstacles drawn into the scene for the bobs to bounce off.
+//! Holding the obstacle key and dragging draws one, from its centre out to
+//! its rim; dragging one moves it and right-clicking deletes it. A bob that
+//! hits one is reflected, losing the share of its speed into the obstacle
+//! the restitution says, then carries on round its circle with what's left
+//! across the rod, or flies off if it hangs from a string that lets it.
+//!
+//! Each step the bob's path is checked as the straight chord it moved
+//! along, with the bob's radius, so a fast swing can't step through an
+//! obstacle between frames.
+//!
+//! Obstacles are saved with the scene as an `[obstacles]` section, the
+//! restitution then a line of `<x> <y> <radius>` for each:
+//!
+//! ```text
+//! [obstacles]
+//! restitution = 0.8
+//! 520 260 30
+//! ```
+
+use std::f32::consts::TAU;
+
+use speedy2d::color::Color;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::draw_arc;
+use crate::focus::{Handling, Registry, Target};
+use crate::framing::{self, Bounds};
+use crate::math;
+use crate::pendulum::{Pendulum, BOB_RADIUS};
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// More than this many obstacles only slows the step down.
+pub const MAX_OBSTACLES: usize = 16;
+const DEFAULT_RESTITUTION: f32 = 0.8;
+/// Smaller drags are taken as slips of the mouse.
+const MIN_RADIUS: f32 = 5.0;
+
+/// Earliest fraction of the step from `start` to `end` at which a circle of
+/// `radius` moving along it touches a fixed circle of `fixed_radius` at
+/// `center`, if it does within the step. A circle already overlapping
+/// touches at the start only if it's moving further in, so it can always
+/// get out.
+pub fn swept_hit(
+    start: Vector,
+    end: Vector,
+    radius: f32,
+    center: Vector,
+    fixed_radius: f32,
+) -> Option<f32> {
+    // Where the centre of the moving circle meets a circle of both radii.
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let (fx, fy) = (start.x - center.x, start.y - center.y);
+    let reach = radius + fixed_radius;
+    let a = dx * dx + dy * dy;
+    let b = 2.0 * (fx * dx + fy * dy);
+    let c = fx * fx + fy * fy - reach * reach;
+    if c <= 0.0 {
+        return (b < 0.0).then_some(0.0);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then_some(t)
+}
+
+/// `velocity` bounced off a surface with outward `normal`, keeping
+/// `restitution` of the part into it.
+pub fn reflect(velocity: Vector, normal: Vector, restitution: f32) -> Vector {
+    let into = velocity.x * normal.x + velocity.y * normal.y;
+    if into >= 0.0 {
+        return velocity;
+    }
+    let kept = (1.0 + restitution) * into;
+    Vector::new(velocity.x - kept * normal.x, velocity.y - kept * normal.y)
+}
+
+#[derive(Copy, Clone)]
+pub struct Obstacle {
+    pub center: Vector,
+    pub radius: f32,
+}
+
+impl Obstacle {
+    fn contains(&self, point: Vector) -> bool {
+        (point.x - self.center.x).hypot(point.y - self.center.y) <= self.radius
+    }
+}
+
+pub struct Obstacles {
+    pub obstacles: Vec<Obstacle>,
+    restitution: f32,
+    /// Where the drag drawing a new obstacle started.
+    drawing: Option<Vector>,
+    /// The obstacle being moved, and where it was grabbed from its centre.
+    dragging: Option<(usize, Vector)>,
+}
+
+impl Obstacles {
+    pub fn new() -> Obstacles {
+        Obstacles {
+            obstacles: Vec::new(),
+            restitution: DEFAULT_RESTITUTION,
+            drawing: None,
+            dragging: None,
+        }
+    }
+
+    /// The box round all the obstacles, if there are any.
+    pub fn bounds(&self) -> Option<Bounds> {
+        framing::union(
+            self.obstacles
+                .iter()
+                .map(|obstacle| Bounds::around(obstacle.center, obstacle.radius)),
+        )
+    }
+
+    pub fn from_config(section: &Section) -> Result<Obstacles, String> {
+        let mut obstacles = Obstacles::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            if let Some(("restitution", value)) = key_value(line) {
+                let restitution = parse_f32("restitution", value).map_err(error)?;
+                if !(0.0..=1.0).contains(&restitution) {
+                    return Err(error("`restitution` must be from 0 to 1".to_string()));
+                }
+                obstacles.restitution = restitution;
+                continue;
+            }
+
+            let numbers: Vec<f32> = line
+                .split_whitespace()
+                .map(|word| word.parse().ok())
+                .collect::<Option<_>>()
+                .filter(|numbers: &Vec<f32>| numbers.len() == 3)
+                .ok_or_else(|| error(format!("expected `<x> <y> <radius>`, found `{}`", line)))?;
+            if numbers[2] <= 0.0 {
+                return Err(error("an obstacle's radius must be positive".to_string()));
+            }
+            if obstacles.obstacles.len() == MAX_OBSTACLES {
+                return Err(error(format!("no more than {} obstacles", MAX_OBSTACLES)));
+            }
+            obstacles.obstacles.push(Obstacle {
+                center: Vector::new(numbers[0], numbers[1]),
+                radius: numbers[2],
+            });
+        }
+
+        Ok(obstacles)
+    }
+
+    /// The obstacles as an `[obstacles]` section, or nothing if there are
+    /// none.
+    pub fn to_section(&self) -> String {
+        if self.obstacles.is_empty() {
+            return String::new();
+        }
+        let mut text = format!("[obstacles]\nrestitution = {}\n", self.restitution);
+        for obstacle in &self.obstacles {
+            text.push_str(&format!(
+                "{} {} {}\n",
+                obstacle.center.x, obstacle.center.y, obstacle.radius
+            ));
+        }
+        text
+    }
+
+    /// Starts drawing an obstacle centred on `point`, unless there are as
+    /// many as there can be.
+    pub fn start_drawing(&mut self, point: Vector) -> Result<(), String> {
+        if self.obstacles.len() >= MAX_OBSTACLES {
+            return Err(format!("No more than {} obstacles", MAX_OBSTACLES));
+        }
+        self.drawing = Some(point);
+        Ok(())
+    }
+
+    /// Finishes the obstacle being drawn with its rim at `point`. Returns
+    /// whether there was one.
+    pub fn finish_drawing(&mut self, point: Vector) -> bool {
+        let Some(center) = self.drawing.take() else {
+            return false;
+        };
+        let radius = (point.x - center.x).hypot(point.y - center.y);
+        if radius >= MIN_RADIUS {
+            self.obstacles.push(Obstacle { center, radius });
+        }
+        true
+    }
+
+    pub fn register(&self, registry: &mut Registry, view: &Viewport) {
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            registry.register(
+                Target::Obstacle(i),
+                view.world_to_screen(obstacle.center),
+                view.length(obstacle.radius),
+                Handling::Drag,
+            );
+        }
+    }
+
+    /// Starts moving the topmost obstacle under `point`, if there is one.
+    pub fn grab(&mut self, point: Vector) -> bool {
+        let Some(index) = self.obstacles.iter().rposition(|o| o.contains(point)) else {
+            return false;
+        };
+        let center = self.obstacles[index].center;
+        self.dragging = Some((index, Vector::new(point.x - center.x, point.y - center.y)));
+        true
+    }
+
+    pub fn drag_to(&mut self, point: Vector) {
+        if let Some((index, offset)) = self.dragging {
+            self.obstacles[index].center = Vector::new(point.x - offset.x, point.y - offset.y);
+        }
+    }
+
+    /// Lets go of the obstacle being moved. Returns whether there was one.
+    pub fn release(&mut self) -> bool {
+        self.dragging.take().is_some()
+    }
+
+    /// Deletes the topmost obstacle under `point`, if there is one.
+    pub fn remove_at(&mut self, point: Vector) -> bool {
+        let Some(index) = self.obstacles.iter().rposition(|o| o.contains(point)) else {
+            return false;
+        };
+        self.obstacles.remove(index);
+        self.dragging = None;
+        true
+    }
+
+    /// Bounces `p` off the first obstacle its bob ran into on the way from
+    /// `before`, the bob's position at the start of the step. Returns
+    /// whether it hit one.
+    pub fn collide(&self, p: &mut Pendulum, before: Vector) -> bool {
+        let after = p.position;
+        let Some((t, obstacle)) = self
+            .obstacles
+            .iter()
+            .filter_map(|obstacle| {
+                swept_hit(before, after, BOB_RADIUS, obstacle.center, obstacle.radius)
+                    .map(|t| (t, obstacle))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+        else {
+            return false;
+        };
+
+        let contact = Vector::new(
+            before.x + t * (after.x - before.x),
+            before.y + t * (after.y - before.y),
+        );
+        let (nx, ny) = (contact.x - obstacle.center.x, contact.y - obstacle.center.y);
+        let length = math::hypot(nx, ny).max(f32::MIN_POSITIVE);
+        let velocity = Vector::new(after.x - before.x, after.y - before.y);
+        let bounced = reflect(
+            velocity,
+            Vector::new(nx / length, ny / length),
+            self.restitution,
+        );
+        p.deflect(contact, bounced);
+        true
+    }
+
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport, mouse: Vector) {
+        for obstacle in &self.obstacles {
+            let center = view.world_to_screen(obstacle.center);
+            graphics.draw_circle(
+                center,
+                view.length(obstacle.radius),
+                view.tint(Color::DARK_GRAY),
+            );
+            graphics.draw_circle(
+                center,
+                view.length(obstacle.radius - 3.0).max(0.0),
+                view.tint(Color::from_rgb(0.75, 0.7, 0.65)),
+            );
+        }
+
+        // The one being drawn, as an outline out to the mouse.
+        if let Some(center) = self.drawing {
+            let radius = (mouse.x - center.x).hypot(mouse.y - center.y);
+            draw_arc(
+                graphics,
+                view,
+                center,
+                radius,
+                0.0,
+                TAU,
+                2.0,
+                view.tint(Color::DARK_GRAY),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(start: (f32, f32), end: (f32, f32), center: (f32, f32), radius: f32) -> Option<f32> {
+        swept_hit(
+            Vector::new(start.0, start.1),
+            Vector::new(end.0, end.1),
+            10.0,
+            Vector::new(center.0, center.1),
+            radius,
+        )
+    }
+
+    #[test]
+    fn a_moving_circle_touches_when_the_centres_are_both_radii_apart() {
+        // Head on, touching with the centre at x = 30.
+        assert!((hit((0.0, 0.0), (100.0, 0.0), (60.0, 0.0), 20.0).unwrap() - 0.3).abs() < 1e-6);
+        // Off to the side, by less and by more than both radii.
+        let t = hit((0.0, 29.0), (100.0, 29.0), (60.0, 0.0), 20.0).unwrap();
+        let x = 100.0 * t;
+        assert!(((60.0 - x).hypot(29.0) - 30.0).abs() < 1e-3);
+        assert!(hit((0.0, 31.0), (100.0, 31.0), (60.0, 0.0), 20.0).is_none());
+        // Short of it, or moving away from it.
+        assert!(hit((0.0, 0.0), (20.0, 0.0), (60.0, 0.0), 20.0).is_none());
+        assert!(hit((0.0, 0.0), (-100.0, 0.0), (60.0, 0.0), 20.0).is_none());
+        // Standing still outside it.
+        assert!(hit((0.0, 0.0), (0.0, 0.0), (60.0, 0.0), 20.0).is_none());
+    }
+
+    #[test]
+    fn a_fast_step_cannot_pass_through() {
+        // Both ends are well clear; only the chord between them crosses.
+        let t = hit((-500.0, 0.0), (500.0, 0.0), (0.0, 0.0), 2.0).unwrap();
+        assert!((t - 0.488).abs() < 1e-4, "{}", t);
+    }
+
+    #[test]
+    fn an_overlapping_circle_is_only_stopped_going_further_in() {
+        assert_eq!(hit((50.0, 0.0), (55.0, 0.0), (60.0, 0.0), 20.0), Some(0.0));
+        assert!(hit((50.0, 0.0), (45.0, 0.0), (60.0, 0.0), 20.0).is_none());
+    }
+
+    #[test]
+    fn reflecting_keeps_the_restitution_of_the_part_into_the_surface() {
+        let normal = Vector::new(0.0, -1.0);
+        let bounced = reflect(Vector::new(3.0, 4.0), normal, 0.5);
+        assert_eq!((bounced.x, bounced.y), (3.0, -2.0));
+        let bounced = reflect(Vector::new(3.0, 4.0), normal, 1.0);
+        assert_eq!((bounced.x, bounced.y), (3.0, -4.0));
+        // Already leaving, it's left alone.
+        let leaving = reflect(Vector::new(3.0, -4.0), normal, 0.5);
+        assert_eq!((leaving.x, leaving.y), (3.0, -4.0));
+    }
+
+    #[test]
+    fn a_swinging_bob_bounces_back_off_an_obstacle_on_its_circle() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.angle = 0.3;
+        p.angular_velocity = -0.04;
+        let mut obstacles = Obstacles::new();
+        let center = Vector::new(400.0 + 200.0 * (-0.3f32).sin(), 200.0 * (-0.3f32).cos());
+        obstacles.obstacles.push(Obstacle {
+            center,
+            radius: 10.0,
+        });
+
+        let mut bounced = false;
+        for _ in 0..60 {
+            let before = p.position;
+            p.update();
+            if obstacles.collide(&mut p, before) {
+                bounced = true;
+                break;
+            }
+        }
+        assert!(bounced, "the bob never reached the obstacle");
+        assert!(p.angular_velocity > 0.0);
+        let gap = (p.position.x - center.x).hypot(p.position.y - center.y);
+        assert!(gap >= BOB_RADIUS + 10.0 - 1e-2, "{} inside", gap);
+    }
+}
@@ -0,0 +1,186 @@
+//! Where the energy went: a stacked area chart of the selected pendulum's
+//! energy ledger over the last minute. Kinetic and potential energy make up
+//! what's left; stacked on top is what viscous damping, friction and a
+//! slack string snapping taut have taken, so the top of the stack is the
+//! energy it started with plus everything put in since.
+
+use std::collections::VecDeque;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::energy;
+use crate::pendulum::Pendulum;
+use crate::STEPS_PER_SECOND;
+
+const RECT: (f32, f32, f32, f32) = (10.0, 234.0, 200.0, 238.0);
+const PLOT_HEIGHT: f32 = 100.0;
+/// Seconds shown, and steps between samples.
+const SECONDS: f32 = 60.0;
+const DECIMATION: usize = 6;
+const CAPACITY: usize = (SECONDS * STEPS_PER_SECOND) as usize / DECIMATION;
+
+/// The stacked quantities, bottom first.
+const BANDS: [(&str, Color); 5] = [
+    ("Kinetic", Color::from_rgb(0.9, 0.5, 0.2)),
+    ("Potential", Color::from_rgb(0.3, 0.5, 0.9)),
+    ("Viscous loss", Color::from_rgb(0.5, 0.5, 0.55)),
+    ("Friction loss", Color::from_rgb(0.6, 0.4, 0.3)),
+    ("String snaps", Color::from_rgb(0.7, 0.6, 0.2)),
+];
+
+pub struct EnergyPartition {
+    pub visible: bool,
+    /// Each band's energy, plus the initial energy, taken every
+    /// `DECIMATION` steps.
+    samples: VecDeque<([f32; 5], f32)>,
+    steps: usize,
+}
+
+impl EnergyPartition {
+    pub fn new() -> EnergyPartition {
+        EnergyPartition {
+            visible: false,
+            samples: VecDeque::with_capacity(CAPACITY),
+            steps: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.steps = 0;
+    }
+
+    pub fn record(&mut self, p: &Pendulum) {
+        self.steps += 1;
+        if !self.steps.is_multiple_of(DECIMATION) {
+            return;
+        }
+
+        let (kinetic, potential) = energy::precise_parts(p);
+        let ledger = &p.ledger;
+        if self.samples.len() == CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((
+            [
+                kinetic as f32,
+                potential as f32,
+                ledger.viscous as f32,
+                ledger.friction as f32,
+                ledger.snap as f32,
+            ],
+            ledger.initial as f32,
+        ));
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, p: &Pendulum) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = RECT;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.6),
+        );
+        let text = |graphics: &mut Graphics2D, x: f32, y: f32, text: &str| {
+            graphics.draw_text(
+                (x, y),
+                Color::BLACK,
+                &font.layout_text(text, 14.0, TextOptions::new()),
+            );
+        };
+        text(
+            graphics,
+            left + 4.0,
+            top + 2.0,
+            "Energy over the last minute",
+        );
+
+        let plot_top = top + 20.0;
+        let scale = self
+            .samples
+            .iter()
+            .map(|(bands, initial)| bands.iter().sum::<f32>().max(*initial))
+            .fold(f32::MIN_POSITIVE, f32::max);
+        let y = |energy: f32| plot_top + PLOT_HEIGHT * (1.0 - energy / scale);
+        let x = |i: usize| left + width * i as f32 / (CAPACITY - 1) as f32;
+
+        for (i, pair) in self
+            .samples
+            .iter()
+            .collect::<Vec<_>>()
+            .windows(2)
+            .enumerate()
+        {
+            let (mut low0, mut low1) = (0.0, 0.0);
+            for (band, (_, color)) in BANDS.iter().enumerate() {
+                let (high0, high1) = (low0 + pair[0].0[band], low1 + pair[1].0[band]);
+                graphics.draw_quad(
+                    [
+                        (x(i), y(low0)).into(),
+                        (x(i + 1), y(low1)).into(),
+                        (x(i + 1), y(high1)).into(),
+                        (x(i), y(high0)).into(),
+                    ],
+                    *color,
+                );
+                (low0, low1) = (high0, high1);
+            }
+        }
+
+        // Anything stacked above this line was put in after the start.
+        if let Some(&(_, initial)) = self.samples.back() {
+            graphics.draw_line(
+                (left, y(initial)),
+                (left + width, y(initial)),
+                1.0,
+                Color::BLACK,
+            );
+        }
+
+        let ledger = &p.ledger;
+        let budget = (ledger.initial + ledger.inputs()).abs().max(1e-9);
+        let (kinetic, potential) = energy::precise_parts(p);
+        let mut row = plot_top + PLOT_HEIGHT + 6.0;
+        for ((name, color), value) in BANDS.iter().zip([
+            kinetic,
+            potential,
+            ledger.viscous,
+            ledger.friction,
+            ledger.snap,
+        ]) {
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((left + 4.0, row + 3.0), (left + 14.0, row + 13.0)),
+                *color,
+            );
+            text(
+                graphics,
+                left + 20.0,
+                row,
+                &format!("{} {:.0}%", name, 100.0 * value / budget),
+            );
+            row += 16.0;
+        }
+
+        // What was put in, against the energy at the start.
+        let initial = ledger.initial.abs().max(1e-9);
+        for (name, value) in [
+            ("drive", ledger.drive),
+            ("motor", ledger.motor),
+            ("impulses and edits", ledger.external),
+            ("step error", ledger.integration),
+        ] {
+            text(
+                graphics,
+                left + 4.0,
+                row,
+                &format!("Added by {}: {:+.0}%", name, 100.0 * value / initial),
+            );
+            row += 16.0;
+        }
+    }
+}
@@ -0,0 +1,1646 @@
+use std::f32::consts::{PI, TAU};
+
+use speedy2d::color::Color;
+
+use crate::energy::{self, EnergyLedger, StepWork};
+use crate::framing::Bounds;
+use crate::integrator::{
+    Derivatives, ExplicitEuler, Integrator, Rk4, Rk45, Scheme, State, VelocityVerlet,
+};
+use crate::math;
+use crate::model::{Model, ModelEntry, PendulumEntry};
+use crate::renderer::Renderer;
+use crate::slack::{self, Flight};
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+use crate::STEPS_PER_SECOND;
+use crate::{draw_arc, hsv};
+
+/// The most steps a pendulum can take for each of the scene's.
+pub const MAX_RATE: u32 = 20;
+/// Kinetic and static friction of the dry friction model, as angular
+/// accelerations per step squared, unless the scene or `set` says otherwise.
+pub const COULOMB_FRICTION: f32 = 0.00002;
+pub const STATIC_THRESHOLD: f32 = 0.0003;
+/// The rates the rate key steps through.
+const RATES: [u32; 4] = [1, 2, 5, 10];
+
+/// Everything needed to put a pendulum back exactly where it was at the end
+/// of a physics step.
+#[derive(Copy, Clone, PartialEq)]
+pub struct PendulumState {
+    pub angle: f32,
+    pub angular_velocity: f32,
+    pub angular_acceleration: f32,
+    pub r: f32,
+    pub m: f32,
+    pub g: f32,
+    pub time: f32,
+    pub slack: Option<Flight>,
+}
+
+/// Iterator over the states of a pendulum, stepping it as it goes. The
+/// pendulum is left where the iteration stopped, exactly as if `update` had
+/// been called as many times. See `Pendulum::steps`.
+pub struct PendulumSteps<'a> {
+    pendulum: &'a mut Pendulum,
+}
+
+impl Iterator for PendulumSteps<'_> {
+    type Item = PendulumState;
+
+    fn next(&mut self) -> Option<PendulumState> {
+        self.pendulum.update();
+        Some(self.pendulum.snapshot())
+    }
+}
+
+/// How energy is taken out of the swing each step.
+#[derive(Copy, Clone, PartialEq)]
+pub enum DampingModel {
+    /// Velocity-proportional damping, scaled by the mass.
+    Viscous,
+    /// Dry friction: a constant torque opposing the motion, plus a static
+    /// dead band in which the bob sticks instead of creeping.
+    Coulomb,
+}
+
+/// Textbook damping regimes the damping key cycles through, each a damping
+/// ratio of the linearised motion.
+#[derive(Copy, Clone, PartialEq)]
+pub enum DampingRegime {
+    Underdamped,
+    Critical,
+    Overdamped,
+}
+
+impl DampingRegime {
+    pub fn ratio(self) -> f32 {
+        match self {
+            DampingRegime::Underdamped => 0.2,
+            DampingRegime::Critical => 1.0,
+            DampingRegime::Overdamped => 3.0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DampingRegime::Underdamped => "underdamped",
+            DampingRegime::Critical => "critically damped",
+            DampingRegime::Overdamped => "overdamped",
+        }
+    }
+
+    fn of(ratio: f32) -> Option<DampingRegime> {
+        [
+            DampingRegime::Underdamped,
+            DampingRegime::Critical,
+            DampingRegime::Overdamped,
+        ]
+        .into_iter()
+        .find(|regime| regime.ratio() == ratio)
+    }
+}
+
+/// Radius of the disc and ring bobs, which is also the size every bob is
+/// drawn and grabbed at.
+pub const BOB_RADIUS: f32 = 25.0;
+/// Width of the bar bob.
+const BAR_WIDTH: f32 = 12.0;
+
+/// What swings on the end of the rod. Apart from the point mass each shape
+/// spreads its mass out, which changes its moment of inertia about the pivot
+/// and so the period, even for the same mass and length.
+#[derive(Copy, Clone, PartialEq)]
+pub enum BobShape {
+    /// All the mass at distance `r` from the pivot.
+    Point,
+    /// A solid disc of radius `BOB_RADIUS` centred at `r`.
+    Disc,
+    /// A thin ring of radius `BOB_RADIUS` centred at `r`.
+    Ring,
+    /// A uniform bar of length `r` pivoted at one end, with no separate rod.
+    Bar,
+}
+
+impl BobShape {
+    pub fn name(self) -> &'static str {
+        match self {
+            BobShape::Point => "point",
+            BobShape::Disc => "disc",
+            BobShape::Ring => "ring",
+            BobShape::Bar => "bar",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<BobShape> {
+        [
+            BobShape::Point,
+            BobShape::Disc,
+            BobShape::Ring,
+            BobShape::Bar,
+        ]
+        .into_iter()
+        .find(|shape| shape.name() == name)
+    }
+
+    pub fn next(self) -> BobShape {
+        match self {
+            BobShape::Point => BobShape::Disc,
+            BobShape::Disc => BobShape::Ring,
+            BobShape::Ring => BobShape::Bar,
+            BobShape::Bar => BobShape::Point,
+        }
+    }
+
+    /// Moment of inertia about the pivot per unit mass, for length `r`.
+    pub fn inertia(self, r: f32) -> f32 {
+        let a = BOB_RADIUS;
+        match self {
+            BobShape::Point => r * r,
+            BobShape::Disc => r * r + 0.5 * a * a,
+            BobShape::Ring => r * r + a * a,
+            BobShape::Bar => r * r / 3.0,
+        }
+    }
+
+    /// Distance from the pivot to the centre of mass.
+    pub fn centre_of_mass(self, r: f32) -> f32 {
+        match self {
+            BobShape::Bar => 0.5 * r,
+            _ => r,
+        }
+    }
+
+    /// Length of the point-mass pendulum with the same period, `I / (m d)`.
+    pub fn effective_length(self, r: f32) -> f32 {
+        self.inertia(r) / self.centre_of_mass(r)
+    }
+
+    /// The length `r` whose effective length is `length`, inverting
+    /// `effective_length`. Disc and ring bobs can't be shorter than twice
+    /// their radius allows, so those are clamped.
+    pub fn length_for_effective(self, length: f32) -> f32 {
+        let a = BOB_RADIUS;
+        // r + k a² / r = L, solved for the larger root.
+        let offset = |k: f32| {
+            let discriminant = (length * length - 4.0 * k * a * a).max(0.0);
+            0.5 * (length + discriminant.sqrt())
+        };
+        match self {
+            BobShape::Point => length,
+            BobShape::Disc => offset(0.5),
+            BobShape::Ring => offset(1.0),
+            BobShape::Bar => 1.5 * length,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Pendulum {
+    /// Optional label drawn above the pivot, e.g. the planet whose gravity
+    /// the pendulum uses.
+    pub name: String,
+    /// The apparatus profile the pendulum stands for, if one was attached;
+    /// see `apparatus`.
+    pub apparatus: String,
+
+    pub origin: Vector,
+
+    pub position: Vector,
+
+    pub angle: f32,
+
+    pub angular_velocity: f32,
+    pub angular_acceleration: f32,
+
+    pub r: f32,
+    pub m: f32,
+    pub g: f32,
+
+    pub damping_model: DampingModel,
+    /// Damping ratio replacing the mass-based viscous damping, if one was
+    /// chosen.
+    pub zeta: Option<f32>,
+    pub bob: BobShape,
+    /// Fraction of `g` gained for every pixel the bob hangs below the
+    /// pivot, and lost above it, so gravity pulls harder at the bottom of
+    /// the swing than at the top. Nothing like this happens at these
+    /// heights; it exaggerates what a clock feels as it's moved, and breaks
+    /// the swing's symmetry and isochronism where it can be seen. `g` is
+    /// the value level with the pivot.
+    pub gravity_gradient: f32,
+    /// Hangs the bob from a string rather than a rod, which goes slack
+    /// instead of pushing; see `slack`.
+    pub string: bool,
+    /// The bob's free flight while the string is slack.
+    pub slack: Option<Flight>,
+    /// Angular deceleration applied by kinetic friction while sliding.
+    pub coulomb_friction: f32,
+    /// Largest gravitational angular acceleration static friction can hold.
+    pub static_threshold: f32,
+
+    /// Angular acceleration applied by the motor this step.
+    pub motor_torque: f32,
+
+    /// Peak angular acceleration of the periodic drive, zero when undriven.
+    pub drive_amplitude: f32,
+    /// Drive frequency in radians per step.
+    pub drive_frequency: f32,
+    /// Added to the drive phase so that retuning the drive carries on from
+    /// the phase it had reached instead of jumping.
+    drive_offset: f32,
+    /// Steps simulated so far, the clock the drive is phased against.
+    pub time: f32,
+
+    /// Seconds over which gravity eases in from zero after `ease_in`, zero
+    /// when soft start is off.
+    pub soft_start: f32,
+    /// Step at which the soft start began.
+    ramp_from: f32,
+
+    /// Where the energy has gone since the pendulum was made.
+    pub ledger: EnergyLedger,
+
+    /// What the smooth swing steps with.
+    pub integrator: Scheme,
+    /// Steps taken for each step of the scene, to run a pendulum fast
+    /// beside real-time ones. Its `time` is its own, so everything timed
+    /// off it keeps to the pendulum's clock.
+    pub rate: u32,
+    /// Kept for its substep size between steps, when that's what the
+    /// pendulum steps with.
+    rk45: Rk45,
+
+    grabbed: bool,
+}
+
+impl Pendulum {
+    pub fn new(x: f32, y: f32, r: f32) -> Pendulum {
+        Pendulum {
+            name: String::new(),
+            apparatus: String::new(),
+            origin: Vector::new(x, y),
+            position: Vector::new(0.0, 0.0),
+            angle: 1.0,
+            angular_velocity: 0.0,
+            angular_acceleration: 0.0,
+            r,
+            m: 1.0,
+            g: 0.5,
+            damping_model: DampingModel::Viscous,
+            zeta: None,
+            bob: BobShape::Point,
+            gravity_gradient: 0.0,
+            string: false,
+            slack: None,
+            coulomb_friction: COULOMB_FRICTION,
+            static_threshold: STATIC_THRESHOLD,
+            motor_torque: 0.0,
+            drive_amplitude: 0.0,
+            drive_frequency: 0.0,
+            drive_offset: 0.0,
+            time: 0.0,
+            soft_start: 0.0,
+            ramp_from: 0.0,
+            ledger: EnergyLedger::default(),
+            integrator: Scheme::SymplecticEuler,
+            rate: 1,
+            rk45: Rk45::new(1e-6),
+            grabbed: false,
+        }
+    }
+
+    pub fn from_entry(entry: &PendulumEntry) -> Pendulum {
+        let mut p = Pendulum::new(entry.x, entry.y, entry.r);
+
+        p.name = entry.name.clone();
+        p.apparatus = entry.apparatus.clone();
+        p.angle = entry.angle;
+        p.angular_velocity = entry.angular_velocity;
+        p.angular_acceleration = entry.angular_acceleration;
+        p.m = entry.m;
+        p.g = entry.g;
+        p.damping_model = entry.damping;
+        p.zeta = entry.damping_ratio;
+        p.bob = entry.bob;
+        p.gravity_gradient = entry.gravity_gradient;
+        p.string = entry.string;
+        p.coulomb_friction = entry.coulomb_friction;
+        p.static_threshold = entry.static_threshold;
+        p.drive_amplitude = entry.drive_amplitude;
+        p.drive_frequency = entry.drive_frequency;
+        p.drive_offset = entry.drive_offset;
+        p.time = entry.time;
+        p.soft_start = entry.soft_start;
+        p.ramp_from = entry.ramp_from;
+        p.slack = entry.slack;
+        p.ledger = entry.ledger;
+        p.integrator = entry.integrator;
+        if let Some(substep) = entry.substep {
+            p.rk45.set_substep(substep);
+        }
+        p.rate = entry.rate;
+
+        p.place_bob();
+        p
+    }
+
+    /// Length of the point-mass pendulum that swings like this one.
+    pub fn effective_length(&self) -> f32 {
+        self.bob.effective_length(self.r)
+    }
+
+    /// Largest angle from the vertical the swing would reach on its current
+    /// energy if damping took none of it: where the bob comes to rest and
+    /// turns back. Infinite if it has the energy to go over the top.
+    pub fn turning_angle(&self) -> f32 {
+        energy::turning_angle(self, energy::energy(self)).unwrap_or(f32::INFINITY)
+    }
+
+    /// How far through its cycle the swing is, from 0 at the turning point
+    /// on the positive side round to 1. Read off the phase plot with the
+    /// velocity scaled by the natural frequency, where a small swing goes
+    /// round a circle; a pendulum going over the top goes by its angle.
+    pub fn swing_phase(&self) -> f32 {
+        let cycle = if self.turning_angle().is_infinite() {
+            self.angle
+        } else {
+            let natural = self.natural_frequency();
+            (-self.angular_velocity / natural).atan2(self.angle)
+        };
+        cycle.rem_euclid(TAU) / TAU
+    }
+
+    /// Point-mass rod length whose small-angle period under gravity `g` is
+    /// `period` seconds, inverting `T = 2π√(r/g)`.
+    ///
+    /// That's `natural_period` inverted only for a point bob in uniform
+    /// gravity: it goes by the effective length, which a shaped bob
+    /// stretches, and by the gradient's gravity at the bottom of the swing.
+    /// For a pendulum with either, `set_period` finds the length instead.
+    pub fn length_for_period(period: f32, g: f32) -> f32 {
+        let steps = period * STEPS_PER_SECOND;
+        g * (steps / TAU).powi(2)
+    }
+
+    /// Gravity where the bob is, `g` plus the gradient's share for the
+    /// depth of the equivalent point bob below the pivot.
+    pub fn gravity_at_bob(&self) -> f32 {
+        self.gravity_at(self.angle)
+    }
+
+    fn gravity_at(&self, angle: f32) -> f32 {
+        self.g * (1.0 + self.gravity_gradient * self.effective_length() * math::cos(angle))
+    }
+
+    /// Small-angle angular frequency per step, under the gravity at the
+    /// bottom of the swing.
+    pub fn natural_frequency(&self) -> f32 {
+        let length = self.effective_length();
+        (self.g * (1.0 + self.gravity_gradient * length) / length).sqrt()
+    }
+
+    /// Small-angle period in seconds.
+    pub fn natural_period(&self) -> f32 {
+        TAU / self.natural_frequency() / STEPS_PER_SECOND
+    }
+
+    /// Exact period in seconds of a swing out to `amplitude` radians,
+    /// `T₀ / agm(1, cos(θ₀/2))`, which is the complete elliptic integral
+    /// form without the series.
+    pub fn period_at(&self, amplitude: f32) -> f32 {
+        self.natural_period() / agm(1.0, (0.5 * amplitude).cos())
+    }
+
+    /// Fraction of the angular velocity kept each step under viscous
+    /// damping.
+    ///
+    /// For a chosen ratio ζ it's `(1 + ω)^-2ζ`, with ω the natural angular
+    /// frequency per step. That's `e^-2ζω` to first order, and makes ζ = 1
+    /// exactly critical for the step update below rather than only for the
+    /// continuous equation, so a critically damped release can't overshoot.
+    fn viscous_factor(&self) -> f32 {
+        match self.zeta {
+            Some(zeta) => {
+                let natural = self.natural_frequency();
+                math::powf(1.0 + natural, -2.0 * zeta)
+            }
+            None => 0.995 - 0.0003 * self.m / 3.0,
+        }
+    }
+
+    /// Viscous damping coefficient per step, the rate at which the angular
+    /// velocity decays.
+    pub fn damping_coefficient(&self) -> f32 {
+        -math::ln(self.viscous_factor())
+    }
+
+    /// Coefficient that damps this pendulum critically at its current
+    /// gravity and length: `2 ln(1 + ω)`, the ζ = 1 case of
+    /// `viscous_factor`. Dry friction has no critical value, as it stops the
+    /// swing in a finite time whatever its strength.
+    pub fn critical_coefficient(&self) -> Option<f32> {
+        match self.damping_model {
+            DampingModel::Viscous => {
+                let natural = self.natural_frequency();
+                Some(2.0 * natural.ln_1p())
+            }
+            DampingModel::Coulomb => None,
+        }
+    }
+
+    /// Off, then each regime in turn. Choosing one switches to viscous
+    /// damping, the only kind a ratio describes.
+    pub fn cycle_regime(&mut self) {
+        self.zeta = match self.zeta.and_then(DampingRegime::of) {
+            None => Some(DampingRegime::Underdamped.ratio()),
+            Some(DampingRegime::Underdamped) => Some(DampingRegime::Critical.ratio()),
+            Some(DampingRegime::Critical) => Some(DampingRegime::Overdamped.ratio()),
+            Some(DampingRegime::Overdamped) => None,
+        };
+        self.damping_model = DampingModel::Viscous;
+    }
+
+    /// Damping ratio of the small-angle motion, which only viscous damping
+    /// has: dry friction stops the swing linearly rather than exponentially.
+    pub fn damping_ratio(&self) -> Option<f32> {
+        match self.damping_model {
+            DampingModel::Viscous => {
+                let natural = self.natural_frequency();
+                Some(
+                    self.zeta
+                        .unwrap_or(-self.viscous_factor().ln() / (2.0 * natural)),
+                )
+            }
+            DampingModel::Coulomb => None,
+        }
+    }
+
+    /// Changes the rod length so the small-angle period becomes `period`
+    /// seconds, keeping the angle and the bob. With a gravity gradient the
+    /// bottom of the swing drops, and gravity grows, as the rod lengthens:
+    /// `ω² = g/L + g k`, which a gradient too steep for the period leaves
+    /// no length for.
+    pub fn set_period(&mut self, period: f32) {
+        let natural = TAU / (period * STEPS_PER_SECOND);
+        let uniform = natural * natural - self.g * self.gravity_gradient;
+        if uniform > 0.0 {
+            self.set_effective_length(self.g / uniform);
+        }
+    }
+
+    /// The next of the usual rates, back to real time after the fastest.
+    pub fn cycle_rate(&mut self) {
+        self.rate = RATES
+            .into_iter()
+            .find(|&rate| rate > self.rate)
+            .unwrap_or(RATES[0]);
+    }
+
+    /// Swaps the rod for a string or back. A rod catches a bob in flight
+    /// where it is, keeping the velocity across it.
+    pub fn set_string(&mut self, string: bool) {
+        self.string = string;
+        if string {
+            return;
+        }
+        if let Some(flight) = self.slack.take() {
+            (self.angle, self.angular_velocity) = flight.onto(self.effective_length());
+            self.place_bob();
+        }
+    }
+
+    /// Puts the bob at `contact`, moving at `velocity` in pixels per step,
+    /// as after a bounce. On the rod only the velocity across it is kept; a
+    /// string lets the bob fly off if it's moving inwards, and a bob
+    /// already in flight keeps all of it.
+    pub fn deflect(&mut self, contact: Vector, velocity: Vector) {
+        let (x, y) = (contact.x - self.origin.x, contact.y - self.origin.y);
+        let angle = math::atan2(x, y);
+        let (sin, cos) = math::sin_cos(angle);
+        let inwards = velocity.x * sin + velocity.y * cos < 0.0;
+        if self.slack.is_some() || (self.string && inwards) {
+            // The flight is of the point bob at the effective length.
+            let scale = self.effective_length() / self.r;
+            let flight = Flight {
+                x: scale * x,
+                y: scale * y,
+                vx: scale * velocity.x,
+                vy: scale * velocity.y,
+            };
+            self.slack = Some(flight);
+            self.angle = flight.angle();
+            self.angular_velocity = flight.angular_velocity();
+        } else {
+            self.angle = angle;
+            self.angular_velocity = (velocity.x * cos - velocity.y * sin) / self.r;
+        }
+        self.place_bob();
+    }
+
+    /// Changes the rod length so the effective length becomes `length`,
+    /// keeping the angle and the bob.
+    pub fn set_effective_length(&mut self, length: f32) {
+        self.r = self.bob.length_for_effective(length);
+        self.place_bob();
+    }
+
+    /// Phase of the drive, in radians, at step `time`.
+    pub fn drive_phase(&self, time: f32) -> f32 {
+        self.drive_frequency * time + self.drive_offset
+    }
+
+    /// Changes the drive frequency without a jump in the drive's phase, as
+    /// a slowly swept drive needs.
+    pub fn retune_drive(&mut self, frequency: f32) {
+        self.drive_offset += (self.drive_frequency - frequency) * self.time;
+        self.drive_frequency = frequency;
+    }
+
+    /// Starts easing gravity in over `seconds` from now, so a large starting
+    /// angle doesn't jolt into motion. Zero turns soft start off.
+    pub fn ease_in(&mut self, seconds: f32) {
+        self.soft_start = seconds;
+        self.ramp_from = self.time;
+    }
+
+    /// Fraction of gravity applied this step: a smoothstep from 0 to 1 over
+    /// the soft start.
+    pub fn gravity_ramp(&self) -> f32 {
+        if self.soft_start <= 0.0 {
+            return 1.0;
+        }
+        let u =
+            ((self.time - self.ramp_from) / (self.soft_start * STEPS_PER_SECOND)).clamp(0.0, 1.0);
+        u * u * (3.0 - 2.0 * u)
+    }
+
+    pub fn update(&mut self) {
+        let length = self.effective_length();
+        let pull = self.gravity_at_bob() * self.gravity_ramp();
+        if self.string
+            && self.slack.is_none()
+            && slack::tension(self.angle, self.angular_velocity, length, pull) < 0.0
+        {
+            self.slack = Some(Flight::leaving(self.angle, self.angular_velocity, length));
+        }
+        if let Some(flight) = self.slack {
+            self.fly(flight);
+            return;
+        }
+        if self.integrator != Scheme::SymplecticEuler && self.damping_model == DampingModel::Viscous
+        {
+            self.step_with_scheme();
+            return;
+        }
+
+        let gravity = -self.gravity_at_bob() * math::sin(self.angle) / self.effective_length();
+        let ramped = gravity * self.gravity_ramp();
+        let drive = self.drive_amplitude * math::cos(self.drive_phase(self.time));
+        let gravity_acceleration = ramped + self.motor_torque + drive;
+
+        self.ledger.open(energy::precise_energy(self));
+        let (velocity, (_, potential)) = (self.angular_velocity, energy::precise_parts(self));
+
+        let kicked = match self.damping_model {
+            DampingModel::Viscous => {
+                let dumping = self.viscous_factor();
+
+                self.angular_acceleration = gravity_acceleration;
+
+                self.angular_velocity += self.angular_acceleration;
+                let kicked = self.angular_velocity;
+
+                self.angular_velocity *= dumping;
+                kicked
+            }
+            DampingModel::Coulomb => self.update_coulomb(gravity_acceleration),
+        };
+
+        self.angle += self.angular_velocity;
+        self.time += 1.0;
+
+        self.place_bob();
+        self.account(velocity, potential, kicked, gravity, ramped, drive);
+    }
+
+    /// A step of the swing's continuous equation with `integrator`. The
+    /// motor, the drive and damping are worked out over the step from where
+    /// the angle went, and gravity is credited with whatever they don't
+    /// explain of the change in kinetic energy, so the scheme's own error
+    /// shows up in the ledger as integration error, as the own step's does.
+    fn step_with_scheme(&mut self) {
+        self.ledger.open(energy::precise_energy(self));
+        let (kinetic, potential) = energy::precise_parts(self);
+
+        let start = State {
+            angle: self.angle,
+            angular_velocity: self.angular_velocity,
+            time: self.time,
+        };
+        let length = self.effective_length();
+        let ramp = self.gravity_ramp();
+        let damping = self.damping_coefficient();
+        let drive = |time: f32| self.drive_amplitude * math::cos(self.drive_phase(time));
+        let dynamics = |state: &State| Derivatives {
+            angle: state.angular_velocity,
+            angular_velocity: -self.gravity_at(state.angle) * ramp * math::sin(state.angle)
+                / length
+                - damping * state.angular_velocity
+                + self.motor_torque
+                + drive(state.time),
+        };
+        let end = match self.integrator {
+            Scheme::Euler => ExplicitEuler.step(&start, 1.0, &dynamics),
+            Scheme::VelocityVerlet => VelocityVerlet.step(&start, 1.0, &dynamics),
+            Scheme::Rk4 => Rk4.step(&start, 1.0, &dynamics),
+            Scheme::Rk45 => self.rk45.step(&start, 1.0, &dynamics),
+            Scheme::SymplecticEuler => unreachable!("the own step handles symplectic Euler"),
+        };
+
+        let turned = (end.angle - start.angle) as f64;
+        let middle = 0.5 * (start.angular_velocity + end.angular_velocity) as f64;
+        let square = (length as f64).powi(2);
+        let motor = square * self.motor_torque as f64 * turned;
+        let driven = square * drive(start.time + 0.5) as f64 * turned;
+        let dissipated = square * damping as f64 * middle * turned;
+
+        self.angular_acceleration = end.angular_velocity - start.angular_velocity;
+        self.angle = end.angle;
+        self.angular_velocity = end.angular_velocity;
+        self.time = start.time + 1.0;
+        self.place_bob();
+
+        let (now, moved) = energy::precise_parts(self);
+        let pulled = now - kinetic - motor - driven + dissipated;
+        let gravity = if ramp == 0.0 {
+            0.0
+        } else {
+            pulled / ramp as f64
+        };
+        let work = StepWork {
+            gravity,
+            ramp: pulled - gravity,
+            motor,
+            drive: driven,
+            dissipated,
+            potential: moved - potential,
+            snap: 0.0,
+        };
+        let mut ledger = self.ledger;
+        ledger.close(self, work);
+        self.ledger = ledger;
+    }
+
+    /// A step of free flight on a slack string, ending taut if the string
+    /// has come straight. Neither the motor nor the drive reach the bob
+    /// through a slack string, and nor does friction at the pivot.
+    fn fly(&mut self, mut flight: Flight) {
+        self.ledger.open(energy::precise_energy(self));
+        let (kinetic, potential) = energy::precise_parts(self);
+
+        let gravity = self.g * (1.0 + self.gravity_gradient * flight.y);
+        let ramped = gravity * self.gravity_ramp();
+        let damping = match self.damping_model {
+            DampingModel::Viscous => self.viscous_factor(),
+            DampingModel::Coulomb => 1.0,
+        };
+        let kicked = flight.step(ramped, damping) as f64;
+        let damped = flight.kinetic() as f64;
+
+        self.slack = Some(flight);
+        self.angle = flight.angle();
+        self.angular_velocity = flight.angular_velocity();
+        self.angular_acceleration = 0.0;
+        self.time += 1.0;
+        let moved = energy::precise_parts(self).1 - potential;
+
+        // Jerked taut, the bob loses its velocity along the string, and is
+        // put back on the circle it's come just past.
+        let loose = energy::precise_energy(self);
+        if let Some((angle, angular_velocity)) = flight.catch(self.effective_length()) {
+            self.slack = None;
+            self.angle = angle;
+            self.angular_velocity = angular_velocity;
+        }
+        self.place_bob();
+
+        // All of the kick is gravity's, and splits like the swing's does.
+        let per_unit = if ramped == 0.0 {
+            0.0
+        } else {
+            (kicked - kinetic) / ramped as f64
+        };
+        let work = StepWork {
+            gravity: per_unit * gravity as f64,
+            ramp: per_unit * (ramped - gravity) as f64,
+            motor: 0.0,
+            drive: 0.0,
+            dissipated: kicked - damped,
+            potential: moved,
+            snap: loose - energy::precise_energy(self),
+        };
+        let mut ledger = self.ledger;
+        ledger.close(self, work);
+        self.ledger = ledger;
+    }
+
+    /// Credits the step just taken to the ledger. The accelerations kicked
+    /// the angular velocity from `velocity` to `kicked`, then damping or
+    /// friction brought it down to where it is now. The kick's work,
+    /// `L²(ω + a/2) a`, is linear in each acceleration that makes up `a`,
+    /// so it splits between them exactly.
+    fn account(
+        &mut self,
+        velocity: f32,
+        potential: f64,
+        kicked: f32,
+        gravity: f32,
+        ramped: f32,
+        drive: f32,
+    ) {
+        let length = self.effective_length() as f64;
+        let (velocity, kicked, now) =
+            (velocity as f64, kicked as f64, self.angular_velocity as f64);
+        let total = (ramped + self.motor_torque + drive) as f64;
+        // Per unit of acceleration; nothing when static friction held the
+        // bob and there was no kick.
+        let per_unit = if total == 0.0 {
+            0.0
+        } else {
+            length * length * 0.5 * (kicked * kicked - velocity * velocity) / total
+        };
+
+        let work = StepWork {
+            gravity: per_unit * gravity as f64,
+            ramp: per_unit * (ramped - gravity) as f64,
+            motor: per_unit * self.motor_torque as f64,
+            drive: per_unit * drive as f64,
+            dissipated: 0.5 * length * length * (kicked * kicked - now * now),
+            potential: energy::precise_parts(self).1 - potential,
+            snap: 0.0,
+        };
+        let mut ledger = self.ledger;
+        ledger.close(self, work);
+        self.ledger = ledger;
+    }
+
+    fn place_bob(&mut self) {
+        match self.slack {
+            // The flight is of the point bob at the effective length.
+            Some(flight) => {
+                let scale = self.r / self.effective_length();
+                self.position.set(scale * flight.x, scale * flight.y);
+            }
+            None => self.position.set(
+                self.r * math::sin(self.angle),
+                self.r * math::cos(self.angle),
+            ),
+        }
+
+        self.position.add(&self.origin);
+    }
+
+    /// Integrates one step at a time as the iterator is pulled, yielding
+    /// the state after each step. Endless, so bound it with `take`:
+    ///
+    /// ```ignore
+    /// let angles: Vec<f32> = pendulum.steps().take(600).map(|s| s.angle).collect();
+    /// ```
+    ///
+    /// There's no `dt` to give it. A step is the unit of time the whole
+    /// simulation counts in, velocities per step and `time` in steps, each
+    /// a `STEPS_PER_SECOND`th of a second; a pendulum run faster takes
+    /// more of them, as `rate` does, rather than longer ones.
+    pub fn steps(&mut self) -> PendulumSteps<'_> {
+        PendulumSteps { pendulum: self }
+    }
+
+    pub fn snapshot(&self) -> PendulumState {
+        PendulumState {
+            angle: self.angle,
+            angular_velocity: self.angular_velocity,
+            angular_acceleration: self.angular_acceleration,
+            r: self.r,
+            m: self.m,
+            g: self.g,
+            time: self.time,
+            slack: self.slack,
+        }
+    }
+
+    pub fn restore(&mut self, state: &PendulumState) {
+        self.angle = state.angle;
+        self.angular_velocity = state.angular_velocity;
+        self.angular_acceleration = state.angular_acceleration;
+        self.r = state.r;
+        self.m = state.m;
+        self.g = state.g;
+        self.time = state.time;
+        self.slack = state.slack;
+
+        self.place_bob();
+    }
+
+    /// Stick-slip step for the dry friction model. While at rest the bob only
+    /// breaks free once gravity exceeds the static threshold; while sliding a
+    /// constant friction opposes the motion and stops it outright instead of
+    /// letting it reverse.
+    /// Returns the velocity the bob was kicked to before friction acted on
+    /// it, or the one it had if static friction held it.
+    fn update_coulomb(&mut self, gravity_acceleration: f32) -> f32 {
+        let start = self.angular_velocity;
+        let at_rest = start.abs() < 1e-6;
+
+        if at_rest && gravity_acceleration.abs() <= self.static_threshold {
+            self.angular_acceleration = 0.0;
+            self.angular_velocity = 0.0;
+            return start;
+        }
+
+        let direction = if at_rest {
+            gravity_acceleration.signum()
+        } else {
+            self.angular_velocity.signum()
+        };
+
+        self.angular_acceleration = gravity_acceleration - self.coulomb_friction * direction;
+
+        let velocity = self.angular_velocity + self.angular_acceleration;
+
+        self.angular_velocity = if velocity.signum() != direction {
+            0.0
+        } else {
+            velocity
+        };
+        start + gravity_acceleration
+    }
+
+    /// The rod, or a slack string drooping between the pivot and the bob:
+    /// a parabola about as long as the string, which for a shallow droop
+    /// `s` over a span `d` is `d + 8s²/3d`.
+    fn draw_rod(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let (dx, dy) = (
+            self.position.x - self.origin.x,
+            self.position.y - self.origin.y,
+        );
+        let span = dx.hypot(dy);
+        let (width, color) = (view.length(3.0), view.tint(Color::GRAY));
+        if self.slack.is_none() || span == 0.0 {
+            let (origin, bob) = (
+                view.world_to_screen(self.origin),
+                view.world_to_screen(self.position),
+            );
+            graphics.draw_line(origin, bob, width, color);
+            return;
+        }
+
+        let droop = (3.0 * span * (self.r - span).max(0.0) / 8.0).sqrt();
+        const SEGMENTS: usize = 12;
+        let at = |i: usize| {
+            let t = i as f32 / SEGMENTS as f32;
+            view.world_to_screen(Vector::new(
+                self.origin.x + t * dx,
+                self.origin.y + t * dy + 4.0 * droop * t * (1.0 - t),
+            ))
+        };
+        for i in 0..SEGMENTS {
+            graphics.draw_line(at(i), at(i + 1), width, color);
+        }
+    }
+
+    pub fn distance(&self, other: &Vector) -> f32 {
+        ((self.position.x - other.x).powi(2) + (self.position.y - other.y).powi(2)).sqrt()
+    }
+}
+
+impl Model for Pendulum {
+    fn step(&mut self) {
+        for _ in 0..self.rate {
+            self.update();
+        }
+    }
+
+    fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let origin = view.world_to_screen(self.origin);
+        let bob = view.world_to_screen(self.position);
+        let (fill, outline) = if view.rhythm {
+            let hue = self.swing_phase();
+            (hsv(hue, 0.5, 1.0), hsv(hue, 0.8, 0.6))
+        } else {
+            (Color::LIGHT_GRAY, Color::DARK_GRAY)
+        };
+
+        if self.bob == BobShape::Bar {
+            let (sin, cos) = self.angle.sin_cos();
+            let half = 0.5 * view.length(BAR_WIDTH);
+            let (dx, dy) = (half * cos, -half * sin);
+            graphics.draw_quad(
+                [
+                    (origin.0 + dx, origin.1 + dy).into(),
+                    (bob.0 + dx, bob.1 + dy).into(),
+                    (bob.0 - dx, bob.1 - dy).into(),
+                    (origin.0 - dx, origin.1 - dy).into(),
+                ],
+                view.tint(outline),
+            );
+            graphics.draw_circle(origin, view.length(3.0), view.tint(Color::LIGHT_GRAY));
+            return;
+        }
+
+        self.draw_rod(graphics, view);
+
+        match self.bob {
+            BobShape::Ring => draw_arc(
+                graphics,
+                view,
+                self.position,
+                BOB_RADIUS - 2.0,
+                0.0,
+                TAU,
+                5.0,
+                view.tint(outline),
+            ),
+            _ => {
+                graphics.draw_circle(bob, view.length(BOB_RADIUS + 3.0), view.tint(outline));
+                graphics.draw_circle(bob, view.length(BOB_RADIUS), view.tint(fill));
+            }
+        }
+
+        // A spoke turning with the disc shows that it rotates as it swings.
+        if self.bob == BobShape::Disc {
+            let (sin, cos) = self.angle.sin_cos();
+            let rim = view.world_to_screen(Vector::new(
+                self.position.x + BOB_RADIUS * sin,
+                self.position.y + BOB_RADIUS * cos,
+            ));
+            graphics.draw_line(bob, rim, view.length(3.0), view.tint(Color::DARK_GRAY));
+        }
+    }
+
+    fn grab(&mut self, point: Vector) -> bool {
+        let distance = self.distance(&point);
+        self.grabbed = match self.bob {
+            BobShape::Point | BobShape::Disc => distance < BOB_RADIUS + 3.0,
+            BobShape::Ring => (distance - BOB_RADIUS).abs() < 6.0,
+            BobShape::Bar => {
+                // Distance from the bar's centre line.
+                let (sin, cos) = self.angle.sin_cos();
+                let (x, y) = (point.x - self.origin.x, point.y - self.origin.y);
+                let along = x * sin + y * cos;
+                let across = x * cos - y * sin;
+                (0.0..=self.r).contains(&along) && across.abs() < 0.5 * BAR_WIDTH + 3.0
+            }
+        };
+        self.grabbed
+    }
+
+    fn drag_to(&mut self, point: Vector) {
+        if !self.grabbed {
+            return;
+        }
+
+        let mut diff = self.origin;
+        diff.sub(&point);
+
+        self.angular_acceleration = 0.0;
+        self.angular_velocity = 0.0;
+        self.slack = None;
+        self.angle = (-diff.y).atan2(diff.x) - PI / 2.0;
+
+        // A bar can be grabbed anywhere along it, so it turns without
+        // changing length; the other bobs follow the mouse out and in.
+        if self.bob == BobShape::Bar {
+            self.place_bob();
+        } else {
+            self.position = point;
+            self.r = ((self.position.x - self.origin.x).powi(2)
+                + (self.position.y - self.origin.y).powi(2))
+            .sqrt();
+        }
+    }
+
+    fn release(&mut self) {
+        if self.grabbed {
+            self.grabbed = false;
+            self.angular_velocity = 0.0;
+        }
+    }
+
+    fn pivot(&self) -> Vector {
+        self.origin
+    }
+
+    fn move_pivot(&mut self, by: Vector) {
+        self.origin.add(&by);
+        self.position.add(&by);
+    }
+
+    fn handles(&self) -> Vec<Vector> {
+        vec![self.position]
+    }
+
+    /// Out to the turning points, or round the whole circle for a swing
+    /// that goes over the top or that something is putting energy into, and
+    /// for a string gone slack.
+    fn reach(&self) -> Bounds {
+        let turning = self.turning_angle();
+        let free = self.slack.is_some() || self.drive_amplitude != 0.0 || self.motor_torque != 0.0;
+        let top = if free || turning >= PI {
+            f32::NEG_INFINITY
+        } else {
+            self.origin.y + self.r * turning.cos()
+        };
+        Bounds::arc(self.origin, self.r, top)
+            .union(Bounds::point(self.origin))
+            .padded(BOB_RADIUS + 3.0)
+    }
+
+    fn name(&self) -> Option<&str> {
+        (!self.name.is_empty()).then_some(self.name.as_str())
+    }
+
+    fn label(&self, units: &Units) -> String {
+        let kind = if self.drive_amplitude != 0.0 {
+            "Driven pendulum"
+        } else {
+            "Pendulum"
+        };
+
+        let bob = match self.bob {
+            BobShape::Point => String::new(),
+            shape => format!(" {}", shape.name()),
+        };
+
+        let apparatus = if self.apparatus.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", self.apparatus)
+        };
+
+        let integrator = match self.integrator {
+            Scheme::SymplecticEuler => String::new(),
+            scheme => format!(", {}", scheme.name()),
+        };
+
+        match self.name() {
+            Some(name) => format!(
+                "{}{}{} ({}), r = {}{}",
+                kind,
+                bob,
+                apparatus,
+                name,
+                units.length(self.r),
+                integrator
+            ),
+            None => format!(
+                "{}{}{}, r = {}{}",
+                kind,
+                bob,
+                apparatus,
+                units.length(self.r),
+                integrator
+            ),
+        }
+    }
+
+    fn hud_lines(&self, units: &Units) -> Vec<String> {
+        let mut lines = vec![
+            if self.gravity_gradient == 0.0 {
+                format!("Gravity: {:.2}", self.g)
+            } else {
+                format!(
+                    "Gravity: {:.2} ({:.2} at the bob)",
+                    self.g,
+                    self.gravity_at_bob()
+                )
+            },
+            format!("Angle: {:.2}", self.angle),
+            format!("Acceleration: {:.2}", self.angular_acceleration * 10.0),
+            format!("Velocity: {:.2}", self.angular_velocity),
+            format!("Mass: {:.2}", self.m),
+            format!("Period: {:.2} s", self.natural_period()),
+            format!(
+                "Length: {} (effective {}), {} bob",
+                units.length(self.r),
+                units.length(self.effective_length()),
+                self.bob.name()
+            ),
+            match (self.damping_model, self.zeta) {
+                (DampingModel::Viscous, None) => "Friction: Viscous".to_string(),
+                (DampingModel::Viscous, Some(zeta)) => match DampingRegime::of(zeta) {
+                    Some(regime) => format!("Friction: Viscous, {} (ζ = {})", regime.name(), zeta),
+                    None => format!("Friction: Viscous, ζ = {:.2}", zeta),
+                },
+                (DampingModel::Coulomb, _) => "Friction: Coulomb".to_string(),
+            },
+            format!("Integrator: {}", self.integrator.name()),
+        ];
+
+        if self.rate != 1 {
+            lines.push(format!("Rate: x{}", self.rate));
+        }
+
+        if self.string {
+            lines.push(match self.slack {
+                Some(_) => "String: slack".to_string(),
+                None => "String: taut".to_string(),
+            });
+        }
+
+        if self.gravity_ramp() < 1.0 {
+            lines.push(format!(
+                "Soft start: {:.0}% gravity",
+                100.0 * self.gravity_ramp()
+            ));
+        }
+
+        if self.drive_amplitude != 0.0 {
+            lines.push(format!(
+                "Drive: {:.4} @ {:.3}",
+                self.drive_amplitude, self.drive_frequency
+            ));
+        }
+
+        lines
+    }
+
+    fn entry(&self) -> ModelEntry {
+        ModelEntry::Pendulum(PendulumEntry {
+            name: self.name.clone(),
+            apparatus: self.apparatus.clone(),
+            x: self.origin.x,
+            y: self.origin.y,
+            r: self.r,
+            angle: self.angle,
+            angular_velocity: self.angular_velocity,
+            angular_acceleration: self.angular_acceleration,
+            m: self.m,
+            g: self.g,
+            damping: self.damping_model,
+            damping_ratio: self.zeta,
+            bob: self.bob,
+            gravity_gradient: self.gravity_gradient,
+            string: self.string,
+            coulomb_friction: self.coulomb_friction,
+            static_threshold: self.static_threshold,
+            drive_amplitude: self.drive_amplitude,
+            drive_frequency: self.drive_frequency,
+            drive_offset: self.drive_offset,
+            time: self.time,
+            soft_start: self.soft_start,
+            ramp_from: self.ramp_from,
+            slack: self.slack,
+            ledger: self.ledger,
+            integrator: self.integrator,
+            substep: Some(self.rk45.substep()).filter(|substep| substep.is_finite()),
+            rate: self.rate,
+        })
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![
+            self.angle,
+            self.angular_velocity,
+            self.angular_acceleration,
+            self.time,
+            self.position.x,
+            self.position.y,
+        ];
+        if let Some(flight) = self.slack {
+            state.extend([flight.x, flight.y, flight.vx, flight.vy]);
+        }
+        state.into_iter().map(f64::from).collect()
+    }
+
+    fn as_pendulum(&self) -> Option<&Pendulum> {
+        Some(self)
+    }
+
+    fn as_pendulum_mut(&mut self) -> Option<&mut Pendulum> {
+        Some(self)
+    }
+}
+
+/// Arithmetic-geometric mean of `a` and `b`, which converges quadratically,
+/// so a handful of rounds reach single precision.
+fn agm(mut a: f32, mut b: f32) -> f32 {
+    for _ in 0..16 {
+        if (a - b).abs() <= 1e-7 * a {
+            break;
+        }
+        (a, b) = (0.5 * (a + b), (a * b).sqrt());
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pendulum on dry friction, at rest `angle` out.
+    fn coulomb_at(angle: f32) -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.damping_model = DampingModel::Coulomb;
+        p.angle = angle;
+        p.angular_velocity = 0.0;
+        p
+    }
+
+    #[test]
+    fn inside_the_dead_band_the_bob_stays_put() {
+        // Gravity pulls with g sin(angle) / r, 0.000125 here, below the
+        // static threshold of 0.0003.
+        let mut p = coulomb_at(0.05);
+        for _ in 0..600 {
+            p.update();
+        }
+        assert_eq!(p.angle, 0.05);
+        assert_eq!(p.angular_velocity, 0.0);
+        assert_eq!(p.angular_acceleration, 0.0);
+    }
+
+    #[test]
+    fn outside_the_dead_band_the_bob_breaks_away_and_slows() {
+        let mut p = coulomb_at(0.5);
+        let mut free = Pendulum::new(400.0, 0.0, 200.0);
+        free.damping_model = DampingModel::Coulomb;
+        free.angle = 0.5;
+        free.coulomb_friction = 0.0;
+
+        p.update();
+        free.update();
+        assert!(p.angle < 0.5, "it should have moved, angle {}", p.angle);
+        assert!(p.angular_velocity < 0.0);
+
+        // While sliding, friction takes a constant deceleration off what
+        // gravity gives.
+        for _ in 0..20 {
+            let gravity = -p.g * math::sin(p.angle) / p.effective_length();
+            let direction = p.angular_velocity.signum();
+            p.update();
+            free.update();
+            let expected = gravity - p.coulomb_friction * direction;
+            assert!((p.angular_acceleration - expected).abs() < 1e-9);
+        }
+        assert!(p.angular_velocity.abs() < free.angular_velocity.abs());
+
+        // Friction takes the energy out until static friction holds it.
+        let mut steps = 0;
+        while p.angular_velocity != 0.0 || p.angular_acceleration != 0.0 {
+            p.update();
+            steps += 1;
+            assert!(steps < 200_000, "it never came to rest");
+        }
+        let held = p.g * math::sin(p.angle.abs()) / p.effective_length();
+        assert!(held <= p.static_threshold);
+    }
+
+    #[test]
+    fn friction_is_kept_in_the_entry() {
+        let mut p = coulomb_at(0.2);
+        p.coulomb_friction = 0.0001;
+        p.static_threshold = 0.002;
+        let ModelEntry::Pendulum(entry) = p.entry() else {
+            panic!("a pendulum's entry is a pendulum entry");
+        };
+        let line = ModelEntry::Pendulum(entry).to_line();
+        let Ok(ModelEntry::Pendulum(parsed)) = ModelEntry::parse(&line) else {
+            panic!("`{}` should parse", line);
+        };
+        let q = Pendulum::from_entry(&parsed);
+        assert_eq!(q.coulomb_friction, 0.0001);
+        assert_eq!(q.static_threshold, 0.002);
+    }
+
+    #[test]
+    fn a_length_for_a_period_swings_with_that_period() {
+        for g in [0.1, 0.5, 2.0] {
+            for period in [0.5, 1.0, 2.0, 7.5] {
+                let mut p = Pendulum::new(400.0, 0.0, Pendulum::length_for_period(period, g));
+                p.g = g;
+                let back = p.natural_period();
+                assert!(
+                    (back - period).abs() < 1e-5 * period,
+                    "{} s under g = {} came back as {} s",
+                    period,
+                    g,
+                    back
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_shaped_bob_or_a_gradient_needs_set_period_for_a_period() {
+        let period = 2.0;
+        let r = Pendulum::length_for_period(period, 0.5);
+        let mut disc = Pendulum::new(400.0, 0.0, r);
+        disc.bob = BobShape::Disc;
+        let mut graded = Pendulum::new(400.0, 0.0, r);
+        graded.gravity_gradient = 0.001;
+
+        assert!(disc.natural_period() > period);
+        assert!(graded.natural_period() < period);
+        for mut p in [disc, graded] {
+            p.set_period(period);
+            assert!((p.natural_period() - period).abs() < 1e-5 * period);
+        }
+    }
+
+    /// A pendulum on viscous damping at ratio `zeta`, let go `angle` out.
+    fn damped_at(angle: f32, zeta: f32) -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.zeta = Some(zeta);
+        p.angle = angle;
+        p
+    }
+
+    #[test]
+    fn the_step_factor_for_a_ratio_of_one_is_exactly_critical() {
+        // Linearised, a step is v' = f (v - ω² θ), θ' = θ + v', whose two
+        // roots are equal, the critical case, when (1 + f - f ω²)² = 4 f,
+        // and with f = (1 + ω)^-2 both sides are 4 / (1 + ω)².
+        let p = damped_at(0.1, 1.0);
+        let (f, w) = (p.viscous_factor() as f64, p.natural_frequency() as f64);
+        let trace = 1.0 + f - f * w * w;
+        assert!((trace * trace - 4.0 * f).abs() < 1e-6 * f);
+
+        let under = damped_at(0.1, 0.9);
+        let (f, w) = (
+            under.viscous_factor() as f64,
+            under.natural_frequency() as f64,
+        );
+        let trace = 1.0 + f - f * w * w;
+        assert!(trace * trace < 4.0 * f, "a ratio below one should ring");
+    }
+
+    #[test]
+    fn a_critically_damped_release_never_crosses_the_bottom() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.cycle_regime();
+        p.cycle_regime();
+        assert!(p.zeta == Some(1.0) && p.damping_model == DampingModel::Viscous);
+
+        // Down to a millionth of the release, past which the rounding of the
+        // factor is all that's left to ring.
+        for angle in [0.01, 0.5, 1.5] {
+            let mut p = damped_at(angle, 1.0);
+            let mut steps = 0;
+            while p.angle > 1e-6 * angle {
+                p.update();
+                steps += 1;
+                assert!(
+                    p.angle >= 0.0,
+                    "let go {} out, it crossed at step {}",
+                    angle,
+                    steps
+                );
+                assert!(steps < 5000, "let go {} out, it never settled", angle);
+            }
+        }
+
+        // Any lighter and it overshoots.
+        let mut p = damped_at(0.5, 0.9);
+        let mut crossed = false;
+        for _ in 0..5000 {
+            p.update();
+            crossed |= p.angle < 0.0;
+        }
+        assert!(crossed);
+    }
+
+    #[test]
+    fn a_soft_start_pulls_less_than_gravity_until_the_ramp_is_done() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.ease_in(2.0);
+        let ramp = 2.0 * STEPS_PER_SECOND;
+        let steady = |p: &Pendulum| p.g * math::sin(p.angle).abs() / p.effective_length();
+
+        let mut fraction = 0.0;
+        while p.time < ramp {
+            let (pull, previous) = (steady(&p), fraction);
+            p.update();
+            fraction = p.angular_acceleration.abs() / pull;
+            assert!(
+                fraction < 1.0 && fraction >= previous,
+                "at step {} the pull was {} of gravity's",
+                p.time,
+                fraction
+            );
+        }
+        let pull = steady(&p);
+        p.update();
+        assert!((p.angular_acceleration.abs() - pull).abs() < 1e-6 * pull);
+    }
+
+    #[test]
+    fn stepping_through_the_iterator_is_calling_update() {
+        let mut iterated = Pendulum::new(400.0, 0.0, 200.0);
+        let mut updated = iterated.clone();
+        for state in iterated.clone().steps().take(300) {
+            updated.update();
+            assert!(state == updated.snapshot(), "apart at step {}", state.time);
+        }
+
+        let states: Vec<PendulumState> = iterated.steps().take(300).collect();
+        assert!(states.last() == Some(&iterated.snapshot()));
+        assert!(iterated.snapshot() == updated.snapshot());
+    }
+
+    #[test]
+    fn the_steps_take_skip_and_map_like_any_iterator() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let times: Vec<f32> = p.steps().skip(10).take(5).map(|state| state.time).collect();
+        assert_eq!(times, vec![11.0, 12.0, 13.0, 14.0, 15.0]);
+        assert_eq!(p.time, 15.0, "the pendulum stops where the iteration did");
+
+        let mut q = Pendulum::new(400.0, 0.0, 200.0);
+        let angles: Vec<f32> = q.steps().take(600).map(|state| state.angle).collect();
+        assert_eq!(angles.len(), 600);
+        assert!(
+            angles.iter().any(|&angle| angle < 0.0),
+            "it should swing back"
+        );
+        assert!(p.steps().next().is_some(), "and never runs out");
+    }
+
+    #[test]
+    fn bob_shapes_follow_the_textbook_formulas() {
+        let (r, a) = (150.0_f32, BOB_RADIUS);
+        let close = |actual: f32, expected: f32| (actual - expected).abs() < 1e-4 * expected;
+        // A disc turning in its own plane has a²/2 about its centre, a thin
+        // ring a², and a bar about its end a third of its length squared;
+        // parallel axes move the first two out to r.
+        let expected = [
+            (BobShape::Point, r * r, r),
+            (BobShape::Disc, r * r + a * a / 2.0, r + a * a / (2.0 * r)),
+            (BobShape::Ring, r * r + a * a, r + a * a / r),
+            (BobShape::Bar, r * r / 3.0, 2.0 * r / 3.0),
+        ];
+        for (shape, inertia, length) in expected {
+            assert!(close(shape.inertia(r), inertia), "{} inertia", shape.name());
+            assert!(close(shape.effective_length(r), length), "{}", shape.name());
+            let back = shape.length_for_effective(shape.effective_length(r));
+            assert!(close(back, r), "{} came back as {}", shape.name(), back);
+
+            // And it swings with T = 2π√(L/g).
+            let mut p = Pendulum::new(400.0, 0.0, r);
+            p.bob = shape;
+            let period = TAU * (length / p.g).sqrt() / STEPS_PER_SECOND;
+            assert!(close(p.natural_period(), period), "{} period", shape.name());
+        }
+    }
+
+    #[test]
+    fn a_length_too_short_for_a_round_bob_still_gives_a_rod() {
+        // r + a²/2r is never below √2 a, reached at r = a/√2.
+        let shortest = std::f32::consts::SQRT_2 * BOB_RADIUS;
+        let r = BobShape::Disc.length_for_effective(shortest);
+        assert!((r - BOB_RADIUS / std::f32::consts::SQRT_2).abs() < 1e-3);
+        for length in [0.5 * shortest, 1.0, 0.0] {
+            let r = BobShape::Disc.length_for_effective(length);
+            assert!(r.is_finite() && r >= 0.0, "{} gave {}", length, r);
+        }
+    }
+
+    #[test]
+    fn an_undriven_swing_keeps_its_initial_energy_or_dissipates_it() {
+        for model in [DampingModel::Viscous, DampingModel::Coulomb] {
+            let mut p = Pendulum::new(400.0, 0.0, 200.0);
+            p.damping_model = model;
+            let initial = energy::precise_energy(&p);
+            let mut worst: f64 = 0.0;
+            for _ in 0..6000 {
+                p.update();
+                let accounted = energy::precise_energy(&p) + p.ledger.dissipated();
+                worst = worst.max((accounted - initial).abs() / initial);
+            }
+            assert_eq!(p.ledger.initial, initial);
+            // Most of it has gone, and what's left over is the step's own
+            // wobble of a few percent within a swing.
+            assert!(p.ledger.dissipated() > 0.5 * initial);
+            assert!(worst < 0.03, "off by {}", worst);
+            assert_eq!(p.ledger.inputs(), p.ledger.integration);
+        }
+    }
+
+    #[test]
+    fn the_readout_balances_and_a_fresh_pendulum_starts_a_new_count() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.steps().take(600).for_each(drop);
+        let current = energy::precise_energy(&p);
+        let [balance, started] = p.ledger.readout(current);
+        let expected = format!(
+            "Energy: {:.1} + {:.1} dissipated = {:.1}",
+            current,
+            p.ledger.dissipated(),
+            current + p.ledger.dissipated()
+        );
+        assert_eq!(balance, expected);
+        assert!(started.starts_with(&format!("Started at {:.1}, off by ", p.ledger.initial)));
+
+        let ModelEntry::Pendulum(entry) = p.entry() else {
+            unreachable!()
+        };
+        let fresh = Pendulum::from_entry(&entry.fresh());
+        assert_eq!(fresh.ledger.dissipated(), 0.0);
+    }
+
+    /// Steps between the first and fifth upward crossing of the bottom, four
+    /// periods.
+    fn four_periods(p: &mut Pendulum) -> f32 {
+        let mut crossings = Vec::new();
+        while crossings.len() < 5 {
+            let before = p.angle;
+            p.update();
+            if before < 0.0 && p.angle >= 0.0 {
+                crossings.push(p.time - p.angle / (p.angle - before));
+            }
+        }
+        crossings[4] - crossings[0]
+    }
+
+    #[test]
+    fn under_a_gradient_the_bottom_is_still_the_equilibrium() {
+        for gradient in [-0.002, 0.0, 0.002] {
+            let mut p = Pendulum::new(400.0, 0.0, 200.0);
+            p.gravity_gradient = gradient;
+            // `g` level with the pivot, more below it and less above.
+            let bottom = p.g * (1.0 + gradient * p.effective_length());
+            assert!((p.gravity_at(0.5 * PI) - p.g).abs() < 1e-6);
+            assert!((p.gravity_at(0.0) - bottom).abs() < 1e-6);
+            assert!((p.gravity_at(PI) - (2.0 * p.g - bottom)).abs() < 1e-6);
+
+            p.angle = 0.0;
+            p.angular_velocity = 0.0;
+            p.steps().take(600).for_each(drop);
+            assert_eq!((p.angle, p.angular_velocity), (0.0, 0.0));
+
+            // Nudged, it comes back.
+            p.angle = 0.01;
+            p.steps().take(6000).for_each(drop);
+            assert!(p.angle.abs() < 0.01 && p.angular_velocity.abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn small_swings_under_a_gradient_keep_the_analytic_frequency() {
+        for gradient in [-0.002, 0.0, 0.002, 0.004] {
+            let mut p = Pendulum::new(400.0, 0.0, 200.0);
+            p.gravity_gradient = gradient;
+            p.zeta = Some(0.0);
+            p.angle = 0.01;
+            let length = p.effective_length();
+            let analytic = (p.g * (1.0 + gradient * length) / length).sqrt();
+            assert!((p.natural_frequency() - analytic).abs() < 1e-7);
+
+            let measured = 4.0 * TAU / four_periods(&mut p);
+            assert!(
+                (measured - analytic).abs() < 1e-3 * analytic,
+                "gradient {}: {} against {}",
+                gradient,
+                measured,
+                analytic
+            );
+        }
+    }
+
+    #[test]
+    fn a_pendulum_at_five_times_the_rate_swings_five_times_as_often() {
+        let twin = |rate| {
+            let mut p = Pendulum::new(400.0, 0.0, 200.0);
+            p.zeta = Some(0.0);
+            p.angle = 0.2;
+            p.rate = rate;
+            p
+        };
+        let (mut slow, mut fast) = (twin(1), twin(5));
+        let mut swings = [0usize, 0];
+        for _ in 0..6000 {
+            for (p, swings) in [&mut slow, &mut fast].into_iter().zip(&mut swings) {
+                let before = p.angle;
+                p.step();
+                if before < 0.0 && p.angle >= 0.0 {
+                    *swings += 1;
+                }
+            }
+        }
+        // About 48 swings of 126 steps in the slow one's 6000, and five
+        // times that, give or take the one under way, in the fast one's.
+        assert_eq!(fast.time, 5.0 * slow.time);
+        assert!(swings[0] >= 47, "{:?}", swings);
+        assert!(swings[1].abs_diff(5 * swings[0]) <= 5, "{:?}", swings);
+
+        // It's the same swing, run on: the fast one is where the slow one
+        // gets to in five times as many frames.
+        let mut again = twin(1);
+        again.steps().take(fast.time as usize).for_each(drop);
+        assert_eq!(
+            (again.angle, again.angular_velocity),
+            (fast.angle, fast.angular_velocity)
+        );
+    }
+}
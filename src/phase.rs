@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use speedy2d::color::Color;
+use speedy2d::shape::Rectangle;
+
+use crate::energy;
+use crate::pendulum::Pendulum;
+use crate::polyline;
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+
+/// Steps of trail kept in the plot.
+const TRAIL_LENGTH: usize = 600;
+/// Points per branch of the energy contour.
+const CONTOUR_POINTS: usize = 64;
+
+/// Inset plot of angular velocity against angle for the selected pendulum,
+/// optionally with the energy shell it should stay on drawn over the trail.
+pub struct PhasePlot {
+    pub visible: bool,
+    rect: (f32, f32, f32, f32),
+    trail: VecDeque<(f32, f32)>,
+}
+
+/// Wraps an angle into [-π, π) so swings over the top stay on the plot.
+fn wrap(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+impl PhasePlot {
+    pub fn new() -> PhasePlot {
+        PhasePlot {
+            visible: false,
+            rect: (590.0, 40.0, 200.0, 140.0),
+            trail: VecDeque::with_capacity(TRAIL_LENGTH),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.trail.clear();
+    }
+
+    pub fn record(&mut self, p: &Pendulum) {
+        if self.trail.len() == TRAIL_LENGTH {
+            self.trail.pop_front();
+        }
+        self.trail.push_back((wrap(p.angle), p.angular_velocity));
+    }
+
+    /// Draws the trail and, if `shell` is given, the contour of states with
+    /// that energy, red while `alarm` is set.
+    pub fn draw(&self, graphics: &mut dyn Renderer, p: &Pendulum, shell: Option<f32>, alarm: bool) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = self.rect;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.6),
+        );
+
+        let contour_top = shell.map_or(0.0, |e| (2.0 * e).sqrt() / p.effective_length());
+        let scale = self
+            .trail
+            .iter()
+            .map(|(_, velocity)| velocity.abs())
+            .fold(contour_top, f32::max)
+            .max(1e-3)
+            * 1.1;
+        let point = |angle: f32, velocity: f32| {
+            Vector::new(
+                left + width * (0.5 + 0.5 * angle / PI),
+                top + height * (0.5 - 0.5 * velocity / scale),
+            )
+        };
+
+        let middle = point(0.0, 0.0);
+        graphics.draw_line(
+            (left, middle.y),
+            (left + width, middle.y),
+            1.0,
+            Color::LIGHT_GRAY,
+        );
+        graphics.draw_line(
+            (middle.x, top),
+            (middle.x, top + height),
+            1.0,
+            Color::LIGHT_GRAY,
+        );
+
+        // A run for each stretch between wraps, so nothing is drawn across
+        // the plot where the angle wrapped round.
+        let mut run: Vec<Vector> = Vec::new();
+        let mut previous = None;
+        for &(angle, velocity) in &self.trail {
+            if previous.is_some_and(|previous: f32| (angle - previous).abs() >= PI) {
+                polyline::draw_polyline(graphics, &run, 1.5, Color::BLUE);
+                run.clear();
+            }
+            run.push(point(angle, velocity));
+            previous = Some(angle);
+        }
+        polyline::draw_polyline(graphics, &run, 1.5, Color::BLUE);
+
+        if let Some(e) = shell {
+            let extent = energy::turning_angle(p, e).unwrap_or(PI);
+            let color = if alarm { Color::RED } else { Color::DARK_GRAY };
+
+            for sign in [1.0, -1.0] {
+                let branch: Vec<Vector> = (0..=CONTOUR_POINTS)
+                    .map(|i| {
+                        let angle = -extent + 2.0 * extent * i as f32 / CONTOUR_POINTS as f32;
+                        let velocity = energy::shell_velocity(p, e, angle).unwrap_or(0.0);
+                        point(angle, sign * velocity)
+                    })
+                    .collect();
+                polyline::draw_polyline(graphics, &branch, 1.0, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::presets::{Preset, PRESETS};
+use crate::STEPS_PER_SECOND;
+
+/// Seconds the outgoing scene takes to fade out as the next fades in.
+const DEFAULT_CROSSFADE: f32 = 1.0;
+/// Seconds a scene's title is shown after it comes on.
+const DEFAULT_TITLE: f32 = 3.0;
+/// Seconds after the last key, click or mouse movement before the rotation
+/// carries on.
+const DEFAULT_RESUME: f32 = 20.0;
+
+/// Booth mode: plays a list of presets for set times, over and over, fading
+/// each into the next. Unlike the demo reel it doesn't end when someone
+/// takes over; it holds the current scene while they play with it and
+/// carries on once they have left it alone for a while.
+pub struct Playlist {
+    /// Each preset with the frames it's shown for.
+    entries: Vec<(&'static Preset, usize)>,
+    crossfade: usize,
+    title: usize,
+    resume: usize,
+    current: usize,
+    /// Frames the current preset has been shown for, not counting while
+    /// the rotation was held.
+    frames: usize,
+    /// Frames since it came on, for the crossfade and title card.
+    shown: usize,
+    /// Frames since the last interaction, while the rotation is held.
+    quiet: Option<usize>,
+}
+
+impl Playlist {
+    /// Reads a `[playlist]` section: one `scene = <preset name>, <seconds>`
+    /// line per entry, in order, and optionally `crossfade`, `title` and
+    /// `resume` in seconds. A title of 0 turns the title card off.
+    pub fn from_config(section: &Section) -> Result<Playlist, String> {
+        let mut entries = Vec::new();
+        let (mut crossfade, mut title, mut resume) =
+            (DEFAULT_CROSSFADE, DEFAULT_TITLE, DEFAULT_RESUME);
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            let seconds = |key: &str, value: &str| -> Result<f32, String> {
+                let seconds = parse_f32(key, value).map_err(error)?;
+                if seconds < 0.0 {
+                    return Err(error(format!("`{}` can't be negative", key)));
+                }
+                Ok(seconds)
+            };
+            match key_value(line) {
+                Some(("scene", value)) => {
+                    let (name, duration) = value.rsplit_once(',').ok_or(error(format!(
+                        "expected `<preset>, <seconds>`, found `{}`",
+                        value
+                    )))?;
+                    let name = name.trim();
+                    let preset = PRESETS
+                        .iter()
+                        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| {
+                            let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+                            error(format!(
+                                "unknown preset `{}`, expected one of: {}",
+                                name,
+                                names.join(", ")
+                            ))
+                        })?;
+                    let duration = seconds("duration", duration.trim())?;
+                    entries.push((preset, duration, *line_number));
+                }
+                Some(("crossfade", value)) => crossfade = seconds("crossfade", value)?,
+                Some(("title", value)) => title = seconds("title", value)?,
+                Some(("resume", value)) => resume = seconds("resume", value)?,
+                _ => return Err(error(format!("unknown playlist setting `{}`", line))),
+            }
+        }
+
+        if entries.is_empty() {
+            return Err("the playlist has no `scene` lines".to_string());
+        }
+        // A scene has to be on for the whole fade in, or the next fade
+        // would start before this one had finished.
+        if let Some((preset, _, line_number)) = entries
+            .iter()
+            .find(|&&(_, duration, _)| duration <= crossfade)
+        {
+            return Err(format!(
+                "line {}: `{}` is shown for no longer than the {} s crossfade",
+                line_number, preset.name, crossfade
+            ));
+        }
+
+        let frames = |seconds: f32| (seconds * STEPS_PER_SECOND).round() as usize;
+        Ok(Playlist {
+            entries: entries
+                .into_iter()
+                .map(|(preset, duration, _)| (preset, frames(duration)))
+                .collect(),
+            crossfade: frames(crossfade),
+            title: frames(title),
+            resume: frames(resume),
+            current: 0,
+            frames: 0,
+            shown: 0,
+            quiet: None,
+        })
+    }
+
+    pub fn current(&self) -> &'static Preset {
+        self.entries[self.current].0
+    }
+
+    /// Holds the rotation until the visitor has been quiet for a while.
+    pub fn interact(&mut self) {
+        self.quiet = Some(0);
+    }
+
+    /// Seconds until a held rotation carries on.
+    pub fn resumes_in(&self) -> Option<f32> {
+        self.quiet
+            .map(|quiet| (self.resume - quiet) as f32 / STEPS_PER_SECOND)
+    }
+
+    /// Counts a frame and returns the next preset when it's time to switch.
+    pub fn tick(&mut self) -> Option<&'static Preset> {
+        self.shown += 1;
+        if let Some(quiet) = &mut self.quiet {
+            *quiet += 1;
+            if *quiet < self.resume {
+                return None;
+            }
+            self.quiet = None;
+        }
+
+        self.frames += 1;
+        if self.frames < self.entries[self.current].1 || self.entries.len() < 2 {
+            return None;
+        }
+
+        self.current = (self.current + 1) % self.entries.len();
+        self.frames = 0;
+        self.shown = 0;
+        Some(self.current())
+    }
+
+    /// How far the current scene has faded in, while it's still fading.
+    pub fn fade(&self) -> Option<f32> {
+        (self.shown < self.crossfade).then(|| self.shown as f32 / self.crossfade as f32)
+    }
+
+    /// The current scene's name in a box across the middle of the window,
+    /// fading out over the last part of its time.
+    pub fn draw_title(&self, graphics: &mut Graphics2D, font: &Font, window: (f32, f32)) {
+        if self.shown >= self.title {
+            return;
+        }
+        let opacity = ((self.title - self.shown) as f32 / (0.25 * self.title as f32)).min(1.0);
+
+        let layout = font.layout_text(self.current().name, 40.0, TextOptions::new());
+        let (width, height) = (layout.width() + 48.0, layout.height() + 24.0);
+        let top_left = (0.5 * (window.0 - width), 0.5 * (window.1 - height));
+        graphics.draw_rectangle(
+            Rectangle::from_tuples(top_left, (top_left.0 + width, top_left.1 + height)),
+            Color::from_rgba(0.2, 0.2, 0.25, 0.8 * opacity),
+        );
+        graphics.draw_text(
+            (top_left.0 + 24.0, top_left.1 + 12.0),
+            Color::from_rgba(1.0, 1.0, 1.0, opacity),
+            &layout,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn load(text: &str) -> Result<Playlist, String> {
+        Playlist::from_config(Config::parse(text).unwrap().section("playlist").unwrap())
+    }
+
+    const BOOTH: &str = "[playlist]\n\
+        scene = Single pendulum, 2\n\
+        scene = Double pendulum, 3\n\
+        scene = Pendulum wave, 2\n\
+        crossfade = 1\n\
+        resume = 4\n";
+
+    /// Runs `frames` frames, with an interaction just before each frame in
+    /// `interactions`, and returns the frames the scenes switched on.
+    fn run(
+        playlist: &mut Playlist,
+        frames: usize,
+        interactions: &[usize],
+    ) -> Vec<(usize, &'static str)> {
+        (1..=frames)
+            .filter_map(|frame| {
+                if interactions.contains(&frame) {
+                    playlist.interact();
+                }
+                playlist.tick().map(|preset| (frame, preset.name))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn left_alone_it_plays_each_scene_for_its_time_and_loops() {
+        let mut playlist = load(BOOTH).unwrap();
+        assert_eq!(playlist.current().name, "Single pendulum");
+        assert_eq!(
+            run(&mut playlist, 900, &[]),
+            [
+                (120, "Double pendulum"),
+                (300, "Pendulum wave"),
+                (420, "Single pendulum"),
+                (540, "Double pendulum"),
+                (720, "Pendulum wave"),
+                (840, "Single pendulum"),
+            ]
+        );
+    }
+
+    #[test]
+    fn interacting_holds_the_scene_until_it_has_been_quiet() {
+        let mut playlist = load(BOOTH).unwrap();
+        // Held from frame 61 for the 240 frame resume, which counts the
+        // last of them, then the other 59 of its 120.
+        assert_eq!(run(&mut playlist, 400, &[61])[0], (359, "Double pendulum"));
+
+        // Each interaction starts the wait again.
+        let mut again = load(BOOTH).unwrap();
+        assert_eq!(
+            run(&mut again, 700, &[61, 200, 390])[0],
+            (688, "Double pendulum")
+        );
+    }
+
+    #[test]
+    fn the_hold_counts_down_and_the_new_scene_fades_in() {
+        let mut playlist = load(BOOTH).unwrap();
+        assert_eq!(playlist.resumes_in(), None);
+        playlist.interact();
+        assert_eq!(playlist.resumes_in(), Some(4.0));
+        run(&mut playlist, 60, &[]);
+        assert_eq!(playlist.resumes_in(), Some(3.0));
+        run(&mut playlist, 180, &[]);
+        assert_eq!(playlist.resumes_in(), None);
+
+        // The frame the hold ended on counted towards the scene's time.
+        assert_eq!(playlist.fade(), None);
+        run(&mut playlist, 119, &[]);
+        assert_eq!(playlist.current().name, "Double pendulum");
+        assert_eq!(playlist.fade(), Some(0.0));
+        run(&mut playlist, 30, &[]);
+        assert_eq!(playlist.fade(), Some(0.5));
+        run(&mut playlist, 30, &[]);
+        assert_eq!(playlist.fade(), None);
+    }
+
+    #[test]
+    fn a_single_scene_stays_and_a_bad_playlist_is_refused() {
+        let mut single = load("[playlist]\nscene = Cart pendulum, 2\n").unwrap();
+        assert!(run(&mut single, 600, &[]).is_empty());
+        assert_eq!(single.current().name, "Cart pendulum");
+
+        assert_eq!(
+            load("[playlist]\ncrossfade = 1\n").err().as_deref(),
+            Some("the playlist has no `scene` lines")
+        );
+        let error = load("[playlist]\nscene = Single pendulum, 1\n")
+            .err()
+            .unwrap();
+        assert!(
+            error.starts_with("line 2: `Single pendulum` is shown"),
+            "{}",
+            error
+        );
+        let error = load("[playlist]\nscene = Triple pendulum, 5\n")
+            .err()
+            .unwrap();
+        assert!(
+            error.contains("unknown preset `Triple pendulum`"),
+            "{}",
+            error
+        );
+        assert!(load("[playlist]\nscene = Single pendulum\n").is_err());
+        assert!(load("[playlist]\nresume = -1\n").is_err());
+    }
+}
@@ -0,0 +1,685 @@
+//! Models from shared libraries, built with the `plugin` feature and loaded
+//! with `--plugin <path>`. A loaded plugin becomes another model type: its
+//! scene lines are `plugin kind=<name> x=.. y=..`, and a scene with none of
+//! them gets one of each loaded plugin in place of the default pendulum.
+//!
+//! A plugin exports `pendulum_plugin_entry`, a C function taking nothing
+//! and returning a pointer to a `PluginVtable` that lives as long as the
+//! library. Everything that crosses is `repr(C)` and laid out below; the
+//! vtable leads with `ABI_VERSION` and its own size, and a plugin built
+//! against another version is refused. `examples/plugin-skeleton` is a
+//! plugin to start from, with these definitions copied into it.
+//!
+//! What the host expects of a plugin, as nothing enforces it: a plugin runs
+//! in the window's process with all its rights and isn't sandboxed, so only
+//! load libraries you trust. Its functions are called from the window's
+//! thread only, one at a time. They must not unwind into the host: a panic
+//! is caught inside the plugin and returned as a non-zero status, as the
+//! skeleton does. A non-zero status, a panic on the host's side of a call,
+//! or an instance that fails to start disables that instance: it stops
+//! stepping and drawing and says why in the HUD, and the rest of the scene
+//! carries on. Pointers handed to a plugin, the state and the draw sink, are
+//! only valid for the call they're passed to. Lengths and positions are in
+//! world units, y downwards like the rest of the scene, and time in seconds.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use libloading::Library;
+use speedy2d::color::Color;
+use speedy2d::window::VirtualKeyCode;
+
+use crate::config::parse_f32;
+use crate::framing::Bounds;
+use crate::model::{Label, Model, ModelEntry};
+use crate::pendulum::BOB_RADIUS;
+use crate::renderer::Renderer;
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+use crate::STEPS_PER_SECOND;
+
+/// Bumped whenever a struct below changes shape.
+pub const ABI_VERSION: u32 = 1;
+/// The symbol every plugin exports.
+const ENTRY_POINT: &[u8] = b"pendulum_plugin_entry";
+
+/// Keys other than letters, digits and space, which are passed as their
+/// ASCII codes, upper case for letters.
+pub const KEY_LEFT: u32 = 0x100;
+pub const KEY_RIGHT: u32 = 0x101;
+pub const KEY_UP: u32 = 0x102;
+pub const KEY_DOWN: u32 = 0x103;
+
+/// What the host and a plugin instance share, kept by the host and passed
+/// to each call that needs it.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct PluginState {
+    /// Seconds simulated so far. The host advances it after every step.
+    pub time: f64,
+    /// Written by the host; the plugin hangs from it.
+    pub pivot: Point,
+    /// The point the mouse can grab, written by the plugin. While `grabbed`
+    /// the host moves it instead and the plugin leaves it where it is.
+    pub bob: Point,
+    /// Non-zero while the mouse holds the bob.
+    pub grabbed: u32,
+    /// How far from the pivot the plugin can reach, for framing the camera.
+    pub reach: f64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// Where a plugin draws: each function takes `context` back first.
+#[repr(C)]
+pub struct DrawSink {
+    pub context: *mut c_void,
+    pub line: unsafe extern "C" fn(
+        context: *mut c_void,
+        start: Point,
+        end: Point,
+        thickness: f64,
+        color: Rgba,
+    ),
+    pub circle: unsafe extern "C" fn(context: *mut c_void, centre: Point, radius: f64, color: Rgba),
+    /// `text` is NUL-terminated UTF-8; `size` is in screen pixels.
+    pub text: unsafe extern "C" fn(
+        context: *mut c_void,
+        at: Point,
+        size: f64,
+        color: Rgba,
+        text: *const c_char,
+    ),
+}
+
+/// A plugin's functions. Those returning `i32` return 0 on success.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PluginVtable {
+    pub abi_version: u32,
+    /// `size_of::<PluginVtable>()` as the plugin was built.
+    pub size: u32,
+    /// NUL-terminated, the `kind` in scene lines.
+    pub name: *const c_char,
+    /// Starts an instance hanging from `state.pivot`, filling in the rest
+    /// of the state. Null if it couldn't.
+    pub init: unsafe extern "C" fn(state: *mut PluginState) -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(instance: *mut c_void),
+    /// Moves the instance on by `dt` seconds.
+    pub step: unsafe extern "C" fn(instance: *mut c_void, dt: f64, state: *mut PluginState) -> i32,
+    pub draw: unsafe extern "C" fn(
+        instance: *const c_void,
+        state: *const PluginState,
+        sink: *const DrawSink,
+    ) -> i32,
+    /// Offers the instance a key while it's selected; it sets `*handled`
+    /// non-zero to keep the key from the bindings.
+    pub key: unsafe extern "C" fn(instance: *mut c_void, key: u32, handled: *mut u32) -> i32,
+    /// Writes the instance's state as a blob of its own format: always sets
+    /// `*length` to the blob's size, and writes it only if `capacity` is
+    /// at least that.
+    pub serialize: unsafe extern "C" fn(
+        instance: *const c_void,
+        buffer: *mut u8,
+        capacity: usize,
+        length: *mut usize,
+    ) -> i32,
+    /// Restores a blob `serialize` wrote, updating `state` to match.
+    pub deserialize: unsafe extern "C" fn(
+        instance: *mut c_void,
+        state: *mut PluginState,
+        data: *const u8,
+        length: usize,
+    ) -> i32,
+}
+
+/// A loaded library and its vtable, which is only valid while the library
+/// stays loaded.
+pub struct Plugin {
+    pub name: String,
+    vtable: PluginVtable,
+    _library: Library,
+}
+
+// The vtable's name pointer is never read after loading, and plugins are
+// only called from the window's thread; see the module's expectations.
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+/// Plugins loaded so far, for scene lines to find by name.
+static PLUGINS: Mutex<Vec<Arc<Plugin>>> = Mutex::new(Vec::new());
+
+/// Loads the plugin at `path` and registers its model type, returning its
+/// name.
+pub fn load(path: &Path) -> Result<String, String> {
+    // SAFETY: loading runs the library's initialisers, and the entry point
+    // is trusted to have the declared signature; plugins are trusted code.
+    let (library, vtable) = unsafe {
+        let library = Library::new(path).map_err(|e| e.to_string())?;
+        let entry: libloading::Symbol<unsafe extern "C" fn() -> *const PluginVtable> = library
+            .get(ENTRY_POINT)
+            .map_err(|_| "no `pendulum_plugin_entry` function".to_string())?;
+        let vtable = entry();
+        if vtable.is_null() {
+            return Err("the entry point returned no vtable".to_string());
+        }
+        // Only the version and size are read until they've been checked.
+        let (version, size) = ((*vtable).abi_version, (*vtable).size);
+        if version != ABI_VERSION || size as usize != std::mem::size_of::<PluginVtable>() {
+            return Err(format!(
+                "built for plugin ABI {} ({} bytes), expected {} ({} bytes)",
+                version,
+                size,
+                ABI_VERSION,
+                std::mem::size_of::<PluginVtable>()
+            ));
+        }
+        let vtable = *vtable;
+        (library, vtable)
+    };
+    if vtable.name.is_null() {
+        return Err("the plugin has no name".to_string());
+    }
+    // SAFETY: checked non-null, and NUL-terminated by the ABI.
+    let name = unsafe { CStr::from_ptr(vtable.name) }
+        .to_string_lossy()
+        .into_owned();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("`{}` can't be a model type", name));
+    }
+
+    let mut plugins = PLUGINS.lock().unwrap();
+    if plugins.iter().any(|plugin| plugin.name == name) {
+        return Err(format!("a plugin called `{}` is already loaded", name));
+    }
+    plugins.push(Arc::new(Plugin {
+        name: name.clone(),
+        vtable,
+        _library: library,
+    }));
+    Ok(name)
+}
+
+fn find(name: &str) -> Option<Arc<Plugin>> {
+    PLUGINS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|plugin| plugin.name == name)
+        .cloned()
+}
+
+/// One instance of each loaded plugin, for a scene that doesn't name any.
+pub fn default_entries() -> Vec<ModelEntry> {
+    PLUGINS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|plugin| {
+            ModelEntry::Plugin(PluginEntry {
+                kind: plugin.name.clone(),
+                ..PluginEntry::default()
+            })
+        })
+        .collect()
+}
+
+/// The code a plugin is passed for `key`, if it's one plugins are given.
+fn key_code(key: VirtualKeyCode) -> Option<u32> {
+    use VirtualKeyCode::*;
+    let letters = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    let digits = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    if let Some(i) = letters.iter().position(|&letter| letter == key) {
+        return Some(b'A' as u32 + i as u32);
+    }
+    if let Some(i) = digits.iter().position(|&digit| digit == key) {
+        return Some(b'0' as u32 + i as u32);
+    }
+    match key {
+        Space => Some(b' ' as u32),
+        Left => Some(KEY_LEFT),
+        Right => Some(KEY_RIGHT),
+        Up => Some(KEY_UP),
+        Down => Some(KEY_DOWN),
+        _ => None,
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct PluginEntry {
+    /// Name of the plugin the model comes from.
+    pub kind: String,
+    pub x: f32,
+    pub y: f32,
+    pub time: f64,
+    /// The instance's own serialized state, empty for a fresh one.
+    pub data: Vec<u8>,
+}
+
+impl Default for PluginEntry {
+    fn default() -> PluginEntry {
+        PluginEntry {
+            kind: String::new(),
+            x: 400.0,
+            y: 100.0,
+            time: 0.0,
+            data: Vec::new(),
+        }
+    }
+}
+
+impl PluginEntry {
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "kind" => self.kind = value.to_string(),
+            "x" => self.x = parse_f32(key, value)?,
+            "y" => self.y = parse_f32(key, value)?,
+            "time" => {
+                self.time = value
+                    .parse()
+                    .map_err(|_| format!("invalid number for `time`: `{}`", value))?
+            }
+            "data" => self.data = parse_hex(value)?,
+            _ => return Err(format!("unknown plugin parameter `{}`", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Checks the entry names a loaded plugin, once its keys are all set.
+    pub fn check(&self) -> Result<(), String> {
+        if self.kind.is_empty() {
+            return Err("a plugin model needs a `kind`".to_string());
+        }
+        find(&self.kind)
+            .map(|_| ())
+            .ok_or_else(|| format!("no plugin `{}` is loaded", self.kind))
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = format!("plugin kind={} x={} y={}", self.kind, self.x, self.y);
+        if self.time != 0.0 {
+            line.push_str(&format!(" time={}", self.time));
+        }
+        if !self.data.is_empty() {
+            let hex: String = self.data.iter().map(|b| format!("{:02x}", b)).collect();
+            line.push_str(&format!(" data={}", hex));
+        }
+        line
+    }
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    let invalid = || format!("expected hex bytes for `data`, found `{}`", value);
+    if !value.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            value
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// What a draw call's sink draws on, and the text it leaves for the HUD.
+struct Canvas<'a> {
+    graphics: &'a mut dyn Renderer,
+    view: &'a Viewport,
+    labels: Vec<Label>,
+}
+
+fn point(p: Point) -> Vector {
+    Vector::new(p.x as f32, p.y as f32)
+}
+
+fn from_vector(v: Vector) -> Point {
+    Point {
+        x: v.x as f64,
+        y: v.y as f64,
+    }
+}
+
+fn color(rgba: Rgba) -> Color {
+    Color::from_rgba(rgba.r, rgba.g, rgba.b, rgba.a)
+}
+
+// The sink's functions catch their own panics, so none unwinds through
+// the plugin.
+
+unsafe extern "C" fn sink_line(
+    context: *mut c_void,
+    start: Point,
+    end: Point,
+    thickness: f64,
+    rgba: Rgba,
+) {
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: `context` is the canvas `PluginModel::draw` passed.
+        let canvas = unsafe { &mut *(context as *mut Canvas) };
+        canvas.graphics.draw_line(
+            canvas.view.world_to_screen(point(start)),
+            canvas.view.world_to_screen(point(end)),
+            canvas.view.length(thickness as f32),
+            canvas.view.tint(color(rgba)),
+        );
+    }));
+}
+
+unsafe extern "C" fn sink_circle(context: *mut c_void, centre: Point, radius: f64, rgba: Rgba) {
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: as for `sink_line`.
+        let canvas = unsafe { &mut *(context as *mut Canvas) };
+        canvas.graphics.draw_circle(
+            canvas.view.world_to_screen(point(centre)),
+            canvas.view.length(radius as f32),
+            canvas.view.tint(color(rgba)),
+        );
+    }));
+}
+
+unsafe extern "C" fn sink_text(
+    context: *mut c_void,
+    at: Point,
+    size: f64,
+    rgba: Rgba,
+    text: *const c_char,
+) {
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        if text.is_null() {
+            return;
+        }
+        // SAFETY: as for `sink_line`, and `text` is NUL-terminated by the
+        // ABI.
+        let (canvas, text) = unsafe { (&mut *(context as *mut Canvas), CStr::from_ptr(text)) };
+        canvas.labels.push(Label {
+            at: point(at),
+            size: size as f32,
+            color: color(rgba),
+            text: text.to_string_lossy().into_owned(),
+        });
+    }));
+}
+
+/// An instance of a plugin's model.
+pub struct PluginModel {
+    plugin: Arc<Plugin>,
+    instance: *mut c_void,
+    state: PluginState,
+    /// What disabled the instance, once something has.
+    failure: RefCell<Option<String>>,
+    /// The entry it was last built or saved as, kept for saving once the
+    /// instance can't be asked.
+    entry: RefCell<PluginEntry>,
+    /// Text from the last draw, drawn with the HUD.
+    labels: RefCell<Vec<Label>>,
+}
+
+impl PluginModel {
+    pub fn from_entry(entry: &PluginEntry) -> PluginModel {
+        let plugin = find(&entry.kind).expect("plugin entries are checked when parsed");
+        let mut state = PluginState {
+            time: entry.time,
+            pivot: from_vector(Vector::new(entry.x, entry.y)),
+            bob: from_vector(Vector::new(entry.x, entry.y)),
+            ..PluginState::default()
+        };
+        let init = plugin.vtable.init;
+        // SAFETY: `state` outlives the call, as the ABI asks.
+        let instance = panic::catch_unwind(AssertUnwindSafe(|| unsafe { init(&mut state) }))
+            .unwrap_or(ptr::null_mut());
+        let mut model = PluginModel {
+            plugin,
+            instance,
+            state,
+            failure: RefCell::new(None),
+            entry: RefCell::new(entry.clone()),
+            labels: RefCell::new(Vec::new()),
+        };
+        if instance.is_null() {
+            model.disable("init gave no instance".to_string());
+        } else if !entry.data.is_empty() {
+            let deserialize = model.plugin.vtable.deserialize;
+            let (state, data) = (&mut model.state, &entry.data);
+            // SAFETY: the instance is live, and `state` and `data` outlive
+            // the call.
+            let status = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+                deserialize(instance, state, data.as_ptr(), data.len())
+            }));
+            model.check("deserialize", status);
+        }
+        model
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.failure.borrow().is_some()
+    }
+
+    fn disable(&self, reason: String) {
+        log!("plugin `{}` disabled: {}", self.plugin.name, reason);
+        *self.failure.borrow_mut() = Some(reason);
+    }
+
+    /// Disables the instance unless a call, named `what`, returned 0.
+    fn check(&self, what: &str, status: std::thread::Result<i32>) -> bool {
+        match status {
+            Ok(0) => true,
+            Ok(code) => {
+                self.disable(format!("{} returned {}", what, code));
+                false
+            }
+            Err(_) => {
+                self.disable(format!("{} panicked", what));
+                false
+            }
+        }
+    }
+
+    /// The instance's blob, if it gives one.
+    fn serialize(&self) -> Option<Vec<u8>> {
+        if self.is_disabled() {
+            return None;
+        }
+        let (serialize, instance) = (self.plugin.vtable.serialize, self.instance);
+        let mut length = 0;
+        // SAFETY: the instance is live, and a null buffer of no capacity is
+        // never written to.
+        let status = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            serialize(instance, ptr::null_mut(), 0, &mut length)
+        }));
+        if !self.check("serialize", status) {
+            return None;
+        }
+        let mut buffer = vec![0; length];
+        let capacity = buffer.len();
+        // SAFETY: `buffer` holds `capacity` bytes.
+        let status = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            serialize(instance, buffer.as_mut_ptr(), capacity, &mut length)
+        }));
+        if !self.check("serialize", status) || length > capacity {
+            return None;
+        }
+        buffer.truncate(length);
+        Some(buffer)
+    }
+
+    /// Offers the instance `key`, true if it took it.
+    fn offer_key(&mut self, key: u32) -> bool {
+        if self.is_disabled() {
+            return false;
+        }
+        let (call, instance) = (self.plugin.vtable.key, self.instance);
+        let mut handled = 0;
+        // SAFETY: the instance is live, and `handled` outlives the call.
+        let status = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            call(instance, key, &mut handled)
+        }));
+        self.check("key", status) && handled != 0
+    }
+}
+
+impl Drop for PluginModel {
+    fn drop(&mut self) {
+        if self.instance.is_null() {
+            return;
+        }
+        let (destroy, instance) = (self.plugin.vtable.destroy, self.instance);
+        // SAFETY: the instance is live and never used again.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| unsafe { destroy(instance) }));
+    }
+}
+
+impl Model for PluginModel {
+    fn step(&mut self) {
+        if self.is_disabled() {
+            return;
+        }
+        let dt = 1.0 / STEPS_PER_SECOND as f64;
+        let (step, instance, state) = (self.plugin.vtable.step, self.instance, &mut self.state);
+        // SAFETY: the instance is live, and `state` outlives the call.
+        let status = panic::catch_unwind(AssertUnwindSafe(|| unsafe { step(instance, dt, state) }));
+        if self.check("step", status) {
+            self.state.time += dt;
+        }
+    }
+
+    fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let pivot = view.world_to_screen(point(self.state.pivot));
+        graphics.draw_circle(pivot, view.length(4.0), view.tint(Color::DARK_GRAY));
+        if self.is_disabled() {
+            self.labels.borrow_mut().clear();
+            return;
+        }
+
+        let mut canvas = Canvas {
+            graphics,
+            view,
+            labels: Vec::new(),
+        };
+        let sink = DrawSink {
+            context: &mut canvas as *mut Canvas as *mut c_void,
+            line: sink_line,
+            circle: sink_circle,
+            text: sink_text,
+        };
+        let (draw, instance, state) = (self.plugin.vtable.draw, self.instance, &self.state);
+        // SAFETY: the instance is live, and `state`, `sink` and the canvas
+        // it points to outlive the call.
+        let status =
+            panic::catch_unwind(AssertUnwindSafe(|| unsafe { draw(instance, state, &sink) }));
+        *self.labels.borrow_mut() = canvas.labels;
+        self.check("draw", status);
+    }
+
+    fn grab(&mut self, at: Vector) -> bool {
+        let bob = point(self.state.bob);
+        let grabbed = (bob.x - at.x).powi(2) + (bob.y - at.y).powi(2) < (BOB_RADIUS + 3.0).powi(2);
+        self.state.grabbed = grabbed as u32;
+        grabbed
+    }
+
+    fn drag_to(&mut self, at: Vector) {
+        if self.state.grabbed != 0 {
+            self.state.bob = from_vector(at);
+        }
+    }
+
+    fn release(&mut self) {
+        self.state.grabbed = 0;
+    }
+
+    fn pivot(&self) -> Vector {
+        point(self.state.pivot)
+    }
+
+    fn move_pivot(&mut self, by: Vector) {
+        for p in [&mut self.state.pivot, &mut self.state.bob] {
+            p.x += by.x as f64;
+            p.y += by.y as f64;
+        }
+    }
+
+    fn handles(&self) -> Vec<Vector> {
+        vec![point(self.state.bob)]
+    }
+
+    fn reach(&self) -> Bounds {
+        let reach = self.state.reach.max(0.0) as f32;
+        Bounds::around(self.pivot(), reach).union(Bounds::point(point(self.state.bob)))
+    }
+
+    fn label(&self, _units: &Units) -> String {
+        format!("Plugin: {}", self.plugin.name)
+    }
+
+    fn hud_lines(&self, _units: &Units) -> Vec<String> {
+        let mut lines = vec![
+            format!("Plugin: {}", self.plugin.name),
+            format!("Time: {:.1} s", self.state.time),
+        ];
+        if let Some(reason) = &*self.failure.borrow() {
+            lines.push(format!("Disabled: {}", reason));
+        }
+        lines
+    }
+
+    fn entry(&self) -> ModelEntry {
+        let mut entry = self.entry.borrow_mut();
+        entry.x = self.state.pivot.x as f32;
+        entry.y = self.state.pivot.y as f32;
+        entry.time = self.state.time;
+        if let Some(data) = self.serialize() {
+            entry.data = data;
+        }
+        ModelEntry::Plugin(entry.clone())
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![
+            self.state.time,
+            self.state.pivot.x,
+            self.state.pivot.y,
+            self.state.bob.x,
+            self.state.bob.y,
+        ];
+        state.extend(
+            self.serialize()
+                .unwrap_or_default()
+                .iter()
+                .map(|&b| b as f64),
+        );
+        state
+    }
+
+    fn key(&mut self, key: VirtualKeyCode) -> bool {
+        key_code(key).is_some_and(|code| self.offer_key(code))
+    }
+
+    fn labels(&self) -> Vec<Label> {
+        self.labels.borrow().clone()
+    }
+}
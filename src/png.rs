@@ -0,0 +1,170 @@
+//! Just enough of PNG to write an image out: 8-bit RGB, one IDAT chunk,
+//! and the zlib stream made of stored deflate blocks, so nothing is
+//! actually compressed. The files come out large but any viewer reads them.
+
+use crate::card::crc32;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Most bytes a stored deflate block can hold.
+const STORED_BLOCK: usize = 65_535;
+
+/// An RGB image drawn into with anti-aliased strokes.
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: [f32; 3]) -> Canvas {
+        let pixels = (width as usize)
+            .checked_mul(height as usize)
+            .expect("the canvas has more pixels than can be addressed");
+        Canvas {
+            width,
+            height,
+            pixels: vec![background; pixels],
+        }
+    }
+
+    /// Mixes `color` into a pixel by `coverage`, from 0 to 1.
+    fn blend(&mut self, x: i64, y: i64, color: [f32; 3], coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let pixel = &mut self.pixels[y as usize * self.width as usize + x as usize];
+        for (channel, target) in pixel.iter_mut().zip(color) {
+            *channel += (target - *channel) * coverage;
+        }
+    }
+
+    /// A line `width` pixels wide from `a` to `b` with round ends, mixed in
+    /// by `alpha`. Pixels are covered by how far their centres are inside
+    /// the stroke, which smooths the edges.
+    pub fn stroke(
+        &mut self,
+        a: (f32, f32),
+        b: (f32, f32),
+        width: f32,
+        color: [f32; 3],
+        alpha: f32,
+    ) {
+        let reach = 0.5 * width + 1.0;
+        let (left, right) = (a.0.min(b.0) - reach, a.0.max(b.0) + reach);
+        let (top, bottom) = (a.1.min(b.1) - reach, a.1.max(b.1) + reach);
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length_squared = (dx * dx + dy * dy).max(1e-12);
+
+        for y in top.floor() as i64..=bottom.ceil() as i64 {
+            for x in left.floor() as i64..=right.ceil() as i64 {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let t = (((px - a.0) * dx + (py - a.1) * dy) / length_squared).clamp(0.0, 1.0);
+                let distance = (px - a.0 - t * dx).hypot(py - a.1 - t * dy);
+                let coverage = (0.5 * width + 0.5 - distance).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage * alpha);
+                }
+            }
+        }
+    }
+
+    /// A filled disc.
+    pub fn dot(&mut self, centre: (f32, f32), radius: f32, color: [f32; 3]) {
+        self.stroke(centre, centre, 2.0 * radius, color, 1.0);
+    }
+
+    /// A circle's outline.
+    pub fn ring(&mut self, centre: (f32, f32), radius: f32, width: f32, color: [f32; 3]) {
+        const SEGMENTS: usize = 48;
+        let point = |i: usize| {
+            let angle = std::f32::consts::TAU * i as f32 / SEGMENTS as f32;
+            (
+                centre.0 + radius * angle.cos(),
+                centre.1 + radius * angle.sin(),
+            )
+        };
+        for i in 0..SEGMENTS {
+            self.stroke(point(i), point(i + 1), width, color, 1.0);
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let rgb: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect();
+        encode(self.width, self.height, &rgb)
+    }
+}
+
+/// A PNG of a `width` by `height` image given as RGB bytes, row by row.
+pub fn encode(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let size = (width as usize)
+        .checked_mul(3)
+        .and_then(|row| row.checked_mul(height as usize));
+    assert_eq!(Some(rgb.len()), size, "wrong amount of pixel data");
+    let row = 3 * width as usize;
+
+    // Every row starts with its filter type, none.
+    let mut scanlines = Vec::with_capacity(rgb.len() + height as usize);
+    for line in rgb.chunks(row.max(1)).take(height as usize) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(line);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, colour type RGB, then the standard compression, filter
+    // and no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// A zlib stream holding `data` in stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(STORED_BLOCK).max(1);
+    let mut stream = Vec::with_capacity(data.len() + 5 * blocks + 6);
+    // Deflate with a 32 KiB window, and check bits making the pair a
+    // multiple of 31.
+    stream.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65_521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % MODULUS;
+        b = (b + a) % MODULUS;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,190 @@
+use std::f32::consts::{PI, TAU};
+use std::fs;
+use std::path::Path;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::pendulum::Pendulum;
+
+/// Points collected unless `[poincare]` says otherwise.
+const DEFAULT_POINTS: usize = 500;
+
+pub enum PoincareState {
+    Idle,
+    /// The last attempt was refused because the pendulum isn't driven.
+    NotDriven,
+    Collecting,
+    Done,
+}
+
+/// Collects the Poincaré section of a driven pendulum, its angle and
+/// angular velocity once per drive period at a fixed drive phase, and writes
+/// it to CSV once enough points are in. The run simply continues until then.
+pub struct PoincareExport {
+    pub state: PoincareState,
+    /// Points to collect per export.
+    pub target: usize,
+    /// Drive phase, in radians, at which the section is taken.
+    pub phase: f32,
+    points: Vec<(f32, f32)>,
+    /// Angle, angular velocity and drive phase after the previous step.
+    previous: Option<(f32, f32, f32)>,
+}
+
+impl PoincareExport {
+    pub fn new() -> PoincareExport {
+        PoincareExport {
+            state: PoincareState::Idle,
+            target: DEFAULT_POINTS,
+            phase: 0.0,
+            points: Vec::new(),
+            previous: None,
+        }
+    }
+
+    /// Reads `points = <count>` and `phase = <radians>` from a `[poincare]`
+    /// section.
+    pub fn from_config(section: &Section) -> Result<PoincareExport, String> {
+        let mut export = PoincareExport::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("points", value)) => {
+                    export.target = value
+                        .parse()
+                        .ok()
+                        .filter(|&points| points > 0)
+                        .ok_or(error(format!("invalid point count `{}`", value)))?
+                }
+                Some(("phase", value)) => {
+                    export.phase = parse_f32("phase", value).map_err(error)?.rem_euclid(TAU)
+                }
+                _ => return Err(error(format!("unknown poincare setting `{}`", line))),
+            }
+        }
+
+        Ok(export)
+    }
+
+    /// Starts collecting from the pendulum's current state.
+    pub fn start(&mut self, p: &Pendulum) {
+        if p.drive_amplitude == 0.0 || p.drive_frequency <= 0.0 {
+            self.state = PoincareState::NotDriven;
+            return;
+        }
+
+        self.points.clear();
+        self.previous = None;
+        self.state = PoincareState::Collecting;
+    }
+
+    pub fn cancel(&mut self) {
+        self.state = PoincareState::Idle;
+        self.points.clear();
+        self.previous = None;
+    }
+
+    /// Looks for the section phase in the step just taken, and writes the
+    /// points to `csv` once there are enough.
+    pub fn record(&mut self, p: &Pendulum, csv: &Path) {
+        if !matches!(self.state, PoincareState::Collecting) {
+            return;
+        }
+
+        // Phase measured from the section, so crossing it is a wrap.
+        let phase = (p.drive_phase(p.time) - self.phase).rem_euclid(TAU);
+        if let Some((angle, angular_velocity, previous_phase)) = self.previous {
+            if phase < previous_phase {
+                // Interpolate to the moment the phase came round.
+                let fraction = (TAU - previous_phase) / (TAU - previous_phase + phase);
+                let angle = angle + (p.angle - angle) * fraction;
+                let angular_velocity =
+                    angular_velocity + (p.angular_velocity - angular_velocity) * fraction;
+                self.points
+                    .push(((angle + PI).rem_euclid(TAU) - PI, angular_velocity));
+            }
+        }
+        self.previous = Some((p.angle, p.angular_velocity, phase));
+
+        if self.points.len() >= self.target {
+            self.finish(p, csv);
+        }
+    }
+
+    fn finish(&mut self, p: &Pendulum, csv: &Path) {
+        self.state = PoincareState::Done;
+
+        let mut text = format!(
+            "# drive_phase={} drive_frequency={} drive_amplitude={} points={}\n\
+             angle,angular_velocity\n",
+            self.phase,
+            p.drive_frequency,
+            p.drive_amplitude,
+            self.points.len()
+        );
+        for (angle, angular_velocity) in &self.points {
+            text.push_str(&format!("{},{}\n", angle, angular_velocity));
+        }
+        if let Err(e) = fs::write(csv, text) {
+            log!("could not write {}: {}", csv.display(), e);
+        }
+    }
+
+    pub fn hud_line(&self) -> Option<String> {
+        match self.state {
+            PoincareState::Idle => None,
+            PoincareState::NotDriven => Some("Poincaré: the pendulum isn't driven".to_string()),
+            PoincareState::Collecting => Some(format!(
+                "Poincaré: {} / {} points at phase {:.2}",
+                self.points.len(),
+                self.target,
+                self.phase
+            )),
+            PoincareState::Done => Some(format!("Poincaré: {} points exported", self.points.len())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_export_has_a_header_and_the_requested_rows() {
+        let csv =
+            std::env::temp_dir().join(format!("pendulum-poincare-{}.csv", std::process::id()));
+        let _ = fs::remove_file(&csv);
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.drive_amplitude = 0.001;
+        p.drive_frequency = 0.04;
+
+        let mut export = PoincareExport::new();
+        export.target = 12;
+        export.start(&p);
+        while !matches!(export.state, PoincareState::Done) {
+            p.update();
+            export.record(&p, &csv);
+            assert!(p.time < 10_000.0, "the export never finished");
+        }
+
+        let text = fs::read_to_string(&csv).unwrap();
+        let mut lines = text.lines();
+        assert!(lines.next().unwrap().ends_with("points=12"));
+        assert_eq!(lines.next(), Some("angle,angular_velocity"));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), 12);
+        for row in rows {
+            let fields: Vec<f32> = row.split(',').map(|field| field.parse().unwrap()).collect();
+            assert_eq!(fields.len(), 2, "{}", row);
+            assert!(fields[0].abs() <= PI, "{}", row);
+        }
+        let _ = fs::remove_file(&csv);
+    }
+
+    #[test]
+    fn an_undriven_pendulum_is_refused() {
+        let mut export = PoincareExport::new();
+        export.start(&Pendulum::new(400.0, 0.0, 200.0));
+        assert!(matches!(export.state, PoincareState::NotDriven));
+    }
+}
@@ -0,0 +1,262 @@
+//! Thick lines through many points, drawn as one piece. Drawing each
+//! segment on its own leaves notches and overlaps at every corner, which
+//! show on a thick line and darken a translucent one; here neighbouring
+//! segments meet on a shared mitred edge instead. Each side also fades out
+//! over a pixel, so the edges are smoothed without a multisampled window.
+
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+
+/// Pixels over which the edges fade out.
+const FEATHER: f32 = 1.0;
+/// Longest a mitred corner may reach, in half thicknesses. Sharper turns
+/// have their corner cut short rather than shooting out a spike.
+pub const MITER_LIMIT: f32 = 4.0;
+/// Points closer than this to the one before are dropped, as they have no
+/// direction to join along.
+const MIN_SEGMENT: f32 = 1e-3;
+
+fn unit(v: Vector) -> Vector {
+    let length = v.x.hypot(v.y);
+    Vector::new(v.x / length, v.y / length)
+}
+
+/// The left-hand normal of a direction, with y down.
+fn normal(direction: Vector) -> Vector {
+    Vector::new(direction.y, -direction.x)
+}
+
+/// The indices of `points` that are far enough from the one kept before.
+fn distinct(points: &[Vector]) -> Vec<usize> {
+    let mut kept: Vec<usize> = Vec::with_capacity(points.len());
+    for (i, point) in points.iter().enumerate() {
+        let far = kept.last().is_none_or(|&last| {
+            let last = points[last];
+            (point.x - last.x).hypot(point.y - last.y) > MIN_SEGMENT
+        });
+        if far {
+            kept.push(i);
+        }
+    }
+    kept
+}
+
+/// At each of `points`, the offset to the line's left edge for a half
+/// thickness of 1: the segment's normal at the ends, and at a joint the
+/// mitre, along the bisector of the two normals, lengthened so both edges
+/// stay a half thickness from their segments. The right edge is the same
+/// offset the other way.
+pub fn miters(points: &[Vector]) -> Vec<Vector> {
+    let directions: Vec<Vector> = points
+        .windows(2)
+        .map(|pair| unit(Vector::new(pair[1].x - pair[0].x, pair[1].y - pair[0].y)))
+        .collect();
+    if directions.is_empty() {
+        return Vec::new();
+    }
+    (0..points.len())
+        .map(|i| {
+            let before = normal(directions[i.saturating_sub(1)]);
+            let after = normal(directions[i.min(directions.len() - 1)]);
+            let sum = Vector::new(before.x + after.x, before.y + after.y);
+            if sum.x.hypot(sum.y) < MIN_SEGMENT {
+                // Doubling straight back: there's no corner to mitre.
+                return after;
+            }
+            let bisector = unit(sum);
+            let cos = bisector.x * after.x + bisector.y * after.y;
+            let reach = (1.0 / cos).min(MITER_LIMIT);
+            Vector::new(bisector.x * reach, bisector.y * reach)
+        })
+        .collect()
+}
+
+/// Draws a line `thickness` pixels wide through `points`, all in `color`.
+pub fn draw_polyline(graphics: &mut dyn Renderer, points: &[Vector], thickness: f32, color: Color) {
+    graphics.draw_polyline(points, thickness, &vec![color; points.len()]);
+}
+
+/// Draws a line `thickness` pixels wide through `points`, with each point
+/// given its colour by `color` from its index and the colour shading
+/// smoothly between them.
+pub fn draw_shaded_polyline(
+    graphics: &mut dyn Renderer,
+    points: &[Vector],
+    thickness: f32,
+    color: impl Fn(usize) -> Color,
+) {
+    let colors: Vec<Color> = (0..points.len()).map(color).collect();
+    graphics.draw_polyline(points, thickness, &colors);
+}
+
+/// A line laid out as `fill_strip` draws it: the points far enough apart
+/// to keep, with their colours and mitres, and the half thicknesses of the
+/// solid core and of the line with its fringe.
+pub struct Strip {
+    pub points: Vec<Vector>,
+    pub colors: Vec<Color>,
+    pub miters: Vec<Vector>,
+    pub core: f32,
+    pub outer: f32,
+}
+
+impl Strip {
+    /// The line `thickness` pixels wide through `points`, `colors` giving
+    /// each its colour, or `None` if too few are apart to make one.
+    pub fn new(points: &[Vector], thickness: f32, colors: &[Color]) -> Option<Strip> {
+        let kept = distinct(points);
+        let kept_points: Vec<Vector> = kept.iter().map(|&i| points[i]).collect();
+        let miters = miters(&kept_points);
+        if miters.is_empty() {
+            return None;
+        }
+        // The solid core and the faded fringe share the half thickness, so
+        // the line looks as heavy as `thickness` says.
+        Some(Strip {
+            colors: kept.iter().map(|&i| colors[i]).collect(),
+            points: kept_points,
+            miters,
+            core: (0.5 * (thickness - FEATHER)).max(0.0),
+            outer: 0.5 * (thickness + FEATHER),
+        })
+    }
+
+    /// The stretches between neighbouring points.
+    pub fn segments(&self) -> usize {
+        self.points.len() - 1
+    }
+
+    /// The point `side` half thicknesses to the left of the `i`th, along
+    /// its mitre.
+    pub fn at(&self, i: usize, side: f32) -> Vec2 {
+        let (point, miter) = (self.points[i], self.miters[i]);
+        Vec2::new(point.x + side * miter.x, point.y + side * miter.y)
+    }
+
+    /// Draws the `i`th stretch as quads: the core, and the fringe fading
+    /// out on each side.
+    pub fn draw_segment<R: Renderer + ?Sized>(&self, graphics: &mut R, i: usize) {
+        let clear = |c: Color| Color::from_rgba(c.r(), c.g(), c.b(), 0.0);
+        let (a, b) = (self.colors[i], self.colors[i + 1]);
+        let (core, outer) = (self.core, self.outer);
+        graphics.draw_quad_four_color(
+            [
+                self.at(i, core),
+                self.at(i + 1, core),
+                self.at(i + 1, -core),
+                self.at(i, -core),
+            ],
+            [a, b, b, a],
+        );
+        for (from, to) in [(core, outer), (-core, -outer)] {
+            graphics.draw_quad_four_color(
+                [
+                    self.at(i, to),
+                    self.at(i + 1, to),
+                    self.at(i + 1, from),
+                    self.at(i, from),
+                ],
+                [clear(a), clear(b), b, a],
+            );
+        }
+    }
+}
+
+/// The line of `draw_shaded_polyline` as quads: a solid core with a fringe
+/// fading out on each side, `colors` giving each of `points` its colour.
+pub fn fill_strip<R: Renderer + ?Sized>(
+    graphics: &mut R,
+    points: &[Vector],
+    thickness: f32,
+    colors: &[Color],
+) {
+    if let Some(strip) = Strip::new(points, thickness, colors) {
+        for i in 0..strip.segments() {
+            strip.draw_segment(graphics, i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(v: Vector, x: f32, y: f32) -> bool {
+        (v.x - x).abs() < 1e-5 && (v.y - y).abs() < 1e-5
+    }
+
+    fn points(xy: &[(f32, f32)]) -> Vec<Vector> {
+        xy.iter().map(|&(x, y)| Vector::new(x, y)).collect()
+    }
+
+    #[test]
+    fn a_right_angle_is_mitred_to_its_corner() {
+        // Right, then down the screen: the left edge is on the outside.
+        let miters = miters(&points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]));
+        assert_eq!(miters.len(), 3);
+        // The ends are square across their segments.
+        assert!(close(miters[0], 0.0, -1.0));
+        assert!(close(miters[2], 1.0, 0.0));
+        // The corner goes out along the bisector, √2 half thicknesses.
+        assert!(close(miters[1], 1.0, -1.0));
+        assert!((miters[1].x.hypot(miters[1].y) - 2f32.sqrt()).abs() < 1e-5);
+        // So both edges through it stay a half thickness off their
+        // segments, whatever the thickness.
+        for half in [0.5, 3.0] {
+            let (x, y) = (10.0 + half * miters[1].x, half * miters[1].y);
+            assert!((-y - half).abs() < 1e-5 && (x - 10.0 - half).abs() < 1e-5);
+            let (x, y) = (10.0 - half * miters[1].x, -half * miters[1].y);
+            assert!((y - half).abs() < 1e-5 && (10.0 - x - half).abs() < 1e-5);
+        }
+
+        // Turning the other way round puts the corner on the right.
+        let miters = super::miters(&points(&[(0.0, 0.0), (10.0, 0.0), (10.0, -10.0)]));
+        assert!(close(miters[1], -1.0, -1.0));
+    }
+
+    #[test]
+    fn straight_on_there_is_no_corner_and_sharp_turns_are_cut_short() {
+        let straight = miters(&points(&[(0.0, 0.0), (5.0, 5.0), (10.0, 10.0)]));
+        let across = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(straight.iter().all(|&miter| close(miter, across, -across)));
+
+        // A hairpin would reach out far past the limit.
+        let hairpin = miters(&points(&[(0.0, 0.0), (100.0, 0.0), (0.0, 1.0)]));
+        assert!((hairpin[1].x.hypot(hairpin[1].y) - MITER_LIMIT).abs() < 1e-4);
+        // Going straight back there's nothing to mitre, and the last
+        // segment's normal is used.
+        let back = miters(&points(&[(0.0, 0.0), (10.0, 0.0), (0.0, 0.0)]));
+        assert!(close(back[1], 0.0, 1.0));
+
+        assert!(miters(&points(&[(1.0, 1.0)])).is_empty());
+        assert!(miters(&[]).is_empty());
+    }
+
+    #[test]
+    fn a_strip_drops_points_with_no_direction() {
+        let line = points(&[
+            (0.0, 0.0),
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 1e-4),
+            (10.0, 10.0),
+        ]);
+        let colors: Vec<Color> = (0..line.len())
+            .map(|i| Color::from_rgb(i as f32 / 4.0, 0.0, 0.0))
+            .collect();
+        let strip = Strip::new(&line, 3.0, &colors).unwrap();
+        assert_eq!(strip.segments(), 2);
+        // Each kept point keeps its own colour.
+        let reds: Vec<f32> = strip.colors.iter().map(|c| c.r()).collect();
+        assert_eq!(reds, [0.0, 0.5, 1.0]);
+        assert!(close(strip.miters[1], 1.0, -1.0));
+        assert!(strip.core == 1.0 && strip.outer == 2.0);
+        let outside = strip.at(1, strip.outer);
+        assert!((outside.x - 12.0).abs() < 1e-5 && (outside.y + 2.0).abs() < 1e-5);
+
+        assert!(Strip::new(&line[..2], 3.0, &colors).is_none());
+    }
+}
@@ -0,0 +1,264 @@
+//! A still phase portrait of the selected pendulum written to a PNG, for
+//! figures rather than for watching: trajectories started from a grid of
+//! angles and angular velocities, the separatrix between swinging and going
+//! over the top, and the fixed points. Unlike the live phase plot it doesn't
+//! follow the pendulum on screen, only its parameters. The drive is left
+//! out, since with it the flow changes from moment to moment and the
+//! picture wouldn't be one portrait.
+
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::model::PendulumEntry;
+use crate::pendulum::Pendulum;
+use crate::png::Canvas;
+use crate::STEPS_PER_SECOND;
+
+/// The velocity axis reaches this far past the top of the separatrix.
+const HEADROOM: f32 = 1.5;
+const MARGIN: f32 = 40.0;
+/// Widest and tallest image drawn. The canvas holds three floats a pixel,
+/// so this square is already 200 MB.
+const MAX_SIDE: u32 = 4096;
+
+const BACKGROUND: [f32; 3] = [1.0, 1.0, 1.0];
+const AXES: [f32; 3] = [0.6, 0.6, 0.6];
+const SWINGING: [f32; 3] = [0.15, 0.35, 0.75];
+const SPINNING: [f32; 3] = [0.85, 0.45, 0.1];
+const SEPARATRIX: [f32; 3] = [0.75, 0.1, 0.1];
+const FIXED_POINT: [f32; 3] = [0.0, 0.0, 0.0];
+
+pub struct PhasePortrait {
+    /// Starting angles across the width, and angular velocities up the
+    /// height.
+    angles: usize,
+    velocities: usize,
+    width: u32,
+    height: u32,
+    /// How long each trajectory is followed.
+    seconds: f32,
+}
+
+impl PhasePortrait {
+    pub fn new() -> PhasePortrait {
+        PhasePortrait {
+            angles: 24,
+            velocities: 16,
+            width: 1600,
+            height: 1000,
+            seconds: 4.0,
+        }
+    }
+
+    /// Reads `angles`, `velocities`, `width`, `height` and `seconds` from a
+    /// `[portrait]` section.
+    pub fn from_config(section: &Section) -> Result<PhasePortrait, String> {
+        let mut portrait = PhasePortrait::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            let count = |key: &str, value: &str| {
+                value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(|| {
+                        error(format!(
+                            "expected a whole number above zero for `{}`, found `{}`",
+                            key, value
+                        ))
+                    })
+            };
+            let side = |key: &str, value: &str| {
+                u32::try_from(count(key, value)?)
+                    .ok()
+                    .filter(|&side| side <= MAX_SIDE)
+                    .ok_or_else(|| {
+                        error(format!(
+                            "`{}` can be at most {} pixels, found `{}`",
+                            key, MAX_SIDE, value
+                        ))
+                    })
+            };
+            match key_value(line) {
+                Some(("angles", value)) => portrait.angles = count("angles", value)?,
+                Some(("velocities", value)) => portrait.velocities = count("velocities", value)?,
+                Some(("width", value)) => portrait.width = side("width", value)?,
+                Some(("height", value)) => portrait.height = side("height", value)?,
+                Some(("seconds", value)) => {
+                    portrait.seconds = parse_f32("seconds", value).map_err(error)?;
+                    if portrait.seconds <= 0.0 {
+                        return Err(error("`seconds` must be positive".to_string()));
+                    }
+                }
+                _ => return Err(error(format!("unknown portrait setting `{}`", line))),
+            }
+        }
+
+        if portrait.width <= 2 * MARGIN as u32 || portrait.height <= 2 * MARGIN as u32 {
+            return Err(format!(
+                "the image must be over {} pixels each way",
+                2 * MARGIN as u32
+            ));
+        }
+        Ok(portrait)
+    }
+
+    /// Draws the portrait of pendulums like `entry`.
+    pub fn render(&self, entry: &PendulumEntry) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height, BACKGROUND);
+        let line = self.width.min(self.height) as f32 / 1000.0;
+
+        let model = Pendulum::from_entry(entry);
+        // Natural angular frequency per step, the unit of the velocity axis.
+        let natural = (model.g / model.effective_length()).sqrt();
+        let top = HEADROOM * 2.0 * natural;
+        let (width, height) = (
+            self.width as f32 - 2.0 * MARGIN,
+            self.height as f32 - 2.0 * MARGIN,
+        );
+        let point = |angle: f32, velocity: f32| {
+            (
+                MARGIN + width * (angle + PI) / (2.0 * PI),
+                MARGIN + height * 0.5 * (1.0 - velocity / top),
+            )
+        };
+
+        // Axes through the bottom of the swing, and a frame.
+        let (left, right) = (point(-PI, 0.0), point(PI, 0.0));
+        canvas.stroke(left, right, line, AXES, 1.0);
+        canvas.stroke(point(0.0, -top), point(0.0, top), line, AXES, 1.0);
+        for (a, b) in [
+            (point(-PI, top), point(PI, top)),
+            (point(PI, top), point(PI, -top)),
+            (point(PI, -top), point(-PI, -top)),
+            (point(-PI, -top), point(-PI, top)),
+        ] {
+            canvas.stroke(a, b, line, AXES, 1.0);
+        }
+
+        // Beyond the separatrix there's enough energy to go over the top.
+        // Each stretch is coloured by which side it's on, so a damped spin
+        // changes colour as it drops into a swing.
+        let color = |angle: f32, velocity: f32| {
+            let energy = 0.5 * velocity * velocity - natural * natural * angle.cos();
+            if energy > natural * natural {
+                SPINNING
+            } else {
+                SWINGING
+            }
+        };
+
+        let steps = (self.seconds * STEPS_PER_SECOND).round() as usize;
+        for i in 0..self.angles {
+            for j in 0..self.velocities {
+                let angle = -PI + 2.0 * PI * (i as f32 + 0.5) / self.angles as f32;
+                let velocity = top * (2.0 * (j as f32 + 0.5) / self.velocities as f32 - 1.0);
+                let mut p = Pendulum::from_entry(&PendulumEntry {
+                    angle,
+                    angular_velocity: velocity,
+                    drive_amplitude: 0.0,
+                    time: 0.0,
+                    ..entry.fresh()
+                });
+                let mut last = point(angle, velocity);
+                canvas.dot(last, 1.5 * line, color(angle, velocity));
+                for state in p.steps().take(steps) {
+                    let wrapped = (state.angle + PI).rem_euclid(2.0 * PI) - PI;
+                    let next = point(wrapped, state.angular_velocity);
+                    // Leaving one side comes back in at the other, which
+                    // isn't a line across the picture.
+                    let across = (next.0 - last.0).abs() > 0.5 * width;
+                    let outside = |y: f32| y < MARGIN || y > MARGIN + height;
+                    if !across && !outside(last.1) && !outside(next.1) {
+                        let color = color(wrapped, state.angular_velocity);
+                        canvas.stroke(last, next, line, color, 0.5);
+                    }
+                    last = next;
+                }
+            }
+        }
+
+        // The separatrix of the undamped swing, ω = ±2ω₀ cos(θ/2).
+        const SAMPLES: usize = 400;
+        for sign in [1.0, -1.0] {
+            let at = |k: usize| {
+                let angle = -PI + 2.0 * PI * k as f32 / SAMPLES as f32;
+                point(angle, sign * 2.0 * natural * (0.5 * angle).cos())
+            };
+            for k in 0..SAMPLES {
+                canvas.stroke(at(k), at(k + 1), 2.5 * line, SEPARATRIX, 1.0);
+            }
+        }
+
+        // Hanging at rest is stable; balanced at the top is a saddle, shown
+        // at both ends since they're the same point.
+        canvas.dot(point(0.0, 0.0), 5.0 * line, FIXED_POINT);
+        for angle in [-PI, PI] {
+            canvas.ring(point(angle, 0.0), 5.0 * line, 2.0 * line, FIXED_POINT);
+        }
+        canvas
+    }
+
+    /// Renders the portrait and writes it to `path`, checking it arrived.
+    /// Returns the file's size.
+    pub fn export(&self, entry: &PendulumEntry, path: &Path) -> Result<u64, String> {
+        let png = self.render(entry).encode();
+        fs::write(path, &png).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.len() > 0 => Ok(metadata.len()),
+            Ok(_) => Err(format!("{} was written empty", path.display())),
+            Err(e) => Err(format!("could not check {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn trajectories(&self) -> usize {
+        self.angles * self.velocities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn portrait(settings: &str) -> Result<PhasePortrait, String> {
+        let config = Config::parse(&format!("[portrait]\n{}\n", settings)).unwrap();
+        PhasePortrait::from_config(config.section("portrait").unwrap())
+    }
+
+    #[test]
+    fn the_portrait_is_written_as_a_png() {
+        let path =
+            std::env::temp_dir().join(format!("pendulum-portrait-{}.png", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let portrait =
+            portrait("angles = 4\nvelocities = 3\nwidth = 240\nheight = 160\nseconds = 1").unwrap();
+
+        let size = portrait.export(&PendulumEntry::default(), &path).unwrap();
+        let png = fs::read(&path).unwrap();
+        assert!(size > 0 && size == png.len() as u64);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // IHDR comes first, with the width and height.
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 240, 0, 0, 0, 160]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn oversized_images_are_refused() {
+        assert!(portrait("width = 4096\nheight = 4096").is_ok());
+        for settings in ["width = 4097", "height = 100000", "width = 4294967297"] {
+            let error = portrait(settings).err().unwrap();
+            assert!(
+                error.contains("at most 4096 pixels"),
+                "{}: {}",
+                settings,
+                error
+            );
+        }
+        assert!(portrait("width = 60").is_err(), "too small for the margins");
+    }
+}
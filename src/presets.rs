@@ -0,0 +1,231 @@
+use crate::integrator::Scheme;
+use crate::model::{CartEntry, DoubleEntry, ModelEntry, PendulumEntry};
+use crate::pendulum::Pendulum;
+use crate::scene::Scene;
+
+/// The default gravity of 0.5 stands for Earth's 9.81 m/s²; other bodies
+/// are scaled from it.
+const EARTH_GRAVITY: f32 = 9.81;
+const SCENE_GRAVITY: f32 = 0.5;
+
+/// Period of the seconds pendulum: one second per swing.
+const SECONDS_PENDULUM_PERIOD: f32 = 2.0;
+
+/// The wave repeats after this many seconds, with the longest pendulum
+/// swinging `WAVE_FIRST_COUNT` times in that time and each shorter one once
+/// more than its neighbour.
+const WAVE_CYCLE: f32 = 20.0;
+const WAVE_FIRST_COUNT: usize = 10;
+const WAVE_PENDULUMS: usize = 12;
+
+pub struct Preset {
+    pub name: &'static str,
+    entries: fn() -> Vec<ModelEntry>,
+}
+
+/// Scene gravity standing for `gravity` in m/s².
+pub fn scene_gravity(gravity: f32) -> f32 {
+    SCENE_GRAVITY * gravity / EARTH_GRAVITY
+}
+
+/// Scene gravity `g` in m/s².
+pub fn real_gravity(g: f32) -> f32 {
+    EARTH_GRAVITY * g / SCENE_GRAVITY
+}
+
+/// Built-in scenes, loaded with the number keys in this order.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "Single pendulum",
+        entries: single,
+    },
+    Preset {
+        name: "Celestial gravities",
+        entries: celestial,
+    },
+    Preset {
+        name: "Seconds pendulum",
+        entries: seconds_pendulum,
+    },
+    Preset {
+        name: "Pendulum wave",
+        entries: pendulum_wave,
+    },
+    Preset {
+        name: "Double pendulum",
+        entries: double_pendulum,
+    },
+    Preset {
+        name: "Cart pendulum",
+        entries: cart_pendulum,
+    },
+    Preset {
+        name: "Integrator drift",
+        entries: integrator_drift,
+    },
+];
+
+impl Preset {
+    pub fn scene(&self) -> Scene {
+        Scene::new((self.entries)())
+    }
+}
+
+fn single() -> Vec<ModelEntry> {
+    vec![ModelEntry::Pendulum(PendulumEntry::default())]
+}
+
+/// Four identical pendulums under the surface gravity of different bodies,
+/// so their periods can be compared side by side.
+fn celestial() -> Vec<ModelEntry> {
+    [
+        ("Moon", 1.62),
+        ("Mars", 3.71),
+        ("Earth", 9.81),
+        ("Jupiter", 24.79),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (name, gravity))| {
+        ModelEntry::Pendulum(PendulumEntry {
+            name: name.to_string(),
+            x: 100.0 + 200.0 * i as f32,
+            y: 60.0,
+            r: 150.0,
+            angle: 0.6,
+            g: scene_gravity(*gravity),
+            ..PendulumEntry::default()
+        })
+    })
+    .collect()
+}
+
+/// A pendulum tuned to beat seconds, the length clocks were built around.
+fn seconds_pendulum() -> Vec<ModelEntry> {
+    vec![ModelEntry::Pendulum(PendulumEntry {
+        r: Pendulum::length_for_period(SECONDS_PENDULUM_PERIOD, SCENE_GRAVITY),
+        ..PendulumEntry::default()
+    })]
+}
+
+/// A row of pendulums with periods chosen so they drift in and out of phase,
+/// tracing travelling waves and lining up again every `WAVE_CYCLE` seconds.
+fn pendulum_wave() -> Vec<ModelEntry> {
+    (0..WAVE_PENDULUMS)
+        .map(|i| {
+            let period = WAVE_CYCLE / (WAVE_FIRST_COUNT + i) as f32;
+            ModelEntry::Pendulum(PendulumEntry {
+                x: 60.0 + 62.0 * i as f32,
+                y: 20.0,
+                r: Pendulum::length_for_period(period, SCENE_GRAVITY),
+                angle: 0.4,
+                g: SCENE_GRAVITY,
+                ..PendulumEntry::default()
+            })
+        })
+        .collect()
+}
+
+/// Two links started high up, where the motion is chaotic.
+fn double_pendulum() -> Vec<ModelEntry> {
+    vec![ModelEntry::Double(DoubleEntry {
+        y: 220.0,
+        angles: [2.4, 2.9],
+        ..DoubleEntry::default()
+    })]
+}
+
+/// A heavy bob on a light cart, so the recoil is easy to see.
+fn cart_pendulum() -> Vec<ModelEntry> {
+    vec![ModelEntry::Cart(CartEntry {
+        cart_mass: 1.0,
+        m: 2.0,
+        angle: 1.2,
+        ..CartEntry::default()
+    })]
+}
+
+/// Two undamped pendulums started the same, one stepped with forward Euler
+/// and one with RK4: the Euler swing gains energy and creeps wider and
+/// slower, and the two soon swing out of step.
+fn integrator_drift() -> Vec<ModelEntry> {
+    [(250.0, Scheme::Euler), (550.0, Scheme::Rk4)]
+        .into_iter()
+        .map(|(x, integrator)| {
+            ModelEntry::Pendulum(PendulumEntry {
+                x,
+                y: 60.0,
+                r: 150.0,
+                angle: 1.0,
+                damping_ratio: Some(0.0),
+                integrator,
+                ..PendulumEntry::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::energy;
+    use crate::units::Units;
+
+    fn preset(name: &str) -> Scene {
+        PRESETS
+            .iter()
+            .find(|preset| preset.name == name)
+            .unwrap()
+            .scene()
+    }
+
+    #[test]
+    fn the_two_integrators_start_together_and_drift_apart() {
+        let mut scene = preset("Integrator drift");
+        let pendulums = |scene: &Scene| -> Vec<Pendulum> {
+            scene
+                .models
+                .iter()
+                .map(|model| model.as_pendulum().unwrap().clone())
+                .collect()
+        };
+        let start = pendulums(&scene);
+        assert_eq!(start.len(), 2);
+        assert_eq!(start[0].angle, start[1].angle);
+        let energies = [energy::energy(&start[0]), energy::energy(&start[1])];
+        let labels: Vec<String> = scene
+            .models
+            .iter()
+            .map(|model| model.label(&Units::default()))
+            .collect();
+        assert!(
+            labels[0].ends_with(", Euler") && labels[1].ends_with(", RK4"),
+            "{:?}",
+            labels
+        );
+
+        // Barely apart at first, then further with every swing.
+        let mut gaps = Vec::new();
+        for _ in 0..10 {
+            for _ in 0..20 {
+                scene.step();
+            }
+            let [euler, rk4] = &pendulums(&scene)[..] else {
+                unreachable!()
+            };
+            gaps.push((euler.angle - rk4.angle).abs());
+        }
+        assert!(gaps[0] < 0.02, "{:?}", gaps);
+        assert!(gaps[9] > 10.0 * gaps[0], "{:?}", gaps);
+        for _ in 0..2000 {
+            scene.step();
+        }
+
+        // Euler's swing has gained energy, RK4's has kept it.
+        let [euler, rk4] = &pendulums(&scene)[..] else {
+            unreachable!()
+        };
+        assert!(energy::energy(euler) > 1.2 * energies[0]);
+        assert!((energy::energy(rk4) - energies[1]).abs() < 1e-3 * energies[1]);
+    }
+}
@@ -0,0 +1,191 @@
+//! Remote control for scripted demos, turned on with `--stdin-control`.
+//! Commands arrive on stdin one per line, in the console's grammar, and are
+//! read on a background thread into a bounded queue. The window takes them
+//! off the queue at the start of each frame, before the step, and answers
+//! each with one JSON line on stdout, in order:
+//!
+//! ```text
+//! {"line":3,"command":"query period","ok":true,"reply":["period = 2.8375 s"],"value":2.8375}
+//! {"line":4,"command":"set q 1","ok":false,"error":"Unknown setting `q`"}
+//! ```
+//!
+//! `line` is the line's number on stdin, counting from 1, so replies can be
+//! matched to commands. Lines that arrive while the queue is full are turned
+//! away, and answered in their turn with an error saying so. The end of
+//! stdin only stops the reading; the window stays open. Run with
+//! `--headless` there's no window, and the app exits once stdin has ended
+//! and the last command has been answered.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::json::quote;
+
+/// Commands waiting for the next frame before any more are turned away.
+const QUEUE: usize = 64;
+
+/// A line read from stdin.
+pub enum Incoming {
+    /// A command to run, with its line number.
+    Command(usize, String),
+    /// A line turned away, with the error to answer it with.
+    Refused(usize, String, String),
+}
+
+pub struct RemoteControl {
+    incoming: Receiver<Incoming>,
+    /// Commands sent and not yet taken.
+    waiting: Arc<AtomicUsize>,
+    /// Set once stdin has ended, after its last line was sent.
+    closed: Arc<AtomicBool>,
+}
+
+impl RemoteControl {
+    pub fn start() -> RemoteControl {
+        RemoteControl::listen(BufReader::new(io::stdin()))
+    }
+
+    fn listen<R: BufRead + Send + 'static>(input: R) -> RemoteControl {
+        // Refusals go down the same channel as the commands so they're
+        // answered in stdin order; the bound is kept by counting.
+        let (sender, incoming) = mpsc::channel();
+        let waiting = Arc::new(AtomicUsize::new(0));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (counter, flag) = (waiting.clone(), closed.clone());
+        thread::spawn(move || {
+            read(input, &sender, &counter);
+            flag.store(true, Ordering::SeqCst);
+        });
+        RemoteControl {
+            incoming,
+            waiting,
+            closed,
+        }
+    }
+
+    /// The lines that have come in since the last call, oldest first.
+    pub fn pending(&self) -> Vec<Incoming> {
+        let incoming: Vec<Incoming> = self.incoming.try_iter().collect();
+        let commands = incoming
+            .iter()
+            .filter(|line| matches!(line, Incoming::Command(..)))
+            .count();
+        self.waiting.fetch_sub(commands, Ordering::SeqCst);
+        incoming
+    }
+
+    /// True once stdin has ended. Lines read before the end are all in
+    /// `pending` by the time this says so.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+fn read<R: BufRead>(input: R, sender: &Sender<Incoming>, waiting: &AtomicUsize) {
+    for (i, line) in input.lines().enumerate() {
+        let line_number = i + 1;
+        let incoming = match line {
+            Ok(text) if text.trim().is_empty() => continue,
+            Err(e) => Incoming::Refused(
+                line_number,
+                String::new(),
+                format!("unreadable line: {}", e),
+            ),
+            Ok(text) if waiting.load(Ordering::SeqCst) >= QUEUE => Incoming::Refused(
+                line_number,
+                text,
+                format!(
+                    "dropped: {} commands are already waiting for the next step",
+                    QUEUE
+                ),
+            ),
+            Ok(text) => {
+                waiting.fetch_add(1, Ordering::SeqCst);
+                Incoming::Command(line_number, text)
+            }
+        };
+        if sender.send(incoming).is_err() {
+            return;
+        }
+    }
+}
+
+/// Writes the answer to the command on stdin line `line`: its reply lines,
+/// and for a query the value on its own, or the error.
+pub fn respond(
+    line: usize,
+    command: &str,
+    result: Result<Vec<String>, String>,
+    value: Option<f32>,
+) {
+    let mut json = format!("{{\"line\":{},\"command\":{},", line, quote(command.trim()));
+    match result {
+        Ok(reply) => {
+            let reply: Vec<String> = reply.iter().map(|line| quote(line)).collect();
+            json.push_str(&format!("\"ok\":true,\"reply\":[{}]", reply.join(",")));
+            if let Some(value) = value.filter(|value| value.is_finite()) {
+                json.push_str(&format!(",\"value\":{}", value));
+            }
+        }
+        Err(e) => json.push_str(&format!("\"ok\":false,\"error\":{}", quote(&e))),
+    }
+    json.push('}');
+    // Whatever reads the answers may have gone, which isn't a reason to
+    // stop carrying out the commands.
+    let _ = writeln!(io::stdout().lock(), "{}", json);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn a_flood_is_turned_away_past_the_queue_in_stdin_order() {
+        let input: String = (0..100).map(|i| format!("set g {}\n\n", i)).collect();
+        let remote = RemoteControl::listen(Cursor::new(input));
+        while !remote.is_closed() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let incoming = remote.pending();
+        assert_eq!(incoming.len(), 100);
+        let mut last = 0;
+        for (i, line) in incoming.iter().enumerate() {
+            let (Incoming::Command(number, _) | Incoming::Refused(number, _, _)) = line;
+            assert!(*number > last, "line {} came after line {}", number, last);
+            last = *number;
+            match line {
+                Incoming::Command(..) => assert!(i < QUEUE),
+                Incoming::Refused(_, command, e) => {
+                    assert!(i >= QUEUE);
+                    assert_eq!(command, &format!("set g {}", i));
+                    assert!(e.starts_with("dropped"));
+                }
+            }
+        }
+        assert!(remote.pending().is_empty());
+    }
+
+    #[test]
+    fn taking_the_commands_makes_room_for_more() {
+        let (sender, incoming) = mpsc::channel();
+        let waiting = Arc::new(AtomicUsize::new(0));
+        read(Cursor::new("pause\n".repeat(QUEUE)), &sender, &waiting);
+        let remote = RemoteControl {
+            incoming,
+            waiting: waiting.clone(),
+            closed: Arc::new(AtomicBool::new(true)),
+        };
+        assert_eq!(remote.pending().len(), QUEUE);
+        assert_eq!(waiting.load(Ordering::SeqCst), 0);
+
+        read(Cursor::new("pause\n"), &sender, &waiting);
+        assert!(matches!(remote.pending()[..], [Incoming::Command(1, _)]));
+    }
+}
@@ -0,0 +1,63 @@
+//! The shapes the scene is drawn from, behind a trait so the same drawing
+//! code can go to the window or to a file. `Graphics2D` draws them on
+//! screen; `svg::Svg` writes them out as vector shapes.
+
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::polyline;
+use crate::vector::Vector;
+
+pub trait Renderer {
+    fn draw_line(&mut self, start: (f32, f32), end: (f32, f32), thickness: f32, color: Color);
+
+    fn draw_circle(&mut self, center: (f32, f32), radius: f32, color: Color);
+
+    fn draw_rectangle(&mut self, rectangle: Rectangle, color: Color);
+
+    fn draw_triangle(&mut self, points: [Vec2; 3], color: Color);
+
+    fn draw_quad(&mut self, points: [Vec2; 4], color: Color);
+
+    /// A quad shaded between the colours at its corners.
+    fn draw_quad_four_color(&mut self, points: [Vec2; 4], colors: [Color; 4]);
+
+    /// A line `thickness` pixels wide through `points`, `colors` giving each
+    /// point its colour. By default the mitred, feathered strip of quads
+    /// `polyline` makes of it.
+    fn draw_polyline(&mut self, points: &[Vector], thickness: f32, colors: &[Color]) {
+        polyline::fill_strip(self, points, thickness, colors);
+    }
+
+    /// How far behind the swing, in world units, what's drawn next stands.
+    /// Only an `anaglyph::Eye` makes anything of it.
+    fn set_depth(&mut self, _depth: f32) {}
+}
+
+impl Renderer for Graphics2D {
+    fn draw_line(&mut self, start: (f32, f32), end: (f32, f32), thickness: f32, color: Color) {
+        Graphics2D::draw_line(self, start, end, thickness, color);
+    }
+
+    fn draw_circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        Graphics2D::draw_circle(self, center, radius, color);
+    }
+
+    fn draw_rectangle(&mut self, rectangle: Rectangle, color: Color) {
+        Graphics2D::draw_rectangle(self, rectangle, color);
+    }
+
+    fn draw_triangle(&mut self, points: [Vec2; 3], color: Color) {
+        Graphics2D::draw_triangle(self, points, color);
+    }
+
+    fn draw_quad(&mut self, points: [Vec2; 4], color: Color) {
+        Graphics2D::draw_quad(self, points, color);
+    }
+
+    fn draw_quad_four_color(&mut self, points: [Vec2; 4], colors: [Color; 4]) {
+        Graphics2D::draw_quad_four_color(self, points, colors);
+    }
+}
@@ -0,0 +1,484 @@
+//! The lab writeup: what the selected pendulum was set up as, what the
+//! sensor measured of its swing against what theory says it should have
+//! done, and where its energy went, written to a file to hand in. The
+//! report key writes it from the sensor's last 30 seconds; `--report
+//! <seconds>` runs the config's scene for that long without a window and
+//! writes it at the end.
+//!
+//! A `[report]` section says where, and whether as Markdown or plain text:
+//!
+//! ```text
+//! [report]
+//! file = lab.md
+//! format = markdown
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{key_value, Section};
+use crate::energy;
+use crate::model::ModelEntry;
+use crate::pendulum::{DampingModel, Pendulum};
+use crate::presets;
+use crate::scene::Scene;
+use crate::sensor::{self, Estimate};
+use crate::units::Units;
+use crate::STEPS_PER_SECOND;
+
+const DEFAULT_FILE: &str = "report.md";
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Format {
+    Markdown,
+    Text,
+}
+
+pub struct LabReport {
+    pub file: PathBuf,
+    pub format: Format,
+}
+
+impl LabReport {
+    pub fn new() -> LabReport {
+        LabReport {
+            file: PathBuf::from(DEFAULT_FILE),
+            format: Format::Markdown,
+        }
+    }
+
+    /// Reads `file = <path>` and `format = markdown|text` from a `[report]`
+    /// section.
+    pub fn from_config(section: &Section) -> Result<LabReport, String> {
+        let mut report = LabReport::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("file", value)) if !value.is_empty() => report.file = PathBuf::from(value),
+                Some(("format", "markdown")) => report.format = Format::Markdown,
+                Some(("format", "text")) => report.format = Format::Text,
+                Some(("format", value)) => {
+                    return Err(error(format!(
+                        "unknown format `{}`: use markdown or text",
+                        value
+                    )))
+                }
+                _ => return Err(error(format!("unknown report setting `{}`", line))),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Writes the report on `p`, loaded as `loaded`, from its recorded
+    /// (seconds, angle) `samples`, and says where.
+    pub fn write(
+        &self,
+        p: &Pendulum,
+        loaded: &ModelEntry,
+        samples: &[(f32, f32)],
+        units: &Units,
+    ) -> Result<String, String> {
+        let text = self.text(p, loaded, samples, units);
+        fs::write(&self.file, text)
+            .map_err(|e| format!("could not write {}: {}", self.file.display(), e))?;
+        Ok(format!("Report written to {}", self.file.display()))
+    }
+
+    pub fn text(
+        &self,
+        p: &Pendulum,
+        loaded: &ModelEntry,
+        samples: &[(f32, f32)],
+        units: &Units,
+    ) -> String {
+        let estimate = sensor::estimate(samples, p.g);
+        let duration = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => last.0 - first.0,
+            _ => 0.0,
+        };
+
+        let mut text = match self.format {
+            Format::Markdown => "# Pendulum lab report\n".to_string(),
+            Format::Text => "PENDULUM LAB REPORT\n".to_string(),
+        };
+        self.heading(&mut text, "Setup");
+        match self.format {
+            Format::Markdown => text.push_str(&format!("```text\n{}\n```\n\n", loaded.to_line())),
+            Format::Text => text.push_str(&format!("    {}\n\n", loaded.to_line())),
+        }
+        self.list(&mut text, &setup(p, units));
+        self.heading(&mut text, "Measured against theory");
+        match estimate {
+            Some(estimate) => {
+                text.push_str(&format!(
+                    "From {} samples over {:.1} s of the swing.\n\n",
+                    samples.len(),
+                    duration
+                ));
+                self.table(&mut text, &comparison(p, &estimate));
+            }
+            None => text.push_str(&format!(
+                "Only {:.1} s recorded, too little of the swing to measure.\n",
+                duration
+            )),
+        }
+        self.heading(&mut text, "Energy");
+        self.list(&mut text, &energy_lines(p));
+        text
+    }
+
+    fn heading(&self, text: &mut String, heading: &str) {
+        match self.format {
+            Format::Markdown => text.push_str(&format!("\n## {}\n\n", heading)),
+            Format::Text => {
+                text.push_str(&format!("\n{}\n{}\n", heading, "-".repeat(heading.len())))
+            }
+        }
+    }
+
+    fn list(&self, text: &mut String, items: &[(&str, String)]) {
+        let width = items.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, value) in items {
+            match self.format {
+                Format::Markdown => text.push_str(&format!("- **{}:** {}\n", name, value)),
+                Format::Text => text.push_str(&format!(
+                    "{:<width$}  {}\n",
+                    format!("{}:", name),
+                    value,
+                    width = width + 1
+                )),
+            }
+        }
+    }
+
+    fn table(&self, text: &mut String, rows: &[[String; 4]]) {
+        let header = ["Quantity", "Measured", "Theory", "Error"].map(String::from);
+        let rows: Vec<&[String; 4]> = std::iter::once(&header).chain(rows).collect();
+        match self.format {
+            Format::Markdown => {
+                for (i, row) in rows.iter().enumerate() {
+                    text.push_str(&format!("| {} |\n", row.join(" | ")));
+                    if i == 0 {
+                        text.push_str("|---|---:|---:|---:|\n");
+                    }
+                }
+            }
+            Format::Text => {
+                let widths: Vec<usize> = (0..4)
+                    .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+                    .collect();
+                for row in rows {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .zip(&widths)
+                        .enumerate()
+                        .map(|(column, (cell, &width))| match column {
+                            0 => format!("{:<width$}", cell, width = width),
+                            _ => format!("{:>width$}", cell, width = width),
+                        })
+                        .collect();
+                    text.push_str(cells.join("  ").trim_end());
+                    text.push('\n');
+                }
+            }
+        }
+    }
+}
+
+/// Steps `scene` for `seconds` with nothing else going on, recording the
+/// selected pendulum as the sensor would, and writes the report on it.
+pub fn run_headless(
+    report: &LabReport,
+    mut scene: Scene,
+    seconds: f32,
+    units: &Units,
+) -> Result<String, String> {
+    let loaded = scene.initial_entries()[scene.selected].clone();
+    if scene.selected_pendulum().is_none() {
+        return Err("the scene's first model isn't a single pendulum".to_string());
+    }
+    let steps = (seconds * STEPS_PER_SECOND).round() as usize;
+    let mut samples = Vec::with_capacity(steps + 1);
+    for step in 0..=steps {
+        if step > 0 {
+            scene.step();
+        }
+        if let Some(p) = scene.selected_pendulum() {
+            samples.push((p.time / STEPS_PER_SECOND, p.angle));
+        }
+    }
+    let p = scene.selected_pendulum().ok_or("the pendulum went away")?;
+    report.write(p, &loaded, &samples, units)
+}
+
+fn setup(p: &Pendulum, units: &Units) -> Vec<(&'static str, String)> {
+    let damping = match (p.damping_model, p.damping_ratio()) {
+        (DampingModel::Viscous, Some(zeta)) => format!("viscous, ratio {:.4}", zeta),
+        (DampingModel::Viscous, None) => "viscous".to_string(),
+        (DampingModel::Coulomb, _) => "Coulomb friction".to_string(),
+    };
+    let mut items = vec![
+        (
+            "Length",
+            format!(
+                "{} (effective {})",
+                units.length(p.r),
+                units.length(p.effective_length())
+            ),
+        ),
+        ("Bob", format!("{}, mass {:.2}", p.bob.name(), p.m)),
+        (
+            "Gravity",
+            format!("{:.3} ({:.2} m/s²)", p.g, presets::real_gravity(p.g)),
+        ),
+        ("Damping", damping),
+        ("Integrator", p.integrator.name().to_string()),
+        (
+            "Time step",
+            format!(
+                "dt = 1/{} s ({:.2} ms)",
+                STEPS_PER_SECOND,
+                1000.0 / STEPS_PER_SECOND
+            ),
+        ),
+        ("Rate", format!("x{}, in steps a frame", p.rate)),
+    ];
+    if p.gravity_gradient != 0.0 {
+        items.push(("Gravity gradient", format!("{}", p.gravity_gradient)));
+    }
+    if p.drive_amplitude != 0.0 {
+        items.push((
+            "Drive",
+            format!("{:.4} at {:.3}", p.drive_amplitude, p.drive_frequency),
+        ));
+    }
+    if p.string {
+        items.push(("String", "can go slack".to_string()));
+    }
+    items
+}
+
+/// Rows of quantity, measured, theory and percent error.
+fn comparison(p: &Pendulum, estimate: &Estimate) -> Vec<[String; 4]> {
+    let row =
+        |name: &str, measured: Option<f32>, theory: Option<f32>, format: &dyn Fn(f32) -> String| {
+            let error = match (measured, theory) {
+                (Some(measured), Some(theory)) if theory != 0.0 => {
+                    format!("{:+.2}%", 100.0 * (measured - theory) / theory)
+                }
+                _ => "-".to_string(),
+            };
+            [
+                name.to_string(),
+                measured.map_or("-".to_string(), format),
+                theory.map_or("-".to_string(), format),
+                error,
+            ]
+        };
+    let seconds = |t: f32| format!("{:.4} s", t);
+    let ratio = |z: f32| format!("{:.4}", z);
+    let period = 1.0 / estimate.frequency;
+    // δ = 2πζ / √(1 - ζ²), which the sensor inverted.
+    let decrement = p
+        .damping_ratio()
+        .filter(|&zeta| zeta < 1.0)
+        .map(|zeta| std::f32::consts::TAU * zeta / (1.0 - zeta * zeta).sqrt());
+
+    vec![
+        row(
+            "Period (undamped)",
+            Some(period),
+            Some(p.period_at(estimate.amplitude)),
+            &seconds,
+        ),
+        row(
+            "Small-angle period",
+            None,
+            Some(p.natural_period()),
+            &seconds,
+        ),
+        row("Amplitude", Some(estimate.amplitude), None, &|a| {
+            format!("{:.4} rad", a)
+        }),
+        row(
+            "Damping ratio",
+            estimate.damping_ratio,
+            p.damping_ratio(),
+            &ratio,
+        ),
+        row(
+            "Log decrement per cycle",
+            estimate.decrement,
+            decrement,
+            &ratio,
+        ),
+    ]
+}
+
+fn energy_lines(p: &Pendulum) -> Vec<(&'static str, String)> {
+    let ledger = &p.ledger;
+    let now = energy::precise_energy(p);
+    let dissipated = ledger.dissipated();
+    let share = if ledger.initial > 0.0 {
+        format!(
+            " ({:.1}% of the start)",
+            100.0 * dissipated / ledger.initial
+        )
+    } else {
+        String::new()
+    };
+    vec![
+        ("At the start", format!("{:.2}", ledger.initial)),
+        ("Now", format!("{:.2}", now)),
+        ("Dissipated", format!("{:.2}{}", dissipated, share)),
+        (
+            "Put in",
+            format!("{:.2}", ledger.inputs() - ledger.integration),
+        ),
+        ("Integration error", format!("{:+.4}", ledger.integration)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    /// The cells of the table row for `quantity`, in a Markdown report.
+    fn row<'a>(text: &'a str, quantity: &str) -> Vec<&'a str> {
+        let line = text
+            .lines()
+            .find(|line| line.starts_with(&format!("| {} |", quantity)))
+            .unwrap_or_else(|| panic!("no `{}` row in\n{}", quantity, text));
+        line.trim_matches('|').split(" | ").map(str::trim).collect()
+    }
+
+    fn percent(cell: &str) -> f32 {
+        cell.trim_end_matches('%').parse().unwrap()
+    }
+
+    #[test]
+    fn a_known_run_reports_every_field() {
+        let file = std::env::temp_dir().join(format!("pendulum-report-{}.md", std::process::id()));
+        let report = LabReport {
+            file: file.clone(),
+            format: Format::Markdown,
+        };
+        let units = Units::default();
+        let said = run_headless(&report, Scene::single(), 30.0, &units).unwrap();
+        assert_eq!(said, format!("Report written to {}", file.display()));
+        let text = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+
+        // The run it made, again, for what the report should say.
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let loaded = p.entry();
+        p.steps()
+            .take(30 * STEPS_PER_SECOND as usize)
+            .for_each(drop);
+
+        assert!(text.starts_with("# Pendulum lab report\n"));
+        for heading in ["Setup", "Measured against theory", "Energy"] {
+            assert!(
+                text.contains(&format!("\n## {}\n", heading)),
+                "no {}",
+                heading
+            );
+        }
+        // What it was set up as.
+        assert!(text.contains(&loaded.to_line()));
+        assert!(text.contains(&format!("- **Length:** {}", units.length(p.r))));
+        assert!(text.contains("- **Gravity:** 0.500"));
+        assert!(text.contains("- **Damping:** viscous, ratio"));
+        assert!(text.contains(&format!("- **Integrator:** {}\n", p.integrator.name())));
+        assert!(text.contains("- **Time step:** dt = 1/60 s (16.67 ms)"));
+        assert!(text.contains("From 1801 samples over 30.0 s of the swing."));
+
+        // The period, measured and from theory, close to one another.
+        let period = row(&text, "Period (undamped)");
+        let measured: f32 = period[1].trim_end_matches(" s").parse().unwrap();
+        let theory: f32 = period[2].trim_end_matches(" s").parse().unwrap();
+        assert!((measured - theory).abs() < 0.01 * theory, "{:?}", period);
+        assert!(percent(period[3]).abs() < 1.0, "{:?}", period);
+        let small = row(&text, "Small-angle period");
+        assert_eq!(small[2], format!("{:.4} s", p.natural_period()));
+
+        // The decay, measured and from theory.
+        for quantity in ["Damping ratio", "Log decrement per cycle"] {
+            let cells = row(&text, quantity);
+            assert!(cells[1] != "-" && cells[2] != "-", "{:?}", cells);
+            assert!(percent(cells[3]).abs() < 10.0, "{:?}", cells);
+        }
+
+        // Where the energy went.
+        let dissipated = p.ledger.dissipated();
+        assert!(dissipated > 0.0);
+        assert!(text.contains(&format!(
+            "- **Dissipated:** {:.2} ({:.1}% of the start)",
+            dissipated,
+            100.0 * dissipated / p.ledger.initial
+        )));
+        assert!(text.contains(&format!("- **At the start:** {:.2}", p.ledger.initial)));
+        assert!(text.contains("- **Integration error:** "));
+    }
+
+    #[test]
+    fn plain_text_has_the_same_fields() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let loaded = p.entry();
+        let samples: Vec<(f32, f32)> = std::iter::once((0.0, p.angle))
+            .chain((0..20 * STEPS_PER_SECOND as usize).map(|_| {
+                p.step();
+                (p.time / STEPS_PER_SECOND, p.angle)
+            }))
+            .collect();
+        let report = LabReport {
+            format: Format::Text,
+            ..LabReport::new()
+        };
+        let text = report.text(&p, &loaded, &samples, &Units::default());
+        assert!(text.starts_with("PENDULUM LAB REPORT\n"));
+        assert!(text.contains("\nSetup\n-----\n"));
+        assert!(text.contains(&format!("    {}\n", loaded.to_line())));
+        for field in [
+            "Length:",
+            "Damping:",
+            "Integrator:",
+            "Time step:",
+            "Quantity",
+            "Period (undamped)",
+            "Damping ratio",
+            "Log decrement per cycle",
+            "Dissipated:",
+        ] {
+            assert!(text.contains(field), "no `{}` in\n{}", field, text);
+        }
+        assert!(!text.contains("**") && !text.contains('|'));
+    }
+
+    #[test]
+    fn too_short_a_run_says_so() {
+        let p = Pendulum::new(400.0, 0.0, 200.0);
+        let text = LabReport::new().text(&p, &p.entry(), &[(0.0, p.angle)], &Units::default());
+        assert!(text.contains("Only 0.0 s recorded, too little of the swing to measure."));
+        assert!(!text.contains("| Quantity |"));
+        assert!(text.contains("- **Dissipated:** 0.00"));
+    }
+
+    #[test]
+    fn reads_the_section() {
+        let config =
+            crate::config::Config::parse("[report]\nfile = lab.txt\nformat = text\n").unwrap();
+        let report = LabReport::from_config(config.section("report").unwrap()).unwrap();
+        assert!(report.file == *"lab.txt" && report.format == Format::Text);
+        let config = crate::config::Config::parse("[report]\nformat = pdf\n").unwrap();
+        assert_eq!(
+            LabReport::from_config(config.section("report").unwrap())
+                .err()
+                .unwrap(),
+            "line 2: unknown format `pdf`: use markdown or text"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::cmp::Ordering;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::focus::{Handling, Registry, Target};
+use crate::store::{format_timestamp, Record, Store};
+
+const ROW_HEIGHT: f32 = 20.0;
+/// Column headings and their left edges, relative to the panel.
+const COLUMNS: [(&str, f32); 6] = [
+    ("When", 8.0),
+    ("Kind", 140.0),
+    ("Name", 240.0),
+    ("Value", 330.0),
+    ("Damping", 410.0),
+    ("Parameters", 490.0),
+];
+
+/// Scrollable table of everything in the store, sorted by the column whose
+/// heading was clicked last, newest first until then.
+pub struct ResultsPanel {
+    pub visible: bool,
+    rect: (f32, f32, f32, f32),
+    sort: usize,
+    descending: bool,
+    /// First row shown.
+    scroll: usize,
+    hover: bool,
+}
+
+fn compare(column: usize, a: &Record, b: &Record) -> Ordering {
+    match column {
+        0 => a.timestamp.cmp(&b.timestamp),
+        1 => a.kind.name().cmp(b.kind.name()),
+        2 => a.name.cmp(&b.name),
+        3 => a.value.total_cmp(&b.value),
+        4 => a.damping.partial_cmp(&b.damping).unwrap_or(Ordering::Equal),
+        _ => a.parameters.len().cmp(&b.parameters.len()),
+    }
+}
+
+impl ResultsPanel {
+    pub fn new() -> ResultsPanel {
+        ResultsPanel {
+            visible: false,
+            rect: (40.0, 50.0, 720.0, 300.0),
+            sort: 0,
+            descending: true,
+            scroll: 0,
+            hover: false,
+        }
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let (left, top, width, height) = self.rect;
+        x >= left && x <= left + width && y >= top && y <= top + height
+    }
+
+    fn visible_rows(&self) -> usize {
+        (self.rect.3 / ROW_HEIGHT) as usize - 1
+    }
+
+    /// Records in display order.
+    pub fn sorted<'a>(&self, store: &'a Store) -> Vec<&'a Record> {
+        let mut records: Vec<&Record> = store.records.iter().collect();
+        records.sort_by(|a, b| {
+            let order = compare(self.sort, a, b);
+            if self.descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        records
+    }
+
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        self.hover = self.visible && self.contains(x, y);
+    }
+
+    /// Returns true if the panel took the click. Clicking a heading sorts by
+    /// that column, and clicking it again reverses the order.
+    pub fn mouse_down(&mut self, x: f32, y: f32) -> bool {
+        if !self.visible || !self.contains(x, y) {
+            return false;
+        }
+
+        let (left, top, _, _) = self.rect;
+        if y < top + ROW_HEIGHT {
+            let column = COLUMNS
+                .iter()
+                .rposition(|&(_, offset)| x >= left + offset)
+                .unwrap_or(0);
+            if column == self.sort {
+                self.descending = !self.descending;
+            } else {
+                self.sort = column;
+                self.descending = false;
+            }
+            self.scroll = 0;
+        }
+        true
+    }
+
+    /// Registers the column headings, when shown.
+    pub fn register(&self, registry: &mut Registry) {
+        if !self.visible {
+            return;
+        }
+        let (left, top, _, _) = self.rect;
+        for (i, &(_, offset)) in COLUMNS.iter().enumerate() {
+            registry.register(
+                Target::Heading(i),
+                (left + offset + 20.0, top + ROW_HEIGHT / 2.0),
+                ROW_HEIGHT / 2.0,
+                Handling::Click,
+            );
+        }
+    }
+
+    /// Scrolls by `lines` wheel notches if the mouse is over the panel.
+    pub fn scroll(&mut self, lines: f32, store: &Store) -> bool {
+        if !self.hover {
+            return false;
+        }
+
+        let last = store.records.len().saturating_sub(self.visible_rows());
+        let rows = (-lines * 3.0) as isize;
+        self.scroll = self.scroll.saturating_add_signed(rows).min(last);
+        true
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, store: &Store) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = self.rect;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.9),
+        );
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + ROW_HEIGHT)),
+            Color::LIGHT_GRAY,
+        );
+
+        let text = |graphics: &mut Graphics2D, x: f32, y: f32, text: &str| {
+            graphics.draw_text(
+                (left + x, y),
+                Color::BLACK,
+                &font.layout_text(text, 16.0, TextOptions::new()),
+            );
+        };
+
+        for (i, (heading, x)) in COLUMNS.iter().enumerate() {
+            let arrow = match (i == self.sort, self.descending) {
+                (false, _) => "",
+                (true, false) => " ^",
+                (true, true) => " v",
+            };
+            text(graphics, *x, top + 2.0, &format!("{}{}", heading, arrow));
+        }
+
+        let records = self.sorted(store);
+        if records.is_empty() {
+            text(
+                graphics,
+                COLUMNS[0].1,
+                top + ROW_HEIGHT + 2.0,
+                "No results yet: finish a game round or an impulse test",
+            );
+        }
+        for (row, record) in records
+            .iter()
+            .skip(self.scroll)
+            .take(self.visible_rows())
+            .enumerate()
+        {
+            let y = top + ROW_HEIGHT * (row + 1) as f32 + 2.0;
+            let parameters: Vec<String> = record
+                .parameters
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            let cells = [
+                format_timestamp(record.timestamp),
+                record.kind.name().to_string(),
+                record.name.clone(),
+                format!("{:.3}", record.value),
+                record
+                    .damping
+                    .map_or("-".to_string(), |d| format!("{:.4}", d)),
+                parameters.join(" "),
+            ];
+            for (cell, (_, x)) in cells.iter().zip(COLUMNS) {
+                text(graphics, x, y, cell);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Kind;
+
+    #[test]
+    fn clicking_a_heading_sorts_by_it_and_again_reverses() {
+        let path = std::env::temp_dir().join(format!("pendulum-results-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = Store::open(&path);
+        for (value, timestamp) in [(2.0, 300), (9.0, 100), (5.0, 200)] {
+            store.add(Record {
+                timestamp,
+                ..Record::new(Kind::Game, "flag", value, &[])
+            });
+        }
+        let values = |panel: &ResultsPanel| -> Vec<f32> {
+            panel
+                .sorted(&store)
+                .iter()
+                .map(|record| record.value)
+                .collect()
+        };
+
+        let mut panel = ResultsPanel::new();
+        panel.visible = true;
+        assert_eq!(values(&panel), vec![2.0, 5.0, 9.0], "newest first");
+
+        let (left, top, _, _) = panel.rect;
+        let value_heading = left + COLUMNS[3].1 + 1.0;
+        assert!(panel.mouse_down(value_heading, top + 1.0));
+        assert_eq!(values(&panel), vec![2.0, 5.0, 9.0]);
+        panel.mouse_down(value_heading, top + 1.0);
+        assert_eq!(values(&panel), vec![9.0, 5.0, 2.0]);
+
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+}
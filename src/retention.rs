@@ -0,0 +1,370 @@
+//! How long a run's records are kept: the strip chart's angle trace and the
+//! history scrubbed through. Each is kept in tiers. The newest samples are
+//! at the full rate. When a tier fills, its oldest samples are merged, ten
+//! at a time, into one sample of the next, coarser tier. The coarsest tier
+//! drops its oldest instead. Recent data is exact, older data is ever more
+//! thinly sampled, and the whole stays inside a fixed amount of memory
+//! however long the window is left running.
+//!
+//! The memory is shared out between the stores, and each store's share is
+//! split evenly across its tiers:
+//!
+//! ```text
+//! [retention]
+//! memory = 50
+//! ```
+//!
+//! in megabytes.
+
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::config::{key_value, parse_f32, Section};
+
+/// Tiers in each store, the full-rate one included.
+const TIERS: usize = 4;
+/// Samples of one tier merged into each sample of the next.
+const FACTOR: usize = 10;
+const DEFAULT_MEMORY: f32 = 50.0;
+const MIN_MEMORY: f32 = 1.0;
+const MAX_MEMORY: f32 = 1024.0 * 1024.0;
+const BYTES_PER_MEGABYTE: f32 = 1024.0 * 1024.0;
+/// Fewest samples a tier holds, however small its share.
+const MIN_TIER: usize = 4 * FACTOR;
+
+/// A sample a tiered store can keep: it has a time, and a run of them can be
+/// merged into one.
+pub trait Sample: Copy {
+    fn time(&self) -> f32;
+
+    /// One sample standing for `run`, a run of consecutive samples oldest
+    /// first, all from the same tier. Its time is the first one's.
+    fn merge(run: &[Self]) -> Self;
+}
+
+/// The memory all the stores may use between them.
+#[derive(Copy, Clone)]
+pub struct Retention {
+    /// In bytes.
+    pub memory: usize,
+}
+
+impl Retention {
+    pub fn new() -> Retention {
+        Retention {
+            memory: (DEFAULT_MEMORY * BYTES_PER_MEGABYTE) as usize,
+        }
+    }
+
+    /// Reads `memory = <megabytes>` from a `[retention]` config section.
+    pub fn from_config(section: &Section) -> Result<Retention, String> {
+        let mut retention = Retention::new();
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("memory", value)) => {
+                    let megabytes = parse_f32("memory", value).map_err(error)?;
+                    if !(MIN_MEMORY..=MAX_MEMORY).contains(&megabytes) {
+                        return Err(error(format!(
+                            "expected {} to {} MB for `memory`, found `{}`",
+                            MIN_MEMORY, MAX_MEMORY, value
+                        )));
+                    }
+                    retention.memory = (megabytes * BYTES_PER_MEGABYTE) as usize;
+                }
+                _ => return Err(error(format!("unknown retention setting `{}`", line))),
+            }
+        }
+        Ok(retention)
+    }
+
+    /// The strip chart's share, in bytes.
+    pub fn chart(&self) -> usize {
+        self.memory / 5 * 2
+    }
+
+    /// The scrub history's share, the rest.
+    pub fn history(&self) -> usize {
+        self.memory - self.chart()
+    }
+}
+
+/// A stretch of a store kept at one resolution.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Resolution {
+    pub start: f32,
+    pub end: f32,
+    /// Full-rate samples behind each one kept.
+    pub samples: usize,
+}
+
+/// Samples kept in tiers of ever coarser resolution within a fixed budget.
+/// Read oldest first, across the tiers, as one sequence.
+pub struct Tiered<T> {
+    /// The full-rate tier first. Every sample of a tier is newer than every
+    /// sample of the tiers after it.
+    tiers: Vec<VecDeque<T>>,
+    /// Most samples each tier holds.
+    capacity: usize,
+    /// Where a run being merged is gathered.
+    run: Vec<T>,
+}
+
+impl<T: Sample> Tiered<T> {
+    /// A store using up to `budget` bytes once it's full.
+    pub fn new(budget: usize) -> Tiered<T> {
+        Tiered {
+            tiers: (0..TIERS).map(|_| VecDeque::new()).collect(),
+            capacity: (budget / TIERS / mem::size_of::<T>().max(1)).max(MIN_TIER),
+            run: Vec::with_capacity(FACTOR),
+        }
+    }
+
+    /// Drops everything and gives back the memory it took.
+    pub fn clear(&mut self) {
+        for tier in &mut self.tiers {
+            *tier = VecDeque::new();
+        }
+    }
+
+    pub fn push(&mut self, sample: T) {
+        self.push_to(0, sample);
+    }
+
+    fn push_to(&mut self, level: usize, sample: T) {
+        if self.tiers[level].len() == self.capacity {
+            if level + 1 < self.tiers.len() {
+                self.run.clear();
+                self.run.extend(self.tiers[level].drain(..FACTOR));
+                let merged = T::merge(&self.run);
+                self.push_to(level + 1, merged);
+            } else {
+                self.tiers[level].pop_front();
+            }
+        }
+
+        // Grown by hand so the allocation never passes the tier's share.
+        let tier = &mut self.tiers[level];
+        if tier.len() == tier.capacity() {
+            let wanted = (2 * tier.len()).clamp(MIN_TIER, self.capacity);
+            tier.reserve_exact(wanted - tier.len());
+        }
+        tier.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiers.iter().map(VecDeque::len).sum()
+    }
+
+    /// Oldest tiers first.
+    fn oldest_first(&self) -> impl Iterator<Item = &VecDeque<T>> + '_ {
+        self.tiers.iter().rev().filter(|tier| !tier.is_empty())
+    }
+
+    /// The `index`th sample, oldest first.
+    pub fn get(&self, mut index: usize) -> Option<T> {
+        for tier in self.oldest_first() {
+            if index < tier.len() {
+                return tier.get(index).copied();
+            }
+            index -= tier.len();
+        }
+        None
+    }
+
+    pub fn first(&self) -> Option<T> {
+        self.oldest_first().next()?.front().copied()
+    }
+
+    pub fn last(&self) -> Option<T> {
+        self.tiers.iter().find_map(|tier| tier.back().copied())
+    }
+
+    /// Every sample, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.oldest_first().flat_map(|tier| tier.iter().copied())
+    }
+
+    /// Index of the first sample at or after `time`, or the length if there
+    /// is none.
+    pub fn partition(&self, time: f32) -> usize {
+        let mut index = 0;
+        for tier in self.oldest_first() {
+            let point = tier.partition_point(|sample| sample.time() < time);
+            index += point;
+            if point < tier.len() {
+                break;
+            }
+        }
+        index
+    }
+
+    /// The samples from `start` to `end`, oldest first, finding where to
+    /// begin in each tier by bisection rather than reading the ones before.
+    pub fn between(&self, start: f32, end: f32) -> impl Iterator<Item = T> + '_ {
+        self.oldest_first().flat_map(move |tier| {
+            let first = tier.partition_point(|sample| sample.time() < start);
+            tier.range(first..)
+                .copied()
+                .take_while(move |sample| sample.time() <= end)
+        })
+    }
+
+    /// Keeps the oldest `len` samples, dropping those after.
+    pub fn truncate(&mut self, len: usize) {
+        let mut excess = self.len().saturating_sub(len);
+        for tier in &mut self.tiers {
+            let taken = excess.min(tier.len());
+            tier.truncate(tier.len() - taken);
+            excess -= taken;
+        }
+    }
+
+    /// Drops the samples from before `time`.
+    pub fn forget_before(&mut self, time: f32) {
+        for tier in self.tiers.iter_mut().rev() {
+            let point = tier.partition_point(|sample| sample.time() < time);
+            tier.drain(..point);
+            if !tier.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Each stretch held at one resolution, oldest first. A stretch ends
+    /// where the next, finer one begins.
+    pub fn resolutions(&self) -> Vec<Resolution> {
+        let mut resolutions: Vec<Resolution> = self
+            .tiers
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(level, tier)| {
+                Some(Resolution {
+                    start: tier.front()?.time(),
+                    end: tier.back()?.time(),
+                    samples: FACTOR.pow(level as u32),
+                })
+            })
+            .collect();
+        for i in 1..resolutions.len() {
+            resolutions[i - 1].end = resolutions[i].start;
+        }
+        resolutions
+    }
+
+    /// Bytes the samples take, room kept for more included.
+    pub fn memory(&self) -> usize {
+        let slots: usize = self.tiers.iter().map(VecDeque::capacity).sum();
+        slots * mem::size_of::<T>() + self.run.capacity() * mem::size_of::<T>()
+    }
+}
+
+/// `bytes` as megabytes, for the console.
+pub fn megabytes(bytes: usize) -> String {
+    format!("{:.1} MB", bytes as f32 / BYTES_PER_MEGABYTE)
+}
+
+/// How `resolution` was sampled, for a note in an export.
+pub fn describe(resolution: &Resolution, unit: &str) -> String {
+    match resolution.samples {
+        1 => format!("every {}", unit),
+        samples => format!("one per {} {}s", samples, unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[derive(Copy, Clone)]
+    struct Reading {
+        time: f32,
+        value: f32,
+    }
+
+    impl Sample for Reading {
+        fn time(&self) -> f32 {
+            self.time
+        }
+
+        fn merge(run: &[Reading]) -> Reading {
+            Reading {
+                time: run[0].time,
+                value: run.iter().map(|r| r.value).sum::<f32>() / run.len() as f32,
+            }
+        }
+    }
+
+    fn fill(store: &mut Tiered<Reading>, samples: std::ops::Range<usize>) {
+        for i in samples {
+            store.push(Reading {
+                time: i as f32,
+                value: i as f32,
+            });
+        }
+    }
+
+    #[test]
+    fn a_full_store_stays_inside_its_budget() {
+        let budget = 4 * 1024;
+        let mut store = Tiered::new(budget);
+        let (capacity, run) = (store.capacity, FACTOR * mem::size_of::<Reading>());
+        let pushes = 2 * capacity * FACTOR.pow(TIERS as u32 - 1);
+        let mut largest = 0;
+        for start in (0..pushes).step_by(capacity) {
+            fill(&mut store, start..start + capacity);
+            largest = largest.max(store.memory());
+        }
+        assert!(largest <= budget + run, "{} bytes of {}", largest, budget);
+        // Full, not merely small: every tier has grown to its share.
+        assert!(largest > budget / 2 + run, "only {} bytes", largest);
+        assert!(store.len() <= TIERS * capacity);
+
+        // The oldest sample kept has been pushed on from the full-rate tier
+        // into the coarsest, and the newest is still exact.
+        assert_eq!(store.resolutions()[0].samples, FACTOR.pow(TIERS as u32 - 1));
+        assert_eq!(store.last().unwrap().time, (pushes - 1) as f32);
+
+        store.clear();
+        assert_eq!(store.memory(), run);
+    }
+
+    #[test]
+    fn a_tiny_budget_still_keeps_the_fewest_a_tier_holds() {
+        let mut store: Tiered<Reading> = Tiered::new(0);
+        assert_eq!(store.capacity, MIN_TIER);
+        fill(&mut store, 0..2 * MIN_TIER * FACTOR.pow(TIERS as u32 - 1));
+        assert!(store.tiers.iter().all(|tier| tier.len() <= MIN_TIER));
+        assert_eq!(store.tiers[TIERS - 1].len(), MIN_TIER);
+        assert!(store.memory() <= (TIERS * MIN_TIER + FACTOR) * mem::size_of::<Reading>());
+    }
+
+    #[test]
+    fn the_memory_setting_is_held_between_its_limits() {
+        let read = |text: &str| {
+            let config = Config::parse(text).unwrap();
+            Retention::from_config(config.section("retention").unwrap()).map(|r| r.memory)
+        };
+        let megabyte = BYTES_PER_MEGABYTE as usize;
+        assert_eq!(
+            read("[retention]\n"),
+            Ok(DEFAULT_MEMORY as usize * megabyte)
+        );
+        assert_eq!(read("[retention]\nmemory = 1\n"), Ok(megabyte));
+        assert_eq!(
+            read(&format!("[retention]\nmemory = {}\n", MAX_MEMORY)),
+            Ok(MAX_MEMORY as usize * megabyte)
+        );
+        for memory in ["0.5", "0", "-3", "2000000", "NaN", "inf"] {
+            assert!(read(&format!("[retention]\nmemory = {}\n", memory)).is_err());
+        }
+        assert!(read("[retention]\nsize = 3\n").is_err());
+
+        let retention = Retention {
+            memory: 10 * megabyte,
+        };
+        assert_eq!(retention.chart() + retention.history(), retention.memory);
+    }
+}
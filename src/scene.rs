@@ -0,0 +1,197 @@
+use crate::config::Config;
+use crate::model::{Model, ModelEntry};
+use crate::pendulum::Pendulum;
+
+/// Everything on screen: a mixed list of models plus which one the keys act
+/// on. The entries it was built from are kept so models can be reset.
+pub struct Scene {
+    pub models: Vec<Box<dyn Model>>,
+    pub selected: usize,
+    initial: Vec<ModelEntry>,
+}
+
+impl Scene {
+    /// A scene of the models `entries` describe, or of a single pendulum if
+    /// there are none: selection always needs a model to point at.
+    pub fn new(entries: Vec<ModelEntry>) -> Scene {
+        if entries.is_empty() {
+            return Scene::single();
+        }
+        Scene {
+            models: entries.iter().map(ModelEntry::build).collect(),
+            selected: 0,
+            initial: entries,
+        }
+    }
+
+    /// The scene used when no config is given: one plain pendulum.
+    pub fn single() -> Scene {
+        Scene::new(vec![Pendulum::new(400.0, 0.0, 200.0).entry()])
+    }
+
+    /// Builds a scene from the `[scene]` section of a config or preset file.
+    pub fn from_config(config: &Config) -> Result<Scene, String> {
+        let section = config.section("scene").ok_or("missing [scene] section")?;
+
+        let entries = section
+            .lines
+            .iter()
+            .map(|(line_number, line)| {
+                ModelEntry::parse(line).map_err(|e| format!("line {}: {}", line_number, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if entries.is_empty() {
+            return Err("the [scene] section is empty".to_string());
+        }
+
+        Ok(Scene::new(entries))
+    }
+
+    /// The current state of every model as a `[scene]` section.
+    pub fn to_section(&self) -> String {
+        let mut text = String::from("[scene]\n");
+        for model in &self.models {
+            text.push_str(&model.entry().to_line());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The entries the scene was built from, before anything moved, as a
+    /// `[scene]` section.
+    pub fn initial_section(&self) -> String {
+        let mut text = String::from("[scene]\n");
+        for entry in &self.initial {
+            text.push_str(&entry.to_line());
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn initial_entries(&self) -> &[ModelEntry] {
+        &self.initial
+    }
+
+    pub fn step(&mut self) {
+        self.models.iter_mut().for_each(|model| model.step());
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.models.len();
+    }
+
+    pub fn selected(&self) -> &dyn Model {
+        self.models[self.selected].as_ref()
+    }
+
+    pub fn selected_mut(&mut self) -> &mut dyn Model {
+        self.models[self.selected].as_mut()
+    }
+
+    pub fn selected_pendulum(&self) -> Option<&Pendulum> {
+        self.models[self.selected].as_pendulum()
+    }
+
+    pub fn selected_pendulum_mut(&mut self) -> Option<&mut Pendulum> {
+        self.models[self.selected].as_pendulum_mut()
+    }
+
+    /// Rebuilds the selected model from the entry it was loaded with.
+    pub fn reset_selected(&mut self) {
+        self.models[self.selected] = self.initial[self.selected].build();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: &str = "
+[scene]
+pendulum x=200 y=0 r=150 angle=0.5 drive_amplitude=0.001 drive_frequency=0.05
+pendulum x=400 y=0 r=200 angle=1 damping_ratio=0.1
+chain x=600 y=0 link_length=60 links=3 angle=0.8
+double x=400 y=250
+";
+
+    #[test]
+    fn a_mixed_scene_loads_and_saves_back() {
+        let scene = Scene::from_config(&Config::parse(MIXED).unwrap()).unwrap();
+        assert_eq!(scene.models.len(), 4);
+        assert!(scene.models[0].as_pendulum().is_some());
+        assert!(scene.models[2].as_pendulum().is_none());
+        assert!(matches!(scene.models[2].entry(), ModelEntry::Chain(_)));
+        assert!(matches!(scene.models[3].entry(), ModelEntry::Double(_)));
+
+        let saved = Scene::from_config(&Config::parse(&scene.to_section()).unwrap()).unwrap();
+        assert_eq!(saved.models.len(), scene.models.len());
+        for (model, read) in scene.models.iter().zip(saved.initial_entries()) {
+            assert!(model.entry() == *read, "{}", model.entry().to_line());
+        }
+    }
+
+    /// Pendulums part way through everything that carries over between
+    /// steps: a slack string, a soft start, a retuned drive, dry friction
+    /// and RK45's substep.
+    fn busy_scene() -> Scene {
+        let mut scene = Scene::from_config(
+            &Config::parse(
+                "[scene]
+pendulum x=200 r=150 angle=2.5 angular_velocity=0.05 string=true name=\"on a string\"
+pendulum x=400 r=200 angle=1 drive_amplitude=0.0004 drive_frequency=0.05
+pendulum x=600 r=120 angle=0.4 damping=coulomb static_threshold=0.0001
+pendulum x=400 y=300 r=100 angle=1.2 integrator=rk45
+",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        scene.models[2].as_pendulum_mut().unwrap().ease_in(20.0);
+        // The string one starts over the top, so it's still in the air after
+        // a few steps.
+        for step in 0..20 {
+            if step == 10 {
+                scene.models[1]
+                    .as_pendulum_mut()
+                    .unwrap()
+                    .retune_drive(0.07);
+            }
+            scene.step();
+        }
+        scene
+    }
+
+    #[test]
+    fn a_saved_scene_reads_back_equal_and_swings_on_alike() {
+        let mut scene = busy_scene();
+        assert!(scene.models[0].as_pendulum().unwrap().slack.is_some());
+
+        let mut restored =
+            Scene::from_config(&Config::parse(&scene.to_section()).unwrap()).unwrap();
+        for (model, read) in scene.models.iter().zip(restored.initial_entries()) {
+            assert!(model.entry() == *read, "{}", model.entry().to_line());
+        }
+        for (model, again) in scene.models.iter().zip(&restored.models) {
+            assert_eq!(model.state(), again.state());
+        }
+
+        for _ in 0..300 {
+            scene.step();
+            restored.step();
+        }
+        for (model, again) in scene.models.iter().zip(&restored.models) {
+            assert_eq!(model.state(), again.state(), "{}", model.entry().to_line());
+            assert!(model.as_pendulum().unwrap().ledger == again.as_pendulum().unwrap().ledger);
+        }
+    }
+
+    #[test]
+    fn a_scene_always_has_a_model_to_select() {
+        assert!(Scene::from_config(&Config::parse("[scene]\n").unwrap()).is_err());
+        let mut scene = Scene::new(Vec::new());
+        assert_eq!(scene.models.len(), 1);
+        scene.select_next();
+        assert!(scene.selected_pendulum().is_some());
+    }
+}
@@ -0,0 +1,382 @@
+//! How sensitive the selected pendulum's motion is to each of its
+//! parameters right now. Every few seconds copies of it are run ahead from
+//! its current state with one parameter nudged up and down by 1%, and the
+//! bob's distance after a couple of seconds from where an unchanged copy
+//! got to is ranked in a small bar chart. In a regular swing the rod length
+//! dominates, since it sets the period; near chaos every nudge grows. The
+//! runs share the frame's simulation budget, so an evaluation may be spread
+//! over a few frames, and each figure is smoothed over evaluations to keep
+//! the bars steady.
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::budget::Budget;
+use crate::pendulum::{DampingModel, Pendulum};
+use crate::STEPS_PER_SECOND;
+
+const RECT: (f32, f32, f32, f32) = (590.0, 40.0, 200.0, 140.0);
+/// Relative size of each nudge.
+const EPSILON: f32 = 0.01;
+/// Seconds run ahead, and between the starts of evaluations.
+const HORIZON: f32 = 2.0;
+const INTERVAL: f32 = 3.0;
+/// Weight of each new evaluation in the smoothed figures.
+const SMOOTHING: f32 = 0.4;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Parameter {
+    Gravity,
+    Length,
+    Mass,
+    Damping,
+    DriveAmplitude,
+}
+
+const PARAMETERS: [Parameter; 5] = [
+    Parameter::Gravity,
+    Parameter::Length,
+    Parameter::Mass,
+    Parameter::Damping,
+    Parameter::DriveAmplitude,
+];
+
+impl Parameter {
+    fn name(self) -> &'static str {
+        match self {
+            Parameter::Gravity => "g",
+            Parameter::Length => "r",
+            Parameter::Mass => "m",
+            Parameter::Damping => "damping",
+            Parameter::DriveAmplitude => "drive",
+        }
+    }
+
+    /// Multiplies the parameter by `factor`.
+    fn scale(self, p: &mut Pendulum, factor: f32) {
+        match self {
+            Parameter::Gravity => p.g *= factor,
+            Parameter::Length => {
+                let mut state = p.snapshot();
+                state.r *= factor;
+                p.restore(&state);
+            }
+            Parameter::Mass => p.m *= factor,
+            Parameter::Damping => match p.damping_model {
+                // As a ratio, which gives back the same coefficient before
+                // the nudge whether or not one was chosen.
+                DampingModel::Viscous => {
+                    let critical = p.critical_coefficient().unwrap_or(1.0);
+                    p.zeta = Some(factor * p.damping_coefficient() / critical);
+                }
+                DampingModel::Coulomb => p.coulomb_friction *= factor,
+            },
+            Parameter::DriveAmplitude => p.drive_amplitude *= factor,
+        }
+    }
+}
+
+/// Sensitivity of the bob's position to a parameter: the mean distance
+/// from `base` of where it got to with the parameter raised and lowered by
+/// the fraction `epsilon`, in rod lengths per unit of relative change. A
+/// figure of 1 means a 1% change moves the bob 1% of a rod length.
+pub fn sensitivity(
+    base: (f32, f32),
+    raised: (f32, f32),
+    lowered: (f32, f32),
+    epsilon: f32,
+    length: f32,
+) -> f32 {
+    let distance = |(x, y): (f32, f32)| (x - base.0).hypot(y - base.1);
+    0.5 * (distance(raised) + distance(lowered)) / (epsilon * length)
+}
+
+/// One round of runs ahead from the same state.
+struct Evaluation {
+    base: Pendulum,
+    /// Relative size of each nudge.
+    epsilon: f32,
+    /// Each parameter raised, then lowered.
+    nudged: Vec<[Pendulum; 2]>,
+    taken: usize,
+    steps: usize,
+}
+
+impl Evaluation {
+    fn new(p: &Pendulum, epsilon: f32) -> Evaluation {
+        let copy = |nudge: Option<(Parameter, f32)>| {
+            let mut copy = p.clone();
+            if let Some((parameter, factor)) = nudge {
+                parameter.scale(&mut copy, factor);
+            }
+            copy
+        };
+        Evaluation {
+            base: copy(None),
+            epsilon,
+            nudged: PARAMETERS
+                .iter()
+                .map(|&parameter| {
+                    [
+                        copy(Some((parameter, 1.0 + epsilon))),
+                        copy(Some((parameter, 1.0 - epsilon))),
+                    ]
+                })
+                .collect(),
+            taken: 0,
+            steps: (HORIZON * STEPS_PER_SECOND) as usize,
+        }
+    }
+
+    /// Steps every copy on together until they're done or the budget is.
+    fn work(&mut self, budget: &Budget) {
+        let copies = 1 + 2 * self.nudged.len();
+        while self.taken < self.steps && !budget.is_spent() {
+            self.base.update();
+            for pair in &mut self.nudged {
+                pair[0].update();
+                pair[1].update();
+            }
+            budget.charge(copies);
+            self.taken += 1;
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.taken >= self.steps
+    }
+
+    /// Each parameter's sensitivity, and its two distances.
+    fn results(&self) -> Vec<(f32, [f32; 2])> {
+        let at = |p: &Pendulum| (p.position.x - p.origin.x, p.position.y - p.origin.y);
+        let base = at(&self.base);
+        self.nudged
+            .iter()
+            .map(|[raised, lowered]| {
+                let distance = |p: &Pendulum| {
+                    let (x, y) = at(p);
+                    (x - base.0).hypot(y - base.1)
+                };
+                (
+                    sensitivity(base, at(raised), at(lowered), self.epsilon, self.base.r),
+                    [distance(raised), distance(lowered)],
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct SensitivityOverlay {
+    pub visible: bool,
+    evaluation: Option<Evaluation>,
+    /// Steps until the next evaluation starts.
+    countdown: usize,
+    /// Smoothed sensitivity of each parameter, with the distances from the
+    /// latest evaluation, once there has been one.
+    smoothed: Option<Vec<(f32, [f32; 2])>>,
+    evaluations: usize,
+}
+
+impl SensitivityOverlay {
+    pub fn new() -> SensitivityOverlay {
+        SensitivityOverlay {
+            visible: false,
+            evaluation: None,
+            countdown: 0,
+            smoothed: None,
+            evaluations: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.evaluation = None;
+        self.countdown = 0;
+        self.smoothed = None;
+        self.evaluations = 0;
+    }
+
+    /// Starts an evaluation from `p` when one is due. Called once a step.
+    pub fn record(&mut self, p: &Pendulum) {
+        if !self.visible {
+            return;
+        }
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return;
+        }
+        if self.evaluation.is_none() {
+            self.evaluation = Some(Evaluation::new(p, EPSILON));
+            self.countdown = (INTERVAL * STEPS_PER_SECOND) as usize;
+        }
+    }
+
+    /// Carries the evaluation on with what's left of the frame's budget.
+    pub fn step(&mut self, budget: &Budget) {
+        let Some(evaluation) = &mut self.evaluation else {
+            return;
+        };
+        evaluation.work(budget);
+        if !evaluation.is_done() {
+            return;
+        }
+
+        let results = evaluation.results();
+        self.evaluation = None;
+        if results.iter().any(|(value, _)| !value.is_finite()) {
+            return;
+        }
+        self.evaluations += 1;
+        self.smoothed = Some(match self.smoothed.take() {
+            None => results,
+            Some(smoothed) => smoothed
+                .iter()
+                .zip(results)
+                .map(|((old, _), (value, distances))| (old + SMOOTHING * (value - old), distances))
+                .collect(),
+        });
+    }
+
+    /// The full table for the console.
+    pub fn table(&self) -> Vec<String> {
+        let Some(smoothed) = &self.smoothed else {
+            return vec!["No sensitivities yet: open the overlay and let it run".to_string()];
+        };
+        let mut lines = vec![format!(
+            "Rod lengths moved per unit change after {} s, smoothed over {} evaluations",
+            HORIZON, self.evaluations
+        )];
+        for (parameter, (value, [raised, lowered])) in PARAMETERS.iter().zip(smoothed) {
+            lines.push(format!(
+                "{}: {:.3} (+{}% {:.2} px, -{}% {:.2} px)",
+                parameter.name(),
+                value,
+                EPSILON * 100.0,
+                raised,
+                EPSILON * 100.0,
+                lowered
+            ));
+        }
+        lines
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = RECT;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.6),
+        );
+        let text = |graphics: &mut Graphics2D, x: f32, y: f32, text: &str| {
+            graphics.draw_text(
+                (x, y),
+                Color::BLACK,
+                &font.layout_text(text, 14.0, TextOptions::new()),
+            );
+        };
+        text(graphics, left + 4.0, top + 2.0, "Sensitivity to 1% changes");
+
+        let Some(smoothed) = &self.smoothed else {
+            text(graphics, left + 4.0, top + 22.0, "Measuring...");
+            return;
+        };
+
+        // Ranked, largest first, with the bars on a log scale so a
+        // parameter hundreds of times less telling still shows.
+        let mut ranked: Vec<(Parameter, f32)> = PARAMETERS
+            .iter()
+            .zip(smoothed)
+            .map(|(&parameter, &(value, _))| (parameter, value))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let scale = ranked[0].1.ln_1p().max(f32::MIN_POSITIVE);
+        let bar_left = left + 60.0;
+        let bar_width = width - 64.0;
+        for (i, (parameter, value)) in ranked.iter().enumerate() {
+            let y = top + 22.0 + 23.0 * i as f32;
+            text(graphics, left + 4.0, y, parameter.name());
+            let length = bar_width * value.ln_1p() / scale;
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((bar_left, y + 3.0), (bar_left + length, y + 15.0)),
+                Color::from_rgb(0.3, 0.5, 0.8),
+            );
+            text(graphics, bar_left + 2.0, y, &format!("{:.3}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pendulum partway through a run with everything that a copy made
+    /// from its saved entry has been known to lose: a retuned drive, a soft
+    /// start, dry friction and the motor.
+    fn in_motion(damping_model: DampingModel) -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.damping_model = damping_model;
+        p.drive_amplitude = 0.0004;
+        p.drive_frequency = 0.05;
+        p.ease_in(5.0);
+        for step in 0..40 {
+            if step == 20 {
+                p.retune_drive(0.07);
+            }
+            p.update();
+        }
+        p.motor_torque = 0.0002;
+        p
+    }
+
+    fn run(p: &Pendulum, epsilon: f32) -> Evaluation {
+        let mut evaluation = Evaluation::new(p, epsilon);
+        while !evaluation.is_done() {
+            evaluation.work(&Budget::frame());
+        }
+        evaluation
+    }
+
+    #[test]
+    fn nudging_by_nothing_changes_nothing() {
+        for damping_model in [DampingModel::Viscous, DampingModel::Coulomb] {
+            let evaluation = run(&in_motion(damping_model), 0.0);
+            for (_, distances) in evaluation.results() {
+                assert!(
+                    distances[0] < 1e-4 && distances[1] < 1e-4,
+                    "{:?}",
+                    distances
+                );
+            }
+        }
+        assert_eq!(
+            sensitivity((3.0, 4.0), (3.0, 4.0), (3.0, 4.0), EPSILON, 200.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn the_unchanged_copy_runs_on_as_the_pendulum_does() {
+        let mut p = in_motion(DampingModel::Coulomb);
+        let evaluation = run(&p, EPSILON);
+        for _ in 0..evaluation.steps {
+            p.update();
+        }
+        assert!(evaluation.base.snapshot() == p.snapshot());
+        assert_eq!(
+            (evaluation.base.position.x, evaluation.base.position.y),
+            (p.position.x, p.position.y)
+        );
+    }
+
+    #[test]
+    fn every_parameter_nudged_moves_a_driven_swing() {
+        let evaluation = run(&in_motion(DampingModel::Viscous), EPSILON);
+        for (parameter, (sensitivity, _)) in PARAMETERS.iter().zip(evaluation.results()) {
+            assert!(sensitivity > 0.0, "{} moved nothing", parameter.name());
+        }
+    }
+}
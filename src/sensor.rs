@@ -0,0 +1,351 @@
+//! The "virtual sensor": estimates the selected pendulum's frequency,
+//! damping and length from its recorded angle alone, the way they would be
+//! measured on a real rig, and compares them with the parameters it was
+//! actually given.
+
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::pendulum::Pendulum;
+use crate::units::Units;
+use crate::STEPS_PER_SECOND;
+
+/// Seconds of angle kept for the estimates.
+const WINDOW_SECONDS: f32 = 30.0;
+/// A crossing only counts once the signal gets this fraction of its largest
+/// swing past zero, so noise near zero doesn't add crossings.
+const HYSTERESIS: f32 = 0.1;
+
+/// Times at which `samples` (time, value) cross zero, linearly interpolated.
+/// Each crossing is the last one before the signal got `hysteresis` clear of
+/// zero on the other side, so jitter around zero is counted once.
+pub fn crossings(samples: &[(f32, f32)], hysteresis: f32) -> Vec<f32> {
+    let mut crossings = Vec::new();
+    let mut side = None;
+    let mut last_crossing = None;
+
+    for pair in samples.windows(2) {
+        let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+        if (v0 < 0.0) != (v1 < 0.0) && v0 != v1 {
+            last_crossing = Some(t0 + (t1 - t0) * v0 / (v0 - v1));
+        }
+
+        if v1.abs() > hysteresis && side != Some(v1 > 0.0) {
+            if let (Some(_), Some(time)) = (side, last_crossing) {
+                crossings.push(time);
+            }
+            side = Some(v1 > 0.0);
+        }
+    }
+
+    crossings
+}
+
+/// Oscillation frequency in cycles per unit time, from the mean spacing of
+/// the crossings, two per cycle.
+pub fn frequency(crossings: &[f32]) -> Option<f32> {
+    let (first, last) = (crossings.first()?, crossings.last()?);
+    let half_cycles = crossings.len().checked_sub(1).filter(|&n| n > 0)?;
+    Some(half_cycles as f32 / (2.0 * (last - first)))
+}
+
+/// The extreme of each half cycle between successive crossings as (time,
+/// value), refined by fitting a parabola through the largest sample and its
+/// neighbours.
+pub fn peaks(samples: &[(f32, f32)], crossings: &[f32]) -> Vec<(f32, f32)> {
+    let mut peaks = Vec::new();
+
+    for pair in crossings.windows(2) {
+        let start = samples.partition_point(|s| s.0 < pair[0]);
+        let end = samples.partition_point(|s| s.0 < pair[1]);
+        let Some(i) =
+            (start..end).max_by(|&a, &b| samples[a].1.abs().total_cmp(&samples[b].1.abs()))
+        else {
+            continue;
+        };
+
+        if i == 0 || i + 1 >= samples.len() {
+            peaks.push(samples[i]);
+            continue;
+        }
+        let (y0, y1, y2) = (samples[i - 1].1, samples[i].1, samples[i + 1].1);
+        let curvature = y0 - 2.0 * y1 + y2;
+        if curvature == 0.0 {
+            peaks.push(samples[i]);
+            continue;
+        }
+        let offset = (0.5 * (y0 - y2) / curvature).clamp(-0.5, 0.5);
+        let spacing = 0.5 * (samples[i + 1].0 - samples[i - 1].0);
+        peaks.push((
+            samples[i].0 + offset * spacing,
+            y1 - 0.25 * (y0 - y2) * offset,
+        ));
+    }
+
+    peaks
+}
+
+/// Logarithmic decrement per full cycle, fitted by least squares to the log
+/// of the peak magnitudes, which are half a cycle apart.
+pub fn log_decrement(peaks: &[(f32, f32)]) -> Option<f32> {
+    let logs: Vec<f32> = peaks
+        .iter()
+        .filter(|peak| peak.1 != 0.0)
+        .map(|peak| peak.1.abs().ln())
+        .collect();
+    if logs.len() < 3 {
+        return None;
+    }
+
+    let n = logs.len() as f32;
+    let mean_index = (n - 1.0) / 2.0;
+    let mean_log = logs.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (i, log) in logs.iter().enumerate() {
+        let d = i as f32 - mean_index;
+        covariance += d * (log - mean_log);
+        variance += d * d;
+    }
+
+    Some(-2.0 * covariance / variance)
+}
+
+/// Damping ratio ζ from the logarithmic decrement δ.
+pub fn damping_ratio(decrement: f32) -> f32 {
+    decrement / (TAU * TAU + decrement * decrement).sqrt()
+}
+
+/// What the sensor worked out from the trace.
+#[derive(Copy, Clone)]
+pub struct Estimate {
+    /// Undamped natural frequency in Hz.
+    pub frequency: f32,
+    pub damping_ratio: Option<f32>,
+    /// Logarithmic decrement per cycle the damping ratio came from.
+    pub decrement: Option<f32>,
+    /// Mean magnitude of the swing's peaks in radians.
+    pub amplitude: f32,
+    /// Effective length implied by the frequency under the current gravity.
+    pub length: f32,
+}
+
+/// Estimates from samples of (seconds, angle), under gravity `g`.
+pub fn estimate(samples: &[(f32, f32)], g: f32) -> Option<Estimate> {
+    let swing = samples.iter().map(|s| s.1.abs()).fold(0.0, f32::max);
+    let crossings = crossings(samples, HYSTERESIS * swing);
+    let damped = frequency(&crossings)?;
+    let peaks = peaks(samples, &crossings);
+    let decrement = log_decrement(&peaks);
+    let damping_ratio = decrement.map(damping_ratio);
+    let amplitude = match peaks.len() {
+        0 => swing,
+        n => peaks.iter().map(|peak| peak.1.abs()).sum::<f32>() / n as f32,
+    };
+
+    let zeta = damping_ratio.unwrap_or(0.0).min(0.99);
+    let frequency = damped / (1.0 - zeta * zeta).sqrt();
+    let per_step = TAU * frequency / STEPS_PER_SECOND;
+
+    Some(Estimate {
+        frequency,
+        damping_ratio,
+        decrement,
+        amplitude,
+        length: g / (per_step * per_step),
+    })
+}
+
+/// Panel showing the estimates next to the true values.
+pub struct SensorPanel {
+    pub visible: bool,
+    rect: (f32, f32, f32, f32),
+    samples: VecDeque<(f32, f32)>,
+}
+
+impl SensorPanel {
+    pub fn new() -> SensorPanel {
+        let capacity = (WINDOW_SECONDS * STEPS_PER_SECOND) as usize;
+        SensorPanel {
+            visible: false,
+            rect: (470.0, 190.0, 320.0, 100.0),
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// The recorded (seconds, angle) samples, oldest first.
+    pub fn samples(&self) -> Vec<(f32, f32)> {
+        self.samples.iter().copied().collect()
+    }
+
+    pub fn record(&mut self, p: &Pendulum) {
+        if self.samples.len() == self.samples.capacity() {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((p.time / STEPS_PER_SECOND, p.angle));
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font, p: &Pendulum, units: &Units) {
+        if !self.visible {
+            return;
+        }
+
+        let (left, top, width, height) = self.rect;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.6),
+        );
+
+        let estimate = estimate(&self.samples(), p.g);
+        let row = |value: Option<f32>, truth: Option<f32>, format: &dyn Fn(f32) -> String| {
+            let error = match (value, truth) {
+                (Some(value), Some(truth)) if truth != 0.0 => {
+                    format!("{:+.1}%", 100.0 * (value - truth) / truth)
+                }
+                _ => String::new(),
+            };
+            [
+                value.map_or("-".to_string(), format),
+                truth.map_or("-".to_string(), format),
+                error,
+            ]
+        };
+
+        let rows = [
+            [
+                "Sensor".to_string(),
+                "est.".to_string(),
+                "true".to_string(),
+                "error".to_string(),
+            ],
+            prefixed(
+                "Frequency",
+                row(
+                    estimate.map(|e| e.frequency),
+                    Some(1.0 / p.natural_period()),
+                    &|f| format!("{:.3} Hz", f),
+                ),
+            ),
+            prefixed(
+                "Damping",
+                row(
+                    estimate.and_then(|e| e.damping_ratio),
+                    p.damping_ratio(),
+                    &|z| format!("{:.4}", z),
+                ),
+            ),
+            prefixed(
+                "Length",
+                row(
+                    estimate.map(|e| e.length),
+                    Some(p.effective_length()),
+                    &|l| units.length(l),
+                ),
+            ),
+        ];
+
+        let columns = [left + 6.0, left + 90.0, left + 170.0, left + 250.0];
+        for (i, row) in rows.iter().enumerate() {
+            for (text, x) in row.iter().zip(columns) {
+                let text = font.layout_text(text, 18.0, TextOptions::new());
+                graphics.draw_text((x, top + 4.0 + 24.0 * i as f32), Color::BLACK, &text);
+            }
+        }
+    }
+}
+
+fn prefixed(name: &str, [a, b, c]: [String; 3]) -> [String; 4] {
+    [name.to_string(), a, b, c]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    const HZ: f32 = 0.5;
+    const ZETA: f32 = 0.02;
+    const AMPLITUDE: f32 = 0.5;
+
+    /// 30 s of a damped swing at `HZ`, sampled at the step rate, with noise
+    /// of up to `noise` radians either way.
+    fn swing(noise: f32) -> Vec<(f32, f32)> {
+        let mut rng = Rng::new(7);
+        let natural = TAU * HZ;
+        let damped = natural * (1.0 - ZETA * ZETA).sqrt();
+        (0..(WINDOW_SECONDS * STEPS_PER_SECOND) as usize)
+            .map(|i| {
+                let t = i as f32 / STEPS_PER_SECOND;
+                let angle = AMPLITUDE * (-ZETA * natural * t).exp() * (damped * t).cos();
+                (t, angle + rng.range(-noise, noise))
+            })
+            .collect()
+    }
+
+    fn close(actual: f32, expected: f32, within: f32) -> bool {
+        (actual - expected).abs() <= within * expected.abs()
+    }
+
+    #[test]
+    fn noise_near_zero_is_one_crossing_each() {
+        let samples = swing(0.01);
+        let crossings = crossings(&samples, HYSTERESIS * AMPLITUDE);
+        // Two a cycle, the first a quarter cycle in.
+        assert_eq!(crossings.len(), 30);
+        assert!(close(crossings[0], 0.5, 0.02), "{}", crossings[0]);
+        let damped = HZ * (1.0 - ZETA * ZETA).sqrt();
+        assert!(close(frequency(&crossings).unwrap(), damped, 0.005));
+        // Without the hysteresis the noise adds crossings.
+        assert!(super::crossings(&samples, 0.0).len() > 30);
+    }
+
+    #[test]
+    fn the_peaks_and_the_decrement_come_out_of_the_noise() {
+        let samples = swing(0.002);
+        let crossings = crossings(&samples, HYSTERESIS * AMPLITUDE);
+        let peaks = peaks(&samples, &crossings);
+        assert_eq!(peaks.len(), crossings.len() - 1);
+        // Each half cycle's extreme is where the envelope says, turning
+        // back and forth.
+        let natural = TAU * HZ;
+        for pair in peaks.windows(2) {
+            assert!((pair[0].1 < 0.0) != (pair[1].1 < 0.0));
+        }
+        for &(time, value) in &peaks {
+            // Off by no more than the noise, near enough.
+            let envelope = AMPLITUDE * (-ZETA * natural * time).exp();
+            assert!(
+                (value.abs() - envelope).abs() < 0.0025,
+                "{} at {}",
+                value,
+                time
+            );
+        }
+
+        let decrement = log_decrement(&peaks).unwrap();
+        let expected = TAU * ZETA / (1.0 - ZETA * ZETA).sqrt();
+        assert!(close(decrement, expected, 0.05), "{}", decrement);
+        assert!(close(damping_ratio(decrement), ZETA, 0.05));
+        assert!(close(damping_ratio(expected), ZETA, 1e-5));
+    }
+
+    #[test]
+    fn the_estimate_recovers_the_frequency_damping_and_length() {
+        let estimate = estimate(&swing(0.002), 0.5).unwrap();
+        assert!(close(estimate.frequency, HZ, 0.005));
+        assert!(close(estimate.damping_ratio.unwrap(), ZETA, 0.05));
+        let per_step = TAU * HZ / STEPS_PER_SECOND;
+        assert!(close(estimate.length, 0.5 / (per_step * per_step), 0.01));
+
+        // A flat trace has nothing to go on.
+        assert!(super::estimate(&[(0.0, 0.0), (1.0, 0.0)], 0.5).is_none());
+    }
+}
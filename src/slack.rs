@@ -0,0 +1,191 @@
+//! A string in place of the rod. A string can pull on the bob but not push
+//! it, so when a swing would need the rod to push, over the top too slowly
+//! or thrown inwards, the bob leaves its circle and flies free under
+//! gravity until the string is stretched straight again. Then it's jerked
+//! taut: the part of the bob's velocity along the string is lost, and the
+//! swing carries on with the rest.
+//!
+//! The flight is worked in the swing's own units, pixels and steps, for the
+//! equivalent point bob at the effective length, relative to the pivot and
+//! with y down.
+
+use crate::math;
+
+/// Outward pull the string needs, per unit mass, to keep the bob on its
+/// circle: the centripetal `ω² L` less the part of `gravity` along the
+/// string. Negative where only a rod could hold it.
+pub fn tension(angle: f32, angular_velocity: f32, length: f32, gravity: f32) -> f32 {
+    length * angular_velocity * angular_velocity + gravity * math::cos(angle)
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Flight {
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+}
+
+impl Flight {
+    /// The bob leaving its circle at `angle`, moving round it at
+    /// `angular_velocity`.
+    pub fn leaving(angle: f32, angular_velocity: f32, length: f32) -> Flight {
+        let (sin, cos) = math::sin_cos(angle);
+        Flight {
+            x: length * sin,
+            y: length * cos,
+            vx: length * angular_velocity * cos,
+            vy: -length * angular_velocity * sin,
+        }
+    }
+
+    pub fn kinetic(&self) -> f32 {
+        0.5 * (self.vx * self.vx + self.vy * self.vy)
+    }
+
+    pub fn distance(&self) -> f32 {
+        math::hypot(self.x, self.y)
+    }
+
+    /// Angle from the pivot, as the swing measures it.
+    pub fn angle(&self) -> f32 {
+        math::atan2(self.x, self.y)
+    }
+
+    /// Speed across the string, the way the angle grows.
+    fn across(&self) -> f32 {
+        let (sin, cos) = math::sin_cos(self.angle());
+        self.vx * cos - self.vy * sin
+    }
+
+    /// Angular velocity about the pivot.
+    pub fn angular_velocity(&self) -> f32 {
+        self.across() / self.distance().max(f32::MIN_POSITIVE)
+    }
+
+    /// Falls for a step under `gravity`, with the velocity kept by
+    /// `damping` after the kick, and returns the kinetic energy between the
+    /// two, as the swing's step does.
+    pub fn step(&mut self, gravity: f32, damping: f32) -> f32 {
+        self.vy += gravity;
+        let kicked = self.kinetic();
+        self.vx *= damping;
+        self.vy *= damping;
+        self.x += self.vx;
+        self.y += self.vy;
+        kicked
+    }
+
+    /// The angle and angular velocity of the bob put on the circle of
+    /// radius `length`, keeping only its velocity across the string.
+    pub fn onto(&self, length: f32) -> (f32, f32) {
+        (self.angle(), self.across() / length)
+    }
+
+    /// Where the swing carries on from, if the string has come out to
+    /// `length`.
+    pub fn catch(&self, length: f32) -> Option<(f32, f32)> {
+        (self.distance() >= length).then(|| self.onto(length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pendulum::Pendulum;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn the_string_pulls_below_and_needs_speed_above() {
+        assert!((tension(0.0, 0.0, 200.0, 0.5) - 0.5).abs() < 1e-6);
+        assert!((tension(PI, 0.0, 200.0, 0.5) + 0.5).abs() < 1e-6);
+        assert!((tension(FRAC_PI_2, 0.1, 200.0, 0.5) - 2.0).abs() < 1e-5);
+        // Over the top it holds at `ω² L = g` and no slower.
+        let just = (0.5f32 / 200.0).sqrt();
+        assert!(tension(PI, 1.01 * just, 200.0, 0.5) > 0.0);
+        assert!(tension(PI, 0.99 * just, 200.0, 0.5) < 0.0);
+    }
+
+    #[test]
+    fn leaving_the_circle_and_coming_back_onto_it_keeps_the_motion() {
+        for (angle, angular_velocity) in [(0.3, 0.02), (2.0, -0.05), (-2.8, 0.01)] {
+            let flight = Flight::leaving(angle, angular_velocity, 150.0);
+            assert!((flight.distance() - 150.0).abs() < 1e-3);
+            assert!((flight.angle() - angle).abs() < 1e-5);
+            assert!((flight.angular_velocity() - angular_velocity).abs() < 1e-6);
+            let (back, velocity) = flight.onto(150.0);
+            assert!((back - angle).abs() < 1e-5 && (velocity - angular_velocity).abs() < 1e-6);
+            assert!(flight.catch(150.0 + 1.0).is_none());
+        }
+    }
+
+    #[test]
+    fn a_free_bob_falls_like_a_thrown_stone() {
+        let mut flight = Flight {
+            x: 0.0,
+            y: 0.0,
+            vx: 2.0,
+            vy: -3.0,
+        };
+        for n in 1..=20 {
+            let kicked = flight.step(0.5, 1.0);
+            assert_eq!(kicked, flight.kinetic());
+            let n = n as f32;
+            assert_eq!(flight.x, 2.0 * n);
+            assert!((flight.y - (-3.0 * n + 0.5 * 0.5 * n * (n + 1.0))).abs() < 1e-4);
+        }
+        // Damping takes the same share off both parts.
+        let (vx, vy) = (flight.vx, flight.vy + 0.5);
+        flight.step(0.5, 0.5);
+        assert!((flight.vx - 0.5 * vx).abs() < 1e-6 && (flight.vy - 0.5 * vy).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_throw_too_slow_for_the_top_goes_slack_and_then_taut_again() {
+        let throw = |string: bool| {
+            let mut p = Pendulum::new(400.0, 0.0, 200.0);
+            p.zeta = Some(0.0);
+            p.string = string;
+            p.angle = 0.0;
+            // The top needs `5 g L` of speed squared at the bottom.
+            p.angular_velocity = (3.5 * p.g / p.r).sqrt();
+            p
+        };
+
+        let mut p = throw(true);
+        let (mut left, mut caught) = (None, None);
+        for _ in 0..600 {
+            let (was_slack, angle) = (p.slack.is_some(), p.angle);
+            p.update();
+            match (was_slack, p.slack) {
+                (false, Some(_)) if left.is_none() => left = Some(angle),
+                (true, None) if caught.is_none() => caught = Some(p.angle),
+                (_, Some(flight)) => assert!(flight.distance() < p.effective_length() + 1e-3),
+                _ => (),
+            }
+            if caught.is_some() {
+                break;
+            }
+        }
+        // Above level with the pivot and short of the top.
+        let left = left.expect("the string never went slack");
+        assert!(left > FRAC_PI_2 && left < PI, "left at {}", left);
+        // With `3.5 g L` the speed squared is `g L (1.5 + 2 cos θ)` on the
+        // way up, and the tension is gone at `cos θ = -1/2`, 30 degrees
+        // above level, near enough for steps of up to a tenth of a radian.
+        assert!((left.cos() + 0.5).abs() < 0.05, "left at {}", left);
+        assert!(caught.is_some(), "the string never came taut");
+        // The speed along the string went into the snap, and the swing
+        // carries on on its circle.
+        assert!(p.ledger.snap > 0.0);
+        let (x, y) = (p.position.x - p.origin.x, p.position.y - p.origin.y);
+        assert!((x.hypot(y) - p.r).abs() < 1e-2);
+
+        // On a rod the same throw stays on the circle.
+        let mut rod = throw(false);
+        for _ in 0..600 {
+            rod.update();
+            assert!(rod.slack.is_none());
+        }
+    }
+}
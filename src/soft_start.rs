@@ -0,0 +1,69 @@
+use crate::config::{key_value, parse_f32, Section};
+use crate::scene::Scene;
+
+/// Seconds gravity takes to ease in unless `[soft_start]` says otherwise.
+const DEFAULT_DURATION: f32 = 2.0;
+
+/// Eases gravity in over a few seconds whenever a scene starts or is reset,
+/// so a pendulum set at a large angle glides into its first swing instead
+/// of snapping. Off by default.
+pub struct SoftStart {
+    pub enabled: bool,
+    duration: f32,
+}
+
+impl SoftStart {
+    pub fn new() -> SoftStart {
+        SoftStart {
+            enabled: false,
+            duration: DEFAULT_DURATION,
+        }
+    }
+
+    /// Reads `enabled = true|false` and `duration = <seconds>` from a
+    /// `[soft_start]` section.
+    pub fn from_config(section: &Section) -> Result<SoftStart, String> {
+        let mut soft_start = SoftStart::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("enabled", value)) => {
+                    soft_start.enabled = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `enabled`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                Some(("duration", value)) => {
+                    soft_start.duration = parse_f32("duration", value).map_err(error)?;
+                    if soft_start.duration <= 0.0 {
+                        return Err(error("`duration` must be positive".to_string()));
+                    }
+                }
+                _ => return Err(error(format!("unknown soft start setting `{}`", line))),
+            }
+        }
+
+        Ok(soft_start)
+    }
+
+    /// Starts the ramp on every pendulum in the scene, or ends it if soft
+    /// start is off.
+    pub fn apply(&self, scene: &mut Scene) {
+        let seconds = if self.enabled { self.duration } else { 0.0 };
+        for model in &mut scene.models {
+            if let Some(p) = model.as_pendulum_mut() {
+                p.ease_in(seconds);
+            }
+        }
+    }
+
+    /// Starts the ramp on the selected pendulum only.
+    pub fn apply_selected(&self, scene: &mut Scene) {
+        if let Some(p) = scene.selected_pendulum_mut() {
+            p.ease_in(if self.enabled { self.duration } else { 0.0 });
+        }
+    }
+}
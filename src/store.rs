@@ -0,0 +1,414 @@
+//! Results that outlive the session: game scores and completed measurements,
+//! one JSON object per line in a file in the data directory. The file is
+//! only ever appended to, from a background thread so a slow disk can't
+//! stall a frame.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::json::{field, quote, Json, Parser};
+
+/// Version written into every record. Older records are brought up to date
+/// by `migrate` when read, newer ones are skipped.
+pub const SCHEMA_VERSION: u32 = 1;
+const FILE_NAME: &str = "history.jsonl";
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Kind {
+    Game,
+    Measurement,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Game => "game",
+            Kind::Measurement => "measurement",
+        }
+    }
+}
+
+/// A finished game round or measurement.
+#[derive(Clone, PartialEq)]
+pub struct Record {
+    pub kind: Kind,
+    /// Game mode, or the quantity measured.
+    pub name: String,
+    /// Score in seconds, or the measured value.
+    pub value: f32,
+    /// Fitted damping ratio, for measurements that have one.
+    pub damping: Option<f32>,
+    /// Parameters of the pendulum at the time.
+    pub parameters: Vec<(String, f32)>,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl Record {
+    pub fn new(kind: Kind, name: &str, value: f32, parameters: &[(&str, f32)]) -> Record {
+        Record {
+            kind,
+            name: name.to_string(),
+            value,
+            damping: None,
+            parameters: parameters
+                .iter()
+                .map(|&(key, value)| (key.to_string(), value))
+                .collect(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs()),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|(key, value)| format!("{}:{}", quote(key), number(*value)))
+            .collect();
+        format!(
+            "{{\"v\":{},\"kind\":\"{}\",\"name\":{},\"value\":{},\"damping\":{},\"parameters\":{{{}}},\"timestamp\":{}}}",
+            SCHEMA_VERSION,
+            self.kind.name(),
+            quote(&self.name),
+            number(self.value),
+            self.damping.map_or("null".to_string(), number),
+            parameters.join(","),
+            self.timestamp
+        )
+    }
+
+    pub fn from_json(line: &str) -> Result<Record, String> {
+        let Json::Object(fields) = Parser::new(line).parse()? else {
+            return Err("not a JSON object".to_string());
+        };
+        let version = match field(&fields, "v") {
+            Some(Json::Number(v)) if *v >= 0.0 && v.fract() == 0.0 => *v as u32,
+            _ => return Err("missing schema version".to_string()),
+        };
+        let fields = migrate(fields, version)?;
+
+        let number = |key: &str| match field(&fields, key) {
+            Some(Json::Number(n)) => Ok(*n),
+            _ => Err(format!("missing number `{}`", key)),
+        };
+        let kind = match field(&fields, "kind") {
+            Some(Json::String(kind)) if kind == "game" => Kind::Game,
+            Some(Json::String(kind)) if kind == "measurement" => Kind::Measurement,
+            _ => return Err("missing or unknown `kind`".to_string()),
+        };
+        let Some(Json::String(name)) = field(&fields, "name") else {
+            return Err("missing string `name`".to_string());
+        };
+        let damping = match field(&fields, "damping") {
+            None | Some(Json::Null) => None,
+            Some(Json::Number(d)) => Some(*d as f32),
+            _ => return Err("`damping` is not a number".to_string()),
+        };
+        let parameters = match field(&fields, "parameters") {
+            None => Vec::new(),
+            Some(Json::Object(parameters)) => parameters
+                .iter()
+                .filter_map(|(key, value)| match value {
+                    Json::Number(n) => Some(Ok((key.clone(), *n as f32))),
+                    Json::Null => None,
+                    _ => Some(Err(format!("parameter `{}` is not a number", key))),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err("`parameters` is not an object".to_string()),
+        };
+
+        Ok(Record {
+            kind,
+            name: name.clone(),
+            value: number("value")? as f32,
+            damping,
+            parameters,
+            timestamp: number("timestamp")? as u64,
+        })
+    }
+
+    pub fn parameter(&self, key: &str) -> Option<f32> {
+        self.parameters
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// `value` as a JSON number, or `null` for the NaN and infinities JSON has
+/// no number for.
+fn number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Brings the fields of a record written with schema `version` up to the
+/// current one. There has only been one schema so far; each future version
+/// adds a step here that rewrites the fields of the one before.
+fn migrate(fields: Vec<(String, Json)>, version: u32) -> Result<Vec<(String, Json)>, String> {
+    match version {
+        SCHEMA_VERSION => Ok(fields),
+        v if v > SCHEMA_VERSION => Err(format!(
+            "written by a newer version (schema {}, this one reads up to {})",
+            v, SCHEMA_VERSION
+        )),
+        v => Err(format!("unknown schema version {}", v)),
+    }
+}
+
+/// The results recorded so far, with new ones appended to the file as they
+/// come in. Dropping it waits for the queued ones to be written.
+pub struct Store {
+    pub records: Vec<Record>,
+    writer: Option<Sender<String>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Store {
+    /// Reads the records already in `path`, skipping any that can't be read,
+    /// and starts the thread that appends new ones to it.
+    pub fn open(path: &Path) -> Store {
+        let mut records = Vec::new();
+        if let Ok(text) = fs::read_to_string(path) {
+            for (i, line) in text.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match Record::from_json(line) {
+                    Ok(record) => records.push(record),
+                    Err(e) => log!("{}: line {}: skipped record: {}", path.display(), i + 1, e),
+                }
+            }
+        }
+
+        let (writer, lines) = mpsc::channel::<String>();
+        let path = path.to_path_buf();
+        let thread = thread::spawn(move || {
+            for line in lines {
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+                    .and_then(|mut file| writeln!(file, "{}", line));
+                if let Err(e) = written {
+                    log!("could not write {}: {}", path.display(), e);
+                }
+            }
+        });
+
+        Store {
+            records,
+            writer: Some(writer),
+            thread: Some(thread),
+        }
+    }
+
+    /// Where the store lives: `$XDG_DATA_HOME/rust-pendulum`, falling back
+    /// to `~/.local/share/rust-pendulum` and then the working directory.
+    pub fn default_path() -> PathBuf {
+        let directory = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+            .map_or(PathBuf::from("."), |data| data.join("rust-pendulum"));
+        directory.join(FILE_NAME)
+    }
+
+    /// Keeps a record and queues it to be written. One whose value isn't a
+    /// number, from a run that blew up, isn't a result and is dropped.
+    pub fn add(&mut self, record: Record) {
+        if !record.value.is_finite() {
+            log!(
+                "not recording {} {}: no finite value",
+                record.kind.name(),
+                record.name
+            );
+            return;
+        }
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(record.to_json());
+        }
+        self.records.push(record);
+    }
+
+    /// Highest-scoring round of a game mode.
+    pub fn best(&self, mode: &str) -> Option<&Record> {
+        self.query(Kind::Game, mode)
+            .into_iter()
+            .max_by(|a, b| a.value.total_cmp(&b.value))
+    }
+
+    /// Every record of `kind` named `name`, oldest first.
+    pub fn query(&self, kind: Kind, name: &str) -> Vec<&Record> {
+        self.records
+            .iter()
+            .filter(|record| record.kind == kind && record.name == name)
+            .collect()
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread once it has written the rest.
+        drop(self.writer.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Formats a timestamp as a UTC date and time, `2024-05-17 09:30`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let minutes = timestamp % 86400 / 60;
+
+    // Civil date from days since the epoch, after Howard Hinnant.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("pendulum-store-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn round(score: f32, timestamp: u64) -> Record {
+        Record {
+            timestamp,
+            ..Record::new(Kind::Game, "flag", score, &[("length", 1.5)])
+        }
+    }
+
+    #[test]
+    fn records_are_queried_by_kind_and_name() {
+        let mut store = Store::open(&temporary("query"));
+        store.add(round(3.0, 10));
+        store.add(round(7.5, 20));
+        store.add(Record::new(Kind::Game, "cartpole", 9.0, &[]));
+        let mut period = Record::new(Kind::Measurement, "period", 2.01, &[("length", 1.0)]);
+        period.damping = Some(0.02);
+        store.add(period);
+
+        assert_eq!(store.query(Kind::Game, "flag").len(), 2);
+        assert_eq!(store.query(Kind::Measurement, "period").len(), 1);
+        assert!(store.query(Kind::Measurement, "flag").is_empty());
+        assert_eq!(store.best("flag").unwrap().value, 7.5);
+        assert_eq!(store.best("cartpole").unwrap().value, 9.0);
+        assert!(store.best("swing").is_none());
+    }
+
+    #[test]
+    fn appended_records_are_all_there_when_the_file_is_read_again() {
+        let path = temporary("reload");
+        let mut store = Store::open(&path);
+        for i in 0..50 {
+            store.add(round(i as f32, i));
+        }
+        let written = store.records.clone();
+        drop(store);
+
+        let reopened = Store::open(&path);
+        assert!(reopened.records == written);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn values_json_has_no_number_for_are_kept_out_of_the_file() {
+        let path = temporary("finite");
+        let mut store = Store::open(&path);
+        let mut measurement = Record::new(
+            Kind::Measurement,
+            "period",
+            2.0,
+            &[("length", 1.0), ("drift", f32::NAN)],
+        );
+        measurement.damping = Some(f32::INFINITY);
+        store.add(measurement);
+        store.add(round(f32::NAN, 5));
+        assert_eq!(store.records.len(), 1);
+        drop(store);
+
+        let reopened = Store::open(&path);
+        assert_eq!(reopened.records.len(), 1);
+        let record = &reopened.records[0];
+        assert_eq!(record.damping, None);
+        assert_eq!(record.parameters, vec![("length".to_string(), 1.0)]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn records_are_migrated_or_skipped_by_schema_version() {
+        let current = round(4.0, 30).to_json();
+        assert!(Record::from_json(&current).unwrap() == round(4.0, 30));
+
+        let newer = current.replacen(
+            &format!("\"v\":{}", SCHEMA_VERSION),
+            &format!("\"v\":{}", SCHEMA_VERSION + 1),
+            1,
+        );
+        let error = Record::from_json(&newer).err().unwrap();
+        assert!(error.contains("newer version"), "{}", error);
+        let older = current.replacen(&format!("\"v\":{}", SCHEMA_VERSION), "\"v\":0", 1);
+        assert!(Record::from_json(&older).is_err());
+        let unversioned = current.replacen(&format!("\"v\":{},", SCHEMA_VERSION), "", 1);
+        assert!(Record::from_json(&unversioned).is_err());
+
+        // The ones that can't be read are skipped, the rest still load.
+        let path = temporary("migrate");
+        fs::write(
+            &path,
+            format!(
+                "{}\n{}\nnot json\n\n{}\n",
+                current,
+                newer,
+                round(6.0, 40).to_json()
+            ),
+        )
+        .unwrap();
+        let store = Store::open(&path);
+        assert!(store.records == vec![round(4.0, 30), round(6.0, 40)]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn timestamps_read_as_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(1_715_938_200), "2024-05-17 09:30");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+
+use speedy2d::color::Color;
+
+use crate::pendulum::{Pendulum, PendulumState};
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// Copy counts `N` cycles through.
+const COPY_COUNTS: [usize; 4] = [8, 16, 32, 64];
+
+/// The moments at which a strobe copy is taken.
+#[derive(Copy, Clone, PartialEq)]
+pub enum StrobeSource {
+    /// The bob passes through the vertical.
+    ZeroCrossing,
+    /// The bob stops and turns round.
+    TurningPoint,
+    /// Each full period of the drive.
+    DrivePeriod,
+}
+
+impl StrobeSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            StrobeSource::ZeroCrossing => "zero crossings",
+            StrobeSource::TurningPoint => "turning points",
+            StrobeSource::DrivePeriod => "drive period",
+        }
+    }
+
+    /// Where in the step from `before` to `after` the event happened, as a
+    /// fraction of the step, if it happened at all.
+    fn event(self, p: &Pendulum, before: &PendulumState, after: &PendulumState) -> Option<f32> {
+        let crossing = |a: f32, b: f32| (a != b && (a < 0.0) != (b < 0.0)).then(|| a / (a - b));
+
+        match self {
+            StrobeSource::ZeroCrossing => crossing(before.angle, after.angle),
+            StrobeSource::TurningPoint => crossing(before.angular_velocity, after.angular_velocity),
+            StrobeSource::DrivePeriod => {
+                if p.drive_frequency <= 0.0 {
+                    return None;
+                }
+                let from = p.drive_phase(before.time) / TAU;
+                let to = p.drive_phase(after.time) / TAU;
+                (from.floor() != to.floor()).then(|| (to.floor() - from) / (to - from))
+            }
+        }
+    }
+}
+
+/// Rod and bob at the interpolated moment of an event.
+#[derive(Copy, Clone)]
+struct Pose {
+    origin: Vector,
+    r: f32,
+    angle: f32,
+}
+
+/// Leaves faint copies of each pendulum behind at phase-locked moments, so a
+/// decaying swing draws a shrinking fan and a driven one shows its Poincaré
+/// section in space. Purely visual: none of this is saved with the scene.
+pub struct Strobe {
+    pub source: Option<StrobeSource>,
+    copies: usize,
+    previous: Vec<Option<PendulumState>>,
+    poses: Vec<VecDeque<Pose>>,
+}
+
+impl Strobe {
+    pub fn new() -> Strobe {
+        Strobe {
+            source: None,
+            copies: COPY_COUNTS[1],
+            previous: Vec::new(),
+            poses: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.poses.clear();
+    }
+
+    /// Off, then each source in turn.
+    pub fn cycle_source(&mut self) {
+        self.source = match self.source {
+            None => Some(StrobeSource::ZeroCrossing),
+            Some(StrobeSource::ZeroCrossing) => Some(StrobeSource::TurningPoint),
+            Some(StrobeSource::TurningPoint) => Some(StrobeSource::DrivePeriod),
+            Some(StrobeSource::DrivePeriod) => None,
+        };
+        self.clear();
+    }
+
+    pub fn cycle_copies(&mut self) {
+        let next = COPY_COUNTS
+            .iter()
+            .position(|&c| c == self.copies)
+            .map_or(0, |i| i + 1);
+        self.copies = COPY_COUNTS[next % COPY_COUNTS.len()];
+        for poses in &mut self.poses {
+            while poses.len() > self.copies {
+                poses.pop_front();
+            }
+        }
+    }
+
+    pub fn copies(&self) -> usize {
+        self.copies
+    }
+
+    /// Looks for events in the step every pendulum just took.
+    pub fn observe(&mut self, scene: &Scene) {
+        let Some(source) = self.source else {
+            return;
+        };
+
+        let count = scene.models.len();
+        self.previous.resize(count, None);
+        self.poses.resize_with(count, VecDeque::new);
+
+        for (i, model) in scene.models.iter().enumerate() {
+            let Some(p) = model.as_pendulum() else {
+                continue;
+            };
+            let after = p.snapshot();
+
+            if let Some(before) = self.previous[i] {
+                if let Some(fraction) = source.event(p, &before, &after) {
+                    if self.poses[i].len() == self.copies {
+                        self.poses[i].pop_front();
+                    }
+                    self.poses[i].push_back(Pose {
+                        origin: p.origin,
+                        r: before.r + (after.r - before.r) * fraction,
+                        angle: before.angle + (after.angle - before.angle) * fraction,
+                    });
+                }
+            }
+
+            self.previous[i] = Some(after);
+        }
+    }
+
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        if self.source.is_none() {
+            return;
+        }
+
+        for poses in &self.poses {
+            for (age, pose) in poses.iter().rev().enumerate() {
+                let alpha = 0.35 * (1.0 - age as f32 / self.copies as f32);
+                let bob = view.world_to_screen(Vector::new(
+                    pose.origin.x + pose.r * pose.angle.sin(),
+                    pose.origin.y + pose.r * pose.angle.cos(),
+                ));
+
+                graphics.draw_line(
+                    view.world_to_screen(pose.origin),
+                    bob,
+                    view.length(2.0),
+                    Color::from_rgba(0.3, 0.3, 0.3, alpha),
+                );
+                graphics.draw_circle(
+                    bob,
+                    view.length(25.0),
+                    Color::from_rgba(0.4, 0.4, 0.4, alpha),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+//! The scene as a vector picture, to put in a paper: the same drawing as
+//! the window's, written as SVG shapes so lines and text stay sharp at any
+//! size. The export key writes what's on screen; a converter such as
+//! `rsvg-convert -f pdf` turns it into a PDF.
+//!
+//! Shapes shaded between their corners have no SVG equivalent and are
+//! filled with their average colour, and thick lines through many points
+//! are written as SVG polylines rather than the window's strips of quads.
+//!
+//! A `[vector_export]` section says where, and whether to include the HUD
+//! text:
+//!
+//! ```text
+//! [vector_export]
+//! file = figure.svg
+//! text = false
+//! ```
+
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::Rectangle;
+
+use crate::config::{key_value, Section};
+use crate::polyline;
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+
+const DEFAULT_FILE: &str = "scene.svg";
+const FONT_FAMILY: &str = "sans-serif";
+/// How far below the top of a line of text its baseline sits, in font
+/// sizes, as the window lays it out.
+const BASELINE: f32 = 0.8;
+
+pub struct VectorExport {
+    pub file: PathBuf,
+    /// Whether the HUD's text goes in too, or only the drawing.
+    pub text: bool,
+}
+
+impl VectorExport {
+    pub fn new() -> VectorExport {
+        VectorExport {
+            file: PathBuf::from(DEFAULT_FILE),
+            text: true,
+        }
+    }
+
+    /// Reads `file = <path>` and `text = true|false` from a
+    /// `[vector_export]` section.
+    pub fn from_config(section: &Section) -> Result<VectorExport, String> {
+        let mut export = VectorExport::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("file", value)) if !value.is_empty() => export.file = PathBuf::from(value),
+                Some(("text", value)) => {
+                    export.text = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `text`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                _ => return Err(error(format!("unknown vector_export setting `{}`", line))),
+            }
+        }
+
+        Ok(export)
+    }
+
+    /// Writes the finished picture to the file, and says where.
+    pub fn write(&self, svg: Svg) -> Result<String, String> {
+        let shapes = svg.shapes;
+        fs::write(&self.file, svg.finish())
+            .map_err(|e| format!("could not write {}: {}", self.file.display(), e))?;
+        Ok(format!(
+            "Scene written to {} ({} shapes)",
+            self.file.display(),
+            shapes
+        ))
+    }
+}
+
+/// An SVG document being drawn into, the size of the window.
+pub struct Svg {
+    width: f32,
+    height: f32,
+    body: String,
+    shapes: usize,
+}
+
+/// `color` as an SVG paint, with its opacity under `property`.
+fn paint(property: &str, color: Color) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut paint = format!(
+        "{}=\"#{:02x}{:02x}{:02x}\"",
+        property,
+        channel(color.r()),
+        channel(color.g()),
+        channel(color.b())
+    );
+    if color.a() < 1.0 {
+        write!(paint, " {}-opacity=\"{:.3}\"", property, color.a().max(0.0)).unwrap();
+    }
+    paint
+}
+
+fn average(colors: &[Color]) -> Color {
+    let n = colors.len().max(1) as f32;
+    let sum = |channel: fn(&Color) -> f32| colors.iter().map(channel).sum::<f32>() / n;
+    Color::from_rgba(sum(Color::r), sum(Color::g), sum(Color::b), sum(Color::a))
+}
+
+fn points(points: impl IntoIterator<Item = (f32, f32)>) -> String {
+    points
+        .into_iter()
+        .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Svg {
+    pub fn new(width: f32, height: f32, background: Color) -> Svg {
+        let mut svg = Svg {
+            width,
+            height,
+            body: String::new(),
+            shapes: 0,
+        };
+        svg.draw_rectangle(
+            Rectangle::from_tuples((0.0, 0.0), (width, height)),
+            background,
+        );
+        svg
+    }
+
+    fn shape(&mut self, element: String) {
+        self.body.push_str("  ");
+        self.body.push_str(&element);
+        self.body.push('\n');
+        self.shapes += 1;
+    }
+
+    /// A line of text with its top left at `position`, `size` pixels high.
+    pub fn text(&mut self, position: (f32, f32), size: f32, color: Color, text: &str) {
+        self.shape(format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"{}\" font-size=\"{:.1}\" {} xml:space=\"preserve\">{}</text>",
+            position.0,
+            position.1 + BASELINE * size,
+            FONT_FAMILY,
+            size,
+            paint("fill", color),
+            escape(text)
+        ));
+    }
+
+    pub fn finish(self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+             {body}</svg>\n",
+            w = self.width,
+            h = self.height,
+            body = self.body
+        )
+    }
+}
+
+impl Renderer for Svg {
+    fn draw_line(&mut self, start: (f32, f32), end: (f32, f32), thickness: f32, color: Color) {
+        self.shape(format!(
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke-width=\"{:.2}\" {}/>",
+            start.0,
+            start.1,
+            end.0,
+            end.1,
+            thickness,
+            paint("stroke", color)
+        ));
+    }
+
+    fn draw_circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        self.shape(format!(
+            "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>",
+            center.0,
+            center.1,
+            radius,
+            paint("fill", color)
+        ));
+    }
+
+    fn draw_rectangle(&mut self, rectangle: Rectangle, color: Color) {
+        self.shape(format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>",
+            rectangle.top_left().x,
+            rectangle.top_left().y,
+            rectangle.width(),
+            rectangle.height(),
+            paint("fill", color)
+        ));
+    }
+
+    fn draw_triangle(&mut self, corners: [Vec2; 3], color: Color) {
+        self.shape(format!(
+            "<polygon points=\"{}\" {}/>",
+            points(corners.map(|v| (v.x, v.y))),
+            paint("fill", color)
+        ));
+    }
+
+    fn draw_quad(&mut self, corners: [Vec2; 4], color: Color) {
+        self.shape(format!(
+            "<polygon points=\"{}\" {}/>",
+            points(corners.map(|v| (v.x, v.y))),
+            paint("fill", color)
+        ));
+    }
+
+    fn draw_quad_four_color(&mut self, corners: [Vec2; 4], colors: [Color; 4]) {
+        self.draw_quad(corners, average(&colors));
+    }
+
+    /// One polyline while the colour holds, and a new one wherever it
+    /// changes, each segment in the average of its ends' colours.
+    fn draw_polyline(&mut self, line: &[Vector], thickness: f32, colors: &[Color]) {
+        let mut start = 0;
+        while start + 1 < line.len() {
+            let color = average(&colors[start..start + 2]);
+            let mut end = start + 1;
+            while end + 1 < line.len() && average(&colors[end..end + 2]) == color {
+                end += 1;
+            }
+            self.shape(format!(
+                "<polyline points=\"{}\" fill=\"none\" stroke-width=\"{:.2}\" stroke-linejoin=\"miter\" stroke-miterlimit=\"{}\" {}/>",
+                points(line[start..=end].iter().map(|v| (v.x, v.y))),
+                thickness,
+                polyline::MITER_LIMIT,
+                paint("stroke", color)
+            ));
+            start = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::model::{CartEntry, ChainEntry, DoubleEntry, ModelEntry, PendulumEntry};
+    use crate::obstacles::{Obstacle, Obstacles};
+    use crate::scene::Scene;
+    use crate::viewport::Viewport;
+    use crate::walls::Walls;
+
+    fn name(text: &str) -> Result<&str, String> {
+        let valid = !text.is_empty()
+            && text
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'));
+        valid.then_some(text).ok_or(format!("bad name `{}`", text))
+    }
+
+    /// Text between tags, or inside an attribute value: no markup, and only
+    /// the predefined entities.
+    fn character_data(text: &str) -> Result<(), String> {
+        if text.contains('<') {
+            return Err(format!("unescaped `<` in `{}`", text));
+        }
+        for (i, _) in text.match_indices('&') {
+            let entity = &text[i..];
+            if !["&amp;", "&lt;", "&gt;", "&quot;", "&apos;"]
+                .iter()
+                .any(|e| entity.starts_with(e))
+            {
+                return Err(format!("bare `&` in `{}`", text));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks `document` is well-formed XML with one `svg` root, and
+    /// returns the names of the elements in it, in order.
+    fn well_formed(document: &str) -> Result<Vec<String>, String> {
+        let rest = document
+            .strip_prefix("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")
+            .ok_or("no XML declaration first")?;
+        let (mut rest, mut open, mut elements) = (rest, Vec::new(), Vec::new());
+        while let Some(start) = rest.find('<') {
+            let text = &rest[..start];
+            if open.is_empty() && !text.trim().is_empty() {
+                return Err(format!("text outside the root: `{}`", text));
+            }
+            character_data(text)?;
+            let end = start + rest[start..].find('>').ok_or("unclosed tag")?;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+
+            if let Some(closing) = tag.strip_prefix('/') {
+                match open.pop() {
+                    Some(name) if name == closing => continue,
+                    name => return Err(format!("`</{}>` closes {:?}", closing, name)),
+                }
+            }
+            if open.is_empty() && !elements.is_empty() {
+                return Err("a second root".to_string());
+            }
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let (element, mut attributes) = tag.split_once(' ').unwrap_or((tag, ""));
+            let mut names = Vec::new();
+            loop {
+                attributes = attributes.trim_start();
+                if attributes.is_empty() {
+                    break;
+                }
+                let (attribute, value) = attributes
+                    .split_once("=\"")
+                    .ok_or(format!("attribute without a quoted value in `<{}>`", tag))?;
+                let (value, after) = value
+                    .split_once('"')
+                    .ok_or(format!("unterminated value in `<{}>`", tag))?;
+                character_data(value)?;
+                if names.contains(&attribute) {
+                    return Err(format!("`{}` given twice in `<{}>`", attribute, tag));
+                }
+                names.push(name(attribute)?);
+                attributes = after;
+            }
+            elements.push(name(element)?.to_string());
+            if !empty {
+                open.push(element.to_string());
+            }
+        }
+        if !open.is_empty() {
+            return Err(format!("{:?} left open", open));
+        }
+        if !rest.trim().is_empty() || elements.first().map(String::as_str) != Some("svg") {
+            return Err("the root isn't one svg element".to_string());
+        }
+        Ok(elements)
+    }
+
+    #[test]
+    fn the_checker_catches_what_isnt_well_formed() {
+        let wrap = |body: &str| {
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg>{}</svg>\n",
+                body
+            )
+        };
+        assert!(well_formed(&wrap("<g><rect x=\"1\"/></g>")).is_ok());
+        for body in [
+            "<g>",
+            "<g></rect>",
+            "<rect x=1/>",
+            "<rect x=\"1\" x=\"2\"/>",
+            "<text>a & b</text>",
+            "</svg><svg>",
+        ] {
+            assert!(well_formed(&wrap(body)).is_err(), "passed {}", body);
+        }
+    }
+
+    #[test]
+    fn the_export_is_well_formed_svg() {
+        let scene = Scene::new(vec![
+            ModelEntry::Pendulum(PendulumEntry {
+                angle: 0.7,
+                ..PendulumEntry::default()
+            }),
+            ModelEntry::Double(DoubleEntry::default()),
+            ModelEntry::Chain(ChainEntry::default()),
+            ModelEntry::Cart(CartEntry::default()),
+        ]);
+        let view = Viewport::new(true);
+        let mut obstacles = Obstacles::new();
+        obstacles.obstacles.push(Obstacle {
+            center: Vector::new(600.0, 350.0),
+            radius: 30.0,
+        });
+        let config = Config::parse("[walls]\nleft = 100\nright = 700\n").unwrap();
+        let walls = Walls::from_config(config.section("walls").unwrap()).unwrap();
+
+        let mut svg = Svg::new(800.0, 480.0, Color::from_rgb(0.9, 0.9, 0.9));
+        for model in &scene.models {
+            model.draw(&mut svg, &view);
+        }
+        obstacles.draw(&mut svg, &view, Vector::new(0.0, 0.0));
+        walls.draw(&mut svg, &view);
+        svg.text((0.0, 0.0), 30.0, Color::BLACK, "θ < π & \"ω\" > 0");
+        let line: Vec<Vector> = (0..5).map(|i| Vector::new(10.0 * i as f32, 5.0)).collect();
+        let colors = [Color::RED, Color::RED, Color::RED, Color::BLUE, Color::BLUE];
+        svg.draw_polyline(&line, 2.0, &colors);
+        svg.draw_quad_four_color(
+            [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 1.0),
+            ],
+            [Color::from_rgba(1.0, 0.0, 0.0, 0.5); 4],
+        );
+
+        let file = std::env::temp_dir().join(format!("pendulum-scene-{}.svg", std::process::id()));
+        let export = VectorExport {
+            file: file.clone(),
+            text: true,
+        };
+        let said = export.write(svg).unwrap();
+        let document = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+
+        let elements = well_formed(&document).unwrap_or_else(|e| panic!("{}\n{}", e, document));
+        assert!(document.contains(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\" height=\"480\" viewBox=\"0 0 800 480\">"
+        ));
+        // Every shape drawn is one element under the root.
+        let shapes = elements.len() - 1;
+        assert_eq!(
+            said,
+            format!("Scene written to {} ({} shapes)", file.display(), shapes)
+        );
+        for element in ["rect", "line", "circle", "polygon", "polyline", "text"] {
+            assert!(elements.iter().any(|e| e == element), "no <{}>", element);
+        }
+        assert!(document.contains(">θ &lt; π &amp; &quot;ω&quot; &gt; 0</text>"));
+        // The line splits where its colour changes, and the halfway
+        // segment goes in the average of its ends.
+        assert_eq!(document.matches("<polyline").count(), 3);
+        assert!(document.contains("points=\"0.00,5.00 10.00,5.00 20.00,5.00\""));
+        assert!(document.contains("fill=\"#ff0000\" fill-opacity=\"0.500\""));
+    }
+
+    #[test]
+    fn reads_the_section() {
+        let config = Config::parse("[vector_export]\nfile = fig.svg\ntext = false\n").unwrap();
+        let export = VectorExport::from_config(config.section("vector_export").unwrap()).unwrap();
+        assert!(export.file == *"fig.svg" && !export.text);
+        let config = Config::parse("[vector_export]\ntext = no\n").unwrap();
+        assert_eq!(
+            VectorExport::from_config(config.section("vector_export").unwrap())
+                .err()
+                .unwrap(),
+            "line 2: expected true or false for `text`, found `no`"
+        );
+    }
+}
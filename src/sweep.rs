@@ -0,0 +1,291 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::pendulum::Pendulum;
+use crate::STEPS_PER_SECOND;
+
+const RECT: (f32, f32, f32, f32) = (380.0, 40.0, 200.0, 140.0);
+
+pub enum SweepState {
+    Idle,
+    /// The last attempt was refused because the pendulum isn't driven.
+    NotDriven,
+    Running,
+    Done,
+}
+
+/// Sweeps the drive frequency of a driven pendulum slowly up through
+/// resonance and back down, holding each frequency long enough to settle
+/// and then measuring the amplitude. A large swing's period grows with its
+/// amplitude, so near resonance the way up and the way down can settle on
+/// different branches, and the two curves part: hysteresis. Both sweeps go
+/// to CSV, and can be plotted as they come in.
+pub struct HysteresisSweep {
+    pub state: SweepState,
+    /// Range swept, as multiples of the natural frequency.
+    from: f32,
+    to: f32,
+    /// Frequencies visited each way.
+    points: usize,
+    /// Seconds held at each frequency before measuring, then measuring.
+    settle: f32,
+    measure: f32,
+    pub overlay: bool,
+    /// Natural frequency, in radians per step, when the sweep started.
+    natural: f32,
+    /// Frequency ratio and amplitude of each point measured, each way.
+    up: Vec<(f32, f32)>,
+    down: Vec<(f32, f32)>,
+    /// Steps spent at the current frequency, and the angle range seen
+    /// since it settled.
+    steps: usize,
+    range: Option<(f32, f32)>,
+}
+
+impl HysteresisSweep {
+    pub fn new() -> HysteresisSweep {
+        HysteresisSweep {
+            state: SweepState::Idle,
+            from: 0.6,
+            to: 1.4,
+            points: 24,
+            settle: 15.0,
+            measure: 5.0,
+            overlay: true,
+            natural: 0.0,
+            up: Vec::new(),
+            down: Vec::new(),
+            steps: 0,
+            range: None,
+        }
+    }
+
+    /// Reads `from` and `to` (multiples of the natural frequency),
+    /// `points`, `settle` and `measure` (seconds per point) and
+    /// `overlay = true|false` from a `[sweep]` section.
+    pub fn from_config(section: &Section) -> Result<HysteresisSweep, String> {
+        let mut sweep = HysteresisSweep::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            let positive = |key: &str, value: &str| -> Result<f32, String> {
+                let number = parse_f32(key, value).map_err(error)?;
+                if number <= 0.0 {
+                    return Err(error(format!("`{}` must be positive", key)));
+                }
+                Ok(number)
+            };
+            match key_value(line) {
+                Some(("from", value)) => sweep.from = positive("from", value)?,
+                Some(("to", value)) => sweep.to = positive("to", value)?,
+                Some(("settle", value)) => sweep.settle = positive("settle", value)?,
+                Some(("measure", value)) => sweep.measure = positive("measure", value)?,
+                Some(("points", value)) => {
+                    sweep.points = value
+                        .parse()
+                        .ok()
+                        .filter(|&points| points >= 2)
+                        .ok_or(error(format!("invalid point count `{}`", value)))?
+                }
+                Some(("overlay", value)) => {
+                    sweep.overlay = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `overlay`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                _ => return Err(error(format!("unknown sweep setting `{}`", line))),
+            }
+        }
+
+        if sweep.from >= sweep.to {
+            return Err("`from` must be below `to`".to_string());
+        }
+        Ok(sweep)
+    }
+
+    /// Frequency ratio of the `index`th point of the sweep, up then down.
+    fn ratio(&self, index: usize) -> f32 {
+        let step = (self.to - self.from) / (self.points - 1) as f32;
+        if index < self.points {
+            self.from + step * index as f32
+        } else {
+            self.to - step * (index - self.points) as f32
+        }
+    }
+
+    fn measured(&self) -> usize {
+        self.up.len() + self.down.len()
+    }
+
+    /// Starts sweeping the drive of `p` from the bottom of the range.
+    pub fn start(&mut self, p: &mut Pendulum) {
+        if p.drive_amplitude == 0.0 {
+            self.state = SweepState::NotDriven;
+            return;
+        }
+
+        self.natural = (p.g / p.effective_length()).sqrt();
+        self.up.clear();
+        self.down.clear();
+        self.steps = 0;
+        self.range = None;
+        self.state = SweepState::Running;
+        p.retune_drive(self.ratio(0) * self.natural);
+    }
+
+    pub fn cancel(&mut self) {
+        self.state = SweepState::Idle;
+        self.up.clear();
+        self.down.clear();
+    }
+
+    /// Counts the step just taken towards the current point, measuring the
+    /// swing once it has settled, and moves the drive on to the next
+    /// frequency once the point is in. Writes `csv` at the end.
+    pub fn record(&mut self, p: &mut Pendulum, csv: &Path) {
+        if !matches!(self.state, SweepState::Running) {
+            return;
+        }
+
+        self.steps += 1;
+        let settle = (self.settle * STEPS_PER_SECOND) as usize;
+        if self.steps <= settle {
+            return;
+        }
+        let (low, high) = self.range.unwrap_or((p.angle, p.angle));
+        self.range = Some((low.min(p.angle), high.max(p.angle)));
+        if self.steps < settle + (self.measure * STEPS_PER_SECOND) as usize {
+            return;
+        }
+
+        // Swinging over the top counts as the largest amplitude there is.
+        let (low, high) = self.range.take().unwrap_or_default();
+        let amplitude = (0.5 * (high - low)).min(PI);
+        let point = (self.ratio(self.measured()), amplitude);
+        if self.up.len() < self.points {
+            self.up.push(point);
+        } else {
+            self.down.push(point);
+        }
+        self.steps = 0;
+
+        if self.measured() == 2 * self.points {
+            self.finish(p, csv);
+        } else {
+            p.retune_drive(self.ratio(self.measured()) * self.natural);
+        }
+    }
+
+    fn finish(&mut self, p: &Pendulum, csv: &Path) {
+        self.state = SweepState::Done;
+
+        let mut text = format!(
+            "# drive_amplitude={} natural_frequency={} settle={} measure={}\n\
+             sweep,frequency_ratio,drive_frequency,amplitude\n",
+            p.drive_amplitude, self.natural, self.settle, self.measure
+        );
+        for (name, points) in [("up", &self.up), ("down", &self.down)] {
+            for (ratio, amplitude) in points {
+                text.push_str(&format!(
+                    "{},{},{},{}\n",
+                    name,
+                    ratio,
+                    ratio * self.natural,
+                    amplitude
+                ));
+            }
+        }
+        if let Err(e) = fs::write(csv, text) {
+            log!("could not write {}: {}", csv.display(), e);
+        }
+    }
+
+    pub fn hud_line(&self) -> Option<String> {
+        match self.state {
+            SweepState::Idle => None,
+            SweepState::NotDriven => Some("Sweep: the pendulum isn't driven".to_string()),
+            SweepState::Running => {
+                let way = if self.up.len() < self.points {
+                    "up"
+                } else {
+                    "down"
+                };
+                let settling = if self.range.is_none() {
+                    ", settling"
+                } else {
+                    ""
+                };
+                Some(format!(
+                    "Sweep {} {} / {}: drive at {:.3} x natural{}",
+                    way,
+                    self.measured() + 1,
+                    2 * self.points,
+                    self.ratio(self.measured()),
+                    settling
+                ))
+            }
+            SweepState::Done => Some(format!("Sweep: {} points exported", self.measured())),
+        }
+    }
+
+    /// Amplitude against drive frequency, the way up in red and the way
+    /// down in blue.
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font) {
+        if !self.overlay || !matches!(self.state, SweepState::Running | SweepState::Done) {
+            return;
+        }
+
+        let (left, top, width, height) = RECT;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.6),
+        );
+
+        let highest = self
+            .up
+            .iter()
+            .chain(&self.down)
+            .map(|&(_, amplitude)| amplitude)
+            .fold(0.1, f32::max);
+        let point = |(ratio, amplitude): (f32, f32)| {
+            (
+                left + width * (ratio - self.from) / (self.to - self.from),
+                top + height * (1.0 - 0.9 * amplitude / highest),
+            )
+        };
+
+        for (points, color) in [
+            (&self.up, Color::from_rgb(0.8, 0.2, 0.2)),
+            (&self.down, Color::from_rgb(0.2, 0.3, 0.8)),
+        ] {
+            for pair in points.windows(2) {
+                graphics.draw_line(point(pair[0]), point(pair[1]), 2.0, color);
+            }
+            for &p in points.iter() {
+                graphics.draw_circle(point(p), 2.5, color);
+            }
+        }
+
+        // Where resonance would be for small swings.
+        if (self.from..=self.to).contains(&1.0) {
+            let x = point((1.0, 0.0)).0;
+            graphics.draw_line((x, top), (x, top + height), 1.0, Color::GRAY);
+        }
+
+        let label = format!("amplitude up to {:.2} rad", highest);
+        graphics.draw_text(
+            (left + 4.0, top + 2.0),
+            Color::BLACK,
+            &font.layout_text(&label, 16.0, TextOptions::new()),
+        );
+    }
+}
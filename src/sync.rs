@@ -0,0 +1,197 @@
+//! A test pattern for lining up the sound and picture of a screen
+//! recording. `sync on` loads a single undamped pendulum tuned to a period
+//! of exactly a second, and on each turning point the box in the bottom
+//! right corner flashes white for one frame while the terminal bell rings,
+//! both from the same step. The box also counts frames, so stepping through
+//! a recording finds the flash frame and how far the click in the audio
+//! track is from it.
+//!
+//! The turning point falls somewhere inside the step that finds it, so the
+//! flash and the click both come a little after it; each beat logs that
+//! lateness, and the time from ringing the bell to drawing the flash. There
+//! is no audio output beyond the bell, so there's no buffer to estimate a
+//! latency from: whatever the terminal and the recorder add shows up only
+//! in the recording.
+
+use std::io::Write;
+use std::time::Instant;
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::metronome;
+use crate::model::{Model, ModelEntry, PendulumEntry};
+use crate::pendulum::{Pendulum, PendulumState};
+use crate::scene::Scene;
+use crate::STEPS_PER_SECOND;
+
+const PERIOD: f32 = 1.0;
+const AMPLITUDE: f32 = 0.5;
+/// The frame counter and flash, on screen.
+const BOX: (f32, f32, f32, f32) = (670.0, 410.0, 120.0, 60.0);
+/// Beats between the summaries in the log.
+const REPORT_EVERY: usize = 10;
+/// Rounds of correcting the length against the stepped period.
+const TRIM_ROUNDS: usize = 3;
+/// Periods the stepped period is timed over.
+const TRIM_PERIODS: f32 = 8.0;
+
+pub struct SyncPattern {
+    pub enabled: bool,
+    previous: Option<PendulumState>,
+    frame: u64,
+    /// When the bell rang for a flash not yet drawn, and how many
+    /// milliseconds after the turning point that was.
+    pending: Option<(Instant, f32)>,
+    /// Lateness and bell-to-flash time of each beat since the last summary,
+    /// in milliseconds.
+    skews: Vec<(f32, f32)>,
+}
+
+impl SyncPattern {
+    pub fn new() -> SyncPattern {
+        SyncPattern {
+            enabled: false,
+            previous: None,
+            frame: 0,
+            pending: None,
+            skews: Vec::new(),
+        }
+    }
+
+    /// The pattern's pendulum, under gravity `g`: undamped, and with the
+    /// length that gives exactly `PERIOD` at its amplitude. The exact period
+    /// is the continuous one, and the steps swing a little faster, so the
+    /// length is then trimmed until a stepped copy keeps time too.
+    pub fn scene(g: f32) -> Scene {
+        let mut p = Pendulum::from_entry(&PendulumEntry {
+            y: 60.0,
+            r: Pendulum::length_for_period(PERIOD, g),
+            angle: AMPLITUDE,
+            g,
+            damping_ratio: Some(0.0),
+            ..PendulumEntry::default()
+        });
+        let mut target = PERIOD;
+        for _ in 0..TRIM_ROUNDS {
+            if let Err(e) = metronome::tune(&mut p, target) {
+                log!("sync pattern: {}", e);
+                break;
+            }
+            let Some(stepped) = stepped_period(&p) else {
+                break;
+            };
+            target *= PERIOD / stepped;
+        }
+        Scene::new(vec![p.entry()])
+    }
+
+    pub fn clear(&mut self) {
+        self.previous = None;
+        self.frame = 0;
+        self.pending = None;
+        self.skews.clear();
+    }
+
+    /// Looks for a turning point in the step just taken, and rings the bell
+    /// for it.
+    pub fn record(&mut self, p: &Pendulum) {
+        if !self.enabled {
+            return;
+        }
+        let after = p.snapshot();
+        let Some(before) = self.previous.replace(after) else {
+            return;
+        };
+        let (a, b) = (before.angular_velocity, after.angular_velocity);
+        if a == b || (a < 0.0) == (b < 0.0) {
+            return;
+        }
+
+        let time = before.time + (after.time - before.time) * a / (a - b);
+        let late = 1000.0 * (after.time - time) / STEPS_PER_SECOND;
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(b"\x07").and_then(|()| stderr.flush());
+        self.pending = Some((Instant::now(), late));
+    }
+
+    /// The `sync` console command, once the pattern is on: a summary of the
+    /// beats since the last.
+    pub fn report(&self) -> String {
+        if self.skews.is_empty() {
+            return "No beats yet".to_string();
+        }
+        let n = self.skews.len() as f32;
+        let mean = |f: fn(&(f32, f32)) -> f32| self.skews.iter().map(f).sum::<f32>() / n;
+        let widest = |f: fn(&(f32, f32)) -> f32| self.skews.iter().map(f).fold(0.0, f32::max);
+        format!(
+            "Over {} beats: the step came {:.1} ms (at most {:.1}) after the turning point, \
+             and the flash was drawn {:.2} ms (at most {:.2}) after the bell",
+            self.skews.len(),
+            mean(|s| s.0),
+            widest(|s| s.0),
+            mean(|s| s.1),
+            widest(|s| s.1)
+        )
+    }
+
+    pub fn draw(&mut self, graphics: &mut Graphics2D, font: &Font) {
+        if !self.enabled {
+            return;
+        }
+        self.frame += 1;
+
+        let flash = self.pending.take();
+        let (background, foreground) = if flash.is_some() {
+            (Color::WHITE, Color::BLACK)
+        } else {
+            (Color::BLACK, Color::WHITE)
+        };
+        let (left, top, width, height) = BOX;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            background,
+        );
+        let label = font.layout_text(&self.frame.to_string(), 36.0, TextOptions::new());
+        graphics.draw_text(
+            (
+                left + (width - label.width()) / 2.0,
+                top + (height - label.height()) / 2.0,
+            ),
+            foreground,
+            &label,
+        );
+
+        if let Some((rang, late)) = flash {
+            let drawn = 1000.0 * rang.elapsed().as_secs_f32();
+            self.skews.push((late, drawn));
+            if self.skews.len() == REPORT_EVERY {
+                log!("sync pattern: {}", self.report());
+                self.skews.clear();
+            }
+        }
+    }
+}
+
+/// The period of `p` in seconds as stepped, from a copy's turning points.
+fn stepped_period(p: &Pendulum) -> Option<f32> {
+    let ModelEntry::Pendulum(entry) = p.entry() else {
+        return None;
+    };
+    let mut copy = Pendulum::from_entry(&entry);
+    let steps = (TRIM_PERIODS * PERIOD * STEPS_PER_SECOND) as usize;
+    let mut turns = Vec::new();
+    let mut before = copy.snapshot();
+    for after in copy.steps().take(steps) {
+        let (a, b) = (before.angular_velocity, after.angular_velocity);
+        if a != b && (a < 0.0) != (b < 0.0) {
+            turns.push(before.time + (after.time - before.time) * a / (a - b));
+        }
+        before = after;
+    }
+    let (first, last) = (turns.first()?, turns.last()?);
+    let half_periods = turns.len().checked_sub(1).filter(|&n| n > 0)?;
+    Some(2.0 * (last - first) / half_periods as f32 / STEPS_PER_SECOND)
+}
@@ -0,0 +1,411 @@
+//! Tournaments of the flag game: a run of rounds, each harder than the one
+//! before, with their flags and gravity drawn at random from a seed. Two
+//! players given the same seed play the same rounds, so their totals can be
+//! compared.
+//!
+//! `--tournament <rounds>` starts one, with `--seed <n>` to play a shared
+//! seed, or the `tournament <rounds> [seed]` console command. The game key
+//! starts each round in turn; a round only ends when the bob leaves the
+//! flags, so every player's rounds run to the end. A round scores its
+//! seconds times its multiplier, which rises with the difficulty, and when
+//! the last one ends the summary is shown and the tournament is kept in the
+//! results history under `tournament`, with its seed and the score of each
+//! round.
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rectangle;
+use speedy2d::Graphics2D;
+
+use crate::game::Difficulty;
+use crate::random::Rng;
+use crate::store::{Kind, Record};
+
+/// Most rounds a tournament can have, as many as the summary has room for.
+pub const MAX_ROUNDS: usize = 10;
+/// Seeds are kept below this, the largest integer every smaller one of
+/// which an `f32` holds exactly, as the history stores them as one.
+pub const SEED_LIMIT: u32 = 1 << 24;
+/// How far either way each parameter is drawn from its difficulty's value,
+/// as a fraction of it.
+const JITTER: f32 = 0.15;
+const SUMMARY_RECT: (f32, f32, f32, f32) = (220.0, 90.0, 360.0, 300.0);
+const ROW_HEIGHT: f32 = 20.0;
+
+/// The hardest round's schedule, which the rounds work up to from the
+/// standard game's.
+fn hardest() -> Difficulty {
+    Difficulty {
+        start_half_width: 0.35,
+        min_half_width: 0.08,
+        narrowing: 0.02,
+        drift: 0.6,
+        drift_period: 6.0,
+        start_gravity: 0.9,
+        max_gravity: 2.5,
+        gravity_ramp: 0.03,
+    }
+}
+
+/// How far round `round` of `count` is from the standard game, at `0`, to
+/// the hardest, at `1`.
+pub fn level(round: usize, count: usize) -> f32 {
+    if count <= 1 {
+        0.0
+    } else {
+        round as f32 / (count - 1) as f32
+    }
+}
+
+/// What a round's seconds are multiplied by for its score.
+pub fn multiplier(round: usize, count: usize) -> f32 {
+    1.0 + level(round, count)
+}
+
+/// The rounds of a tournament: each schedule part way from the standard
+/// game's to the hardest by its level, and every parameter moved by up to
+/// the jitter either way, all drawn from `seed`.
+pub fn rounds(seed: u32, count: usize) -> Vec<Difficulty> {
+    let mut rng = Rng::new(seed as u64);
+    let (easy, hard) = (Difficulty::standard(), hardest());
+    (0..count)
+        .map(|round| {
+            let level = level(round, count);
+            let mut pick = |easy: f32, hard: f32| {
+                (easy + (hard - easy) * level) * rng.range(1.0 - JITTER, 1.0 + JITTER)
+            };
+            Difficulty {
+                start_half_width: pick(easy.start_half_width, hard.start_half_width),
+                min_half_width: pick(easy.min_half_width, hard.min_half_width),
+                narrowing: pick(easy.narrowing, hard.narrowing),
+                drift: pick(easy.drift, hard.drift),
+                drift_period: pick(easy.drift_period, hard.drift_period),
+                start_gravity: pick(easy.start_gravity, hard.start_gravity),
+                max_gravity: pick(easy.max_gravity, hard.max_gravity),
+                gravity_ramp: pick(easy.gravity_ramp, hard.gravity_ramp),
+            }
+        })
+        .collect()
+}
+
+/// Parses a round count for `--tournament` or the console.
+pub fn parse_rounds(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(count) if (1..=MAX_ROUNDS).contains(&count) => Ok(count),
+        _ => Err(format!(
+            "expected 1 to {} rounds, found `{}`",
+            MAX_ROUNDS, text
+        )),
+    }
+}
+
+pub fn parse_seed(text: &str) -> Result<u32, String> {
+    match text.parse() {
+        Ok(seed) if seed < SEED_LIMIT => Ok(seed),
+        _ => Err(format!(
+            "expected a seed below {}, found `{}`",
+            SEED_LIMIT, text
+        )),
+    }
+}
+
+/// A seed for when none is given, from the clock.
+pub fn fresh_seed() -> u32 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    (Rng::new(nanos as u64).next_u64() % SEED_LIMIT as u64) as u32
+}
+
+pub struct Tournament {
+    pub seed: u32,
+    rounds: Vec<Difficulty>,
+    /// The seconds survived in each round played so far.
+    seconds: Vec<f32>,
+}
+
+impl Tournament {
+    pub fn new(seed: u32, count: usize) -> Tournament {
+        Tournament {
+            seed,
+            rounds: rounds(seed, count),
+            seconds: Vec::new(),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.seconds.len() == self.rounds.len()
+    }
+
+    /// The round to be played next, from `0`, and its schedule.
+    pub fn next_round(&self) -> Option<(usize, Difficulty)> {
+        let round = self.seconds.len();
+        Some((round, *self.rounds.get(round)?))
+    }
+
+    fn score(&self, round: usize) -> f32 {
+        self.seconds[round] * multiplier(round, self.rounds.len())
+    }
+
+    pub fn total(&self) -> f32 {
+        (0..self.seconds.len()).map(|round| self.score(round)).sum()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_over() {
+            return format!(
+                "Tournament of {} rounds, seed {}, over with {:.1} - G to close",
+                self.rounds.len(),
+                self.seed,
+                self.total()
+            );
+        }
+        format!(
+            "Tournament of {} rounds, seed {} - G to start round {}",
+            self.rounds.len(),
+            self.seed,
+            self.seconds.len() + 1
+        )
+    }
+
+    /// Scores the round just played, lasting `seconds`.
+    pub fn finish_round(&mut self, seconds: f32) -> String {
+        if self.is_over() {
+            return String::new();
+        }
+        self.seconds.push(seconds);
+        let round = self.seconds.len() - 1;
+        let scored = format!(
+            "Round {}: {:.1} s x {:.2} = {:.1}, total {:.1}",
+            round + 1,
+            seconds,
+            multiplier(round, self.rounds.len()),
+            self.score(round),
+            self.total()
+        );
+        if self.is_over() {
+            format!("{} - tournament over, G to close", scored)
+        } else {
+            format!("{} - G for round {}", scored, round + 2)
+        }
+    }
+
+    /// The finished tournament for the results history, with its seed and
+    /// round count and each round's score.
+    pub fn record(&self) -> Record {
+        let names: Vec<String> = (1..=self.seconds.len())
+            .map(|round| format!("round{}", round))
+            .collect();
+        let mut parameters = vec![
+            ("seed", self.seed as f32),
+            ("rounds", self.rounds.len() as f32),
+        ];
+        for (round, name) in names.iter().enumerate() {
+            parameters.push((name, self.score(round)));
+        }
+        Record::new(Kind::Game, "tournament", self.total(), &parameters)
+    }
+
+    /// Draws the standing under the game's own lines while rounds are left,
+    /// and the summary once they're all played.
+    pub fn draw(&self, graphics: &mut Graphics2D, font: &Font) {
+        if !self.is_over() {
+            graphics.draw_text(
+                (520.0, 60.0),
+                Color::BLACK,
+                &font.layout_text(
+                    &format!(
+                        "Round {} of {} (x{:.2}), total {:.1}",
+                        self.seconds.len() + 1,
+                        self.rounds.len(),
+                        multiplier(self.seconds.len(), self.rounds.len()),
+                        self.total()
+                    ),
+                    20.0,
+                    TextOptions::new(),
+                ),
+            );
+            return;
+        }
+
+        let (left, top, width, height) = SUMMARY_RECT;
+        graphics.draw_rectangle(
+            Rectangle::from_tuples((left, top), (left + width, top + height)),
+            Color::from_rgba(1.0, 1.0, 1.0, 0.9),
+        );
+        let text = |graphics: &mut Graphics2D, x: f32, row: usize, size: f32, text: &str| {
+            graphics.draw_text(
+                (left + x, top + 8.0 + ROW_HEIGHT * row as f32),
+                Color::BLACK,
+                &font.layout_text(text, size, TextOptions::new()),
+            );
+        };
+        text(graphics, 10.0, 0, 24.0, "Tournament over");
+        text(graphics, 10.0, 1, 16.0, &format!("Seed {}", self.seed));
+        for round in 0..self.rounds.len() {
+            let row = round + 2;
+            text(graphics, 10.0, row, 16.0, &format!("Round {}", round + 1));
+            text(
+                graphics,
+                100.0,
+                row,
+                16.0,
+                &format!("{:.1} s", self.seconds[round]),
+            );
+            text(
+                graphics,
+                180.0,
+                row,
+                16.0,
+                &format!("x{:.2}", multiplier(round, self.rounds.len())),
+            );
+            text(
+                graphics,
+                260.0,
+                row,
+                16.0,
+                &format!("{:.1}", self.score(round)),
+            );
+        }
+        let row = self.rounds.len() + 2;
+        text(
+            graphics,
+            10.0,
+            row,
+            20.0,
+            &format!("Total {:.1}", self.total()),
+        );
+        text(graphics, 10.0, row + 1, 16.0, "G to close");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(difficulty: &Difficulty) -> [f32; 8] {
+        [
+            difficulty.start_half_width,
+            difficulty.min_half_width,
+            difficulty.narrowing,
+            difficulty.drift,
+            difficulty.drift_period,
+            difficulty.start_gravity,
+            difficulty.max_gravity,
+            difficulty.gravity_ramp,
+        ]
+    }
+
+    #[test]
+    fn the_same_seed_plays_the_same_rounds() {
+        for seed in [0, 1, 42, SEED_LIMIT - 1] {
+            let (one, other) = (rounds(seed, MAX_ROUNDS), rounds(seed, MAX_ROUNDS));
+            assert_eq!(one.len(), MAX_ROUNDS);
+            for (a, b) in one.iter().zip(&other) {
+                assert_eq!(
+                    fields(a).map(f32::to_bits),
+                    fields(b).map(f32::to_bits),
+                    "seed {}",
+                    seed
+                );
+            }
+            // And so do two tournaments started from it.
+            let (mut a, mut b) = (Tournament::new(seed, 5), Tournament::new(seed, 5));
+            while let (Some(x), Some(y)) = (a.next_round(), b.next_round()) {
+                assert_eq!(x, y);
+                a.finish_round(10.0);
+                b.finish_round(10.0);
+            }
+            assert!(a.is_over() && b.is_over());
+        }
+        assert_ne!(rounds(1, 3), rounds(2, 3));
+    }
+
+    #[test]
+    fn rounds_work_up_to_the_hardest_within_the_jitter() {
+        let count = 4;
+        let (easy, hard) = (fields(&Difficulty::standard()), fields(&hardest()));
+        for seed in 0..50 {
+            for (round, difficulty) in rounds(seed, count).iter().enumerate() {
+                let level = level(round, count);
+                for ((value, easy), hard) in fields(difficulty).iter().zip(easy).zip(hard) {
+                    let middle = easy + (hard - easy) * level;
+                    assert!(
+                        (value / middle - 1.0).abs() <= JITTER + 1e-5,
+                        "seed {} round {}: {} from {}",
+                        seed,
+                        round,
+                        value,
+                        middle
+                    );
+                }
+            }
+        }
+        assert_eq!(level(0, 4), 0.0);
+        assert_eq!(level(3, 4), 1.0);
+        assert_eq!(level(0, 1), 0.0);
+        assert_eq!(multiplier(3, 4), 2.0);
+    }
+
+    #[test]
+    fn rounds_are_scored_and_recorded() {
+        let mut tournament = Tournament::new(7, 3);
+        assert_eq!(
+            tournament.describe(),
+            "Tournament of 3 rounds, seed 7 - G to start round 1"
+        );
+        assert_eq!(
+            tournament.finish_round(10.0),
+            "Round 1: 10.0 s x 1.00 = 10.0, total 10.0 - G for round 2"
+        );
+        assert_eq!(
+            tournament.finish_round(10.0),
+            "Round 2: 10.0 s x 1.50 = 15.0, total 25.0 - G for round 3"
+        );
+        assert_eq!(
+            tournament.finish_round(4.0),
+            "Round 3: 4.0 s x 2.00 = 8.0, total 33.0 - tournament over, G to close"
+        );
+        assert!(tournament.is_over() && tournament.next_round().is_none());
+        assert_eq!(tournament.finish_round(100.0), "");
+        assert_eq!(
+            tournament.describe(),
+            "Tournament of 3 rounds, seed 7, over with 33.0 - G to close"
+        );
+
+        let record = tournament.record();
+        assert_eq!((record.name.as_str(), record.value), ("tournament", 33.0));
+        let parameters: Vec<(&str, f32)> = record
+            .parameters
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        assert_eq!(
+            parameters,
+            [
+                ("seed", 7.0),
+                ("rounds", 3.0),
+                ("round1", 10.0),
+                ("round2", 15.0),
+                ("round3", 8.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_and_seeds_are_checked() {
+        assert_eq!(parse_rounds("3").unwrap(), 3);
+        assert_eq!(
+            parse_rounds("0").unwrap_err(),
+            "expected 1 to 10 rounds, found `0`"
+        );
+        assert!(parse_rounds("11").is_err() && parse_rounds("many").is_err());
+        assert_eq!(parse_seed("16777215").unwrap(), SEED_LIMIT - 1);
+        assert_eq!(
+            parse_seed("16777216").unwrap_err(),
+            "expected a seed below 16777216, found `16777216`"
+        );
+        assert!(parse_seed("-1").is_err());
+        assert!(fresh_seed() < SEED_LIMIT);
+    }
+}
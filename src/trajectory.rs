@@ -0,0 +1,706 @@
+//! The selected pendulum's recent swing written out as CSV, for plotting:
+//! its angle and angular velocity at every step of the last minute. The
+//! window steps at a practical accuracy, so with `refine` on the swing is
+//! worked out again before it's written, on a thread of its own while the
+//! window carries on, from the states kept of it and the parameters each
+//! step had, in double precision at a fraction of the step.
+//!
+//! The refined run follows the recorded swing wherever the equation of
+//! motion says what happened: through changes of the parameters, the motor
+//! and the drive. A grab, a collision, a slack string, dry friction, a soft
+//! start or a jump in the history is more than the equation knows, so the
+//! run starts again from the state kept after it. The CSV's `#` lines say
+//! how it was worked out, how far it came from the window's swing, and
+//! whether the swing is chaotic, found by running it a second time from a
+//! start moved by a hair: where that ends up far away, small differences
+//! grow and neither run is the motion to the last digit.
+//!
+//! A `[trajectory]` section says where, whether to refine and with how many
+//! substeps a step:
+//!
+//! ```text
+//! [trajectory]
+//! file = swing.csv
+//! refine = true
+//! substeps = 64
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::accuracy::Equation;
+use crate::config::{key_value, Section};
+use crate::job::{Job, Progress};
+use crate::pendulum::{DampingModel, Pendulum, PendulumState};
+use crate::STEPS_PER_SECOND;
+
+const DEFAULT_FILE: &str = "trajectory.csv";
+const DEFAULT_SUBSTEPS: usize = 64;
+const MAX_SUBSTEPS: usize = 4096;
+/// Substeps a refinement may take, its twin run included.
+const BUDGET: usize = 200_000_000;
+/// How far the twin run's start is moved, in radians.
+const NUDGE: f64 = 1e-9;
+/// How far apart the twin runs may end up before the swing counts as
+/// chaotic: a million times the nudge.
+const CHAOTIC_SPREAD: f64 = 1e-3;
+
+pub struct TrajectoryExport {
+    pub file: PathBuf,
+    pub refine: bool,
+    /// Refined substeps in each of the pendulum's steps.
+    pub substeps: usize,
+}
+
+impl TrajectoryExport {
+    pub fn new() -> TrajectoryExport {
+        TrajectoryExport {
+            file: PathBuf::from(DEFAULT_FILE),
+            refine: false,
+            substeps: DEFAULT_SUBSTEPS,
+        }
+    }
+
+    /// Reads `file = <path>`, `refine = true|false` and `substeps = <n>`
+    /// from a `[trajectory]` section.
+    pub fn from_config(section: &Section) -> Result<TrajectoryExport, String> {
+        let mut export = TrajectoryExport::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("file", value)) if !value.is_empty() => export.file = PathBuf::from(value),
+                Some(("refine", value)) => {
+                    export.refine = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `refine`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                Some(("substeps", value)) => {
+                    export.substeps = parse_substeps(value).map_err(error)?
+                }
+                _ => return Err(error(format!("unknown trajectory setting `{}`", line))),
+            }
+        }
+
+        Ok(export)
+    }
+}
+
+pub fn parse_substeps(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(substeps) if (1..=MAX_SUBSTEPS).contains(&substeps) => Ok(substeps),
+        _ => Err(format!(
+            "expected 1 to {} substeps, found `{}`",
+            MAX_SUBSTEPS, text
+        )),
+    }
+}
+
+/// A step as the window took it: the state after, the equation it followed
+/// and how many of the pendulum's own steps it was.
+#[derive(Clone)]
+struct Sample {
+    state: PendulumState,
+    equation: Equation,
+    rate: u32,
+    /// Whether the equation is all that happened in the step, so a refined
+    /// run can take it from the state before.
+    follows: bool,
+}
+
+/// What the refined run made of the samples.
+struct Refined {
+    angles: Vec<f64>,
+    angular_velocities: Vec<f64>,
+    /// Runs the samples were refined in, each started from a kept state.
+    segments: usize,
+    /// Furthest the twin run got from this one, in radians.
+    spread: f64,
+}
+
+/// One substep of `dt` steps of classic RK4.
+fn rk4(equation: &Equation, (angle, velocity): (f64, f64), time: f64, dt: f64) -> (f64, f64) {
+    let f = |(angle, velocity): (f64, f64), time: f64| {
+        (velocity, equation.acceleration(angle, velocity, time))
+    };
+    let k1 = f((angle, velocity), time);
+    let k2 = f(
+        (angle + 0.5 * dt * k1.0, velocity + 0.5 * dt * k1.1),
+        time + 0.5 * dt,
+    );
+    let k3 = f(
+        (angle + 0.5 * dt * k2.0, velocity + 0.5 * dt * k2.1),
+        time + 0.5 * dt,
+    );
+    let k4 = f((angle + dt * k3.0, velocity + dt * k3.1), time + dt);
+    (
+        angle + dt / 6.0 * (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0),
+        velocity + dt / 6.0 * (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1),
+    )
+}
+
+fn substeps_needed(samples: &[Sample], substeps: usize) -> usize {
+    samples
+        .iter()
+        .filter(|sample| sample.follows)
+        .map(|sample| 2 * sample.rate as usize * substeps)
+        .sum()
+}
+
+/// Works the samples out again `substeps` to a step, and a twin run from
+/// each segment's start nudged by a hair.
+fn refine(samples: &[Sample], substeps: usize, progress: &Progress) -> Result<Refined, String> {
+    let mut refined = Refined {
+        angles: Vec::with_capacity(samples.len()),
+        angular_velocities: Vec::with_capacity(samples.len()),
+        segments: 0,
+        spread: 0.0,
+    };
+    let dt = 1.0 / substeps as f64;
+    let (mut state, mut twin) = ((0.0, 0.0), (0.0, 0.0));
+
+    for (i, sample) in samples.iter().enumerate() {
+        if i == 0 || !sample.follows {
+            let kept = (
+                sample.state.angle as f64,
+                sample.state.angular_velocity as f64,
+            );
+            state = kept;
+            twin = (kept.0 + NUDGE, kept.1);
+            refined.segments += 1;
+        } else {
+            let start = samples[i - 1].state.time as f64;
+            for substep in 0..sample.rate as usize * substeps {
+                let time = start + substep as f64 * dt;
+                state = rk4(&sample.equation, state, time, dt);
+                twin = rk4(&sample.equation, twin, time, dt);
+            }
+            progress.advance(2 * sample.rate as usize * substeps)?;
+        }
+        refined.spread = refined.spread.max((twin.0 - state.0).abs());
+        refined.angles.push(state.0);
+        refined.angular_velocities.push(state.1);
+    }
+    Ok(refined)
+}
+
+/// The samples as CSV, the refined run's values in place of the window's
+/// where there is one, under `#` lines saying how they were worked out.
+fn csv(
+    label: &str,
+    samples: &[Sample],
+    integrator: &str,
+    refined: Option<(&Refined, usize)>,
+) -> String {
+    let mut text = String::new();
+    let (first, last) = (&samples[0].state, &samples[samples.len() - 1].state);
+    writeln!(
+        text,
+        "# {}: {} steps, pendulum time {:.3} s to {:.3} s",
+        label,
+        samples.len(),
+        first.time / STEPS_PER_SECOND,
+        last.time / STEPS_PER_SECOND
+    )
+    .unwrap();
+    match refined {
+        None => writeln!(
+            text,
+            "# solver: the window's, {}, one step a step",
+            integrator
+        )
+        .unwrap(),
+        Some((refined, substeps)) => {
+            let deviation = samples
+                .iter()
+                .zip(&refined.angles)
+                .map(|(sample, angle)| (angle - sample.state.angle as f64).abs())
+                .fold(0.0, f64::max);
+            writeln!(
+                text,
+                "# solver: refined, RK4 in double precision, {} substeps a step",
+                substeps
+            )
+            .unwrap();
+            writeln!(
+                text,
+                "# segments: {}, each started from a kept state",
+                refined.segments
+            )
+            .unwrap();
+            writeln!(
+                text,
+                "# deviation: {:.3e} rad at most from the window's swing ({})",
+                deviation, integrator
+            )
+            .unwrap();
+            if refined.spread > CHAOTIC_SPREAD {
+                writeln!(
+                    text,
+                    "# chaotic: yes, a start {:e} rad away ends up {:.3e} rad away",
+                    NUDGE, refined.spread
+                )
+                .unwrap();
+            } else {
+                writeln!(text, "# chaotic: no, spread {:.3e} rad", refined.spread).unwrap();
+            }
+        }
+    }
+
+    text.push_str("time,angle,angular_velocity\n");
+    for (i, sample) in samples.iter().enumerate() {
+        let (angle, velocity) = match refined {
+            Some((refined, _)) => (refined.angles[i], refined.angular_velocities[i]),
+            None => (
+                sample.state.angle as f64,
+                sample.state.angular_velocity as f64,
+            ),
+        };
+        writeln!(
+            text,
+            "{},{},{}",
+            sample.state.time / STEPS_PER_SECOND,
+            angle,
+            velocity * STEPS_PER_SECOND as f64
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// The selected pendulum's steps as they were taken, the refinement being
+/// worked out, and where to write them.
+pub struct Trajectory {
+    pub settings: TrajectoryExport,
+    samples: VecDeque<Sample>,
+    capacity: usize,
+    /// The refinement being worked out, and the CSV's file when it's done.
+    job: Option<(Job<(String, bool)>, PathBuf)>,
+}
+
+impl Trajectory {
+    pub fn new(settings: TrajectoryExport, capacity: usize) -> Trajectory {
+        Trajectory {
+            settings,
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            job: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Keeps `p`'s state after a step of the scene, `disturbed` if it was
+    /// grabbed or hit something during it.
+    pub fn record(&mut self, p: &Pendulum, disturbed: bool) {
+        let state = p.snapshot();
+        let follows = self.samples.back().is_some_and(|before| {
+            before.state.time + p.rate as f32 == state.time && before.state.slack.is_none()
+        }) && !disturbed
+            && state.slack.is_none()
+            && p.damping_model == DampingModel::Viscous
+            && p.gravity_ramp() == 1.0;
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            state,
+            equation: Equation::of(p),
+            rate: p.rate,
+            follows,
+        });
+    }
+
+    /// Writes the steps kept so far, or with `refine` on starts them being
+    /// worked out again and says so; they're written when that's done.
+    pub fn export(&mut self, p: &Pendulum, label: &str) -> Result<String, String> {
+        if self.job.is_some() {
+            return Err("Already refining a trajectory".to_string());
+        }
+        let samples: Vec<Sample> = self.samples.iter().cloned().collect();
+        if samples.is_empty() {
+            return Err("No steps kept to export yet".to_string());
+        }
+        let integrator = p.integrator.name().to_string();
+        let file = self.settings.file.clone();
+        if !self.settings.refine {
+            write(&file, &csv(label, &samples, &integrator, None))?;
+            return Ok(format!(
+                "Trajectory written to {} ({} steps)",
+                file.display(),
+                samples.len()
+            ));
+        }
+
+        let substeps = self.settings.substeps;
+        let total = substeps_needed(&samples, substeps);
+        if total > BUDGET {
+            return Err(format!(
+                "Refining would take {} substeps, over the budget of {}: lower `substeps`",
+                total, BUDGET
+            ));
+        }
+        let label = label.to_string();
+        let job = Job::spawn(total, BUDGET, move |progress| {
+            let refined = refine(&samples, substeps, progress)?;
+            Ok((
+                csv(&label, &samples, &integrator, Some((&refined, substeps))),
+                refined.spread > CHAOTIC_SPREAD,
+            ))
+        });
+        self.job = Some((job, file));
+        Ok(format!(
+            "Refining {} steps at {} substeps a step",
+            self.samples.len(),
+            substeps
+        ))
+    }
+
+    /// Asks the refinement to stop, and says whether there was one.
+    pub fn cancel(&mut self) -> bool {
+        match &self.job {
+            Some((job, _)) => {
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes the refined trajectory once it's been worked out, and says
+    /// how that went.
+    pub fn poll(&mut self) -> Option<String> {
+        let result = self.job.as_ref()?.0.poll()?;
+        let (_, file) = self.job.take()?;
+        Some(
+            match result.and_then(|(text, chaotic)| write(&file, &text).map(|_| chaotic)) {
+                Ok(chaotic) => format!(
+                    "Refined trajectory written to {}{}",
+                    file.display(),
+                    if chaotic { ", chaotic" } else { "" }
+                ),
+                Err(e) => format!("Refining stopped: {}", e),
+            },
+        )
+    }
+
+    pub fn status_line(&self) -> Option<String> {
+        let (job, _) = self.job.as_ref()?;
+        Some(format!(
+            "Refining trajectory: {:.0}% - shift+PageUp to cancel",
+            100.0 * job.fraction()
+        ))
+    }
+
+    /// The `trajectory` console command: `refine on|off`, `substeps <n>`
+    /// or `cancel`, and the settings.
+    pub fn command(&mut self, words: &[&str]) -> Result<Vec<String>, String> {
+        match words {
+            [] => {}
+            ["refine", "on"] => self.settings.refine = true,
+            ["refine", "off"] => self.settings.refine = false,
+            ["substeps", substeps] => self.settings.substeps = parse_substeps(substeps)?,
+            ["cancel"] => {
+                if !self.cancel() {
+                    return Err("Not refining a trajectory".to_string());
+                }
+                return Ok(vec!["Cancelling the refinement".to_string()]);
+            }
+            _ => {
+                return Err("Usage: trajectory [refine on|off | substeps <n> | cancel]".to_string())
+            }
+        }
+        Ok(vec![format!(
+            "Trajectory: {} steps kept, to {}, {}",
+            self.samples.len(),
+            self.settings.file.display(),
+            if self.settings.refine {
+                format!("refined at {} substeps a step", self.settings.substeps)
+            } else {
+                "as stepped".to_string()
+            }
+        )])
+    }
+}
+
+fn write(file: &Path, text: &str) -> Result<(), String> {
+    fs::write(file, text).map_err(|e| format!("could not write {}: {}", file.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    const SUBSTEPS: usize = 16;
+
+    /// `p` after `steps` steps, each one kept.
+    fn recorded(p: &mut Pendulum, steps: usize) -> Trajectory {
+        let mut trajectory = Trajectory::new(TrajectoryExport::new(), steps);
+        trajectory.record(p, false);
+        for _ in 1..steps {
+            p.update();
+            trajectory.record(p, false);
+        }
+        trajectory
+    }
+
+    fn wait<T: Send + 'static>(job: &Job<T>) -> Result<T, String> {
+        loop {
+            if let Some(result) = job.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn refined(trajectory: &Trajectory) -> Refined {
+        let samples: Vec<Sample> = trajectory.samples.iter().cloned().collect();
+        let total = substeps_needed(&samples, SUBSTEPS);
+        wait(&Job::spawn(total, BUDGET, move |progress| {
+            refine(&samples, SUBSTEPS, progress)
+        }))
+        .unwrap()
+    }
+
+    /// The driven, damped pendulum that's chaotic in the textbooks: a
+    /// quality factor of 2, driven at 1.5 times its weight at two thirds
+    /// of its natural frequency.
+    fn chaotic() -> Pendulum {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let natural = p.natural_frequency();
+        // Damped at half its natural frequency, in the step's terms.
+        p.zeta = Some(0.25 * natural / (1.0 + natural).ln());
+        p.angle = 0.2;
+        p.drive_amplitude = 1.5 * natural * natural;
+        p.drive_frequency = 2.0 / 3.0 * natural;
+        p
+    }
+
+    #[test]
+    fn a_regular_swing_is_refined_close_to_the_windows() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let mut trajectory = recorded(&mut p, 1200);
+        let refined = refined(&trajectory);
+        assert_eq!(refined.segments, 1);
+        assert_eq!(refined.angles.len(), 1200);
+        let deviation = trajectory
+            .samples
+            .iter()
+            .zip(&refined.angles)
+            .map(|(sample, angle)| (angle - sample.state.angle as f64).abs())
+            .fold(0.0, f64::max);
+        // The window's symplectic Euler step drifts a little in phase over
+        // the ten swings, but no more than a few hundredths of a radian.
+        assert!(deviation < 0.03, "{} rad from the window's", deviation);
+        assert!(refined.spread < 1e-7, "spread {}", refined.spread);
+
+        // Written out, it says so and isn't chaotic.
+        trajectory.settings.refine = true;
+        trajectory.settings.substeps = SUBSTEPS;
+        trajectory.settings.file = std::env::temp_dir().join(format!(
+            "pendulum-trajectory-regular-{}.csv",
+            std::process::id()
+        ));
+        assert_eq!(
+            trajectory.export(&p, "Pendulum").unwrap(),
+            "Refining 1200 steps at 16 substeps a step"
+        );
+        assert_eq!(
+            trajectory.export(&p, "Pendulum").unwrap_err(),
+            "Already refining a trajectory"
+        );
+        let message = loop {
+            if let Some(message) = trajectory.poll() {
+                break message;
+            }
+            assert!(trajectory
+                .status_line()
+                .unwrap()
+                .starts_with("Refining trajectory: "));
+            thread::sleep(Duration::from_millis(5));
+        };
+        let file = trajectory.settings.file.clone();
+        assert_eq!(
+            message,
+            format!("Refined trajectory written to {}", file.display())
+        );
+        let text = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert!(text.contains("# solver: refined, RK4 in double precision, 16 substeps a step"));
+        assert!(text.contains("# segments: 1, each started from a kept state"));
+        assert!(text.contains("# chaotic: no, spread "));
+        assert_eq!(
+            text.lines().filter(|line| !line.starts_with('#')).count(),
+            1 + 1200
+        );
+        assert!(trajectory.status_line().is_none());
+    }
+
+    #[test]
+    fn a_chaotic_swing_is_flagged() {
+        let mut p = chaotic();
+        assert!((p.damping_coefficient() - 0.5 * p.natural_frequency()).abs() < 1e-5);
+        // A nudge grows by about e every six seconds, so it takes a
+        // couple of minutes to come out to the spread.
+        let mut trajectory = recorded(&mut p, 7200);
+        let refined = refined(&trajectory);
+        assert!(refined.spread > CHAOTIC_SPREAD, "spread {}", refined.spread);
+
+        trajectory.settings.refine = true;
+        trajectory.settings.substeps = SUBSTEPS;
+        trajectory.settings.file = std::env::temp_dir().join(format!(
+            "pendulum-trajectory-chaotic-{}.csv",
+            std::process::id()
+        ));
+        trajectory.export(&p, "Pendulum").unwrap();
+        let message = loop {
+            if let Some(message) = trajectory.poll() {
+                break message;
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+        let file = trajectory.settings.file.clone();
+        assert!(message.ends_with(", chaotic"), "{}", message);
+        let text = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert!(text.contains("# chaotic: yes, a start 1e-9 rad away ends up "));
+
+        // Without the drive, the same pendulum settles and isn't.
+        let mut p = chaotic();
+        p.drive_amplitude = 0.0;
+        assert!(refined_spread(&mut p) < CHAOTIC_SPREAD);
+    }
+
+    fn refined_spread(p: &mut Pendulum) -> f64 {
+        refined(&recorded(p, 3600)).spread
+    }
+
+    #[test]
+    fn a_disturbance_starts_a_new_segment_from_the_state_kept() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let mut trajectory = recorded(&mut p, 300);
+        // Grabbed and moved, the run carries on from where it was left.
+        p.angle = -0.5;
+        p.update();
+        trajectory.record(&p, true);
+        for _ in 0..99 {
+            p.update();
+            trajectory.record(&p, false);
+        }
+        let samples: Vec<Sample> = trajectory.samples.iter().cloned().collect();
+        assert_eq!(samples.len(), 300);
+        assert_eq!(samples.iter().filter(|sample| !sample.follows).count(), 1);
+        assert_eq!(substeps_needed(&samples, SUBSTEPS), 2 * 299 * SUBSTEPS);
+        let refined = refined(&trajectory);
+        assert_eq!(refined.segments, 2);
+        let kept = samples.iter().position(|sample| !sample.follows).unwrap();
+        assert_eq!(refined.angles[kept], samples[kept].state.angle as f64);
+
+        // A gap in the steps, as after going back in the history, too.
+        let mut trajectory = Trajectory::new(TrajectoryExport::new(), 10);
+        trajectory.record(&p, false);
+        p.update();
+        p.update();
+        trajectory.record(&p, false);
+        assert!(!trajectory.samples[1].follows);
+    }
+
+    #[test]
+    fn unrefined_it_writes_the_windows_steps() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let mut trajectory = Trajectory::new(TrajectoryExport::new(), 100);
+        assert_eq!(
+            trajectory.export(&p, "Pendulum").unwrap_err(),
+            "No steps kept to export yet"
+        );
+        trajectory = recorded(&mut p, 100);
+        trajectory.settings.file = std::env::temp_dir().join(format!(
+            "pendulum-trajectory-plain-{}.csv",
+            std::process::id()
+        ));
+        let file = trajectory.settings.file.clone();
+        assert_eq!(
+            trajectory.export(&p, "Pendulum").unwrap(),
+            format!("Trajectory written to {} (100 steps)", file.display())
+        );
+        let text = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        let mut lines = text.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("# Pendulum: 100 steps, pendulum time "));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("# solver: the window's, "));
+        assert_eq!(lines.next(), Some("time,angle,angular_velocity"));
+        let last: Vec<f64> = lines
+            .last()
+            .unwrap()
+            .split(',')
+            .map(|field| field.parse().unwrap())
+            .collect();
+        assert_eq!(last[1], p.angle as f64);
+        assert!((last[2] - p.angular_velocity as f64 * 60.0).abs() < 1e-9);
+        // The oldest go once it's full.
+        trajectory.record(&p, false);
+        assert_eq!(trajectory.samples.len(), 100);
+        trajectory.clear();
+        assert!(trajectory.samples.is_empty());
+    }
+
+    #[test]
+    fn the_settings_are_read_and_changed() {
+        let config = crate::config::Config::parse(
+            "[trajectory]\nfile = swing.csv\nrefine = true\nsubsteps = 8\n",
+        )
+        .unwrap();
+        let export = TrajectoryExport::from_config(config.section("trajectory").unwrap()).unwrap();
+        assert!(export.file == *"swing.csv" && export.refine && export.substeps == 8);
+        let error = |text: &str| {
+            let config = crate::config::Config::parse(text).unwrap();
+            TrajectoryExport::from_config(config.section("trajectory").unwrap())
+                .err()
+                .unwrap()
+        };
+        assert_eq!(
+            error("[trajectory]\nrefine = yes\n"),
+            "line 2: expected true or false for `refine`, found `yes`"
+        );
+        assert_eq!(
+            error("[trajectory]\nsubsteps = 5000\n"),
+            "line 2: expected 1 to 4096 substeps, found `5000`"
+        );
+        assert!(error("[trajectory]\ncolour = red\n").contains("unknown trajectory setting"));
+
+        let mut trajectory = Trajectory::new(export, 10);
+        assert_eq!(
+            trajectory.command(&["refine", "off"]).unwrap(),
+            ["Trajectory: 0 steps kept, to swing.csv, as stepped"]
+        );
+        assert_eq!(
+            trajectory.command(&["substeps", "32"]).unwrap(),
+            ["Trajectory: 0 steps kept, to swing.csv, as stepped"]
+        );
+        assert_eq!(
+            trajectory.command(&["refine", "on"]).unwrap(),
+            ["Trajectory: 0 steps kept, to swing.csv, refined at 32 substeps a step"]
+        );
+        assert_eq!(
+            trajectory.command(&["cancel"]).unwrap_err(),
+            "Not refining a trajectory"
+        );
+        assert!(trajectory.command(&["substeps", "0"]).is_err());
+        assert!(trajectory.command(&["sideways"]).is_err());
+    }
+}
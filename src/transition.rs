@@ -0,0 +1,303 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::pendulum::Pendulum;
+use crate::scene::Scene;
+use crate::tween::{Easing, Tween};
+use crate::vector::Vector;
+use crate::STEPS_PER_SECOND;
+
+/// Seconds a transition takes unless `[transitions]` says otherwise.
+const DEFAULT_DURATION: f32 = 0.5;
+
+/// Where a pendulum is drawn: its pivot, rod length and angle.
+#[derive(Copy, Clone)]
+pub struct Pose {
+    origin: Vector,
+    r: f32,
+    angle: f32,
+}
+
+impl Pose {
+    pub fn of(p: &Pendulum) -> Pose {
+        Pose {
+            origin: p.origin,
+            r: p.r,
+            angle: p.angle,
+        }
+    }
+}
+
+/// Animates pendulums into place when they're reset or a preset is loaded,
+/// instead of having them jump there. On by default.
+pub struct Transitions {
+    pub enabled: bool,
+    duration: f32,
+    easing: Easing,
+}
+
+impl Transitions {
+    pub fn new() -> Transitions {
+        Transitions {
+            enabled: true,
+            duration: DEFAULT_DURATION,
+            easing: Easing::Cubic,
+        }
+    }
+
+    /// Reads `enabled = true|false`, `duration = <seconds>` and
+    /// `easing = linear|smoothstep|cubic` from a `[transitions]` section.
+    pub fn from_config(section: &Section) -> Result<Transitions, String> {
+        let mut transitions = Transitions::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("enabled", value)) => {
+                    transitions.enabled = value.parse().map_err(|_| {
+                        error(format!(
+                            "expected true or false for `enabled`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                Some(("duration", value)) => {
+                    transitions.duration = parse_f32("duration", value).map_err(error)?;
+                    if transitions.duration <= 0.0 {
+                        return Err(error("`duration` must be positive".to_string()));
+                    }
+                }
+                Some(("easing", value)) => {
+                    transitions.easing = Easing::parse(value).ok_or_else(|| {
+                        error(format!(
+                            "expected linear, smoothstep or cubic for `easing`, found `{}`",
+                            value
+                        ))
+                    })?
+                }
+                _ => return Err(error(format!("unknown transition setting `{}`", line))),
+            }
+        }
+
+        Ok(transitions)
+    }
+
+    /// Moves each pendulum in `scene` back to where the one in the same place
+    /// in `old` was, to glide from there. Pendulums with nothing to come from
+    /// are left where they are.
+    pub fn between(&self, old: &Scene, scene: &mut Scene) -> Option<Transition> {
+        let poses = scene
+            .models
+            .iter()
+            .enumerate()
+            .filter_map(|(index, _)| {
+                let p = old.models.get(index)?.as_pendulum()?;
+                Some((index, Pose::of(p)))
+            })
+            .collect();
+        self.start(poses, scene)
+    }
+
+    /// Moves the selected pendulum, just reset, back to `old` to glide from
+    /// there.
+    pub fn reset(&self, old: Pose, scene: &mut Scene) -> Option<Transition> {
+        self.start(vec![(scene.selected, old)], scene)
+    }
+
+    fn start(&self, poses: Vec<(usize, Pose)>, scene: &mut Scene) -> Option<Transition> {
+        if !self.enabled {
+            return None;
+        }
+        let frames = (self.duration * STEPS_PER_SECOND).round() as usize;
+        let moves: Vec<Move> = poses
+            .into_iter()
+            .filter_map(|(model, from)| {
+                let p = scene.models[model].as_pendulum_mut()?;
+                let to = Pose::of(p);
+                // The shortest way round, so a pendulum that has been
+                // spinning doesn't unwind all its turns.
+                let turn = (to.angle - from.angle + PI).rem_euclid(TAU) - PI;
+                let glide = Move {
+                    model,
+                    origin: Tween::new(from.origin, to.origin, frames, self.easing),
+                    r: Tween::new(from.r, to.r, frames, self.easing),
+                    angle: Tween::new(to.angle - turn, to.angle, frames, self.easing),
+                    velocity: p.angular_velocity,
+                };
+                glide.place(p, glide.pose());
+                Some(glide)
+            })
+            .collect();
+        (!moves.is_empty()).then_some(Transition { moves })
+    }
+}
+
+/// One pendulum's way from its old pose to its new one.
+struct Move {
+    model: usize,
+    origin: Tween<Vector>,
+    r: Tween<f32>,
+    angle: Tween<f32>,
+    /// The angular velocity it starts swinging with once in place.
+    velocity: f32,
+}
+
+impl Move {
+    fn pose(&self) -> Pose {
+        Pose {
+            origin: self.origin.value(),
+            r: self.r.value(),
+            angle: self.angle.value(),
+        }
+    }
+
+    /// Puts `p` at `pose`, held still until the move is over.
+    fn place(&self, p: &mut Pendulum, pose: Pose) {
+        let done = self.angle.is_done();
+        let mut state = p.snapshot();
+        state.r = pose.r;
+        state.angle = pose.angle;
+        state.angular_velocity = if done { self.velocity } else { 0.0 };
+        state.angular_acceleration = 0.0;
+        p.origin = pose.origin;
+        p.restore(&state);
+    }
+}
+
+/// Pendulums gliding into place. Their physics waits until it's over.
+pub struct Transition {
+    moves: Vec<Move>,
+}
+
+impl Transition {
+    /// Moves every pendulum on a frame. Returns true once they're all in
+    /// place and swinging.
+    pub fn step(&mut self, scene: &mut Scene) -> bool {
+        for glide in &mut self.moves {
+            glide.origin.step();
+            glide.r.step();
+            glide.angle.step();
+            if let Some(p) = scene.models[glide.model].as_pendulum_mut() {
+                glide.place(p, glide.pose());
+            }
+        }
+        self.moves.iter().all(|glide| glide.angle.is_done())
+    }
+
+    /// Stops where things are for a grab: the pivots and rods jump to where
+    /// they were going, and the bobs stay put at rest for the mouse to move.
+    pub fn cancel(mut self, scene: &mut Scene) {
+        for glide in &mut self.moves {
+            if let Some(p) = scene.models[glide.model].as_pendulum_mut() {
+                let angle = p.angle;
+                glide.velocity = 0.0;
+                glide.origin.finish();
+                glide.r.finish();
+                glide.angle.finish();
+                glide.place(
+                    p,
+                    Pose {
+                        angle,
+                        ..glide.pose()
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    fn scene(x: f32, r: f32, angle: f32, angular_velocity: f32) -> Scene {
+        let mut p = Pendulum::new(x, 0.0, r);
+        p.angle = angle;
+        p.angular_velocity = angular_velocity;
+        Scene::new(vec![p.entry()])
+    }
+
+    fn pendulum(scene: &Scene) -> &Pendulum {
+        scene.models[0].as_pendulum().unwrap()
+    }
+
+    /// Half a second of frames, the default duration.
+    const FRAMES: usize = 30;
+
+    #[test]
+    fn a_transition_glides_from_the_old_pose_and_then_swings() {
+        let old = scene(300.0, 150.0, -0.5, 0.0);
+        let mut new = scene(400.0, 200.0, 1.0, 0.02);
+        let mut transition = Transitions::new().between(&old, &mut new).unwrap();
+
+        // It starts where the old one was, held still.
+        let p = pendulum(&new);
+        assert_eq!((p.origin.x, p.r, p.angular_velocity), (300.0, 150.0, 0.0));
+        assert!((p.angle + 0.5).abs() < 1e-6);
+
+        let mut angles = Vec::new();
+        for frame in 1..=FRAMES {
+            let done = transition.step(&mut new);
+            assert_eq!(done, frame == FRAMES);
+            angles.push(pendulum(&new).angle);
+            if !done {
+                assert_eq!(pendulum(&new).angular_velocity, 0.0);
+            }
+        }
+        // Along the default cubic easing.
+        for (frame, angle) in angles.iter().enumerate() {
+            let t = (frame + 1) as f32 / FRAMES as f32;
+            assert!((angle - (-0.5 + 1.5 * Easing::Cubic.apply(t))).abs() < 1e-5);
+        }
+
+        let p = pendulum(&new);
+        assert_eq!((p.origin.x, p.r, p.angle), (400.0, 200.0, 1.0));
+        assert_eq!(p.angular_velocity, 0.02);
+    }
+
+    #[test]
+    fn it_takes_the_short_way_round() {
+        let old = scene(400.0, 200.0, 1.0 + 4.0 * TAU, 0.0);
+        let mut new = scene(400.0, 200.0, 1.2, 0.0);
+        let mut transition = Transitions::new().between(&old, &mut new).unwrap();
+        assert!((pendulum(&new).angle - 1.0).abs() < 1e-4);
+        for _ in 0..FRAMES {
+            transition.step(&mut new);
+            assert!(pendulum(&new).angle > 1.0 - 1e-4 && pendulum(&new).angle <= 1.2);
+        }
+    }
+
+    #[test]
+    fn cancelling_finishes_the_pivot_and_rod_and_leaves_the_bob_still() {
+        let old = scene(300.0, 150.0, -0.5, 0.0);
+        let mut new = scene(400.0, 200.0, 1.0, 0.02);
+        let mut transition = Transitions::new().between(&old, &mut new).unwrap();
+        for _ in 0..FRAMES / 2 {
+            transition.step(&mut new);
+        }
+        let halfway = pendulum(&new).angle;
+        assert!(halfway > -0.5 && halfway < 1.0);
+
+        transition.cancel(&mut new);
+        let p = pendulum(&new);
+        assert_eq!((p.origin.x, p.r), (400.0, 200.0));
+        assert_eq!((p.angle, p.angular_velocity), (halfway, 0.0));
+    }
+
+    #[test]
+    fn turned_off_or_with_nothing_to_come_from_there_is_no_transition() {
+        let old = scene(300.0, 150.0, -0.5, 0.0);
+        let mut new = scene(400.0, 200.0, 1.0, 0.0);
+        let off = Transitions {
+            enabled: false,
+            ..Transitions::new()
+        };
+        assert!(off.between(&old, &mut new).is_none());
+        assert_eq!(pendulum(&new).angle, 1.0);
+
+        let mut empty = Scene::single();
+        empty.models.clear();
+        assert!(Transitions::new().between(&empty, &mut new).is_none());
+    }
+}
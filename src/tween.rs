@@ -0,0 +1,185 @@
+//! A small tweening utility: a value moving from one setting to another over
+//! a fixed number of frames along an easing curve. Anything that animates a
+//! change instead of jumping to it can use one.
+
+use crate::vector::Vector;
+
+/// How progress through a tween, from 0 to 1, maps to how far the value has
+/// got, also from 0 to 1.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    /// `3t² − 2t³`: starts and ends at rest.
+    Smoothstep,
+    /// Cubic ease in and out, gentler at the ends than `Smoothstep`.
+    Cubic,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "smoothstep" => Some(Easing::Smoothstep),
+            "cubic" => Some(Easing::Cubic),
+            _ => None,
+        }
+    }
+
+    /// Eased progress at `t`, which is clamped to the curve's ends.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Easing::Cubic if t < 0.5 => 4.0 * t * t * t,
+            Easing::Cubic => 1.0 - 0.5 * (2.0 - 2.0 * t).powi(3),
+        }
+    }
+}
+
+/// Values a tween can move between.
+pub trait Lerp: Copy {
+    /// The value `t` of the way from `self` to `to`.
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: f32, t: f32) -> f32 {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Vector {
+    fn lerp(self, to: Vector, t: f32) -> Vector {
+        Vector::new(self.x.lerp(to.x, t), self.y.lerp(to.y, t))
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Tween<T: Lerp> {
+    pub from: T,
+    pub to: T,
+    /// Frames the tween lasts, and has run for.
+    frames: usize,
+    elapsed: usize,
+    easing: Easing,
+}
+
+impl<T: Lerp> Tween<T> {
+    /// A tween over `frames` frames. One of no frames is done from the start.
+    pub fn new(from: T, to: T, frames: usize, easing: Easing) -> Tween<T> {
+        Tween {
+            from,
+            to,
+            frames,
+            elapsed: 0,
+            easing,
+        }
+    }
+
+    pub fn value(&self) -> T {
+        if self.is_done() {
+            return self.to;
+        }
+        let t = self.elapsed as f32 / self.frames as f32;
+        self.from.lerp(self.to, self.easing.apply(t))
+    }
+
+    /// Moves on a frame and returns the value there.
+    pub fn step(&mut self) -> T {
+        self.elapsed = (self.elapsed + 1).min(self.frames);
+        self.value()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.frames
+    }
+
+    /// Jumps to the end.
+    pub fn finish(&mut self) -> T {
+        self.elapsed = self.frames;
+        self.to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 3] = [Easing::Linear, Easing::Smoothstep, Easing::Cubic];
+
+    #[test]
+    fn every_easing_runs_from_start_to_end_through_the_middle() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-6);
+            assert_eq!(easing.apply(-1.0), 0.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+
+            let mut previous = 0.0;
+            for i in 1..=100 {
+                let t = i as f32 / 100.0;
+                let eased = easing.apply(t);
+                assert!(eased >= previous, "goes back at {}", t);
+                // Symmetric about the middle, easing out as it eased in.
+                assert!((eased + easing.apply(1.0 - t) - 1.0).abs() < 1e-6);
+                previous = eased;
+            }
+        }
+    }
+
+    #[test]
+    fn the_curves_start_and_end_at_rest_and_cubic_more_gently() {
+        let slope = |easing: Easing, t: f32| (easing.apply(t + 1e-3) - easing.apply(t)) / 1e-3;
+        assert!((slope(Easing::Linear, 0.0) - 1.0).abs() < 1e-3);
+        for easing in [Easing::Smoothstep, Easing::Cubic] {
+            assert!(slope(easing, 0.0) < 1e-2);
+            assert!(slope(easing, 1.0 - 1e-3) < 1e-2);
+        }
+        // Further behind near the start, and steeper through the middle.
+        assert!(Easing::Cubic.apply(0.2) < Easing::Smoothstep.apply(0.2));
+        assert!(slope(Easing::Cubic, 0.5) > slope(Easing::Smoothstep, 0.5));
+        // The two halves of the cubic meet.
+        assert!((Easing::Cubic.apply(0.4999) - Easing::Cubic.apply(0.5001)).abs() < 1e-3);
+        assert!((Easing::Smoothstep.apply(0.25) - 0.15625).abs() < 1e-6);
+        assert!((Easing::Cubic.apply(0.25) - 0.0625).abs() < 1e-6);
+    }
+
+    #[test]
+    fn easing_names_parse() {
+        for (name, easing) in ["linear", "smoothstep", "cubic"].into_iter().zip(EASINGS) {
+            assert!(Easing::parse(name) == Some(easing));
+        }
+        assert!(Easing::parse("bounce").is_none());
+    }
+
+    #[test]
+    fn a_tween_steps_along_its_curve_and_stops_at_the_end() {
+        let mut tween = Tween::new(10.0, 20.0, 4, Easing::Linear);
+        assert_eq!(tween.value(), 10.0);
+        assert_eq!(
+            [tween.step(), tween.step(), tween.step()],
+            [12.5, 15.0, 17.5]
+        );
+        assert!(!tween.is_done());
+        assert_eq!(tween.step(), 20.0);
+        assert!(tween.is_done());
+        assert_eq!(tween.step(), 20.0);
+
+        let mut tween = Tween::new(
+            Vector::new(0.0, 0.0),
+            Vector::new(4.0, -8.0),
+            2,
+            Easing::Cubic,
+        );
+        let middle = tween.step();
+        assert_eq!((middle.x, middle.y), (2.0, -4.0));
+        let end = tween.finish();
+        assert!(tween.is_done() && (end.x, end.y) == (4.0, -8.0));
+
+        let instant = Tween::new(1.0, 3.0, 0, Easing::Smoothstep);
+        assert!(instant.is_done());
+        assert_eq!(instant.value(), 3.0);
+    }
+}
@@ -0,0 +1,261 @@
+//! Real lengths for the scene's pixels, for working next to a real
+//! pendulum. Holding the calibrate key and dragging lays a bar over
+//! something of known size on screen, and typing its real length, `30 cm`
+//! say, sets the scale. From then on lengths are shown in metres or
+//! centimetres and a scale bar is drawn in the corner. Only what's shown
+//! changes: the models still step in pixels, so calibrating never moves
+//! anything.
+//!
+//! The scale is kept with the session settings as `pixels_per_metre`.
+
+use speedy2d::color::Color;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::Graphics2D;
+
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+/// Shorter bars are taken as slips of the mouse, and would give a scale
+/// too coarse to trust.
+const MIN_BAR: f32 = 20.0;
+/// Rough on-screen length of the scale bar, which is rounded to a length
+/// that reads well.
+const SCALE_BAR: f32 = 120.0;
+/// Right end of the scale bar, on screen.
+const SCALE_BAR_END: (f32, f32) = (790.0, 30.0);
+
+/// What a length in pixels is shown as.
+#[derive(Copy, Clone, PartialEq, Default)]
+pub struct Units {
+    pixels_per_metre: Option<f32>,
+}
+
+impl Units {
+    /// `pixels` on screen measured as `metres` in the lab.
+    pub fn calibrated(pixels: f32, metres: f32) -> Result<Units, String> {
+        if pixels < MIN_BAR {
+            return Err(format!("The bar must be at least {} pixels long", MIN_BAR));
+        }
+        if !(metres.is_finite() && metres > 0.0) {
+            return Err("The real length must be more than nothing".to_string());
+        }
+        Units::per_metre(pixels / metres)
+    }
+
+    pub fn per_metre(pixels_per_metre: f32) -> Result<Units, String> {
+        if !(pixels_per_metre.is_finite() && pixels_per_metre > 0.0) {
+            return Err(format!(
+                "pixels per metre must be positive, found `{}`",
+                pixels_per_metre
+            ));
+        }
+        Ok(Units {
+            pixels_per_metre: Some(pixels_per_metre),
+        })
+    }
+
+    pub fn pixels_per_metre(&self) -> Option<f32> {
+        self.pixels_per_metre
+    }
+
+    /// `pixels` in metres, if there's a scale.
+    pub fn metres(&self, pixels: f32) -> Option<f32> {
+        self.pixels_per_metre.map(|scale| pixels / scale)
+    }
+
+    /// `pixels` as it's shown: in metres or centimetres once calibrated,
+    /// and as the bare number of pixels until then.
+    pub fn length(&self, pixels: f32) -> String {
+        match self.metres(pixels) {
+            Some(metres) => format_metres(metres),
+            None => format!("{:.0}", pixels),
+        }
+    }
+
+    /// A bar of a round length in the top right corner, labelled with it.
+    pub fn draw_scale_bar(&self, graphics: &mut Graphics2D, font: &Font, view: &Viewport) {
+        let Some(scale) = self.pixels_per_metre else {
+            return;
+        };
+        let on_screen = view.length(scale);
+        let metres = round_down(SCALE_BAR / on_screen);
+        let width = metres * on_screen;
+
+        let (right, y) = SCALE_BAR_END;
+        let left = right - width;
+        graphics.draw_line((left, y), (right, y), 3.0, Color::BLACK);
+        for x in [left, right] {
+            graphics.draw_line((x, y - 6.0), (x, y + 1.5), 2.0, Color::BLACK);
+        }
+        let label = font.layout_text(&format_metres(metres), 16.0, TextOptions::new());
+        graphics.draw_text(
+            (left + (width - label.width()) / 2.0, y - 22.0),
+            Color::BLACK,
+            &label,
+        );
+    }
+}
+
+/// Reads a real length with its unit, `30 cm`, `0.3 m` or `300 mm`,
+/// as metres.
+pub fn parse_length(text: &str) -> Result<f32, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (number, unit) = (text[..split].trim(), text[split..].trim());
+    let value: f32 = number
+        .parse()
+        .map_err(|_| format!("Not a length: `{}`, try `30 cm`", text))?;
+    let per_metre = match unit {
+        "m" => 1.0,
+        "cm" => 100.0,
+        "mm" => 1000.0,
+        "" => return Err(format!("Give `{}` a unit: m, cm or mm", number)),
+        _ => return Err(format!("Unknown unit `{}`: use m, cm or mm", unit)),
+    };
+    Ok(value / per_metre)
+}
+
+fn format_metres(metres: f32) -> String {
+    if metres.abs() >= 1.0 {
+        format!("{:.2} m", metres)
+    } else {
+        format!("{:.1} cm", 100.0 * metres)
+    }
+}
+
+/// The largest of 1, 2 or 5 times a power of ten no more than `x`.
+fn round_down(x: f32) -> f32 {
+    let power = 10f32.powf(x.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * power)
+        .find(|&length| length <= x)
+        .unwrap_or(power)
+}
+
+/// The bar dragged out to calibrate against, and then held while its real
+/// length is typed.
+pub struct Calibration {
+    bar: Option<(Vector, Vector)>,
+    dragging: bool,
+}
+
+impl Calibration {
+    pub fn new() -> Calibration {
+        Calibration {
+            bar: None,
+            dragging: false,
+        }
+    }
+
+    pub fn start(&mut self, point: Vector) {
+        self.bar = Some((point, point));
+        self.dragging = true;
+    }
+
+    pub fn drag_to(&mut self, point: Vector) {
+        if let Some((_, end)) = self.bar.as_mut().filter(|_| self.dragging) {
+            *end = point;
+        }
+    }
+
+    /// Stops dragging the bar. Returns whether it was being dragged.
+    pub fn finish(&mut self) -> bool {
+        std::mem::take(&mut self.dragging)
+    }
+
+    /// The bar's length in pixels, if there is one.
+    pub fn length(&self) -> Option<f32> {
+        self.bar
+            .map(|(start, end)| (end.x - start.x).hypot(end.y - start.y))
+    }
+
+    pub fn clear(&mut self) {
+        self.bar = None;
+        self.dragging = false;
+    }
+
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let Some((start, end)) = self.bar else {
+            return;
+        };
+        let color = Color::from_rgb(0.85, 0.3, 0.1);
+        graphics.draw_line(
+            view.world_to_screen(start),
+            view.world_to_screen(end),
+            3.0,
+            color,
+        );
+        for point in [start, end] {
+            graphics.draw_circle(view.world_to_screen(point), 4.0, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+    use crate::pendulum::Pendulum;
+
+    #[test]
+    fn lengths_read_with_their_units() {
+        assert_eq!(parse_length("30 cm"), Ok(0.3));
+        assert_eq!(parse_length(" 0.3m "), Ok(0.3));
+        assert_eq!(parse_length("300 mm"), Ok(0.3));
+        assert_eq!(parse_length("2 m"), Ok(2.0));
+        assert!(parse_length("30").unwrap_err().contains("unit"));
+        assert!(parse_length("30 in")
+            .unwrap_err()
+            .contains("Unknown unit `in`"));
+        assert!(parse_length("cm").is_err());
+    }
+
+    #[test]
+    fn a_dragged_bar_and_its_real_length_set_the_scale() {
+        let mut bar = Calibration::new();
+        bar.start(Vector::new(100.0, 100.0));
+        bar.drag_to(Vector::new(160.0, 180.0));
+        assert!(bar.finish() && !bar.finish());
+        // Once let go the bar stays put.
+        bar.drag_to(Vector::new(500.0, 500.0));
+        assert_eq!(bar.length(), Some(100.0));
+
+        let units = Units::calibrated(bar.length().unwrap(), parse_length("25 cm").unwrap());
+        assert_eq!(units.unwrap().pixels_per_metre(), Some(400.0));
+        assert!(Units::calibrated(10.0, 0.25).is_err());
+        assert!(Units::calibrated(100.0, 0.0).is_err());
+        assert!(Units::per_metre(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn a_calibration_changes_how_lengths_are_shown_everywhere() {
+        let p = Pendulum::new(400.0, 0.0, 200.0);
+        let uncalibrated = Units::default();
+        assert_eq!(uncalibrated.length(200.0), "200");
+        assert_eq!(uncalibrated.metres(200.0), None);
+        assert_eq!(p.label(&uncalibrated), "Pendulum, r = 200");
+
+        let units = Units::per_metre(400.0).unwrap();
+        assert_eq!(units.metres(200.0), Some(0.5));
+        assert_eq!(units.length(200.0), "50.0 cm");
+        assert_eq!(units.length(1000.0), "2.50 m");
+        assert_eq!(p.label(&units), "Pendulum, r = 50.0 cm");
+
+        // Recalibrated, the same pixels read differently.
+        let units = Units::per_metre(100.0).unwrap();
+        assert_eq!(p.label(&units), "Pendulum, r = 2.00 m");
+    }
+
+    #[test]
+    fn the_scale_bar_is_a_round_length() {
+        assert_eq!(round_down(0.37), 0.2);
+        assert_eq!(round_down(7.0), 5.0);
+        assert_eq!(round_down(1.0), 1.0);
+        assert!((round_down(0.012) - 0.01).abs() < 1e-6);
+        assert_eq!(format_metres(0.2), "20.0 cm");
+    }
+}
@@ -0,0 +1,249 @@
+use speedy2d::color::Color;
+use speedy2d::shape::Rectangle;
+
+use crate::config::{key_value, Section};
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+
+/// Size of the world the scenes are laid out in, which is also the initial
+/// window size.
+pub const WORLD_SIZE: (f32, f32) = (800.0, 480.0);
+
+/// Maps world coordinates to window pixels with the same scale on both axes,
+/// so circles stay round and angles are drawn true whatever the window
+/// shape. Letterboxed, the world is fitted into the window and the unused
+/// strips are covered with bars; otherwise it's drawn one pixel per unit from
+/// the top left corner. On top of that a camera can move and zoom, showing
+/// the world round `centre` at `zoom` times the size in the same space.
+#[derive(Copy, Clone)]
+pub struct Viewport {
+    pub letterbox: bool,
+    pub scale: f32,
+    pub offset: (f32, f32),
+    pub centre: Vector,
+    pub zoom: f32,
+    /// How opaque models are drawn, for fading whole scenes in and out.
+    pub opacity: f32,
+    /// Colours each pendulum's bob by how far through its swing it is.
+    pub rhythm: bool,
+    window: (f32, f32),
+    /// Scale and offset that fit the world into the window, before the
+    /// camera.
+    fit: (f32, (f32, f32)),
+}
+
+impl Viewport {
+    pub fn new(letterbox: bool) -> Viewport {
+        let mut viewport = Viewport {
+            letterbox,
+            scale: 1.0,
+            offset: (0.0, 0.0),
+            centre: home(),
+            zoom: 1.0,
+            opacity: 1.0,
+            rhythm: false,
+            window: WORLD_SIZE,
+            fit: (1.0, (0.0, 0.0)),
+        };
+        viewport.resize(WORLD_SIZE);
+        viewport
+    }
+
+    /// Reads `letterbox = true|false` from a `[window]` config section.
+    pub fn letterbox_from_config(section: &Section) -> Result<bool, String> {
+        let mut letterbox = None;
+        for (line_number, line) in &section.lines {
+            match key_value(line) {
+                Some(("letterbox", value)) => {
+                    letterbox = Some(value.parse::<bool>().map_err(|_| {
+                        format!(
+                            "line {}: expected true or false for `letterbox`, found `{}`",
+                            line_number, value
+                        )
+                    })?)
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}: unknown window setting `{}`",
+                        line_number, line
+                    ))
+                }
+            }
+        }
+        letterbox.ok_or("the [window] section does not set `letterbox`".to_string())
+    }
+
+    pub fn resize(&mut self, window: (f32, f32)) {
+        self.window = window;
+
+        self.fit = if self.letterbox {
+            let scale = (window.0 / WORLD_SIZE.0).min(window.1 / WORLD_SIZE.1);
+            let offset = (
+                0.5 * (window.0 - WORLD_SIZE.0 * scale),
+                0.5 * (window.1 - WORLD_SIZE.1 * scale),
+            );
+            (scale, offset)
+        } else {
+            (1.0, (0.0, 0.0))
+        };
+        self.set_camera(self.centre, self.zoom);
+    }
+
+    /// Points the camera at `centre`, showing the world `zoom` times the
+    /// size.
+    pub fn set_camera(&mut self, centre: Vector, zoom: f32) {
+        self.centre = centre;
+        self.zoom = zoom;
+        let (scale, offset) = self.fit;
+        self.scale = scale * zoom;
+        self.offset = (
+            offset.0 + scale * (0.5 * WORLD_SIZE.0 - zoom * centre.x),
+            offset.1 + scale * (0.5 * WORLD_SIZE.1 - zoom * centre.y),
+        );
+    }
+
+    /// Top left and bottom right corners of what the camera frames, the part
+    /// of the world shown where the whole world is without it.
+    pub fn framed_world(&self) -> (Vector, Vector) {
+        let half = (
+            0.5 * WORLD_SIZE.0 / self.zoom,
+            0.5 * WORLD_SIZE.1 / self.zoom,
+        );
+        (
+            Vector::new(self.centre.x - half.0, self.centre.y - half.1),
+            Vector::new(self.centre.x + half.0, self.centre.y + half.1),
+        )
+    }
+
+    pub fn world_to_screen(&self, point: Vector) -> (f32, f32) {
+        (
+            self.offset.0 + self.scale * point.x,
+            self.offset.1 + self.scale * point.y,
+        )
+    }
+
+    pub fn screen_to_world(&self, x: f32, y: f32) -> Vector {
+        Vector::new(
+            (x - self.offset.0) / self.scale,
+            (y - self.offset.1) / self.scale,
+        )
+    }
+
+    /// Top left and bottom right corners of the window in world
+    /// coordinates, which reach past the world when the window is bigger.
+    pub fn visible_world(&self) -> (Vector, Vector) {
+        (
+            self.screen_to_world(0.0, 0.0),
+            self.screen_to_world(self.window.0, self.window.1),
+        )
+    }
+
+    /// A world length, such as a radius or line thickness, in pixels.
+    pub fn length(&self, length: f32) -> f32 {
+        self.scale * length
+    }
+
+    /// A view showing the world `half` units round `centre` in the
+    /// `(left, top, width, height)` part of the window, for drawing a
+    /// model in an inset.
+    pub fn inset(&self, centre: Vector, half: f32, rect: (f32, f32, f32, f32)) -> Viewport {
+        let (left, top, width, height) = rect;
+        let scale = width.min(height) / (2.0 * half);
+        Viewport {
+            scale,
+            offset: (
+                left + 0.5 * width - scale * centre.x,
+                top + 0.5 * height - scale * centre.y,
+            ),
+            ..*self
+        }
+    }
+
+    /// The same view with models drawn at `opacity`.
+    pub fn faded(&self, opacity: f32) -> Viewport {
+        Viewport { opacity, ..*self }
+    }
+
+    /// `color` at the view's opacity.
+    pub fn tint(&self, color: Color) -> Color {
+        Color::from_rgba(color.r(), color.g(), color.b(), color.a() * self.opacity)
+    }
+
+    /// Covers the parts of the window outside the world.
+    pub fn draw_letterbox(&self, graphics: &mut dyn Renderer) {
+        if !self.letterbox {
+            return;
+        }
+
+        let (x, y) = self.fit.1;
+        let (width, height) = self.window;
+        let color = Color::from_rgb(0.2, 0.2, 0.25);
+
+        if x > 0.0 {
+            graphics.draw_rectangle(Rectangle::from_tuples((0.0, 0.0), (x, height)), color);
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((width - x, 0.0), (width, height)),
+                color,
+            );
+        }
+        if y > 0.0 {
+            graphics.draw_rectangle(Rectangle::from_tuples((0.0, 0.0), (width, y)), color);
+            graphics.draw_rectangle(
+                Rectangle::from_tuples((0.0, height - y), (width, height)),
+                color,
+            );
+        }
+    }
+}
+
+/// Where the camera points to show the world as it's laid out.
+pub fn home() -> Vector {
+    Vector::new(0.5 * WORLD_SIZE.0, 0.5 * WORLD_SIZE.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_unit_circle_stays_a_circle_in_a_window_of_another_shape() {
+        for letterbox in [true, false] {
+            for window in [(1000.0, 300.0), (300.0, 900.0)] {
+                let mut viewport = Viewport::new(letterbox);
+                viewport.resize(window);
+                viewport.set_camera(Vector::new(300.0, 200.0), 1.5);
+
+                let centre = Vector::new(350.0, 220.0);
+                let (cx, cy) = viewport.world_to_screen(centre);
+                for i in 0..16 {
+                    let angle = i as f32 * std::f32::consts::TAU / 16.0;
+                    let point = Vector::new(centre.x + angle.cos(), centre.y + angle.sin());
+                    let (x, y) = viewport.world_to_screen(point);
+                    let radius = (x - cx).hypot(y - cy);
+                    assert!(
+                        (radius - viewport.length(1.0)).abs() < 1e-3,
+                        "radius {} at {} in {:?}, letterbox {}",
+                        radius,
+                        angle,
+                        window,
+                        letterbox
+                    );
+
+                    let back = viewport.screen_to_world(x, y);
+                    assert!((back.x - point.x).abs() < 1e-3 && (back.y - point.y).abs() < 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn letterboxing_fits_the_whole_world_in_and_centres_it() {
+        let mut viewport = Viewport::new(true);
+        viewport.resize((1000.0, 300.0));
+        let (left, top) = viewport.world_to_screen(Vector::new(0.0, 0.0));
+        let (right, bottom) = viewport.world_to_screen(Vector::new(WORLD_SIZE.0, WORLD_SIZE.1));
+        assert_eq!((top, bottom), (0.0, 300.0));
+        assert!((left - (1000.0 - right)).abs() < 1e-3);
+        assert!(((right - left) / (bottom - top) - WORLD_SIZE.0 / WORLD_SIZE.1).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,266 @@
+//! A pair of upright walls, one each side of the scene, for the bobs to
+//! bounce off. A long rod between them can't swing out as far as it would,
+//! and the bounces make its motion chaotic while keeping it in bounds.
+//! The wall takes back the restitution's share of the bob's speed into it;
+//! then, as off an obstacle, the bob carries on round its circle with what
+//! is left across the rod, and stays on it.
+//!
+//! A config or saved scene with a `[walls]` section starts with them up:
+//!
+//! ```text
+//! [walls]
+//! left = 240
+//! right = 560
+//! restitution = 0.9
+//! ```
+
+use speedy2d::color::Color;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::obstacles::reflect;
+use crate::pendulum::{Pendulum, BOB_RADIUS};
+use crate::renderer::Renderer;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+
+const DEFAULT_WALLS: (f32, f32) = (240.0, 560.0);
+const DEFAULT_RESTITUTION: f32 = 0.9;
+
+pub struct Walls {
+    pub enabled: bool,
+    left: f32,
+    right: f32,
+    restitution: f32,
+}
+
+impl Walls {
+    pub fn new() -> Walls {
+        Walls {
+            enabled: false,
+            left: DEFAULT_WALLS.0,
+            right: DEFAULT_WALLS.1,
+            restitution: DEFAULT_RESTITUTION,
+        }
+    }
+
+    pub fn from_config(section: &Section) -> Result<Walls, String> {
+        let mut walls = Walls::new();
+        walls.enabled = true;
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            match key_value(line) {
+                Some(("left", value)) => walls.left = parse_f32("left", value).map_err(error)?,
+                Some(("right", value)) => walls.right = parse_f32("right", value).map_err(error)?,
+                Some(("restitution", value)) => {
+                    walls.restitution = parse_f32("restitution", value).map_err(error)?;
+                    if !(0.0..=1.0).contains(&walls.restitution) {
+                        return Err(error("`restitution` must be from 0 to 1".to_string()));
+                    }
+                }
+                _ => return Err(error(format!("unknown wall setting `{}`", line))),
+            }
+        }
+
+        if walls.right - walls.left < 2.0 * BOB_RADIUS {
+            return Err("the walls must be a bob's width apart, left of right".to_string());
+        }
+        Ok(walls)
+    }
+
+    /// Where the walls stand across the scene, while they're up.
+    pub fn span(&self) -> Option<(f32, f32)> {
+        self.enabled.then_some((self.left, self.right))
+    }
+
+    /// The walls as a `[walls]` section, or nothing while they're down.
+    pub fn to_section(&self) -> String {
+        if !self.enabled {
+            return String::new();
+        }
+        format!(
+            "[walls]\nleft = {}\nright = {}\nrestitution = {}\n",
+            self.left, self.right, self.restitution
+        )
+    }
+
+    /// Bounces `p` off the first wall its bob ran into on the way from
+    /// `before`, the bob's position at the start of the step. Returns
+    /// whether it hit one.
+    pub fn collide(&self, p: &mut Pendulum, before: Vector) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let after = p.position;
+
+        // Where the bob's centre comes up against each wall, and the wall's
+        // normal into the open; `start` and `end` are how far short of it
+        // the bob is at either end of the step.
+        let hits = [
+            (self.left + BOB_RADIUS, 1.0),
+            (self.right - BOB_RADIUS, -1.0),
+        ]
+        .into_iter()
+        .filter_map(|(limit, normal)| {
+            let (start, end) = (normal * (before.x - limit), normal * (after.x - limit));
+            if end >= 0.0 {
+                None
+            } else if start <= 0.0 {
+                // A bob already past the wall hits it only if it's going
+                // further, so it can always get back.
+                (end < start).then_some((0.0, limit, normal))
+            } else {
+                Some((start / (start - end), limit, normal))
+            }
+        });
+        let Some((t, limit, normal)) = hits.min_by(|a, b| a.0.total_cmp(&b.0)) else {
+            return false;
+        };
+
+        let mut contact = Vector::new(
+            before.x + t * (after.x - before.x),
+            before.y + t * (after.y - before.y),
+        );
+        // On the rod the bob went round an arc, not the chord, and meets
+        // the wall where its circle does, on the side the chord met it.
+        let reach = p.r * p.r - (limit - p.origin.x).powi(2);
+        if p.slack.is_none() && reach >= 0.0 {
+            let below = (contact.y - p.origin.y).signum();
+            contact = Vector::new(limit, p.origin.y + below * reach.sqrt());
+        }
+        let velocity = Vector::new(after.x - before.x, after.y - before.y);
+        let bounced = reflect(velocity, Vector::new(normal, 0.0), self.restitution);
+        p.deflect(contact, bounced);
+        true
+    }
+
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        if !self.enabled {
+            return;
+        }
+        // Top to bottom of the window, wherever the camera is.
+        let (top, bottom) = view.visible_world();
+        for x in [self.left, self.right] {
+            graphics.draw_line(
+                view.world_to_screen(Vector::new(x, top.y)),
+                view.world_to_screen(Vector::new(x, bottom.y)),
+                view.length(4.0),
+                view.tint(Color::DARK_GRAY),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn up() -> Walls {
+        Walls {
+            enabled: true,
+            ..Walls::new()
+        }
+    }
+
+    /// Steps `p` between `walls` for `steps` steps, checking it stays
+    /// between them, and returns how many times it bounced.
+    fn bouncing(walls: &Walls, p: &mut Pendulum, steps: usize) -> usize {
+        let (left, right) = (DEFAULT_WALLS.0 + BOB_RADIUS, DEFAULT_WALLS.1 - BOB_RADIUS);
+        let mut bounces = 0;
+        for step in 0..steps {
+            let before = p.position;
+            p.update();
+            if walls.collide(p, before) {
+                bounces += 1;
+            }
+            assert!(
+                p.position.x >= left - 1e-2 && p.position.x <= right + 1e-2,
+                "out at {} on step {}",
+                p.position.x,
+                step
+            );
+        }
+        bounces
+    }
+
+    #[test]
+    fn a_wide_swing_bounces_off_the_walls_and_stays_between_them() {
+        // Free, it would swing out to x = 568, past the right wall. Each
+        // bounce takes much of the swing, so it soon swings clear.
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        assert!(bouncing(&up(), &mut p, 3000) > 1);
+
+        // However fast it's going round.
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.angular_velocity = 0.3;
+        assert!(bouncing(&up(), &mut p, 3000) > 1);
+
+        // A slack string is kept in too.
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.string = true;
+        p.angle = 2.5;
+        assert!(bouncing(&up(), &mut p, 3000) > 0);
+    }
+
+    #[test]
+    fn a_bounce_keeps_the_restitution_of_the_speed() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        p.zeta = Some(0.0);
+        p.angle = 0.0;
+        p.angular_velocity = 0.08;
+        let walls = up();
+        loop {
+            let before = p.position;
+            p.update();
+            let velocity = p.angular_velocity;
+            if walls.collide(&mut p, before) {
+                // Moving round the circle at `θ`, `cos θ` of the speed is
+                // into the wall and comes back at the restitution, and the
+                // rest carries on. Only what's across the rod is kept.
+                let (sin, cos) = p.angle.sin_cos();
+                let expected = DEFAULT_RESTITUTION * cos * cos - sin * sin;
+                let kept = -p.angular_velocity / velocity;
+                assert!(
+                    (kept - expected).abs() < 0.02,
+                    "{} against {}",
+                    kept,
+                    expected
+                );
+                let (x, y) = (p.position.x - p.origin.x, p.position.y - p.origin.y);
+                assert!((x.hypot(y) - p.r).abs() < 1e-2);
+                break;
+            }
+            assert!(p.time < 600.0, "the bob never reached the wall");
+        }
+    }
+
+    #[test]
+    fn walls_that_are_down_let_the_bob_through() {
+        let mut p = Pendulum::new(400.0, 0.0, 200.0);
+        let walls = Walls::new();
+        let mut furthest: f32 = 0.0;
+        for _ in 0..200 {
+            let before = p.position;
+            p.update();
+            assert!(!walls.collide(&mut p, before));
+            furthest = furthest.max(p.position.x);
+        }
+        assert!(furthest > DEFAULT_WALLS.1);
+        assert!(walls.span().is_none() && walls.to_section().is_empty());
+    }
+
+    #[test]
+    fn walls_save_and_load() {
+        let load =
+            |text: &str| Walls::from_config(Config::parse(text).unwrap().section("walls").unwrap());
+        let walls = load("[walls]\nleft = 100\nright = 300\nrestitution = 0.5\n").unwrap();
+        assert_eq!(walls.span(), Some((100.0, 300.0)));
+        assert_eq!(
+            load(&walls.to_section()).unwrap().to_section(),
+            walls.to_section()
+        );
+        assert!(load("[walls]\nleft = 300\nright = 100\n").is_err());
+        assert!(load("[walls]\nrestitution = 2\n").is_err());
+    }
+}
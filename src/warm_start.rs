@@ -0,0 +1,776 @@
+//! Taking over from a real pendulum. `--warm-start <track.csv>` reads the
+//! bob's tracked position, a line of `t, x, y` per video frame in seconds
+//! and world units with `y` down as in an image, and starts the scene as
+//! a single pendulum carrying on from where the track ends.
+//!
+//! The pivot and the length come from a least-squares circle through the
+//! track's last second. Gravity and damping are measured from the whole
+//! track's swing about that pivot the way the sensor measures the
+//! simulation's, if there's enough of it, and the angle and angular
+//! velocity at its end are fitted to the angles of its last 0.4 seconds,
+//! which smooths the tracking noise out of the derivative. The track
+//! stays drawn behind the pendulum, fading back into its past, and the fit
+//! is reported with its RMS residuals, to show whether it was a pendulum's
+//! track at all.
+//!
+//! A `[warm_start]` section sets the spans fitted over, and whether gravity
+//! and damping are fitted or left as they are for a new pendulum:
+//!
+//! ```text
+//! [warm_start]
+//! window = 1
+//! smoothing = 0.4
+//! gravity = fit
+//! damping = keep
+//! ```
+
+use std::f64::consts::TAU;
+use std::fs;
+use std::path::Path;
+
+use speedy2d::color::Color;
+
+use crate::config::{key_value, parse_f32, Section};
+use crate::integrator::Scheme;
+use crate::model::PendulumEntry;
+use crate::pendulum::Pendulum;
+use crate::polyline;
+use crate::presets;
+use crate::renderer::Renderer;
+use crate::sensor;
+use crate::units::Units;
+use crate::vector::Vector;
+use crate::viewport::Viewport;
+use crate::STEPS_PER_SECOND;
+
+const DEFAULT_WINDOW: f32 = 1.0;
+const DEFAULT_SMOOTHING: f32 = 0.4;
+/// Geometric refinements of the circle after the algebraic first guess.
+const REFINEMENTS: usize = 20;
+/// RMS residuals, as fractions of the length, under which the track is
+/// taken for a pendulum's, and over which it's taken for something else.
+const GOOD_FIT: f32 = 0.02;
+const POOR_FIT: f32 = 0.05;
+const TRAIL: Color = Color::from_rgb(0.2, 0.3, 0.7);
+
+/// Tracked bob positions, as (seconds, position), in time order.
+pub struct Track {
+    pub points: Vec<(f32, Vector)>,
+}
+
+impl Track {
+    /// Reads `t, x, y` lines, skipping blank ones, `#` comments and a
+    /// header line.
+    pub fn parse(text: &str) -> Result<Track, String> {
+        let mut points: Vec<(f32, Vector)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let numbers: Result<Vec<f32>, _> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty())
+                .map(str::parse)
+                .collect();
+            let numbers = match numbers {
+                Ok(numbers) => numbers,
+                Err(_) if points.is_empty() => continue,
+                Err(_) => return Err(format!("line {}: expected `t, x, y`", line_number)),
+            };
+            let [t, x, y] = numbers[..] else {
+                return Err(format!(
+                    "line {}: expected three numbers, found {}",
+                    line_number,
+                    numbers.len()
+                ));
+            };
+            if points.last().is_some_and(|&(last, _)| t <= last) {
+                return Err(format!(
+                    "line {}: time {} doesn't come after the line before",
+                    line_number, t
+                ));
+            }
+            points.push((t, Vector::new(x, y)));
+        }
+        if points.len() < 5 {
+            return Err(format!(
+                "only {} points, too few to fit a swing to",
+                points.len()
+            ));
+        }
+        Ok(Track { points })
+    }
+
+    pub fn load(path: &Path) -> Result<Track, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Track::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn end(&self) -> f32 {
+        self.points.last().map_or(0.0, |&(t, _)| t)
+    }
+
+    /// The points from the last `seconds`.
+    fn last(&self, seconds: f32) -> &[(f32, Vector)] {
+        let start = self
+            .points
+            .partition_point(|&(t, _)| t < self.end() - seconds);
+        &self.points[start..]
+    }
+
+    fn duration(&self) -> f32 {
+        self.end() - self.points.first().map_or(0.0, |&(t, _)| t)
+    }
+}
+
+/// Solves `a x = b` by elimination with partial pivoting, unless `a` is
+/// too near singular for the answer to mean anything.
+fn solve3(mut a: [[f64; 3]; 3], mut b: [f64; 3]) -> Option<[f64; 3]> {
+    let scale = a.iter().flatten().fold(0.0, |m: f64, x| m.max(x.abs()));
+    for column in 0..3 {
+        let pivot =
+            (column..3).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() <= 1e-12 * scale {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        for row in column + 1..3 {
+            let upper = a[column];
+            let factor = a[row][column] / upper[column];
+            for (k, value) in a[row].iter_mut().enumerate().skip(column) {
+                *value -= factor * upper[k];
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = [0.0; 3];
+    for row in (0..3).rev() {
+        let known: f64 = (row + 1..3).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - known) / a[row][row];
+    }
+    Some(x)
+}
+
+/// A circle fitted to points, and the RMS of their distances from it.
+#[derive(Copy, Clone)]
+pub struct Circle {
+    pub centre: Vector,
+    pub radius: f32,
+    pub rms: f32,
+}
+
+/// The circle closest to `points` by least squares of their distances from
+/// it: Kåsa's algebraic fit to start, which has a closed form but shrinks
+/// the circle under noise on a short arc, then Gauss-Newton on the
+/// distances themselves. None if the points are too few or too near a
+/// straight line to tell a centre.
+pub fn fit_circle(points: &[Vector]) -> Option<Circle> {
+    if points.len() < 3 {
+        return None;
+    }
+    // Worked about the mean, which keeps the sums well conditioned.
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.x as f64).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.y as f64).sum::<f64>() / n;
+    let centred: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| (p.x as f64 - mean_x, p.y as f64 - mean_y))
+        .collect();
+
+    // u² + v² = 2au + 2bv + c, linear in a, b and c.
+    let (mut m, mut rhs) = ([[0.0; 3]; 3], [0.0; 3]);
+    for &(u, v) in &centred {
+        let row = [u, v, 1.0];
+        let z = u * u + v * v;
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] += row[i] * row[j];
+            }
+            rhs[i] += row[i] * z;
+        }
+    }
+    let [two_a, two_b, c] = solve3(m, rhs)?;
+    let (mut a, mut b) = (0.5 * two_a, 0.5 * two_b);
+    let mut r = (c + a * a + b * b).max(0.0).sqrt();
+
+    for _ in 0..REFINEMENTS {
+        let (mut jtj, mut jtr) = ([[0.0; 3]; 3], [0.0; 3]);
+        for &(u, v) in &centred {
+            let d = (u - a).hypot(v - b).max(f64::MIN_POSITIVE);
+            let row = [-(u - a) / d, -(v - b) / d, -1.0];
+            let residual = d - r;
+            for i in 0..3 {
+                for j in 0..3 {
+                    jtj[i][j] += row[i] * row[j];
+                }
+                jtr[i] -= row[i] * residual;
+            }
+        }
+        let Some([da, db, dr]) = solve3(jtj, jtr) else {
+            break;
+        };
+        (a, b, r) = (a + da, b + db, r + dr);
+        if da.abs().max(db.abs()).max(dr.abs()) <= 1e-9 * r.abs() {
+            break;
+        }
+    }
+
+    let square_sum: f64 = centred
+        .iter()
+        .map(|&(u, v)| ((u - a).hypot(v - b) - r).powi(2))
+        .sum();
+    (r.is_finite() && r > 0.0).then(|| Circle {
+        centre: Vector::new((mean_x + a) as f32, (mean_y + b) as f32),
+        radius: r as f32,
+        rms: (square_sum / n).sqrt() as f32,
+    })
+}
+
+/// Each point's angle about `pivot` as (seconds, angle), 0 hanging straight
+/// down as the pendulum's is, unwrapped so going round keeps counting.
+pub fn angles(points: &[(f32, Vector)], pivot: Vector) -> Vec<(f32, f32)> {
+    let mut angles: Vec<(f32, f32)> = Vec::with_capacity(points.len());
+    for &(t, point) in points {
+        let mut angle = ((point.x - pivot.x) as f64).atan2((point.y - pivot.y) as f64);
+        if let Some(&(_, last)) = angles.last() {
+            angle += TAU * ((last as f64 - angle) / TAU).round();
+        }
+        angles.push((t, angle as f32));
+    }
+    angles
+}
+
+/// The angle and angular velocity of a swing at the last of `samples`
+/// (seconds, angle), fitted by least squares to the last `span` seconds,
+/// under `k`, the square of the small-angle angular frequency, and viscous
+/// damping `c`, both per second. Differencing the raw samples would
+/// amplify the tracking noise; fitting averages it out. Only the angle and
+/// the rate are free: the swing's acceleration and the two derivatives
+/// after it follow from them through the equation of motion,
+/// θ'' = -k sin θ - c θ', so the fit can reach further back, over more of
+/// the noise, without a polynomial's bending off the swing. Also the RMS of
+/// the samples about the fit.
+pub fn end_state(samples: &[(f32, f32)], span: f32, k: f32, c: f32) -> Option<(f32, f32, f32)> {
+    const ROUNDS: usize = 6;
+    let &(end, last) = samples.last()?;
+    let start = samples.partition_point(|&(t, _)| t < end - span);
+    let window: Vec<(f64, f64)> = samples[start..]
+        .iter()
+        .map(|&(t, angle)| ((t - end) as f64, angle as f64))
+        .collect();
+    if window.len() < 3 {
+        return None;
+    }
+    let (k, c) = (k as f64, c as f64);
+    // The Taylor terms beyond the rate at the end, for an angle and rate
+    // there.
+    let higher = |angle: f64, rate: f64, tau: f64| {
+        let (sin, cos) = angle.sin_cos();
+        let acceleration = -k * sin - c * rate;
+        let jerk = -k * cos * rate - c * acceleration;
+        let snap = k * (sin * rate * rate - cos * acceleration) - c * jerk;
+        tau * tau * (acceleration / 2.0 + tau * (jerk / 6.0 + tau * snap / 24.0))
+    };
+
+    let (mut angle, mut rate) = (last as f64, 0.0);
+    for _ in 0..ROUNDS {
+        // The rest is a straight line in τ, fitted in closed form.
+        let n = window.len() as f64;
+        let mean_tau = window.iter().map(|&(tau, _)| tau).sum::<f64>() / n;
+        let residuals: Vec<f64> = window
+            .iter()
+            .map(|&(tau, theta)| theta - higher(angle, rate, tau))
+            .collect();
+        let mean = residuals.iter().sum::<f64>() / n;
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for (&(tau, _), residual) in window.iter().zip(&residuals) {
+            covariance += (tau - mean_tau) * (residual - mean);
+            variance += (tau - mean_tau).powi(2);
+        }
+        if variance <= 0.0 {
+            return None;
+        }
+        rate = covariance / variance;
+        angle = mean - rate * mean_tau;
+    }
+
+    let square_sum: f64 = window
+        .iter()
+        .map(|&(tau, theta)| (theta - angle - rate * tau - higher(angle, rate, tau)).powi(2))
+        .sum();
+    Some((
+        angle as f32,
+        rate as f32,
+        (square_sum / window.len() as f64).sqrt() as f32,
+    ))
+}
+
+/// The pendulum fitted to a track, and how well it fitted.
+pub struct Fit {
+    pub entry: PendulumEntry,
+    pub circle: Circle,
+    /// Points the circle went through.
+    pub circle_points: usize,
+    /// RMS of the angles about the smoothing quadratic, in radians.
+    pub angle_rms: f32,
+    /// Angular velocity at the end in radians per second.
+    pub angular_velocity: f32,
+    /// What came of fitting gravity and damping.
+    pub gravity: Measured,
+    pub damping: Measured,
+}
+
+/// A quantity the settings may ask to measure from the swing.
+pub enum Measured {
+    /// Measured from this many seconds of the track.
+    Fitted(f32, f32),
+    /// Asked for, but the track didn't swing enough to measure it from.
+    Unmeasurable,
+    Kept,
+}
+
+pub struct WarmStart {
+    /// Seconds at the end of the track the circle is fitted to.
+    window: f32,
+    /// Seconds at the end the angular state is smoothed over.
+    smoothing: f32,
+    fit_gravity: bool,
+    fit_damping: bool,
+}
+
+impl WarmStart {
+    pub fn new() -> WarmStart {
+        WarmStart {
+            window: DEFAULT_WINDOW,
+            smoothing: DEFAULT_SMOOTHING,
+            fit_gravity: true,
+            fit_damping: false,
+        }
+    }
+
+    /// Reads `window = <seconds>`, `smoothing = <seconds>`, `gravity =
+    /// fit|keep` and `damping = fit|keep` from a `[warm_start]` section.
+    pub fn from_config(section: &Section) -> Result<WarmStart, String> {
+        let mut warm_start = WarmStart::new();
+
+        for (line_number, line) in &section.lines {
+            let error = |message: String| format!("line {}: {}", line_number, message);
+            let fit = |key: &str, value: &str| match value {
+                "fit" => Ok(true),
+                "keep" => Ok(false),
+                _ => Err(error(format!(
+                    "`{}` must be fit or keep, not `{}`",
+                    key, value
+                ))),
+            };
+            match key_value(line) {
+                Some(("window", value)) => {
+                    warm_start.window = parse_f32("window", value).map_err(error)?
+                }
+                Some(("smoothing", value)) => {
+                    warm_start.smoothing = parse_f32("smoothing", value).map_err(error)?
+                }
+                Some(("gravity", value)) => warm_start.fit_gravity = fit("gravity", value)?,
+                Some(("damping", value)) => warm_start.fit_damping = fit("damping", value)?,
+                _ => return Err(error(format!("unknown warm start setting `{}`", line))),
+            }
+        }
+        if !(warm_start.window > 0.0 && warm_start.smoothing > 0.0) {
+            return Err("`window` and `smoothing` must be more than 0 seconds".to_string());
+        }
+
+        Ok(warm_start)
+    }
+
+    /// Fits a pendulum to `track` that carries on from its end.
+    pub fn fit(&self, track: &Track) -> Result<Fit, String> {
+        let recent = track.last(self.window);
+        let positions: Vec<Vector> = recent.iter().map(|&(_, point)| point).collect();
+        let circle = fit_circle(&positions).ok_or(format!(
+            "the track's last {} s is too short or too straight to fit a circle to",
+            self.window
+        ))?;
+        let mut entry = PendulumEntry {
+            x: circle.centre.x,
+            y: circle.centre.y,
+            r: circle.radius,
+            ..PendulumEntry::default()
+        };
+
+        // The whole track's swing, about the same pivot.
+        let swing = angles(&track.points, circle.centre);
+        let estimate = sensor::estimate(&swing, entry.g);
+        let seconds = track.duration();
+        let gravity = match estimate {
+            _ if !self.fit_gravity => Measured::Kept,
+            Some(estimate) => {
+                // The period goes as one over the root of gravity, so the
+                // period under gravity 1 says what gravity gives the one seen.
+                let unit = Pendulum::from_entry(&PendulumEntry {
+                    g: 1.0,
+                    ..entry.clone()
+                });
+                entry.g = (unit.period_at(estimate.amplitude) * estimate.frequency).powi(2);
+                Measured::Fitted(entry.g, seconds)
+            }
+            None => Measured::Unmeasurable,
+        };
+        let damping = match estimate.and_then(|estimate| estimate.damping_ratio) {
+            _ if !self.fit_damping => Measured::Kept,
+            Some(zeta) => {
+                let zeta = zeta.max(0.0);
+                entry.damping_ratio = Some(zeta);
+                Measured::Fitted(zeta, seconds)
+            }
+            None => Measured::Unmeasurable,
+        };
+
+        let p = Pendulum::from_entry(&entry);
+        let per_second = p.natural_frequency() * STEPS_PER_SECOND;
+        let viscous = 2.0 * p.damping_ratio().unwrap_or(0.0) * per_second;
+        let (angle, angular_velocity, angle_rms) = end_state(
+            &angles(recent, circle.centre),
+            self.smoothing,
+            per_second * per_second,
+            viscous,
+        )
+        .ok_or(format!(
+            "too few points in the last {} s to smooth the swing over",
+            self.smoothing
+        ))?;
+        entry.angle = angle;
+        entry.angular_velocity = angular_velocity / STEPS_PER_SECOND;
+        // Symplectic Euler keeps the velocity half a step behind the angle,
+        // as leapfrog does, and carries on in step only if it's started so.
+        if entry.integrator == Scheme::SymplecticEuler {
+            let per_step = per_second / STEPS_PER_SECOND;
+            entry.angular_velocity += 0.5 * per_step * per_step * angle.sin();
+        }
+
+        Ok(Fit {
+            entry,
+            circle,
+            circle_points: recent.len(),
+            angle_rms,
+            angular_velocity,
+            gravity,
+            damping,
+        })
+    }
+}
+
+impl Fit {
+    /// What was fitted, and whether the track looked like a pendulum's.
+    pub fn report(&self, units: &Units) -> Vec<String> {
+        let circle = &self.circle;
+        let share = circle.rms / circle.radius;
+        let verdict = if share <= GOOD_FIT {
+            "a pendulum's arc"
+        } else if share <= POOR_FIT {
+            "roughly a pendulum's arc"
+        } else {
+            "not much like a pendulum's arc: check the track"
+        };
+        let mut lines = vec![
+            format!(
+                "Circle through the last {} points: pivot ({:.1}, {:.1}), length {}",
+                self.circle_points,
+                circle.centre.x,
+                circle.centre.y,
+                units.length(circle.radius)
+            ),
+            format!(
+                "RMS residual {} ({:.2}% of the length): {}",
+                units.length(circle.rms),
+                100.0 * share,
+                verdict
+            ),
+            format!(
+                "Starting at {:.4} rad, {:.4} rad/s (RMS {:.4} rad about the smoothing)",
+                self.entry.angle, self.angular_velocity, self.angle_rms
+            ),
+        ];
+        lines.push(match self.gravity {
+            Measured::Fitted(g, seconds) => format!(
+                "Gravity from {:.1} s of the swing: {:.4} ({:.2} m/s²)",
+                seconds,
+                g,
+                presets::real_gravity(g)
+            ),
+            Measured::Unmeasurable => {
+                "Gravity kept: the track doesn't swing enough to measure it".to_string()
+            }
+            Measured::Kept => format!("Gravity kept at {}", self.entry.g),
+        });
+        lines.push(match self.damping {
+            Measured::Fitted(zeta, seconds) => {
+                format!(
+                    "Damping ratio from {:.1} s of the swing: {:.4}",
+                    seconds, zeta
+                )
+            }
+            Measured::Unmeasurable => {
+                "Damping kept: the track doesn't swing enough to measure it".to_string()
+            }
+            Measured::Kept => "Damping kept".to_string(),
+        });
+        lines
+    }
+}
+
+/// The track, drawn behind the pendulum that took over from it.
+pub struct Trail {
+    points: Vec<Vector>,
+}
+
+impl Trail {
+    pub fn new(track: &Track) -> Trail {
+        Trail {
+            points: track.points.iter().map(|&(_, point)| point).collect(),
+        }
+    }
+
+    /// Fainter the further back it goes.
+    pub fn draw(&self, graphics: &mut dyn Renderer, view: &Viewport) {
+        let last = self.points.len().saturating_sub(1).max(1) as f32;
+        let points: Vec<Vector> = self
+            .points
+            .iter()
+            .map(|&point| {
+                let (x, y) = view.world_to_screen(point);
+                Vector::new(x, y)
+            })
+            .collect();
+        polyline::draw_shaded_polyline(graphics, &points, view.length(2.0), |i| {
+            let alpha = 0.05 + 0.4 * i as f32 / last;
+            Color::from_rgba(TRAIL.r(), TRAIL.g(), TRAIL.b(), alpha)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    const ZETA: f32 = 0.01;
+
+    fn on_circle(centre: Vector, radius: f32, angle: f32) -> Vector {
+        Vector::new(
+            centre.x + radius * angle.sin(),
+            centre.y + radius * angle.cos(),
+        )
+    }
+
+    /// A lightly damped pendulum's swing tracked at 30 frames a second for
+    /// `seconds`, each position off by up to `noise` either way, and the
+    /// pendulum as the track left it.
+    fn tracked(g: f32, seconds: f32, noise: f32) -> (Track, Pendulum) {
+        let mut p = Pendulum::new(400.0, 60.0, 200.0);
+        p.g = g;
+        p.zeta = Some(ZETA);
+        p.angle = 0.8;
+        let mut rng = Rng::new(7);
+        let mut points = Vec::new();
+        let frames = (seconds * 30.0) as usize;
+        for frame in 0..=frames {
+            if frame > 0 {
+                p.update();
+                p.update();
+            }
+            let jitter = Vector::new(rng.range(-noise, noise), rng.range(-noise, noise));
+            points.push((
+                p.time / STEPS_PER_SECOND,
+                Vector::new(p.position.x + jitter.x, p.position.y + jitter.y),
+            ));
+        }
+        (Track { points }, p)
+    }
+
+    #[test]
+    fn a_circle_through_points_on_an_arc() {
+        let centre = Vector::new(400.0, 100.0);
+        let exact: Vec<Vector> = (0..=20)
+            .map(|i| on_circle(centre, 200.0, -0.5 + 0.05 * i as f32))
+            .collect();
+        let circle = fit_circle(&exact).unwrap();
+        assert!((circle.centre.x - 400.0).abs() < 1e-2 && (circle.centre.y - 100.0).abs() < 1e-2);
+        assert!((circle.radius - 200.0).abs() < 1e-2 && circle.rms < 1e-2);
+
+        // Noise on a short arc, where the algebraic fit alone comes out
+        // small.
+        let mut rng = Rng::new(3);
+        let noisy: Vec<Vector> = (0..60)
+            .map(|i| {
+                let point = on_circle(centre, 200.0, -0.4 + 0.8 * i as f32 / 59.0);
+                Vector::new(
+                    point.x + rng.range(-2.0, 2.0),
+                    point.y + rng.range(-2.0, 2.0),
+                )
+            })
+            .collect();
+        let circle = fit_circle(&noisy).unwrap();
+        assert!(
+            (circle.radius - 200.0).abs() < 0.03 * 200.0,
+            "radius {}",
+            circle.radius
+        );
+        assert!(
+            (circle.centre.x - 400.0).abs() < 3.0,
+            "centre x {}",
+            circle.centre.x
+        );
+        assert!(
+            (circle.centre.y - 100.0).abs() < 0.03 * 200.0,
+            "centre y {}",
+            circle.centre.y
+        );
+        // Uniform noise of ±2 either way is about 1.15 off the circle.
+        assert!(circle.rms > 0.8 && circle.rms < 1.5, "rms {}", circle.rms);
+
+        let line: Vec<Vector> = (0..10)
+            .map(|i| Vector::new(i as f32, 2.0 * i as f32))
+            .collect();
+        assert!(fit_circle(&line).is_none());
+        assert!(fit_circle(&exact[..2]).is_none());
+    }
+
+    #[test]
+    fn angles_hang_from_zero_and_keep_counting_round() {
+        let pivot = Vector::new(0.0, 0.0);
+        let points: Vec<(f32, Vector)> = (0..40)
+            .map(|i| (i as f32, on_circle(pivot, 10.0, 0.3 * i as f32)))
+            .collect();
+        for (i, (t, angle)) in angles(&points, pivot).into_iter().enumerate() {
+            assert_eq!(t, i as f32);
+            assert!((angle - 0.3 * i as f32).abs() < 1e-4, "{} at {}", angle, i);
+        }
+    }
+
+    #[test]
+    fn the_end_state_is_smoothed_out_of_the_noise() {
+        let (amplitude, omega) = (0.5f32, 3.0f32);
+        let mut rng = Rng::new(11);
+        let samples: Vec<(f32, f32)> = (0..=90)
+            .map(|i| {
+                let t = i as f32 / 30.0;
+                (t, amplitude * (omega * t).cos() + rng.range(-0.01, 0.01))
+            })
+            .collect();
+        let end = 3.0;
+        // Small enough that sin θ is near θ, so the cosine is the swing.
+        let (angle, rate, rms) = end_state(&samples, 0.4, omega * omega, 0.0).unwrap();
+        let swing = |t: f32| amplitude * (omega * t).cos();
+        let speed = -amplitude * omega * (omega * end).sin();
+        assert!(
+            (angle - swing(end)).abs() < 0.01,
+            "angle {} for {}",
+            angle,
+            swing(end)
+        );
+        assert!(
+            (rate - speed).abs() < 0.1 * amplitude * omega,
+            "rate {} for {}",
+            rate,
+            speed
+        );
+        assert!(rms < 0.01);
+        // Differencing the last two samples is much worse.
+        let (a, b) = (samples[89].1, samples[90].1);
+        let differenced = (b - a) * 30.0;
+        assert!((differenced - speed).abs() > (rate - speed).abs());
+
+        assert!(end_state(&samples[..2], 0.4, 1.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn a_tracked_pendulum_is_carried_on_from_its_end() {
+        let (track, p) = tracked(0.8, 10.0, 1.0);
+        let fit = WarmStart::new().fit(&track).unwrap();
+        let entry = &fit.entry;
+        assert!(
+            (entry.x - 400.0).abs() < 2.0 && (entry.y - 60.0).abs() < 4.0,
+            "pivot ({}, {})",
+            entry.x,
+            entry.y
+        );
+        assert!((entry.r - 200.0).abs() < 4.0, "length {}", entry.r);
+        match fit.gravity {
+            Measured::Fitted(g, seconds) => {
+                assert!((g - 0.8).abs() < 0.03 * 0.8, "gravity {}", g);
+                assert!((seconds - 10.0).abs() < 0.1);
+            }
+            _ => panic!("gravity wasn't fitted"),
+        }
+        assert!(matches!(fit.damping, Measured::Kept));
+        assert!(
+            (entry.angle - p.angle).abs() < 0.02,
+            "angle {} for {}",
+            entry.angle,
+            p.angle
+        );
+        let scale = p.angular_velocity.abs().max(0.005);
+        assert!(
+            (entry.angular_velocity - p.angular_velocity).abs() < 0.15 * scale,
+            "angular velocity {} for {}",
+            entry.angular_velocity,
+            p.angular_velocity
+        );
+        let report = fit.report(&Units::default());
+        assert!(report[1].ends_with(": a pendulum's arc"), "{}", report[1]);
+        assert_eq!(report[4], "Damping kept");
+
+        // Damping too, when asked.
+        let config = crate::config::Config::parse("[warm_start]\ndamping = fit\n").unwrap();
+        let warm_start = WarmStart::from_config(config.section("warm_start").unwrap()).unwrap();
+        let fit = warm_start.fit(&track).unwrap();
+        match fit.damping {
+            Measured::Fitted(zeta, _) => {
+                assert!((zeta - ZETA).abs() < 0.2 * ZETA, "damping {}", zeta)
+            }
+            _ => panic!("damping wasn't fitted"),
+        }
+    }
+
+    #[test]
+    fn too_little_of_a_swing_keeps_gravity() {
+        let (track, _) = tracked(0.5, 0.5, 0.0);
+        let fit = WarmStart::new().fit(&track).unwrap();
+        assert!(matches!(fit.gravity, Measured::Unmeasurable));
+        assert!(fit.entry.g == PendulumEntry::default().g);
+
+        let straight = Track {
+            points: (0..30)
+                .map(|i| (i as f32, Vector::new(i as f32, 0.0)))
+                .collect(),
+        };
+        assert_eq!(
+            WarmStart::new().fit(&straight).err().unwrap(),
+            "the track's last 1 s is too short or too straight to fit a circle to"
+        );
+    }
+
+    #[test]
+    fn reads_a_track() {
+        let track =
+            Track::parse("t,x,y\n# tracked\n0, 1, 2\n0.1 1 3\n\n0.2,1,4\n0.3,1,5\n0.4,1,6\n")
+                .unwrap();
+        assert_eq!(track.points.len(), 5);
+        assert!(track.points[1].0 == 0.1 && track.points[1].1.y == 3.0);
+        for (text, error) in [
+            (
+                "0,1,2\n0,1,3\n",
+                "line 2: time 0 doesn't come after the line before",
+            ),
+            ("0,1,2\n1,2\n", "line 2: expected three numbers, found 2"),
+            ("0,1,2\nx,y,z\n", "line 2: expected `t, x, y`"),
+            ("0,1,2\n1,1,2\n", "only 2 points, too few to fit a swing to"),
+        ] {
+            assert_eq!(Track::parse(text).err().unwrap(), error);
+        }
+    }
+}
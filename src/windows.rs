@@ -0,0 +1,179 @@
+//! Side-by-side comparison in separate windows, started with `--windows N`.
+//!
+//! Threading model: speedy2d drives each window with a winit event loop,
+//! and winit allows one event loop per process, on its main thread on most
+//! platforms. So the windows can't each be given a thread. Instead the
+//! process started with `--windows N` becomes a supervisor that opens no
+//! window itself: it starts N copies of the program, passing each the same
+//! arguments plus `--window <i>/<N>`, and waits on them. Each copy is the
+//! ordinary app with its own event loop on its own main thread and its own
+//! physics, so the pendulums share nothing and nothing needs locking. As
+//! soon as any window closes, the supervisor stops the rest, so closing one
+//! closes them all.
+//!
+//! Stopping a window is asking it: the supervisor holds each window's stdin
+//! and closes it, and the window, watching for that, ends its event loop
+//! the way closing it would, writing out what it has queued on the way,
+//! such as results for the history store. Only a window still open after
+//! `GRACE` is killed.
+
+use std::env;
+use std::io::{self, Read};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use speedy2d::window::UserEventSender;
+
+use crate::model::{ModelEntry, PendulumEntry};
+use crate::scene::Scene;
+
+/// How often the supervisor checks whether a window has closed.
+const POLL: Duration = Duration::from_millis(100);
+/// How long the windows have to close once asked, before they're killed.
+const GRACE: Duration = Duration::from_secs(5);
+/// Gap between windows laid out side by side.
+const GAP: u32 = 10;
+
+/// Which of the comparison windows this process is, from `--window i/N`.
+#[derive(Copy, Clone)]
+pub struct WindowSlot {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl WindowSlot {
+    pub fn parse(text: &str) -> Result<WindowSlot, String> {
+        let invalid = || format!("expected `--window <index>/<count>`, found `{}`", text);
+        let (index, count) = text.split_once('/').ok_or_else(invalid)?;
+        let (index, count): (usize, usize) = (
+            index.parse().map_err(|_| invalid())?,
+            count.parse().map_err(|_| invalid())?,
+        );
+        if index >= count {
+            return Err(invalid());
+        }
+        Ok(WindowSlot { index, count })
+    }
+
+    /// This window's share of the comparison: the model at this index in
+    /// the configured scene, or without one, a pendulum whose rod gets
+    /// longer from each window to the next.
+    pub fn scene(&self, scene: &Scene, configured: bool) -> Scene {
+        let entries = scene.initial_entries();
+        let entry = if configured {
+            entries[self.index % entries.len()].clone()
+        } else {
+            let fraction = self.index as f32 / (self.count - 1).max(1) as f32;
+            ModelEntry::Pendulum(PendulumEntry {
+                r: 120.0 + 160.0 * fraction,
+                ..PendulumEntry::default()
+            })
+        };
+        Scene::new(vec![entry])
+    }
+
+    /// Where the window goes: tiled left to right, wrapping after four.
+    pub fn position(&self, size: (u32, u32)) -> (i32, i32) {
+        let (column, row) = (self.index % 4, self.index / 4);
+        (
+            (column as u32 * (size.0 + GAP)) as i32,
+            (row as u32 * (size.1 + GAP)) as i32,
+        )
+    }
+}
+
+/// Starts `count` windows and waits until the first is closed, then closes
+/// the others. Returns the exit code to finish with.
+pub fn supervise(count: usize, args: &[String]) -> i32 {
+    let program = match env::current_exe() {
+        Ok(program) => program,
+        Err(e) => {
+            log!("--windows: can't find the program to start: {}", e);
+            return 1;
+        }
+    };
+
+    let mut children: Vec<Child> = Vec::with_capacity(count);
+    for index in 0..count {
+        match Command::new(&program)
+            .args(args)
+            .arg("--window")
+            .arg(format!("{}/{}", index, count))
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => children.push(child),
+            Err(e) => {
+                log!("--windows: could not start window {}: {}", index + 1, e);
+                stop(&mut children);
+                return 1;
+            }
+        }
+    }
+
+    loop {
+        for child in &mut children {
+            if let Ok(Some(status)) = child.try_wait() {
+                stop(&mut children);
+                return status.code().unwrap_or(0);
+            }
+        }
+        thread::sleep(POLL);
+    }
+}
+
+/// Asks the windows to close, and kills those that haven't within `GRACE`.
+fn stop(children: &mut [Child]) {
+    for child in children.iter_mut() {
+        drop(child.stdin.take());
+    }
+    let deadline = Instant::now() + GRACE;
+    for child in children.iter_mut() {
+        while matches!(child.try_wait(), Ok(None)) {
+            if Instant::now() >= deadline {
+                log!("--windows: a window didn't close when asked, killing it");
+                let _ = child.kill();
+                break;
+            }
+            thread::sleep(POLL);
+        }
+        let _ = child.wait();
+    }
+}
+
+/// In a comparison window, sends `close` once the supervisor has closed
+/// stdin, or gone. Nothing is ever written to it, so reading it to the end
+/// is waiting for that.
+pub fn watch_supervisor(close: UserEventSender<()>) {
+    thread::spawn(move || {
+        let mut buffer = [0; 64];
+        while io::stdin().read(&mut buffer).is_ok_and(|read| read > 0) {}
+        let _ = close.send_event(());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn stopping_asks_through_stdin_before_killing() {
+        // `cat` stands in for a window: it runs until its stdin closes.
+        let start = || {
+            Command::new("cat")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        };
+        let mut children = vec![start(), start()];
+        let started = Instant::now();
+        stop(&mut children);
+        assert!(started.elapsed() < GRACE);
+        for child in &mut children {
+            assert!(child.wait().unwrap().success(), "it was killed");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! The determinism audit run end to end: dumps made by separate runs agree
+//! bit for bit, and, built with the `deterministic` feature, so does one
+//! made on another machine. `data/determinism.dump` is the first minute of
+//! `--determinism-dump` from an x86-64 Linux build with the feature; a
+//! change that means to alter the physics writes it again.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+
+fn pendulum(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-pendulum"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn check(dump: &Path) -> (bool, String) {
+    let output = pendulum(&["--determinism-check", dump.to_str().unwrap()]);
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn separate_runs_step_alike_to_the_last_bit() {
+    let dump: PathBuf =
+        env::temp_dir().join(format!("pendulum-determinism-{}.dump", process::id()));
+    let output = pendulum(&["--determinism-dump", dump.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let (matched, report) = check(&dump);
+    assert!(matched, "{}", report);
+    assert!(
+        report.contains("Every bit matches, over 301 samples"),
+        "{}",
+        report
+    );
+
+    // And a run that has drifted by a last place is caught where it does.
+    let text = fs::read_to_string(&dump).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let (step, rest) = lines[10].split_once(' ').unwrap();
+    let first = u64::from_str_radix(&rest[..16], 16).unwrap() ^ 1;
+    let tampered = format!("{} {:016x}{}", step, first, &rest[16..]);
+    let mut lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    lines[10] = tampered;
+    fs::write(&dump, lines.join("\n") + "\n").unwrap();
+    let (matched, report) = check(&dump);
+    assert!(!matched);
+    assert!(
+        report.contains(&format!("First differs at step {}", step)),
+        "{}",
+        report
+    );
+    let _ = fs::remove_file(&dump);
+}
+
+#[cfg(feature = "deterministic")]
+#[test]
+fn the_portable_functions_step_as_the_reference_machine_did() {
+    let dump = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/determinism.dump");
+    let (matched, report) = check(&dump);
+    assert!(matched, "{}", report);
+}
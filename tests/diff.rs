@@ -0,0 +1,88 @@
+//! Compares the saved sessions in `tests/data/diff` with `--diff`, one pair
+//! for each kind of difference, and checks what it prints and how it exits.
+
+use std::path::Path;
+use std::process::Command;
+
+/// `--diff` of the base session against `other`, with `extra` arguments:
+/// the exit code and the lines printed.
+fn diff(other: &str, extra: &[&str]) -> (i32, Vec<String>) {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/diff");
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-pendulum"))
+        .arg("--diff")
+        .arg(data.join("base.cfg"))
+        .arg(data.join(other))
+        .args(extra)
+        .output()
+        .unwrap();
+    let lines = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    (output.status.code().unwrap(), lines)
+}
+
+#[test]
+fn the_same_session_written_another_way_has_no_differences() {
+    let (code, lines) = diff("same.cfg", &["--all"]);
+    assert_eq!(code, 0);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with("same.cfg are the same"), "{:?}", lines);
+}
+
+#[test]
+fn a_difference_only_in_state_exits_with_1() {
+    let (code, lines) = diff("state.cfg", &[]);
+    assert_eq!(code, 1);
+    assert!(lines[0].ends_with("3 differences, the most important state"));
+    assert_eq!(
+        lines[1..],
+        [
+            "state pendulum \"left\" angle 0.5 | 0.8",
+            "state pendulum \"left\" angular_velocity 0 | -0.01",
+            "state chain 3 angles 1,1,1 | 1,1,1.2",
+        ]
+    );
+}
+
+#[test]
+fn a_difference_in_configuration_exits_with_3() {
+    let (code, lines) = diff("configuration.cfg", &[]);
+    assert_eq!(code, 3);
+    assert!(lines[0].ends_with("6 differences, the most important configuration"));
+    let places: Vec<&str> = lines[1..]
+        .iter()
+        .map(|line| line.split(" | ").next().unwrap())
+        .collect();
+    assert_eq!(
+        places[..3],
+        [
+            "configuration pendulum \"right\" g 0.5",
+            "configuration pendulum 4 -",
+            "configuration walls right 700",
+        ]
+    );
+    assert!(places.contains(&"configuration obstacles entry 1 -"));
+    // The state difference comes after, as the less important.
+    assert_eq!(places.last(), Some(&"state pendulum \"left\" angle 0.5"));
+}
+
+#[test]
+fn cosmetic_differences_are_shown_only_when_asked_for() {
+    let (code, lines) = diff("cosmetic.cfg", &[]);
+    assert_eq!(code, 0);
+    assert!(lines[0].ends_with("are the same"));
+
+    let (code, lines) = diff("cosmetic.cfg", &["--all"]);
+    assert_eq!(code, 0);
+    assert!(lines[0].ends_with("1 difference, the most important cosmetic"));
+    assert_eq!(lines[1], "cosmetic report file lab.md | lab-2.md");
+}
+
+#[test]
+fn a_session_that_doesnt_read_exits_with_2() {
+    let (code, lines) = diff("missing.cfg", &[]);
+    assert_eq!(code, 2);
+    assert!(lines.is_empty());
+}
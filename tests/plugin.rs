@@ -0,0 +1,149 @@
+//! Builds the sample plugin, loads it into a headless instance and steps it,
+//! and checks plugins built against another ABI are refused.
+
+#![cfg(feature = "plugin")]
+
+use std::env;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, ChildStdin, ChildStdout, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn skeleton() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/plugin-skeleton")
+}
+
+/// A directory of the test's own, emptied.
+fn scratch(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("pendulum-plugin-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Builds the plugin crate at `manifest` into `target`, returning the
+/// library.
+fn build(manifest: &Path, target: &Path) -> PathBuf {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--quiet", "--manifest-path"])
+        .arg(manifest)
+        .arg("--target-dir")
+        .arg(target)
+        .status()
+        .unwrap();
+    assert!(status.success(), "building {} failed", manifest.display());
+    target
+        .join("debug")
+        .join(format!("{}plugin_skeleton{}", DLL_PREFIX, DLL_SUFFIX))
+}
+
+/// The skeleton built in `directory` with `from` in its source replaced by
+/// `to`.
+fn build_altered(directory: &Path, from: &str, to: &str) -> PathBuf {
+    fs::create_dir_all(directory.join("src")).unwrap();
+    fs::copy(skeleton().join("Cargo.toml"), directory.join("Cargo.toml")).unwrap();
+    let source = fs::read_to_string(skeleton().join("src/lib.rs")).unwrap();
+    assert!(source.contains(from), "the skeleton has no `{}`", from);
+    fs::write(directory.join("src/lib.rs"), source.replace(from, to)).unwrap();
+    build(&directory.join("Cargo.toml"), &directory.join("target"))
+}
+
+/// Runs a headless instance with the plugin at `library` and nothing to
+/// read, as it starts.
+fn start_with(library: &Path, directory: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-pendulum"))
+        .args(["--stdin-control", "--headless", "--plugin"])
+        .arg(library)
+        .current_dir(directory)
+        .env("XDG_DATA_HOME", directory)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+/// The number in the `Time: <seconds> s` line of an exported SVG.
+fn plugin_time(svg: &str) -> f64 {
+    let rest = &svg[svg.find("Time: ").expect(svg) + 6..];
+    rest[..rest.find(" s").unwrap()].parse().unwrap()
+}
+
+/// Asks for the scene as an SVG and reads it back.
+fn export(stdin: &mut ChildStdin, stdout: &mut BufReader<ChildStdout>, directory: &Path) -> String {
+    writeln!(stdin, "svg").unwrap();
+    let mut answer = String::new();
+    stdout.read_line(&mut answer).unwrap();
+    assert!(answer.contains("\"ok\":true"), "{}", answer);
+    fs::read_to_string(directory.join("scene.svg")).unwrap()
+}
+
+#[test]
+fn the_sample_plugin_loads_and_steps() {
+    let directory = scratch("steps");
+    let library = build(&skeleton().join("Cargo.toml"), &directory.join("target"));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust-pendulum"))
+        .args(["--stdin-control", "--headless", "--plugin"])
+        .arg(&library)
+        .current_dir(&directory)
+        .env("XDG_DATA_HOME", &directory)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // The scene names no models, so it's one of the plugin's. Its HUD lines
+    // go into the SVG with the drawing.
+    let first = export(&mut stdin, &mut stdout, &directory);
+    assert!(first.contains("Plugin: oscillator"), "{}", first);
+    let started = Instant::now();
+    let svg = loop {
+        let svg = export(&mut stdin, &mut stdout, &directory);
+        if plugin_time(&svg) >= 0.5 {
+            break svg;
+        }
+        assert!(started.elapsed() < Duration::from_secs(20), "{}", svg);
+        thread::sleep(Duration::from_millis(50));
+    };
+    assert!(!svg.contains("Disabled"), "{}", svg);
+    assert_ne!(first, svg);
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn a_plugin_built_for_another_abi_version_is_refused() {
+    let directory = scratch("version");
+    let library = build_altered(
+        &directory,
+        "const ABI_VERSION: u32 = 1;",
+        "const ABI_VERSION: u32 = 2;",
+    );
+    let output = start_with(&library, &directory);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr);
+    assert!(stderr.contains("built for plugin ABI 2"), "{}", stderr);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn a_plugin_whose_vtable_is_another_size_is_refused() {
+    let directory = scratch("size");
+    let library = build_altered(
+        &directory,
+        "size: std::mem::size_of::<PluginVtable>() as u32,",
+        "size: std::mem::size_of::<PluginVtable>() as u32 + 8,",
+    );
+    let output = start_with(&library, &directory);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr);
+    assert!(stderr.contains("built for plugin ABI 1"), "{}", stderr);
+    assert!(stderr.contains("expected 1"), "{}", stderr);
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,149 @@
+//! Drives a headless instance through a pipe, the way a notebook would, and
+//! reads back what it answers.
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{self, Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// A headless instance started in a directory of its own, so it reads no
+/// config and leaves the user's history alone.
+fn start(name: &str) -> (Child, PathBuf) {
+    let directory = env::temp_dir().join(format!("pendulum-remote-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_rust-pendulum"))
+        .args(["--stdin-control", "--headless"])
+        .current_dir(&directory)
+        .env("XDG_DATA_HOME", &directory)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    (child, directory)
+}
+
+/// Writes all of `input` and closes stdin, then reads every answer.
+fn run(name: &str, input: &str) -> Vec<String> {
+    let (mut child, directory) = start(name);
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let _ = fs::remove_dir_all(&directory);
+    assert!(output.status.success(), "ended with {}", output.status);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The `line` field of an answer.
+fn line_number(answer: &str) -> usize {
+    let rest = answer.strip_prefix("{\"line\":").expect(answer);
+    rest[..rest.find(',').unwrap()].parse().unwrap()
+}
+
+/// The `value` field of an answer to a query.
+fn value(answer: &str) -> f32 {
+    let rest = &answer[answer.find("\"value\":").expect(answer) + 8..];
+    rest[..rest.find('}').unwrap()].parse().unwrap()
+}
+
+/// Sends one command and waits for its answer.
+fn ask(stdin: &mut ChildStdin, stdout: &mut BufReader<ChildStdout>, command: &str) -> String {
+    writeln!(stdin, "{}", command).unwrap();
+    let mut answer = String::new();
+    stdout.read_line(&mut answer).unwrap();
+    answer.trim_end().to_string()
+}
+
+#[test]
+fn each_command_is_answered_in_order_and_stdin_ending_stops_the_run() {
+    let answers = run(
+        "script",
+        "set g 9.81\nquery g\nset q 1\n\nbogus\nquery colour\nimpulse 0.5\n",
+    );
+    assert_eq!(
+        answers,
+        vec![
+            r#"{"line":1,"command":"set g 9.81","ok":true,"reply":["g = 9.81"]}"#,
+            r#"{"line":2,"command":"query g","ok":true,"reply":["g = 9.81"],"value":9.81}"#,
+            r#"{"line":3,"command":"set q 1","ok":false,"error":"Unknown setting `q`"}"#,
+            r#"{"line":5,"command":"bogus","ok":false,"error":"Unknown command `bogus`"}"#,
+            r#"{"line":6,"command":"query colour","ok":false,"error":"Unknown quantity `colour`"}"#,
+            r#"{"line":7,"command":"impulse 0.5","ok":true,"reply":["Kicked by 0.5 rad/s"]}"#,
+        ]
+    );
+}
+
+#[test]
+fn only_finite_numbers_are_set() {
+    let answers = run("finite", "set g NaN\nset g inf\nimpulse -inf\nquery g\n");
+    assert_eq!(
+        answers,
+        vec![
+            r#"{"line":1,"command":"set g NaN","ok":false,"error":"Not a number: `NaN`"}"#,
+            r#"{"line":2,"command":"set g inf","ok":false,"error":"Not a number: `inf`"}"#,
+            r#"{"line":3,"command":"impulse -inf","ok":false,"error":"Not an angular velocity: `-inf`"}"#,
+            r#"{"line":4,"command":"query g","ok":true,"reply":["g = 9.81"],"value":9.81}"#,
+        ]
+    );
+}
+
+#[test]
+fn the_swing_steps_between_commands_and_stops_while_paused() {
+    let (mut child, directory) = start("steps");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut send = |command: &str| ask(&mut stdin, &mut stdout, command);
+
+    let before = value(&send("query time"));
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let running = value(&send("query time"));
+    assert!(running > before, "time went from {} to {}", before, running);
+
+    assert!(send("pause").contains("\"ok\":true"));
+    let paused = value(&send("query time"));
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert_eq!(value(&send("query time")), paused);
+
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn a_flood_is_answered_once_per_line_in_order() {
+    let input: String = (0..300)
+        .map(|i| format!("set r {}\n", 1.0 + i as f32 / 100.0))
+        .collect();
+    let answers = run("flood", &input);
+    assert_eq!(answers.len(), 300);
+    for (i, answer) in answers.iter().enumerate() {
+        assert_eq!(line_number(answer), i + 1);
+        assert!(
+            answer.contains("\"ok\":true") || answer.contains("\"error\":\"dropped: "),
+            "{}",
+            answer
+        );
+    }
+}
+
+#[test]
+fn answering_into_a_closed_stdout_still_runs_to_the_end() {
+    let (mut child, directory) = start("closed");
+    drop(child.stdout.take());
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"set g 1\nquery period\n").unwrap();
+    drop(stdin);
+    let status = child.wait().unwrap();
+    let _ = fs::remove_dir_all(&directory);
+    assert!(status.success(), "ended with {}", status);
+}